@@ -0,0 +1,53 @@
+//! Whole exchanges through the server and client, over an in-memory transport
+
+use std::hint::black_box;
+
+use bytes::Bytes;
+use carbon_http_server::{
+    Router, RouterError,
+    http::{
+        request::{Request, RequestBuilder},
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+    testing::TestClient,
+};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+struct Echo;
+
+impl Router for Echo {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let body = request.body.collect(None).await?;
+        Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+            .body(body)
+            .build())
+    }
+}
+
+fn echo(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let client = TestClient::new(Echo);
+    let mut conn = runtime.block_on(async { client.connect() });
+    let mut group = c.benchmark_group("echo");
+    for (name, body) in [("empty_body", &b""[..]), ("body_4k", &[b'a'; 4096])] {
+        group.throughput(Throughput::Bytes(2 * body.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let request = RequestBuilder::post("/echo")
+                        .body(Bytes::from_static(body))
+                        .build();
+                    let response = conn.send(request).await.unwrap();
+                    black_box(response);
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, echo);
+criterion_main!(benches);
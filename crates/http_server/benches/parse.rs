@@ -0,0 +1,54 @@
+//! Request parsing throughput, for a minimal request and a typical browser request
+
+use std::hint::black_box;
+
+use carbon_http_server::http::parser::Parser;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+const MINIMAL: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+
+const BROWSER: &[u8] = b"GET /static/app.js?v=3 HTTP/1.1\r\n\
+Host: www.example.com\r\n\
+User-Agent: Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0\r\n\
+Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
+Accept-Language: en-US,en;q=0.5\r\n\
+Accept-Encoding: gzip, deflate, br\r\n\
+Referer: https://www.example.com/\r\n\
+Cookie: session=0123456789abcdef; theme=dark\r\n\
+Connection: keep-alive\r\n\
+Upgrade-Insecure-Requests: 1\r\n\
+Sec-Fetch-Dest: script\r\n\
+Sec-Fetch-Mode: no-cors\r\n\
+Sec-Fetch-Site: same-origin\r\n\
+X-Request-Id: 5f2b8c1e\r\n\
+\r\n";
+
+/// How many requests are pipelined on one connection per iteration
+const PIPELINED: usize = 64;
+
+fn parse(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("parse");
+    for (name, request) in [("minimal_request", MINIMAL), ("browser_request", BROWSER)] {
+        let input = request.repeat(PIPELINED);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                runtime.block_on(async {
+                    let mut parser = Parser::new(&input[..]);
+                    for _ in 0..PIPELINED {
+                        let request = parser.parse_request().await.unwrap();
+                        black_box(request);
+                    }
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,61 @@
+//! Serializing response heads and bodies with the [`Sender`]
+
+use std::hint::black_box;
+
+use bytes::Bytes;
+use carbon_http_server::http::{
+    HttpVersion,
+    method::Method,
+    parser::Sender,
+    response::{Response, ResponseBuilder, StatusCode},
+};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+
+fn response(body: &'static [u8]) -> Response {
+    let mut builder = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK);
+    for (name, value) in [
+        (&b"Content-Type"[..], &b"text/html; charset=utf-8"[..]),
+        (b"Cache-Control", b"public, max-age=3600"),
+        (b"ETag", b"\"5f2b8c1e\""),
+        (b"Vary", b"Accept-Encoding"),
+        (b"Set-Cookie", b"session=0123456789abcdef; Path=/; HttpOnly"),
+        (b"Set-Cookie", b"theme=dark; Path=/"),
+        (b"X-Request-Id", b"5f2b8c1e"),
+    ] {
+        builder = builder.add_header(&Bytes::from_static(name), Bytes::from_static(value));
+    }
+    builder.body(Bytes::from_static(body)).build()
+}
+
+fn serialize(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut out = Vec::with_capacity(64 * 1024);
+    let send = |out: &mut Vec<u8>, body| {
+        out.clear();
+        runtime.block_on(async {
+            let mut sender = Sender::new(out);
+            sender
+                .send_response(response(body), &Method::GET, HttpVersion::HTTP_1_1)
+                .await
+                .unwrap();
+        });
+    };
+    let mut group = c.benchmark_group("serialize");
+    for (name, body) in [("headers_only", &b""[..]), ("small_body", &[b'a'; 512])] {
+        send(&mut out, body);
+        group.throughput(Throughput::Bytes(out.len() as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                send(&mut out, body);
+                black_box(&out);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, serialize);
+criterion_main!(benches);
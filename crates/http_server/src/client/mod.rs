@@ -0,0 +1,430 @@
+//! An HTTP/1.1 client, built on the same [`Parser`] and [`Sender`] as the server
+//!
+//! A [`Client`] holds the limits and timeouts, and opens [`ClientConnection`]s, which send
+//! requests one at a time and read their responses in full
+
+#[cfg(feature = "tls")]
+mod tls;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadHalf, WriteHalf},
+    net::TcpStream,
+    time::Instant,
+};
+
+use crate::clock::{self, SharedClock, TokioClock};
+use crate::http::{
+    Body, BodyError, BodyStream, HttpVersion,
+    header::{ConnectionOptions, HeaderField, Host, HostWithPort},
+    parser::{BodyFraming, BodyLimits, HeadLimits, HttpParseError, Parser, Sender},
+    request::Request,
+    response::{Response, StatusCode},
+    uri::{IpLiteral, UriHost},
+};
+#[cfg(feature = "tls")]
+use crate::tls::HandshakeFailure;
+#[cfg(feature = "webpki-roots")]
+pub use tls::webpki_roots;
+#[cfg(feature = "tls")]
+pub use tls::{
+    CertificatePin, ClientConfig, RootCertStore, TlsConnector, VerifierBuilderError,
+    certificate_pin,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// The authority is not a host with an optional port
+    #[error("invalid authority")]
+    InvalidAuthority,
+    #[error("failed to connect: {0}")]
+    Connect(std::io::Error),
+    #[cfg(feature = "tls")]
+    #[error("TLS handshake failed: {0:?}")]
+    Handshake(HandshakeFailure),
+    /// [`Client::connect_tls`] was called without a connector set with [`Client::with_tls`]
+    #[cfg(feature = "tls")]
+    #[error("TLS is not configured")]
+    TlsNotConfigured,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Parse(#[from] HttpParseError),
+    #[error(transparent)]
+    Body(#[from] BodyError),
+    #[error("timed out waiting for the response")]
+    TimedOut,
+    /// The connection can't be used for another request, as either side closed it, or an
+    /// earlier exchange failed
+    #[error("the connection is closed")]
+    Closed,
+}
+
+/// Opens connections, and holds the settings they use
+#[derive(Debug, Clone)]
+pub struct Client {
+    timeout: Duration,
+    head_limits: HeadLimits,
+    body_limits: BodyLimits,
+    clock: SharedClock,
+    #[cfg(feature = "tls")]
+    tls: Option<TlsConnector>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Client {
+    /// A client with the limits of a default server, and a 30 second timeout per request
+    pub fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            head_limits: HeadLimits::default(),
+            body_limits: BodyLimits::default(),
+            clock: TokioClock::shared(),
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+
+    /// How long connecting, and each exchange from sending the request to receiving the whole
+    /// response, may take
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_head_limits(mut self, head_limits: HeadLimits) -> Self {
+        self.head_limits = head_limits;
+        self
+    }
+
+    pub fn with_body_limits(mut self, body_limits: BodyLimits) -> Self {
+        self.body_limits = body_limits;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// How HTTPS connections are made, see [`Self::connect_tls`]
+    #[cfg(feature = "tls")]
+    pub fn with_tls(mut self, tls: TlsConnector) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Connects to `authority`, such as `example.com:8080`, port 80 if it names none
+    /// The authority is also sent as the Host of requests which don't have one
+    pub async fn connect(
+        &self,
+        authority: &str,
+    ) -> Result<ClientConnection<TcpStream>, ClientError> {
+        let deadline = self.clock.now() + self.timeout;
+        let (_, stream) = self.connect_tcp(authority, 80, deadline).await?;
+        Ok(self.handshake(stream, Bytes::copy_from_slice(authority.as_bytes())))
+    }
+
+    /// Connects to `authority` over TLS, port 443 if it names none, its host is sent as the
+    /// SNI server name, and the certificate of the server must be valid for it
+    /// Connecting and the TLS handshake share the timeout of the client
+    /// Fails with [`ClientError::TlsNotConfigured`] if no connector was set with
+    /// [`Self::with_tls`]
+    #[cfg(feature = "tls")]
+    pub async fn connect_tls(
+        &self,
+        authority: &str,
+    ) -> Result<ClientConnection<tokio_rustls::client::TlsStream<TcpStream>>, ClientError> {
+        use tokio_rustls::rustls::pki_types::ServerName;
+
+        let tls = self.tls.as_ref().ok_or(ClientError::TlsNotConfigured)?;
+        let deadline = self.clock.now() + self.timeout;
+        let (host, stream) = self.connect_tcp(authority, 443, deadline).await?;
+        let server_name = match host {
+            UriHost::Ipv4(addr) => ServerName::from(std::net::IpAddr::from(addr)),
+            UriHost::IpLiteral(IpLiteral::Ipv6(addr)) => {
+                ServerName::from(std::net::IpAddr::from(addr))
+            }
+            UriHost::RegName(name) => {
+                ServerName::try_from(name.to_string()).map_err(|_| ClientError::InvalidAuthority)?
+            }
+            UriHost::IpLiteral(IpLiteral::IpvFuture(_)) => {
+                return Err(ClientError::InvalidAuthority);
+            }
+        };
+        let stream = clock::timeout_at(&*self.clock, deadline, tls.connect(server_name, stream))
+            .await
+            .map_err(|_| ClientError::TimedOut)?
+            .map_err(ClientError::Handshake)?;
+        Ok(self.handshake(stream, Bytes::copy_from_slice(authority.as_bytes())))
+    }
+
+    /// Connects to the host of `authority`, at `default_port` if it names none
+    async fn connect_tcp(
+        &self,
+        authority: &str,
+        default_port: u16,
+        deadline: Instant,
+    ) -> Result<(UriHost, TcpStream), ClientError> {
+        let HostWithPort { host, port } = authority
+            .parse()
+            .map_err(|_| ClientError::InvalidAuthority)?;
+        let addr = match &host {
+            UriHost::Ipv4(addr) => addr.to_string(),
+            UriHost::IpLiteral(IpLiteral::Ipv6(addr)) => addr.to_string(),
+            UriHost::RegName(name) => name.to_string(),
+            UriHost::IpLiteral(IpLiteral::IpvFuture(_)) => {
+                return Err(ClientError::InvalidAuthority);
+            }
+        };
+        let connect = TcpStream::connect((addr.as_str(), port.unwrap_or(default_port)));
+        match clock::timeout_at(&*self.clock, deadline, connect).await {
+            Ok(Ok(stream)) => Ok((host, stream)),
+            Ok(Err(err)) => Err(ClientError::Connect(err)),
+            Err(_) => Err(ClientError::TimedOut),
+        }
+    }
+
+    /// Uses an established `stream`, such as a TLS or Unix socket stream, `host` is sent as
+    /// the Host of requests which don't have one
+    pub fn handshake<S>(&self, stream: S, host: impl Into<Bytes>) -> ClientConnection<S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (read, write) = tokio::io::split(stream);
+        ClientConnection {
+            parser: Parser::with_limits(read, self.head_limits.clone()),
+            sender: Sender::new(write),
+            host: host.into(),
+            timeout: self.timeout,
+            body_limits: self.body_limits.clone(),
+            clock: self.clock.clone(),
+            closed: false,
+        }
+    }
+
+    /// Sends a single request on a new connection to `authority`
+    pub async fn send(&self, authority: &str, request: Request) -> Result<Response, ClientError> {
+        self.connect(authority).await?.send(request).await
+    }
+}
+
+/// A connection to a server, which is kept open between requests unless either side asks to
+/// close it
+pub struct ClientConnection<S: AsyncRead + AsyncWrite + Unpin> {
+    parser: Parser<ReadHalf<S>>,
+    sender: Sender<WriteHalf<S>>,
+    host: Bytes,
+    timeout: Duration,
+    body_limits: BodyLimits,
+    clock: SharedClock,
+    closed: bool,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> ClientConnection<S> {
+    /// Whether another request can't be sent on the connection
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Sends `request` and reads its response, with the timeout of the client
+    pub async fn send(&mut self, request: Request) -> Result<Response, ClientError> {
+        self.send_with_timeout(request, self.timeout).await
+    }
+
+    /// Sends `request` and reads its response, with the body read in full
+    /// Interim (1xx) responses are skipped, except 101 Switching Protocols, after which the
+    /// connection no longer speaks HTTP/1.1 and is closed for further requests
+    pub async fn send_with_timeout(
+        &mut self,
+        request: Request,
+        timeout: Duration,
+    ) -> Result<Response, ClientError> {
+        if self.closed {
+            return Err(ClientError::Closed);
+        }
+        // The connection is in an unknown state unless the exchange completes
+        self.closed = true;
+        let clock = self.clock.clone();
+        let res = clock::timeout(&*clock, timeout, self.exchange(request)).await;
+        let (res, reusable) = res.map_err(|_| ClientError::TimedOut)??;
+        self.closed = !reusable;
+        Ok(res)
+    }
+
+    /// The response, and whether the connection can be used for another request
+    async fn exchange(&mut self, mut request: Request) -> Result<(Response, bool), ClientError> {
+        // SPEC: RFC 9112 - 3.2. Request Target
+        // A client must send Host in all HTTP/1.1 requests
+        if !request.headers.contains(&Host::NAME) {
+            request.headers.append(Host::NAME, self.host.clone());
+        }
+        let method = request.method.clone();
+        let mut reusable = persists(request.version, &ConnectionOptions::of(&request.headers));
+        self.sender.send_request(request).await?;
+
+        let (mut res, framing) = loop {
+            let (res, framing) = self.parser.parse_response_head(&method).await?;
+            if res.status == StatusCode::SWITCHING_PROTOCOLS || !res.status.is_informational() {
+                break (res, framing);
+            }
+        };
+        if res.status == StatusCode::SWITCHING_PROTOCOLS {
+            return Ok((res, false));
+        }
+        // SPEC: RFC 9112 - 9.3. Persistence
+        reusable &= persists(res.version, &ConnectionOptions::of(&res.headers))
+            && framing != BodyFraming::UntilClose;
+
+        if framing != BodyFraming::None {
+            let (tx, stream) = BodyStream::channel(1);
+            let (pumped, body) = tokio::join!(
+                self.parser.pump_body(framing, tx, &self.body_limits),
+                stream.collect(None)
+            );
+            pumped?;
+            res.body = Body::Full(body?);
+        }
+        Ok((res, reusable))
+    }
+}
+
+/// Whether a message allows the connection to be reused after it
+/// SPEC: RFC 9112 - 9.3. Persistence
+fn persists(version: HttpVersion, options: &ConnectionOptions) -> bool {
+    if version >= HttpVersion::HTTP_1_1 {
+        !options.close
+    } else {
+        options.keep_alive && !options.close
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        http::{header::ContentLength, method::Method},
+    };
+
+    /// A connection to a server which answers with `responses`, and the requests it received
+    fn connection(
+        responses: &'static [u8],
+    ) -> (
+        ClientConnection<DuplexStream>,
+        tokio::task::JoinHandle<Vec<u8>>,
+    ) {
+        let (client, mut server) = tokio::io::duplex(4096);
+        let received = tokio::spawn(async move {
+            server.write_all(responses).await.unwrap();
+            server.shutdown().await.unwrap();
+            let mut received = Vec::new();
+            server.read_to_end(&mut received).await.unwrap();
+            received
+        });
+        let conn = Client::new().handshake(client, "example.com");
+        (conn, received)
+    }
+
+    #[tokio::test]
+    async fn content_length_and_chunked() {
+        let (mut conn, received) = connection(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello\
+              HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n\
+              3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n\
+              HTTP/1.1 204\r\nContent-Length: 10\r\n\r\n",
+        );
+        let res = conn.send(Request::new(Method::GET, "/a")).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.message, "OK");
+        assert!(matches!(res.body, Body::Full(ref body) if body == "hello"));
+
+        let mut req = Request::new(Method::POST, "/b");
+        req.body = Body::Full(Bytes::from_static(b"data"));
+        let res = conn.send(req).await.unwrap();
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        assert!(matches!(res.body, Body::Full(ref body) if body == "abcde"));
+
+        // 204 never has a body, whatever Content-Length says
+        let res = conn.send(Request::new(Method::GET, "/c")).await.unwrap();
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        assert!(res.message.is_empty());
+        assert!(matches!(res.body, Body::None));
+        assert!(!conn.is_closed());
+        drop(conn);
+
+        let received = String::from_utf8(received.await.unwrap()).unwrap();
+        assert!(received.starts_with("GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n"));
+        assert!(received.contains("POST /b HTTP/1.1\r\n"));
+        assert!(received.contains("Content-Length: 4\r\n\r\ndata"));
+    }
+
+    #[tokio::test]
+    async fn head_and_close_delimited() {
+        let (mut conn, _) = connection(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n\
+              HTTP/1.0 200 OK\r\n\r\nuntil close",
+        );
+        let res = conn.send(Request::new(Method::HEAD, "/")).await.unwrap();
+        assert!(matches!(res.body, Body::None));
+        assert_eq!(res.headers.get(&ContentLength::NAME).unwrap()[0], "5");
+
+        let res = conn.send(Request::new(Method::GET, "/")).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "until close"));
+        assert!(conn.is_closed());
+        assert!(matches!(
+            conn.send(Request::new(Method::GET, "/")).await,
+            Err(ClientError::Closed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn connection_close() {
+        let (mut conn, _) =
+            connection(b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n");
+        conn.send(Request::new(Method::GET, "/")).await.unwrap();
+        assert!(conn.is_closed());
+    }
+
+    #[tokio::test]
+    async fn invalid_status_line() {
+        for response in [
+            &b"HTTP/1.1 2000 OK\r\n\r\n"[..],
+            b"HTTP/1.1 OK\r\n\r\n",
+            b"ICY 200 OK\r\n\r\n",
+        ] {
+            let (mut conn, _) = connection(response);
+            assert!(matches!(
+                conn.send(Request::new(Method::GET, "/")).await,
+                Err(ClientError::Parse(_))
+            ));
+            assert!(conn.is_closed());
+        }
+    }
+
+    #[tokio::test]
+    async fn request_timeout() {
+        let clock = std::sync::Arc::new(MockClock::new());
+        let (client, _server) = tokio::io::duplex(4096);
+        let mut conn = Client::new()
+            .with_timeout(Duration::from_secs(5))
+            .with_clock(clock.clone())
+            .handshake(client, "example.com");
+        let (res, ()) = tokio::join!(conn.send(Request::new(Method::GET, "/")), async {
+            tokio::task::yield_now().await;
+            clock.advance(Duration::from_secs(5))
+        });
+        assert!(matches!(res, Err(ClientError::TimedOut)));
+        assert!(conn.is_closed());
+    }
+}
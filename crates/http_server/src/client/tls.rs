@@ -0,0 +1,312 @@
+//! HTTPS for the client, the server is verified against a root store, and optionally against
+//! a set of pinned certificates
+//! The certificates of the platform are not read, the roots of the Mozilla CA program are
+//! available with the `webpki-roots` feature, see [`webpki_roots`], or any roots can be added
+//! to a [`RootCertStore`]
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{
+        self, CertificateError, DigitallySignedStruct, SignatureScheme,
+        client::{
+            WebPkiServerVerifier,
+            danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        },
+        crypto::ring::default_provider,
+        pki_types::{CertificateDer, ServerName, UnixTime},
+    },
+};
+
+use crate::tls::HandshakeFailure;
+
+pub use tokio_rustls::rustls::{ClientConfig, RootCertStore, client::VerifierBuilderError};
+
+/// The SHA-256 digest of the DER encoding of a certificate
+pub type CertificatePin = [u8; 32];
+
+/// Opens TLS connections for a [`super::Client`], sending the host as the SNI server name
+/// SPEC: RFC 6066 - 3. Server Name Indication
+#[derive(Clone)]
+pub struct TlsConnector {
+    connector: tokio_rustls::TlsConnector,
+}
+
+impl std::fmt::Debug for TlsConnector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TlsConnector")
+    }
+}
+
+impl TlsConnector {
+    /// Verifies servers against the certificates in `roots`
+    /// Fails if `roots` is empty, as no server could be verified
+    pub fn new(roots: RootCertStore) -> Result<Self, VerifierBuilderError> {
+        Ok(Self::with_verifier(Self::webpki(roots)?))
+    }
+
+    /// Verifies servers against the certificates in `roots`, and only accepts a server whose
+    /// certificate is one of `pins`
+    /// The pins are checked after the usual verification, so a pinned certificate must also be
+    /// valid for the host
+    pub fn with_pinned_certificates(
+        roots: RootCertStore,
+        pins: impl IntoIterator<Item = CertificatePin>,
+    ) -> Result<Self, VerifierBuilderError> {
+        Ok(Self::with_verifier(Arc::new(PinnedVerifier {
+            inner: Self::webpki(roots)?,
+            pins: pins.into_iter().collect(),
+        })))
+    }
+
+    /// Uses `config` as it is, for client certificates or other settings
+    pub fn from_config(config: Arc<ClientConfig>) -> Self {
+        Self {
+            connector: tokio_rustls::TlsConnector::from(config),
+        }
+    }
+
+    fn webpki(roots: RootCertStore) -> Result<Arc<dyn ServerCertVerifier>, VerifierBuilderError> {
+        Ok(WebPkiServerVerifier::builder_with_provider(
+            Arc::new(roots),
+            Arc::new(default_provider()),
+        )
+        .build()?)
+    }
+
+    fn with_verifier(verifier: Arc<dyn ServerCertVerifier>) -> Self {
+        let mut config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("the ring provider supports the default versions")
+            .dangerous()
+            .with_custom_certificate_verifier(verifier)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Self::from_config(Arc::new(config))
+    }
+
+    pub(super) async fn connect<S>(
+        &self,
+        server_name: ServerName<'static>,
+        stream: S,
+    ) -> Result<TlsStream<S>, HandshakeFailure>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        self.connector
+            .connect(server_name, stream)
+            .await
+            .map_err(|err| HandshakeFailure::from_io(&err))
+    }
+}
+
+/// The roots of the Mozilla CA program, as shipped with this version of the crate
+#[cfg(feature = "webpki-roots")]
+pub fn webpki_roots() -> RootCertStore {
+    RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned())
+}
+
+/// The SHA-256 digest of `cert`, to pin it
+pub fn certificate_pin(cert: &CertificateDer<'_>) -> CertificatePin {
+    let digest = ring::digest::digest(&ring::digest::SHA256, cert);
+    digest
+        .as_ref()
+        .try_into()
+        .expect("SHA-256 digests are 32 bytes")
+}
+
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    pins: Vec<CertificatePin>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        match self.pins.contains(&certificate_pin(end_entity)) {
+            true => Ok(verified),
+            false => Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            )),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        TlsAcceptor,
+        rustls::{
+            ServerConfig,
+            pki_types::{PrivateKeyDer, pem::PemObject},
+        },
+    };
+
+    use super::*;
+    use crate::{
+        client::{Client, ClientError},
+        http::{Body, method::Method, request::Request},
+    };
+
+    const CA: &[u8] = include_bytes!("testdata/ca.pem");
+    const CERT: &[u8] = include_bytes!("testdata/localhost.pem");
+    const KEY: &[u8] = include_bytes!("testdata/localhost.key");
+
+    fn roots() -> RootCertStore {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_slice(CA).unwrap())
+            .unwrap();
+        roots
+    }
+
+    /// A server which answers one request on each connection, and the port it listens on
+    async fn server() -> u16 {
+        let config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from_pem_slice(CERT).unwrap()],
+                PrivateKeyDer::from_pem_slice(KEY).unwrap(),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut buf = [0; 1024];
+                    let _ = stream.read(&mut buf).await;
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nsecure")
+                        .await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn verified_connection() {
+        let port = server().await;
+        let client = Client::new().with_tls(TlsConnector::new(roots()).unwrap());
+        let mut conn = client
+            .connect_tls(&format!("localhost:{port}"))
+            .await
+            .unwrap();
+        let res = conn.send(Request::new(Method::GET, "/")).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "secure"));
+
+        // IP addresses are verified against the IP addresses the certificate names
+        let mut conn = client
+            .connect_tls(&format!("127.0.0.1:{port}"))
+            .await
+            .unwrap();
+        assert!(conn.send(Request::new(Method::GET, "/")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn untrusted_certificate() {
+        let port = server().await;
+        let mut other = RootCertStore::empty();
+        // The server certificate is not a CA, so nothing chains to it
+        other
+            .add(CertificateDer::from_pem_slice(CERT).unwrap())
+            .unwrap();
+        let client = Client::new().with_tls(TlsConnector::new(other).unwrap());
+        assert!(matches!(
+            client.connect_tls(&format!("localhost:{port}")).await,
+            Err(ClientError::Handshake(HandshakeFailure::BadCertificate))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unconfigured() {
+        assert!(matches!(
+            TlsConnector::new(RootCertStore::empty()),
+            Err(VerifierBuilderError::NoRootAnchors)
+        ));
+        assert!(matches!(
+            Client::new().connect_tls("localhost:443").await,
+            Err(ClientError::TlsNotConfigured)
+        ));
+    }
+
+    #[cfg(feature = "webpki-roots")]
+    #[test]
+    fn mozilla_roots() {
+        assert!(!webpki_roots().is_empty());
+        assert!(TlsConnector::new(webpki_roots()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn pinned_certificates() {
+        let port = server().await;
+        let pin = certificate_pin(&CertificateDer::from_pem_slice(CERT).unwrap());
+        let client =
+            Client::new().with_tls(TlsConnector::with_pinned_certificates(roots(), [pin]).unwrap());
+        assert!(
+            client
+                .connect_tls(&format!("localhost:{port}"))
+                .await
+                .is_ok()
+        );
+
+        let client = Client::new()
+            .with_tls(TlsConnector::with_pinned_certificates(roots(), [[0; 32]]).unwrap());
+        assert!(matches!(
+            client.connect_tls(&format!("localhost:{port}")).await,
+            Err(ClientError::Handshake(HandshakeFailure::BadCertificate))
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+//! Access to time for timeouts and timers
+//!
+//! Everything in the server which waits or measures time goes through a [`Clock`], so timers
+//! such as the keep-alive reaper, body read timeouts and rate limit windows can be tested
+//! deterministically. [`TokioClock`] follows `tokio::time`, so it also respects
+//! `tokio::time::pause`, and [`MockClock`] only moves when it is advanced
+
+use std::{fmt, pin::Pin, sync::Arc, time::Duration};
+
+use tokio::{sync::watch, time::Instant};
+
+/// A future which resolves once a deadline has been reached
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A clock shared between the parts of a server
+pub type SharedClock = Arc<dyn Clock>;
+
+/// A source of time
+pub trait Clock: fmt::Debug + Send + Sync + 'static {
+    fn now(&self) -> Instant;
+
+    /// Resolves once [`Self::now`] has reached `deadline`
+    fn sleep_until(&self, deadline: Instant) -> Sleep;
+
+    fn sleep(&self, duration: Duration) -> Sleep {
+        self.sleep_until(self.now() + duration)
+    }
+}
+
+/// The time of the tokio runtime
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TokioClock;
+
+impl Clock for TokioClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        Box::pin(tokio::time::sleep_until(deadline))
+    }
+}
+
+impl TokioClock {
+    pub fn shared() -> SharedClock {
+        Arc::new(Self)
+    }
+}
+
+/// A clock which stands still until it is advanced, sleeps resolve once the clock has been
+/// advanced past their deadline
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    elapsed: watch::Sender<Duration>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: watch::Sender::new(Duration::ZERO),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.send_modify(|elapsed| *elapsed += duration);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.borrow()
+    }
+
+    fn sleep_until(&self, deadline: Instant) -> Sleep {
+        let start = self.start;
+        let mut elapsed = self.elapsed.subscribe();
+        Box::pin(async move {
+            // A dropped clock is never advanced again
+            if elapsed
+                .wait_for(|elapsed| start + *elapsed >= deadline)
+                .await
+                .is_err()
+            {
+                std::future::pending().await
+            }
+        })
+    }
+}
+
+/// The deadline of [`timeout`] passed before the future completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("deadline has elapsed")]
+pub struct Elapsed;
+
+/// Runs `future` until it completes, or `duration` has passed on `clock`
+pub async fn timeout<F: Future>(
+    clock: &dyn Clock,
+    duration: Duration,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    timeout_at(clock, clock.now() + duration, future).await
+}
+
+/// Runs `future` until it completes, or `clock` reaches `deadline`
+pub async fn timeout_at<F: Future>(
+    clock: &dyn Clock,
+    deadline: Instant,
+    future: F,
+) -> Result<F::Output, Elapsed> {
+    let sleep = clock.sleep_until(deadline);
+    tokio::select! {
+        biased;
+        output = future => Ok(output),
+        _ = sleep => Err(Elapsed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_sleep() {
+        let clock = Arc::new(MockClock::new());
+        let start = clock.now();
+        let sleep = tokio::spawn(clock.sleep(Duration::from_secs(10)));
+
+        clock.advance(Duration::from_secs(9));
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+        clock.advance(Duration::from_secs(1));
+        sleep.await.unwrap();
+        assert_eq!(clock.now() - start, Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn mock_timeout() {
+        let clock = MockClock::new();
+        let res = timeout(&clock, Duration::from_secs(1), async { 1 }).await;
+        assert_eq!(res, Ok(1));
+
+        let pending = timeout(&clock, Duration::from_secs(1), std::future::pending::<()>());
+        tokio::pin!(pending);
+        assert!(
+            poll_once(pending.as_mut()).is_pending(),
+            "the clock has not moved"
+        );
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(pending.await, Err(Elapsed));
+    }
+
+    fn poll_once<F: Future>(future: Pin<&mut F>) -> std::task::Poll<F::Output> {
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        future.poll(&mut cx)
+    }
+}
@@ -0,0 +1,334 @@
+//! Loading settings from a TOML file or from environment variables, see [`super`] for the
+//! format
+
+use std::{path::Path, time::Duration};
+
+use super::{ConfigError, HttpServerConfigBuilder};
+use crate::HttpServerConfig;
+
+/// The prefix of the environment variables read by [`HttpServerConfigBuilder::apply_env`]
+pub const ENV_PREFIX: &str = "CARBON_HTTP_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Integer(u64),
+    Bool(bool),
+    String(String),
+}
+
+impl Value {
+    fn from_toml(value: toml::Value) -> Result<Self, &'static str> {
+        match value {
+            toml::Value::Integer(n) => u64::try_from(n)
+                .map(Value::Integer)
+                .map_err(|_| "out of range"),
+            toml::Value::Boolean(b) => Ok(Value::Bool(b)),
+            toml::Value::String(s) => Ok(Value::String(s)),
+            _ => Err("expected an integer, boolean or string"),
+        }
+    }
+
+    /// Environment variables are untyped, so the value is typed by its contents
+    fn from_env(value: &str) -> Self {
+        match value {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => match parse_integer(value) {
+                Some(n) => Value::Integer(n),
+                None => Value::String(value.to_owned()),
+            },
+        }
+    }
+
+    fn integer<T: TryFrom<u64>>(&self) -> Result<T, &'static str> {
+        match self {
+            Value::Integer(n) => T::try_from(*n).map_err(|_| "out of range"),
+            _ => Err("expected an integer"),
+        }
+    }
+
+    fn size(&self) -> Result<usize, &'static str> {
+        let Value::String(s) = self else {
+            return self.integer();
+        };
+        let (digits, unit) = s.split_at(
+            s.find(|c: char| c.is_ascii_alphabetic())
+                .ok_or("expected a size")?,
+        );
+        let unit: u64 = match unit {
+            "B" => 1,
+            "KiB" => 1 << 10,
+            "MiB" => 1 << 20,
+            "GiB" => 1 << 30,
+            _ => return Err("unknown size unit"),
+        };
+        parse_integer(digits.trim_end())
+            .and_then(|n| n.checked_mul(unit))
+            .and_then(|n| usize::try_from(n).ok())
+            .ok_or("expected a size")
+    }
+
+    fn duration(&self) -> Result<Duration, &'static str> {
+        let Value::String(s) = self else {
+            return self.integer().map(Duration::from_secs);
+        };
+        let (digits, unit) = s.split_at(
+            s.find(|c: char| c.is_ascii_alphabetic())
+                .ok_or("expected a duration")?,
+        );
+        let n = parse_integer(digits.trim_end()).ok_or("expected a duration")?;
+        let secs = match unit {
+            "ms" => return Ok(Duration::from_millis(n)),
+            "s" => Some(n),
+            "m" => n.checked_mul(60),
+            "h" => n.checked_mul(60 * 60),
+            _ => return Err("unknown duration unit"),
+        };
+        secs.map(Duration::from_secs).ok_or("out of range")
+    }
+
+    fn bool(&self) -> Result<bool, &'static str> {
+        match self {
+            Value::Bool(b) => Ok(*b),
+            _ => Err("expected true or false"),
+        }
+    }
+
+    fn optional<T>(
+        &self,
+        value: impl FnOnce(&Self) -> Result<T, &'static str>,
+    ) -> Result<Option<T>, &'static str> {
+        match self {
+            Value::String(s) if s == "none" => Ok(None),
+            _ => value(self).map(Some),
+        }
+    }
+}
+
+/// ABNF: dec-int = [ "+" ] unsigned-dec-int, with underscores between digits
+fn parse_integer(s: &str) -> Option<u64> {
+    let s = s.strip_prefix('+').unwrap_or(s);
+    let valid = !s.is_empty()
+        && s.split('_')
+            .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+    if !valid {
+        return None;
+    }
+    s.replace('_', "").parse().ok()
+}
+
+impl HttpServerConfigBuilder {
+    /// Applies the settings in the file at `path`, see [`crate::config`] for the format
+    pub fn apply_toml(self, path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        self.apply_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn apply_toml_str(mut self, toml: &str) -> Result<Self, ConfigError> {
+        for (name, value) in toml.parse::<toml::Table>()? {
+            let value = Value::from_toml(value).map_err(|reason| ConfigError::InvalidValue {
+                setting: name.clone(),
+                reason,
+            })?;
+            self = self.set(&name, &value)?;
+        }
+        Ok(self)
+    }
+
+    /// Applies the settings of the environment variables starting with [`ENV_PREFIX`]
+    pub fn apply_env(self) -> Result<Self, ConfigError> {
+        self.apply_vars(std::env::vars())
+    }
+
+    fn apply_vars(
+        mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        for (key, value) in vars {
+            let Some(name) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            self = self.set(&name.to_ascii_lowercase(), &Value::from_env(&value))?;
+        }
+        Ok(self)
+    }
+
+    fn set(self, name: &str, value: &Value) -> Result<Self, ConfigError> {
+        let invalid = |reason| ConfigError::InvalidValue {
+            setting: name.to_owned(),
+            reason,
+        };
+        Ok(match name {
+            "max_request_line_bytes" => self.max_request_line_bytes(value.size().map_err(invalid)?),
+            "max_header_bytes_total" => self.max_header_bytes_total(value.size().map_err(invalid)?),
+            "max_header_line_bytes" => self.max_header_line_bytes(value.size().map_err(invalid)?),
+            "max_header_count" => self.max_header_count(value.integer().map_err(invalid)?),
+            "max_path_bytes" => self.max_path_bytes(value.size().map_err(invalid)?),
+            "max_query_bytes" => self.max_query_bytes(value.size().map_err(invalid)?),
+            "max_body_bytes" => self.max_body_bytes(value.optional(Value::size).map_err(invalid)?),
+            "max_chunk_size_bytes" => self.max_chunk_size_bytes(value.size().map_err(invalid)?),
+            "max_trailer_bytes_total" => {
+                self.max_trailer_bytes_total(value.size().map_err(invalid)?)
+            }
+            "max_body_drain_bytes" => self.max_body_drain_bytes(value.size().map_err(invalid)?),
+            "max_connections" => {
+                self.max_connections(value.optional(Value::integer).map_err(invalid)?)
+            }
+            "max_in_flight_requests" => {
+                self.max_in_flight_requests(value.optional(Value::integer).map_err(invalid)?)
+            }
+            "max_requests_per_connection" => {
+                self.max_requests_per_connection(value.optional(Value::integer).map_err(invalid)?)
+            }
+            "max_pipelined_requests" => {
+                self.max_pipelined_requests(value.integer().map_err(invalid)?)
+            }
+            "header_read_timeout" => self.header_read_timeout(value.duration().map_err(invalid)?),
+            "request_body_timeout" => self.request_body_timeout(value.duration().map_err(invalid)?),
+            "keep_alive_timeout" => self.keep_alive_timeout(value.duration().map_err(invalid)?),
+            "response_write_timeout" => {
+                self.response_write_timeout(value.duration().map_err(invalid)?)
+            }
+            "min_response_write_rate" => self.min_response_write_rate(
+                value
+                    .optional(Value::size)
+                    .map_err(invalid)?
+                    .map(|rate| rate as u64),
+            ),
+            "linger_timeout" => self.linger_timeout(value.duration().map_err(invalid)?),
+            "idle_sweep_interval" => {
+                self.idle_sweep_interval(value.optional(Value::duration).map_err(invalid)?)
+            }
+            #[cfg(feature = "tls")]
+            "tls_handshake_timeout" => {
+                self.tls_handshake_timeout(value.duration().map_err(invalid)?)
+            }
+            "shutdown_grace_period" => {
+                self.shutdown_grace_period(value.duration().map_err(invalid)?)
+            }
+            "strip_hop_by_hop_headers" => {
+                self.strip_hop_by_hop_headers(value.bool().map_err(invalid)?)
+            }
+            "allow_obs_fold" => self.allow_obs_fold(value.bool().map_err(invalid)?),
+            "advertise_keep_alive" => self.advertise_keep_alive(value.bool().map_err(invalid)?),
+            "enable_trace" => self.enable_trace(value.bool().map_err(invalid)?),
+            _ => return Err(ConfigError::UnknownSetting(name.to_owned())),
+        })
+    }
+}
+
+impl HttpServerConfig {
+    /// The default config with the settings in the file at `path` applied
+    pub fn from_toml(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        Self::builder().apply_toml(path)?.build()
+    }
+
+    /// The default config with the settings of the environment applied
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::builder().apply_env()?.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+
+    use super::*;
+
+    #[test]
+    fn toml() {
+        let config = HttpServerConfig::builder()
+            .apply_toml_str(
+                r#"
+                # Limits
+                max_body_bytes = "16MiB"
+                max_header_count = 1_000 # fields
+                max_connections = 'none'
+
+                keep_alive_timeout = "500ms"
+                header_read_timeout = 5
+                idle_sweep_interval = "none"
+                allow_obs_fold = true
+                enable_trace = true
+                "#,
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.max_body_bytes, NonZeroUsize::new(16 << 20));
+        assert_eq!(config.max_header_count.get(), 1000);
+        assert_eq!(config.max_connections, None);
+        assert_eq!(config.keep_alive_timeout, Duration::from_millis(500));
+        assert_eq!(config.header_read_timeout, Duration::from_secs(5));
+        assert_eq!(config.idle_sweep_interval, None);
+        assert!(config.allow_obs_fold);
+        assert!(config.enable_trace);
+    }
+
+    #[test]
+    fn toml_errors() {
+        let apply = |toml| HttpServerConfig::builder().apply_toml_str(toml).err();
+        assert!(matches!(
+            apply("max_header_count = 10\nmax_body_bytes = \"16MiB"),
+            Some(ConfigError::Syntax(_))
+        ));
+        assert!(matches!(
+            apply("linger_timeout = 1\nlinger_timeout = 2"),
+            Some(ConfigError::Syntax(_))
+        ));
+        assert!(matches!(
+            apply("[server]\nlinger_timeout = 1"),
+            Some(ConfigError::InvalidValue { setting, .. }) if setting == "server"
+        ));
+        assert!(matches!(
+            apply("max_header_count = -1"),
+            Some(ConfigError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            apply("max_body = 1"),
+            Some(ConfigError::UnknownSetting(name)) if name == "max_body"
+        ));
+        assert!(matches!(
+            apply("keep_alive_timeout = \"5 days\""),
+            Some(ConfigError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            apply("allow_obs_fold = 1"),
+            Some(ConfigError::InvalidValue { .. })
+        ));
+        // Out of range values are reported when the config is built
+        let builder = HttpServerConfig::builder()
+            .apply_toml_str("max_header_count = 0")
+            .unwrap();
+        assert!(matches!(
+            builder.build(),
+            Err(ConfigError::Zero("max_header_count"))
+        ));
+    }
+
+    #[test]
+    fn env() {
+        let vars = [
+            ("PATH", "/usr/bin"),
+            ("CARBON_HTTP_MAX_BODY_BYTES", "64KiB"),
+            ("CARBON_HTTP_REQUEST_BODY_TIMEOUT", "2m"),
+            ("CARBON_HTTP_ADVERTISE_KEEP_ALIVE", "true"),
+            ("CARBON_HTTP_MAX_REQUESTS_PER_CONNECTION", "100"),
+        ]
+        .map(|(key, value)| (key.to_owned(), value.to_owned()));
+        let config = HttpServerConfig::builder()
+            .apply_vars(vars)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(config.max_body_bytes, NonZeroUsize::new(64 * 1024));
+        assert_eq!(config.request_body_timeout, Duration::from_secs(120));
+        assert!(config.advertise_keep_alive);
+        assert_eq!(config.max_requests_per_connection.unwrap().get(), 100);
+
+        let vars = [("CARBON_HTTP_KEEP_ALIVE".to_owned(), "1".to_owned())];
+        assert!(matches!(
+            HttpServerConfig::builder().apply_vars(vars),
+            Err(ConfigError::UnknownSetting(_))
+        ));
+    }
+}
@@ -0,0 +1,382 @@
+//! Building an [`HttpServerConfig`] with its settings checked against each other
+//!
+//! With the `config` feature, settings can also be read from a TOML file or from environment
+//! variables, so limits and timeouts can be tuned without recompiling
+//!
+//! Settings are named after the fields of [`HttpServerConfig`], `max_body_bytes = "16MiB"` in
+//! a file, or `CARBON_HTTP_MAX_BODY_BYTES=16MiB` in the environment
+//! - Sizes are a number of bytes, or a string with a `KiB`, `MiB` or `GiB` suffix
+//! - Durations are a number of seconds, or a string with a `ms`, `s`, `m` or `h` suffix
+//! - Optional settings are disabled with `"none"`
+//!
+//! Files are TOML, with the settings as top level keys with integer, boolean or string values
+//!
+//! ```ignore
+//! let config = HttpServerConfig::builder()
+//!     .max_body_bytes(16 * 1024 * 1024)
+//!     .keep_alive_timeout(Duration::from_secs(30))
+//!     .apply_toml("server.toml")?
+//!     .apply_env()?
+//!     .build()?;
+//! ```
+
+use std::{
+    num::{NonZeroU64, NonZeroUsize},
+    time::Duration,
+};
+
+use crate::{HttpServerConfig, clock::SharedClock};
+
+#[cfg(feature = "config")]
+mod load;
+#[cfg(feature = "config")]
+pub use load::ENV_PREFIX;
+
+/// A setting was out of range, or could not be loaded
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("{0} must not be zero")]
+    Zero(&'static str),
+    /// A limit is larger than a limit which contains it
+    #[error("{field} must not be larger than {limit}")]
+    Exceeds {
+        field: &'static str,
+        limit: &'static str,
+    },
+    #[error("unknown setting {0}")]
+    UnknownSetting(String),
+    #[error("invalid value for {setting}: {reason}")]
+    InvalidValue {
+        setting: String,
+        reason: &'static str,
+    },
+    /// The file is not valid TOML
+    #[cfg(feature = "config")]
+    #[error(transparent)]
+    Syntax(#[from] toml::de::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl HttpServerConfig {
+    /// A builder starting from the default settings
+    pub fn builder() -> HttpServerConfigBuilder {
+        HttpServerConfigBuilder::new()
+    }
+}
+
+/// Builds an [`HttpServerConfig`], settings are checked when set, and against each other by
+/// [`Self::build`]
+/// Settings without a method here can be set on the built config
+pub struct HttpServerConfigBuilder {
+    config: HttpServerConfig,
+    /// The first setting which was out of range, reported by [`Self::build`]
+    error: Option<ConfigError>,
+}
+
+impl Default for HttpServerConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpServerConfigBuilder {
+    pub fn new() -> Self {
+        Self::from_config(HttpServerConfig::default())
+    }
+
+    /// Starts from the settings of `config`
+    pub fn from_config(config: HttpServerConfig) -> Self {
+        Self {
+            config,
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, error: ConfigError) {
+        self.error.get_or_insert(error);
+    }
+
+    fn non_zero(&mut self, field: &'static str, value: usize) -> Option<NonZeroUsize> {
+        let value = NonZeroUsize::new(value);
+        if value.is_none() {
+            self.fail(ConfigError::Zero(field));
+        }
+        value
+    }
+
+    fn non_zero_u64(&mut self, field: &'static str, value: u64) -> Option<NonZeroU64> {
+        let value = NonZeroU64::new(value);
+        if value.is_none() {
+            self.fail(ConfigError::Zero(field));
+        }
+        value
+    }
+
+    fn non_zero_duration(&mut self, field: &'static str, value: Duration) -> Duration {
+        if value.is_zero() {
+            self.fail(ConfigError::Zero(field));
+        }
+        value
+    }
+
+    pub fn max_request_line_bytes(mut self, bytes: usize) -> Self {
+        if let Some(bytes) = self.non_zero("max_request_line_bytes", bytes) {
+            self.config.max_request_line_bytes = bytes;
+        }
+        self
+    }
+
+    pub fn max_header_bytes_total(mut self, bytes: usize) -> Self {
+        if let Some(bytes) = self.non_zero("max_header_bytes_total", bytes) {
+            self.config.max_header_bytes_total = bytes;
+        }
+        self
+    }
+
+    pub fn max_header_line_bytes(mut self, bytes: usize) -> Self {
+        if let Some(bytes) = self.non_zero("max_header_line_bytes", bytes) {
+            self.config.max_header_line_bytes = bytes;
+        }
+        self
+    }
+
+    pub fn max_header_count(mut self, count: usize) -> Self {
+        if let Some(count) = self.non_zero("max_header_count", count) {
+            self.config.max_header_count = count;
+        }
+        self
+    }
+
+    pub fn max_path_bytes(mut self, bytes: usize) -> Self {
+        if let Some(bytes) = self.non_zero("max_path_bytes", bytes) {
+            self.config.max_path_bytes = bytes;
+        }
+        self
+    }
+
+    pub fn max_query_bytes(mut self, bytes: usize) -> Self {
+        if let Some(bytes) = self.non_zero("max_query_bytes", bytes) {
+            self.config.max_query_bytes = bytes;
+        }
+        self
+    }
+
+    pub fn max_body_bytes(mut self, bytes: impl Into<Option<usize>>) -> Self {
+        self.config.max_body_bytes = match bytes.into() {
+            Some(bytes) => self.non_zero("max_body_bytes", bytes),
+            None => None,
+        };
+        self
+    }
+
+    pub fn max_chunk_size_bytes(mut self, bytes: usize) -> Self {
+        if let Some(bytes) = self.non_zero("max_chunk_size_bytes", bytes) {
+            self.config.max_chunk_size_bytes = bytes;
+        }
+        self
+    }
+
+    pub fn max_trailer_bytes_total(mut self, bytes: usize) -> Self {
+        if let Some(bytes) = self.non_zero("max_trailer_bytes_total", bytes) {
+            self.config.max_trailer_bytes_total = bytes;
+        }
+        self
+    }
+
+    /// 0 closes connections rather than discarding unread bodies
+    pub fn max_body_drain_bytes(mut self, bytes: usize) -> Self {
+        self.config.max_body_drain_bytes = bytes;
+        self
+    }
+
+    pub fn max_connections(mut self, count: impl Into<Option<usize>>) -> Self {
+        self.config.max_connections = match count.into() {
+            Some(count) => self.non_zero("max_connections", count),
+            None => None,
+        };
+        self
+    }
+
+    pub fn max_in_flight_requests(mut self, count: impl Into<Option<usize>>) -> Self {
+        self.config.max_in_flight_requests = match count.into() {
+            Some(count) => self.non_zero("max_in_flight_requests", count),
+            None => None,
+        };
+        self
+    }
+
+    pub fn max_requests_per_connection(mut self, count: impl Into<Option<u64>>) -> Self {
+        self.config.max_requests_per_connection = match count.into() {
+            Some(count) => self.non_zero_u64("max_requests_per_connection", count),
+            None => None,
+        };
+        self
+    }
+
+    pub fn max_pipelined_requests(mut self, count: usize) -> Self {
+        if let Some(count) = self.non_zero("max_pipelined_requests", count) {
+            self.config.max_pipelined_requests = count;
+        }
+        self
+    }
+
+    pub fn header_read_timeout(mut self, timeout: Duration) -> Self {
+        self.config.header_read_timeout = self.non_zero_duration("header_read_timeout", timeout);
+        self
+    }
+
+    pub fn request_body_timeout(mut self, timeout: Duration) -> Self {
+        self.config.request_body_timeout = self.non_zero_duration("request_body_timeout", timeout);
+        self
+    }
+
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.config.keep_alive_timeout = self.non_zero_duration("keep_alive_timeout", timeout);
+        self
+    }
+
+    pub fn response_write_timeout(mut self, timeout: Duration) -> Self {
+        self.config.response_write_timeout =
+            self.non_zero_duration("response_write_timeout", timeout);
+        self
+    }
+
+    /// In bytes per second
+    pub fn min_response_write_rate(mut self, rate: impl Into<Option<u64>>) -> Self {
+        self.config.min_response_write_rate = match rate.into() {
+            Some(rate) => self.non_zero_u64("min_response_write_rate", rate),
+            None => None,
+        };
+        self
+    }
+
+    /// 0 closes connections without lingering
+    pub fn linger_timeout(mut self, timeout: Duration) -> Self {
+        self.config.linger_timeout = timeout;
+        self
+    }
+
+    pub fn idle_sweep_interval(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.config.idle_sweep_interval = interval
+            .into()
+            .map(|interval| self.non_zero_duration("idle_sweep_interval", interval));
+        self
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.config.tls_handshake_timeout =
+            self.non_zero_duration("tls_handshake_timeout", timeout);
+        self
+    }
+
+    pub fn shutdown_grace_period(mut self, period: Duration) -> Self {
+        self.config.shutdown_grace_period = period;
+        self
+    }
+
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.config.clock = clock;
+        self
+    }
+
+    pub fn strip_hop_by_hop_headers(mut self, strip: bool) -> Self {
+        self.config.strip_hop_by_hop_headers = strip;
+        self
+    }
+
+    pub fn allow_obs_fold(mut self, allow: bool) -> Self {
+        self.config.allow_obs_fold = allow;
+        self
+    }
+
+    pub fn advertise_keep_alive(mut self, advertise: bool) -> Self {
+        self.config.advertise_keep_alive = advertise;
+        self
+    }
+
+    pub fn enable_trace(mut self, enable: bool) -> Self {
+        self.config.enable_trace = enable;
+        self
+    }
+
+    /// The config, or the first setting which was out of range
+    pub fn build(self) -> Result<HttpServerConfig, ConfigError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        let config = self.config;
+        let exceeds = |field, limit| Err(ConfigError::Exceeds { field, limit });
+        // A single line can't be longer than the whole head
+        if config.max_header_line_bytes > config.max_header_bytes_total {
+            return exceeds("max_header_line_bytes", "max_header_bytes_total");
+        }
+        // The path and query are part of the request line
+        if config.max_path_bytes > config.max_request_line_bytes {
+            return exceeds("max_path_bytes", "max_request_line_bytes");
+        }
+        if config.max_query_bytes > config.max_request_line_bytes {
+            return exceeds("max_query_bytes", "max_request_line_bytes");
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build() {
+        let config = HttpServerConfig::builder()
+            .max_body_bytes(1024 * 1024)
+            .max_connections(100)
+            .keep_alive_timeout(Duration::from_secs(5))
+            .idle_sweep_interval(None)
+            .allow_obs_fold(true)
+            .build()
+            .unwrap();
+        assert_eq!(config.max_body_bytes, NonZeroUsize::new(1024 * 1024));
+        assert_eq!(config.max_connections, NonZeroUsize::new(100));
+        assert_eq!(config.keep_alive_timeout, Duration::from_secs(5));
+        assert_eq!(config.idle_sweep_interval, None);
+        assert!(config.allow_obs_fold);
+        assert_eq!(config.max_header_count.get(), 100);
+    }
+
+    #[test]
+    fn validation() {
+        let err = HttpServerConfig::builder()
+            .max_header_count(0)
+            .max_body_bytes(0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ConfigError::Zero("max_header_count")));
+
+        let err = HttpServerConfig::builder()
+            .max_header_line_bytes(128 * 1024)
+            .build()
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ConfigError::Exceeds {
+                field: "max_header_line_bytes",
+                limit: "max_header_bytes_total"
+            }
+        ));
+
+        assert!(
+            HttpServerConfig::builder()
+                .max_query_bytes(16 * 1024)
+                .build()
+                .is_err()
+        );
+        assert!(
+            HttpServerConfig::builder()
+                .header_read_timeout(Duration::ZERO)
+                .build()
+                .is_err()
+        );
+    }
+}
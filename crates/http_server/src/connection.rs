@@ -0,0 +1,445 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::Notify,
+    time::Instant,
+};
+
+use crate::{clock::SharedClock, metrics::ServerMetrics, shutdown::ShutdownSignal};
+
+pub type ConnectionId = u64;
+
+/// The connection a request was received on, inserted into the request extensions by the
+/// server, see [`crate::http::request::Request::connection`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// Unique among the connections of a server
+    pub id: ConnectionId,
+    /// The address the client connected to, the destination of the PROXY header if there is
+    /// one, None for Unix domain sockets
+    pub local_addr: Option<SocketAddr>,
+    /// The address of the peer, or the source of the PROXY header if there is one, None for
+    /// Unix domain sockets
+    pub remote_addr: Option<SocketAddr>,
+    /// Set if the connection is encrypted
+    pub tls: Option<TlsInfo>,
+    /// How many requests were received on the connection, including this one
+    pub requests: u64,
+}
+
+/// What was negotiated in the TLS handshake
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// The server name the client asked for with SNI
+    pub server_name: Option<String>,
+    /// The application protocol chosen with ALPN
+    pub alpn_protocol: Option<Bytes>,
+    /// Such as `TLSv1_3`
+    pub version: Option<&'static str>,
+}
+
+/// Tracks every open connection and its last activity, so idle connections can be closed even
+/// while they are parked waiting for a read
+pub(crate) struct ConnectionRegistry {
+    clock: SharedClock,
+    epoch: Instant,
+    next_id: AtomicU64,
+    conns: Mutex<HashMap<ConnectionId, Arc<ConnectionState>>>,
+    /// Notified when the last connection is deregistered
+    emptied: Notify,
+    metrics: Arc<ServerMetrics>,
+    /// Requests being routed, over every connection
+    in_flight: AtomicUsize,
+}
+
+pub(crate) struct ConnectionState {
+    remote_addr: Option<SocketAddr>,
+    /// Milliseconds since the registry epoch
+    opened: u64,
+    /// Milliseconds since the registry epoch
+    last_activity: AtomicU64,
+    requests: AtomicU64,
+    /// Set while a request is being handled, busy connections are never reaped
+    busy: AtomicBool,
+    close: Notify,
+}
+
+impl ConnectionRegistry {
+    pub fn new(clock: SharedClock, metrics: Arc<ServerMetrics>) -> Self {
+        Self {
+            epoch: clock.now(),
+            clock,
+            next_id: AtomicU64::new(0),
+            conns: Mutex::new(HashMap::new()),
+            emptied: Notify::new(),
+            metrics,
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    fn now(&self) -> u64 {
+        (self.clock.now() - self.epoch).as_millis() as u64
+    }
+
+    pub fn register(self: &Arc<Self>, remote_addr: Option<SocketAddr>) -> ConnectionHandle {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let now = self.now();
+        let state = Arc::new(ConnectionState {
+            remote_addr,
+            opened: now,
+            last_activity: AtomicU64::new(now),
+            requests: AtomicU64::new(0),
+            busy: AtomicBool::new(false),
+            close: Notify::new(),
+        });
+        self.conns.lock().unwrap().insert(id, state.clone());
+        self.metrics.connection_opened();
+        ConnectionHandle {
+            id,
+            state,
+            registry: self.clone(),
+        }
+    }
+
+    /// Signals every connection which has not been busy or active for longer than `timeout` to
+    /// close, returns the number of connections signalled
+    pub fn reap_idle(&self, timeout: Duration) -> usize {
+        let now = self.now();
+        let timeout = timeout.as_millis() as u64;
+        let conns = self.conns.lock().unwrap();
+        let mut reaped = 0;
+        for state in conns.values() {
+            if state.busy.load(Ordering::Acquire) {
+                continue;
+            }
+            let last = state.last_activity.load(Ordering::Acquire);
+            if now.saturating_sub(last) > timeout {
+                state.close.notify_one();
+                reaped += 1;
+            }
+        }
+        reaped
+    }
+
+    /// Signals every connection to close, busy connections are skipped unless `include_busy`
+    pub fn close_all(&self, include_busy: bool) -> usize {
+        let conns = self.conns.lock().unwrap();
+        let mut closed = 0;
+        for state in conns.values() {
+            if include_busy || !state.busy.load(Ordering::Acquire) {
+                state.close.notify_one();
+                closed += 1;
+            }
+        }
+        closed
+    }
+
+    /// Counts a request as being routed until the guard is dropped
+    pub fn route_started(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(&self.in_flight)
+    }
+
+    /// Resolves once every connection has been deregistered
+    pub async fn wait_empty(&self) {
+        loop {
+            let emptied = self.emptied.notified();
+            tokio::pin!(emptied);
+            emptied.as_mut().enable();
+            if self.conns.lock().unwrap().is_empty() {
+                return;
+            }
+            emptied.await;
+        }
+    }
+
+    /// Runs the reaper until the registry is dropped by every other owner
+    pub async fn run_reaper(self: Arc<Self>, sweep_interval: Duration, timeout: Duration) {
+        loop {
+            self.clock.sleep(sweep_interval).await;
+            if Arc::strong_count(&self) == 1 {
+                return;
+            }
+            let reaped = self.reap_idle(timeout);
+            if reaped > 0 {
+                log::debug!("reaped {} idle connections", reaped);
+            }
+        }
+    }
+}
+
+pub(crate) struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A handle to a registered connection, the connection is deregistered on drop
+pub(crate) struct ConnectionHandle {
+    id: ConnectionId,
+    state: Arc<ConnectionState>,
+    registry: Arc<ConnectionRegistry>,
+}
+
+impl ConnectionHandle {
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    pub fn touch(&self) {
+        self.state
+            .last_activity
+            .store(self.registry.now(), Ordering::Release);
+    }
+
+    pub fn request_received(&self) {
+        self.state.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_busy(&self, busy: bool) {
+        self.state.busy.store(busy, Ordering::Release);
+        self.touch();
+    }
+
+    /// Resolves once the reaper decides this connection should be closed
+    pub async fn closed(&self) {
+        self.state.close.notified().await
+    }
+
+    /// Wraps an I/O stream so that every successful read or write counts as activity, and its
+    /// bytes are counted in the server metrics
+    pub fn track<S>(&self, inner: S) -> Tracked<'_, S> {
+        Tracked {
+            inner,
+            handle: self,
+        }
+    }
+}
+
+impl Drop for ConnectionHandle {
+    fn drop(&mut self) {
+        self.registry.metrics.connection_closed();
+        let mut conns = self.registry.conns.lock().unwrap();
+        conns.remove(&self.id);
+        if conns.is_empty() {
+            self.registry.emptied.notify_waiters();
+        }
+    }
+}
+
+/// A snapshot of an open connection, see [`ServerHandle::connections`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionStats {
+    pub id: ConnectionId,
+    /// None for Unix domain sockets, the source of the PROXY header if there is one
+    pub remote_addr: Option<SocketAddr>,
+    /// How long ago the connection was accepted
+    pub age: Duration,
+    /// How long ago the connection last read or wrote anything
+    pub idle: Duration,
+    /// Whether a request is being handled
+    pub busy: bool,
+    /// How many requests were received on the connection
+    pub requests: u64,
+}
+
+/// Live state of a server, for dashboards and for finding leaked connections, see
+/// [`crate::HttpServer::handle`]
+/// The handle doesn't keep the server alive, once the server is dropped it reports no
+/// connections
+#[derive(Clone)]
+pub struct ServerHandle {
+    registry: Weak<ConnectionRegistry>,
+    shutdown: ShutdownSignal,
+}
+
+impl ServerHandle {
+    pub(crate) fn new(registry: &Arc<ConnectionRegistry>, shutdown: ShutdownSignal) -> Self {
+        Self {
+            registry: Arc::downgrade(registry),
+            shutdown,
+        }
+    }
+
+    pub fn active_connections(&self) -> usize {
+        self.registry
+            .upgrade()
+            .map_or(0, |registry| registry.conns.lock().unwrap().len())
+    }
+
+    /// Requests being routed, pipelined requests routed together each count
+    pub fn in_flight_requests(&self) -> usize {
+        self.registry
+            .upgrade()
+            .map_or(0, |registry| registry.in_flight.load(Ordering::Relaxed))
+    }
+
+    /// The open connections, oldest first
+    pub fn connections(&self) -> Vec<ConnectionStats> {
+        let Some(registry) = self.registry.upgrade() else {
+            return Vec::new();
+        };
+        let now = registry.now();
+        let mut stats: Vec<_> = registry
+            .conns
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, state)| {
+                let last_activity = state.last_activity.load(Ordering::Acquire);
+                ConnectionStats {
+                    id,
+                    remote_addr: state.remote_addr,
+                    age: Duration::from_millis(now.saturating_sub(state.opened)),
+                    idle: Duration::from_millis(now.saturating_sub(last_activity)),
+                    busy: state.busy.load(Ordering::Acquire),
+                    requests: state.requests.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        stats.sort_unstable_by_key(|stats| stats.id);
+        stats
+    }
+
+    /// Closes every connection which is not handling a request, and has not read or written
+    /// anything for longer than `idle_for`, returns how many were closed
+    pub fn close_idle(&self, idle_for: Duration) -> usize {
+        self.registry
+            .upgrade()
+            .map_or(0, |registry| registry.reap_idle(idle_for))
+    }
+
+    /// Closes the connection, even in the middle of a response
+    /// Returns false if there is no such connection
+    pub fn close_connection(&self, id: ConnectionId) -> bool {
+        let Some(registry) = self.registry.upgrade() else {
+            return false;
+        };
+        match registry.conns.lock().unwrap().get(&id) {
+            Some(state) => {
+                state.close.notify_one();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the server is shutting down, and waiting for its connections to finish
+    pub fn is_draining(&self) -> bool {
+        self.shutdown.is_shutting_down()
+    }
+}
+
+pub(crate) struct Tracked<'a, S> {
+    inner: S,
+    handle: &'a ConnectionHandle,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Tracked<'_, S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if matches!(res, Poll::Ready(Ok(()))) && buf.filled().len() > before {
+            self.handle.touch();
+            self.handle
+                .registry
+                .metrics
+                .received(buf.filled().len() - before);
+        }
+        res
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Tracked<'_, S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = res
+            && n > 0
+        {
+            self.handle.touch();
+            self.handle.registry.metrics.sent(n);
+        }
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[tokio::test]
+    async fn reap_idle_connections() {
+        let clock = Arc::new(MockClock::new());
+        let registry = Arc::new(ConnectionRegistry::new(clock.clone(), Arc::default()));
+        let idle = registry.register(None);
+        let busy = registry.register(None);
+        let active = registry.register(None);
+        busy.set_busy(true);
+        assert_eq!(registry.conns.lock().unwrap().len(), 3);
+
+        clock.advance(Duration::from_secs(30));
+        active.touch();
+        assert_eq!(registry.reap_idle(Duration::from_secs(30)), 0);
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(registry.reap_idle(Duration::from_secs(30)), 1);
+        // The notification is stored, so the idle connection sees it once it waits
+        tokio::time::timeout(Duration::from_secs(1), idle.closed())
+            .await
+            .expect("idle connection should be signalled");
+
+        drop(idle);
+        assert_eq!(registry.conns.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reaper_follows_clock() {
+        let clock = Arc::new(MockClock::new());
+        let registry = Arc::new(ConnectionRegistry::new(clock.clone(), Arc::default()));
+        let idle = registry.register(None);
+        tokio::spawn(
+            registry
+                .clone()
+                .run_reaper(Duration::from_secs(5), Duration::from_secs(10)),
+        );
+        // Let the reaper start waiting for its first sweep
+        tokio::task::yield_now().await;
+
+        clock.advance(Duration::from_secs(11));
+        tokio::time::timeout(Duration::from_secs(1), idle.closed())
+            .await
+            .expect("idle connection should be reaped");
+    }
+}
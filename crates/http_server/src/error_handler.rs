@@ -0,0 +1,106 @@
+//! Rendering router errors as responses
+//!
+//! Every [`RouterError`] is turned into a response by the [`ErrorHandler`] of the server, so
+//! applications can give their errors a consistent body, such as a branded page or a JSON
+//! document, in one place
+
+use std::{fmt, sync::Arc};
+
+use crate::{
+    RouterError,
+    http::{
+        header::WWWAuthenticate,
+        request::Request,
+        response::{Response, ResponseBuilder},
+    },
+};
+
+/// Renders the response for a request which the router failed
+pub trait ErrorHandler: Send + Sync + 'static {
+    /// The status of the response should usually be [`RouterError::status_code`]
+    /// A 401 response must send the challenge of [`RouterError::Unauthorized`] in its
+    /// WWW-Authenticate field
+    fn render(&self, request: &Request, error: &RouterError) -> Response;
+}
+
+impl<F> ErrorHandler for F
+where
+    F: Fn(&Request, &RouterError) -> Response + Send + Sync + 'static,
+{
+    fn render(&self, request: &Request, error: &RouterError) -> Response {
+        self(request, error)
+    }
+}
+
+/// Responds with the status of the error and no body, so nothing about the error is revealed
+/// to the client, along with the challenge of [`RouterError::Unauthorized`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultErrorHandler;
+
+impl ErrorHandler for DefaultErrorHandler {
+    fn render(&self, request: &Request, error: &RouterError) -> Response {
+        let res = ResponseBuilder::from_req(request, error.status_code());
+        match error {
+            RouterError::Unauthorized(challenge) => res
+                .set_header::<WWWAuthenticate>(vec![challenge.clone()])
+                .build(),
+            _ => res.build(),
+        }
+    }
+}
+
+/// An [`ErrorHandler`] shared between the connections of a server
+#[derive(Clone)]
+pub struct SharedErrorHandler(Arc<dyn ErrorHandler>);
+
+impl SharedErrorHandler {
+    pub fn new(handler: impl ErrorHandler) -> Self {
+        Self(Arc::new(handler))
+    }
+}
+
+impl Default for SharedErrorHandler {
+    fn default() -> Self {
+        Self::new(DefaultErrorHandler)
+    }
+}
+
+impl fmt::Debug for SharedErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedErrorHandler")
+    }
+}
+
+impl ErrorHandler for SharedErrorHandler {
+    fn render(&self, request: &Request, error: &RouterError) -> Response {
+        self.0.render(request, error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        header::{Challenge, HeaderField},
+        method::Method,
+        response::StatusCode,
+    };
+
+    #[test]
+    fn default_challenge() {
+        let request = Request::new(Method::GET, "/");
+        let res = DefaultErrorHandler.render(
+            &request,
+            &RouterError::Unauthorized(Challenge::basic("admin")),
+        );
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            res.headers.get_header::<WWWAuthenticate>().unwrap(),
+            Some(vec![Challenge::basic("admin")])
+        );
+
+        let res = DefaultErrorHandler.render(&request, &RouterError::NotFound);
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        assert!(!res.headers.contains(&WWWAuthenticate::NAME));
+    }
+}
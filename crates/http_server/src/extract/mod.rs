@@ -0,0 +1,431 @@
+//! Extractors take what a handler needs out of the request, so a handler can be an async
+//! function of its parameters, turned into a [`Router`] with [`handler`]
+//!
+//! When an extractor fails, the handler is not called and the request fails instead, with
+//! 400 Bad Request for malformed input
+//! Extractors which read the body, such as [`Json`] and [`Form`], consume it, so a handler
+//! can only take one of them
+
+#[cfg(feature = "json")]
+mod de;
+
+use std::{future::Future, marker::PhantomData, net::SocketAddr};
+
+use crate::{
+    Router, RouterError,
+    http::{
+        header::HeaderField,
+        request::Request,
+        response::{Response, StatusCode},
+    },
+};
+
+/// A value taken from a request, such as a parameter of a [`Handler`]
+pub trait FromRequest: Sized + Send {
+    fn from_request(request: &Request) -> impl Future<Output = Result<Self, RouterError>> + Send;
+}
+
+/// An error from a misconfigured server rather than a bad request
+fn server_error(message: &str) -> RouterError {
+    RouterError::Custom(StatusCode::INTERNAL_SERVER_ERROR, message.to_owned())
+}
+
+/// Any extractor can be optional, it is `None` when the extractor fails
+impl<T: FromRequest> FromRequest for Option<T> {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        Ok(T::from_request(request).await.ok())
+    }
+}
+
+/// The whole request
+impl FromRequest for Request {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        Ok(request.clone())
+    }
+}
+
+/// A typed header, failing when it is missing or malformed
+/// ```ignore
+/// async fn upload(HeaderTyped(len): HeaderTyped<ContentLength>) -> Result<Response, RouterError>
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeaderTyped<H: HeaderField>(pub H::Output);
+
+impl<H: HeaderField> FromRequest for HeaderTyped<H>
+where
+    H::Output: Send,
+{
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        let name = String::from_utf8_lossy(H::NAME.as_bytes()).into_owned();
+        match request.headers.get_header::<H>() {
+            Ok(Some(value)) => Ok(Self(value)),
+            Ok(None) => Err(RouterError::BadRequest(format!("missing {name} header"))),
+            Err(_) => Err(RouterError::BadRequest(format!("malformed {name} header"))),
+        }
+    }
+}
+
+/// Shared state added to the request extensions by [`AddState`]
+///
+/// [`AddState`]: crate::middleware::AddState
+#[derive(Debug, Clone)]
+pub struct State<T>(pub T);
+
+impl<T: Clone + Send + Sync + 'static> FromRequest for State<T> {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        match request.extensions.get::<T>() {
+            Some(state) => Ok(Self(state.clone())),
+            None => Err(server_error("state was not added to the request")),
+        }
+    }
+}
+
+/// The address of the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoteAddr(pub SocketAddr);
+
+impl FromRequest for RemoteAddr {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        match request.remote {
+            Some(remote) => Ok(Self(remote)),
+            None => Err(server_error("the request has no remote address")),
+        }
+    }
+}
+
+/// The parameters matched from the request path, such as `id` in `/users/{id}`, which are
+/// added to the request extensions by the router which matched them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PathParams(Vec<(String, String)>);
+
+impl PathParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.0.push((name.into(), value.into()));
+    }
+
+    /// Sets a parameter, replacing any parameter with the same name
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.0.retain(|(param, _)| *param != name);
+        self.0.push((name, value.into()));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+impl<N: Into<String>, V: Into<String>> FromIterator<(N, V)> for PathParams {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+/// The path parameters, see [`PathParams`]
+/// A struct or map takes the parameters by name, a tuple takes them in order, and any other
+/// type takes the only parameter
+#[cfg(feature = "json")]
+#[derive(Debug, Clone)]
+pub struct Path<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned + Send> FromRequest for Path<T> {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        let Some(params) = request.extensions.get::<PathParams>() else {
+            return Err(server_error("the route has no path parameters"));
+        };
+        T::deserialize(de::Pairs(&params.0))
+            .map(Self)
+            .map_err(|err| RouterError::BadRequest(format!("invalid path parameters: {err}")))
+    }
+}
+
+/// The query string, decoded as `application/x-www-form-urlencoded`
+/// A request without a query has no parameters, rather than failing
+#[cfg(feature = "json")]
+#[derive(Debug, Clone)]
+pub struct Query<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned + Send> FromRequest for Query<T> {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        use crate::http::uri::form_urlencoded_decode;
+
+        let target = &request.target;
+        let query = match target.iter().position(|b| *b == b'?') {
+            Some(idx) => &target[idx + 1..],
+            None => &[][..],
+        };
+        let pairs = form_urlencoded_decode(query)
+            .map_err(|err| RouterError::BadRequest(format!("malformed query: {err}")))?;
+        T::deserialize(de::Pairs(&pairs))
+            .map(Self)
+            .map_err(|err| RouterError::BadRequest(format!("invalid query: {err}")))
+    }
+}
+
+/// The body as JSON, see [`Request::json`]
+#[cfg(feature = "json")]
+#[derive(Debug, Clone)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned + Send> FromRequest for Json<T> {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        Ok(Self(request.json().await?))
+    }
+}
+
+/// An `application/x-www-form-urlencoded` body, as sent by HTML forms
+#[cfg(feature = "json")]
+#[derive(Debug, Clone)]
+pub struct Form<T>(pub T);
+
+#[cfg(feature = "json")]
+impl<T> Form<T> {
+    /// The largest body which is read
+    pub const LIMIT: usize = 1024 * 1024;
+}
+
+#[cfg(feature = "json")]
+impl<T: serde::de::DeserializeOwned + Send> FromRequest for Form<T> {
+    async fn from_request(request: &Request) -> Result<Self, RouterError> {
+        use crate::http::{
+            header::{ContentType, MediaType},
+            uri::form_urlencoded_decode,
+        };
+
+        let content_type = request.headers.get_header::<ContentType>().ok().flatten();
+        let is_form = content_type
+            .and_then(|value| MediaType::parse(&value))
+            .is_some_and(|media| {
+                media.ty() == b"application" && media.subtype() == b"x-www-form-urlencoded"
+            });
+        if !is_form {
+            return Err(RouterError::Custom(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "expected a form content type".to_owned(),
+            ));
+        }
+        let body = request.body.collect(Some(Self::LIMIT)).await?;
+        let pairs = form_urlencoded_decode(&body)
+            .map_err(|err| RouterError::BadRequest(format!("malformed form: {err}")))?;
+        T::deserialize(de::Pairs(&pairs))
+            .map(Self)
+            .map_err(|err| RouterError::BadRequest(format!("invalid form: {err}")))
+    }
+}
+
+/// An async function whose parameters are all [`FromRequest`] extractors
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, request: &Request)
+    -> impl Future<Output = Result<Response, RouterError>> + Send;
+}
+
+macro_rules! impl_handler {
+    ($($arg:ident),*) => {
+        impl<F, Fut, $($arg,)*> Handler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Fut + Send + Sync + 'static,
+            Fut: Future<Output = Result<Response, RouterError>> + Send,
+            $($arg: FromRequest,)*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            async fn call(&self, request: &Request) -> Result<Response, RouterError> {
+                $(let $arg = $arg::from_request(request).await?;)*
+                self($($arg),*).await
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A);
+impl_handler!(A, B);
+impl_handler!(A, B, C);
+impl_handler!(A, B, C, D);
+impl_handler!(A, B, C, D, E);
+impl_handler!(A, B, C, D, E, G);
+
+/// A [`Handler`] as a [`Router`]
+pub struct HandlerRouter<H, Args> {
+    handler: H,
+    args: PhantomData<fn() -> Args>,
+}
+
+/// Turns an async function of extractors into a [`Router`]
+/// ```ignore
+/// async fn show(Path(id): Path<u64>, State(db): State<Db>) -> Result<Response, RouterError>
+///
+/// let router = AddState::new(handler(show), db);
+/// ```
+pub fn handler<H: Handler<Args>, Args>(handler: H) -> HandlerRouter<H, Args> {
+    HandlerRouter {
+        handler,
+        args: PhantomData,
+    }
+}
+
+impl<H: Handler<Args>, Args: 'static> Router for HandlerRouter<H, Args> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        self.handler.call(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{
+        http::{Body, header::ContentLength, request::RequestBuilder, response::ResponseBuilder},
+        middleware::AddState,
+    };
+
+    fn request(target: &'static str) -> Request {
+        RequestBuilder::get(target)
+            .remote("192.0.2.1:1000".parse().unwrap())
+            .build()
+    }
+
+    async fn status<R: Router>(router: &R, request: &Request) -> StatusCode {
+        match router.route(request).await {
+            Ok(res) => res.status,
+            Err(err) => err.status_code(),
+        }
+    }
+
+    #[tokio::test]
+    async fn extract_parameters() {
+        async fn greet(
+            State(greeting): State<Arc<str>>,
+            RemoteAddr(remote): RemoteAddr,
+            length: Option<HeaderTyped<ContentLength>>,
+        ) -> Result<Response, RouterError> {
+            let length = length.map_or(0, |HeaderTyped(length)| length);
+            let text = format!("{greeting} {} {length}", remote.ip());
+            Ok(ResponseBuilder::text(text).build())
+        }
+        let router = AddState::new(handler(greet), Arc::<str>::from("hello"));
+
+        let res = router.route(&request("/")).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "hello 192.0.2.1 0"));
+        let mut req = request("/");
+        req.headers
+            .entry(ContentLength::NAME)
+            .push(Bytes::from_static(b"5"));
+        let res = router.route(&req).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "hello 192.0.2.1 5"));
+
+        // Without the state the server is misconfigured
+        let res = handler(greet).route(&request("/")).await;
+        assert_eq!(
+            res.unwrap_err().status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        async fn length(
+            HeaderTyped(len): HeaderTyped<ContentLength>,
+        ) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::text(len.to_string()).build())
+        }
+        assert_eq!(
+            status(&handler(length), &request("/")).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn extract_serde() {
+        use std::collections::BTreeMap;
+
+        use crate::http::{HttpVersion, header::ContentType, method::Method};
+
+        async fn show(
+            Path((user, id)): Path<(String, u32)>,
+            Query(query): Query<BTreeMap<String, String>>,
+        ) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::text(format!("{user} {id} {query:?}")).build())
+        }
+        let mut req = request("/users/ann/7?sort=new+first&page=2");
+        req.extensions
+            .insert(PathParams::from_iter([("user", "ann"), ("id", "7")]));
+        let res = handler(show).route(&req).await.unwrap();
+        assert!(matches!(
+            res.body,
+            Body::Full(ref body) if body == r#"ann 7 {"page": "2", "sort": "new first"}"#
+        ));
+        req.extensions
+            .insert(PathParams::from_iter([("user", "ann"), ("id", "x")]));
+        assert_eq!(status(&handler(show), &req).await, StatusCode::BAD_REQUEST);
+
+        async fn page(Query(page): Query<BTreeMap<String, u32>>) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::text(format!("{page:?}")).build())
+        }
+        assert_eq!(
+            status(&handler(page), &request("/?page=2")).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&handler(page), &request("/?page=x")).await,
+            StatusCode::BAD_REQUEST
+        );
+
+        async fn submit(
+            Form(form): Form<BTreeMap<String, String>>,
+        ) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::text(form["name"].clone()).build())
+        }
+        let mut req = request("/");
+        req.method = Method::POST;
+        req.body = Body::Full(Bytes::from_static(b"name=J%C3%BCrgen+M"));
+        assert_eq!(
+            status(&handler(submit), &req).await,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        req.headers
+            .entry(ContentType::NAME)
+            .push(Bytes::from_static(b"application/x-www-form-urlencoded"));
+        let res = handler(submit).route(&req).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "Jürgen M"));
+
+        async fn create(Json(item): Json<BTreeMap<String, u32>>) -> Result<Response, RouterError> {
+            Ok(
+                ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::CREATED)
+                    .json(&item)?
+                    .build(),
+            )
+        }
+        let mut req = request("/");
+        req.headers
+            .entry(ContentType::NAME)
+            .push(Bytes::from_static(b"application/json"));
+        req.body = Body::Full(Bytes::from_static(br#"{"a":1}"#));
+        assert_eq!(status(&handler(create), &req).await, StatusCode::CREATED);
+        req.body = Body::Full(Bytes::from_static(b"{"));
+        assert_eq!(
+            status(&handler(create), &req).await,
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use std::{fmt::Write, io, path::Path, time::SystemTime};
+
+use crate::http::{HttpDate, json::write_json_string, uri::url_encode};
+
+/// The order of the entries of a directory listing, directories are always listed first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ListingSort {
+    #[default]
+    Name,
+    Modified,
+    Size,
+}
+
+impl ListingSort {
+    const ALL: [Self; 3] = [Self::Name, Self::Size, Self::Modified];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Modified => "modified",
+            Self::Size => "size",
+        }
+    }
+}
+
+/// Lists directories without an index file, see [`super::StaticFiles::with_listing`]
+/// Listings are HTML, or JSON for clients which accept `application/json`
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryListing {
+    /// The default order, clients can pick another with the `sort` and `order` query parameters
+    pub sort: ListingSort,
+    pub descending: bool,
+    /// Whether names starting with a dot are listed
+    pub show_hidden: bool,
+}
+
+impl DirectoryListing {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_sort(mut self, sort: ListingSort, descending: bool) -> Self {
+        self.sort = sort;
+        self.descending = descending;
+        self
+    }
+
+    pub fn with_hidden(mut self, show_hidden: bool) -> Self {
+        self.show_hidden = show_hidden;
+        self
+    }
+
+    /// The order picked by the query of a request, such as `sort=size&order=desc`
+    pub(super) fn order(&self, query: Option<&[u8]>) -> (ListingSort, bool) {
+        let (mut sort, mut descending) = (self.sort, self.descending);
+        for pair in query.unwrap_or_default().split(|b| *b == b'&') {
+            let split = pair.iter().position(|b| *b == b'=');
+            match split.map(|idx| (&pair[..idx], &pair[idx + 1..])) {
+                Some((b"sort", value)) => {
+                    if let Some(by) = ListingSort::ALL
+                        .into_iter()
+                        .find(|by| by.as_str().as_bytes() == value)
+                    {
+                        sort = by;
+                    }
+                }
+                Some((b"order", b"asc")) => descending = false,
+                Some((b"order", b"desc")) => descending = true,
+                _ => {}
+            }
+        }
+        (sort, descending)
+    }
+
+    /// Reads the entries of `dir`, leaving out symbolic links which resolve outside of `root`
+    pub(super) async fn read(&self, root: &Path, dir: &Path) -> io::Result<Vec<Entry>> {
+        let mut read = tokio::fs::read_dir(dir).await?;
+        let mut entries = Vec::new();
+        while let Some(entry) = read.next_entry().await? {
+            // A name which is not UTF-8 can't be linked to
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if !self.show_hidden && name.starts_with('.') {
+                continue;
+            }
+            if entry.file_type().await?.is_symlink() {
+                match tokio::fs::canonicalize(entry.path()).await {
+                    Ok(target) if target.starts_with(root) => {}
+                    _ => continue,
+                }
+            }
+            let Ok(metadata) = tokio::fs::metadata(entry.path()).await else {
+                continue;
+            };
+            entries.push(Entry {
+                name,
+                is_dir: metadata.is_dir(),
+                size: metadata.len(),
+                modified: metadata.modified().ok(),
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// A file or directory in a listing
+#[derive(Debug)]
+pub(super) struct Entry {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Entry {
+    /// The relative link to the entry
+    fn href(&self) -> String {
+        let mut href = url_encode(self.name.as_bytes());
+        if self.is_dir {
+            href.push('/');
+        }
+        href
+    }
+}
+
+pub(super) fn sort(entries: &mut [Entry], sort: ListingSort, descending: bool) {
+    entries.sort_by(|a, b| {
+        let by_name = || a.name.cmp(&b.name);
+        let ordering = match sort {
+            ListingSort::Name => by_name(),
+            ListingSort::Modified => a.modified.cmp(&b.modified).then_with(by_name),
+            ListingSort::Size => a.size.cmp(&b.size).then_with(by_name),
+        };
+        let ordering = match descending {
+            true => ordering.reverse(),
+            false => ordering,
+        };
+        b.is_dir.cmp(&a.is_dir).then(ordering)
+    });
+}
+
+/// Writes `text` escaped for HTML text and attribute values
+fn write_html(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+/// Renders the listing of the directory at the decoded request `path`
+pub(super) fn render_html(
+    path: &str,
+    entries: &[Entry],
+    sort: ListingSort,
+    descending: bool,
+) -> String {
+    let mut out = String::with_capacity(256 + entries.len() * 128);
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Index of ");
+    write_html(&mut out, path);
+    out.push_str("</title>\n</head>\n<body>\n<h1>Index of ");
+    write_html(&mut out, path);
+    out.push_str("</h1>\n<table>\n<tr>");
+    for (column, title) in ListingSort::ALL
+        .into_iter()
+        .zip(["Name", "Size", "Modified"])
+    {
+        // Picking the current column again reverses the order
+        let order = match column == sort && !descending {
+            true => "desc",
+            false => "asc",
+        };
+        write!(
+            out,
+            "<th><a href=\"?sort={}&amp;order={order}\">{title}</a></th>",
+            column.as_str()
+        )
+        .unwrap();
+    }
+    out.push_str("</tr>\n");
+    if path != "/" {
+        out.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n");
+    }
+    for entry in entries {
+        write!(out, "<tr><td><a href=\"{}\">", entry.href()).unwrap();
+        write_html(&mut out, &entry.name);
+        if entry.is_dir {
+            out.push('/');
+        }
+        out.push_str("</a></td><td>");
+        if !entry.is_dir {
+            write!(out, "{}", entry.size).unwrap();
+        }
+        out.push_str("</td><td>");
+        if let Some(modified) = entry.modified {
+            write!(out, "{}", HttpDate::from(modified)).unwrap();
+        }
+        out.push_str("</td></tr>\n");
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+/// Renders the listing as a JSON array, with the modification times as HTTP dates
+pub(super) fn render_json(entries: &[Entry]) -> String {
+    let mut out = String::with_capacity(2 + entries.len() * 128);
+    out.push('[');
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"name\":");
+        write_json_string(&mut out, entry.name.as_bytes());
+        write!(
+            out,
+            ",\"href\":\"{}\",\"dir\":{},\"size\":{},\"modified\":",
+            entry.href(),
+            entry.is_dir,
+            entry.size
+        )
+        .unwrap();
+        match entry.modified {
+            Some(modified) => write!(out, "\"{}\"", HttpDate::from(modified)).unwrap(),
+            None => out.push_str("null"),
+        }
+        out.push('}');
+    }
+    out.push(']');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn entry(name: &str, is_dir: bool, size: u64, modified: u64) -> Entry {
+        Entry {
+            name: name.to_owned(),
+            is_dir,
+            size,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(modified)),
+        }
+    }
+
+    #[test]
+    fn order_and_render() {
+        let listing = DirectoryListing::new();
+        assert_eq!(listing.order(None), (ListingSort::Name, false));
+        assert_eq!(
+            listing.order(Some(b"sort=size&order=desc")),
+            (ListingSort::Size, true)
+        );
+        assert_eq!(
+            listing.order(Some(b"sort=owner")),
+            (ListingSort::Name, false)
+        );
+
+        let mut entries = vec![
+            entry("b.txt", false, 1, 20),
+            entry("a <b>.txt", false, 3, 10),
+            entry("zz", true, 4096, 0),
+        ];
+        sort(&mut entries, ListingSort::Size, true);
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["zz", "a <b>.txt", "b.txt"]);
+        sort(&mut entries, ListingSort::Modified, false);
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["zz", "a <b>.txt", "b.txt"]);
+
+        let html = render_html("/docs/", &entries, ListingSort::Modified, false);
+        assert!(html.contains("<title>Index of /docs/</title>"));
+        assert!(html.contains("<a href=\"?sort=modified&amp;order=desc\">Modified</a>"));
+        assert!(html.contains("<a href=\"?sort=size&amp;order=asc\">Size</a>"));
+        assert!(html.contains("<a href=\"../\">"));
+        assert!(html.contains(
+            "<tr><td><a href=\"a%20%3Cb%3E.txt\">a &lt;b&gt;.txt</a></td><td>3</td>\
+             <td>Thu, 01 Jan 1970 00:00:10 GMT</td></tr>"
+        ));
+        assert!(html.contains("<a href=\"zz/\">zz/</a></td><td></td>"));
+        assert!(!render_html("/", &entries, ListingSort::Name, false).contains("../"));
+
+        assert_eq!(
+            render_json(&entries[1..2]),
+            "[{\"name\":\"a <b>.txt\",\"href\":\"a%20%3Cb%3E.txt\",\"dir\":false,\"size\":3,\
+             \"modified\":\"Thu, 01 Jan 1970 00:00:10 GMT\"}]"
+        );
+    }
+}
@@ -0,0 +1,440 @@
+mod listing;
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bytes::{Bytes, BytesMut};
+pub use listing::{DirectoryListing, ListingSort};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        Body, BodyError, BodyStream, HttpDate,
+        header::{
+            Allow, ContentLength, ContentType, ETag, EntityTag, LastModified, Location, MediaType,
+            Vary,
+        },
+        method::Method,
+        mime::content_type_for,
+        request::{Request, RequestTarget},
+        response::{Response, ResponseBuilder, StatusCode, conditional},
+    },
+};
+
+/// Serves the files below a root directory, with the request path mapped onto it
+/// Paths which could leave the root, such as ones containing `..`, are refused with 403
+/// Forbidden, as are symbolic links which resolve outside of it
+/// A directory is served by its index file, and is only listed when listings are enabled
+/// Only GET and HEAD are allowed, and files are streamed rather than read into memory
+pub struct StaticFiles {
+    root: PathBuf,
+    index: Option<String>,
+    listing: Option<DirectoryListing>,
+}
+
+impl StaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            index: Some("index.html".to_owned()),
+            listing: None,
+        }
+    }
+
+    /// The file served for a directory, `index.html` by default
+    /// Directories are refused with 403 Forbidden when there is no index
+    pub fn with_index(mut self, index: Option<&str>) -> Self {
+        self.index = index.map(str::to_owned);
+        self
+    }
+
+    /// Lists directories which have no index, instead of refusing them
+    /// Disabled by default, as a listing reveals every file below the root
+    pub fn with_listing(mut self, listing: Option<DirectoryListing>) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Maps a decoded request path onto the root, without touching the file system
+    fn map(&self, path: &str) -> Result<PathBuf, StatusCode> {
+        let mut mapped = self.root.clone();
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            // A decoded segment can contain anything, including separators of other platforms
+            if segment == "."
+                || segment == ".."
+                || segment.contains(['\\', '\0'])
+                || (cfg!(windows) && segment.contains(':'))
+            {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            mapped.push(segment);
+        }
+        Ok(mapped)
+    }
+
+    /// Resolves `path` to a file system path below the root, following symbolic links
+    async fn resolve(&self, root: &Path, path: &Path) -> Result<PathBuf, RouterError> {
+        let resolved = tokio::fs::canonicalize(path).await.map_err(io_error)?;
+        match resolved.starts_with(root) {
+            true => Ok(resolved),
+            false => Err(forbidden()),
+        }
+    }
+
+    async fn serve(&self, request: &Request, path: &str) -> Result<Response, RouterError> {
+        let mapped = self.map(path).map_err(|_| forbidden())?;
+        let root = tokio::fs::canonicalize(&self.root)
+            .await
+            .map_err(|err| RouterError::Generic(Box::new(err)))?;
+        let mut file = self.resolve(&root, &mapped).await?;
+        if tokio::fs::metadata(&file).await.map_err(io_error)?.is_dir() {
+            // Relative links in the index are resolved against the directory, so it has to be
+            // requested with a trailing slash
+            if !path.ends_with('/') {
+                let target = request.target.clone();
+                let (path, query) = match target.iter().position(|b| *b == b'?') {
+                    Some(idx) => target.split_at(idx),
+                    None => (&target[..], &[][..]),
+                };
+                let location = [path, b"/", query].concat();
+                return Ok(
+                    ResponseBuilder::from_req(request, StatusCode::MOVED_PERMANENTLY)
+                        .set_header::<Location>(Bytes::from(location))
+                        .build(),
+                );
+            }
+            let index = match &self.index {
+                Some(index) => self.resolve(&root, &file.join(index)).await,
+                None => Err(RouterError::NotFound),
+            };
+            file = match (index, &self.listing) {
+                (Err(RouterError::NotFound), Some(listing)) => {
+                    return self.list(request, listing, &root, &file, path).await;
+                }
+                (Err(RouterError::NotFound), None) => return Err(forbidden()),
+                (other, _) => other?,
+            };
+        }
+
+        // The metadata is taken from the opened file, so it matches what is read
+        let handle = tokio::fs::File::open(&file).await.map_err(io_error)?;
+        let metadata = handle.metadata().await.map_err(io_error)?;
+        // SPEC: RFC 9110 - 8.8.2.1. Last-Modified
+        let modified = metadata.modified().ok();
+        let etag = modified.map(|modified| EntityTag::from_metadata(modified, metadata.len()));
+        let last_modified = modified.map(HttpDate::from);
+        if let Some(res) = conditional(request, etag.as_ref(), last_modified) {
+            return Ok(res);
+        }
+
+        let name = file.file_name().map(|name| name.to_string_lossy());
+        let mut builder = ResponseBuilder::from_req(request, StatusCode::OK)
+            .set_header::<ContentType>(Bytes::from_static(
+                content_type_for(name.as_deref().unwrap_or_default()).as_bytes(),
+            ))
+            .set_header::<ContentLength>(metadata.len());
+        if let (Some(etag), Some(last_modified)) = (etag, last_modified) {
+            builder = builder
+                .set_header::<ETag>(etag)
+                .set_header::<LastModified>(last_modified);
+        }
+        let mut res = builder.build();
+        // SPEC: RFC 9110 - 9.3.2. HEAD
+        if request.method == Method::GET {
+            res.body = stream_file(handle, metadata.len());
+        }
+        Ok(res)
+    }
+
+    async fn list(
+        &self,
+        request: &Request,
+        listing: &DirectoryListing,
+        root: &Path,
+        dir: &Path,
+        path: &str,
+    ) -> Result<Response, RouterError> {
+        let mut entries = listing.read(root, dir).await.map_err(io_error)?;
+        let query = request
+            .target
+            .iter()
+            .position(|b| *b == b'?')
+            .map(|idx| &request.target[idx + 1..]);
+        let (sort, descending) = listing.order(query);
+        listing::sort(&mut entries, sort, descending);
+
+        // Browsers get HTML, unless they only accept JSON
+        let (media, body) = match request.negotiate(&[MediaType::html(), MediaType::json()]) {
+            Some(media) if media == MediaType::json() => (media, listing::render_json(&entries)),
+            _ => (
+                MediaType::html(),
+                listing::render_html(path, &entries, sort, descending),
+            ),
+        };
+        let mut res = ResponseBuilder::from_req(request, StatusCode::OK)
+            .set_header::<ContentType>(media.to_bytes())
+            .set_header::<Vary>(vec![Bytes::from_static(b"Accept")])
+            .build();
+        res.body = Body::Full(Bytes::from(body));
+        Ok(res)
+    }
+}
+
+/// Files are read and sent in chunks of this size, so they are never held in memory
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Streams the first `len` bytes of `file`, a file which is truncated while it is sent ends the
+/// body with an error, since fewer bytes than the Content-Length would be sent
+fn stream_file(file: tokio::fs::File, len: u64) -> Body {
+    let (tx, stream) = BodyStream::channel(1);
+    tokio::spawn(async move {
+        let mut file = file.take(len);
+        let mut remaining = len;
+        while remaining > 0 {
+            let mut buf = BytesMut::with_capacity(CHUNK_SIZE.min(remaining as usize));
+            match file.read_buf(&mut buf).await {
+                Ok(0) => {
+                    let err = io::Error::from(io::ErrorKind::UnexpectedEof);
+                    return tx.abort(BodyError::Encode(Arc::new(err))).await;
+                }
+                Ok(n) => remaining -= n as u64,
+                Err(err) => return tx.abort(BodyError::Encode(Arc::new(err))).await,
+            }
+            if tx.send(buf.freeze()).await.is_err() {
+                return;
+            }
+        }
+    });
+    Body::Stream(stream)
+}
+
+fn forbidden() -> RouterError {
+    RouterError::Custom(StatusCode::FORBIDDEN, "forbidden".to_owned())
+}
+
+fn io_error(err: io::Error) -> RouterError {
+    match err.kind() {
+        // A file in place of a directory is the same as a missing directory
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => RouterError::NotFound,
+        io::ErrorKind::PermissionDenied => forbidden(),
+        _ => RouterError::Generic(Box::new(err)),
+    }
+}
+
+impl Router for StaticFiles {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if request.method != Method::GET && request.method != Method::HEAD {
+            return Ok(
+                ResponseBuilder::from_req(request, StatusCode::METHOD_NOT_ALLOWED)
+                    .set_header::<Allow>(vec![
+                        Bytes::from_static(b"GET"),
+                        Bytes::from_static(b"HEAD"),
+                    ])
+                    .build(),
+            );
+        }
+        let Ok(RequestTarget::Origin(origin)) = request.target() else {
+            return Err(RouterError::BadRequest(
+                "expected an origin-form target".into(),
+            ));
+        };
+        let path = origin
+            .path()
+            .map_err(|_| RouterError::BadRequest("malformed path".into()))?;
+        self.serve(request, &path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header::{Accept, HeaderField, IfModifiedSince, IfNoneMatch};
+
+    /// A fresh directory below the system temporary directory
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("carbon-static-files-{}-{name}", std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn request(target: &'static str) -> Request {
+        Request::new(Method::GET, target)
+    }
+
+    async fn status(files: &StaticFiles, target: &'static str) -> StatusCode {
+        match files.route(&request(target)).await {
+            Ok(res) => res.status,
+            Err(err) => err.status_code(),
+        }
+    }
+
+    #[tokio::test]
+    async fn serves_files() {
+        let dir = temp_dir("serves");
+        std::fs::create_dir_all(dir.join("public/docs")).unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        std::fs::write(dir.join("public/index.html"), "<h1>home</h1>").unwrap();
+        std::fs::write(dir.join("public/app.css"), "body{}").unwrap();
+        let files = StaticFiles::new(dir.join("public"));
+
+        let res = files.route(&request("/app.css")).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(
+            res.headers.get(&ContentType::NAME).unwrap()[0],
+            "text/css; charset=utf-8"
+        );
+        assert_eq!(res.headers.get(&ContentLength::NAME).unwrap()[0], "6");
+        assert!(matches!(res.body, Body::Stream(_)));
+        assert_eq!(res.body.collect(None).await.unwrap(), "body{}");
+
+        // HEAD gets the same fields, without reading the file
+        let mut head = request("/app.css");
+        head.method = Method::HEAD;
+        let res = files.route(&head).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.headers.get(&ContentLength::NAME).unwrap()[0], "6");
+        assert!(matches!(res.body, Body::None));
+
+        let res = files.route(&request("/")).await.unwrap();
+        assert_eq!(res.body.collect(None).await.unwrap(), "<h1>home</h1>");
+        // Files larger than a chunk are sent in several
+        let large = vec![b'a'; CHUNK_SIZE * 2 + 1];
+        std::fs::write(dir.join("public/large.txt"), &large).unwrap();
+        let res = files.route(&request("/large.txt")).await.unwrap();
+        assert_eq!(res.body.collect(None).await.unwrap(), large);
+
+        let res = files.route(&request("/docs?page=2")).await.unwrap();
+        assert_eq!(res.status, StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(
+            res.headers.get(&Location::NAME).unwrap()[0],
+            "/docs/?page=2"
+        );
+        // A directory without an index is not listed
+        assert_eq!(status(&files, "/docs/").await, StatusCode::FORBIDDEN);
+        let files = files.with_index(None);
+        assert_eq!(status(&files, "/").await, StatusCode::FORBIDDEN);
+
+        assert_eq!(status(&files, "/missing.css").await, StatusCode::NOT_FOUND);
+        assert_eq!(status(&files, "/app.css/x").await, StatusCode::NOT_FOUND);
+        assert_eq!(
+            status(&files, "/../secret.txt").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&files, "/%2e%2e/secret.txt").await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(&files, "/docs/..%5c..%5csecret.txt").await,
+            StatusCode::FORBIDDEN
+        );
+
+        let mut post = request("/app.css");
+        post.method = Method::POST;
+        let res = files.route(&post).await.unwrap();
+        assert_eq!(res.status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            res.headers.get(&Allow::NAME).unwrap().collect(),
+            "GET, HEAD"
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn not_modified() {
+        let dir = temp_dir("conditional");
+        std::fs::write(dir.join("app.js"), "let a;").unwrap();
+        let files = StaticFiles::new(&dir);
+
+        let res = files.route(&request("/app.js")).await.unwrap();
+        let etag = res.headers.get_header::<ETag>().unwrap().unwrap();
+        assert!(etag.weak);
+        let last_modified = res.headers.get(&LastModified::NAME).unwrap()[0].clone();
+
+        let mut req = request("/app.js");
+        req.headers
+            .entry(IfNoneMatch::NAME)
+            .push(Bytes::from(etag.to_string()));
+        let res = files.route(&req).await.unwrap();
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+        assert!(matches!(res.body, Body::None));
+        assert_eq!(res.headers.get_header::<ETag>().unwrap(), Some(etag));
+
+        let mut req = request("/app.js");
+        req.headers.entry(IfModifiedSince::NAME).push(last_modified);
+        let res = files.route(&req).await.unwrap();
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+
+        // A changed size changes the tag, even within the same second
+        std::fs::write(dir.join("app.js"), "let ab;").unwrap();
+        let mut req = request("/app.js");
+        req.headers
+            .entry(IfNoneMatch::NAME)
+            .push(res.headers.get(&ETag::NAME).unwrap()[0].clone());
+        let res = files.route(&req).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn lists_directories() {
+        let dir = temp_dir("listing");
+        std::fs::create_dir_all(dir.join("docs/guides")).unwrap();
+        std::fs::write(dir.join("docs/a b.txt"), "ab").unwrap();
+        std::fs::write(dir.join("docs/.hidden"), "").unwrap();
+        let files = StaticFiles::new(&dir);
+        assert_eq!(status(&files, "/docs/").await, StatusCode::FORBIDDEN);
+
+        let files = files.with_listing(Some(DirectoryListing::new()));
+        let res = files.route(&request("/docs/")).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        let Body::Full(body) = res.body else {
+            panic!("expected a full body");
+        };
+        let html = std::str::from_utf8(&body).unwrap();
+        assert!(html.contains("<a href=\"guides/\">guides/</a>"));
+        assert!(html.contains("<a href=\"a%20b.txt\">a b.txt</a></td><td>2</td>"));
+        assert!(!html.contains(".hidden"));
+
+        let mut req = request("/docs/?sort=size&order=desc");
+        req.headers
+            .entry(Accept::NAME)
+            .push(Bytes::from_static(b"application/json; q=0.9"));
+        let res = files.route(&req).await.unwrap();
+        assert_eq!(
+            res.headers.get(&ContentType::NAME).unwrap()[0],
+            "application/json"
+        );
+        let Body::Full(body) = res.body else {
+            panic!("expected a full body");
+        };
+        assert!(body.starts_with(b"[{\"name\":\"guides\",\"href\":\"guides/\",\"dir\":true,"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_stay_below_root() {
+        let dir = temp_dir("symlinks");
+        std::fs::create_dir_all(dir.join("public")).unwrap();
+        std::fs::write(dir.join("secret.txt"), "secret").unwrap();
+        std::fs::write(dir.join("public/a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.txt"), dir.join("public/secret.txt")).unwrap();
+        std::os::unix::fs::symlink(dir.join("public/a.txt"), dir.join("public/b.txt")).unwrap();
+        let files = StaticFiles::new(dir.join("public"));
+
+        assert_eq!(status(&files, "/secret.txt").await, StatusCode::FORBIDDEN);
+        assert_eq!(status(&files, "/b.txt").await, StatusCode::OK);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+//! Base64, for the byte sequences of structured fields, credentials and cookies
+//! SPEC: RFC 4648 - 4. Base 64 Encoding, 5. Base 64 Encoding with URL and Filename Safe Alphabet
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Alphabet {
+    /// `+` and `/`, with padding
+    Standard,
+    /// `-` and `_`, without padding
+    #[cfg_attr(not(feature = "secure-cookies"), allow(dead_code))]
+    UrlSafe,
+}
+
+impl Alphabet {
+    fn table(self) -> &'static [u8; 64] {
+        match self {
+            Self::Standard => b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/",
+            Self::UrlSafe => b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_",
+        }
+    }
+}
+
+pub(crate) fn encode(data: &[u8], alphabet: Alphabet) -> String {
+    let table = alphabet.table();
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(table[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else if alphabet == Alphabet::Standard {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes with or without padding, returning `None` for characters outside the alphabet or
+/// a truncated final group
+pub(crate) fn decode(data: &[u8], alphabet: Alphabet) -> Option<Vec<u8>> {
+    let table = alphabet.table();
+    let end = data.iter().rposition(|b| *b != b'=').map_or(0, |i| i + 1);
+    if data.len() - end > 2 {
+        return None;
+    }
+    let data = &data[..end];
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    for chunk in data.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, b) in chunk.iter().enumerate() {
+            let value = table.iter().position(|c| c == b)? as u32;
+            n |= value << (18 - 6 * i);
+        }
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_base64() {
+        let base64 = |data: &[u8]| encode(data, Alphabet::Standard);
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(&[0xfb, 0xff], Alphabet::UrlSafe), "-_8");
+    }
+
+    #[test]
+    fn decode_base64() {
+        for data in [
+            &b""[..],
+            b"f",
+            b"fo",
+            b"foo",
+            b"foob",
+            b"fooba",
+            b"foobar",
+            &[0xfb, 0xff],
+        ] {
+            for alphabet in [Alphabet::Standard, Alphabet::UrlSafe] {
+                let encoded = encode(data, alphabet);
+                assert_eq!(decode(encoded.as_bytes(), alphabet).as_deref(), Some(data));
+            }
+        }
+        assert_eq!(decode(b"Zm8", Alphabet::Standard).unwrap(), b"fo");
+        assert_eq!(decode(b"Z", Alphabet::Standard), None);
+        assert_eq!(decode(b"Zm9v!", Alphabet::Standard), None);
+        assert_eq!(decode(b"-_8", Alphabet::Standard), None);
+    }
+}
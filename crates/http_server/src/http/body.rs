@@ -0,0 +1,192 @@
+use std::sync::{Arc, OnceLock};
+
+use bytes::{Bytes, BytesMut};
+use tokio::sync::{Mutex, mpsc};
+
+use crate::http::{header::HeaderMap, response::StatusCode};
+
+/// Message Body
+/// SPEC: RFC 9112 - 6. Message Body
+/// OBNF: message-body = *OCTET
+#[derive(Debug, Clone)]
+pub enum Body {
+    None,
+    Full(Bytes),
+    Stream(BodyStream),
+}
+
+impl Body {
+    /// Reads the entire body into memory, failing if it is larger than `limit`
+    pub async fn collect(&self, limit: Option<usize>) -> Result<Bytes, BodyError> {
+        match self {
+            Self::None => Ok(Bytes::new()),
+            Self::Full(bytes) => match limit {
+                Some(limit) if bytes.len() > limit => Err(BodyError::LimitExceeded { limit }),
+                _ => Ok(bytes.clone()),
+            },
+            Self::Stream(stream) => stream.collect(limit).await,
+        }
+    }
+}
+
+/// An error while consuming a message body, these are distinct from [`HttpParseError`]s as the
+/// message head has already been handed to the router when they occur
+///
+/// [`HttpParseError`]: crate::http::parser::HttpParseError
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum BodyError {
+    #[error("body limit exceeded (limit: {limit})")]
+    LimitExceeded { limit: usize },
+    #[error("failed to decode body: {0}")]
+    Decode(Arc<dyn std::error::Error + Send + Sync>),
+    /// A streamed response body could not be produced
+    #[error("failed to encode body: {0}")]
+    Encode(Arc<dyn std::error::Error + Send + Sync>),
+    #[error("timed out reading body")]
+    TimedOut,
+    #[error("client disconnected")]
+    ClientDisconnected,
+}
+
+impl BodyError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::LimitExceeded { .. } => StatusCode::CONTENT_TOO_LARGE,
+            Self::Decode(_) => StatusCode::BAD_REQUEST,
+            Self::Encode(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::TimedOut => StatusCode::REQUEST_TIMEOUT,
+            // The client will most likely never see this
+            Self::ClientDisconnected => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+type Chunk = Result<Bytes, BodyError>;
+
+/// A body which is received in chunks, the stream can be shared, but each chunk is only
+/// received once
+#[derive(Clone)]
+pub struct BodyStream {
+    rx: Arc<Mutex<mpsc::Receiver<Chunk>>>,
+    trailers: Arc<OnceLock<HeaderMap>>,
+}
+
+impl std::fmt::Debug for BodyStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("BodyStream")
+    }
+}
+
+impl BodyStream {
+    /// Creates a new stream, and the sender which feeds it
+    /// `capacity` is the number of chunks buffered before the sender waits for the receiver
+    pub fn channel(capacity: usize) -> (BodySender, BodyStream) {
+        let (tx, rx) = mpsc::channel(capacity);
+        let trailers = Arc::new(OnceLock::new());
+        (
+            BodySender {
+                tx,
+                trailers: trailers.clone(),
+            },
+            BodyStream {
+                rx: Arc::new(Mutex::new(rx)),
+                trailers,
+            },
+        )
+    }
+
+    /// The trailer fields sent after the body, only available once the stream has ended
+    /// SPEC: RFC 9110 - 6.5. Trailer Fields
+    pub fn trailers(&self) -> Option<&HeaderMap> {
+        self.trailers.get()
+    }
+
+    /// Receives the next chunk, returns None once the body is complete
+    pub async fn next_chunk(&self) -> Option<Result<Bytes, BodyError>> {
+        self.rx.lock().await.recv().await
+    }
+
+    /// Reads the remaining body into memory, failing if it is larger than `limit`
+    pub async fn collect(&self, limit: Option<usize>) -> Result<Bytes, BodyError> {
+        let mut rx = self.rx.lock().await;
+        let mut buf = BytesMut::new();
+        while let Some(chunk) = rx.recv().await {
+            let chunk = chunk?;
+            if let Some(limit) = limit
+                && buf.len() + chunk.len() > limit
+            {
+                return Err(BodyError::LimitExceeded { limit });
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf.freeze())
+    }
+}
+
+/// The sending half of a [`BodyStream`]
+pub struct BodySender {
+    tx: mpsc::Sender<Chunk>,
+    trailers: Arc<OnceLock<HeaderMap>>,
+}
+
+impl BodySender {
+    /// Sends a chunk, fails if the receiving [`BodyStream`] has been dropped
+    pub async fn send(&self, chunk: Bytes) -> Result<(), BodyError> {
+        self.tx
+            .send(Ok(chunk))
+            .await
+            .map_err(|_| BodyError::ClientDisconnected)
+    }
+
+    /// Sets the trailer fields of the body, only the first call has an effect
+    pub fn set_trailers(&self, trailers: HeaderMap) {
+        _ = self.trailers.set(trailers);
+    }
+
+    /// Aborts the stream with an error, which will be the last chunk received
+    pub async fn abort(self, err: BodyError) {
+        _ = self.tx.send(Err(err)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stream_collect() {
+        let (tx, stream) = BodyStream::channel(4);
+        tokio::spawn(async move {
+            tx.send(Bytes::from_static(b"hello ")).await.unwrap();
+            tx.send(Bytes::from_static(b"world")).await.unwrap();
+        });
+        let body = Body::Stream(stream);
+        assert_eq!(body.collect(None).await.unwrap(), "hello world");
+    }
+
+    #[tokio::test]
+    async fn stream_collect_limit() {
+        let (tx, stream) = BodyStream::channel(4);
+        tokio::spawn(async move {
+            tx.send(Bytes::from_static(b"hello ")).await.unwrap();
+            tx.send(Bytes::from_static(b"world")).await.unwrap();
+        });
+        assert!(matches!(
+            stream.collect(Some(8)).await,
+            Err(BodyError::LimitExceeded { limit: 8 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn stream_abort() {
+        let (tx, stream) = BodyStream::channel(4);
+        tx.send(Bytes::from_static(b"partial")).await.unwrap();
+        tx.abort(BodyError::TimedOut).await;
+        assert_eq!(stream.next_chunk().await.unwrap().unwrap(), "partial");
+        assert!(matches!(
+            stream.next_chunk().await,
+            Some(Err(BodyError::TimedOut))
+        ));
+        assert!(stream.next_chunk().await.is_none());
+    }
+}
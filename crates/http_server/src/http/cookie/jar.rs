@@ -0,0 +1,296 @@
+use bytes::Bytes;
+use ring::{
+    aead::{Aad, CHACHA20_POLY1305, LessSafeKey, NONCE_LEN, Nonce, UnboundKey},
+    hkdf, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::http::{
+    base64::{self, Alphabet},
+    cookie::{Cookies, SetCookie},
+    header::{self, HeaderField, HeaderMap, HeaderValueTrait},
+};
+
+/// A server secret is too short to derive cookie keys from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("cookie secret must be at least {} bytes", Key::MIN_SECRET_LEN)]
+pub struct ShortSecret;
+
+/// The keys which sign and encrypt cookies, derived from one server secret
+/// Every server which shares the secret can read the cookies of the others
+pub struct Key {
+    signing: hmac::Key,
+    encryption: LessSafeKey,
+}
+
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key")
+    }
+}
+
+impl Key {
+    pub const MIN_SECRET_LEN: usize = 32;
+
+    /// Panics if the secret is shorter than [`Self::MIN_SECRET_LEN`], see [`Self::try_derive`]
+    pub fn derive(secret: &[u8]) -> Self {
+        Self::try_derive(secret).expect("invalid cookie secret")
+    }
+
+    /// Derives independent signing and encryption keys from `secret`
+    /// SPEC: RFC 5869 - HMAC-based Extract-and-Expand Key Derivation Function
+    pub fn try_derive(secret: &[u8]) -> Result<Self, ShortSecret> {
+        if secret.len() < Self::MIN_SECRET_LEN {
+            return Err(ShortSecret);
+        }
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, b"carbon-http-server cookie").extract(secret);
+        let signing = prk
+            .expand(&[b"signing"], hmac::HMAC_SHA256)
+            .expect("output length is valid")
+            .into();
+        let encryption: UnboundKey = prk
+            .expand(&[b"encryption"], &CHACHA20_POLY1305)
+            .expect("output length is valid")
+            .into();
+        Ok(Self {
+            signing,
+            encryption: LessSafeKey::new(encryption),
+        })
+    }
+
+    /// A random key, cookies signed or encrypted with it can't be read after a restart
+    pub fn generate() -> Self {
+        let mut secret = [0; 64];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .expect("system random is available");
+        Self::derive(&secret)
+    }
+}
+
+/// The cookies of a request, and the changes to send back with the response
+/// Cookies added through [`Self::signed`] can be read but not altered by the client, and
+/// cookies added through [`Self::private`] can be neither read nor altered
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Cookies,
+    delta: Vec<SetCookie>,
+}
+
+impl CookieJar {
+    /// The cookies sent with a request, an unparsable Cookie field is treated as empty
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        Self {
+            cookies: headers
+                .get_header::<header::Cookie>()
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+            delta: Vec::new(),
+        }
+    }
+
+    /// The value of a cookie, including any added to the jar
+    pub fn get(&self, name: &[u8]) -> Option<&Bytes> {
+        match self.delta.iter().rfind(|cookie| cookie.name == name) {
+            Some(cookie) if cookie.max_age == Some(std::time::Duration::ZERO) => None,
+            Some(cookie) => Some(&cookie.value),
+            None => self.cookies.get(name),
+        }
+    }
+
+    pub fn add(&mut self, cookie: SetCookie) {
+        self.delta.push(cookie);
+    }
+
+    /// Removes a cookie from the client, with the default path and domain
+    pub fn remove(&mut self, name: impl Into<Bytes>) {
+        self.delta.push(SetCookie::removal(name));
+    }
+
+    pub fn signed<'a>(&'a mut self, key: &'a Key) -> SignedJar<'a> {
+        SignedJar { jar: self, key }
+    }
+
+    pub fn private<'a>(&'a mut self, key: &'a Key) -> PrivateJar<'a> {
+        PrivateJar { jar: self, key }
+    }
+
+    /// The cookies added or removed since the jar was created
+    pub fn delta(&self) -> &[SetCookie] {
+        &self.delta
+    }
+
+    /// Adds a Set-Cookie field line for every change to the response headers
+    pub fn write_to(self, headers: &mut HeaderMap) {
+        if !self.delta.is_empty() {
+            self.delta
+                .to_header_value(headers.entry(header::SetCookie::NAME));
+        }
+    }
+}
+
+/// Cookies whose values are authenticated with HMAC-SHA256
+/// The value is sent as `base64url(tag) "." value`, the tag also covers the name, so a signed
+/// value can't be moved to another cookie
+pub struct SignedJar<'a> {
+    jar: &'a mut CookieJar,
+    key: &'a Key,
+}
+
+impl SignedJar<'_> {
+    /// The verified value of a cookie, `None` if it is missing or was tampered with
+    pub fn get(&self, name: &[u8]) -> Option<Bytes> {
+        let raw = self.jar.get(name)?;
+        let dot = raw.iter().position(|b| *b == b'.')?;
+        let tag = base64::decode(&raw[..dot], Alphabet::UrlSafe)?;
+        let value = raw.slice(dot + 1..);
+        hmac::verify(&self.key.signing, &[name, b"=", &value].concat(), &tag).ok()?;
+        Some(value)
+    }
+
+    pub fn add(&mut self, mut cookie: SetCookie) {
+        let tag = hmac::sign(
+            &self.key.signing,
+            &[&cookie.name[..], b"=", &cookie.value].concat(),
+        );
+        let tag = base64::encode(tag.as_ref(), Alphabet::UrlSafe);
+        cookie.value = [tag.as_bytes(), b".", &cookie.value].concat().into();
+        self.jar.add(cookie);
+    }
+
+    pub fn remove(&mut self, name: impl Into<Bytes>) {
+        self.jar.remove(name);
+    }
+}
+
+/// Cookies whose values are encrypted and authenticated with ChaCha20-Poly1305
+/// The value is sent as `base64url(nonce ciphertext tag)`, the name is authenticated data, so
+/// an encrypted value can't be moved to another cookie
+pub struct PrivateJar<'a> {
+    jar: &'a mut CookieJar,
+    key: &'a Key,
+}
+
+impl PrivateJar<'_> {
+    /// The decrypted value of a cookie, `None` if it is missing or was tampered with
+    pub fn get(&self, name: &[u8]) -> Option<Bytes> {
+        let raw = base64::decode(self.jar.get(name)?, Alphabet::UrlSafe)?;
+        if raw.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, sealed) = raw.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut sealed = sealed.to_vec();
+        let value = self
+            .key
+            .encryption
+            .open_in_place(nonce, Aad::from(name), &mut sealed)
+            .ok()?;
+        Some(Bytes::copy_from_slice(value))
+    }
+
+    /// Encrypts the value of `cookie`, panics if the system random generator fails
+    pub fn add(&mut self, mut cookie: SetCookie) {
+        let mut nonce = [0; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .expect("system random is available");
+        let mut sealed = cookie.value.to_vec();
+        self.key
+            .encryption
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(&cookie.name[..]),
+                &mut sealed,
+            )
+            .expect("cookie is small enough to encrypt");
+        let raw = [&nonce[..], &sealed].concat();
+        cookie.value = Bytes::from(base64::encode(&raw, Alphabet::UrlSafe));
+        self.jar.add(cookie);
+    }
+
+    pub fn remove(&mut self, name: impl Into<Bytes>) {
+        self.jar.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header::{Builtin, HeaderName};
+
+    const SECRET: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    /// The jar a client would send back after receiving `jar`
+    fn round_trip(jar: CookieJar) -> CookieJar {
+        let cookie = jar
+            .delta()
+            .iter()
+            .map(|cookie| {
+                format!(
+                    "{}={}",
+                    String::from_utf8_lossy(&cookie.name),
+                    String::from_utf8_lossy(&cookie.value)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        let mut headers = HeaderMap::new();
+        headers
+            .entry(HeaderName::builtin(Builtin::Cookie))
+            .push(Bytes::from(cookie));
+        CookieJar::from_headers(&headers)
+    }
+
+    #[test]
+    fn signed() {
+        let key = Key::derive(SECRET);
+        let mut jar = CookieJar::default();
+        jar.signed(&key).add(SetCookie::new("user", "42"));
+        let raw = jar.delta()[0].value.clone();
+        assert!(raw.ends_with(b".42"));
+
+        let mut received = round_trip(jar);
+        assert_eq!(received.signed(&key).get(b"user").unwrap(), "42");
+        assert_eq!(received.signed(&Key::generate()).get(b"user"), None);
+
+        // Altering the value, or moving it to another cookie, breaks the signature
+        let tampered = [&raw[..raw.len() - 2], b"43"].concat();
+        let mut jar = CookieJar::default();
+        jar.add(SetCookie::new("user", tampered));
+        jar.add(SetCookie::new("admin", raw));
+        let mut received = round_trip(jar);
+        assert_eq!(received.signed(&key).get(b"user"), None);
+        assert_eq!(received.signed(&key).get(b"admin"), None);
+    }
+
+    #[test]
+    fn private() {
+        let key = Key::derive(SECRET);
+        let mut jar = CookieJar::default();
+        jar.private(&key)
+            .add(SetCookie::new("flash", "saved").http_only());
+        let raw = jar.delta()[0].value.clone();
+        assert!(!raw.windows(5).any(|window| window == b"saved"));
+
+        let mut received = round_trip(jar);
+        assert_eq!(received.private(&key).get(b"flash").unwrap(), "saved");
+        assert_eq!(
+            received.private(&Key::derive(&[b'x'; 32])).get(b"flash"),
+            None
+        );
+
+        let mut jar = CookieJar::default();
+        jar.add(SetCookie::new("other", raw));
+        let mut received = round_trip(jar);
+        assert_eq!(received.private(&key).get(b"other"), None);
+
+        received.remove("flash");
+        assert_eq!(received.get(b"flash"), None);
+        let mut headers = HeaderMap::new();
+        received.write_to(&mut headers);
+        assert_eq!(headers.get(&header::SetCookie::NAME).unwrap().len(), 1);
+        assert_eq!(Key::try_derive(b"short").unwrap_err(), ShortSecret);
+    }
+}
@@ -0,0 +1,398 @@
+//! Cookies
+//! SPEC: RFC 6265 - HTTP State Management Mechanism
+
+use std::{collections::HashMap, fmt, time::Duration};
+
+#[cfg(feature = "secure-cookies")]
+mod jar;
+#[cfg(feature = "secure-cookies")]
+pub use jar::{CookieJar, Key, PrivateJar, ShortSecret, SignedJar};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::http::{
+    date::HttpDate,
+    header::{HeaderParseError, HeaderValue, HeaderValueTrait},
+    parser::is_tchar,
+};
+
+/// ABNF: cookie-octet = %x21 / %x23-2B / %x2D-3A / %x3C-5B / %x5D-7E
+fn is_cookie_octet(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+/// Splits at the first '='
+fn split_pair(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let eq = bytes.iter().position(|b| *b == b'=')?;
+    Some((&bytes[..eq], &bytes[eq + 1..]))
+}
+
+/// ABNF: cookie-value = *cookie-octet / ( DQUOTE *cookie-octet DQUOTE )
+fn unquote(value: &[u8]) -> &[u8] {
+    value
+        .strip_prefix(b"\"")
+        .and_then(|value| value.strip_suffix(b"\""))
+        .unwrap_or(value)
+}
+
+/// The cookies sent with a request, by name
+/// When a name is sent more than once the first value is kept, user agents send the cookie
+/// with the longest path first
+/// SPEC: RFC 6265 - 5.4. The Cookie Header
+/// ABNF: cookie-string = cookie-pair *( ";" SP cookie-pair )
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cookies {
+    map: HashMap<Bytes, Bytes>,
+}
+
+impl Cookies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, name: &[u8]) -> Option<&Bytes> {
+        self.map.get(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Bytes, &Bytes)> {
+        self.map.iter()
+    }
+
+    /// Adds the pairs of a Cookie field line, pairs which are not `name=value` are skipped
+    fn extend_from_line(&mut self, line: &Bytes) {
+        for pair in line.split(|b| *b == b';') {
+            let Some((name, value)) = split_pair(pair.trim_ascii()) else {
+                continue;
+            };
+            if name.is_empty() || !name.iter().copied().all(is_tchar) {
+                continue;
+            }
+            self.map
+                .entry(line.slice_ref(name))
+                .or_insert_with(|| line.slice_ref(unquote(value)));
+        }
+    }
+}
+
+/// Cookies may be split over multiple field lines by HTTP/2, the lines are combined
+impl HeaderValueTrait for Cookies {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let mut cookies = Self::new();
+        for line in value.iter() {
+            cookies.extend_from_line(line);
+        }
+        Ok(cookies)
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        let mut line = BytesMut::new();
+        for (name, val) in self.map {
+            if !line.is_empty() {
+                line.put_slice(b"; ");
+            }
+            line.put_slice(&name);
+            line.put_u8(b'=');
+            line.put_slice(&val);
+        }
+        value.push(line.freeze());
+    }
+}
+
+/// Whether a cookie is sent with cross-site requests
+/// SPEC: draft-ietf-httpbis-rfc6265bis - 4.1.2.7. The SameSite Attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl SameSite {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        }
+    }
+}
+
+/// A cookie name or value, or attribute value, which can't be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidCookie {
+    #[error("cookie name is not a token")]
+    Name,
+    #[error("cookie value contains invalid characters")]
+    Value,
+    #[error("cookie attribute contains ';' or control characters")]
+    Attribute,
+}
+
+/// A cookie for the Set-Cookie response header
+/// Each cookie is sent in its own field line, since the Expires attribute contains a comma
+/// SPEC: RFC 6265 - 4.1. Set-Cookie
+/// ABNF: set-cookie-string = cookie-pair *( ";" SP cookie-av )
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetCookie {
+    name: Bytes,
+    value: Bytes,
+    expires: Option<HttpDate>,
+    max_age: Option<Duration>,
+    domain: Option<Bytes>,
+    path: Option<Bytes>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+impl SetCookie {
+    /// Panics if the name is not a token or the value contains invalid characters, see
+    /// [`Self::try_new`]
+    pub fn new(name: impl Into<Bytes>, value: impl Into<Bytes>) -> Self {
+        Self::try_new(name, value).expect("invalid cookie")
+    }
+
+    pub fn try_new(name: impl Into<Bytes>, value: impl Into<Bytes>) -> Result<Self, InvalidCookie> {
+        let (name, value) = (name.into(), value.into());
+        if name.is_empty() || !name.iter().copied().all(is_tchar) {
+            return Err(InvalidCookie::Name);
+        }
+        if !value.iter().copied().all(is_cookie_octet) {
+            return Err(InvalidCookie::Value);
+        }
+        Ok(Self {
+            name,
+            value,
+            expires: None,
+            max_age: None,
+            domain: None,
+            path: None,
+            secure: false,
+            http_only: false,
+            same_site: None,
+        })
+    }
+
+    /// A cookie which removes the cookie `name` from the user agent, its path and domain
+    /// must match the cookie being removed
+    pub fn removal(name: impl Into<Bytes>) -> Self {
+        let mut cookie = Self::new(name, Bytes::new());
+        cookie.max_age = Some(Duration::ZERO);
+        cookie.expires = Some(HttpDate::from(std::time::UNIX_EPOCH));
+        cookie
+    }
+
+    pub fn name(&self) -> &Bytes {
+        &self.name
+    }
+
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+
+    pub fn expires(mut self, expires: HttpDate) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Takes precedence over Expires
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Panics if the domain contains ';' or control characters
+    pub fn domain(mut self, domain: impl Into<Bytes>) -> Self {
+        self.domain = Some(Self::attribute(domain.into()));
+        self
+    }
+
+    /// Panics if the path contains ';' or control characters
+    pub fn path(mut self, path: impl Into<Bytes>) -> Self {
+        self.path = Some(Self::attribute(path.into()));
+        self
+    }
+
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    pub fn http_only(mut self) -> Self {
+        self.http_only = true;
+        self
+    }
+
+    /// `SameSite::None` also sets Secure, since user agents reject it otherwise
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self.secure |= same_site == SameSite::None;
+        self
+    }
+
+    /// ABNF: av-octet = %x20-3A / %x3C-7E
+    fn attribute(value: Bytes) -> Bytes {
+        if !value.iter().all(|b| matches!(b, 0x20..=0x3A | 0x3C..=0x7E)) {
+            panic!("{}", InvalidCookie::Attribute);
+        }
+        value
+    }
+
+    /// Parses a Set-Cookie field line, unknown and malformed attributes are ignored
+    /// SPEC: RFC 6265 - 5.2. The Set-Cookie Header
+    pub fn parse(line: &Bytes) -> Option<Self> {
+        let mut parts = line.split(|b| *b == b';');
+        let (name, value) = split_pair(parts.next()?.trim_ascii())?;
+        let mut cookie = Self::try_new(
+            line.slice_ref(name.trim_ascii()),
+            line.slice_ref(unquote(value.trim_ascii())),
+        )
+        .ok()?;
+        for av in parts {
+            let (name, value) = match split_pair(av) {
+                Some((name, value)) => (name.trim_ascii(), value.trim_ascii()),
+                None => (av.trim_ascii(), &b""[..]),
+            };
+            let text = std::str::from_utf8(value).ok();
+            match name.to_ascii_lowercase().as_slice() {
+                b"expires" => {
+                    cookie.expires = text.and_then(|text| text.parse().ok()).or(cookie.expires)
+                }
+                b"max-age" => {
+                    cookie.max_age = match text.and_then(|text| text.parse::<i64>().ok()) {
+                        Some(secs) => Some(Duration::from_secs(secs.max(0) as u64)),
+                        None => cookie.max_age,
+                    }
+                }
+                b"domain" if !value.is_empty() => cookie.domain = Some(line.slice_ref(value)),
+                b"path" if value.starts_with(b"/") => cookie.path = Some(line.slice_ref(value)),
+                b"secure" => cookie.secure = true,
+                b"httponly" => cookie.http_only = true,
+                b"samesite" => {
+                    cookie.same_site = [SameSite::Strict, SameSite::Lax, SameSite::None]
+                        .into_iter()
+                        .find(|same_site| value.eq_ignore_ascii_case(same_site.as_str().as_bytes()))
+                }
+                _ => {}
+            }
+        }
+        Some(cookie)
+    }
+}
+
+impl fmt::Display for SetCookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Everything was checked to be ASCII when it was set
+        let text = |bytes: &Bytes| String::from_utf8_lossy(bytes).into_owned();
+        write!(f, "{}={}", text(&self.name), text(&self.value))?;
+        if let Some(expires) = &self.expires {
+            write!(f, "; Expires={expires}")?;
+        }
+        if let Some(max_age) = &self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", text(domain))?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", text(path))?;
+        }
+        if self.secure {
+            f.write_str("; Secure")?;
+        }
+        if self.http_only {
+            f.write_str("; HttpOnly")?;
+        }
+        if let Some(same_site) = self.same_site {
+            write!(f, "; SameSite={}", same_site.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+/// Every Set-Cookie field line, which must not be combined into a list
+/// SPEC: RFC 9110 - 5.3. Field Order
+impl HeaderValueTrait for Vec<SetCookie> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        Ok(value.iter().filter_map(SetCookie::parse).collect())
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for cookie in self {
+            value.push(Bytes::from(cookie.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        HttpVersion,
+        header::{self, HeaderField, HeaderMap, HeaderName},
+        response::{ResponseBuilder, StatusCode},
+    };
+
+    #[test]
+    fn parse_cookies() {
+        let mut headers = HeaderMap::new();
+        let value = headers.entry(HeaderName::builtin(header::Builtin::Cookie));
+        value.push(Bytes::from_static(b"SID=31d4d96e407aad42; lang=\"en-US\""));
+        value.push(Bytes::from_static(b"lang=fr; junk; =x; theme=dark"));
+        let cookies = headers.get_header::<header::Cookie>().unwrap().unwrap();
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies.get(b"SID").unwrap(), "31d4d96e407aad42");
+        assert_eq!(cookies.get(b"lang").unwrap(), "en-US");
+        assert_eq!(cookies.get(b"theme").unwrap(), "dark");
+    }
+
+    #[test]
+    fn set_cookie() {
+        let session = SetCookie::new("SID", "31d4d96e407aad42")
+            .path("/")
+            .domain("example.com")
+            .max_age(Duration::from_secs(3600))
+            .http_only()
+            .same_site(SameSite::None);
+        assert_eq!(
+            session.to_string(),
+            "SID=31d4d96e407aad42; Max-Age=3600; Domain=example.com; Path=/; Secure; HttpOnly; \
+             SameSite=None"
+        );
+        assert_eq!(
+            SetCookie::parse(&Bytes::from(session.to_string())),
+            Some(session.clone())
+        );
+
+        let removal = SetCookie::removal("lang");
+        assert_eq!(
+            removal.to_string(),
+            "lang=; Expires=Thu, 01 Jan 1970 00:00:00 GMT; Max-Age=0"
+        );
+
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK)
+            .set_cookie(session.clone())
+            .set_cookie(removal.clone())
+            .build();
+        let value = res.headers.get(&header::SetCookie::NAME).unwrap();
+        assert_eq!(value.len(), 2);
+        assert_eq!(
+            res.headers
+                .get_header::<header::SetCookie>()
+                .unwrap()
+                .unwrap(),
+            vec![session, removal]
+        );
+
+        assert_eq!(SetCookie::try_new("a b", "c"), Err(InvalidCookie::Name));
+        assert_eq!(SetCookie::try_new("a", "b;c"), Err(InvalidCookie::Value));
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A date in the preferred HTTP format, with second precision
+/// SPEC: RFC 9110 - 5.6.7. Date/Time Formats
+/// ABNF:
+///     IMF-fixdate  = day-name "," SP date1 SP time-of-day SP GMT
+///     date1        = day SP month SP year
+///     time-of-day  = hour ":" minute ":" second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct HttpDate(SystemTime);
+
+const DAY_NAMES: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid HTTP date")]
+pub struct ParseHttpDateError;
+
+impl HttpDate {
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// The (year, month, day, hour, minute, second) of the date in UTC
+    pub(crate) fn civil(&self) -> (i64, u32, u32, u32, u32, u32) {
+        let secs = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+        let rem = secs.rem_euclid(86_400) as u32;
+        (year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+    }
+
+    /// The abbreviated name of a month from 1 to 12
+    pub(crate) fn month_name(month: u32) -> &'static str {
+        MONTHS[month as usize - 1]
+    }
+}
+
+impl From<SystemTime> for HttpDate {
+    /// Truncates to whole seconds, dates before the epoch are clamped to it
+    fn from(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+}
+
+impl From<HttpDate> for SystemTime {
+    fn from(date: HttpDate) -> Self {
+        date.0
+    }
+}
+
+/// The (year, month, day) of a day counted from 1970-01-01
+/// HELPER: Howard Hinnant - chrono-Compatible Low-Level Date Algorithms, civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The inverse of [`civil_from_days`]
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+impl fmt::Display for HttpDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let days = secs.div_euclid(86_400);
+        let rem = secs.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            DAY_NAMES[days.rem_euclid(7) as usize],
+            day,
+            MONTHS[month as usize - 1],
+            year,
+            rem / 3600,
+            rem / 60 % 60,
+            rem % 60
+        )
+    }
+}
+
+impl FromStr for HttpDate {
+    type Err = ParseHttpDateError;
+
+    /// Only IMF-fixdate is accepted, not the obsolete formats
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.as_bytes();
+        if s.len() != 29 || &s[3..5] != b", " || &s[25..] != b" GMT" {
+            return Err(ParseHttpDateError);
+        }
+        let number = |range: std::ops::Range<usize>| -> Result<u32, ParseHttpDateError> {
+            let digits = &s[range];
+            if !digits.iter().all(u8::is_ascii_digit) {
+                return Err(ParseHttpDateError);
+            }
+            Ok(digits
+                .iter()
+                .fold(0, |n, digit| n * 10 + u32::from(digit - b'0')))
+        };
+        if s[7] != b' ' || s[11] != b' ' || s[16] != b' ' || s[19] != b':' || s[22] != b':' {
+            return Err(ParseHttpDateError);
+        }
+        let day = number(5..7)?;
+        let month = MONTHS
+            .iter()
+            .position(|month| month.as_bytes() == &s[8..11])
+            .ok_or(ParseHttpDateError)? as u32
+            + 1;
+        let year = number(12..16)?;
+        let (hour, minute, second) = (number(17..19)?, number(20..22)?, number(23..25)?);
+        // A leap second is allowed by the grammar, and is rounded down
+        if !(1..=31).contains(&day) || year < 1970 || hour > 23 || minute > 59 || second > 60 {
+            return Err(ParseHttpDateError);
+        }
+        let days = days_from_civil(i64::from(year), month, day);
+        if civil_from_days(days) != (i64::from(year), month, day)
+            || DAY_NAMES[days.rem_euclid(7) as usize].as_bytes() != &s[..3]
+        {
+            return Err(ParseHttpDateError);
+        }
+        let secs = days as u64 * 86_400
+            + u64::from(hour) * 3600
+            + u64::from(minute) * 60
+            + u64::from(second.min(59));
+        Ok(Self(UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        // SPEC: RFC 9110 - 5.6.7. Date/Time Formats
+        let date: HttpDate = "Sun, 06 Nov 1994 08:49:37 GMT".parse().unwrap();
+        assert_eq!(
+            SystemTime::from(date),
+            UNIX_EPOCH + Duration::from_secs(784_111_777)
+        );
+        assert_eq!(date.to_string(), "Sun, 06 Nov 1994 08:49:37 GMT");
+
+        let date = HttpDate::from(UNIX_EPOCH + Duration::from_secs(951_825_600));
+        assert_eq!(date.to_string(), "Tue, 29 Feb 2000 12:00:00 GMT");
+        assert_eq!(date.to_string().parse::<HttpDate>().unwrap(), date);
+
+        for invalid in [
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "Mon, 06 Nov 1994 08:49:37 GMT",
+            "Sun, 31 Feb 1994 08:49:37 GMT",
+            "Sun, 06 Nov 1994 24:49:37 GMT",
+        ] {
+            assert!(invalid.parse::<HttpDate>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Length prefixed message streaming in the style of gRPC-Web, for browser clients which can
+//! stream responses over HTTP/1.1, but can't read HTTP trailers
+//!
+//! Every message is a frame of a flag byte, the big endian length of the message and the
+//! message itself. The stream ends with a frame flagged as trailers, which carries the status
+//! of the call as a header block
+//! SPEC: gRPC-Web - PROTOCOL-WEB.md
+
+use std::sync::Arc;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::http::{
+    Body, BodyError, BodySender, BodyStream,
+    header::{ContentType, HeaderField, HeaderValueTrait},
+    request::Request,
+    response::{Response, ResponseBuilder, StatusCode},
+    uri::url_encode,
+};
+
+pub const CONTENT_TYPE: &str = "application/grpc-web+proto";
+
+/// The flag of a frame carrying trailers rather than a message
+const TRAILERS_FLAG: u8 = 0x80;
+/// The flag byte and the length
+const FRAME_HEADER_LEN: usize = 5;
+
+/// A frame of a length prefixed message stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrpcWebFrame {
+    /// Whether the frame carries trailers, rather than a message
+    pub trailers: bool,
+    pub data: Bytes,
+}
+
+impl GrpcWebFrame {
+    /// Splits the next complete frame off the front of `buf`, returns None if `buf` does not
+    /// contain a complete frame yet
+    pub fn decode(buf: &mut BytesMut) -> Option<Self> {
+        let header = buf.get(..FRAME_HEADER_LEN)?;
+        let len = u32::from_be_bytes(header[1..].try_into().unwrap()) as usize;
+        if buf.len() - FRAME_HEADER_LEN < len {
+            return None;
+        }
+        let trailers = buf.get_u8() & TRAILERS_FLAG != 0;
+        buf.advance(FRAME_HEADER_LEN - 1);
+        Some(Self {
+            trailers,
+            data: buf.split_to(len).freeze(),
+        })
+    }
+
+    fn encode(flag: u8, data: &[u8]) -> Result<Bytes, BodyError> {
+        let len = u32::try_from(data.len()).map_err(|err| BodyError::Encode(Arc::new(err)))?;
+        let mut frame = BytesMut::with_capacity(FRAME_HEADER_LEN + data.len());
+        frame.put_u8(flag);
+        frame.put_u32(len);
+        frame.put_slice(data);
+        Ok(frame.freeze())
+    }
+}
+
+/// Creates a streaming response, messages sent through the [`GrpcWebSender`] are written to
+/// the client as they are sent
+pub fn response(request: &Request, capacity: usize) -> (GrpcWebSender, Response) {
+    let (tx, stream) = BodyStream::channel(capacity);
+    let mut res = ResponseBuilder::from_req(request, StatusCode::OK).build();
+    Bytes::from_static(CONTENT_TYPE.as_bytes())
+        .to_header_value(res.headers.entry(ContentType::NAME));
+    res.body = Body::Stream(stream);
+    (GrpcWebSender { tx }, res)
+}
+
+/// Sends the messages of a streaming response
+pub struct GrpcWebSender {
+    tx: BodySender,
+}
+
+impl GrpcWebSender {
+    /// Sends a message, waiting if the client has not kept up with previous messages
+    pub async fn send(&self, message: &[u8]) -> Result<(), BodyError> {
+        self.tx.send(GrpcWebFrame::encode(0, message)?).await
+    }
+
+    /// Ends the stream with the status of the call, and an optional message describing it
+    pub async fn finish(self, status: u32, message: Option<&str>) -> Result<(), BodyError> {
+        let mut trailers = format!("grpc-status: {status}\r\n");
+        if let Some(message) = message {
+            trailers.push_str("grpc-message: ");
+            trailers.push_str(&url_encode(message.as_bytes()));
+            trailers.push_str("\r\n");
+        }
+        self.tx
+            .send(GrpcWebFrame::encode(TRAILERS_FLAG, trailers.as_bytes())?)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::method::Method;
+
+    #[tokio::test]
+    async fn frames() {
+        let req = Request::new(Method::POST, "/svc/Call");
+        let (tx, res) = response(&req, 4);
+        assert_eq!(
+            res.headers.get(&ContentType::NAME).unwrap()[0],
+            CONTENT_TYPE
+        );
+        tokio::spawn(async move {
+            tx.send(b"hello").await.unwrap();
+            tx.send(b"").await.unwrap();
+            tx.finish(3, Some("bad arg")).await.unwrap();
+        });
+
+        let mut buf = BytesMut::from(res.body.collect(None).await.unwrap());
+        // An incomplete frame is left in the buffer
+        let mut partial = BytesMut::from(&buf[..7]);
+        assert_eq!(GrpcWebFrame::decode(&mut partial), None);
+        assert_eq!(partial.len(), 7);
+
+        let frame = |trailers, data: &'static [u8]| GrpcWebFrame {
+            trailers,
+            data: Bytes::from_static(data),
+        };
+        assert_eq!(GrpcWebFrame::decode(&mut buf), Some(frame(false, b"hello")));
+        assert_eq!(GrpcWebFrame::decode(&mut buf), Some(frame(false, b"")));
+        assert_eq!(
+            GrpcWebFrame::decode(&mut buf),
+            Some(frame(
+                true,
+                b"grpc-status: 3\r\ngrpc-message: bad%20arg\r\n"
+            ))
+        );
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,343 @@
+use std::fmt;
+
+use bytes::Bytes;
+
+use crate::http::{
+    header::{
+        HeaderParseError, HeaderValue, HeaderValueTrait,
+        auth::{parse_param, split_unquoted},
+    },
+    parser::is_tchar,
+};
+
+/// Parses a weight in thousandths
+/// SPEC: RFC 9110 - 12.4.2. Quality Values
+/// ABNF: qvalue = ( "0" [ "." 0*3DIGIT ] ) / ( "1" [ "." 0*3("0") ] )
+pub(crate) fn parse_qvalue(value: &[u8]) -> Option<u16> {
+    let (int, frac) = match value.iter().position(|&b| b == b'.') {
+        Some(dot) => (&value[..dot], &value[dot + 1..]),
+        None => (value, &b""[..]),
+    };
+    if frac.len() > 3 || !frac.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let frac = frac
+        .iter()
+        .chain(std::iter::repeat(&b'0'))
+        .take(3)
+        .fold(0, |n, &b| n * 10 + (b - b'0') as u16);
+    match int {
+        b"0" => Some(frac),
+        b"1" if frac == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+/// A media type, such as `text/html; charset=utf-8`
+/// The type, subtype and parameter names are kept in lowercase, since they are case insensitive
+/// SPEC: RFC 9110 - 8.3.1. Media Type
+/// ABNF:
+///     media-type = type "/" subtype parameters
+///     parameters = *( OWS ";" OWS [ parameter ] )
+///     parameter  = parameter-name "=" parameter-value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaType {
+    ty: Bytes,
+    subtype: Bytes,
+    params: Vec<(Bytes, Bytes)>,
+}
+
+impl MediaType {
+    /// # Panics
+    /// If the type or subtype is not a token
+    pub fn new(ty: &str, subtype: &str) -> Self {
+        assert!(
+            [ty, subtype]
+                .iter()
+                .all(|part| !part.is_empty() && part.bytes().all(is_tchar)),
+            "invalid media type {ty}/{subtype}"
+        );
+        Self {
+            ty: Bytes::from(ty.to_ascii_lowercase()),
+            subtype: Bytes::from(subtype.to_ascii_lowercase()),
+            params: Vec::new(),
+        }
+    }
+
+    /// `application/json`
+    pub fn json() -> Self {
+        Self::new("application", "json")
+    }
+
+    /// `text/html; charset=utf-8`
+    pub fn html() -> Self {
+        Self::new("text", "html").with_param("charset", "utf-8")
+    }
+
+    /// `text/plain; charset=utf-8`
+    pub fn plain_text() -> Self {
+        Self::new("text", "plain").with_param("charset", "utf-8")
+    }
+
+    /// Adds a parameter, quoting the value when it is not a token
+    /// # Panics
+    /// If the name is not a token
+    pub fn with_param(mut self, name: &str, value: &str) -> Self {
+        assert!(
+            !name.is_empty() && name.bytes().all(is_tchar),
+            "invalid parameter name {name}"
+        );
+        self.params.push((
+            Bytes::from(name.to_ascii_lowercase()),
+            Bytes::copy_from_slice(value.as_bytes()),
+        ));
+        self
+    }
+
+    /// Parses a media type, which may have a wildcard type or subtype
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut parts = split_unquoted(bytes, b';').into_iter();
+        let essence = parts.next()?.trim_ascii();
+        let slash = essence.iter().position(|b| *b == b'/')?;
+        let (ty, subtype) = (&essence[..slash], &essence[slash + 1..]);
+        if [ty, subtype]
+            .iter()
+            .any(|part| part.is_empty() || !part.iter().copied().all(is_tchar))
+        {
+            return None;
+        }
+        let mut params = Vec::new();
+        for param in parts {
+            // Empty parameters are allowed, such as in `text/plain;`
+            if param.trim_ascii().is_empty() {
+                continue;
+            }
+            let (name, value) = parse_param(param)?;
+            params.push((Bytes::from(name.to_ascii_lowercase()), value));
+        }
+        Some(Self {
+            ty: Bytes::from(ty.to_ascii_lowercase()),
+            subtype: Bytes::from(subtype.to_ascii_lowercase()),
+            params,
+        })
+    }
+
+    pub fn ty(&self) -> &[u8] {
+        &self.ty
+    }
+
+    pub fn subtype(&self) -> &[u8] {
+        &self.subtype
+    }
+
+    /// The value of a parameter, names are case insensitive
+    pub fn param(&self, name: &str) -> Option<&[u8]> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name.as_bytes()))
+            .map(|(_, value)| &value[..])
+    }
+
+    pub fn params(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.params
+            .iter()
+            .map(|(name, value)| (&name[..], &value[..]))
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::from(self.to_string())
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let str = |bytes: &Bytes| String::from_utf8_lossy(bytes).into_owned();
+        write!(f, "{}/{}", str(&self.ty), str(&self.subtype))?;
+        for (name, value) in &self.params {
+            write!(f, "; {}=", str(name))?;
+            if !value.is_empty() && value.iter().copied().all(is_tchar) {
+                f.write_str(&String::from_utf8_lossy(value))?;
+                continue;
+            }
+            f.write_str("\"")?;
+            for c in String::from_utf8_lossy(value).chars() {
+                // ABNF: quoted-pair = "\" ( HTAB / SP / VCHAR / obs-text )
+                if c == '"' || c == '\\' {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+            f.write_str("\"")?;
+        }
+        Ok(())
+    }
+}
+
+/// An element of an Accept field, a media type which may have a wildcard type or subtype, with
+/// the weight the client gives it in thousandths
+/// SPEC: RFC 9110 - 12.5.1. Accept
+/// ABNF:
+///     Accept      = #( media-range [ weight ] )
+///     media-range = ( "*/*" / ( type "/*" ) / ( type "/" subtype ) ) parameters
+///     weight      = OWS ";" OWS "q=" qvalue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaRange {
+    pub media: MediaType,
+    pub weight: u16,
+}
+
+impl MediaRange {
+    /// Parses one element of an Accept field, parameters after the weight are ignored
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut media = MediaType::parse(bytes)?;
+        let mut weight = 1000;
+        if let Some(q) = media.params.iter().position(|(name, _)| name == "q") {
+            weight = parse_qvalue(&media.params[q].1)?;
+            media.params.truncate(q);
+        }
+        if media.ty == "*" && media.subtype != "*" {
+            return None;
+        }
+        Some(Self { media, weight })
+    }
+
+    /// Whether `media` is in the range, any parameters of the range must match its parameters
+    pub fn contains(&self, media: &MediaType) -> bool {
+        let range = &self.media;
+        (range.ty == "*" || range.ty == media.ty)
+            && (range.subtype == "*" || range.subtype == media.subtype)
+            && range.params.iter().all(|(name, value)| {
+                // Parameter values are compared case insensitively by most media types,
+                // including charset
+                media
+                    .param(std::str::from_utf8(name).unwrap_or_default())
+                    .is_some_and(|other| other.eq_ignore_ascii_case(value))
+            })
+    }
+
+    /// How specific the range is, the most specific range containing a media type gives its
+    /// weight
+    fn precedence(&self) -> (u8, usize) {
+        let wildcards = match (&self.media.ty[..], &self.media.subtype[..]) {
+            (b"*", _) => 0,
+            (_, b"*") => 1,
+            _ => 2,
+        };
+        (wildcards, self.media.params.len())
+    }
+}
+
+/// Every valid element of the field, invalid elements are left out
+impl HeaderValueTrait for Vec<MediaRange> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        Ok(value
+            .iter_elements()
+            .filter_map(|element| MediaRange::parse(&element))
+            .collect())
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for range in self {
+            let mut element = range.media.to_string();
+            match range.weight {
+                1000 => {}
+                0 => element.push_str(";q=0"),
+                weight => {
+                    let weight = format!("{weight:03}");
+                    element.push_str(&format!(";q=0.{}", weight.trim_end_matches('0')));
+                }
+            }
+            value.push(Bytes::from(element));
+        }
+    }
+}
+
+/// Picks the media type in `available` the client weights highest, ties go to the order of
+/// `available`, and media types with a weight of 0 are never picked
+/// Every media type is acceptable when the client sent no Accept field
+/// SPEC: RFC 9110 - 12.5.1. Accept
+pub fn negotiate(accept: &[MediaRange], available: &[MediaType]) -> Option<MediaType> {
+    if accept.is_empty() {
+        return available.first().cloned();
+    }
+    available
+        .iter()
+        .filter_map(|media| {
+            let weight = accept
+                .iter()
+                .filter(|range| range.contains(media))
+                .reduce(|best, next| match next.precedence() > best.precedence() {
+                    true => next,
+                    false => best,
+                })?
+                .weight;
+            (weight > 0).then_some((media, weight))
+        })
+        .reduce(|best, next| if next.1 > best.1 { next } else { best })
+        .map(|(media, _)| media.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &'static str) -> Vec<MediaRange> {
+        let mut header = HeaderValue::new();
+        header.push(Bytes::from_static(value.as_bytes()));
+        Vec::<MediaRange>::from_header_value(&header).unwrap()
+    }
+
+    #[test]
+    fn parse_media_types() {
+        let media = MediaType::parse(b"Text/HTML ; Charset=\"UTF-8\"; x=\"a;b\"").unwrap();
+        assert_eq!(media.ty(), b"text");
+        assert_eq!(media.subtype(), b"html");
+        assert_eq!(media.param("charset"), Some(&b"UTF-8"[..]));
+        assert_eq!(media.to_string(), "text/html; charset=UTF-8; x=\"a;b\"");
+        assert_eq!(MediaType::parse(b"text"), None);
+        assert_eq!(MediaType::parse(b"text/html; charset"), None);
+
+        let ranges = accept("text/html;level=1;q=0.7;ext=1, */*;q=0.1, text/*, bad, */html");
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].weight, 700);
+        assert_eq!(ranges[0].media.to_string(), "text/html; level=1");
+        assert_eq!(accept("text/html;q=2, text/plain;q=0.5").len(), 1);
+
+        let mut header = HeaderValue::new();
+        ranges.to_header_value(&mut header);
+        assert_eq!(
+            header.collect(),
+            "text/html; level=1;q=0.7, */*;q=0.1, text/*"
+        );
+    }
+
+    #[test]
+    fn negotiation() {
+        let available = [
+            MediaType::json(),
+            MediaType::html(),
+            MediaType::plain_text(),
+        ];
+        let pick = |value| negotiate(&accept(value), &available);
+        assert_eq!(negotiate(&[], &available), Some(MediaType::json()));
+        assert_eq!(pick("text/html"), Some(MediaType::html()));
+        assert_eq!(
+            pick("text/html;q=0.5, text/plain, application/json;q=0.9"),
+            Some(MediaType::plain_text())
+        );
+        // Ties go to the order of the available media types
+        assert_eq!(pick("text/*, */*;q=0.1"), Some(MediaType::html()));
+        // The most specific range gives the weight
+        assert_eq!(
+            pick("text/*, text/html;q=0, */*;q=0"),
+            Some(MediaType::plain_text())
+        );
+        assert_eq!(
+            pick("text/plain; charset=utf-8"),
+            Some(MediaType::plain_text())
+        );
+        assert_eq!(pick("text/plain; charset=latin1"), None);
+        assert_eq!(pick("image/png"), None);
+        assert_eq!(pick("*/*;q=0"), None);
+    }
+}
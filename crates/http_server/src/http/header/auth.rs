@@ -0,0 +1,339 @@
+use std::fmt;
+
+use bytes::Bytes;
+
+use crate::http::{
+    base64::{self, Alphabet},
+    header::{HeaderParseError, HeaderValue, HeaderValueTrait},
+    parser::{HttpParseError, Location as ParseLocation, ParseErrorKind, is_tchar},
+};
+
+pub(super) fn invalid() -> HeaderParseError {
+    HeaderParseError::HttpParseError(HttpParseError {
+        kind: ParseErrorKind::InvalidHeaderValue,
+        location: ParseLocation::Headers,
+        offset: 0,
+        line: None,
+    })
+}
+
+/// ABNF: token68 = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+fn is_token68(bytes: &[u8]) -> bool {
+    let end = bytes.iter().rposition(|b| *b != b'=').map_or(0, |i| i + 1);
+    end > 0
+        && bytes[..end]
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~+/".contains(b))
+}
+
+/// The credentials of an Authorization field
+/// SPEC: RFC 9110 - 11.6.2. Authorization
+/// ABNF: credentials = auth-scheme [ 1*SP ( token68 / #auth-param ) ]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// SPEC: RFC 7617 - 2. The 'Basic' Authentication Scheme
+    Basic { user_id: String, password: String },
+    /// SPEC: RFC 6750 - 2.1. Authorization Request Header Field
+    Bearer(Bytes),
+    /// Any other scheme, with everything after the scheme unparsed
+    Other { scheme: Bytes, data: Bytes },
+}
+
+impl Credentials {
+    pub fn parse(bytes: &Bytes) -> Option<Self> {
+        let bytes = bytes.slice_ref(bytes.trim_ascii());
+        let split = bytes.iter().position(|b| *b == b' ').unwrap_or(bytes.len());
+        let scheme = &bytes[..split];
+        if scheme.is_empty() || !scheme.iter().copied().all(is_tchar) {
+            return None;
+        }
+        let data = bytes.slice_ref(bytes[split..].trim_ascii_start());
+        // Scheme names are case insensitive
+        if scheme.eq_ignore_ascii_case(b"Basic") {
+            if !is_token68(&data) {
+                return None;
+            }
+            let decoded = String::from_utf8(base64::decode(&data, Alphabet::Standard)?).ok()?;
+            // ABNF: user-pass = userid ":" password
+            let (user_id, password) = decoded.split_once(':')?;
+            Some(Self::Basic {
+                user_id: user_id.to_owned(),
+                password: password.to_owned(),
+            })
+        } else if scheme.eq_ignore_ascii_case(b"Bearer") {
+            // ABNF: b64token = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+            is_token68(&data).then_some(Self::Bearer(data))
+        } else {
+            Some(Self::Other {
+                scheme: bytes.slice_ref(scheme),
+                data,
+            })
+        }
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Basic { user_id, password } => {
+                let user_pass = format!("{user_id}:{password}");
+                write!(
+                    f,
+                    "Basic {}",
+                    base64::encode(user_pass.as_bytes(), Alphabet::Standard)
+                )
+            }
+            Self::Bearer(token) => write!(f, "Bearer {}", String::from_utf8_lossy(token)),
+            Self::Other { scheme, data } if data.is_empty() => {
+                f.write_str(&String::from_utf8_lossy(scheme))
+            }
+            Self::Other { scheme, data } => write!(
+                f,
+                "{} {}",
+                String::from_utf8_lossy(scheme),
+                String::from_utf8_lossy(data)
+            ),
+        }
+    }
+}
+
+impl HeaderValueTrait for Credentials {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let raw = Bytes::from_header_value(value)?;
+        Self::parse(&raw).ok_or_else(invalid)
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        value.push(Bytes::from(self.to_string()));
+    }
+}
+
+/// A challenge of a WWW-Authenticate field, telling the client how to authenticate
+/// SPEC: RFC 9110 - 11.6.1. WWW-Authenticate
+/// ABNF:
+///     challenge  = auth-scheme [ 1*SP ( token68 / #auth-param ) ]
+///     auth-param = token BWS "=" BWS ( token / quoted-string )
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    pub scheme: Bytes,
+    pub token68: Option<Bytes>,
+    pub params: Vec<(Bytes, Bytes)>,
+}
+
+impl Challenge {
+    pub fn new(scheme: impl Into<Bytes>) -> Self {
+        Self {
+            scheme: scheme.into(),
+            token68: None,
+            params: Vec::new(),
+        }
+    }
+
+    /// SPEC: RFC 7617 - 2. The 'Basic' Authentication Scheme
+    pub fn basic(realm: impl Into<Bytes>) -> Self {
+        Self::new("Basic")
+            .param("realm", realm)
+            .param("charset", "UTF-8")
+    }
+
+    /// SPEC: RFC 6750 - 3. The WWW-Authenticate Response Header Field
+    pub fn bearer(realm: impl Into<Bytes>) -> Self {
+        Self::new("Bearer").param("realm", realm)
+    }
+
+    /// Adds a parameter, the value is always sent as a quoted-string
+    pub fn param(mut self, name: impl Into<Bytes>, value: impl Into<Bytes>) -> Self {
+        self.params.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn get(&self, name: &[u8]) -> Option<&Bytes> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+}
+
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(&self.scheme))?;
+        if let Some(token68) = &self.token68 {
+            write!(f, " {}", String::from_utf8_lossy(token68))?;
+        }
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            f.write_str(if i == 0 { " " } else { ", " })?;
+            write!(f, "{}=\"", String::from_utf8_lossy(name))?;
+            // ABNF: quoted-pair = "\" ( HTAB / SP / VCHAR / obs-text )
+            for c in String::from_utf8_lossy(value).chars() {
+                if c == '"' || c == '\\' {
+                    f.write_str("\\")?;
+                }
+                write!(f, "{c}")?;
+            }
+            f.write_str("\"")?;
+        }
+        Ok(())
+    }
+}
+
+/// Splits a field line at the separators which are not inside a quoted-string
+pub(super) fn split_unquoted(line: &[u8], separator: u8) -> Vec<&[u8]> {
+    let mut elements = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, b) in line.iter().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b if *b == separator && !quoted => {
+                elements.push(&line[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(&line[start..]);
+    elements
+}
+
+/// Parses `name=value`, where the value is a token or quoted-string
+pub(super) fn parse_param(element: &[u8]) -> Option<(Bytes, Bytes)> {
+    let eq = element.iter().position(|b| *b == b'=')?;
+    let name = element[..eq].trim_ascii();
+    let value = element[eq + 1..].trim_ascii();
+    if name.is_empty() || !name.iter().copied().all(is_tchar) {
+        return None;
+    }
+    let value = match value.strip_prefix(b"\"") {
+        Some(quoted) => {
+            let quoted = quoted.strip_suffix(b"\"")?;
+            let mut unescaped = Vec::with_capacity(quoted.len());
+            let mut escaped = false;
+            for b in quoted {
+                match b {
+                    b'\\' if !escaped => escaped = true,
+                    _ => {
+                        unescaped.push(*b);
+                        escaped = false;
+                    }
+                }
+            }
+            unescaped
+        }
+        None if value.iter().copied().all(is_tchar) => value.to_vec(),
+        None => return None,
+    };
+    Some((Bytes::copy_from_slice(name), Bytes::from(value)))
+}
+
+/// Every challenge of the field, which may be split over multiple field lines
+impl HeaderValueTrait for Vec<Challenge> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let mut challenges: Vec<Challenge> = Vec::new();
+        for line in value.iter() {
+            for element in split_unquoted(line, b',') {
+                let element = element.trim_ascii();
+                if element.is_empty() {
+                    continue;
+                }
+                let split = element.iter().position(|b| *b == b' ');
+                // A parameter of the previous challenge, which has no space before the "="
+                let is_param = element
+                    .iter()
+                    .position(|b| *b == b'=')
+                    .is_some_and(|eq| split.is_none_or(|split| eq < split));
+                if is_param && !is_token68(element) {
+                    challenges
+                        .last_mut()
+                        .ok_or_else(invalid)?
+                        .params
+                        .push(parse_param(element).ok_or_else(invalid)?);
+                    continue;
+                }
+                let split = split.unwrap_or(element.len());
+                let scheme = &element[..split];
+                if !scheme.iter().copied().all(is_tchar) {
+                    return Err(invalid());
+                }
+                let mut challenge = Challenge::new(Bytes::copy_from_slice(scheme));
+                let rest = element[split..].trim_ascii_start();
+                if is_token68(rest) {
+                    challenge.token68 = Some(Bytes::copy_from_slice(rest));
+                } else if !rest.is_empty() {
+                    challenge
+                        .params
+                        .push(parse_param(rest).ok_or_else(invalid)?);
+                }
+                challenges.push(challenge);
+            }
+        }
+        Ok(challenges)
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for challenge in self {
+            value.push(Bytes::from(challenge.to_string()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials() {
+        // SPEC: RFC 7617 - 2. The 'Basic' Authentication Scheme
+        let basic = Credentials::parse(&Bytes::from_static(b"basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="));
+        assert_eq!(
+            basic,
+            Some(Credentials::Basic {
+                user_id: "Aladdin".into(),
+                password: "open sesame".into(),
+            })
+        );
+        assert_eq!(
+            basic.unwrap().to_string(),
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+
+        assert_eq!(
+            Credentials::parse(&Bytes::from_static(b"Bearer mF_9.B5f-4.1JqM")),
+            Some(Credentials::Bearer(Bytes::from_static(b"mF_9.B5f-4.1JqM")))
+        );
+        assert!(matches!(
+            Credentials::parse(&Bytes::from_static(b"Digest username=\"a\"")),
+            Some(Credentials::Other { ref scheme, .. }) if scheme == "Digest"
+        ));
+        for invalid in [
+            &b"Basic"[..],
+            b"Basic !!!",
+            b"Basic Zm9v",
+            b"Bearer a b",
+            b"",
+        ] {
+            assert_eq!(Credentials::parse(&Bytes::from_static(invalid)), None);
+        }
+    }
+
+    #[test]
+    fn challenges() {
+        let challenge = Challenge::bearer("api").param("error", "invalid_token");
+        assert_eq!(
+            challenge.to_string(),
+            "Bearer realm=\"api\", error=\"invalid_token\""
+        );
+
+        let mut value = HeaderValue::new();
+        vec![Challenge::basic("a \"b\""), challenge.clone()].to_header_value(&mut value);
+        value.push(Bytes::from_static(b"Newauth abc==, Other"));
+        let parsed = Vec::<Challenge>::from_header_value(&value).unwrap();
+        assert_eq!(parsed.len(), 4);
+        assert_eq!(parsed[0].get(b"realm").unwrap(), "a \"b\"");
+        assert_eq!(parsed[0].get(b"charset").unwrap(), "UTF-8");
+        assert_eq!(parsed[1], challenge);
+        assert_eq!(parsed[2].token68.as_ref().unwrap(), "abc==");
+        assert_eq!(parsed[3], Challenge::new("Other"));
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt::{self, Write};
+
+use bytes::Bytes;
+
+use crate::http::{
+    header::{
+        HeaderParseError, HeaderValue, HeaderValueTrait,
+        auth::{invalid, parse_param, split_unquoted},
+    },
+    parser::is_tchar,
+    uri::url_decode,
+};
+
+/// How the content of a response, or a part of a multipart body, is presented
+/// SPEC: RFC 6266 - 4.2. Disposition Type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DispositionType {
+    /// Shown as part of the page
+    Inline,
+    /// Downloaded rather than shown
+    Attachment,
+    /// A field of a multipart/form-data body
+    /// SPEC: RFC 7578 - 4.2. Content-Disposition Header Field for Each Part
+    FormData,
+    /// Recipients treat unknown types as attachment
+    Other(Bytes),
+}
+
+impl DispositionType {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Inline => b"inline",
+            Self::Attachment => b"attachment",
+            Self::FormData => b"form-data",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+/// The Content-Disposition field
+/// SPEC: RFC 6266 - 4.1. Grammar
+/// ABNF:
+///     content-disposition = "Content-Disposition" ":" disposition-type *( ";" disposition-parm )
+///     disposition-parm    = filename-parm / disp-ext-parm
+///     filename-parm       = "filename" "=" value / "filename*" "=" ext-value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentDispositionValue {
+    pub kind: DispositionType,
+    /// The filename the recipient should save the content as, `filename*` is preferred over
+    /// `filename` when both are given
+    pub filename: Option<String>,
+    /// The name of the form field, for form-data
+    pub name: Option<String>,
+}
+
+impl ContentDispositionValue {
+    pub fn new(kind: DispositionType) -> Self {
+        Self {
+            kind,
+            filename: None,
+            name: None,
+        }
+    }
+
+    /// An attachment saved as `filename`
+    pub fn attachment(filename: impl Into<String>) -> Self {
+        Self::new(DispositionType::Attachment).with_filename(filename)
+    }
+
+    pub fn with_filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let mut parts = split_unquoted(bytes, b';').into_iter();
+        let kind = parts.next()?.trim_ascii();
+        if kind.is_empty() || !kind.iter().copied().all(is_tchar) {
+            return None;
+        }
+        let kind = match kind {
+            _ if kind.eq_ignore_ascii_case(b"inline") => DispositionType::Inline,
+            _ if kind.eq_ignore_ascii_case(b"attachment") => DispositionType::Attachment,
+            _ if kind.eq_ignore_ascii_case(b"form-data") => DispositionType::FormData,
+            _ => DispositionType::Other(Bytes::copy_from_slice(kind)),
+        };
+        let mut disposition = Self::new(kind);
+        let mut extended = None;
+        for part in parts {
+            if part.trim_ascii().is_empty() {
+                continue;
+            }
+            let (name, value) = parse_param(part)?;
+            if name.eq_ignore_ascii_case(b"filename*") {
+                // An extended value which can't be decoded is ignored, the plain one is used
+                extended = extended.or_else(|| decode_ext_value(&value));
+            } else if name.eq_ignore_ascii_case(b"filename") {
+                disposition.filename = Some(String::from_utf8_lossy(&value).into_owned());
+            } else if name.eq_ignore_ascii_case(b"name") {
+                disposition.name = Some(String::from_utf8_lossy(&value).into_owned());
+            }
+        }
+        if extended.is_some() {
+            disposition.filename = extended;
+        }
+        Some(disposition)
+    }
+}
+
+/// Decodes an extended parameter value
+/// SPEC: RFC 8187 - 3.2.1. Parameter Value Character Encoding and Language Information
+/// ABNF: ext-value = charset "'" [ language ] "'" value-chars
+fn decode_ext_value(value: &[u8]) -> Option<String> {
+    let mut parts = value.splitn(3, |&b| b == b'\'');
+    let (charset, _language, chars) = (parts.next()?, parts.next()?, parts.next()?);
+    if charset.eq_ignore_ascii_case(b"UTF-8") {
+        url_decode(chars).ok()
+    } else if charset.eq_ignore_ascii_case(b"ISO-8859-1") {
+        // Each byte is the code point of the same value
+        let bytes = url_decode_bytes(chars)?;
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+fn url_decode_bytes(chars: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(chars.len());
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == b'%' {
+            let hex = std::str::from_utf8(chars.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(chars[i]);
+            i += 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// ABNF: attr-char = ALPHA / DIGIT / "!" / "#" / "$" / "&" / "+" / "-" / "." / "^" / "_" / "`"
+///     / "|" / "~"
+fn is_attr_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric()
+        || matches!(
+            b,
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~'
+        )
+}
+
+/// Writes `value` as a quoted-string, which only holds visible ASCII here
+fn write_quoted(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => write!(f, "\\{c}")?,
+            // Recipients which don't understand filename* get an ASCII approximation
+            c if !c.is_ascii() || c.is_ascii_control() => f.write_char('_')?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl fmt::Display for ContentDispositionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&String::from_utf8_lossy(self.kind.as_bytes()))?;
+        if let Some(name) = &self.name {
+            f.write_str("; name=")?;
+            write_quoted(f, name)?;
+        }
+        if let Some(filename) = &self.filename {
+            f.write_str("; filename=")?;
+            write_quoted(f, filename)?;
+            // SPEC: RFC 6266 - 4.3. Disposition Parameter: 'Filename'
+            // Names which can't be sent as they are in filename are also sent as filename*
+            if filename
+                .bytes()
+                .any(|b| !b.is_ascii() || b.is_ascii_control())
+            {
+                f.write_str("; filename*=UTF-8''")?;
+                for b in filename.bytes() {
+                    match is_attr_char(b) {
+                        true => f.write_char(b as char)?,
+                        false => write!(f, "%{b:02X}")?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HeaderValueTrait for ContentDispositionValue {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        match value.as_slice() {
+            [line] => Self::parse(line).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        value.push(Bytes::from(self.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let disposition = ContentDispositionValue::parse(
+            b"Attachment; filename=\"EURO rates.txt\"; filename*=utf-8''%e2%82%ac%20rates.txt",
+        )
+        .unwrap();
+        assert_eq!(disposition.kind, DispositionType::Attachment);
+        assert_eq!(disposition.filename.unwrap(), "€ rates.txt");
+
+        let disposition =
+            ContentDispositionValue::parse(b"form-data; name=\"field\"; filename=a.txt").unwrap();
+        assert_eq!(disposition.kind, DispositionType::FormData);
+        assert_eq!(disposition.name.unwrap(), "field");
+        assert_eq!(disposition.filename.unwrap(), "a.txt");
+
+        let disposition =
+            ContentDispositionValue::parse(b"inline; filename*=iso-8859-1'en'%A3%20rates").unwrap();
+        assert_eq!(disposition.filename.unwrap(), "£ rates");
+
+        assert!(ContentDispositionValue::parse(b"").is_none());
+        assert!(ContentDispositionValue::parse(b"attachment; filename=a b").is_none());
+    }
+
+    #[test]
+    fn serialize() {
+        let disposition = ContentDispositionValue::attachment("report \"final\".pdf");
+        assert_eq!(
+            disposition.to_string(),
+            "attachment; filename=\"report \\\"final\\\".pdf\""
+        );
+
+        let disposition = ContentDispositionValue::attachment("€ rates.txt");
+        let serialized = disposition.to_string();
+        assert_eq!(
+            serialized,
+            "attachment; filename=\"_ rates.txt\"; filename*=UTF-8''%E2%82%AC%20rates.txt"
+        );
+        let parsed = ContentDispositionValue::parse(serialized.as_bytes()).unwrap();
+        assert_eq!(parsed, disposition);
+    }
+}
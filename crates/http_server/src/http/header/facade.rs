@@ -0,0 +1,94 @@
+//! Conversions between carbon's header map and the [`uhsapi`] facade header map
+//! Field values are moved between the maps, so no header bytes are copied
+
+use uhsapi::http::header::{HeaderMap as FacadeHeaderMap, HeaderType};
+
+use super::{HeaderMap, HeaderName};
+
+impl From<HeaderName> for HeaderType {
+    fn from(name: HeaderName) -> Self {
+        // SAFETY: header names are checked to be ascii when they are created
+        unsafe { HeaderType::from_bytes_unchecked(name.into_bytes()) }
+    }
+}
+
+impl From<HeaderType> for HeaderName {
+    fn from(name: HeaderType) -> Self {
+        HeaderName::try_from(&name.into_bytes()).expect("header types are ascii")
+    }
+}
+
+impl From<HeaderMap> for FacadeHeaderMap {
+    fn from(headers: HeaderMap) -> Self {
+        let mut map = FacadeHeaderMap::with_capacity(headers.iter().len());
+        for (name, value) in headers {
+            // Custom names which only differ in case are the same facade header
+            map.entry(name.into()).extend(value.values);
+        }
+        map
+    }
+}
+
+impl From<FacadeHeaderMap> for HeaderMap {
+    fn from(headers: FacadeHeaderMap) -> Self {
+        let mut map = HeaderMap::with_capacity(headers.len());
+        for (name, values) in headers {
+            let value = map.entry(name.into());
+            for bytes in values {
+                value.push(bytes);
+            }
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use uhsapi::http::header::{ContentLength, Vary};
+
+    use super::*;
+    use crate::http::header::{self, Builtin};
+
+    #[test]
+    fn round_trip() {
+        let mut headers = HeaderMap::new();
+        headers
+            .entry(HeaderName::builtin(Builtin::Vary))
+            .push(Bytes::from_static(b"Accept"));
+        headers
+            .entry(HeaderName::builtin(Builtin::Vary))
+            .push(Bytes::from_static(b"User-Agent"));
+        headers
+            .entry(HeaderName::try_from(&Bytes::from_static(b"X-Custom")).unwrap())
+            .push(Bytes::from_static(b"value"));
+
+        let mut facade = FacadeHeaderMap::from(headers);
+        assert_eq!(
+            facade.get_header::<Vary>().unwrap().unwrap(),
+            vec![
+                Bytes::from_static(b"Accept"),
+                Bytes::from_static(b"User-Agent"),
+            ]
+        );
+        assert_eq!(
+            facade.get(&HeaderType::from_static("x-custom")).unwrap(),
+            [Bytes::from_static(b"value")]
+        );
+        facade.set_header::<ContentLength>(5);
+
+        let headers = HeaderMap::from(facade);
+        assert_eq!(
+            headers.get_header::<header::ContentLength>().unwrap(),
+            Some(5)
+        );
+        assert_eq!(
+            headers.get_header::<header::Vary>().unwrap().unwrap().len(),
+            2
+        );
+        let custom = headers
+            .get(&HeaderName::try_from(&Bytes::from_static(b"X-Custom")).unwrap())
+            .unwrap();
+        assert_eq!(custom.as_slice(), [Bytes::from_static(b"value")]);
+    }
+}
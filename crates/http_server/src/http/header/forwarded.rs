@@ -0,0 +1,303 @@
+use std::{fmt, net::IpAddr};
+
+use bytes::Bytes;
+
+use crate::http::{
+    header::{
+        HeaderParseError, HeaderValue, HeaderValueTrait,
+        auth::{invalid, parse_param, split_unquoted},
+    },
+    parser::is_tchar,
+};
+
+/// A node named by the `for` or `by` parameter of a Forwarded element, or an element of an
+/// X-Forwarded-For field
+/// Obfuscated ports are left out, since they can't be used
+/// SPEC: RFC 7239 - 6. Node Identifiers
+/// ABNF:
+///     node     = nodename [ ":" node-port ]
+///     nodename = IPv4address / "[" IPv6address "]" / "unknown" / obfnode
+///     obfnode  = "_" 1*( ALPHA / DIGIT / "." / "_" / "-")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForwardedNode {
+    Ip(IpAddr, Option<u16>),
+    /// The proxy does not know, or does not disclose, the node
+    Unknown,
+    Obfuscated(Bytes),
+}
+
+impl ForwardedNode {
+    /// Parses a node, IPv6 addresses may also be given without brackets, as X-Forwarded-For
+    /// sends them
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        // ABNF: node-port = port / obfport
+        let port = |port: &[u8]| match port.first() {
+            Some(b'_') => is_obfuscated(port).then_some(None),
+            _ if port.len() <= 5 && port.iter().all(u8::is_ascii_digit) => {
+                std::str::from_utf8(port).ok()?.parse().ok().map(Some)
+            }
+            _ => None,
+        };
+        if let Some(rest) = bytes.strip_prefix(b"[") {
+            let end = rest.iter().position(|b| *b == b']')?;
+            let addr = std::str::from_utf8(&rest[..end]).ok()?.parse().ok()?;
+            let port = match &rest[end + 1..] {
+                b"" => None,
+                [b':', tail @ ..] => port(tail)?,
+                _ => return None,
+            };
+            return Some(Self::Ip(IpAddr::V6(addr), port));
+        }
+        if let Ok(addr) = std::str::from_utf8(bytes).ok()?.parse() {
+            return Some(Self::Ip(addr, None));
+        }
+        let (name, port) = match bytes.iter().position(|b| *b == b':') {
+            Some(colon) => (&bytes[..colon], port(&bytes[colon + 1..])?),
+            None => (bytes, None),
+        };
+        if name.eq_ignore_ascii_case(b"unknown") {
+            return Some(Self::Unknown);
+        }
+        if is_obfuscated(name) {
+            return Some(Self::Obfuscated(Bytes::copy_from_slice(name)));
+        }
+        let addr = std::str::from_utf8(name).ok()?.parse().ok()?;
+        Some(Self::Ip(IpAddr::V4(addr), port))
+    }
+
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            Self::Ip(addr, _) => Some(*addr),
+            _ => None,
+        }
+    }
+}
+
+/// The form used in Forwarded, IPv6 addresses are in brackets
+impl fmt::Display for ForwardedNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(IpAddr::V4(addr), None) => write!(f, "{addr}"),
+            Self::Ip(IpAddr::V4(addr), Some(port)) => write!(f, "{addr}:{port}"),
+            Self::Ip(IpAddr::V6(addr), None) => write!(f, "[{addr}]"),
+            Self::Ip(IpAddr::V6(addr), Some(port)) => write!(f, "[{addr}]:{port}"),
+            Self::Unknown => f.write_str("unknown"),
+            Self::Obfuscated(name) => f.write_str(&String::from_utf8_lossy(name)),
+        }
+    }
+}
+
+fn is_obfuscated(bytes: &[u8]) -> bool {
+    bytes.len() > 1
+        && bytes[0] == b'_'
+        && bytes[1..]
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || b"._-".contains(b))
+}
+
+/// One element of a Forwarded field, added by one proxy
+/// SPEC: RFC 7239 - 4. Forwarded HTTP Header Field
+/// ABNF:
+///     Forwarded         = 1#forwarded-element
+///     forwarded-element = [ forwarded-pair ] *( ";" [ forwarded-pair ] )
+///     forwarded-pair    = token "=" value
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForwardedElement {
+    /// The client the proxy received the request from
+    pub r#for: Option<ForwardedNode>,
+    /// The interface the proxy received the request on
+    pub by: Option<ForwardedNode>,
+    /// The Host field of the request the proxy received
+    pub host: Option<Bytes>,
+    /// The scheme of the request the proxy received
+    pub proto: Option<Bytes>,
+}
+
+/// Every element of the field, parameters other than `for`, `by`, `host` and `proto` are
+/// ignored
+/// SPEC: RFC 7239 - 4. Each parameter must not occur more than once per element
+impl HeaderValueTrait for Vec<ForwardedElement> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let mut elements = Vec::new();
+        for line in value.iter() {
+            for element in split_unquoted(line, b',') {
+                if element.trim_ascii().is_empty() {
+                    continue;
+                }
+                let mut parsed = ForwardedElement::default();
+                for pair in split_unquoted(element, b';') {
+                    if pair.trim_ascii().is_empty() {
+                        continue;
+                    }
+                    let (name, value) = parse_param(pair).ok_or_else(invalid)?;
+                    let (slot, value) = match name.to_ascii_lowercase().as_slice() {
+                        b"for" => (&mut parsed.r#for, ForwardedNode::parse(&value)),
+                        b"by" => (&mut parsed.by, ForwardedNode::parse(&value)),
+                        b"host" => {
+                            set_once(&mut parsed.host, value)?;
+                            continue;
+                        }
+                        b"proto" => {
+                            set_once(&mut parsed.proto, value)?;
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    set_once(slot, value.ok_or_else(invalid)?)?;
+                }
+                elements.push(parsed);
+            }
+        }
+        Ok(elements)
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for element in self {
+            let pairs = [
+                ("by", element.by.map(|by| by.to_string())),
+                ("for", element.r#for.map(|node| node.to_string())),
+                ("host", element.host.map(lossy)),
+                ("proto", element.proto.map(lossy)),
+            ];
+            let pairs: Vec<_> = pairs
+                .into_iter()
+                .filter_map(|(name, value)| Some(format!("{name}={}", quote(&value?))))
+                .collect();
+            value.push(Bytes::from(pairs.join(";")));
+        }
+    }
+}
+
+fn lossy(bytes: Bytes) -> String {
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// ABNF: value = token / quoted-string
+fn quote(value: &str) -> String {
+    if !value.is_empty() && value.bytes().all(is_tchar) {
+        return value.to_owned();
+    }
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T) -> Result<(), HeaderParseError> {
+    match slot.replace(value) {
+        Some(_) => Err(invalid()),
+        None => Ok(()),
+    }
+}
+
+/// The de facto X-Forwarded-For field, each proxy appends the address of its client
+/// ABNF: X-Forwarded-For = 1#node
+impl HeaderValueTrait for Vec<ForwardedNode> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let elements = Vec::<Bytes>::from_header_value(value)?;
+        elements
+            .iter()
+            .map(|element| ForwardedNode::parse(element).ok_or_else(invalid))
+            .collect()
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for node in self {
+            value.push(Bytes::from(match node {
+                // IPv6 addresses are usually sent without brackets
+                ForwardedNode::Ip(addr, None) => addr.to_string(),
+                node => node.to_string(),
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    fn value(lines: &[&'static str]) -> HeaderValue {
+        let mut value = HeaderValue::new();
+        for line in lines {
+            value.push(Bytes::from_static(line.as_bytes()));
+        }
+        value
+    }
+
+    #[test]
+    fn parse_nodes() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 60));
+        let v6 = IpAddr::V6("2001:db8:cafe::17".parse::<Ipv6Addr>().unwrap());
+        for (node, parsed) in [
+            (&b"192.0.2.60"[..], Some(ForwardedNode::Ip(v4, None))),
+            (b"192.0.2.60:8080", Some(ForwardedNode::Ip(v4, Some(8080)))),
+            (
+                b"[2001:db8:cafe::17]:47011",
+                Some(ForwardedNode::Ip(v6, Some(47011))),
+            ),
+            (b"2001:db8:cafe::17", Some(ForwardedNode::Ip(v6, None))),
+            (b"192.0.2.60:_port", Some(ForwardedNode::Ip(v4, None))),
+            (b"Unknown", Some(ForwardedNode::Unknown)),
+            (
+                b"_hidden.1",
+                Some(ForwardedNode::Obfuscated(Bytes::from_static(b"_hidden.1"))),
+            ),
+            (b"_", None),
+            (b"192.0.2.60:99999", None),
+            (b"[2001:db8:cafe::17", None),
+            (b"example.com", None),
+        ] {
+            assert_eq!(ForwardedNode::parse(node), parsed, "{node:?}");
+        }
+    }
+
+    #[test]
+    fn parse_forwarded() {
+        let elements = Vec::<ForwardedElement>::from_header_value(&value(&[
+            "for=192.0.2.60;proto=https;by=203.0.113.43;ext=\"a,b\"",
+            "for=\"[2001:db8:cafe::17]:4711\", For=unknown;Host=\"example.com\"",
+        ]))
+        .unwrap();
+        assert_eq!(elements.len(), 3);
+        assert_eq!(
+            elements[0].r#for,
+            Some(ForwardedNode::Ip("192.0.2.60".parse().unwrap(), None))
+        );
+        assert_eq!(elements[0].proto.as_deref(), Some(&b"https"[..]));
+        assert_eq!(
+            elements[1].r#for,
+            Some(ForwardedNode::Ip(
+                "2001:db8:cafe::17".parse().unwrap(),
+                Some(4711)
+            ))
+        );
+        assert_eq!(elements[2].r#for, Some(ForwardedNode::Unknown));
+        assert_eq!(elements[2].host.as_deref(), Some(&b"example.com"[..]));
+
+        let mut header = HeaderValue::new();
+        elements.to_header_value(&mut header);
+        assert_eq!(
+            header.collect(),
+            "by=203.0.113.43;for=192.0.2.60;proto=https, \
+             for=\"[2001:db8:cafe::17]:4711\", for=unknown;host=example.com"
+        );
+
+        for invalid in ["for=192.0.2.60;for=192.0.2.61", "for=example.com", "for"] {
+            assert!(Vec::<ForwardedElement>::from_header_value(&value(&[invalid])).is_err());
+        }
+    }
+
+    #[test]
+    fn parse_x_forwarded_for() {
+        let nodes =
+            Vec::<ForwardedNode>::from_header_value(&value(&["203.0.113.195, 2001:db8::1"]))
+                .unwrap();
+        assert_eq!(
+            nodes.iter().map(ForwardedNode::ip).collect::<Vec<_>>(),
+            [
+                Some("203.0.113.195".parse().unwrap()),
+                Some("2001:db8::1".parse().unwrap())
+            ]
+        );
+        assert!(Vec::<ForwardedNode>::from_header_value(&value(&["1.2.3.4, nope"])).is_err());
+    }
+}
@@ -1,14 +1,20 @@
-use std::{fmt, num::ParseIntError};
+use std::{
+    fmt,
+    num::ParseIntError,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::http::{
+    HttpDate,
     header::{Builtin, HeaderName},
-    parser::{HttpParseError, Location, ParseErrorKind},
+    parser::{HttpParseError, Location as ParseLocation, ParseErrorKind},
+    request::Request,
     uri::{MalformedUriError, UriHost, UriPort},
 };
 use bytes::Bytes;
 use uhsapi::ascii::{AsciiStr, InvalidAsciiError};
 
-use super::HeaderValue;
+use super::{HeaderMap, HeaderValue};
 
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum HeaderParseError {
@@ -67,16 +73,32 @@ impl HeaderValueTrait for HostWithPort {
         if value.len() != 1 {
             return Err(HeaderParseError::HttpParseError(HttpParseError {
                 kind: ParseErrorKind::DuplicateHeader,
-                location: Location::Headers,
+                location: ParseLocation::Headers,
                 offset: 0,
                 line: None,
             }));
         }
         let s = std::str::from_utf8(&value[0]).map_err(|_| InvalidAsciiError)?;
+        s.parse()
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        value.push(Bytes::from(self.to_string()));
+    }
+}
 
+/// ABNF: Host = uri-host [ ":" port ]
+impl std::str::FromStr for HostWithPort {
+    type Err = HeaderParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         if let Some((host, port)) = s.rsplit_once(':') {
+            // ABNF: port = *DIGIT, so the port may be empty
             if port.is_empty() {
-                todo!("handle empty port")
+                return Ok(Self {
+                    host: host.parse()?,
+                    port: None,
+                });
             }
             if port.bytes().all(|c| c.is_ascii_digit()) {
                 return Ok(Self {
@@ -90,9 +112,15 @@ impl HeaderValueTrait for HostWithPort {
             port: None,
         })
     }
+}
 
-    fn to_header_value(self, value: &mut HeaderValue) {
-        todo!()
+impl fmt::Display for HostWithPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.host, f)?;
+        match self.port {
+            Some(port) => write!(f, ":{port}"),
+            None => Ok(()),
+        }
     }
 }
 
@@ -101,7 +129,7 @@ impl HeaderValueTrait for u64 {
         if value.len() != 1 {
             return Err(HeaderParseError::HttpParseError(HttpParseError {
                 kind: ParseErrorKind::DuplicateHeader,
-                location: Location::Headers,
+                location: ParseLocation::Headers,
                 offset: 0,
                 line: None,
             }));
@@ -111,9 +139,6 @@ impl HeaderValueTrait for u64 {
     }
 
     fn to_header_value(self, value: &mut HeaderValue) {
-        if !value.is_empty() {
-            todo!("content-length should not be set");
-        }
         value.push(Bytes::from(self.to_string()));
     }
 }
@@ -138,74 +163,624 @@ pub enum TransferEncodingKind {
     ///     chunk-data     = 1*OCTET ; a sequence of chunk-size octets
     Chunked,
     Compression(CompressionMethod),
+    /// A coding which isn't known, with any parameters it has
+    Unknown(Bytes),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionMethod {
     Compress,
     Deflate,
     Gzip,
 }
 
-impl HeaderValueTrait for TransferEncodingKind {
+impl TransferEncodingKind {
+    /// SPEC: RFC 9112 - 7. Transfer Codings
+    /// The x-compress and x-gzip aliases must be treated as compress and gzip
+    fn parse(element: Bytes) -> Self {
+        let end = element
+            .iter()
+            .position(|&b| b == b';' || b == b' ' || b == b'\t');
+        let name = &element[..end.unwrap_or(element.len())];
+        let is = |coding: &[u8]| name.eq_ignore_ascii_case(coding);
+        match () {
+            _ if is(b"chunked") => Self::Chunked,
+            _ if is(b"compress") || is(b"x-compress") => {
+                Self::Compression(CompressionMethod::Compress)
+            }
+            _ if is(b"deflate") => Self::Compression(CompressionMethod::Deflate),
+            _ if is(b"gzip") || is(b"x-gzip") => Self::Compression(CompressionMethod::Gzip),
+            _ => Self::Unknown(element),
+        }
+    }
+
+    fn token(&self) -> Bytes {
+        Bytes::from_static(match self {
+            Self::Chunked => b"chunked",
+            Self::Compression(CompressionMethod::Compress) => b"compress",
+            Self::Compression(CompressionMethod::Deflate) => b"deflate",
+            Self::Compression(CompressionMethod::Gzip) => b"gzip",
+            Self::Unknown(coding) => return coding.clone(),
+        })
+    }
+}
+
+/// The codings of the field, in the order they were applied
+impl HeaderValueTrait for Vec<TransferEncodingKind> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        Ok(value
+            .iter_elements()
+            .map(TransferEncodingKind::parse)
+            .collect())
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for coding in self {
+            value.push(coding.token());
+        }
+    }
+}
+
+/// The options of the Connection field, as a set, an option listed more than once is only
+/// kept once
+/// SPEC: RFC 9110 - 7.6.1. Connection
+/// ABNF: Connection = #connection-option
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    /// The connection will be closed after the current message
+    /// SPEC: RFC 9112 - 9.6. Tear-down
+    pub close: bool,
+    /// An HTTP/1.0 connection persists after the current message
+    /// SPEC: RFC 9112 - C.2.2. Keep-Alive Connections
+    pub keep_alive: bool,
+    /// The Upgrade field applies to this connection
+    /// SPEC: RFC 9110 - 7.8. Upgrade
+    pub upgrade: bool,
+    /// The other options, which name the hop-by-hop fields of the message
+    pub fields: Vec<Bytes>,
+}
+
+impl ConnectionOptions {
+    pub fn close() -> Self {
+        Self {
+            close: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn keep_alive() -> Self {
+        Self {
+            keep_alive: true,
+            ..Self::default()
+        }
+    }
+
+    /// The options of the Connection field of `headers`, none if it has no Connection field
+    pub fn of(headers: &HeaderMap) -> Self {
+        let value = headers.get(&Connection::NAME);
+        value.map_or_else(Self::default, |value| {
+            Self::from_header_value(value).unwrap_or_default()
+        })
+    }
+
+    /// Whether `option` is listed, compared case insensitively
+    pub fn contains(&self, option: &[u8]) -> bool {
+        let is = |name: &[u8]| option.eq_ignore_ascii_case(name);
+        (self.close && is(b"close"))
+            || (self.keep_alive && is(b"keep-alive"))
+            || (self.upgrade && is(b"upgrade"))
+            || self.fields.iter().any(|field| is(field))
+    }
+
+    fn insert(&mut self, option: Bytes) {
+        if option.eq_ignore_ascii_case(b"close") {
+            self.close = true;
+        } else if option.eq_ignore_ascii_case(b"keep-alive") {
+            self.keep_alive = true;
+        } else if option.eq_ignore_ascii_case(b"upgrade") {
+            self.upgrade = true;
+        } else if !self.contains(&option) {
+            self.fields.push(option);
+        }
+    }
+}
+
+impl HeaderValueTrait for ConnectionOptions {
     fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
-        todo!()
+        let mut options = Self::default();
+        for option in value.iter_elements() {
+            options.insert(option);
+        }
+        Ok(options)
     }
 
     fn to_header_value(self, value: &mut HeaderValue) {
-        todo!()
+        let flags = [
+            (self.close, "Close"),
+            (self.keep_alive, "Keep-Alive"),
+            (self.upgrade, "Upgrade"),
+        ];
+        for (_, option) in flags.into_iter().filter(|(set, _)| *set) {
+            value.push(Bytes::from_static(option.as_bytes()));
+        }
+        for field in self.fields {
+            value.push(field);
+        }
     }
 }
 
+/// A protocol a client asks to switch the connection to, or the server switches it to
+/// SPEC: RFC 9110 - 7.8. Upgrade
+/// ABNF:
+///     protocol         = protocol-name ["/" protocol-version]
+///     protocol-name    = token
+///     protocol-version = token
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub enum ConnectionType {
-    ProxyConnection,
-    KeepAlive,
-    TE,
-    TransferEncoding,
-    Upgrade,
-    Close,
-    Unknown(Bytes),
+pub struct Protocol {
+    pub name: Bytes,
+    pub version: Option<Bytes>,
+}
+
+impl Protocol {
+    /// Panics if `name` is not a token
+    pub fn new(name: impl Into<Bytes>) -> Self {
+        let name = name.into();
+        assert!(is_token(&name), "invalid protocol name");
+        Self {
+            name,
+            version: None,
+        }
+    }
+
+    /// Panics if `version` is not a token
+    pub fn with_version(mut self, version: impl Into<Bytes>) -> Self {
+        let version = version.into();
+        assert!(is_token(&version), "invalid protocol version");
+        self.version = Some(version);
+        self
+    }
+
+    pub fn parse(element: &Bytes) -> Option<Self> {
+        let (name, version) = match element.iter().position(|&b| b == b'/') {
+            Some(slash) => (element.slice(..slash), Some(element.slice(slash + 1..))),
+            None => (element.clone(), None),
+        };
+        if !is_token(&name) || version.as_ref().is_some_and(|version| !is_token(version)) {
+            return None;
+        }
+        Some(Self { name, version })
+    }
+
+    /// Whether this protocol is `other`, names are compared ignoring case, a protocol without a
+    /// version is any version of it
+    pub fn matches(&self, other: &Protocol) -> bool {
+        self.name.eq_ignore_ascii_case(&other.name)
+            && match (&self.version, &other.version) {
+                (Some(version), Some(other)) => version == other,
+                _ => true,
+            }
+    }
+
+    /// Picks the protocol of `supported` to switch to, from the ones the client offered with
+    /// `request`, in the client's order of preference
+    /// The [`UpgradeOffer`] recorded by the server is used, so the offer is still seen once the
+    /// hop-by-hop fields have been stripped, otherwise it is read from the fields
+    pub fn select<'a>(request: &Request, supported: &'a [Protocol]) -> Option<&'a Protocol> {
+        let offered = match request.extensions.get::<UpgradeOffer>() {
+            Some(offer) => offer.0.clone(),
+            None => UpgradeOffer::of(&request.headers)?.0,
+        };
+        offered
+            .iter()
+            .find_map(|offered| supported.iter().find(|protocol| offered.matches(protocol)))
+    }
+}
+
+/// The protocols a client offered to switch the connection to, in order of preference
+/// Inserted into the request extensions by the server before hop-by-hop fields are removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradeOffer(pub Vec<Protocol>);
+
+impl UpgradeOffer {
+    /// The offer made in `headers`
+    /// A client which doesn't list the upgrade option in the Connection field is not offering
+    /// any, as the Upgrade field may have been forwarded from another connection
+    pub fn of(headers: &HeaderMap) -> Option<Self> {
+        if !ConnectionOptions::of(headers).upgrade {
+            return None;
+        }
+        let offered = headers.get_header::<Upgrade>().ok().flatten()?;
+        (!offered.is_empty()).then_some(Self(offered))
+    }
 }
 
-impl ConnectionType {
-    const MAP: &[(&'static [u8], ConnectionType)] = &[
-        (b"Proxy-Connection", Self::ProxyConnection),
-        (b"Keep-Alive", Self::KeepAlive),
-        (b"TE", Self::TE),
-        (b"Transfer-Encoding", Self::TransferEncoding),
-        (b"Upgrade", Self::Upgrade),
-        (b"Close", Self::Close),
-    ];
+fn is_token(bytes: &[u8]) -> bool {
+    !bytes.is_empty() && bytes.iter().copied().all(crate::http::parser::is_tchar)
 }
 
-impl fmt::Display for ConnectionType {
+impl fmt::Display for Protocol {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::ProxyConnection => f.write_str("Proxy-Connection"),
-            Self::KeepAlive => f.write_str("Keep-Alive"),
-            Self::TE => f.write_str("TE"),
-            Self::TransferEncoding => f.write_str("Transfer-Encoding"),
-            Self::Upgrade => f.write_str("Upgrade"),
-            Self::Close => f.write_str("Close"),
-            Self::Unknown(bytes) => {
-                f.write_str(std::str::from_utf8(&bytes).expect("should be valid ascii"))
+        f.write_str(&String::from_utf8_lossy(&self.name))?;
+        match &self.version {
+            Some(version) => write!(f, "/{}", String::from_utf8_lossy(version)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The protocols of the field in order of preference, invalid elements are left out
+/// ABNF: Upgrade = #protocol
+impl HeaderValueTrait for Vec<Protocol> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        Ok(value
+            .iter_elements()
+            .filter_map(|element| Protocol::parse(&element))
+            .collect())
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for protocol in self {
+            value.push(Bytes::from(protocol.to_string()));
+        }
+    }
+}
+
+/// How long and for how many more requests the sender will keep a persistent connection open
+/// SPEC: RFC 2068 - 19.7.1.1. The Keep-Alive Header
+/// ABNF: Keep-Alive = #( "timeout=" delta-seconds / "max=" 1*DIGIT / keepalive-extension )
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeepAliveParams {
+    /// Seconds an idle connection is kept open
+    pub timeout: Option<u64>,
+    /// How many more requests the connection will be used for
+    pub max: Option<u64>,
+}
+
+/// Unknown and malformed parameters are ignored
+impl HeaderValueTrait for KeepAliveParams {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let mut params = Self::default();
+        for element in value.iter_elements() {
+            let Some(eq) = element.iter().position(|&b| b == b'=') else {
+                continue;
+            };
+            let (name, value) = (element[..eq].trim_ascii(), element[eq + 1..].trim_ascii());
+            let Some(value) = std::str::from_utf8(value)
+                .ok()
+                .and_then(|value| value.parse().ok())
+            else {
+                continue;
+            };
+            if name.eq_ignore_ascii_case(b"timeout") {
+                params.timeout = Some(value);
+            } else if name.eq_ignore_ascii_case(b"max") {
+                params.max = Some(value);
             }
         }
+        Ok(params)
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        if let Some(timeout) = self.timeout {
+            value.push(Bytes::from(format!("timeout={timeout}")));
+        }
+        if let Some(max) = self.max {
+            value.push(Bytes::from(format!("max={max}")));
+        }
     }
 }
 
-impl HeaderValueTrait for ConnectionType {
+/// The HSTS policy of a host, which user agents keep to only use HTTPS to reach it
+/// SPEC: RFC 6797 - 6.1. Strict-Transport-Security HTTP Response Header Field
+/// ABNF: Strict-Transport-Security = "Strict-Transport-Security" ":" [ directive ]
+///     *( ";" [ directive ] )
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrictTransportSecurityValue {
+    /// Seconds the policy is kept for, 0 makes the user agent forget it
+    pub max_age: u64,
+    /// Whether the policy also applies to subdomains of the host
+    pub include_subdomains: bool,
+    /// Asks to be included in the preload lists of browsers, which is not part of RFC 6797
+    pub preload: bool,
+}
+
+impl StrictTransportSecurityValue {
+    pub fn new(max_age: u64) -> Self {
+        Self {
+            max_age,
+            include_subdomains: false,
+            preload: false,
+        }
+    }
+
+    pub fn with_include_subdomains(mut self, include_subdomains: bool) -> Self {
+        self.include_subdomains = include_subdomains;
+        self
+    }
+
+    pub fn with_preload(mut self, preload: bool) -> Self {
+        self.preload = preload;
+        self
+    }
+
+    /// Unknown directives are ignored, a missing max-age or a repeated directive is invalid
+    /// SPEC: RFC 6797 - 6.1. Strict-Transport-Security HTTP Response Header Field
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let (mut max_age, mut include_subdomains, mut preload) = (None, false, false);
+        let mut seen = Vec::new();
+        for directive in super::auth::split_unquoted(bytes, b';') {
+            let directive = directive.trim_ascii();
+            if directive.is_empty() {
+                continue;
+            }
+            let name = match directive.iter().position(|&b| b == b'=') {
+                Some(eq) => directive[..eq].trim_ascii(),
+                None => directive,
+            };
+            let name = name.to_ascii_lowercase();
+            if seen.contains(&name) {
+                return None;
+            }
+            match &name[..] {
+                b"max-age" => {
+                    let (_, value) = super::auth::parse_param(directive)?;
+                    max_age = Some(std::str::from_utf8(&value).ok()?.parse().ok()?);
+                }
+                b"includesubdomains" => include_subdomains = true,
+                b"preload" => preload = true,
+                _ => {}
+            }
+            seen.push(name);
+        }
+        Some(Self {
+            max_age: max_age?,
+            include_subdomains,
+            preload,
+        })
+    }
+}
+
+impl fmt::Display for StrictTransportSecurityValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "max-age={}", self.max_age)?;
+        if self.include_subdomains {
+            f.write_str("; includeSubDomains")?;
+        }
+        if self.preload {
+            f.write_str("; preload")?;
+        }
+        Ok(())
+    }
+}
+
+/// Only the first field line is used
+/// SPEC: RFC 6797 - 8.1. Strict-Transport-Security Response Header Field Processing
+impl HeaderValueTrait for StrictTransportSecurityValue {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        value
+            .iter()
+            .next()
+            .and_then(|line| Self::parse(line))
+            .ok_or_else(super::auth::invalid)
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        value.push(Bytes::from(self.to_string()));
+    }
+}
+
+/// A raw field value, for fields which only allow a single field line
+impl HeaderValueTrait for Bytes {
     fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
         if value.len() != 1 {
-            todo!("handle err");
+            return Err(HeaderParseError::HttpParseError(HttpParseError {
+                kind: ParseErrorKind::DuplicateHeader,
+                location: ParseLocation::Headers,
+                offset: 0,
+                line: None,
+            }));
+        }
+        Ok(value[0].clone())
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        value.push(self);
+    }
+}
+
+/// The elements of a list based field, which may be split over multiple field lines
+/// SPEC: RFC 9110 - 5.6.1. Lists (#rule ABNF Extension)
+/// ABNF: #element => [ element ] *( OWS "," OWS [ element ] )
+impl HeaderValueTrait for Vec<Bytes> {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        Ok(value.iter_elements().collect())
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        for element in self {
+            value.push(element);
+        }
+    }
+}
+
+/// An Entity Tag, used for conditional requests
+/// SPEC: RFC 9110 - 8.8.3. ETag
+/// ABNF:
+///     ETag       = entity-tag
+///     entity-tag = [ weak ] opaque-tag
+///     weak       = %s"W/"
+///     opaque-tag = DQUOTE *etagc DQUOTE
+///     etagc      = %x21 / %x23-7E / obs-text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityTag {
+    pub weak: bool,
+    /// The opaque tag, without the surrounding quotes
+    pub tag: Bytes,
+}
+
+impl EntityTag {
+    pub fn strong(tag: impl Into<Bytes>) -> Self {
+        Self {
+            weak: false,
+            tag: tag.into(),
+        }
+    }
+
+    pub fn weak(tag: impl Into<Bytes>) -> Self {
+        Self {
+            weak: true,
+            tag: tag.into(),
+        }
+    }
+
+    /// A strong tag from a hash of the content, which changes whenever a byte of it does
+    pub fn from_content(content: &[u8]) -> Self {
+        Self::strong(format!("{:016x}", fingerprint(content)))
+    }
+
+    /// A weak tag from the modification time and size of a file
+    /// Two writes within the same second may leave both unchanged, so the tag is only weak
+    pub fn from_metadata(modified: SystemTime, len: u64) -> Self {
+        let modified = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Self::weak(format!(
+            "{:x}.{:x}-{len:x}",
+            modified.as_secs(),
+            modified.subsec_nanos()
+        ))
+    }
+
+    /// SPEC: RFC 9110 - 8.8.3.2. Comparison
+    /// Both tags have to be strong, and their opaque tags equal
+    pub fn strong_eq(&self, other: &Self) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// SPEC: RFC 9110 - 8.8.3.2. Comparison
+    /// The opaque tags are equal, regardless of either being weak
+    pub fn weak_eq(&self, other: &Self) -> bool {
+        self.tag == other.tag
+    }
+
+    pub fn parse(bytes: &Bytes) -> Option<Self> {
+        let (weak, opaque) = match bytes.strip_prefix(b"W/") {
+            Some(opaque) => (true, opaque),
+            None => (false, &bytes[..]),
+        };
+        let tag = opaque.strip_prefix(b"\"")?.strip_suffix(b"\"")?;
+        if !tag
+            .iter()
+            .all(|&b| b == 0x21 || (0x23..=0x7E).contains(&b) || b >= 0x80)
+        {
+            return None;
         }
-        let val = &value[0];
-        for (str, ty) in Self::MAP {
-            if val.eq_ignore_ascii_case(str) {
-                return Ok(ty.clone());
+        Some(Self {
+            weak,
+            tag: bytes.slice_ref(tag),
+        })
+    }
+}
+
+impl fmt::Display for EntityTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            f.write_str("W/")?;
+        }
+        write!(f, "\"{}\"", String::from_utf8_lossy(&self.tag))
+    }
+}
+
+impl HeaderValueTrait for EntityTag {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let raw = Bytes::from_header_value(value)?;
+        Self::parse(&raw).ok_or(HeaderParseError::HttpParseError(HttpParseError {
+            kind: ParseErrorKind::InvalidHeaderValue,
+            location: ParseLocation::Headers,
+            offset: 0,
+            line: None,
+        }))
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        value.push(Bytes::from(self.to_string()));
+    }
+}
+
+/// 64-bit FNV-1a, which is stable between builds and platforms
+/// A fingerprint only needs to change when the content does, it is not a security boundary
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// The entity tags a precondition is evaluated against
+/// SPEC: RFC 9110 - 13.1.2. If-None-Match
+/// ABNF: If-None-Match = "*" / #entity-tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityTagMatch {
+    /// Matches any current representation of the resource
+    Any,
+    Tags(Vec<EntityTag>),
+}
+
+impl EntityTagMatch {
+    /// Whether `etag` is matched, using the weak comparison of If-None-Match
+    pub fn matches_weak(&self, etag: &EntityTag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        }
+    }
+
+    /// Whether `etag` is matched, using the strong comparison of If-Match
+    pub fn matches_strong(&self, etag: &EntityTag) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Tags(tags) => tags.iter().any(|tag| tag.strong_eq(etag)),
+        }
+    }
+}
+
+/// Invalid entity tags are left out, a `*` has to be the only element
+impl HeaderValueTrait for EntityTagMatch {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let elements = Vec::<Bytes>::from_header_value(value)?;
+        if let [element] = &elements[..]
+            && &element[..] == b"*"
+        {
+            return Ok(Self::Any);
+        }
+        Ok(Self::Tags(
+            elements.iter().filter_map(EntityTag::parse).collect(),
+        ))
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        match self {
+            Self::Any => value.push(Bytes::from_static(b"*")),
+            Self::Tags(tags) => {
+                for tag in tags {
+                    value.push(Bytes::from(tag.to_string()));
+                }
             }
         }
-        Ok(Self::Unknown(val.clone()))
+    }
+}
+
+/// A date field, such as Last-Modified
+/// SPEC: RFC 9110 - 5.6.7. Date/Time Formats
+impl HeaderValueTrait for HttpDate {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let raw = Bytes::from_header_value(value)?;
+        std::str::from_utf8(&raw)
+            .ok()
+            .and_then(|raw| raw.parse().ok())
+            .ok_or(HeaderParseError::HttpParseError(HttpParseError {
+                kind: ParseErrorKind::InvalidHeaderValue,
+                location: ParseLocation::Headers,
+                offset: 0,
+                line: None,
+            }))
     }
 
     fn to_header_value(self, value: &mut HeaderValue) {
@@ -213,7 +788,104 @@ impl HeaderValueTrait for ConnectionType {
     }
 }
 
+/// An Expectation
+/// SPEC: RFC 9110 - 10.1.1. Expect
+/// ABNF: Expect = #expectation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    /// The only expectation defined by the spec
+    Continue,
+    Unknown(Bytes),
+}
+
+impl HeaderValueTrait for Expectation {
+    fn from_header_value(value: &HeaderValue) -> Result<Self, HeaderParseError> {
+        let raw = Bytes::from_header_value(value)?;
+        if raw.eq_ignore_ascii_case(b"100-continue") {
+            Ok(Self::Continue)
+        } else {
+            Ok(Self::Unknown(raw))
+        }
+    }
+
+    fn to_header_value(self, value: &mut HeaderValue) {
+        value.push(match self {
+            Self::Continue => Bytes::from_static(b"100-continue"),
+            Self::Unknown(bytes) => bytes,
+        });
+    }
+}
+
 header_struct!(Host, b"host", HostWithPort);
 header_struct!(ContentLength, b"content-length", u64);
-header_struct!(TransferEncoding, b"transfer-encoding", TransferEncodingKind);
-header_struct!(Connection, b"connection", ConnectionType);
+header_struct!(
+    TransferEncoding,
+    b"transfer-encoding",
+    Vec<TransferEncodingKind>
+);
+header_struct!(Connection, b"connection", ConnectionOptions);
+header_struct!(KeepAlive, b"keep-alive", KeepAliveParams);
+header_struct!(
+    ContentDisposition,
+    b"content-disposition",
+    super::ContentDispositionValue
+);
+header_struct!(
+    StrictTransportSecurity,
+    b"strict-transport-security",
+    StrictTransportSecurityValue
+);
+header_struct!(ContentLocation, b"content-location", Bytes);
+header_struct!(ContentType, b"content-type", Bytes);
+header_struct!(Trailer, b"trailer", Vec<Bytes>);
+header_struct!(Accept, b"accept", Vec<super::MediaRange>);
+header_struct!(AcceptCharset, b"accept-charset", Vec<Bytes>);
+header_struct!(AcceptEncoding, b"accept-encoding", Vec<Bytes>);
+header_struct!(AcceptLanguage, b"accept-language", Vec<Bytes>);
+header_struct!(AcceptRanges, b"accept-ranges", Vec<Bytes>);
+header_struct!(Age, b"age", u64);
+header_struct!(Allow, b"allow", Vec<Bytes>);
+header_struct!(Authorization, b"authorization", super::Credentials);
+header_struct!(CacheControl, b"cache-control", Vec<Bytes>);
+header_struct!(ContentEncoding, b"content-encoding", Vec<Bytes>);
+header_struct!(ContentLanguage, b"content-language", Vec<Bytes>);
+header_struct!(ContentRange, b"content-range", Bytes);
+header_struct!(Cookie, b"cookie", crate::http::cookie::Cookies);
+header_struct!(ETag, b"etag", EntityTag);
+header_struct!(Expect, b"expect", Expectation);
+header_struct!(IfMatch, b"if-match", EntityTagMatch);
+header_struct!(IfModifiedSince, b"if-modified-since", HttpDate);
+header_struct!(IfNoneMatch, b"if-none-match", EntityTagMatch);
+header_struct!(IfUnmodifiedSince, b"if-unmodified-since", HttpDate);
+header_struct!(LastModified, b"last-modified", HttpDate);
+header_struct!(Location, b"location", Bytes);
+header_struct!(MaxForwards, b"max-forwards", u64);
+header_struct!(
+    ProxyAuthorization,
+    b"proxy-authorization",
+    super::Credentials
+);
+header_struct!(
+    ProxyAuthenticate,
+    b"proxy-authenticate",
+    Vec<super::Challenge>
+);
+header_struct!(Range, b"range", Bytes);
+header_struct!(Referer, b"referer", Bytes);
+header_struct!(Server, b"server", Bytes);
+header_struct!(TE, b"te", Vec<Bytes>);
+header_struct!(Upgrade, b"upgrade", Vec<Protocol>);
+header_struct!(UserAgent, b"user-agent", Bytes);
+header_struct!(Vary, b"vary", Vec<Bytes>);
+header_struct!(Via, b"via", Vec<Bytes>);
+header_struct!(WWWAuthenticate, b"www-authenticate", Vec<super::Challenge>);
+header_struct!(Link, b"link", Vec<Bytes>);
+header_struct!(Forwarded, b"forwarded", Vec<super::ForwardedElement>);
+header_struct!(XForwardedFor, b"x-forwarded-for", Vec<super::ForwardedNode>);
+header_struct!(XForwardedProto, b"x-forwarded-proto", Vec<Bytes>);
+header_struct!(XForwardedHost, b"x-forwarded-host", Vec<Bytes>);
+header_struct!(
+    SetCookie,
+    b"set-cookie",
+    Vec<crate::http::cookie::SetCookie>
+);
@@ -1,35 +1,223 @@
-use std::collections::{HashMap, hash_map};
+use std::{collections::HashMap, slice, vec};
 
 use bytes::Bytes;
 
-use crate::http::header::{Builtin, HeaderField, HeaderParseError};
+use crate::http::header::{Builtin, HeaderField, HeaderParseError, HeaderValueTrait};
 
 use super::{HeaderName, HeaderValue};
 
+/// The fields of a message, kept in the order they were first added, which is the order they are
+/// sent in
+/// Fields are found by comparing names until there are more than [`HeaderMap::INDEX_THRESHOLD`]
+/// of them, which is faster than hashing for the few fields most messages have, and through a
+/// hashed index beyond that
 #[derive(Debug, Clone)]
 pub struct HeaderMap {
-    map: HashMap<HeaderName, HeaderValue>,
+    entries: Vec<(HeaderName, HeaderValue)>,
+    /// The position of each name in `entries`, only built once there are too many to search
+    index: Option<HashMap<HeaderName, usize>>,
+}
+
+impl Default for HeaderMap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HeaderMap {
+    const INDEX_THRESHOLD: usize = 16;
+
     pub fn new() -> Self {
         Self {
-            map: HashMap::new(),
+            entries: Vec::new(),
+            index: None,
         }
     }
 
     pub fn with_capacity(size: usize) -> Self {
         Self {
-            map: HashMap::with_capacity(size),
+            entries: Vec::with_capacity(size),
+            index: None,
         }
     }
 
+    /// Removes every field, keeping the allocated capacity
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        if let Some(index) = &mut self.index {
+            index.clear();
+        }
+    }
+
+    fn position(&self, name: &HeaderName) -> Option<usize> {
+        match &self.index {
+            Some(index) if self.entries.len() > Self::INDEX_THRESHOLD => index.get(name).copied(),
+            _ => self.entries.iter().position(|(entry, _)| entry == name),
+        }
+    }
+
+    /// Rebuilds the index, once there are enough fields to need it, or after fields were
+    /// removed, as the positions after them moved
+    fn reindex(&mut self) {
+        if self.entries.len() <= Self::INDEX_THRESHOLD {
+            if let Some(index) = &mut self.index {
+                index.clear();
+            }
+            return;
+        }
+        let index = self.index.get_or_insert_default();
+        index.clear();
+        let positions = self.entries.iter().enumerate();
+        index.extend(positions.map(|(i, (name, _))| (name.clone(), i)));
+    }
+
     pub fn entry(&mut self, name: HeaderName) -> &mut HeaderValue {
-        self.map.entry(name).or_insert(HeaderValue::default())
+        let i = match self.position(&name) {
+            Some(i) => i,
+            None => {
+                let i = self.entries.len();
+                self.entries.push((name, HeaderValue::default()));
+                if i == Self::INDEX_THRESHOLD {
+                    self.reindex();
+                } else if i > Self::INDEX_THRESHOLD {
+                    let name = self.entries[i].0.clone();
+                    self.index
+                        .as_mut()
+                        .expect("the index was built")
+                        .insert(name, i);
+                }
+                i
+            }
+        };
+        &mut self.entries[i].1
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, name: &HeaderName) -> bool {
+        self.position(name).is_some()
+    }
+
+    pub fn get(&self, name: &HeaderName) -> Option<&HeaderValue> {
+        self.position(name).map(|i| &self.entries[i].1)
+    }
+
+    /// Returns every field line value of `name`, in the order they were added
+    pub fn get_all(&self, name: &HeaderName) -> impl Iterator<Item = &Bytes> {
+        self.get(name).into_iter().flat_map(HeaderValue::iter)
     }
 
-    pub fn contains(&mut self, name: &HeaderName) -> bool {
-        self.map.contains_key(&name)
+    /// Replaces the values of `name` with `value`, keeping its position, and returns the values
+    /// it replaced
+    /// Panics if the value contains CR, LF or NUL, see [`HeaderValue::try_push`]
+    pub fn insert(&mut self, name: HeaderName, value: Bytes) -> Option<HeaderValue> {
+        let mut values = HeaderValue::new();
+        values.push(value);
+        self.replace(name, values)
+    }
+
+    /// Adds a field line value to `name`, after any it already has
+    /// Panics if the value contains CR, LF or NUL, see [`HeaderValue::try_push`]
+    pub fn append(&mut self, name: HeaderName, value: Bytes) {
+        self.entry(name).push(value);
+    }
+
+    /// Replaces the field `T` with `val`, or removes it when `val` serializes to no values
+    pub fn set_header<T: HeaderField>(&mut self, val: T::Output) {
+        let mut values = HeaderValue::new();
+        val.to_header_value(&mut values);
+        match values.is_empty() {
+            true => drop(self.remove(&T::NAME)),
+            false => drop(self.replace(T::NAME, values)),
+        }
+    }
+
+    fn replace(&mut self, name: HeaderName, values: HeaderValue) -> Option<HeaderValue> {
+        match self.position(&name) {
+            Some(i) => Some(std::mem::replace(&mut self.entries[i].1, values)),
+            None => {
+                *self.entry(name) = values;
+                None
+            }
+        }
+    }
+
+    /// Returns the names of the fields, in the order they were first added
+    pub fn keys(&self) -> impl Iterator<Item = &HeaderName> {
+        self.entries.iter().map(|(name, _)| name)
+    }
+
+    pub fn remove(&mut self, name: &HeaderName) -> Option<HeaderValue> {
+        let i = self.position(name)?;
+        let (_, value) = self.entries.remove(i);
+        self.reindex();
+        Some(value)
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&HeaderName) -> bool) {
+        let len = self.entries.len();
+        self.entries.retain(|(name, _)| keep(name));
+        if self.entries.len() != len {
+            self.reindex();
+        }
+    }
+
+    /// Whether a list based header contains `token`, compared case insensitively
+    pub fn contains_token(&self, name: &HeaderName, token: &[u8]) -> bool {
+        self.get(name)
+            .and_then(|value| Vec::<Bytes>::from_header_value(value).ok())
+            .is_some_and(|elements| {
+                elements
+                    .iter()
+                    .any(|element| element.eq_ignore_ascii_case(token))
+            })
+    }
+
+    /// Adds `name` to the Vary field, for a response which was chosen by the request field
+    /// `name`, so caches don't reuse it for requests with a different value
+    /// A name which is already listed, or a Vary of `*`, is left as it is
+    /// SPEC: RFC 9110 - 12.5.5. Vary
+    pub fn add_vary(&mut self, name: &HeaderName) {
+        let vary = HeaderName::builtin(Builtin::Vary);
+        let listed = self.get(&vary).is_some_and(|value| {
+            value
+                .iter_elements()
+                .any(|listed| &listed[..] == b"*" || listed.eq_ignore_ascii_case(name.as_bytes()))
+        });
+        if !listed {
+            self.append(vary, name.clone().into_bytes());
+        }
+    }
+
+    /// Removes the hop-by-hop fields, which only apply to a single connection and must not be
+    /// forwarded: the Connection field, every field it nominates, and the fields which are
+    /// always hop-by-hop
+    /// SPEC: RFC 9110 - 7.6.1. Connection
+    pub fn remove_hop_by_hop(&mut self) {
+        let connection_name = HeaderName::builtin(Builtin::Connection);
+        if let Some(connection) = self.remove(&connection_name)
+            && let Ok(options) = Vec::<Bytes>::from_header_value(&connection)
+        {
+            for option in options {
+                self.retain(|name| !name.as_bytes().eq_ignore_ascii_case(&option));
+            }
+        }
+        self.retain(|name| {
+            let name = name.as_bytes();
+            !(name.eq_ignore_ascii_case(b"Keep-Alive")
+                || name.eq_ignore_ascii_case(b"TE")
+                || name.eq_ignore_ascii_case(b"Transfer-Encoding")
+                || name.eq_ignore_ascii_case(b"Upgrade")
+                || name
+                    .get(..6)
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(b"Proxy-")))
+        });
     }
 
     pub fn get_header<T: HeaderField>(&self) -> Result<Option<T::Output>, HeaderParseError> {
@@ -37,14 +225,136 @@ impl HeaderMap {
             Builtin::from_bytes(&Bytes::from_static(T::IDENT.as_bytes()))
                 .expect("invalid header name"),
         );
-        let val = match self.map.get(&name) {
+        let val = match self.get(&name) {
             None => return Ok(None),
             Some(val) => val,
         };
         T::parse(val).map(Some)
     }
 
-    pub fn iter(&self) -> hash_map::Iter<'_, HeaderName, HeaderValue> {
-        self.map.iter()
+    /// Iterates over the fields in the order they were first added
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.iter())
+    }
+}
+
+pub struct Iter<'a>(slice::Iter<'a, (HeaderName, HeaderValue)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a HeaderName, &'a HeaderValue);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(name, value)| (name, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl IntoIterator for HeaderMap {
+    type Item = (HeaderName, HeaderValue);
+    type IntoIter = vec::IntoIter<(HeaderName, HeaderValue)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header::{ContentLength, SetCookie};
+
+    fn name(i: usize) -> HeaderName {
+        HeaderName::try_from(&Bytes::from(format!("X-Field-{i}"))).unwrap()
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut headers = HeaderMap::new();
+        for builtin in [Builtin::Host, Builtin::Connection, Builtin::ContentLength] {
+            headers.entry(HeaderName::builtin(builtin));
+        }
+        headers.entry(HeaderName::builtin(Builtin::Host));
+        let names: Vec<_> = headers.iter().map(|(name, _)| name.to_string()).collect();
+        assert_eq!(names, ["Host", "Connection", "Content-Length"]);
+    }
+
+    #[test]
+    fn indexed_beyond_threshold() {
+        let mut headers = HeaderMap::new();
+        for i in 0..40 {
+            headers.entry(name(i)).push(Bytes::from(i.to_string()));
+        }
+        assert!(headers.index.is_some());
+        assert_eq!(headers.len(), 40);
+        for i in 0..40 {
+            assert_eq!(headers.get(&name(i)).unwrap()[0], i.to_string());
+        }
+
+        // Removing fields moves the ones after them
+        for i in (0..40).step_by(2) {
+            assert!(headers.remove(&name(i)).is_some());
+        }
+        assert_eq!(headers.len(), 20);
+        assert!(!headers.contains(&name(0)));
+        for i in (1..40).step_by(2) {
+            assert_eq!(headers.get(&name(i)).unwrap()[0], i.to_string());
+        }
+        let order: Vec<_> = headers.iter().map(|(_, value)| value[0].clone()).collect();
+        let expected: Vec<_> = (1..40)
+            .step_by(2)
+            .map(|i| Bytes::from(i.to_string()))
+            .collect();
+        assert_eq!(order, expected);
+
+        headers.clear();
+        assert!(headers.is_empty() && headers.get(&name(1)).is_none());
+    }
+
+    #[test]
+    fn mutation() {
+        let mut headers = HeaderMap::new();
+        headers.append(name(0), Bytes::from_static(b"a"));
+        headers.set_header::<ContentLength>(5);
+        headers.append(name(0), Bytes::from_static(b"b"));
+        let values: Vec<_> = headers.get_all(&name(0)).collect();
+        assert_eq!(values, [&"a", &"b"]);
+        assert_eq!(headers.get_all(&name(1)).count(), 0);
+
+        let replaced = headers.insert(name(0), Bytes::from_static(b"c")).unwrap();
+        assert_eq!(replaced.len(), 2);
+        assert!(headers.insert(name(1), Bytes::from_static(b"d")).is_none());
+        headers.set_header::<ContentLength>(10);
+        assert_eq!(headers.get_header::<ContentLength>().unwrap(), Some(10));
+
+        let keys: Vec<_> = headers.keys().map(ToString::to_string).collect();
+        assert_eq!(keys, ["X-Field-0", "Content-Length", "X-Field-1"]);
+        assert_eq!(headers.get(&name(0)).unwrap().as_slice(), ["c"]);
+
+        // Nothing to serialize removes the field
+        headers.set_header::<SetCookie>(Vec::new());
+        assert!(!headers.contains(&SetCookie::NAME));
+    }
+
+    #[test]
+    fn vary() {
+        let vary = HeaderName::builtin(Builtin::Vary);
+        let mut headers = HeaderMap::new();
+        headers.append(vary.clone(), Bytes::from_static(b"Origin"));
+        headers.add_vary(&HeaderName::builtin(Builtin::AcceptEncoding));
+        headers.add_vary(&HeaderName::builtin(Builtin::Origin));
+        headers.add_vary(&HeaderName::try_from(&Bytes::from_static(b"accept-encoding")).unwrap());
+        assert_eq!(
+            headers.get(&vary).unwrap().collect(),
+            "Origin, Accept-Encoding"
+        );
+
+        headers.insert(vary.clone(), Bytes::from_static(b"*"));
+        headers.add_vary(&HeaderName::builtin(Builtin::Accept));
+        assert_eq!(headers.get(&vary).unwrap().collect(), "*");
     }
 }
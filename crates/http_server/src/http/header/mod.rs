@@ -1,10 +1,25 @@
 use bytes::{Bytes, BytesMut};
 use smallvec::SmallVec;
-use std::{fmt, ops::Index};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::{self, Index},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 use uhsapi::ascii::{InvalidAsciiError, bytes_are_ascii};
 
-pub use {impls::*, map::*};
+pub use {accept::*, auth::*, disposition::*, forwarded::*, impls::*, map::*};
 
+use auth::split_unquoted;
+
+mod accept;
+mod auth;
+mod disposition;
+mod facade;
+mod forwarded;
 mod impls;
 mod map;
 
@@ -18,22 +33,144 @@ impl HeaderName {
     pub const fn builtin(builtin: Builtin) -> Self {
         Self(Repr::Builtin(builtin))
     }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.0 {
+            Repr::Builtin(builtin) => builtin.as_str().as_bytes(),
+            Repr::Custom(custom) => &custom.value,
+        }
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        match self.0 {
+            Repr::Builtin(builtin) => Bytes::from_static(builtin.as_str().as_bytes()),
+            Repr::Custom(custom) => custom.value,
+        }
+    }
 }
 
 impl TryFrom<&Bytes> for HeaderName {
     type Error = InvalidAsciiError;
 
     fn try_from(bytes: &Bytes) -> Result<Self, Self::Error> {
-        Ok(match Builtin::from_bytes(&bytes) {
+        Ok(match Builtin::from_bytes(bytes) {
             Some(builtin) => Self(Repr::Builtin(builtin)),
             None => {
                 bytes_are_ascii(bytes)?;
-                Self(Repr::Custom(Custom::new(bytes.clone())))
+                Self(Repr::Custom(
+                    interned(bytes).unwrap_or_else(|| Custom::new(bytes.clone())),
+                ))
             }
         })
     }
 }
 
+impl HeaderName {
+    /// The name in `range` of a received head, which was checked to be a token while parsing
+    /// Builtin and interned names are matched in place, only other names take a reference to
+    /// `buf`
+    pub(crate) fn from_parsed(buf: &Bytes, range: ops::Range<usize>) -> Self {
+        let name = &buf[range.clone()];
+        debug_assert!(name.iter().copied().all(crate::http::parser::is_tchar));
+        if let Some(builtin) = Builtin::from_bytes(name) {
+            return Self(Repr::Builtin(builtin));
+        }
+        Self(Repr::Custom(
+            interned(name).unwrap_or_else(|| Custom::new(buf.slice(range))),
+        ))
+    }
+}
+
+/// Names which aren't builtin, but are common enough to be stored once per process rather than
+/// referencing the message they were received in
+const COMMON_NAMES: &[&str] = &[
+    "X-Request-Id",
+    "X-Correlation-Id",
+    "X-Real-IP",
+    "X-Requested-With",
+    "X-CSRF-Token",
+    "X-Frame-Options",
+    "X-Content-Type-Options",
+    "X-Powered-By",
+    "DNT",
+    "Pragma",
+    "Priority",
+    "Proxy-Connection",
+    "Upgrade-Insecure-Requests",
+    "Sec-Fetch-Dest",
+    "Sec-Fetch-Mode",
+    "Sec-Fetch-Site",
+    "Sec-Fetch-User",
+    "Sec-CH-UA",
+    "Sec-CH-UA-Mobile",
+    "Sec-CH-UA-Platform",
+    "Content-Security-Policy",
+    "Referrer-Policy",
+    "Access-Control-Allow-Credentials",
+    "Traceparent",
+    "Tracestate",
+];
+
+/// How many names [`HeaderName::intern`] adds to the table at most, so it can't grow without
+/// bound
+const MAX_INTERNED: usize = 1024;
+
+static COMMON: NameTable<(), { COMMON_NAMES.len() }> = NameTable::new({
+    let mut names = [("", ()); COMMON_NAMES.len()];
+    let mut i = 0;
+    while i < names.len() {
+        names[i].0 = COMMON_NAMES[i];
+        i += 1;
+    }
+    names
+});
+
+/// The names added by [`HeaderName::intern`], only ever appended to, so they are read without
+/// a lock: a slot below [`INTERNED_LEN`] is always set
+static INTERNED: [OnceLock<&'static str>; MAX_INTERNED] = [const { OnceLock::new() }; MAX_INTERNED];
+static INTERNED_LEN: AtomicUsize = AtomicUsize::new(0);
+/// Held while adding a name, so two threads don't add it twice
+static INTERNING: Mutex<()> = Mutex::new(());
+
+/// The interned copy of `name`, names are matched ignoring case, so the interned case is used
+/// Received names are looked up on every request, so this never takes a lock
+fn interned(name: &[u8]) -> Option<Custom> {
+    let common = COMMON.get_name(name);
+    let added = || {
+        INTERNED[..INTERNED_LEN.load(Ordering::Acquire)]
+            .iter()
+            .filter_map(OnceLock::get)
+            .find(|other| other.as_bytes().eq_ignore_ascii_case(name))
+            .copied()
+    };
+    common.or_else(added).map(Custom::from_static)
+}
+
+impl HeaderName {
+    /// Stores `name` once for the process, so received fields with this name share it rather
+    /// than each referencing the message they were received in
+    /// Panics if `name` is not a token
+    pub fn intern(name: &'static str) -> Self {
+        assert!(
+            !name.is_empty() && name.bytes().all(crate::http::parser::is_tchar),
+            "invalid header name"
+        );
+        if let Some(builtin) = Builtin::from_bytes(name.as_bytes()) {
+            return Self::builtin(builtin);
+        }
+        let _adding = INTERNING.lock().unwrap();
+        if let Some(interned) = interned(name.as_bytes()) {
+            return Self(Repr::Custom(interned));
+        }
+        let len = INTERNED_LEN.load(Ordering::Relaxed);
+        if len < MAX_INTERNED {
+            _ = INTERNED[len].set(name);
+            INTERNED_LEN.store(len + 1, Ordering::Release);
+        }
+        Self(Repr::Custom(Custom::from_static(name)))
+    }
+}
+
 impl fmt::Display for HeaderName {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.0 {
@@ -49,15 +186,42 @@ enum Repr {
     Custom(Custom),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// A name which isn't builtin, kept as it was received, but compared and hashed ignoring case
+/// SPEC: RFC 9110 - 5.1. Field Names
+/// Field names are case-insensitive
+#[derive(Debug, Clone)]
 struct Custom {
     value: Bytes,
 }
 
+impl PartialEq for Custom {
+    fn eq(&self, other: &Self) -> bool {
+        // Interned names share their bytes
+        let same =
+            self.value.as_ptr() == other.value.as_ptr() && self.value.len() == other.value.len();
+        same || self.value.eq_ignore_ascii_case(&other.value)
+    }
+}
+
+impl Eq for Custom {}
+
+impl Hash for Custom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.value.len());
+        for b in self.value.iter() {
+            state.write_u8(b.to_ascii_lowercase());
+        }
+    }
+}
+
 impl Custom {
     pub fn new(value: Bytes) -> Self {
         Self { value }
     }
+
+    fn from_static(value: &'static str) -> Self {
+        Self::new(Bytes::from_static(value.as_bytes()))
+    }
 }
 
 impl fmt::Display for Custom {
@@ -67,54 +231,234 @@ impl fmt::Display for Custom {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Builtin {
-    Host,
-    Connection,
-    ContentLength,
-    TransferEncoding,
-    SetCookie,
-    ContentLocation,
-    ContentType,
-    Date,
-    Trailer,
+macro_rules! builtin_headers {
+    ($(($name: ident, $str: literal);)+) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Builtin {
+            $($name,)+
+        }
+
+        impl fmt::Display for Builtin {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl Builtin {
+            pub const fn as_str(&self) -> &'static str {
+                match self {
+                    $(Self::$name => $str,)+
+                }
+            }
+
+            #[cfg(test)]
+            const ALL: &[Builtin] = &[$(Builtin::$name,)+];
+
+            /// The builtin name matching `bytes` ignoring case
+            pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+                const COUNT: usize = [$($str,)+].len();
+                static NAMES: NameTable<Builtin, COUNT> =
+                    NameTable::new([$(($str, Builtin::$name),)+]);
+                NAMES.get(bytes).copied()
+            }
+        }
+    };
 }
 
-impl fmt::Display for Builtin {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Self::Host => "Host",
-            Self::Connection => "Connection",
-            Self::ContentLength => "Content-Length",
-            Self::TransferEncoding => "Transfer-Encoding",
-            Self::SetCookie => "Set-Cookie",
-            Self::ContentLocation => "Content-Location",
-            Self::ContentType => "Content-Type",
-            Self::Date => "Date",
-            Self::Trailer => "Trailer",
-        })
+/// Names sorted by their length and lowercase first byte, so a lookup only compares the few
+/// names which could match, rather than scanning every name
+struct NameTable<T: 'static, const N: usize>([(&'static str, T); N]);
+
+impl<T: Copy, const N: usize> NameTable<T, N> {
+    const fn new(mut names: [(&'static str, T); N]) -> Self {
+        // Insertion sort, as the table is built at compile time
+        let mut i = 1;
+        while i < N {
+            let mut j = i;
+            while j > 0 && Self::less(names[j].0.as_bytes(), names[j - 1].0.as_bytes()) {
+                names.swap(j, j - 1);
+                j -= 1;
+            }
+            i += 1;
+        }
+        Self(names)
+    }
+
+    const fn key(name: &[u8]) -> (usize, u8) {
+        match name.first() {
+            Some(first) => (name.len(), first.to_ascii_lowercase()),
+            None => (0, 0),
+        }
+    }
+
+    const fn less(a: &[u8], b: &[u8]) -> bool {
+        let (a, b) = (Self::key(a), Self::key(b));
+        a.0 < b.0 || (a.0 == b.0 && a.1 < b.1)
+    }
+
+    /// The value of the name matching `name` ignoring case
+    fn get(&self, name: &[u8]) -> Option<&T> {
+        self.entry(name).map(|(_, value)| value)
+    }
+
+    /// The name matching `name` ignoring case, in the case it was added with
+    fn get_name(&self, name: &[u8]) -> Option<&'static str> {
+        self.entry(name).map(|(other, _)| *other)
+    }
+
+    fn entry(&self, name: &[u8]) -> Option<&(&'static str, T)> {
+        let key = Self::key(name);
+        let start = self
+            .0
+            .partition_point(|(other, _)| Self::key(other.as_bytes()) < key);
+        self.0[start..]
+            .iter()
+            .take_while(|(other, _)| Self::key(other.as_bytes()) == key)
+            .find(|(other, _)| other.as_bytes().eq_ignore_ascii_case(name))
     }
 }
 
-impl Builtin {
-    pub fn from_bytes(bytes: &Bytes) -> Option<Self> {
-        const MAP: &[(&'static [u8], Builtin)] = &[
-            (b"Host", Builtin::Host),
-            (b"Connection", Builtin::Connection),
-            (b"Content-Length", Builtin::ContentLength),
-            (b"Transfer-Encoding", Builtin::TransferEncoding),
-            (b"Set-Cookie", Builtin::SetCookie),
-            (b"Content-Location", Builtin::ContentLocation),
-            (b"Content-Type", Builtin::ContentType),
-            (b"Date", Builtin::Date),
-            (b"Trailer", Builtin::Trailer),
-        ];
-        for (name, ty) in MAP {
-            if bytes.eq_ignore_ascii_case(name) {
-                return Some(*ty);
-            }
+// SPEC: RFC 9110 - 18.4. Field Name Registration
+builtin_headers! {
+    (Host, "Host");
+    (Connection, "Connection");
+    (ContentLength, "Content-Length");
+    (TransferEncoding, "Transfer-Encoding");
+    (SetCookie, "Set-Cookie");
+    (ContentLocation, "Content-Location");
+    (ContentType, "Content-Type");
+    (Date, "Date");
+    (Trailer, "Trailer");
+    (Accept, "Accept");
+    (AcceptCharset, "Accept-Charset");
+    (AcceptEncoding, "Accept-Encoding");
+    (AcceptLanguage, "Accept-Language");
+    (AcceptRanges, "Accept-Ranges");
+    (Age, "Age");
+    (Allow, "Allow");
+    (Authorization, "Authorization");
+    (CacheControl, "Cache-Control");
+    (ContentEncoding, "Content-Encoding");
+    (ContentLanguage, "Content-Language");
+    (ContentRange, "Content-Range");
+    (Cookie, "Cookie");
+    (ETag, "ETag");
+    (Expect, "Expect");
+    (Expires, "Expires");
+    (IfMatch, "If-Match");
+    (IfModifiedSince, "If-Modified-Since");
+    (IfNoneMatch, "If-None-Match");
+    (IfRange, "If-Range");
+    (IfUnmodifiedSince, "If-Unmodified-Since");
+    (LastModified, "Last-Modified");
+    (Location, "Location");
+    (MaxForwards, "Max-Forwards");
+    (ProxyAuthenticate, "Proxy-Authenticate");
+    (ProxyAuthorization, "Proxy-Authorization");
+    (Range, "Range");
+    (Referer, "Referer");
+    (RetryAfter, "Retry-After");
+    (Server, "Server");
+    (TE, "TE");
+    (Upgrade, "Upgrade");
+    (UserAgent, "User-Agent");
+    (Vary, "Vary");
+    (Via, "Via");
+    (WWWAuthenticate, "WWW-Authenticate");
+    (Link, "Link");
+    (Origin, "Origin");
+    (AccessControlAllowOrigin, "Access-Control-Allow-Origin");
+    (AccessControlAllowMethods, "Access-Control-Allow-Methods");
+    (AccessControlAllowHeaders, "Access-Control-Allow-Headers");
+    (AccessControlExposeHeaders, "Access-Control-Expose-Headers");
+    (AccessControlMaxAge, "Access-Control-Max-Age");
+    (AccessControlRequestMethod, "Access-Control-Request-Method");
+    (AccessControlRequestHeaders, "Access-Control-Request-Headers");
+    (ContentDigest, "Content-Digest");
+    (ReprDigest, "Repr-Digest");
+    (IdempotencyKey, "Idempotency-Key");
+    (Forwarded, "Forwarded");
+    (XForwardedFor, "X-Forwarded-For");
+    (XForwardedProto, "X-Forwarded-Proto");
+    (XForwardedHost, "X-Forwarded-Host");
+    (KeepAlive, "Keep-Alive");
+    (ContentDisposition, "Content-Disposition");
+    (StrictTransportSecurity, "Strict-Transport-Security");
+}
+
+/// How the values of a field with more than one value are serialized
+/// SPEC: RFC 9110 - 5.3. Field Order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldLines {
+    /// Joined into one comma separated line, which is only correct for list based fields
+    Combine,
+    /// Each value is sent on its own field line
+    Repeat,
+}
+
+/// Chooses [`FieldLines`] per field, fields without an override use the default
+/// Set-Cookie is repeated by default, since its values can contain commas and must not be
+/// combined
+/// SPEC: RFC 6265 - 3. Overview
+#[derive(Debug, Clone)]
+pub struct FieldLinePolicy {
+    default: FieldLines,
+    overrides: Vec<(HeaderName, FieldLines)>,
+}
+
+impl Default for FieldLinePolicy {
+    fn default() -> Self {
+        Self::new(FieldLines::Combine)
+            .with(HeaderName::builtin(Builtin::SetCookie), FieldLines::Repeat)
+    }
+}
+
+impl FieldLinePolicy {
+    /// A policy without any overrides, not even for Set-Cookie
+    pub fn new(default: FieldLines) -> Self {
+        Self {
+            default,
+            overrides: Vec::new(),
         }
-        None
+    }
+
+    /// Overrides how the values of `name` are serialized
+    pub fn with(mut self, name: HeaderName, lines: FieldLines) -> Self {
+        self.overrides
+            .retain(|(existing, _)| !existing.as_bytes().eq_ignore_ascii_case(name.as_bytes()));
+        self.overrides.push((name, lines));
+        self
+    }
+
+    pub fn lines_for(&self, name: &HeaderName) -> FieldLines {
+        self.overrides
+            .iter()
+            .find(|(existing, _)| existing.as_bytes().eq_ignore_ascii_case(name.as_bytes()))
+            .map_or(self.default, |(_, lines)| *lines)
+    }
+}
+
+/// A field value contains a byte which would end the field or the head early
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("header value contains CR, LF or NUL")]
+pub struct InvalidHeaderValue;
+
+/// A header which can't be added to a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum InvalidHeader {
+    #[error("header name is not a token")]
+    Name,
+    #[error(transparent)]
+    Value(#[from] InvalidHeaderValue),
+}
+
+/// Checks a field value can't split the field, a value containing CR or LF would let it inject
+/// headers, or end the head and inject a body
+/// SPEC: RFC 9110 - 5.5. Field Values
+pub fn validate_header_value(bytes: &[u8]) -> Result<(), InvalidHeaderValue> {
+    match memchr::memchr3(b'\r', b'\n', b'\0', bytes) {
+        Some(_) => Err(InvalidHeaderValue),
+        None => Ok(()),
     }
 }
 
@@ -136,7 +480,21 @@ impl HeaderValue {
         }
     }
 
+    /// Adds a field line value
+    /// Panics if the value contains CR, LF or NUL, see [`Self::try_push`]
     pub fn push(&mut self, bytes: Bytes) {
+        self.try_push(bytes).expect("invalid header value");
+    }
+
+    pub fn try_push(&mut self, bytes: Bytes) -> Result<(), InvalidHeaderValue> {
+        validate_header_value(&bytes)?;
+        self.values.push(bytes);
+        Ok(())
+    }
+
+    /// Adds a value which has already been validated with [`validate_header_value`]
+    pub(crate) fn push_unchecked(&mut self, bytes: Bytes) {
+        debug_assert!(validate_header_value(&bytes).is_ok());
         self.values.push(bytes);
     }
 
@@ -165,6 +523,21 @@ impl HeaderValue {
     pub fn iter(&self) -> impl Iterator<Item = &Bytes> {
         self.values.iter()
     }
+
+    /// Returns the elements of a list based field, over all of its field lines, with the
+    /// whitespace around them trimmed, commas inside a quoted-string don't split an element
+    /// SPEC: RFC 9110 - 5.6.1. Lists (#rule ABNF Extension)
+    /// ABNF: #element => [ element ] *( OWS "," OWS [ element ] )
+    pub fn iter_elements(&self) -> impl Iterator<Item = Bytes> {
+        self.values.iter().flat_map(|line| {
+            split_unquoted(line, b',')
+                .into_iter()
+                .map(<[u8]>::trim_ascii)
+                // Empty list elements are allowed, and should be ignored
+                .filter(|element| !element.is_empty())
+                .map(|element| line.slice_ref(element))
+        })
+    }
 }
 
 impl Index<usize> for HeaderValue {
@@ -175,4 +548,360 @@ impl Index<usize> for HeaderValue {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_value() {
+        let mut value = HeaderValue::new();
+        for bytes in [&b"a\r\nSet-Cookie: a=b"[..], b"a\rb", b"a\nb", b"a\0b"] {
+            assert_eq!(
+                value.try_push(Bytes::from_static(bytes)),
+                Err(InvalidHeaderValue)
+            );
+        }
+        value.try_push(Bytes::from_static(b"a\tb c")).unwrap();
+        assert_eq!(value.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid header value")]
+    fn push_invalid_value() {
+        HeaderValue::new().push(Bytes::from_static(b"a\r\n\r\nbody"));
+    }
+
+    #[test]
+    fn custom_names_ignore_case() {
+        use std::hash::BuildHasher;
+
+        let name = |name: &'static [u8]| HeaderName::try_from(&Bytes::from_static(name)).unwrap();
+        let state = std::collections::hash_map::RandomState::new();
+        assert_eq!(name(b"x-api-key"), name(b"X-Api-Key"));
+        assert_eq!(
+            state.hash_one(name(b"x-api-key")),
+            state.hash_one(name(b"X-API-KEY"))
+        );
+        assert_ne!(name(b"x-api-key"), name(b"x-api-keys"));
+        assert_eq!(name(b"x-api-key").to_string(), "x-api-key");
+    }
+
+    #[test]
+    fn interned_names() {
+        let received = Bytes::from(b"x-request-id".to_vec());
+        let name = HeaderName::try_from(&received).unwrap();
+        // The interned name is used, rather than the received bytes
+        assert_eq!(name.to_string(), "X-Request-Id");
+        let other = HeaderName::try_from(&Bytes::from(b"X-REQUEST-ID".to_vec())).unwrap();
+        assert_eq!(name.as_bytes().as_ptr(), other.as_bytes().as_ptr());
+
+        let interned = HeaderName::intern("X-Tenant-Id");
+        let received = HeaderName::try_from(&Bytes::from(b"x-tenant-id".to_vec())).unwrap();
+        assert_eq!(received, interned);
+        assert_eq!(received.as_bytes().as_ptr(), interned.as_bytes().as_ptr());
+        assert_eq!(
+            HeaderName::intern("host"),
+            HeaderName::builtin(Builtin::Host)
+        );
+
+        // Other names keep the received bytes
+        let received = Bytes::from(b"x-other".to_vec());
+        let name = HeaderName::try_from(&received).unwrap();
+        assert_eq!(name.as_bytes().as_ptr(), received.as_ptr());
+
+        // Parsed names are looked up in place, only unknown names reference the buffer
+        let buf = Bytes::from(b"x-tenant-id: a\r\nx-other: b\r\n".to_vec());
+        let name = HeaderName::from_parsed(&buf, 0..11);
+        assert_eq!(name.as_bytes().as_ptr(), interned.as_bytes().as_ptr());
+        let name = HeaderName::from_parsed(&buf, 16..23);
+        assert_eq!(name.as_bytes(), b"x-other");
+        assert_eq!(name.as_bytes().as_ptr(), buf[16..].as_ptr());
+    }
+
+    #[test]
+    fn builtin_from_bytes() {
+        assert_eq!(
+            Builtin::from_bytes(&Bytes::from_static(b"accept-encoding")),
+            Some(Builtin::AcceptEncoding)
+        );
+        assert_eq!(
+            Builtin::from_bytes(&Bytes::from_static(b"WWW-AUTHENTICATE")),
+            Some(Builtin::WWWAuthenticate)
+        );
+        assert_eq!(Builtin::from_bytes(&Bytes::from_static(b"X-Custom")), None);
+        assert_eq!(Builtin::from_bytes(b""), None);
+        // Names of the same length and first letter are told apart
+        assert_eq!(Builtin::from_bytes(b"via"), Some(Builtin::Via));
+        assert_eq!(Builtin::from_bytes(b"vary"), Some(Builtin::Vary));
+        assert_eq!(Builtin::from_bytes(b"if-match"), Some(Builtin::IfMatch));
+        assert_eq!(Builtin::from_bytes(b"if-range"), Some(Builtin::IfRange));
+        assert_eq!(Builtin::from_bytes(b"if-rangf"), None);
+        for builtin in Builtin::ALL {
+            let lower = builtin.as_str().to_ascii_lowercase();
+            assert_eq!(Builtin::from_bytes(lower.as_bytes()), Some(*builtin));
+        }
+        assert_eq!(Builtin::ETag.to_string(), "ETag");
+    }
+
+    #[test]
+    fn list_header() {
+        let mut headers = HeaderMap::new();
+        let value = headers.entry(HeaderName::builtin(Builtin::Vary));
+        value.push(Bytes::from_static(b"Accept-Encoding, ,Accept"));
+        value.push(Bytes::from_static(b" User-Agent "));
+        assert_eq!(
+            headers.get_header::<Vary>().unwrap().unwrap(),
+            vec![
+                Bytes::from_static(b"Accept-Encoding"),
+                Bytes::from_static(b"Accept"),
+                Bytes::from_static(b"User-Agent"),
+            ]
+        );
+    }
+
+    #[test]
+    fn list_elements_quoted() {
+        let mut value = HeaderValue::new();
+        value.push(Bytes::from_static(b"a, \"b, c\" ,"));
+        value.push(Bytes::from_static(b"d;p=\"\\\",\""));
+        let elements: Vec<_> = value.iter_elements().collect();
+        assert_eq!(elements, [&b"a"[..], b"\"b, c\"", b"d;p=\"\\\",\""]);
+    }
+
+    #[test]
+    fn connection_options() {
+        let mut headers = HeaderMap::new();
+        headers.append(Connection::NAME, Bytes::from_static(b"keep-alive, Upgrade"));
+        headers.append(
+            Connection::NAME,
+            Bytes::from_static(b"X-Option, x-option, UPGRADE"),
+        );
+        let options = ConnectionOptions::of(&headers);
+        assert_eq!(
+            options,
+            ConnectionOptions {
+                close: false,
+                keep_alive: true,
+                upgrade: true,
+                fields: vec![Bytes::from_static(b"X-Option")],
+            }
+        );
+        assert!(options.contains(b"x-OPTION") && !options.contains(b"close"));
+        assert_eq!(ConnectionOptions::of(&HeaderMap::new()), Default::default());
+
+        let mut value = HeaderValue::new();
+        options.to_header_value(&mut value);
+        assert_eq!(value.collect(), "Keep-Alive, Upgrade, X-Option");
+    }
+
+    #[test]
+    fn transfer_codings() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            TransferEncoding::NAME,
+            Bytes::from_static(b"x-gzip, foo;a=1"),
+        );
+        headers.append(TransferEncoding::NAME, Bytes::from_static(b"Chunked"));
+        let codings = headers.get_header::<TransferEncoding>().unwrap().unwrap();
+        assert!(matches!(
+            codings[..],
+            [
+                TransferEncodingKind::Compression(CompressionMethod::Gzip),
+                TransferEncodingKind::Unknown(_),
+                TransferEncodingKind::Chunked,
+            ]
+        ));
+    }
+
+    #[test]
+    fn host_with_port() {
+        let parse = |host: &'static [u8]| {
+            let mut headers = HeaderMap::new();
+            headers.append(Host::NAME, Bytes::from_static(host));
+            headers.get_header::<Host>().map(Option::unwrap)
+        };
+        for (host, port, serialized) in [
+            (&b"example.com"[..], None, "example.com"),
+            (b"example.com:", None, "example.com"),
+            (b"example.com:8080", Some(8080), "example.com:8080"),
+            (b"[::1]", None, "[::1]"),
+            (b"[::1]:", None, "[::1]"),
+            (b"[::1]:443", Some(443), "[::1]:443"),
+            (b"127.0.0.1:80", Some(80), "127.0.0.1:80"),
+        ] {
+            let parsed = parse(host).unwrap();
+            assert_eq!(parsed.port, port);
+            let mut headers = HeaderMap::new();
+            headers.set_header::<Host>(parsed);
+            assert_eq!(headers.get(&Host::NAME).unwrap()[0], serialized);
+        }
+        for invalid in [&b"exa mple.com"[..], b"::1", b"a:b:1", b"a:99999", b"[::1"] {
+            assert!(parse(invalid).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn keep_alive_params() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            KeepAlive::NAME,
+            Bytes::from_static(b"Timeout=5, max=x, ext=\"a,b\", MAX = 100"),
+        );
+        let params = headers.get_header::<KeepAlive>().unwrap().unwrap();
+        assert_eq!(
+            params,
+            KeepAliveParams {
+                timeout: Some(5),
+                max: Some(100),
+            }
+        );
+        headers.set_header::<KeepAlive>(params);
+        assert_eq!(
+            headers.get(&KeepAlive::NAME).unwrap().collect(),
+            "timeout=5, max=100"
+        );
+    }
+
+    #[test]
+    fn upgrade_protocols() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            Upgrade::NAME,
+            Bytes::from_static(b"HTTP/2.0, bad/, websocket, IRC/6.9"),
+        );
+        let offered = headers.get_header::<Upgrade>().unwrap().unwrap();
+        let names: Vec<_> = offered.iter().map(ToString::to_string).collect();
+        assert_eq!(names, ["HTTP/2.0", "websocket", "IRC/6.9"]);
+
+        let supported = [
+            Protocol::new("IRC").with_version("7.0"),
+            Protocol::new("WebSocket"),
+        ];
+        let mut request = crate::http::request::Request::new(crate::http::method::Method::GET, "/");
+        request.headers = headers.clone();
+        // Without the upgrade connection option nothing is offered
+        assert_eq!(UpgradeOffer::of(&request.headers), None);
+        assert_eq!(Protocol::select(&request, &supported), None);
+        request
+            .headers
+            .append(Connection::NAME, Bytes::from_static(b"Upgrade"));
+        assert_eq!(Protocol::select(&request, &supported), Some(&supported[1]));
+
+        // The recorded offer is used once the fields are gone
+        let offer = UpgradeOffer::of(&request.headers).unwrap();
+        request.headers = HeaderMap::new();
+        assert_eq!(Protocol::select(&request, &supported), None);
+        request.extensions.insert(offer);
+        assert_eq!(Protocol::select(&request, &supported), Some(&supported[1]));
+
+        let res = crate::http::response::ResponseBuilder::switching_protocols(supported[1].clone())
+            .build();
+        assert_eq!(res.headers.get(&Upgrade::NAME).unwrap()[0], "WebSocket");
+        assert!(ConnectionOptions::of(&res.headers).upgrade);
+    }
+
+    #[test]
+    fn strict_transport_security() {
+        let parse = |value: &'static [u8]| StrictTransportSecurityValue::parse(value);
+        assert_eq!(
+            parse(b"max-age=\"31536000\"; IncludeSubDomains; unknown=1"),
+            Some(StrictTransportSecurityValue::new(31536000).with_include_subdomains(true))
+        );
+        assert_eq!(parse(b"includeSubDomains"), None);
+        assert_eq!(parse(b"max-age=1; max-age=2"), None);
+        assert_eq!(parse(b"max-age=-1"), None);
+
+        let mut headers = HeaderMap::new();
+        headers.set_header::<StrictTransportSecurity>(
+            StrictTransportSecurityValue::new(600)
+                .with_include_subdomains(true)
+                .with_preload(true),
+        );
+        assert_eq!(
+            headers.get(&StrictTransportSecurity::NAME).unwrap()[0],
+            "max-age=600; includeSubDomains; preload"
+        );
+    }
+
+    #[test]
+    fn contains_token() {
+        let mut headers = HeaderMap::new();
+        let connection = HeaderName::builtin(Builtin::Connection);
+        headers
+            .entry(connection.clone())
+            .push(Bytes::from_static(b"Upgrade, Keep-Alive"));
+        assert!(headers.contains_token(&connection, b"keep-alive"));
+        assert!(!headers.contains_token(&connection, b"close"));
+        assert!(!headers.contains_token(&HeaderName::builtin(Builtin::Vary), b"close"));
+    }
+
+    #[test]
+    fn remove_hop_by_hop() {
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            (&b"Host"[..], &b"example.com"[..]),
+            (b"Connection", b"keep-alive, X-Hop"),
+            (b"Keep-Alive", b"timeout=5"),
+            (b"X-Hop", b"1"),
+            (b"X-End-To-End", b"1"),
+            (b"Proxy-Connection", b"close"),
+            (b"Proxy-Authorization", b"Basic abc"),
+            (b"TE", b"trailers"),
+            (b"Upgrade", b"websocket"),
+        ] {
+            headers
+                .entry(HeaderName::try_from(&Bytes::from_static(name)).unwrap())
+                .push(Bytes::from_static(value));
+        }
+        headers.remove_hop_by_hop();
+        let mut remaining: Vec<_> = headers.iter().map(|(name, _)| name.to_string()).collect();
+        remaining.sort();
+        assert_eq!(remaining, ["Host", "X-End-To-End"]);
+    }
+
+    #[test]
+    fn entity_tag() {
+        let tag = EntityTag::parse(&Bytes::from_static(b"W/\"xyzzy\"")).unwrap();
+        assert!(tag.weak);
+        assert_eq!(tag.tag, Bytes::from_static(b"xyzzy"));
+        assert_eq!(tag.to_string(), "W/\"xyzzy\"");
+
+        let tag = EntityTag::parse(&Bytes::from_static(b"\"\"")).unwrap();
+        assert!(!tag.weak);
+        assert!(tag.tag.is_empty());
+
+        assert!(EntityTag::parse(&Bytes::from_static(b"xyzzy")).is_none());
+        assert!(EntityTag::parse(&Bytes::from_static(b"\"a b\"")).is_none());
+    }
+
+    #[test]
+    fn entity_tag_match() {
+        // SPEC: RFC 9110 - 8.8.3.2. Comparison
+        let weak = EntityTag::weak("1");
+        let strong = EntityTag::strong("1");
+        assert!(!weak.strong_eq(&weak));
+        assert!(strong.strong_eq(&strong));
+        assert!(weak.weak_eq(&strong));
+        assert!(!strong.weak_eq(&EntityTag::strong("2")));
+
+        let mut headers = HeaderMap::new();
+        headers
+            .entry(IfNoneMatch::NAME)
+            .push(Bytes::from_static(b"W/\"1\", bad, \"2\""));
+        let tags = headers.get_header::<IfNoneMatch>().unwrap().unwrap();
+        assert_eq!(
+            tags,
+            EntityTagMatch::Tags(vec![weak.clone(), EntityTag::strong("2")])
+        );
+        assert!(tags.matches_weak(&strong));
+        assert!(!tags.matches_strong(&strong));
+
+        let mut headers = HeaderMap::new();
+        headers.entry(IfMatch::NAME).push(Bytes::from_static(b"*"));
+        let tags = headers.get_header::<IfMatch>().unwrap().unwrap();
+        assert_eq!(tags, EntityTagMatch::Any);
+        assert!(tags.matches_strong(&weak));
+
+        assert_eq!(EntityTag::from_content(b"a"), EntityTag::from_content(b"a"));
+        assert_ne!(EntityTag::from_content(b"a"), EntityTag::from_content(b"b"));
+    }
+}
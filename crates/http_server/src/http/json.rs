@@ -0,0 +1,16 @@
+use std::fmt::Write;
+
+/// Writes `bytes` as a JSON string
+/// SPEC: RFC 8259 - 7. Strings
+pub(crate) fn write_json_string(out: &mut String, bytes: &[u8]) {
+    out.push('"');
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c < ' ' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -1,17 +1,17 @@
 use std::fmt::{Debug, Display};
 
 use bytes::Bytes;
-use uhsapi::ascii::{AsciiStr, AsciiString, InvalidAsciiError};
+use uhsapi::ascii::{AsciiStr, InvalidAsciiError};
 
 /// An HTTP Method
 /// SPEC: Defined in RFC9112 3.1
-/// ABNF: 
+/// ABNF:
 #[derive(Clone, PartialEq, Eq)]
 pub struct Method(Repr);
 
 impl Display for Method {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Debug::fmt(&self.0, f)
+        std::fmt::Display::fmt(&self.0, f)
     }
 }
 
@@ -56,6 +56,20 @@ impl Method {
     pub const fn custom(bytes: Bytes) -> Self {
         Self(Repr::Custom(bytes))
     }
+
+    /// Whether the method is not one of the methods defined by RFC 9110
+    pub fn is_custom(&self) -> bool {
+        matches!(self.0, Repr::Custom(_))
+    }
+
+    /// Whether the method is idempotent, custom methods are never considered idempotent
+    /// See [`Builtin::is_idempotent`]
+    pub fn is_idempotent(&self) -> bool {
+        match &self.0 {
+            Repr::Builtin(builtin) => builtin.is_idempotent(),
+            Repr::Custom(_) => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -78,6 +92,7 @@ impl Display for Repr {
 // It should be possible for rust to optimize it to 24 bits, we check that it is the case
 static_assertions::assert_eq_size!(Repr, Bytes);
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum Builtin {
     GET,
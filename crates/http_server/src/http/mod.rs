@@ -1,19 +1,28 @@
-pub mod request;
-pub mod response;
 pub mod header;
 pub mod method;
+pub mod request;
+pub mod response;
 pub mod uri;
 
 pub mod parser;
 
+pub mod cookie;
+pub mod grpc_web;
+
+pub(crate) mod base64;
+mod body;
+mod date;
+mod extensions;
+pub(crate) mod json;
+pub(crate) mod mime;
+#[cfg(feature = "serde")]
+mod ndjson;
+mod spill;
 mod version;
+pub use body::{Body, BodyError, BodySender, BodyStream};
+pub use date::{HttpDate, ParseHttpDateError};
+pub use extensions::Extensions;
+#[cfg(feature = "serde")]
+pub use ndjson::NdJsonStream;
+pub use spill::{CollectedBody, CollectedReader, SpillConfig, SpillError, SpilledFile};
 pub use version::{HttpVersion, ParseHttpVersionError};
-
-/// Message Body
-/// SPEC: RFC 9112 - 6. Message Body
-/// OBNF: message-body = *OCTET
-#[derive(Debug, Clone)]
-pub enum Body {
-    None,
-    Full(bytes::Bytes),
-}
@@ -0,0 +1,85 @@
+use std::{pin::pin, sync::Arc};
+
+use bytes::Bytes;
+use futures_core::Stream;
+use serde::Serialize;
+
+use crate::http::{Body, BodyError, BodyStream};
+
+/// A response body of newline delimited JSON, each item of the stream is serialized as one line
+/// Items are only pulled from the stream once the previous line has been sent, and each line
+/// is written to the connection as soon as it is produced, so clients see results
+/// incrementally
+pub struct NdJsonStream<S> {
+    items: S,
+}
+
+impl<S> NdJsonStream<S>
+where
+    S: Stream + Send + 'static,
+    S::Item: Serialize,
+{
+    pub const CONTENT_TYPE: &'static str = "application/x-ndjson";
+
+    pub fn new(items: S) -> Self {
+        Self { items }
+    }
+
+    /// Starts serializing the stream, a failure to serialize an item ends the body with
+    /// [`BodyError::Encode`]
+    pub fn into_body(self) -> Body {
+        let (tx, stream) = BodyStream::channel(1);
+        tokio::spawn(async move {
+            let mut items = pin!(self.items);
+            loop {
+                // The item is not held across an await, so it does not need to be Send
+                let line = match std::future::poll_fn(|cx| items.as_mut().poll_next(cx)).await {
+                    Some(item) => serde_json::to_vec(&item),
+                    None => return,
+                };
+                let mut line = match line {
+                    Ok(line) => line,
+                    Err(err) => return tx.abort(BodyError::Encode(Arc::new(err))).await,
+                };
+                line.push(b'\n');
+                if tx.send(Bytes::from(line)).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Body::Stream(stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    struct Iter(VecDeque<Value>);
+
+    impl Stream for Iter {
+        type Item = Value;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Value>> {
+            Poll::Ready(self.0.pop_front())
+        }
+    }
+
+    #[tokio::test]
+    async fn lines() {
+        let items = Iter(VecDeque::from([json!({"a": 1}), json!("b\nc"), json!([])]));
+        let Body::Stream(stream) = NdJsonStream::new(items).into_body() else {
+            panic!("expected a stream");
+        };
+        assert_eq!(stream.next_chunk().await.unwrap().unwrap(), "{\"a\":1}\n");
+        assert_eq!(stream.collect(None).await.unwrap(), "\"b\\nc\"\n[]\n");
+    }
+}
@@ -41,6 +41,7 @@ pub enum ParseErrorKind {
     InvalidMethod,
     InvalidTarget, // origin-form etc.
     InvalidVersion,
+    InvalidStatus,
     MalformedHeaderLine, // no colon / bad OWS
     InvalidHeaderName,   // non-tchar
     InvalidHeaderValue,  // illegal bytes (bare CR/LF)
@@ -82,6 +83,7 @@ impl Display for ParseErrorKind {
             Self::InvalidMethod => f.write_str("invalid method"),
             Self::InvalidTarget => f.write_str("invalid target"),
             Self::InvalidVersion => f.write_str("invalid version"),
+            Self::InvalidStatus => f.write_str("invalid status"),
             Self::MalformedHeaderLine => f.write_str("malformed header"),
             Self::InvalidHeaderName => f.write_str("invalid_header_name"),
             Self::InvalidHeaderValue => f.write_str("invalid header value"),
@@ -89,7 +91,7 @@ impl Display for ParseErrorKind {
                 write!(f, "expected byte {}, got {}", expected, found)
             }
             Self::MissingRequiredHeader => f.write_str("missing required header"),
-                Self::DuplicateHeader => f.write_str("duplicate header"),
+            Self::DuplicateHeader => f.write_str("duplicate header"),
             Self::ConflictingContentLength => f.write_str("conflicting content length"),
             Self::InvalidContentLength => f.write_str("invalid content length"),
             Self::InvalidTransferEncoding => f.write_str("invalid transfer encoding"),
@@ -151,6 +153,7 @@ impl HttpParseError {
             ParseErrorKind::InvalidMethod
             | ParseErrorKind::InvalidTarget
             | ParseErrorKind::InvalidVersion
+            | ParseErrorKind::InvalidStatus
             | ParseErrorKind::MalformedHeaderLine
             | ParseErrorKind::InvalidHeaderName
             | ParseErrorKind::InvalidHeaderValue
@@ -162,7 +165,30 @@ impl HttpParseError {
             | ParseErrorKind::InvalidTransferEncoding
             | ParseErrorKind::ChunkSizeInvalid
             | ParseErrorKind::ChunkCrlfMissing
-            | ParseErrorKind::ChunkExtensionsInvalid => StatusCode::BAD_REQUEST,
+            | ParseErrorKind::ChunkExtensionsInvalid
+            | ParseErrorKind::IncompleteMessage => StatusCode::BAD_REQUEST,
+            // SPEC: RFC 9112 - 3. Request Line
+            // A request line longer than the server is willing to parse gets 414
+            ParseErrorKind::TooLarge {
+                what: LimitKind::RequestLineBytes | LimitKind::PathBytes | LimitKind::QueryBytes,
+                ..
+            } => StatusCode::URI_TOO_LONG,
+            // SPEC: RFC 6585 - 5. 431 Request Header Fields Too Large
+            ParseErrorKind::TooLarge {
+                what:
+                    LimitKind::HeaderLineBytes
+                    | LimitKind::HeaderBytesTotal
+                    | LimitKind::HeaderCount
+                    | LimitKind::TrailerBytesTotal,
+                ..
+            } => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            ParseErrorKind::TooLarge {
+                what: LimitKind::BodyBytes | LimitKind::ChunkSizeBytes,
+                ..
+            } => StatusCode::CONTENT_TOO_LARGE,
+            ParseErrorKind::Timeout => StatusCode::REQUEST_TIMEOUT,
+            ParseErrorKind::UnsupportedFeature => StatusCode::NOT_IMPLEMENTED,
+            ParseErrorKind::VersionNotSupported => StatusCode::HTTP_VERSION_NOT_SUPPORTED,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -1,17 +1,38 @@
 use std::ops::Range;
 
 use bytes::Bytes;
-use uhsapi::ascii::AsciiStr;
 
 use crate::http::{
-    Body, HttpVersion,
+    Body, Extensions, HttpVersion,
     header::{Builtin, HeaderMap, HeaderName},
     method::Method,
-    parser::{HttpParseError, HttpParseResult, LineParse, Location, ParseErrorKind},
+    parser::{HttpParseError, HttpParseResult, LineParse, Location, ParseErrorKind, is_tchar},
     request::Request,
-    response::Response,
+    response::{Response, StatusCode},
 };
 
+/// Parses the version of a request line
+/// Well formed versions other than HTTP/1.x, including the HTTP/2 connection preface
+/// (`PRI * HTTP/2.0`), are not supported rather than invalid
+/// SPEC: RFC 9112 - 2.3. HTTP Version
+/// ABNF: HTTP-version = HTTP-name "/" DIGIT "." DIGIT
+fn parse_version(bytes: &[u8]) -> Result<HttpVersion, ParseErrorKind> {
+    let Some(version) = bytes.strip_prefix(b"HTTP/") else {
+        return Err(ParseErrorKind::InvalidVersion);
+    };
+    match version {
+        [b'1', b'.', minor @ b'0'..=b'9'] => Ok(HttpVersion {
+            major: 1,
+            minor: minor - b'0',
+        }),
+        // HTTP/2 and HTTP/3 are sometimes written without the minor version
+        [b'0'..=b'9', b'.', b'0'..=b'9'] | [b'0'..=b'9'] => {
+            Err(ParseErrorKind::VersionNotSupported)
+        }
+        _ => Err(ParseErrorKind::InvalidVersion),
+    }
+}
+
 /// The Request Line for a HTTP Message
 /// SPEC: RFC 9112 - 3. Request Line
 /// ABNF: request-line = method SP request-target SP HTTP-version
@@ -39,13 +60,33 @@ impl LineParse for RequestLine {
         // OBNF: request-line = method SP request-target SP HTTP-version
 
         let method = line.next_word().ok_or_else(|| make_err(&line))?;
+        // ABNF: method = token
+        if !line.buf[method.clone()].iter().copied().all(is_tchar) {
+            return Err(HttpParseError {
+                kind: ParseErrorKind::InvalidMethod,
+                location: Location::StartLine,
+                offset: method.start,
+                line: None,
+            });
+        }
         let target = line.next_word().ok_or_else(|| make_err(&line))?;
-        let version =
-            AsciiStr::from_ascii(&line.buf[line.next_word().ok_or_else(|| make_err(&line))?])
-                .map_err(|_| make_err(&line))?
-                .as_str()
-                .parse::<HttpVersion>()
-                .map_err(|_| make_err(&line))?;
+        // The forms of the target are only told apart when the router asks for it, but it must
+        // at least be printable ASCII
+        if !line.buf[target.clone()].iter().all(u8::is_ascii_graphic) {
+            return Err(HttpParseError {
+                kind: ParseErrorKind::InvalidTarget,
+                location: Location::StartLine,
+                offset: target.start,
+                line: None,
+            });
+        }
+        let version = line.next_word().ok_or_else(|| make_err(&line))?;
+        let version = parse_version(&line.buf[version.clone()]).map_err(|kind| HttpParseError {
+            kind,
+            location: Location::StartLine,
+            offset: version.start,
+            line: None,
+        })?;
 
         if !line.is_empty() {
             return Err(HttpParseError {
@@ -66,10 +107,14 @@ impl LineParse for RequestLine {
     fn to_output(
         bytes: Bytes,
         data: Self,
-        mut headers: HeaderMap,
+        headers: HeaderMap,
         body: Body,
     ) -> HttpParseResult<Self::Output> {
-        if !headers.contains(&HeaderName::builtin(Builtin::Host)) {
+        // SPEC: RFC 9112 - 3.2. Request Target
+        // Host is only required from HTTP/1.1 clients
+        if data.version >= HttpVersion::HTTP_1_1
+            && !headers.contains(&HeaderName::builtin(Builtin::Host))
+        {
             return Err(HttpParseError {
                 kind: ParseErrorKind::MissingRequiredHeader,
                 location: Location::Headers,
@@ -79,18 +124,27 @@ impl LineParse for RequestLine {
         }
 
         Ok(Self::Output {
-            method: Method::try_from(bytes.slice(data.method)).unwrap(),
+            method: Method::try_from(bytes.slice(data.method.clone())).map_err(|_| {
+                HttpParseError {
+                    kind: ParseErrorKind::InvalidMethod,
+                    location: Location::StartLine,
+                    offset: data.method.start,
+                    line: None,
+                }
+            })?,
             target: bytes.slice(data.target),
             version: data.version,
             headers,
             body,
             remote: None,
+            extensions: Extensions::new(),
+            interim: None,
         })
     }
 }
 
-/// The Response Line for a HTTP Message
-/// SPEC: RFC 9112 - 3.2. Request Target
+/// The Status Line for a HTTP Message
+/// SPEC: RFC 9112 - 4. Status Line
 /// ABNF:
 ///     status-line = HTTP-version SP status-code SP [ reason-phrase ]
 ///     status-code    = 3DIGIT
@@ -98,15 +152,54 @@ impl LineParse for RequestLine {
 #[derive(Debug)]
 pub struct ResponseLine {
     pub version: HttpVersion,
-    pub status_code: u32,
-    pub reason_phrase: Option<Range<usize>>,
+    pub status: StatusCode,
+    pub reason_phrase: Range<usize>,
 }
 
 impl LineParse for ResponseLine {
     type Output = Response;
 
-    fn parse(line: super::ReaderLine) -> super::HttpParseResult<Self> {
-        todo!()
+    fn parse(mut line: super::ReaderLine) -> super::HttpParseResult<Self> {
+        let err = |kind, offset| HttpParseError {
+            kind,
+            location: Location::StartLine,
+            offset,
+            line: None,
+        };
+        let version = line
+            .next_word()
+            .ok_or_else(|| err(ParseErrorKind::MalformedHeaderLine, line.line_start))?;
+        let version =
+            parse_version(&line.buf[version.clone()]).map_err(|kind| err(kind, version.start))?;
+
+        // The space before an empty reason phrase is required, but often left out
+        // SPEC: RFC 9112 - 4. Status Line
+        let status = line
+            .next_word()
+            .ok_or_else(|| err(ParseErrorKind::InvalidStatus, line.line_start))?;
+        let status = match line.buf[status.clone()] {
+            [a, b, c] if [a, b, c].iter().all(u8::is_ascii_digit) => StatusCode::from_u16(
+                (a - b'0') as u16 * 100 + (b - b'0') as u16 * 10 + (c - b'0') as u16,
+            )
+            .ok(),
+            _ => None,
+        }
+        .ok_or_else(|| err(ParseErrorKind::InvalidStatus, status.start))?;
+
+        let reason_phrase = line.range();
+        if line
+            .as_slice()
+            .iter()
+            .any(|&b| b != b'\t' && b.is_ascii_control())
+        {
+            return Err(err(ParseErrorKind::InvalidStatus, reason_phrase.start));
+        }
+
+        Ok(Self {
+            version,
+            status,
+            reason_phrase,
+        })
     }
 
     fn to_output(
@@ -115,6 +208,12 @@ impl LineParse for ResponseLine {
         headers: HeaderMap,
         body: Body,
     ) -> HttpParseResult<Self::Output> {
-        todo!()
+        Ok(Self::Output {
+            version: data.version,
+            status: data.status,
+            message: bytes.slice(data.reason_phrase),
+            headers,
+            body,
+        })
     }
 }
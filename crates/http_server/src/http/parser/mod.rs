@@ -1,50 +1,77 @@
 use std::{
-    fmt::{self, Debug},
+    collections::VecDeque,
+    fmt::Debug,
+    num::NonZeroUsize,
     ops::{Index, Range, RangeInclusive},
+    sync::Arc,
+    time::Duration,
 };
 
+use crate::clock::{self, SharedClock};
 use crate::http::{
-    Body,
-    header::{ContentLength, HeaderMap, HeaderName, TransferEncoding},
+    Body, BodyError, BodySender, BodyStream,
+    header::{Builtin, HeaderMap, HeaderName, HeaderValueTrait, validate_header_value},
+    method::Method,
     request::Request,
-    response::Response,
+    response::{Response, StatusCode},
 };
 
 mod error;
 mod line;
+mod pool;
+mod sender;
 use bytes::{Bytes, BytesMut};
 pub use error::*;
-use memchr::{memchr, memchr2};
+use memchr::{memchr, memchr_iter, memchr2};
+pub use pool::BufferPool;
+use pool::PooledBuf;
+pub use sender::{Sender, WriteLimits, frame_response};
 use smallvec::SmallVec;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-
-fn is_tchar(b: u8) -> bool {
-    (b'A'..=b'Z').contains(&b)
-        || (b'a'..=b'z').contains(&b)
-        || (b'0'..=b'9').contains(&b)
-        || matches!(
-            b,
-            b'!' | b'#'
-                | b'$'
-                | b'%'
-                | b'&'
-                | b'\''
-                | b'*'
-                | b'+'
-                | b'-'
-                | b'.'
-                | b'^'
-                | b'_'
-                | b'`'
-                | b'|'
-                | b'~'
-        )
+use tokio::io::AsyncReadExt;
+
+/// ABNF: tchar = "!" / "#" / "$" / "%" / "&" / "'" / "*" / "+" / "-" / "." / "^" / "_" / "`" /
+///   "|" / "~" / DIGIT / ALPHA
+const TCHAR: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut b = 0;
+    while b < 256 {
+        table[b] = (b as u8).is_ascii_alphanumeric()
+            || matches!(
+                b as u8,
+                b'!' | b'#'
+                    | b'$'
+                    | b'%'
+                    | b'&'
+                    | b'\''
+                    | b'*'
+                    | b'+'
+                    | b'-'
+                    | b'.'
+                    | b'^'
+                    | b'_'
+                    | b'`'
+                    | b'|'
+                    | b'~'
+            );
+        b += 1;
+    }
+    table
+};
+
+pub(crate) fn is_tchar(b: u8) -> bool {
+    TCHAR[b as usize]
 }
 
 struct Reader<T: AsyncReadExt + Unpin> {
     inner: T,
-    buf: BytesMut,
+    buf: PooledBuf,
     cursor: usize,
+    /// Positions of the line ends found after the cursor, which are scanned for a block at a
+    /// time rather than a line at a time
+    line_ends: VecDeque<usize>,
+    /// How far the buffer has been scanned for line ends, so the search for the end of a partial
+    /// line continues after it once more bytes arrive
+    scanned: usize,
 }
 
 impl<READER> Reader<READER>
@@ -52,18 +79,84 @@ where
     READER: AsyncReadExt + Unpin,
 {
     const BUF_SIZE: usize = 8192;
+    /// How many bytes are scanned for line ends at once, enough for the whole head of most
+    /// messages, without scanning far into a body that follows it
+    const SCAN_BLOCK: usize = 2048;
 
     pub fn new(reader: READER) -> Self {
         Self {
             inner: reader,
-            buf: BytesMut::with_capacity(Self::BUF_SIZE),
+            // Allocated by the first read, unless a pooled buffer is given first
+            buf: PooledBuf::new(),
             cursor: 0,
+            line_ends: VecDeque::new(),
+            scanned: 0,
         }
     }
 
     async fn read(&mut self) -> std::io::Result<usize> {
         self.buf.reserve(Self::BUF_SIZE);
-        self.inner.read_buf(&mut self.buf).await
+        self.inner.read_buf(&mut *self.buf).await
+    }
+
+    /// Reads at most `limit` bytes, so the buffer only grows as much as the caller allows
+    async fn read_at_most(&mut self, limit: usize) -> std::io::Result<usize> {
+        let limit = limit.clamp(1, Self::BUF_SIZE);
+        self.buf.reserve(limit);
+        (&mut self.inner)
+            .take(limit as u64)
+            .read_buf(&mut *self.buf)
+            .await
+    }
+
+    /// Removes everything before the cursor from the buffer, and returns it
+    fn consume(&mut self) -> BytesMut {
+        let consumed = self.buf.split_to(self.cursor);
+        self.shift(self.cursor);
+        self.cursor = 0;
+        consumed
+    }
+
+    /// Removes `len` bytes from the start of the buffer, which must not have a cursor
+    fn take(&mut self, len: usize) -> Bytes {
+        debug_assert_eq!(self.cursor, 0);
+        self.shift(len);
+        self.buf.split_to(len).freeze()
+    }
+
+    fn clear(&mut self) {
+        self.buf.clear();
+        self.cursor = 0;
+        self.line_ends.clear();
+        self.scanned = 0;
+    }
+
+    /// Moves the line ends which were found back by `len` bytes removed from the buffer
+    fn shift(&mut self, len: usize) {
+        while self.line_ends.front().is_some_and(|&nl| nl < len) {
+            self.line_ends.pop_front();
+        }
+        for nl in &mut self.line_ends {
+            *nl -= len;
+        }
+        self.scanned = self.scanned.saturating_sub(len);
+    }
+
+    /// Returns the next line end after the cursor, scanning the buffer for more of them when
+    /// none are left
+    fn next_line_end(&mut self) -> Option<usize> {
+        while self.line_ends.front().is_some_and(|&nl| nl < self.cursor) {
+            self.line_ends.pop_front();
+        }
+        self.scanned = self.scanned.max(self.cursor);
+        while self.line_ends.is_empty() && self.scanned < self.buf.len() {
+            let start = self.scanned;
+            let end = self.buf.len().min(start + Self::SCAN_BLOCK);
+            let found = memchr_iter(b'\n', &self.buf[start..end]).map(|nl| start + nl);
+            self.line_ends.extend(found);
+            self.scanned = end;
+        }
+        self.line_ends.pop_front()
     }
 
     fn get_line(&mut self) -> Option<ReaderLine<'_>> {
@@ -72,10 +165,9 @@ where
         }
 
         let line_start = self.cursor;
-        let nl_rel = memchr(b'\n', &self.buf[line_start..])?;
-        let nl = nl_rel + line_start;
+        let nl = self.next_line_end()?;
         self.cursor = nl + 1;
-        let line_end = if nl_rel > 0 && self.buf[nl - 1] == b'\r' {
+        let line_end = if nl > line_start && self.buf[nl - 1] == b'\r' {
             nl - 1..=nl
         } else {
             nl..=nl
@@ -167,6 +259,30 @@ impl ReaderLine<'_> {
 /// An HTTP Parser which can parse any HTTP message ()
 pub struct Parser<READER: AsyncReadExt + Unpin> {
     reader: Reader<READER>,
+    head_limits: HeadLimits,
+    /// Set after parsing a head with a body, until the body has been fully read
+    body_pending: bool,
+    /// Set while a head is being parsed, a parse which was dropped before completing resumes
+    /// from it
+    head: Option<HeadProgress>,
+    /// The field list of the last head, kept so the next head doesn't allocate it again
+    spare_fields: SmallVec<[HeaderIx; 32]>,
+    /// A header map given back by [`Self::recycle_headers`]
+    spare_headers: Option<HeaderMap>,
+}
+
+/// How far the parse of a head has come, the ranges index the reader buffer
+#[derive(Debug, Default)]
+struct HeadProgress {
+    state: ParseState,
+    line_cnt: usize,
+    /// The start of the start line, and its line end
+    start_line: Option<(usize, RangeInclusive<usize>)>,
+    /// Where the field lines start in the buffer
+    headers_start: usize,
+    headers: SmallVec<[HeaderIx; 32]>,
+    /// Set once the first byte of the head has arrived
+    deadline: Option<tokio::time::Instant>,
 }
 
 pub type HttpParseResult<T> = Result<T, HttpParseError>;
@@ -187,21 +303,220 @@ trait LineParse: Sized {
 struct HeaderIx {
     name: Range<usize>,
     value: Range<usize>,
+    /// Continuation lines of an obsolete line folded value
+    folds: Vec<Range<usize>>,
 }
 
+/// How the length of a message body is determined
+/// SPEC: RFC 9112 - 6.3. Message Body Length
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    /// There is no body
+    None,
+    /// The body is exactly this many bytes
+    Length(u64),
+    /// The body uses the chunked transfer coding
+    Chunked,
+    /// The body ends when the connection is closed, which only responses can do
+    UntilClose,
+}
+
+impl BodyFraming {
+    /// Determines the framing of a request body from its headers
+    pub fn for_request(headers: &HeaderMap) -> HttpParseResult<Self> {
+        fn make_err(kind: ParseErrorKind) -> HttpParseError {
+            HttpParseError {
+                kind,
+                location: Location::Headers,
+                offset: 0,
+                line: None,
+            }
+        }
+
+        let content_length = headers.get(&HeaderName::builtin(Builtin::ContentLength));
+        if let Some(value) = headers.get(&HeaderName::builtin(Builtin::TransferEncoding)) {
+            // A message with both framing headers is likely an attempt at request smuggling, as
+            // intermediaries may disagree on which one frames the body
+            // SPEC: RFC 9112 - 6.3. Message Body Length (3)
+            if content_length.is_some() {
+                return Err(make_err(ParseErrorKind::ConflictingContentLength));
+            }
+            // A request with a transfer encoding which does not end in chunked can't be framed,
+            // and chunked must not be applied more than once
+            // SPEC: RFC 9112 - 6.3. Message Body Length (4)
+            // SPEC: RFC 9112 - 6.1. Transfer-Encoding
+            let codings = Vec::<Bytes>::from_header_value(value)
+                .map_err(|_| make_err(ParseErrorKind::InvalidTransferEncoding))?;
+            let Some((last, rest)) = codings.split_last() else {
+                return Err(make_err(ParseErrorKind::InvalidTransferEncoding));
+            };
+            if !last.eq_ignore_ascii_case(b"chunked")
+                || rest
+                    .iter()
+                    .any(|coding| coding.eq_ignore_ascii_case(b"chunked"))
+            {
+                return Err(make_err(ParseErrorKind::InvalidTransferEncoding));
+            }
+            return Ok(Self::Chunked);
+        }
+        let Some(value) = content_length else {
+            return Ok(Self::None);
+        };
+        // Repeated Content-Length values (in one field or across fields) are only accepted if
+        // they are all the same
+        // SPEC: RFC 9112 - 6.3. Message Body Length (5)
+        // ABNF: Content-Length = 1*DIGIT
+        let mut length = None;
+        for element in value.iter().flat_map(|line| line.split(|b| *b == b',')) {
+            let element = element.trim_ascii();
+            if element.is_empty() || !element.iter().all(u8::is_ascii_digit) {
+                return Err(make_err(ParseErrorKind::InvalidContentLength));
+            }
+            let len = std::str::from_utf8(element)
+                .ok()
+                .and_then(|s| s.parse::<u64>().ok())
+                .ok_or_else(|| make_err(ParseErrorKind::InvalidContentLength))?;
+            match length {
+                Some(prev) if prev != len => {
+                    return Err(make_err(ParseErrorKind::ConflictingContentLength));
+                }
+                _ => length = Some(len),
+            }
+        }
+        match length {
+            Some(0) => Ok(Self::None),
+            Some(len) => Ok(Self::Length(len)),
+            None => Err(make_err(ParseErrorKind::InvalidContentLength)),
+        }
+    }
+
+    /// Determines the framing of a response body from its headers, its status, and the method
+    /// of the request it answers
+    /// SPEC: RFC 9112 - 6.3. Message Body Length
+    pub fn for_response(
+        headers: &HeaderMap,
+        status: StatusCode,
+        method: &Method,
+    ) -> HttpParseResult<Self> {
+        // Responses to HEAD, 1xx, 204 and 304 responses, and a successful CONNECT, which turns
+        // the connection into a tunnel, never have a body, whatever their fields say
+        // SPEC: RFC 9112 - 6.3. Message Body Length (1) (2)
+        if *method == Method::HEAD
+            || status.is_informational()
+            || status == StatusCode::NO_CONTENT
+            || status == StatusCode::NOT_MODIFIED
+            || (*method == Method::CONNECT && status.is_success())
+        {
+            return Ok(Self::None);
+        }
+        // A response whose transfer coding doesn't end in chunked is delimited by the server
+        // closing the connection, and Transfer-Encoding overrides Content-Length
+        // SPEC: RFC 9112 - 6.3. Message Body Length (3) (4)
+        if let Some(value) = headers.get(&HeaderName::builtin(Builtin::TransferEncoding)) {
+            let codings = Vec::<Bytes>::from_header_value(value).unwrap_or_default();
+            let chunked = codings
+                .iter()
+                .filter(|coding| coding.eq_ignore_ascii_case(b"chunked"))
+                .count();
+            return match codings.last() {
+                Some(last) if chunked == 1 && last.eq_ignore_ascii_case(b"chunked") => {
+                    Ok(Self::Chunked)
+                }
+                _ if chunked > 1 => Err(HttpParseError {
+                    kind: ParseErrorKind::InvalidTransferEncoding,
+                    location: Location::Headers,
+                    offset: 0,
+                    line: None,
+                }),
+                _ => Ok(Self::UntilClose),
+            };
+        }
+        // SPEC: RFC 9112 - 6.3. Message Body Length (8)
+        if !headers.contains(&HeaderName::builtin(Builtin::ContentLength)) {
+            return Ok(Self::UntilClose);
+        }
+        Self::for_request(headers)
+    }
+}
+
+/// Limits applied while reading a message head, the head is rejected as soon as a limit is
+/// exceeded, rather than once it has been read
+#[derive(Debug, Clone)]
+pub struct HeadLimits {
+    pub max_start_line_bytes: NonZeroUsize,
+    pub max_header_bytes_total: NonZeroUsize,
+    pub max_header_line_bytes: NonZeroUsize,
+    pub max_header_count: NonZeroUsize,
+    /// Merge obsolete line folding into the previous field value, rather than rejecting it
+    pub allow_obs_fold: bool,
+    /// The maximum time to receive the rest of a head once its first byte has arrived
+    /// Waiting for the first byte is not limited, idle connections are closed by the reaper
+    pub read_timeout: Duration,
+    pub clock: SharedClock,
+}
+
+impl Default for HeadLimits {
+    fn default() -> Self {
+        crate::HttpServerConfig::default().head_limits()
+    }
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        crate::HttpServerConfig::default().body_limits()
+    }
+}
+
+/// Converts an error while reading a body, for when the body is parsed along with its head
+fn body_error(err: BodyError) -> HttpParseError {
+    let kind = match err {
+        BodyError::Decode(err) => match err.downcast_ref::<HttpParseError>() {
+            Some(err) => return err.clone(),
+            None => ParseErrorKind::InvalidTransferEncoding,
+        },
+        BodyError::Encode(_) => ParseErrorKind::InvalidTransferEncoding,
+        // The exact size is not known, only that it exceeded the limit
+        BodyError::LimitExceeded { limit } => ParseErrorKind::TooLarge {
+            what: LimitKind::BodyBytes,
+            limit,
+            actual: limit.saturating_add(1),
+        },
+        BodyError::TimedOut => ParseErrorKind::Timeout,
+        BodyError::ClientDisconnected => ParseErrorKind::IncompleteMessage,
+    };
+    HttpParseError {
+        kind,
+        location: Location::Body,
+        offset: 0,
+        line: None,
+    }
+}
+
+/// Limits applied while reading a message body
+#[derive(Debug, Clone)]
+pub struct BodyLimits {
+    pub max_body_bytes: Option<NonZeroUsize>,
+    pub max_chunk_size_bytes: NonZeroUsize,
+    pub max_trailer_bytes_total: NonZeroUsize,
+    /// The maximum time to wait for more body bytes to arrive
+    pub read_timeout: Duration,
+    pub clock: SharedClock,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 enum ParseState {
+    #[default]
     Line,
     Headers,
     Body,
 }
 
-impl Into<Location> for ParseState {
-    fn into(self) -> Location {
-        match self {
-            Self::Line => Location::StartLine,
-            Self::Headers => Location::Headers,
-            Self::Body => Location::Body,
+impl From<ParseState> for Location {
+    fn from(state: ParseState) -> Location {
+        match state {
+            ParseState::Line => Location::StartLine,
+            ParseState::Headers => Location::Headers,
+            ParseState::Body => Location::Body,
         }
     }
 }
@@ -211,31 +526,153 @@ where
     READER: AsyncReadExt + Unpin,
 {
     pub fn new(reader: READER) -> Self {
+        Self::with_limits(reader, HeadLimits::default())
+    }
+
+    pub fn with_limits(reader: READER, head_limits: HeadLimits) -> Self {
         Self {
             reader: Reader::new(reader),
+            head_limits,
+            body_pending: false,
+            head: None,
+            spare_fields: SmallVec::new(),
+            spare_headers: None,
         }
     }
 
-    async fn parse_message<M: LineParse>(&mut self) -> HttpParseResult<M::Output> {
+    /// Gives back the header map of a message which is no longer needed, the next head is
+    /// parsed into it rather than a new map, which saves allocating one per message on a
+    /// keep-alive connection
+    pub fn recycle_headers(&mut self, mut headers: HeaderMap) {
+        headers.clear();
+        self.spare_headers = Some(headers);
+    }
+
+    /// Takes the read buffer from `pool`, and gives it back once the parser is dropped
+    /// A buffer which grew past the maximum capacity of the pool is replaced between messages
+    pub fn with_buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.reader.buf.set_pool(pool);
+        self
+    }
+
+    /// Prepares the parser for the next message on the connection
+    /// Bytes which were consumed by the previous message are dropped, but bytes which were read
+    /// past the end of it are kept, as they belong to the next (pipelined) message
+    /// This is called before parsing every message, and must only be called once the body of the
+    /// previous message has been read, otherwise the body would be parsed as the next message
+    pub fn reset(&mut self) {
+        debug_assert!(
+            !self.body_pending,
+            "parser reset before the message body was read"
+        );
+        debug_assert!(self.reader.cursor <= self.reader.buf.len());
+        drop(self.reader.consume());
+        debug_assert_eq!(self.reader.cursor, 0);
+        self.reader.buf.shrink();
+    }
+
+    /// Whether no bytes of the next message have been received, a connection closed in this
+    /// state was closed cleanly between messages
+    pub fn is_idle(&self) -> bool {
+        self.reader.buf.is_empty()
+    }
+
+    /// Reads and drops what the peer sends until it closes the connection, or more than
+    /// `max_bytes` have been read
+    pub async fn discard(&mut self, max_bytes: usize) -> std::io::Result<()> {
+        let mut discarded = self.reader.buf.len();
+        self.reader.clear();
+        while discarded <= max_bytes {
+            match self.reader.read().await? {
+                0 => break,
+                n => discarded += n,
+            }
+            self.reader.clear();
+        }
+        Ok(())
+    }
+
+    /// Whether the whole head of the next message has already been read, so parsing it won't
+    /// wait on the connection
+    pub fn has_buffered_head(&self) -> bool {
+        // SPEC: RFC 9112 - 2.2. Message Parsing Robustness
+        // Empty lines before the start line are ignored
+        let buffered = self.buffered();
+        let start = buffered
+            .iter()
+            .position(|b| !matches!(b, b'\r' | b'\n'))
+            .unwrap_or(buffered.len());
+        let head = &buffered[start..];
+        memchr_iter(b'\n', head)
+            .any(|nl| matches!(&head[nl + 1..], [b'\n', ..] | [b'\r', b'\n', ..]))
+    }
+
+    /// The bytes which have been read from the connection, but not yet parsed
+    pub fn buffered(&self) -> &[u8] {
+        &self.reader.buf[self.reader.cursor..]
+    }
+
+    async fn parse_head<M: LineParse>(&mut self) -> HttpParseResult<(Bytes, M, HeaderMap)> {
         // Parses an entire HTTP Request Message
         // SPEC: RFC 9112 - 2.1 Message Format
         // ABNF:
         //  HTTP-message = start-line CRLF *( field-line CRLF ) CRLF [ message-body ]
         //  start-line = request-line | status-line
 
-        let mut s_line: Option<M> = None;
-        let mut headers = SmallVec::<[HeaderIx; 32]>::new();
-        let mut state = ParseState::Line;
-        let mut line_cnt = 0;
+        let res = self.resume_head::<M>().await;
+        // A parse which is dropped before getting here resumes where it stopped
+        self.head = None;
+        res
+    }
+
+    async fn resume_head<M: LineParse>(&mut self) -> HttpParseResult<(Bytes, M, HeaderMap)> {
+        if self.head.is_none() {
+            self.reset();
+        }
+        let limits = self.head_limits.clone();
+        let spare_fields = &mut self.spare_fields;
+        let progress = self.head.get_or_insert_with(|| HeadProgress {
+            headers: std::mem::take(spare_fields),
+            ..HeadProgress::default()
+        });
+        let too_large = |what: LimitKind, limit: NonZeroUsize, actual: usize, line_cnt| {
+            let location = match what {
+                LimitKind::RequestLineBytes => Location::StartLine,
+                _ => Location::Headers,
+            };
+            HttpParseError {
+                kind: ParseErrorKind::TooLarge {
+                    what,
+                    limit: limit.get(),
+                    actual,
+                },
+                location,
+                offset: 0,
+                line: Some(line_cnt),
+            }
+        };
 
         // Here we lazily parse the start line and headers
         'outer: loop {
             while let Some(mut line) = self.reader.get_line() {
-                line_cnt += 1;
-                match state {
+                progress.line_cnt += 1;
+                match progress.state {
                     ParseState::Line => {
-                        s_line = Some(M::parse(line)?);
-                        state = ParseState::Headers;
+                        let len = line.range().len();
+                        if len > limits.max_start_line_bytes.get() {
+                            return Err(too_large(
+                                LimitKind::RequestLineBytes,
+                                limits.max_start_line_bytes,
+                                len,
+                                progress.line_cnt,
+                            ));
+                        }
+                        let start_line = (line.line_start, line.line_end.clone());
+                        // Parsed again once the head is complete, but errors are found early
+                        M::parse(line)?;
+                        progress.start_line = Some(start_line);
+                        progress.state = ParseState::Headers;
+                        progress.headers_start = self.reader.cursor;
                     }
                     ParseState::Headers => {
                         // Header Field Parsing
@@ -243,31 +680,83 @@ where
                         // OBNF: field-line = field-name ":" OWS field-value OWS
                         if line.is_empty() {
                             // We don't parse the body here
-                            state = ParseState::Body;
+                            progress.state = ParseState::Body;
                             break 'outer;
                         }
+                        let len = line.range().len();
+                        if len > limits.max_header_line_bytes.get() {
+                            return Err(too_large(
+                                LimitKind::HeaderLineBytes,
+                                limits.max_header_line_bytes,
+                                len,
+                                progress.line_cnt,
+                            ));
+                        }
                         if memchr2(b' ', b'\t', line.as_slice()) == Some(0) {
                             // Starts with space, horizontal tab, do Obsolete Line Folding
+                            // A server must either reject the message, or replace each fold with
+                            // a space, a fold before the first field line can't be merged
                             // SPEC: RFC 9112 - 5.2. Obsolete Line Folding
-                            todo!()
+                            // ABNF: obs-fold = OWS CRLF RWS
+                            let header = progress
+                                .headers
+                                .last_mut()
+                                .filter(|_| limits.allow_obs_fold)
+                                .ok_or_else(|| HttpParseError {
+                                    kind: ParseErrorKind::MalformedHeaderLine,
+                                    location: progress.state.into(),
+                                    offset: line.line_start,
+                                    line: Some(progress.line_cnt),
+                                })?;
+                            let continuation = line.trim();
+                            if validate_header_value(&line.buf[continuation.clone()]).is_err() {
+                                return Err(HttpParseError {
+                                    kind: ParseErrorKind::InvalidHeaderValue,
+                                    location: progress.state.into(),
+                                    offset: continuation.start,
+                                    line: Some(progress.line_cnt),
+                                });
+                            }
+                            header.folds.push(continuation);
+                            continue;
+                        }
+                        if progress.headers.len() >= limits.max_header_count.get() {
+                            return Err(too_large(
+                                LimitKind::HeaderCount,
+                                limits.max_header_count,
+                                progress.headers.len() + 1,
+                                progress.line_cnt,
+                            ));
                         }
 
                         let name = line.next(b':').ok_or_else(|| HttpParseError {
                             kind: ParseErrorKind::MalformedHeaderLine,
-                            location: state.into(),
+                            location: progress.state.into(),
                             offset: line.line_start,
-                            line: Some(line_cnt),
+                            line: Some(progress.line_cnt),
                         })?;
                         if !line.buf[name.clone()].iter().copied().all(is_tchar) {
                             return Err(HttpParseError {
                                 kind: ParseErrorKind::InvalidHeaderName,
-                                location: state.into(),
+                                location: progress.state.into(),
                                 offset: name.start,
-                                line: Some(line_cnt),
+                                line: Some(progress.line_cnt),
                             });
                         }
                         let value = line.trim();
-                        headers.push(HeaderIx { name, value });
+                        if validate_header_value(&line.buf[value.clone()]).is_err() {
+                            return Err(HttpParseError {
+                                kind: ParseErrorKind::InvalidHeaderValue,
+                                location: progress.state.into(),
+                                offset: value.start,
+                                line: Some(progress.line_cnt),
+                            });
+                        }
+                        progress.headers.push(HeaderIx {
+                            name,
+                            value,
+                            folds: Vec::new(),
+                        });
                     }
                     ParseState::Body => unreachable!(),
                 }
@@ -275,155 +764,987 @@ where
                 continue;
             }
 
-            if 0 == self.reader.read().await.unwrap() {
-                return Err(HttpParseError {
-                    kind: ParseErrorKind::IncompleteMessage,
-                    location: state.into(),
-                    offset: self.reader.cursor,
-                    line: Some(line_cnt),
-                });
+            // Everything buffered belongs to this head, as the end of it has not been found, so
+            // the limits can be checked before reading more
+            let partial = self.reader.buf.len() - self.reader.cursor;
+            let budget = match progress.state {
+                ParseState::Line => {
+                    if partial > limits.max_start_line_bytes.get() {
+                        return Err(too_large(
+                            LimitKind::RequestLineBytes,
+                            limits.max_start_line_bytes,
+                            partial,
+                            progress.line_cnt + 1,
+                        ));
+                    }
+                    limits.max_start_line_bytes.get() - partial
+                }
+                ParseState::Headers => {
+                    let total = self.reader.buf.len() - progress.headers_start;
+                    if total > limits.max_header_bytes_total.get() {
+                        return Err(too_large(
+                            LimitKind::HeaderBytesTotal,
+                            limits.max_header_bytes_total,
+                            total,
+                            progress.line_cnt + 1,
+                        ));
+                    }
+                    if partial > limits.max_header_line_bytes.get() {
+                        return Err(too_large(
+                            LimitKind::HeaderLineBytes,
+                            limits.max_header_line_bytes,
+                            partial,
+                            progress.line_cnt + 1,
+                        ));
+                    }
+                    (limits.max_header_bytes_total.get() - total)
+                        .min(limits.max_header_line_bytes.get() - partial)
+                }
+                ParseState::Body => unreachable!(),
+            };
+            let offset = self.reader.cursor;
+            let (state, line_cnt) = (progress.state, progress.line_cnt);
+            let error = |kind| HttpParseError {
+                kind,
+                location: state.into(),
+                offset,
+                line: Some(line_cnt),
+            };
+            let idle = self.reader.buf.is_empty();
+            // Reading one byte past the budget is enough to find out the limit was exceeded
+            let read = self.reader.read_at_most(budget + 1);
+            let read = if idle {
+                read.await
+            } else {
+                let clock = &*limits.clock;
+                let deadline = *progress
+                    .deadline
+                    .get_or_insert_with(|| clock.now() + limits.read_timeout);
+                clock::timeout_at(clock, deadline, read)
+                    .await
+                    .map_err(|_| error(ParseErrorKind::Timeout))?
+            };
+            let read = read.map_err(|err| error(ParseErrorKind::Io(err.kind())))?;
+            if read == 0 {
+                return Err(error(ParseErrorKind::IncompleteMessage));
             }
         }
 
-        let header_bytes = self.reader.buf.split_to(self.reader.cursor).freeze();
-        // Reset cursor
-        self.reader.cursor = 0;
-        let mut header_map = HeaderMap::with_capacity(headers.len());
-        for header in headers {
-            let name = header_bytes.slice(header.name);
-            let value = header_bytes.slice(header.value);
-            let name = HeaderName::try_from(&name).map_err(|_| todo!())?;
-            header_map.entry(name).push(value);
-        }
-
-        // Now we can parse body
+        let HeadProgress {
+            state,
+            start_line,
+            mut headers,
+            ..
+        } = self.head.take().expect("the head is being parsed");
         assert_eq!(state, ParseState::Body);
-        let body = if let Some(_encoding) = header_map.get_header::<TransferEncoding>().unwrap() {
-            todo!()
-        } else if let Some(cl) = header_map.get_header::<ContentLength>().unwrap() {
-            // TODO: Handle message larger than 4GB on 32bit maybe?
-            let cl = cl as usize;
-            // Remove all header chunks
-            let mut body_buf = self.reader.buf.split_to(cl.min(self.reader.buf.len()));
-            let old_len = body_buf.len();
-            // We can safety resize, because the size is at most cl
-            body_buf.resize(cl, 0);
-            self.reader
-                .inner
-                .read_exact(&mut body_buf[old_len..cl])
-                .await
-                .unwrap();
-            Body::Full(body_buf.freeze())
-        } else {
-            // Everything else is part of the next request
-            Body::None
+        let (line_start, line_end) = start_line.expect("start line should be parsed");
+        let s_line = M::parse(ReaderLine {
+            buf: &self.reader.buf,
+            line_start,
+            line_end,
+        })?;
+        let header_bytes = self.reader.consume().freeze();
+        let mut header_map = match self.spare_headers.take() {
+            Some(header_map) => header_map,
+            None => HeaderMap::with_capacity(headers.len()),
         };
+        for header in headers.drain(..) {
+            let name = HeaderName::from_parsed(&header_bytes, header.name);
+            let value = if header.folds.is_empty() {
+                header_bytes.slice(header.value)
+            } else {
+                let mut value = BytesMut::new();
+                for part in std::iter::once(header.value).chain(header.folds) {
+                    if part.is_empty() {
+                        continue;
+                    }
+                    if !value.is_empty() {
+                        value.extend_from_slice(b" ");
+                    }
+                    value.extend_from_slice(&header_bytes[part]);
+                }
+                value.freeze()
+            };
+            header_map.entry(name).push_unchecked(value);
+        }
+        self.spare_fields = headers;
+
+        Ok((header_bytes, s_line, header_map))
+    }
 
-        M::to_output(
-            header_bytes,
-            s_line.expect("status line should be parsed"),
-            header_map,
-            body,
-        )
+    /// Reads a whole message body with the default limits
+    async fn read_body(&mut self, framing: BodyFraming) -> HttpParseResult<Body> {
+        self.body_pending = framing != BodyFraming::None;
+        Ok(match framing {
+            // Everything else is part of the next request
+            BodyFraming::None => Body::None,
+            _ => {
+                let (tx, stream) = BodyStream::channel(1);
+                let limits = BodyLimits::default();
+                let (res, body) =
+                    tokio::join!(self.pump_body(framing, tx, &limits), stream.collect(None));
+                res.map_err(body_error)?;
+                Body::Full(body.map_err(body_error)?)
+            }
+        })
     }
 
     pub async fn parse_request(&mut self) -> HttpParseResult<Request> {
-        self.parse_message::<line::RequestLine>().await
+        let (header_bytes, s_line, header_map) = self.parse_head::<line::RequestLine>().await?;
+        let framing = BodyFraming::for_request(&header_map)?;
+        let body = self.read_body(framing).await?;
+        line::RequestLine::to_output(header_bytes, s_line, header_map, body)
     }
 
-    pub async fn parse_response(&mut self) -> HttpParseResult<Response> {
-        self.parse_message::<line::ResponseLine>().await
+    /// Parses only the head of a request, the body must then be read with [`Self::pump_body`]
+    /// before the next request can be parsed
+    pub async fn parse_request_head(&mut self) -> HttpParseResult<(Request, BodyFraming)> {
+        let (header_bytes, s_line, header_map) = self.parse_head::<line::RequestLine>().await?;
+        let framing = BodyFraming::for_request(&header_map)?;
+        let req = line::RequestLine::to_output(header_bytes, s_line, header_map, Body::None)?;
+        self.body_pending = framing != BodyFraming::None;
+        Ok((req, framing))
     }
-}
 
-pub struct Sender<WRITER: AsyncWriteExt + Unpin> {
-    writer: WRITER,
-    buf: BytesMut,
-}
+    /// Reads a message body with the given framing, sending it to `tx` in chunks
+    /// On failure the error is sent to `tx` as well, and the connection is no longer in sync
+    pub async fn pump_body(
+        &mut self,
+        framing: BodyFraming,
+        tx: BodySender,
+        limits: &BodyLimits,
+    ) -> Result<(), BodyError> {
+        let res = match framing {
+            BodyFraming::None => Ok(()),
+            BodyFraming::Length(len) => match limits.max_body_bytes {
+                Some(max) if len > max.get() as u64 => {
+                    Err(BodyError::LimitExceeded { limit: max.get() })
+                }
+                _ => self.read_body_bytes(len, &tx, limits).await,
+            },
+            BodyFraming::Chunked => self.read_chunked_body(&tx, limits).await,
+            BodyFraming::UntilClose => self.read_body_until_close(&tx, limits).await,
+        };
+        match &res {
+            Ok(()) => self.body_pending = false,
+            Err(err) => tx.abort(err.clone()).await,
+        }
+        res
+    }
 
-impl<WRITER> Sender<WRITER>
-where
-    WRITER: AsyncWriteExt + Unpin,
-{
-    pub fn new(writer: WRITER) -> Self {
-        Self {
-            writer,
-            buf: BytesMut::with_capacity(8192),
+    async fn fill_body(&mut self, limits: &BodyLimits) -> Result<(), BodyError> {
+        match clock::timeout(&*limits.clock, limits.read_timeout, self.reader.read()).await {
+            Err(_) => Err(BodyError::TimedOut),
+            Ok(Ok(0)) | Ok(Err(_)) => Err(BodyError::ClientDisconnected),
+            Ok(Ok(_)) => Ok(()),
         }
     }
 
-    async fn send_headers(&mut self, headers: HeaderMap) -> std::io::Result<()> {
-        use std::fmt::Write;
-        for (name, value) in headers.iter() {
-            write!(self, "{}: ", name).unwrap();
-            self.buf.extend_from_slice(&value.collect());
-            write!(self, "\r\n").unwrap();
+    async fn read_body_bytes(
+        &mut self,
+        mut remaining: u64,
+        tx: &BodySender,
+        limits: &BodyLimits,
+    ) -> Result<(), BodyError> {
+        debug_assert_eq!(self.reader.cursor, 0);
+        while remaining > 0 {
+            if self.reader.buf.is_empty() {
+                self.fill_body(limits).await?;
+            }
+            let len = remaining.min(self.reader.buf.len() as u64) as usize;
+            remaining -= len as u64;
+            tx.send(self.reader.take(len)).await?;
         }
-        write!(self, "\r\n").unwrap();
         Ok(())
     }
 
-    pub async fn send_request(&mut self, request: Request) -> std::io::Result<()> {
-        use std::fmt::Write;
-        write!(
-            self,
-            "{} {} {}\r\n",
-            request.method,
-            std::str::from_utf8(&request.target).unwrap(),
-            request.version
-        )
-        .unwrap();
-        self.send_headers(request.headers).await?;
-        match request.body {
-            Body::None => {}
-            Body::Full(bytes) => self.buf.extend_from_slice(&bytes),
-        }
-        self.flush().await?;
-        Ok(())
+    /// Reads a body delimited by the peer closing the connection
+    async fn read_body_until_close(
+        &mut self,
+        tx: &BodySender,
+        limits: &BodyLimits,
+    ) -> Result<(), BodyError> {
+        let mut total: usize = 0;
+        loop {
+            if !self.reader.buf.is_empty() {
+                let len = self.reader.buf.len();
+                total = total.saturating_add(len);
+                if let Some(max) = limits.max_body_bytes
+                    && total > max.get()
+                {
+                    return Err(BodyError::LimitExceeded { limit: max.get() });
+                }
+                tx.send(self.reader.take(len)).await?;
+            }
+            match clock::timeout(&*limits.clock, limits.read_timeout, self.reader.read()).await {
+                Err(_) => return Err(BodyError::TimedOut),
+                Ok(Ok(0)) => return Ok(()),
+                Ok(Err(_)) => return Err(BodyError::ClientDisconnected),
+                Ok(Ok(_)) => {}
+            }
+        }
     }
 
-    pub async fn send_response(&mut self, response: Response) -> std::io::Result<()> {
-        use std::fmt::Write;
-        write!(
-            self,
-            "{} {} {}\r\n",
-            response.version,
-            response.status,
-            std::str::from_utf8(&response.message).unwrap()
-        )
-        .unwrap();
-        self.send_headers(response.headers).await?;
-        match response.body {
-            Body::None => {}
-            Body::Full(bytes) => self.buf.extend_from_slice(&bytes),
-        }
-        self.flush().await?;
-        Ok(())
+    /// Reads a line of a chunked body, without the line terminator
+    async fn read_body_line(
+        &mut self,
+        limit: usize,
+        limits: &BodyLimits,
+    ) -> Result<Bytes, BodyError> {
+        loop {
+            if let Some(line) = self.reader.get_line() {
+                let range = line.range();
+                let line_bytes = self.reader.consume().freeze();
+                return Ok(line_bytes.slice(range));
+            }
+            if self.reader.buf.len() > limit {
+                return Err(BodyError::LimitExceeded { limit });
+            }
+            self.fill_body(limits).await?;
+        }
     }
 
-    async fn flush(&mut self) -> std::io::Result<()> {
-        self.writer.write_all(&self.buf).await?;
-        self.buf.clear();
-        self.writer.flush().await
+    /// Reads a chunked body
+    /// SPEC: RFC 9112 - 7.1. Chunked Transfer Coding
+    async fn read_chunked_body(
+        &mut self,
+        tx: &BodySender,
+        limits: &BodyLimits,
+    ) -> Result<(), BodyError> {
+        // Chunk size lines are tiny, extensions are ignored but must not be unbounded
+        const MAX_CHUNK_LINE: usize = 4096;
+        fn decode_err(kind: ParseErrorKind) -> BodyError {
+            BodyError::Decode(Arc::new(HttpParseError {
+                kind,
+                location: Location::Body,
+                offset: 0,
+                line: None,
+            }))
+        }
+
+        let mut total: usize = 0;
+        loop {
+            // ABNF: chunk = chunk-size [ chunk-ext ] CRLF chunk-data CRLF
+            let line = self.read_body_line(MAX_CHUNK_LINE, limits).await?;
+            let size = match memchr(b';', &line) {
+                Some(ext) => &line[..ext],
+                None => &line[..],
+            }
+            .trim_ascii();
+            if size.is_empty() || size.len() > 16 || !size.iter().all(u8::is_ascii_hexdigit) {
+                return Err(decode_err(ParseErrorKind::ChunkSizeInvalid));
+            }
+            // SAFETY: We checked that it is all hex digits
+            let size = u64::from_str_radix(unsafe { std::str::from_utf8_unchecked(size) }, 16)
+                .map_err(|_| decode_err(ParseErrorKind::ChunkSizeInvalid))?;
+
+            if size == 0 {
+                break;
+            }
+            if size > limits.max_chunk_size_bytes.get() as u64 {
+                return Err(BodyError::LimitExceeded {
+                    limit: limits.max_chunk_size_bytes.get(),
+                });
+            }
+            total = total.saturating_add(size as usize);
+            if let Some(max) = limits.max_body_bytes
+                && total > max.get()
+            {
+                return Err(BodyError::LimitExceeded { limit: max.get() });
+            }
+
+            self.read_body_bytes(size, tx, limits).await?;
+            if !self.read_body_line(2, limits).await?.is_empty() {
+                return Err(decode_err(ParseErrorKind::ChunkCrlfMissing));
+            }
+        }
+
+        // Trailers are kept apart from the headers, they are available from the body stream
+        // SPEC: RFC 9112 - 7.1.2. Chunked Trailer Section
+        // ABNF: trailer-section = *( field-line CRLF )
+        let trailer_err = |kind| {
+            BodyError::Decode(Arc::new(HttpParseError {
+                kind,
+                location: Location::Trailers,
+                offset: 0,
+                line: None,
+            }))
+        };
+        let max_trailer = limits.max_trailer_bytes_total.get();
+        let mut trailer_bytes = 0;
+        let mut trailers = HeaderMap::new();
+        loop {
+            let line = self.read_body_line(max_trailer, limits).await?;
+            if line.is_empty() {
+                if !trailers.is_empty() {
+                    tx.set_trailers(trailers);
+                }
+                return Ok(());
+            }
+            trailer_bytes += line.len() + 2;
+            if trailer_bytes > max_trailer {
+                return Err(BodyError::LimitExceeded { limit: max_trailer });
+            }
+            // Obsolete line folding is not allowed here, a fold fails as an invalid name
+            let colon = memchr(b':', &line)
+                .ok_or_else(|| trailer_err(ParseErrorKind::MalformedHeaderLine))?;
+            let name = &line[..colon];
+            if name.is_empty() || !name.iter().copied().all(is_tchar) {
+                return Err(trailer_err(ParseErrorKind::InvalidHeaderName));
+            }
+            let value = line[colon + 1..].trim_ascii();
+            if validate_header_value(value).is_err() {
+                return Err(trailer_err(ParseErrorKind::InvalidHeaderValue));
+            }
+            let value = line.slice_ref(value);
+            let name = HeaderName::from_parsed(&line, 0..colon);
+            trailers.entry(name).push_unchecked(value);
+        }
     }
-}
 
-impl<WRITER> fmt::Write for Sender<WRITER>
-where
-    WRITER: AsyncWriteExt + Unpin,
-{
-    fn write_str(&mut self, s: &str) -> fmt::Result {
-        self.buf.extend_from_slice(s.as_bytes());
-        Ok(())
+    /// Parses a response to a request with `method`, interim (1xx) responses are returned as
+    /// well, and are followed by the final response
+    pub async fn parse_response(&mut self, method: &Method) -> HttpParseResult<Response> {
+        let (header_bytes, s_line, header_map) = self.parse_head::<line::ResponseLine>().await?;
+        let framing = BodyFraming::for_response(&header_map, s_line.status, method)?;
+        let body = self.read_body(framing).await?;
+        line::ResponseLine::to_output(header_bytes, s_line, header_map, body)
+    }
+
+    /// Parses only the head of a response to a request with `method`, the body must then be
+    /// read with [`Self::pump_body`]
+    pub async fn parse_response_head(
+        &mut self,
+        method: &Method,
+    ) -> HttpParseResult<(Response, BodyFraming)> {
+        let (header_bytes, s_line, header_map) = self.parse_head::<line::ResponseLine>().await?;
+        let framing = BodyFraming::for_response(&header_map, s_line.status, method)?;
+        let res = line::ResponseLine::to_output(header_bytes, s_line, header_map, Body::None)?;
+        self.body_pending = framing != BodyFraming::None;
+        Ok((res, framing))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    mod reset {
+        use tokio::io::AsyncReadExt;
+
+        use crate::http::{
+            BodyStream,
+            parser::{BodyFraming, Parser, tests::body::limits},
+        };
+
+        #[tokio::test]
+        async fn pipelined_requests() {
+            let mut parser = Parser::new(
+                &b"GET /a HTTP/1.1\r\nHost: a\r\n\r\nGET /b HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+            );
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.target, "/a");
+            // The second request was read along with the first, and must survive the reset
+            parser.reset();
+            assert_eq!(parser.buffered(), b"GET /b HTTP/1.1\r\nHost: a\r\n\r\n");
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.target, "/b");
+            parser.reset();
+            assert!(parser.buffered().is_empty());
+        }
+
+        #[tokio::test]
+        async fn leftover_after_body() {
+            let mut parser = Parser::new(
+                &b"POST /a HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\r\nabcGET /b HTTP/1.1\r\nHost: a\r\n\r\n"[..],
+            );
+            let (_, framing) = parser.parse_request_head().await.unwrap();
+            let (tx, stream) = BodyStream::channel(4);
+            parser.pump_body(framing, tx, &limits()).await.unwrap();
+            assert_eq!(stream.collect(None).await.unwrap(), "abc");
+            parser.reset();
+            let (req, framing) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.target, "/b");
+            assert_eq!(framing, BodyFraming::None);
+        }
+
+        #[tokio::test]
+        async fn partial_next_request() {
+            // The next request arrives in a separate read, after a partial line
+            let reader = (&b"GET /a HTTP/1.1\r\nHost: a\r\n\r\nGET /b HT"[..])
+                .chain(&b"TP/1.1\r\nHost: a\r\n\r\n"[..]);
+            let mut parser = Parser::new(reader);
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.target, "/a");
+            parser.reset();
+            assert_eq!(parser.buffered(), b"GET /b HT");
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.target, "/b");
+        }
+
+        #[cfg(debug_assertions)]
+        #[tokio::test]
+        #[should_panic(expected = "parser reset before the message body was read")]
+        async fn reset_with_pending_body() {
+            let mut parser =
+                Parser::new(&b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 3\r\n\r\nabc"[..]);
+            parser.parse_request_head().await.unwrap();
+            parser.reset();
+        }
+    }
+
+    mod resume {
+        use tokio::io::AsyncWriteExt;
+
+        use crate::http::parser::Parser;
+
+        #[tokio::test]
+        async fn dropped_parse_resumes() {
+            let (mut client, server) = tokio::io::duplex(1024);
+            let mut parser = Parser::new(server);
+            client
+                .write_all(b"GET /a HTTP/1.1\r\nHost: a\r\nAcc")
+                .await
+                .unwrap();
+            // The parse is cancelled once it has consumed the complete lines
+            tokio::select! {
+                biased;
+                _ = parser.parse_request_head() => panic!("the head is incomplete"),
+                _ = tokio::task::yield_now() => {}
+            }
+            let progress = parser.head.as_ref().expect("the parse was started");
+            assert_eq!(progress.headers.len(), 1);
+            assert_eq!(parser.reader.scanned, 29);
+            assert!(parser.reader.line_ends.is_empty());
+
+            client.write_all(b"ept: */*\r\n\r\n").await.unwrap();
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.target, "/a");
+            assert_eq!(req.headers.len(), 2);
+            assert!(parser.head.is_none());
+        }
+
+        #[tokio::test]
+        async fn recycled_headers_are_reused() {
+            let (mut client, server) = tokio::io::duplex(1024);
+            let mut parser = Parser::new(server);
+            client
+                .write_all(b"GET /a HTTP/1.1\r\nHost: a\r\nAccept: */*\r\n\r\n")
+                .await
+                .unwrap();
+            client
+                .write_all(b"GET /b HTTP/1.1\r\nHost: b\r\n\r\n")
+                .await
+                .unwrap();
+            let (mut req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.headers.len(), 2);
+            parser.recycle_headers(std::mem::take(&mut req.headers));
+            assert!(parser.spare_headers.as_ref().is_some_and(|h| h.is_empty()));
+
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert!(parser.spare_headers.is_none());
+            assert_eq!(req.target, "/b");
+            // Nothing of the previous message is left in the reused map
+            assert_eq!(req.headers.len(), 1);
+        }
+    }
+
+    mod headers {
+        use bytes::Bytes;
+
+        use crate::http::{
+            header::{Builtin, HeaderName},
+            parser::{HeadLimits, ParseErrorKind, Parser},
+            response::StatusCode,
+        };
+
+        const FOLDED: &[u8] =
+            b"GET / HTTP/1.1\r\nHost: a\r\nX-A: a\r\n \t b \r\n\tc\r\nX-B: d\r\n\r\n";
+
+        #[tokio::test]
+        async fn obs_fold_rejected() {
+            for head in [
+                FOLDED,
+                // A fold before the first field line has nothing to continue
+                b"GET / HTTP/1.1\r\n Host: a\r\n\r\n",
+            ] {
+                let mut parser = Parser::new(head);
+                let err = parser.parse_request_head().await.unwrap_err();
+                assert!(matches!(err.kind, ParseErrorKind::MalformedHeaderLine));
+                assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+            }
+        }
+
+        #[tokio::test]
+        async fn obs_fold_merged() {
+            let limits = HeadLimits {
+                allow_obs_fold: true,
+                ..Default::default()
+            };
+            let mut parser = Parser::with_limits(FOLDED, limits.clone());
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            let name =
+                |name: &'static [u8]| HeaderName::try_from(&Bytes::from_static(name)).unwrap();
+            assert_eq!(req.headers.get(&name(b"X-A")).unwrap()[0], "a b c");
+            assert_eq!(req.headers.get(&name(b"X-B")).unwrap()[0], "d");
+            assert!(req.headers.contains(&HeaderName::builtin(Builtin::Host)));
+
+            let mut parser =
+                Parser::with_limits(&b"GET / HTTP/1.1\r\n Host: a\r\n\r\n"[..], limits);
+            assert!(parser.parse_request_head().await.is_err());
+        }
+
+        #[tokio::test]
+        async fn invalid_value() {
+            let mut parser = Parser::new(&b"GET / HTTP/1.1\r\nHost: a\r\nX-A: a\rb\r\n\r\n"[..]);
+            let err = parser.parse_request_head().await.unwrap_err();
+            assert!(matches!(err.kind, ParseErrorKind::InvalidHeaderValue));
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn mixed_case_names() {
+            let head = b"GET / HTTP/1.1\r\nhost: a\r\nx-api-key: 1\r\nX-API-KEY: 2\r\n\r\n";
+            let mut parser = Parser::new(&head[..]);
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.headers.len(), 2);
+            assert!(req.headers.contains(&HeaderName::builtin(Builtin::Host)));
+            let key = HeaderName::try_from(&Bytes::from_static(b"X-Api-Key")).unwrap();
+            let values = req.headers.get(&key).unwrap();
+            assert_eq!(values[0], "1");
+            assert_eq!(values[1], "2");
+            // The name is kept as it was first received
+            let names: Vec<_> = req
+                .headers
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect();
+            assert_eq!(names, ["Host", "x-api-key"]);
+        }
+    }
+
+    mod malformed {
+        use crate::http::{
+            Body,
+            parser::{ParseErrorKind, Parser},
+            request::RequestTarget,
+            response::StatusCode,
+        };
+
+        async fn error(input: &'static [u8]) -> ParseErrorKind {
+            let err = Parser::new(input).parse_request().await.unwrap_err();
+            assert_ne!(err.status_code(), StatusCode::OK);
+            err.kind
+        }
+
+        #[tokio::test]
+        async fn request_line() {
+            let kind = error(b"G(T / HTTP/1.1\r\nHost: a\r\n\r\n").await;
+            assert!(matches!(kind, ParseErrorKind::InvalidMethod));
+            let kind = error(b"GET /\x01 HTTP/1.1\r\nHost: a\r\n\r\n").await;
+            assert!(matches!(kind, ParseErrorKind::InvalidTarget));
+        }
+
+        #[tokio::test]
+        async fn truncated_body() {
+            let kind = error(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 10\r\n\r\nabc").await;
+            assert!(matches!(kind, ParseErrorKind::IncompleteMessage));
+            let kind =
+                error(b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n")
+                    .await;
+            assert!(matches!(kind, ParseErrorKind::ChunkSizeInvalid));
+        }
+
+        #[tokio::test]
+        async fn chunked_body() {
+            let mut parser = Parser::new(
+                &b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n"[..],
+            );
+            let req = parser.parse_request().await.unwrap();
+            assert!(matches!(req.body, Body::Full(body) if body == "abc"));
+        }
+
+        #[tokio::test]
+        async fn targets() {
+            for (target, expected) in [
+                (&b"*"[..], Some(RequestTarget::Asterisk)),
+                (b"*/a", None),
+                (
+                    b"example.com:443",
+                    Some(RequestTarget::Authority("example.com:443".into())),
+                ),
+                (
+                    b"[::1]:443",
+                    Some(RequestTarget::Authority("[::1]:443".into())),
+                ),
+                (
+                    b"http://a/b",
+                    Some(RequestTarget::Absolute("http://a/b".into())),
+                ),
+                (b"a", None),
+                (b"1http://a", None),
+            ] {
+                let mut input = b"GET ".to_vec();
+                input.extend_from_slice(target);
+                input.extend_from_slice(b" HTTP/1.1\r\nHost: a\r\n\r\n");
+                let req = Parser::new(&input[..]).parse_request().await.unwrap();
+                assert_eq!(req.target().ok(), expected);
+            }
+        }
+    }
+
+    mod framing {
+        use crate::http::{
+            parser::{BodyFraming, ParseErrorKind, Parser},
+            response::StatusCode,
+        };
+
+        async fn framing(head: &'static [u8]) -> Result<BodyFraming, ParseErrorKind> {
+            let mut parser = Parser::new(head);
+            parser
+                .parse_request_head()
+                .await
+                .map(|(_, framing)| framing)
+                .map_err(|err| {
+                    assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+                    err.kind
+                })
+        }
+
+        #[tokio::test]
+        async fn transfer_encoding_and_content_length() {
+            let res = framing(
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\nTransfer-Encoding: chunked\r\n\r\n",
+            )
+            .await;
+            assert!(matches!(res, Err(ParseErrorKind::ConflictingContentLength)));
+        }
+
+        #[tokio::test]
+        async fn repeated_content_length() {
+            let res = framing(
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\nContent-Length: 5, 5\r\n\r\n",
+            )
+            .await;
+            assert_eq!(res.unwrap(), BodyFraming::Length(5));
+
+            let res = framing(
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n",
+            )
+            .await;
+            assert!(matches!(res, Err(ParseErrorKind::ConflictingContentLength)));
+
+            let res = framing(b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5, 6\r\n\r\n").await;
+            assert!(matches!(res, Err(ParseErrorKind::ConflictingContentLength)));
+        }
+
+        #[tokio::test]
+        async fn invalid_content_length() {
+            for head in [
+                &b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: +5\r\n\r\n"[..],
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5,\r\n\r\n",
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 0x5\r\n\r\n",
+            ] {
+                let res = framing(head).await;
+                assert!(matches!(res, Err(ParseErrorKind::InvalidContentLength)));
+            }
+        }
+
+        #[tokio::test]
+        async fn chunked_not_final() {
+            for head in [
+                &b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked, gzip\r\n\r\n"[..],
+                b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n\r\n",
+            ] {
+                let res = framing(head).await;
+                assert!(matches!(res, Err(ParseErrorKind::InvalidTransferEncoding)));
+            }
+            let res = framing(
+                b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n",
+            )
+            .await;
+            assert_eq!(res.unwrap(), BodyFraming::Chunked);
+        }
+    }
+
+    mod version {
+        use crate::http::{
+            HttpVersion,
+            parser::{ParseErrorKind, Parser},
+            response::StatusCode,
+        };
+
+        #[tokio::test]
+        async fn http_1_0_without_host() {
+            let mut parser = Parser::new(&b"GET / HTTP/1.0\r\n\r\n"[..]);
+            let (req, _) = parser.parse_request_head().await.unwrap();
+            assert_eq!(req.version, HttpVersion::HTTP_1_0);
+        }
+
+        async fn version_error(head: &'static [u8]) -> (ParseErrorKind, StatusCode) {
+            let mut parser = Parser::new(head);
+            let err = parser.parse_request_head().await.unwrap_err();
+            let status = err.status_code();
+            (err.kind, status)
+        }
+
+        #[tokio::test]
+        async fn unsupported_versions() {
+            for head in [
+                &b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n"[..],
+                b"GET / HTTP/3\r\nHost: a\r\n\r\n",
+                b"GET / HTTP/0.9\r\nHost: a\r\n\r\n",
+            ] {
+                let (kind, status) = version_error(head).await;
+                assert!(matches!(kind, ParseErrorKind::VersionNotSupported));
+                assert_eq!(status, StatusCode::HTTP_VERSION_NOT_SUPPORTED);
+            }
+            let (kind, status) = version_error(b"GET / HTTP/1.x\r\nHost: a\r\n\r\n").await;
+            assert!(matches!(kind, ParseErrorKind::InvalidVersion));
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn http_1_1_requires_host() {
+            let mut parser = Parser::new(&b"GET / HTTP/1.1\r\n\r\n"[..]);
+            let err = parser.parse_request_head().await.unwrap_err();
+            assert!(matches!(err.kind, ParseErrorKind::MissingRequiredHeader));
+        }
+    }
+
+    mod limits {
+        use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        use crate::{
+            clock::{MockClock, TokioClock},
+            http::{
+                parser::{HeadLimits, LimitKind, ParseErrorKind, Parser},
+                response::StatusCode,
+            },
+        };
+
+        fn limits() -> HeadLimits {
+            HeadLimits {
+                max_start_line_bytes: NonZeroUsize::new(64).unwrap(),
+                max_header_bytes_total: NonZeroUsize::new(256).unwrap(),
+                max_header_line_bytes: NonZeroUsize::new(64).unwrap(),
+                max_header_count: NonZeroUsize::new(8).unwrap(),
+                allow_obs_fold: false,
+                read_timeout: Duration::from_secs(1),
+                clock: TokioClock::shared(),
+            }
+        }
+
+        async fn limit_exceeded<R: AsyncReadExt + Unpin>(reader: R) -> LimitKind {
+            let mut parser = Parser::with_limits(reader, limits());
+            let err = parser.parse_request_head().await.unwrap_err();
+            // The buffer must not keep growing past the limit
+            assert!(parser.reader.buf.len() <= 256 + 64);
+            match err.kind {
+                ParseErrorKind::TooLarge { what, .. } => {
+                    assert_eq!(
+                        err.status_code(),
+                        match what {
+                            LimitKind::RequestLineBytes => StatusCode::URI_TOO_LONG,
+                            _ => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+                        }
+                    );
+                    what
+                }
+                other => panic!("unexpected error: {}", other),
+            }
+        }
+
+        #[tokio::test]
+        async fn endless_request_line() {
+            let reader = (&b"GET /"[..]).chain(tokio::io::repeat(b'a'));
+            assert!(matches!(
+                limit_exceeded(reader).await,
+                LimitKind::RequestLineBytes
+            ));
+        }
+
+        #[tokio::test]
+        async fn endless_header_line() {
+            let reader = (&b"GET / HTTP/1.1\r\nX-Long: "[..]).chain(tokio::io::repeat(b'a'));
+            assert!(matches!(
+                limit_exceeded(reader).await,
+                LimitKind::HeaderLineBytes
+            ));
+        }
+
+        #[tokio::test]
+        async fn header_count() {
+            let head = format!("GET / HTTP/1.1\r\n{}\r\n", "X: a\r\n".repeat(9));
+            assert!(matches!(
+                limit_exceeded(head.as_bytes()).await,
+                LimitKind::HeaderCount
+            ));
+        }
+
+        #[tokio::test]
+        async fn header_bytes_total() {
+            let head = format!(
+                "GET / HTTP/1.1\r\n{}",
+                "X-Header: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n".repeat(1000)
+            );
+            assert!(matches!(
+                limit_exceeded(head.as_bytes()).await,
+                LimitKind::HeaderBytesTotal
+            ));
+        }
+
+        #[tokio::test]
+        async fn head_read_timeout() {
+            let clock = Arc::new(MockClock::new());
+            let limits = HeadLimits {
+                clock: clock.clone(),
+                ..limits()
+            };
+            let (mut client, server) = tokio::io::duplex(1024);
+            let mut parser = Parser::with_limits(server, limits);
+            let parse = parser.parse_request_head();
+            tokio::pin!(parse);
+
+            // An idle connection is not timed out by the parser
+            tokio::select! {
+                biased;
+                _ = &mut parse => panic!("the head is incomplete"),
+                _ = async { clock.advance(Duration::from_secs(5)) } => {}
+            }
+            client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+            let (err, ()) = tokio::join!(parse, async {
+                tokio::task::yield_now().await;
+                clock.advance(Duration::from_secs(1))
+            });
+            let err = err.unwrap_err();
+            assert!(matches!(err.kind, ParseErrorKind::Timeout));
+            assert_eq!(err.status_code(), StatusCode::REQUEST_TIMEOUT);
+        }
+    }
+
+    mod body {
+        use std::{num::NonZeroUsize, time::Duration};
+
+        use bytes::Bytes;
+
+        use crate::{
+            clock::TokioClock,
+            http::{
+                BodyError, BodyStream,
+                header::HeaderName,
+                parser::{BodyFraming, BodyLimits, Parser},
+            },
+        };
+
+        pub(super) fn limits() -> BodyLimits {
+            BodyLimits {
+                max_body_bytes: None,
+                max_chunk_size_bytes: NonZeroUsize::new(1024).unwrap(),
+                max_trailer_bytes_total: NonZeroUsize::new(1024).unwrap(),
+                read_timeout: Duration::from_secs(1),
+                clock: TokioClock::shared(),
+            }
+        }
+
+        async fn read_body(
+            input: &'static [u8],
+            limits: &BodyLimits,
+        ) -> (BodyFraming, Result<Bytes, BodyError>, Parser<&'static [u8]>) {
+            let mut parser = Parser::new(input);
+            let (_, framing) = parser.parse_request_head().await.unwrap();
+            let (tx, stream) = BodyStream::channel(64);
+            let res = parser.pump_body(framing, tx, limits).await;
+            let body = stream.collect(None).await;
+            assert_eq!(res.is_ok(), body.is_ok());
+            (framing, body, parser)
+        }
+
+        #[tokio::test]
+        async fn content_length() {
+            let (framing, body, mut parser) = read_body(
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhelloGET / HTTP/1.1\r\nHost: a\r\n\r\n",
+                &limits(),
+            )
+            .await;
+            assert_eq!(framing, BodyFraming::Length(5));
+            assert_eq!(body.unwrap(), "hello");
+            // The next request should be intact
+            let (_, framing) = parser.parse_request_head().await.unwrap();
+            assert_eq!(framing, BodyFraming::None);
+        }
+
+        #[tokio::test]
+        async fn chunked() {
+            let (framing, body, _) = read_body(
+                b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Trailer: a\r\n\r\n",
+                &limits(),
+            )
+            .await;
+            assert_eq!(framing, BodyFraming::Chunked);
+            assert_eq!(body.unwrap(), "hello world");
+        }
+
+        #[tokio::test]
+        async fn chunked_trailers() {
+            let mut parser = Parser::new(
+                &b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\nX-Sum:  abc \r\nX-Sum: def\r\n\r\n"[..],
+            );
+            let (_, framing) = parser.parse_request_head().await.unwrap();
+            let (tx, stream) = BodyStream::channel(4);
+            parser.pump_body(framing, tx, &limits()).await.unwrap();
+            assert_eq!(stream.collect(None).await.unwrap(), "hi");
+            let name = HeaderName::try_from(&Bytes::from_static(b"X-Sum")).unwrap();
+            let sums = stream.trailers().unwrap().get(&name).unwrap();
+            assert_eq!(sums[0], "abc");
+            assert_eq!(sums[1], "def");
+
+            let mut parser = Parser::new(
+                &b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n folded\r\n\r\n"[..],
+            );
+            let (_, framing) = parser.parse_request_head().await.unwrap();
+            let (tx, _stream) = BodyStream::channel(4);
+            let err = parser.pump_body(framing, tx, &limits()).await.unwrap_err();
+            assert!(matches!(err, BodyError::Decode(_)));
+        }
+
+        #[tokio::test]
+        async fn chunked_invalid_size() {
+            let (_, body, _) = read_body(
+                b"POST / HTTP/1.1\r\nHost: a\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\nhello\r\n0\r\n\r\n",
+                &limits(),
+            )
+            .await;
+            assert!(matches!(body, Err(BodyError::Decode(_))));
+        }
+
+        #[tokio::test]
+        async fn body_limit() {
+            let mut limits = limits();
+            limits.max_body_bytes = NonZeroUsize::new(4);
+            let (_, body, _) = read_body(
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhello",
+                &limits,
+            )
+            .await;
+            assert!(matches!(body, Err(BodyError::LimitExceeded { limit: 4 })));
+        }
+
+        #[tokio::test]
+        async fn client_disconnected() {
+            let (_, body, _) = read_body(
+                b"POST / HTTP/1.1\r\nHost: a\r\nContent-Length: 10\r\n\r\nhello",
+                &limits(),
+            )
+            .await;
+            assert!(matches!(body, Err(BodyError::ClientDisconnected)));
+        }
+    }
+
     mod reader {
         use bytes::BytesMut;
 
-        use crate::http::parser::ReaderLine;
+        use crate::http::parser::{Reader, ReaderLine, is_tchar};
 
         #[test]
         fn line_next_word() {
@@ -469,5 +1790,33 @@ mod tests {
             let trimmed = line.trim();
             assert_eq!(trimmed, 3..20);
         }
+
+        #[test]
+        fn line_ends_scanned_once() {
+            let mut reader = Reader::new(tokio::io::empty());
+            reader.buf.extend_from_slice(b"a\r\nb\n\r\nc");
+            let line = reader.get_line().unwrap();
+            assert_eq!(line.as_slice(), b"a");
+            // The whole buffer was scanned by the first line
+            assert_eq!(reader.scanned, 8);
+            assert_eq!(reader.line_ends, [4, 6]);
+
+            drop(reader.consume());
+            assert_eq!(reader.line_ends, [1, 3]);
+            assert_eq!(reader.get_line().unwrap().as_slice(), b"b");
+            assert!(reader.get_line().unwrap().is_empty());
+            assert!(reader.get_line().is_none());
+
+            reader.buf.extend_from_slice(b"\n");
+            assert_eq!(reader.get_line().unwrap().as_slice(), b"c");
+        }
+
+        #[test]
+        fn tchar_table() {
+            let tchars = (0..=255u8).filter(|&b| is_tchar(b)).count();
+            assert_eq!(tchars, 26 * 2 + 10 + 15);
+            assert!(is_tchar(b'~') && is_tchar(b'z') && is_tchar(b'0'));
+            assert!(!is_tchar(b':') && !is_tchar(b' ') && !is_tchar(0x80));
+        }
     }
 }
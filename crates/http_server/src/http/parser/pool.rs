@@ -0,0 +1,205 @@
+//! Buffers shared between the connections of a server
+//!
+//! Connections take their read and write buffers from a [`BufferPool`] and give them back when
+//! they close, so a busy server doesn't allocate new buffers for every connection. Buffers which
+//! grew past [`BufferPool::max_buffer_capacity`] while handling a large message are replaced
+//! between messages, which bounds the memory an idle keep-alive connection holds
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use bytes::BytesMut;
+
+#[derive(Debug)]
+struct Inner {
+    buffers: Mutex<Retained>,
+    buffer_size: usize,
+    max_buffer_capacity: usize,
+    max_retained_bytes: usize,
+}
+
+#[derive(Debug, Default)]
+struct Retained {
+    buffers: Vec<BytesMut>,
+    bytes: usize,
+}
+
+/// A pool of reusable connection buffers
+#[derive(Debug, Clone)]
+pub struct BufferPool(Arc<Inner>);
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(8 * 1024, 64 * 1024, 4 * 1024 * 1024)
+    }
+}
+
+impl BufferPool {
+    /// A pool handing out buffers of `buffer_size` bytes, which keeps at most
+    /// `max_retained_bytes` of buffers for reuse, and replaces buffers larger than
+    /// `max_buffer_capacity` between messages
+    ///
+    /// # Panics
+    /// If `buffer_size` is larger than `max_buffer_capacity`
+    pub fn new(buffer_size: usize, max_buffer_capacity: usize, max_retained_bytes: usize) -> Self {
+        assert!(
+            buffer_size <= max_buffer_capacity,
+            "buffers must fit in the maximum capacity"
+        );
+        Self(Arc::new(Inner {
+            buffers: Mutex::default(),
+            buffer_size,
+            max_buffer_capacity,
+            max_retained_bytes,
+        }))
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.0.buffer_size
+    }
+
+    /// The largest buffer a connection keeps between messages
+    pub fn max_buffer_capacity(&self) -> usize {
+        self.0.max_buffer_capacity
+    }
+
+    /// The bytes of the buffers waiting to be reused
+    pub fn retained_bytes(&self) -> usize {
+        self.0.buffers.lock().unwrap().bytes
+    }
+
+    /// An empty buffer with at least [`Self::buffer_size`] bytes of capacity
+    pub(crate) fn get(&self) -> BytesMut {
+        let mut retained = self.0.buffers.lock().unwrap();
+        match retained.buffers.pop() {
+            Some(buf) => {
+                retained.bytes -= buf.capacity();
+                buf
+            }
+            None => BytesMut::with_capacity(self.0.buffer_size),
+        }
+    }
+
+    /// Gives a buffer back to be reused, buffers which are too large, or which don't fit in the
+    /// pool, are freed
+    pub(crate) fn put(&self, mut buf: BytesMut) {
+        buf.clear();
+        let capacity = buf.capacity();
+        if capacity < self.0.buffer_size || capacity > self.0.max_buffer_capacity {
+            return;
+        }
+        let mut retained = self.0.buffers.lock().unwrap();
+        if retained.bytes + capacity <= self.0.max_retained_bytes {
+            retained.bytes += capacity;
+            retained.buffers.push(buf);
+        }
+    }
+
+    /// Replaces `buf` with a pooled buffer if it grew too large, keeping its contents
+    pub(crate) fn shrink(&self, buf: &mut BytesMut) {
+        if buf.capacity() <= self.0.max_buffer_capacity || buf.len() > self.0.buffer_size {
+            return;
+        }
+        let mut replacement = self.get();
+        replacement.extend_from_slice(buf);
+        // The large buffer is freed rather than pooled
+        *buf = replacement;
+    }
+}
+
+/// A buffer which is given back to its pool when dropped
+/// It holds no borrows, so owners like [`super::Sender`] can hold borrowed writers
+#[derive(Debug, Default)]
+pub(crate) struct PooledBuf {
+    buf: BytesMut,
+    pool: Option<BufferPool>,
+}
+
+impl PooledBuf {
+    /// A buffer which is allocated once it is written to
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Swaps the buffer for one from `pool`, keeping its contents
+    pub(crate) fn set_pool(&mut self, pool: BufferPool) {
+        let mut buf = pool.get();
+        buf.extend_from_slice(&self.buf);
+        self.buf = buf;
+        self.pool = Some(pool);
+    }
+
+    /// Replaces the buffer if it grew past the maximum capacity of its pool
+    pub(crate) fn shrink(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.shrink(&mut self.buf);
+        }
+    }
+}
+
+impl Deref for PooledBuf {
+    type Target = BytesMut;
+
+    fn deref(&self) -> &BytesMut {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuf {
+    fn deref_mut(&mut self) -> &mut BytesMut {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuf {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.put(std::mem::take(&mut self.buf));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse_buffers() {
+        let pool = BufferPool::new(16, 64, 48);
+        let mut buf = pool.get();
+        assert!(buf.capacity() >= 16);
+        buf.extend_from_slice(b"data");
+        let capacity = buf.capacity();
+        pool.put(buf);
+        assert_eq!(pool.retained_bytes(), capacity);
+        let buf = pool.get();
+        assert!(buf.is_empty());
+        assert_eq!(buf.capacity(), capacity);
+        assert_eq!(pool.retained_bytes(), 0);
+
+        // Buffers which grew too large are not kept
+        pool.put(BytesMut::with_capacity(128));
+        // Nor are buffers beyond what the pool retains
+        pool.put(BytesMut::with_capacity(32));
+        pool.put(BytesMut::with_capacity(32));
+        assert_eq!(pool.retained_bytes(), 32);
+    }
+
+    #[test]
+    fn shrink_large_buffers() {
+        let pool = BufferPool::new(16, 64, 1024);
+        let mut buf = BytesMut::with_capacity(1024);
+        buf.extend_from_slice(b"next request");
+        pool.shrink(&mut buf);
+        assert_eq!(buf, &b"next request"[..]);
+        assert!(buf.capacity() <= 64);
+
+        // A buffer still holding a lot of data is left alone
+        let mut buf = BytesMut::from(&[0; 100][..]);
+        buf.reserve(1024);
+        pool.shrink(&mut buf);
+        assert!(buf.capacity() > 1024);
+    }
+}
@@ -0,0 +1,816 @@
+use std::{
+    fmt,
+    io::{self, IoSlice},
+    num::NonZeroU64,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::io::AsyncWriteExt;
+
+use crate::clock::{self, SharedClock};
+use crate::http::parser::{BufferPool, PooledBuf};
+use crate::http::{
+    Body, HttpVersion,
+    header::{
+        Connection, ConnectionOptions, ContentLength, FieldLinePolicy, FieldLines, HeaderField,
+        HeaderMap, HeaderValueTrait, TransferEncoding, TransferEncodingKind,
+    },
+    method::Method,
+    request::Request,
+    response::{Response, StatusCode},
+};
+
+/// How an outgoing message body is delimited
+/// SPEC: RFC 9112 - 6.3. Message Body Length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutgoingFraming {
+    /// The body length is known ahead of time, or there is no body
+    Length,
+    /// The body is sent using the chunked transfer coding
+    Chunked,
+    /// The body is delimited by closing the connection
+    Close,
+    /// The message never has a body, whatever its fields say, so none is written
+    None,
+}
+
+fn is_chunked(headers: &HeaderMap) -> bool {
+    headers
+        .get_header::<TransferEncoding>()
+        .ok()
+        .flatten()
+        .is_some_and(|codings| matches!(codings.last(), Some(TransferEncodingKind::Chunked)))
+}
+
+/// Sets the framing headers of a message, based on its body
+fn frame_message(headers: &mut HeaderMap, body: &Body, version: HttpVersion) -> OutgoingFraming {
+    match body {
+        Body::None | Body::Full(_) => {
+            let len = match body {
+                Body::Full(bytes) => bytes.len() as u64,
+                _ => 0,
+            };
+            // The content length must always be correct, and transfer codings don't apply
+            headers.remove(&TransferEncoding::NAME);
+            headers.remove(&ContentLength::NAME);
+            len.to_header_value(headers.entry(ContentLength::NAME));
+            OutgoingFraming::Length
+        }
+        // HTTP/1.0 recipients don't understand transfer codings, even ones set by the handler
+        // SPEC: RFC 9112 - 6.1. Transfer-Encoding
+        Body::Stream(_) if version < HttpVersion::HTTP_1_1 => {
+            headers.remove(&TransferEncoding::NAME);
+            if headers.contains(&ContentLength::NAME) {
+                OutgoingFraming::Length
+            } else {
+                // The only other way to delimit the body is to close the connection
+                headers.set_header::<Connection>(ConnectionOptions::close());
+                OutgoingFraming::Close
+            }
+        }
+        Body::Stream(_) => {
+            if is_chunked(headers) {
+                OutgoingFraming::Chunked
+            } else if headers.contains(&ContentLength::NAME) {
+                // The handler knows the length ahead of time
+                OutgoingFraming::Length
+            } else {
+                headers
+                    .entry(TransferEncoding::NAME)
+                    .push(Bytes::from_static(b"chunked"));
+                OutgoingFraming::Chunked
+            }
+        }
+    }
+}
+
+/// Sets the headers which determine how the response body is delimited
+/// A Content-Length is set for full bodies, streams are chunked on HTTP/1.1 and delimited by
+/// closing the connection on HTTP/1.0, in which case `Connection: close` is set
+/// `version` is the version of the request, which the client understands, rather than the
+/// version of the response
+/// Responses to HEAD, and 1xx, 204 and 304 responses never have a body, see
+/// [`BodyFraming::for_response`](crate::http::parser::BodyFraming::for_response)
+/// This is done by [`Sender::send_response`], and can be done ahead of time to inspect the final
+/// headers
+pub fn frame_response(response: &mut Response, method: &Method, version: HttpVersion) {
+    response_framing(response, method, version);
+}
+
+fn response_framing(
+    response: &mut Response,
+    method: &Method,
+    version: HttpVersion,
+) -> OutgoingFraming {
+    // SPEC: RFC 9110 - 8.6. Content-Length
+    // 1xx and 204 responses can't describe a body either
+    if response.status.is_informational() || response.status == StatusCode::NO_CONTENT {
+        response.headers.remove(&TransferEncoding::NAME);
+        response.headers.remove(&ContentLength::NAME);
+        return OutgoingFraming::None;
+    }
+    // SPEC: RFC 9110 - 15.4.5. 304 Not Modified
+    // The fields describe the representation a GET would have selected, as set by the handler
+    if response.status == StatusCode::NOT_MODIFIED {
+        return OutgoingFraming::None;
+    }
+    // SPEC: RFC 9110 - 9.3.2. HEAD
+    // The fields are the ones a GET would have been answered with, but the body is left out
+    if *method == Method::HEAD {
+        if let Body::Full(_) = response.body {
+            frame_message(&mut response.headers, &response.body, version);
+        }
+        return OutgoingFraming::None;
+    }
+    frame_message(&mut response.headers, &response.body, version)
+}
+
+/// Limits on how slowly a client can take a message, so a client which stops reading can't hold
+/// the connection and its buffers open
+#[derive(Debug, Clone)]
+pub struct WriteLimits {
+    /// The maximum time a write can wait without the client taking any bytes
+    pub write_timeout: Duration,
+    /// The minimum rate in bytes per second at which the client must take a message, on average
+    /// over the time spent writing it
+    /// Only checked once writing the message has taken longer than [`Self::write_timeout`], so
+    /// small messages and the start of large ones are not held to it
+    pub min_write_rate: Option<NonZeroU64>,
+    pub clock: SharedClock,
+}
+
+/// The writer of a [`Sender`], enforcing its [`WriteLimits`]
+struct LimitedWriter<WRITER> {
+    inner: WRITER,
+    limits: Option<WriteLimits>,
+    /// Bytes of the current message written so far
+    written: u64,
+    /// Time spent writing the current message, waiting for the body to be produced is not counted
+    writing: Duration,
+}
+
+impl<WRITER> LimitedWriter<WRITER>
+where
+    WRITER: AsyncWriteExt + Unpin,
+{
+    /// Starts measuring a new message
+    fn reset(&mut self) {
+        self.written = 0;
+        self.writing = Duration::ZERO;
+    }
+
+    async fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write_all_vectored(&mut [IoSlice::new(bytes)]).await
+    }
+
+    /// Writes all of `bufs` in as few writes as the writer allows, without copying them together
+    async fn write_all_vectored(&mut self, mut bufs: &mut [IoSlice<'_>]) -> io::Result<()> {
+        let limits = self.limits.clone();
+        // Skips any empty slices
+        IoSlice::advance_slices(&mut bufs, 0);
+        while !bufs.is_empty() {
+            let write = self.inner.write_vectored(bufs);
+            let n = match &limits {
+                None => write.await?,
+                Some(limits) => {
+                    let started = limits.clock.now();
+                    let n = clock::timeout(&*limits.clock, limits.write_timeout, write)
+                        .await
+                        .map_err(|_| timed_out())??;
+                    self.written += n as u64;
+                    self.writing += limits.clock.now() - started;
+                    n
+                }
+            };
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            IoSlice::advance_slices(&mut bufs, n);
+            if let Some(limits) = &limits {
+                self.check_rate(limits)?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        let Some(limits) = &self.limits else {
+            return self.inner.flush().await;
+        };
+        clock::timeout(&*limits.clock, limits.write_timeout, self.inner.flush())
+            .await
+            .map_err(|_| timed_out())?
+    }
+
+    async fn shutdown(&mut self) -> io::Result<()> {
+        let Some(limits) = &self.limits else {
+            return self.inner.shutdown().await;
+        };
+        clock::timeout(&*limits.clock, limits.write_timeout, self.inner.shutdown())
+            .await
+            .map_err(|_| timed_out())?
+    }
+
+    fn check_rate(&self, limits: &WriteLimits) -> io::Result<()> {
+        let Some(rate) = limits.min_write_rate else {
+            return Ok(());
+        };
+        if self.writing <= limits.write_timeout {
+            return Ok(());
+        }
+        if (self.written as f64) < rate.get() as f64 * self.writing.as_secs_f64() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the client is reading the response too slowly",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        "the client stopped reading the response",
+    )
+}
+
+/// Bodies up to this size are copied after the head and sent in a single write, larger ones are
+/// written together with the head without copying them
+const COPY_BODY_MAX: usize = 1024;
+
+pub struct Sender<WRITER: AsyncWriteExt + Unpin> {
+    writer: LimitedWriter<WRITER>,
+    buf: PooledBuf,
+    field_lines: FieldLinePolicy,
+}
+
+impl<WRITER> Sender<WRITER>
+where
+    WRITER: AsyncWriteExt + Unpin,
+{
+    pub fn new(writer: WRITER) -> Self {
+        Self::with_field_lines(writer, FieldLinePolicy::default())
+    }
+
+    pub fn with_field_lines(writer: WRITER, field_lines: FieldLinePolicy) -> Self {
+        Self {
+            writer: LimitedWriter {
+                inner: writer,
+                limits: None,
+                written: 0,
+                writing: Duration::ZERO,
+            },
+            // Allocated by the first message, unless a pooled buffer is given first
+            buf: PooledBuf::new(),
+            field_lines,
+        }
+    }
+
+    /// Takes the write buffer from `pool`, and gives it back once the sender is dropped
+    /// A buffer which grew past the maximum capacity of the pool is replaced after each message
+    pub fn with_buffer_pool(mut self, pool: BufferPool) -> Self {
+        self.buf.set_pool(pool);
+        self
+    }
+
+    /// Fails writes with [`io::ErrorKind::TimedOut`] when the client doesn't take the message
+    /// fast enough
+    pub fn with_write_limits(mut self, limits: WriteLimits) -> Self {
+        self.writer.limits = Some(limits);
+        self
+    }
+
+    async fn send_headers(&mut self, headers: HeaderMap) -> std::io::Result<()> {
+        for (name, value) in headers.iter() {
+            match self.field_lines.lines_for(name) {
+                FieldLines::Combine => {
+                    self.buf.extend_from_slice(name.as_bytes());
+                    self.buf.extend_from_slice(b": ");
+                    for (idx, line) in value.iter().enumerate() {
+                        if idx > 0 {
+                            self.buf.extend_from_slice(b", ");
+                        }
+                        self.buf.extend_from_slice(line);
+                    }
+                    self.buf.extend_from_slice(b"\r\n");
+                }
+                FieldLines::Repeat => {
+                    for line in value.iter() {
+                        self.buf.extend_from_slice(name.as_bytes());
+                        self.buf.extend_from_slice(b": ");
+                        self.buf.extend_from_slice(line);
+                        self.buf.extend_from_slice(b"\r\n");
+                    }
+                }
+            }
+        }
+        self.buf.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+
+    pub async fn send_request(&mut self, mut request: Request) -> std::io::Result<()> {
+        use std::fmt::Write;
+        self.writer.reset();
+        let framing = match request.body {
+            // Requests without a body don't need any framing
+            Body::None => OutgoingFraming::Length,
+            _ => frame_message(&mut request.headers, &request.body, request.version),
+        };
+        write!(
+            self,
+            "{} {} {}\r\n",
+            request.method,
+            std::str::from_utf8(&request.target).unwrap(),
+            request.version
+        )
+        .unwrap();
+        self.send_headers(request.headers).await?;
+        self.send_body(request.body, framing).await?;
+        self.buf.shrink();
+        Ok(())
+    }
+
+    /// Sends the response to a request sent with `method` and `version`, see [`frame_response`]
+    pub async fn send_response(
+        &mut self,
+        mut response: Response,
+        method: &Method,
+        version: HttpVersion,
+    ) -> std::io::Result<()> {
+        self.writer.reset();
+        let framing = response_framing(&mut response, method, version);
+        self.send_status_line(&response);
+        self.send_headers(response.headers).await?;
+        self.send_body(response.body, framing).await?;
+        self.buf.shrink();
+        Ok(())
+    }
+
+    /// Sends an informational (1xx) response, any number of which can precede the final
+    /// response of an exchange
+    /// SPEC: RFC 9110 - 15.2. Informational 1xx
+    pub async fn send_interim(&mut self, response: Response) -> std::io::Result<()> {
+        debug_assert!(response.status.is_informational());
+        self.writer.reset();
+        self.send_status_line(&response);
+        self.send_headers(response.headers).await?;
+        // The client may act on the interim response while the final response is produced
+        self.flush().await
+    }
+
+    fn send_status_line(&mut self, response: &Response) {
+        // ABNF: status-line = HTTP-version SP status-code SP [ reason-phrase ]
+        let HttpVersion { major, minor } = response.version;
+        if major < 10 && minor < 10 {
+            self.buf.extend_from_slice(&[
+                b'H',
+                b'T',
+                b'T',
+                b'P',
+                b'/',
+                b'0' + major,
+                b'.',
+                b'0' + minor,
+            ]);
+        } else {
+            use std::fmt::Write;
+            write!(self, "{}", response.version).unwrap();
+        }
+        // Status codes are always three digits
+        let status = response.status.as_u16();
+        self.buf.extend_from_slice(&[
+            b' ',
+            b'0' + (status / 100) as u8,
+            b'0' + (status / 10 % 10) as u8,
+            b'0' + (status % 10) as u8,
+            b' ',
+        ]);
+        self.buf.extend_from_slice(&response.message);
+        self.buf.extend_from_slice(b"\r\n");
+    }
+
+    async fn send_body(&mut self, body: Body, framing: OutgoingFraming) -> std::io::Result<()> {
+        match body {
+            Body::None => {}
+            _ if framing == OutgoingFraming::None => {}
+            Body::Full(bytes) if bytes.len() <= COPY_BODY_MAX => self.buf.extend_from_slice(&bytes),
+            Body::Full(bytes) => {
+                let mut bufs = [IoSlice::new(&self.buf), IoSlice::new(&bytes)];
+                self.writer.write_all_vectored(&mut bufs).await?;
+                self.buf.clear();
+            }
+            Body::Stream(stream) => {
+                // Send the head before waiting on the first chunk
+                self.flush().await?;
+                while let Some(chunk) = stream.next_chunk().await {
+                    let chunk = chunk.map_err(std::io::Error::other)?;
+                    if framing == OutgoingFraming::Chunked {
+                        // An empty chunk would end the body
+                        if chunk.is_empty() {
+                            continue;
+                        }
+                        // ABNF: chunk = chunk-size [ chunk-ext ] CRLF chunk-data CRLF
+                        use std::fmt::Write;
+                        write!(self, "{:X}\r\n", chunk.len()).unwrap();
+                        let mut bufs = [
+                            IoSlice::new(&self.buf),
+                            IoSlice::new(&chunk),
+                            IoSlice::new(b"\r\n"),
+                        ];
+                        self.writer.write_all_vectored(&mut bufs).await?;
+                        self.buf.clear();
+                    } else {
+                        self.writer.write_all(&chunk).await?;
+                    }
+                    // Streams are sent as they are produced, rather than when the writer fills
+                    self.writer.flush().await?;
+                }
+                if framing == OutgoingFraming::Chunked {
+                    // ABNF: last-chunk = 1*("0") [ chunk-ext ] CRLF, followed by an empty trailer
+                    // section and a CRLF
+                    self.buf.extend_from_slice(b"0\r\n\r\n");
+                }
+            }
+        }
+        self.flush().await
+    }
+
+    /// Sends anything still buffered, then closes the write side of the connection, so the
+    /// peer reads the end of the stream
+    pub async fn shutdown(&mut self) -> std::io::Result<()> {
+        self.flush().await?;
+        self.writer.shutdown().await
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.write_all(&self.buf).await?;
+        self.buf.clear();
+        self.writer.flush().await
+    }
+}
+
+impl<WRITER> fmt::Write for Sender<WRITER>
+where
+    WRITER: AsyncWriteExt + Unpin,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.buf.extend_from_slice(s.as_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    };
+
+    use bytes::Bytes;
+    use tokio::io::AsyncWrite;
+
+    use super::*;
+    use crate::clock::MockClock;
+    use crate::http::{
+        BodyStream, HttpDate,
+        cookie::SetCookie,
+        header::{HeaderName, Link},
+        response::ResponseBuilder,
+    };
+
+    async fn send(response: Response) -> String {
+        let mut out = Vec::new();
+        Sender::new(&mut out)
+            .send_response(response, &Method::GET, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn stream_response(version: HttpVersion) -> Response {
+        let (tx, stream) = BodyStream::channel(4);
+        tokio::spawn(async move {
+            tx.send(Bytes::from_static(b"hello ")).await.unwrap();
+            tx.send(Bytes::new()).await.unwrap();
+            tx.send(Bytes::from_static(b"world")).await.unwrap();
+        });
+        let mut res = ResponseBuilder::new(version, StatusCode::OK).build();
+        res.body = Body::Stream(stream);
+        res
+    }
+
+    fn large_response(len: usize) -> Response {
+        let mut res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+        res.body = Body::Full(Bytes::from(vec![b'a'; len]));
+        res
+    }
+
+    #[tokio::test]
+    async fn write_timeout() {
+        let clock = Arc::new(MockClock::new());
+        let limits = WriteLimits {
+            write_timeout: Duration::from_secs(5),
+            min_write_rate: None,
+            clock: clock.clone(),
+        };
+        // The client never reads, so the response fills the pipe and stalls
+        let (_client, stream) = tokio::io::duplex(1024);
+        let mut sender = Sender::new(stream).with_write_limits(limits);
+        let send = sender.send_response(large_response(4096), &Method::GET, HttpVersion::HTTP_1_1);
+        tokio::pin!(send);
+        tokio::select! {
+            biased;
+            _ = &mut send => panic!("the response can't be written"),
+            _ = async { clock.advance(Duration::from_secs(4)) } => {}
+        }
+        tokio::task::yield_now().await;
+        clock.advance(Duration::from_secs(1));
+        let err = send.await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    /// Takes 10 bytes for every 100ms of the clock, 100 bytes a second
+    struct SlowReader(Vec<u8>, Arc<MockClock>);
+
+    impl AsyncWrite for SlowReader {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.1.advance(Duration::from_millis(100));
+            let n = buf.len().min(10);
+            self.0.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn min_write_rate() {
+        let clock = Arc::new(MockClock::new());
+        let limits = |rate| WriteLimits {
+            write_timeout: Duration::from_secs(1),
+            min_write_rate: NonZeroU64::new(rate),
+            clock: clock.clone(),
+        };
+        let reader = || SlowReader(Vec::new(), clock.clone());
+        let mut sender = Sender::new(reader()).with_write_limits(limits(50));
+        sender
+            .send_response(large_response(500), &Method::GET, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+
+        let mut sender = Sender::new(reader()).with_write_limits(limits(200));
+        // Responses which take less than the timeout are not held to the rate
+        sender
+            .send_response(large_response(50), &Method::GET, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+        let err = sender
+            .send_response(large_response(500), &Method::GET, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        // The rate is checked once the timeout has passed
+        assert!(
+            sender.writer.inner.0.len() < 200,
+            "{}",
+            sender.writer.inner.0.len()
+        );
+    }
+
+    /// Records the bytes taken by each write, taking all of a vectored write at once
+    #[derive(Default)]
+    struct Writes(Vec<Vec<u8>>);
+
+    impl AsyncWrite for Writes {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.0.push(buf.to_vec());
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let write = bufs.iter().flat_map(|buf| buf.iter().copied()).collect();
+            self.0.push(write);
+            Poll::Ready(Ok(bufs.iter().map(|buf| buf.len()).sum()))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn single_write_per_message() {
+        let head = "HTTP/1.1 200 OK\r\nContent-Length: 4096\r\n\r\n";
+        let mut sender = Sender::new(Writes::default());
+        sender
+            .send_response(large_response(4096), &Method::GET, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+        sender
+            .send_response(large_response(10), &Method::GET, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+        let writes = &sender.writer.inner.0;
+        assert_eq!(writes.len(), 2);
+        assert_eq!(writes[0].len(), head.len() + 4096);
+        assert!(writes[0].starts_with(head.as_bytes()));
+        assert!(writes[1].ends_with(b"\r\n\r\naaaaaaaaaa"));
+
+        let mut sender = Sender::new(Writes::default());
+        sender
+            .send_response(
+                stream_response(HttpVersion::HTTP_1_1),
+                &Method::GET,
+                HttpVersion::HTTP_1_1,
+            )
+            .await
+            .unwrap();
+        let writes: Vec<_> = sender.writer.inner.0.iter().map(|w| &w[..]).collect();
+        assert_eq!(
+            writes,
+            [
+                &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"[..],
+                b"6\r\nhello \r\n",
+                b"5\r\nworld\r\n",
+                b"0\r\n\r\n",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn full_body_content_length() {
+        let mut res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+        res.body = Body::Full(Bytes::from_static(b"hello"));
+        assert_eq!(
+            send(res).await,
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    #[tokio::test]
+    async fn no_body() {
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+        assert_eq!(
+            send(res).await,
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"
+        );
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::NO_CONTENT).build();
+        assert_eq!(send(res).await, "HTTP/1.1 204 No Content\r\n\r\n");
+
+        // A body and framing fields set by the handler are left out
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::NO_CONTENT)
+            .body(Bytes::from_static(b"hello"))
+            .build();
+        assert_eq!(send(res).await, "HTTP/1.1 204 No Content\r\n\r\n");
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::NOT_MODIFIED)
+            .body(Bytes::from_static(b"hello"))
+            .build();
+        assert_eq!(
+            send(res).await,
+            "HTTP/1.1 304 Not Modified\r\nContent-Length: 5\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn head() {
+        let mut out = Vec::new();
+        let mut sender = Sender::new(&mut out);
+        let mut res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+        res.body = Body::Full(Bytes::from_static(b"hello"));
+        sender
+            .send_response(res, &Method::HEAD, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+        // A streamed body is never waited on
+        let (_tx, stream) = BodyStream::channel(1);
+        let mut res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+        res.body = Body::Stream(stream);
+        sender
+            .send_response(res, &Method::HEAD, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nHTTP/1.1 200 OK\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_chunked() {
+        let res = stream_response(HttpVersion::HTTP_1_1);
+        assert_eq!(
+            send(res).await,
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn early_hints() {
+        let mut out = Vec::new();
+        let mut sender = Sender::new(&mut out);
+        let hints = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::EARLY_HINTS)
+            .set_header::<Link>(vec![Bytes::from_static(b"</style.css>; rel=preload")])
+            .build();
+        sender.send_interim(hints).await.unwrap();
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+        sender
+            .send_response(res, &Method::GET, HttpVersion::HTTP_1_1)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 103 Early Hints\r\nLink: </style.css>; rel=preload\r\n\r\n\
+             HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn repeated_field_lines() {
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::NO_CONTENT)
+            .set_cookie(SetCookie::new("a", "1").expires(HttpDate::from(std::time::UNIX_EPOCH)))
+            .set_cookie(SetCookie::new("b", "2"))
+            .build();
+        assert_eq!(
+            send(res).await,
+            "HTTP/1.1 204 No Content\r\n\
+             Set-Cookie: a=1; Expires=Thu, 01 Jan 1970 00:00:00 GMT\r\n\
+             Set-Cookie: b=2\r\n\r\n"
+        );
+
+        let warning = HeaderName::try_from(&Bytes::from_static(b"X-Warning")).unwrap();
+        let mut res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::NO_CONTENT).build();
+        let value = res.headers.entry(warning.clone());
+        value.push(Bytes::from_static(b"a"));
+        value.push(Bytes::from_static(b"b"));
+        let mut out = Vec::new();
+        Sender::with_field_lines(
+            &mut out,
+            FieldLinePolicy::new(FieldLines::Combine).with(warning, FieldLines::Repeat),
+        )
+        .send_response(res.clone(), &Method::GET, HttpVersion::HTTP_1_1)
+        .await
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 204 No Content\r\nX-Warning: a\r\nX-Warning: b\r\n\r\n"
+        );
+        assert_eq!(
+            send(res).await,
+            "HTTP/1.1 204 No Content\r\nX-Warning: a, b\r\n\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_http_1_0() {
+        let mut res = stream_response(HttpVersion::HTTP_1_1);
+        frame_response(&mut res, &Method::GET, HttpVersion::HTTP_1_0);
+        assert_eq!(
+            res.headers.get_header::<Connection>().unwrap(),
+            Some(ConnectionOptions::close())
+        );
+
+        // The response keeps its version, and chunked set by the handler isn't used either
+        let mut res = stream_response(HttpVersion::HTTP_1_1);
+        res.headers
+            .entry(TransferEncoding::NAME)
+            .push(Bytes::from_static(b"chunked"));
+        let mut out = Vec::new();
+        Sender::new(&mut out)
+            .send_response(res, &Method::GET, HttpVersion::HTTP_1_0)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "HTTP/1.1 200 OK\r\nConnection: Close\r\n\r\nhello world"
+        );
+    }
+}
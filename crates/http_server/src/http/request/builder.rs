@@ -0,0 +1,197 @@
+use std::{any::Any, net::SocketAddr};
+
+use bytes::Bytes;
+
+use crate::http::{
+    Body, Extensions, HttpVersion,
+    header::{ContentLength, HeaderField, HeaderMap, HeaderName, InvalidHeader},
+    method::Method,
+    parser::is_tchar,
+    request::Request,
+};
+#[cfg(feature = "json")]
+use crate::http::{header::ContentType, request::JsonError};
+
+/// Builds a request, for sending with a [`crate::client::Client`] or for testing routers
+pub struct RequestBuilder {
+    method: Method,
+    target: Bytes,
+    version: HttpVersion,
+    headers: HeaderMap,
+    body: Body,
+    remote: Option<SocketAddr>,
+    extensions: Extensions,
+}
+
+impl RequestBuilder {
+    /// An HTTP/1.1 request for `target`, in origin-form (`/path?query`), absolute-form, or
+    /// any other form
+    /// Panics if the target is not printable ASCII
+    pub fn new(method: Method, target: impl Into<Bytes>) -> Self {
+        let target = target.into();
+        assert!(
+            !target.is_empty() && target.iter().all(u8::is_ascii_graphic),
+            "invalid request target"
+        );
+        Self {
+            method,
+            target,
+            version: HttpVersion::HTTP_1_1,
+            headers: HeaderMap::new(),
+            body: Body::None,
+            remote: None,
+            extensions: Extensions::new(),
+        }
+    }
+
+    pub fn get(target: impl Into<Bytes>) -> Self {
+        Self::new(Method::GET, target)
+    }
+
+    pub fn post(target: impl Into<Bytes>) -> Self {
+        Self::new(Method::POST, target)
+    }
+
+    pub fn version(mut self, version: HttpVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Replaces the field `NAME` with `val`, see [`Self::add_header`] to add a value instead
+    pub fn set_header<NAME>(mut self, val: NAME::Output) -> Self
+    where
+        NAME: HeaderField,
+    {
+        self.headers.set_header::<NAME>(val);
+        self
+    }
+
+    /// Adds a header
+    /// Panics if the name is not a token, or the value contains CR, LF or NUL, see
+    /// [`Self::try_add_header`]
+    pub fn add_header(self, name: &Bytes, val: Bytes) -> Self {
+        self.try_add_header(name, val).expect("invalid header")
+    }
+
+    pub fn try_add_header(mut self, name: &Bytes, val: Bytes) -> Result<Self, InvalidHeader> {
+        // SPEC: RFC 9110 - 5.1. Field Names
+        if name.is_empty() || !name.iter().copied().all(is_tchar) {
+            return Err(InvalidHeader::Name);
+        }
+        let name = HeaderName::try_from(name).map_err(|_| InvalidHeader::Name)?;
+        self.headers.entry(name).try_push(val)?;
+        Ok(self)
+    }
+
+    pub fn body(mut self, bytes: Bytes) -> Self {
+        let len = bytes.len() as u64;
+        self.body = Body::Full(bytes);
+        self.set_header::<ContentLength>(len)
+    }
+
+    /// Sets the body to `value` serialized as JSON, with its Content-Type and Content-Length
+    #[cfg(feature = "json")]
+    pub fn json<T>(self, value: &T) -> Result<Self, JsonError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        let body = serde_json::to_vec(value).map_err(JsonError::Serialize)?;
+        Ok(self
+            .set_header::<ContentType>(Bytes::from_static(b"application/json"))
+            .body(Bytes::from(body)))
+    }
+
+    /// The address of the peer, as the server would set it
+    pub fn remote(mut self, remote: SocketAddr) -> Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Inserts `value` into the extensions, as the server or a middleware would
+    pub fn extension<T: Any + Send + Sync>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    pub fn build(self) -> Request {
+        let RequestBuilder {
+            method,
+            target,
+            version,
+            headers,
+            body,
+            remote,
+            extensions,
+        } = self;
+        Request {
+            method,
+            target,
+            version,
+            headers,
+            body,
+            remote,
+            extensions,
+            interim: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        header::{Accept, ContentType},
+        request::{RequestTarget, Scheme},
+    };
+
+    #[test]
+    fn build() {
+        let req = RequestBuilder::post("/items?page=2")
+            .add_header(
+                &Bytes::from_static(b"Host"),
+                Bytes::from_static(b"example.com"),
+            )
+            .add_header(&Bytes::from_static(b"X-Trace"), Bytes::from_static(b"1"))
+            .body(Bytes::from_static(b"data"))
+            .extension(Scheme::Https)
+            .build();
+        assert_eq!(req.method, Method::POST);
+        assert!(matches!(req.target(), Ok(RequestTarget::Origin(_))));
+        assert_eq!(req.version, HttpVersion::HTTP_1_1);
+        assert_eq!(req.headers.get(&ContentLength::NAME).unwrap()[0], "4");
+        assert_eq!(req.headers.keys().count(), 3);
+        assert_eq!(req.scheme(), Scheme::Https);
+        assert!(matches!(req.body, Body::Full(ref body) if body == "data"));
+    }
+
+    #[test]
+    fn set_header_replaces() {
+        // As with ResponseBuilder, set_header replaces the field and add_header adds a value
+        let req = RequestBuilder::post("/")
+            .set_header::<ContentType>(Bytes::from_static(b"text/plain"))
+            .body(Bytes::from_static(b"a"))
+            .set_header::<ContentType>(Bytes::from_static(b"application/json"))
+            .body(Bytes::from_static(b"{}"))
+            .build();
+        assert_eq!(req.headers.get(&ContentType::NAME).unwrap().len(), 1);
+        assert_eq!(
+            req.headers.get(&ContentType::NAME).unwrap()[0],
+            "application/json"
+        );
+        assert_eq!(req.headers.get(&ContentLength::NAME).unwrap().len(), 1);
+        assert_eq!(req.headers.get(&ContentLength::NAME).unwrap()[0], "2");
+
+        let name = Bytes::from_static(b"Accept");
+        let req = RequestBuilder::get("/")
+            .add_header(&name, Bytes::from_static(b"text/html"))
+            .add_header(&name, Bytes::from_static(b"*/*"))
+            .build();
+        assert_eq!(req.headers.get(&Accept::NAME).unwrap().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid request target")]
+    fn rejects_spaces() {
+        RequestBuilder::get("/a b");
+    }
+}
@@ -0,0 +1,181 @@
+use serde::de::DeserializeOwned;
+
+use crate::{
+    RouterError,
+    http::{
+        BodyError,
+        header::{ContentType, MediaType},
+        request::Request,
+        response::StatusCode,
+    },
+};
+
+/// An error reading or writing a JSON body
+#[derive(Debug, thiserror::Error)]
+pub enum JsonError {
+    /// The request body is not declared as JSON
+    #[error("expected a JSON content type")]
+    ContentType,
+    #[error(transparent)]
+    Body(#[from] BodyError),
+    /// The request body is not valid JSON, or does not match the expected type
+    #[error("invalid JSON body: {0}")]
+    Deserialize(serde_json::Error),
+    /// The value could not be written as JSON, such as a map with non-string keys
+    #[error("failed to serialize JSON: {0}")]
+    Serialize(serde_json::Error),
+}
+
+impl JsonError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::ContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::Body(err) => err.status_code(),
+            // Well formed JSON of the wrong shape is understood, but can't be processed
+            // SPEC: RFC 9110 - 15.5.21. 422 Unprocessable Content
+            Self::Deserialize(err) if err.is_data() => StatusCode::UNPROCESSABLE_CONTENT,
+            Self::Deserialize(_) => StatusCode::BAD_REQUEST,
+            Self::Serialize(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<JsonError> for RouterError {
+    fn from(err: JsonError) -> Self {
+        match err {
+            JsonError::Body(err) => Self::Body(err),
+            JsonError::Serialize(err) => Self::Generic(Box::new(err)),
+            err => Self::Custom(err.status_code(), err.to_string()),
+        }
+    }
+}
+
+/// Whether the media type is JSON, including structured syntax suffixes such as
+/// `application/problem+json`
+/// SPEC: RFC 6839 - 3.1. The +json Structured Syntax Suffix
+fn is_json(media: &MediaType) -> bool {
+    media.ty() == b"application"
+        && (media.subtype() == b"json" || media.subtype().ends_with(b"+json"))
+}
+
+impl Request {
+    /// The largest body [`Self::json`] reads
+    pub const DEFAULT_JSON_LIMIT: usize = 1024 * 1024;
+
+    /// Reads the body as JSON, up to [`Self::DEFAULT_JSON_LIMIT`] bytes
+    /// The Content-Type has to be `application/json`, or another JSON media type
+    pub async fn json<T: DeserializeOwned>(&self) -> Result<T, JsonError> {
+        self.json_with_limit(Self::DEFAULT_JSON_LIMIT).await
+    }
+
+    /// Reads the body as JSON, failing once it is larger than `limit`
+    pub async fn json_with_limit<T: DeserializeOwned>(&self, limit: usize) -> Result<T, JsonError> {
+        let content_type = self.headers.get_header::<ContentType>().ok().flatten();
+        if !content_type
+            .and_then(|value| MediaType::parse(&value))
+            .is_some_and(|media| is_json(&media))
+        {
+            return Err(JsonError::ContentType);
+        }
+        let body = self.body.collect(Some(limit)).await?;
+        serde_json::from_slice(&body).map_err(JsonError::Deserialize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::http::{
+        Body, HttpVersion,
+        header::{Builtin, HeaderField, HeaderName},
+        request::RequestBuilder,
+        response::ResponseBuilder,
+    };
+
+    type Item = BTreeMap<String, u32>;
+
+    fn request(content_type: Option<&'static str>, body: &'static str) -> Request {
+        let mut builder = RequestBuilder::post("/").body(Bytes::from_static(body.as_bytes()));
+        if let Some(content_type) = content_type {
+            builder =
+                builder.set_header::<ContentType>(Bytes::from_static(content_type.as_bytes()));
+        }
+        builder.build()
+    }
+
+    #[tokio::test]
+    async fn read_json() {
+        let body = r#"{"count":2}"#;
+        let item = Item::from([("count".to_owned(), 2)]);
+        let req = request(Some("application/json; charset=utf-8"), body);
+        assert_eq!(req.json::<Item>().await.unwrap(), item);
+        let req = request(Some("application/merge-patch+json"), body);
+        assert_eq!(req.json::<Item>().await.unwrap(), item);
+
+        let status = async |req: Request, limit| {
+            let err = req.json_with_limit::<Item>(limit).await.unwrap_err();
+            RouterError::from(err).status_code()
+        };
+        let cases = [
+            (
+                request(None, body),
+                1024,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                request(Some("text/plain"), body),
+                1024,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                request(Some("application/json"), body),
+                8,
+                StatusCode::CONTENT_TOO_LARGE,
+            ),
+            (
+                request(Some("application/json"), "{"),
+                1024,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                request(Some("application/json"), r#"{"count":"2"}"#),
+                1024,
+                StatusCode::UNPROCESSABLE_CONTENT,
+            ),
+        ];
+        for (req, limit, expected) in cases {
+            assert_eq!(status(req, limit).await, expected);
+        }
+    }
+
+    #[test]
+    fn write_json() {
+        let item = Item::from([("count".to_owned(), 2)]);
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK)
+            .json(&item)
+            .unwrap()
+            .build();
+        assert_eq!(
+            res.headers.get(&ContentType::NAME).unwrap()[0],
+            "application/json"
+        );
+        assert_eq!(
+            res.headers
+                .get(&HeaderName::builtin(Builtin::ContentLength))
+                .unwrap()[0],
+            "11"
+        );
+        assert!(matches!(res.body, Body::Full(ref body) if body == r#"{"count":2}"#));
+
+        let map = std::collections::HashMap::from([((1, 2), 3)]);
+        let err = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK)
+            .json(&map)
+            .err()
+            .unwrap();
+        assert!(matches!(err, JsonError::Serialize(_)));
+    }
+}
@@ -29,7 +29,7 @@ impl RequestTarget {
         match self {
             Self::Asterisk => "*",
             Self::Origin(origin) => origin.as_str(),
-            _ => unimplemented!(),
+            Self::Absolute(s) | Self::Authority(s) => s,
         }
     }
 }
@@ -47,11 +47,11 @@ pub struct OriginForm {
 }
 
 impl OriginForm {
-    pub fn from_bytes(bytes: &Bytes) -> Result<Self, InvalidAsciiError> {
+    pub fn from_bytes(bytes: &Bytes) -> Result<Self, RequestTargetParseError> {
         // Check to make sure it is valid ascii
         _ = AsciiStr::from_ascii(bytes)?;
-        if *bytes.get(0).unwrap() != b'/' {
-            todo!()
+        if bytes.first() != Some(&b'/') {
+            return Err(RequestTargetParseError);
         }
         // SAFETY: We checked that byte position 0 is a slash, so it can never be a question mark
         let query = bytes
@@ -76,7 +76,7 @@ impl OriginForm {
     pub fn query(&self) -> Result<Option<String>, UrlDecodeError> {
         // FIXME: Untested
         match self.query {
-            Some(query) => Ok(Some(url_decode(&self.data[query.get()..]).unwrap())),
+            Some(query) => Ok(Some(url_decode(&self.data[query.get()..])?)),
             None => Ok(None),
         }
     }
@@ -98,29 +98,48 @@ pub struct AbsoluteForm {}
 /// ABNF: authority-form = uri-host ":" port
 pub struct AuthorityForm {}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid request target")]
 pub struct RequestTargetParseError;
 
+impl From<InvalidAsciiError> for RequestTargetParseError {
+    fn from(_: InvalidAsciiError) -> Self {
+        Self
+    }
+}
+
 impl TryFrom<&Bytes> for RequestTarget {
     type Error = RequestTargetParseError;
 
     fn try_from(s: &Bytes) -> Result<Self, Self::Error> {
-        if let Some(fc) = s.get(0).copied() {
-            match fc {
-                b'*' => {
-                    if s.len() > 1 {
-                        todo!("handle error")
-                    }
-                    return Ok(Self::Asterisk);
+        match s.first().copied() {
+            Some(b'*') if s.len() == 1 => Ok(Self::Asterisk),
+            Some(b'/') => Ok(Self::Origin(OriginForm::from_bytes(s)?)),
+            // Otherwise it is either authority-form, which is a host and port without a scheme,
+            // or an absolute-URI
+            // ABNF: absolute-URI = scheme ":" hier-part [ "?" query ]
+            // ABNF: scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+            Some(_) => {
+                let s = AsciiStr::from_ascii(s)?.as_str();
+                if let Some((host, port)) = s.rsplit_once(':')
+                    && !host.is_empty()
+                    && !host.contains('/')
+                    && !port.is_empty()
+                    && port.bytes().all(|b| b.is_ascii_digit())
+                {
+                    return Ok(Self::Authority(s.to_owned()));
                 }
-                b'/' => return Ok(Self::Origin(OriginForm::from_bytes(s).unwrap())),
-                _ => {
-                    // so it can either be absolute path or authority-form
-                    todo!()
+                let (scheme, _) = s.split_once(':').ok_or(RequestTargetParseError)?;
+                let mut scheme = scheme.bytes();
+                if !scheme.next().is_some_and(|b| b.is_ascii_alphabetic())
+                    || !scheme.all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'-' | b'.'))
+                {
+                    return Err(RequestTargetParseError);
                 }
+                Ok(Self::Absolute(s.to_owned()))
             }
+            None => Err(RequestTargetParseError),
         }
-        todo!("error, cannot be empty")
     }
 }
 
@@ -129,7 +148,7 @@ impl std::fmt::Display for RequestTarget {
         match self {
             Self::Origin(s) => f.write_str(s.as_str()),
             Self::Asterisk => f.write_str("*"),
-            _ => unimplemented!(),
+            Self::Absolute(s) | Self::Authority(s) => f.write_str(s),
         }
     }
 }
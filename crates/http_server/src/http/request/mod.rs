@@ -1,10 +1,43 @@
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
+mod builder;
+#[cfg(feature = "json")]
+mod json;
 mod line;
+pub use builder::RequestBuilder;
 use bytes::Bytes;
+#[cfg(feature = "json")]
+pub use json::JsonError;
 pub use line::*;
 
-use crate::http::{header::HeaderMap, method::Method, Body, HttpVersion};
+use crate::{
+    connection::ConnectionInfo,
+    http::{
+        Body, Extensions, HttpVersion,
+        header::{Accept, HeaderField, HeaderMap, Link, MediaType, negotiate},
+        method::Method,
+        response::{InterimError, InterimSender, Response, ResponseBuilder, StatusCode},
+    },
+    proxy::ForwardedClient,
+};
+
+/// The scheme of the connection a request was received on, inserted into the request
+/// extensions by the server
+/// SPEC: RFC 9110 - 4.2. HTTP-Related URI Schemes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Http,
+    Https,
+}
+
+impl Scheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Https => "https",
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Request {
@@ -14,10 +47,74 @@ pub struct Request {
     pub headers: HeaderMap,
     pub body: Body,
     pub remote: Option<SocketAddr>,
+    pub extensions: Extensions,
+    /// Set by the server when the client supports interim responses
+    pub(crate) interim: Option<InterimSender>,
 }
 
 impl Request {
+    /// An HTTP/1.1 request for `target`, without fields or a body, see [`RequestBuilder`]
+    /// Panics if the target is not printable ASCII
+    pub fn new(method: Method, target: impl Into<Bytes>) -> Self {
+        RequestBuilder::new(method, target).build()
+    }
+
     pub fn target(&self) -> Result<RequestTarget, RequestTargetParseError> {
         RequestTarget::try_from(&self.target)
     }
+
+    /// The scheme the client used, as forwarded by a trusted proxy, or otherwise the scheme of
+    /// the connection, `http` when the request was not received by the server
+    pub fn scheme(&self) -> Scheme {
+        self.extensions
+            .get::<ForwardedClient>()
+            .and_then(|client| client.scheme)
+            .or_else(|| self.extensions.get::<Scheme>().copied())
+            .unwrap_or(Scheme::Http)
+    }
+
+    /// The address of the client, as forwarded by a trusted proxy, or otherwise the address of
+    /// the peer
+    /// See [`crate::proxy::TrustedProxies`]
+    pub fn client_addr(&self) -> Option<IpAddr> {
+        match self.extensions.get::<ForwardedClient>() {
+            Some(client) => Some(client.addr),
+            None => self.remote.map(|remote| remote.ip()),
+        }
+    }
+
+    /// The connection the request was received on, None when the request was not received by
+    /// the server
+    pub fn connection(&self) -> Option<&ConnectionInfo> {
+        self.extensions.get::<ConnectionInfo>()
+    }
+
+    /// Picks the media type in `available` the client prefers by its Accept field
+    /// See [`negotiate`]
+    pub fn negotiate(&self, available: &[MediaType]) -> Option<MediaType> {
+        let accept = self.headers.get_header::<Accept>().ok().flatten();
+        negotiate(&accept.unwrap_or_default(), available)
+    }
+
+    /// Sends an informational (1xx) response before the final response
+    pub async fn send_interim(&self, response: Response) -> Result<(), InterimError> {
+        match &self.interim {
+            Some(interim) => interim.send(response).await,
+            None => Err(InterimError::Unsupported),
+        }
+    }
+
+    /// Sends a 103 Early Hints response, with a Link field for each of `links`
+    /// SPEC: RFC 8297 - 2. HTTP Status Code 103: Early Hints
+    pub async fn send_early_hints<I>(&self, links: I) -> Result<(), InterimError>
+    where
+        I: IntoIterator<Item = Bytes>,
+    {
+        let mut response = ResponseBuilder::from_req(self, StatusCode::EARLY_HINTS).build();
+        let value = response.headers.entry(Link::NAME);
+        for link in links {
+            value.push(link);
+        }
+        self.send_interim(response).await
+    }
 }
@@ -2,7 +2,13 @@ use bytes::Bytes;
 
 use crate::http::{
     Body, HttpVersion,
-    header::{ContentLength, HeaderField, HeaderMap, HeaderName, HeaderValueTrait},
+    cookie::SetCookie,
+    header::{
+        self, Connection, ConnectionOptions, ContentDispositionValue, ContentLength, ContentType,
+        HeaderField, HeaderMap, HeaderName, HeaderValueTrait, InvalidHeader, Location, Protocol,
+        Upgrade,
+    },
+    parser::is_tchar,
     request::Request,
     response::{Response, StatusCode},
 };
@@ -30,6 +36,64 @@ impl ResponseBuilder {
         }
     }
 
+    /// A 200 OK response with a `text/plain` body
+    pub fn text(body: impl Into<Bytes>) -> Self {
+        Self::new(HttpVersion::HTTP_1_1, StatusCode::OK)
+            .set_header::<ContentType>(Bytes::from_static(b"text/plain; charset=utf-8"))
+            .body(body.into())
+    }
+
+    /// A 200 OK response with a `text/html` body
+    pub fn html(body: impl Into<Bytes>) -> Self {
+        Self::new(HttpVersion::HTTP_1_1, StatusCode::OK)
+            .set_header::<ContentType>(Bytes::from_static(b"text/html; charset=utf-8"))
+            .body(body.into())
+    }
+
+    /// A redirect to `location`, which may be relative to the request target
+    /// Panics if the status is not a redirection, or the location contains CR, LF or NUL
+    /// SPEC: RFC 9110 - 10.2.2. Location
+    pub fn redirect(status: StatusCode, location: impl Into<Bytes>) -> Self {
+        assert!(status.is_redirection(), "{status:?} is not a redirection");
+        let mut builder = Self::new(HttpVersion::HTTP_1_1, status);
+        builder
+            .headers
+            .entry(Location::NAME)
+            .try_push(location.into())
+            .expect("invalid location");
+        builder.body(Bytes::new())
+    }
+
+    /// A 101 Switching Protocols response, switching the connection to `protocol`
+    /// SPEC: RFC 9110 - 15.2.2. 101 Switching Protocols
+    /// The server must send an Upgrade field naming the protocol it switches to
+    pub fn switching_protocols(protocol: Protocol) -> Self {
+        let options = ConnectionOptions {
+            upgrade: true,
+            ..ConnectionOptions::default()
+        };
+        Self::new(HttpVersion::HTTP_1_1, StatusCode::SWITCHING_PROTOCOLS)
+            .set_header::<Connection>(options)
+            .set_header::<Upgrade>(vec![protocol])
+    }
+
+    /// Tells the client to download the body, saving it as `filename`
+    /// SPEC: RFC 6266 - 4. Header Field Definition
+    pub fn attachment(self, filename: impl Into<String>) -> Self {
+        self.set_header::<header::ContentDisposition>(ContentDispositionValue::attachment(filename))
+    }
+
+    /// A 204 No Content response, which never has a body
+    pub fn no_content() -> Self {
+        Self::new(HttpVersion::HTTP_1_1, StatusCode::NO_CONTENT)
+    }
+
+    /// Replaces the status, keeping the headers and body
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
     pub fn build(self) -> Response {
         let ResponseBuilder {
             version,
@@ -59,18 +123,35 @@ impl ResponseBuilder {
         }
     }
 
+    /// Replaces the field `NAME` with `val`, see [`Self::add_header`] to add a value instead
     pub fn set_header<NAME>(mut self, val: NAME::Output) -> Self
     where
         NAME: HeaderField,
     {
-        val.to_header_value(self.headers.entry(NAME::NAME));
+        self.headers.set_header::<NAME>(val);
         self
     }
 
-    pub fn add_header(mut self, name: &Bytes, val: Bytes) -> Self {
-        self.headers
-            .entry(HeaderName::try_from(name).expect("header is not valid ascii"))
-            .push(val);
+    /// Adds a header
+    /// Panics if the name is not a token, or the value contains CR, LF or NUL, see
+    /// [`Self::try_add_header`]
+    pub fn add_header(self, name: &Bytes, val: Bytes) -> Self {
+        self.try_add_header(name, val).expect("invalid header")
+    }
+
+    pub fn try_add_header(mut self, name: &Bytes, val: Bytes) -> Result<Self, InvalidHeader> {
+        // SPEC: RFC 9110 - 5.1. Field Names
+        if name.is_empty() || !name.iter().copied().all(is_tchar) {
+            return Err(InvalidHeader::Name);
+        }
+        let name = HeaderName::try_from(name).map_err(|_| InvalidHeader::Name)?;
+        self.headers.entry(name).try_push(val)?;
+        Ok(self)
+    }
+
+    /// Adds a Set-Cookie field line, repeated calls add more cookies
+    pub fn set_cookie(mut self, cookie: SetCookie) -> Self {
+        vec![cookie].to_header_value(self.headers.entry(header::SetCookie::NAME));
         self
     }
 
@@ -80,5 +161,115 @@ impl ResponseBuilder {
         self.set_header::<ContentLength>(len)
     }
 
+    /// Sets the body to `value` serialized as JSON, with its Content-Type and Content-Length
+    #[cfg(feature = "json")]
+    pub fn json<T>(self, value: &T) -> Result<Self, crate::http::request::JsonError>
+    where
+        T: serde::Serialize + ?Sized,
+    {
+        use crate::http::request::JsonError;
+        let body = serde_json::to_vec(value).map_err(JsonError::Serialize)?;
+        Ok(self
+            .set_header::<ContentType>(Bytes::from_static(b"application/json"))
+            .body(Bytes::from(body)))
+    }
+
+    /// Sets a newline delimited JSON body, and its Content-Type
+    #[cfg(feature = "serde")]
+    pub fn ndjson<S>(mut self, items: crate::http::NdJsonStream<S>) -> Self
+    where
+        S: futures_core::Stream + Send + 'static,
+        S::Item: serde::Serialize,
+    {
+        use crate::http::NdJsonStream;
+        self.body = items.into_body();
+        self.headers.remove(&ContentLength::NAME);
+        self.set_header::<ContentType>(Bytes::from_static(
+            NdJsonStream::<S>::CONTENT_TYPE.as_bytes(),
+        ))
+    }
+
     // pub fn body_ext(mut self)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(res: &Response, name: HeaderName) -> Option<Bytes> {
+        res.headers.get(&name).map(|value| value.collect())
+    }
+
+    #[test]
+    fn attachment() {
+        let res = ResponseBuilder::text("a,b").attachment("data.csv").build();
+        assert_eq!(
+            header(&res, header::ContentDisposition::NAME).unwrap(),
+            "attachment; filename=\"data.csv\""
+        );
+    }
+
+    #[test]
+    fn convenience_constructors() {
+        let res = ResponseBuilder::text("missing")
+            .status(StatusCode::NOT_FOUND)
+            .build();
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+        assert_eq!(res.message, "Not Found");
+        assert_eq!(
+            header(&res, ContentType::NAME).unwrap(),
+            "text/plain; charset=utf-8"
+        );
+        assert_eq!(header(&res, ContentLength::NAME).unwrap(), "7");
+
+        let res = ResponseBuilder::html("<p></p>").build();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(
+            header(&res, ContentType::NAME).unwrap(),
+            "text/html; charset=utf-8"
+        );
+
+        let res = ResponseBuilder::redirect(StatusCode::SEE_OTHER, "/items/1").build();
+        assert_eq!(header(&res, Location::NAME).unwrap(), "/items/1");
+        assert_eq!(header(&res, ContentLength::NAME).unwrap(), "0");
+
+        let res = ResponseBuilder::no_content().build();
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        assert!(res.headers.is_empty());
+        assert!(matches!(res.body, Body::None));
+    }
+
+    #[test]
+    fn overrides_helpers() {
+        // The body and its fields replace the ones each helper set
+        let helpers = [
+            ResponseBuilder::text("a"),
+            ResponseBuilder::html("a"),
+            ResponseBuilder::redirect(StatusCode::FOUND, "/a"),
+        ];
+        for builder in helpers {
+            let res = builder
+                .body(Bytes::from_static(b"<a/>"))
+                .set_header::<ContentType>(Bytes::from_static(b"application/xml"))
+                .build();
+            assert!(matches!(res.body, Body::Full(ref body) if body == "<a/>"));
+            assert_eq!(res.headers.get(&ContentLength::NAME).unwrap().len(), 1);
+            assert_eq!(header(&res, ContentLength::NAME).unwrap(), "4");
+            assert_eq!(res.headers.get(&ContentType::NAME).unwrap().len(), 1);
+            assert_eq!(header(&res, ContentType::NAME).unwrap(), "application/xml");
+        }
+
+        // Cookies are still added, rather than replaced
+        let res = ResponseBuilder::no_content()
+            .set_cookie(SetCookie::new("a", "1"))
+            .set_cookie(SetCookie::new("b", "2"))
+            .build();
+        assert_eq!(res.headers.get(&header::SetCookie::NAME).unwrap().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid location")]
+    fn redirect_rejects_line_breaks() {
+        ResponseBuilder::redirect(StatusCode::FOUND, "/\r\nSet-Cookie: a=b");
+    }
+}
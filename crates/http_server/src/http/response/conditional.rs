@@ -0,0 +1,130 @@
+use crate::http::{
+    HttpDate,
+    header::{ETag, EntityTag, EntityTagMatch, IfModifiedSince, IfNoneMatch, LastModified},
+    method::Method,
+    request::Request,
+    response::{Response, ResponseBuilder, StatusCode},
+};
+
+/// Evaluates the If-None-Match and If-Modified-Since preconditions of `request` against the
+/// validators of the selected representation
+/// Returns the response to send instead of the representation, 304 Not Modified for GET and
+/// HEAD, and 412 Precondition Failed for other methods, or `None` when it should be sent
+/// A representation without an entity tag is only matched by `If-None-Match: *`
+/// SPEC: RFC 9110 - 13.2.2. Precedence of Preconditions
+pub fn conditional(
+    request: &Request,
+    etag: Option<&EntityTag>,
+    last_modified: Option<HttpDate>,
+) -> Option<Response> {
+    let safe = request.method == Method::GET || request.method == Method::HEAD;
+    // SPEC: RFC 9110 - 13.1.2. If-None-Match
+    // A malformed field is ignored, as if it was not sent
+    let unmodified = match request.headers.get_header::<IfNoneMatch>().ok().flatten() {
+        Some(tags) => match etag {
+            Some(etag) => tags.matches_weak(etag),
+            None => tags == EntityTagMatch::Any,
+        },
+        // SPEC: RFC 9110 - 13.1.3. If-Modified-Since
+        // Only evaluated without If-None-Match, and only for GET and HEAD
+        None if safe => match (
+            request
+                .headers
+                .get_header::<IfModifiedSince>()
+                .ok()
+                .flatten(),
+            last_modified,
+        ) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        },
+        None => false,
+    };
+    if !unmodified {
+        return None;
+    }
+    if !safe {
+        return Some(ResponseBuilder::from_req(request, StatusCode::PRECONDITION_FAILED).build());
+    }
+    // SPEC: RFC 9110 - 15.4.5. 304 Not Modified
+    // The validators are sent again, so the client can update its stored response
+    let mut builder = ResponseBuilder::from_req(request, StatusCode::NOT_MODIFIED);
+    if let Some(etag) = etag {
+        builder = builder.set_header::<ETag>(etag.clone());
+    }
+    if let Some(modified) = last_modified {
+        builder = builder.set_header::<LastModified>(modified);
+    }
+    Some(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::http::header::{HeaderField, HeaderName};
+
+    fn request(method: Method, field: HeaderName, value: &'static str) -> Request {
+        let mut request = Request::new(method, "/");
+        request
+            .headers
+            .entry(field)
+            .push(Bytes::from_static(value.as_bytes()));
+        request
+    }
+
+    #[test]
+    fn if_none_match() {
+        let etag = EntityTag::strong("abc");
+        let req = request(Method::GET, IfNoneMatch::NAME, "\"xyz\", W/\"abc\"");
+        let res = conditional(&req, Some(&etag), None).unwrap();
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers.get(&ETag::NAME).unwrap()[0], "\"abc\"");
+
+        let req = request(Method::GET, IfNoneMatch::NAME, "\"xyz\"");
+        assert!(conditional(&req, Some(&etag), None).is_none());
+        let req = request(Method::PUT, IfNoneMatch::NAME, "*");
+        let res = conditional(&req, Some(&etag), None).unwrap();
+        assert_eq!(res.status, StatusCode::PRECONDITION_FAILED);
+        // A representation without a tag is still matched by `*`, but by no listed tag
+        assert!(conditional(&req, None, None).is_some());
+        let req = request(Method::GET, IfNoneMatch::NAME, "\"abc\"");
+        assert!(conditional(&req, None, None).is_none());
+    }
+
+    #[test]
+    fn if_modified_since() {
+        let modified = HttpDate::from(UNIX_EPOCH + Duration::from_secs(784_111_777));
+        let req = request(
+            Method::GET,
+            IfModifiedSince::NAME,
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+        );
+        let res = conditional(&req, None, Some(modified)).unwrap();
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+        assert_eq!(
+            res.headers.get(&LastModified::NAME).unwrap()[0],
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        let later = HttpDate::from(UNIX_EPOCH + Duration::from_secs(784_111_778));
+        assert!(conditional(&req, None, Some(later)).is_none());
+
+        // If-None-Match takes precedence, even when the date would match
+        let mut req = req;
+        req.headers
+            .entry(IfNoneMatch::NAME)
+            .push(Bytes::from_static(b"\"xyz\""));
+        let etag = EntityTag::strong("abc");
+        assert!(conditional(&req, Some(&etag), Some(modified)).is_none());
+
+        let req = request(
+            Method::POST,
+            IfModifiedSince::NAME,
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+        );
+        assert!(conditional(&req, None, Some(modified)).is_none());
+    }
+}
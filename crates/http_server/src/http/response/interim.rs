@@ -0,0 +1,72 @@
+use tokio::sync::mpsc;
+
+use crate::http::response::{Response, StatusCode};
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InterimError {
+    /// Only 1xx responses can be sent before the final response, and 101 Switching Protocols
+    /// changes the connection, so it can only be sent as the final response
+    #[error("{0} is not an interim status")]
+    NotInterim(StatusCode),
+    /// The client does not support interim responses
+    /// SPEC: RFC 9110 - 15.2. Informational 1xx
+    #[error("interim responses are not supported by the client")]
+    Unsupported,
+    /// The final response has already been sent, or the connection is closed
+    #[error("the exchange is closed")]
+    Closed,
+}
+
+/// Sends informational (1xx) responses ahead of the final response of an exchange
+/// Interim responses are written in the order they are sent, before the final response
+#[derive(Debug, Clone)]
+pub(crate) struct InterimSender {
+    tx: mpsc::Sender<Response>,
+}
+
+impl InterimSender {
+    pub const CAPACITY: usize = 4;
+
+    pub fn channel() -> (InterimSender, mpsc::Receiver<Response>) {
+        let (tx, rx) = mpsc::channel(Self::CAPACITY);
+        (InterimSender { tx }, rx)
+    }
+
+    pub async fn send(&self, response: Response) -> Result<(), InterimError> {
+        if !response.status.is_informational() || response.status == StatusCode::SWITCHING_PROTOCOLS
+        {
+            return Err(InterimError::NotInterim(response.status));
+        }
+        self.tx
+            .send(response)
+            .await
+            .map_err(|_| InterimError::Closed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{HttpVersion, response::ResponseBuilder};
+
+    #[tokio::test]
+    async fn interim_status() {
+        let (tx, mut rx) = InterimSender::channel();
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+        assert_eq!(
+            tx.send(res).await,
+            Err(InterimError::NotInterim(StatusCode::OK))
+        );
+        let res =
+            ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::SWITCHING_PROTOCOLS).build();
+        assert!(tx.send(res).await.is_err());
+
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::EARLY_HINTS).build();
+        tx.send(res).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap().status, StatusCode::EARLY_HINTS);
+
+        rx.close();
+        let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::CONTINUE).build();
+        assert_eq!(tx.send(res).await, Err(InterimError::Closed));
+    }
+}
@@ -1,36 +1,18 @@
-use std::fmt::Display;
-
 use bytes::Bytes;
 mod builder;
+mod conditional;
+mod interim;
+mod representations;
+mod status;
 pub use builder::ResponseBuilder;
+pub use conditional::conditional;
+pub use interim::InterimError;
+pub(crate) use interim::InterimSender;
+pub use representations::Representations;
+pub use status::{InvalidStatusCode, StatusCode};
 
 use crate::http::{Body, HttpVersion, header::HeaderMap};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct StatusCode(u16);
-
-impl StatusCode {
-    pub const OK: Self = Self(200);
-    pub const BAD_REQUEST: Self = Self(400);
-    pub const NOT_FOUND: Self = Self(404);
-    pub const INTERNAL_SERVER_ERROR: Self = Self(500);
-
-    pub const fn canonical_reason(&self) -> Option<&'static str> {
-        Some(match self.0 {
-            200 => "OK",
-            404 => "Not Found",
-            500 => "Internal Server Error",
-            _ => return None,
-        })
-    }
-}
-
-impl Display for StatusCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(&self.0, f)
-    }
-}
-
 #[derive(Debug, Clone)]
 pub struct Response {
     pub version: HttpVersion,
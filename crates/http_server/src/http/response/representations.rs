@@ -0,0 +1,122 @@
+use bytes::Bytes;
+
+use crate::{
+    RouterError,
+    http::{
+        Body,
+        header::{Accept, ContentType, HeaderField, MediaType},
+        request::Request,
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+};
+
+/// The representations of one resource in different media types, the one sent is picked by
+/// the Accept field of the request
+/// SPEC: RFC 9110 - 12.1. Proactive Negotiation
+#[derive(Debug, Clone, Default)]
+pub struct Representations {
+    variants: Vec<(MediaType, Bytes)>,
+}
+
+impl Representations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a representation, when the client weights several equally the first added is sent
+    pub fn with(mut self, media: MediaType, body: impl Into<Bytes>) -> Self {
+        self.variants.push((media, body.into()));
+        self
+    }
+
+    /// Adds an `application/json` representation
+    pub fn json(self, body: impl Into<Bytes>) -> Self {
+        self.with(MediaType::json(), body)
+    }
+
+    /// Adds a `text/html` representation
+    pub fn html(self, body: impl Into<Bytes>) -> Self {
+        self.with(MediaType::html(), body)
+    }
+
+    /// Adds a `text/plain` representation
+    pub fn plain_text(self, body: impl Into<Bytes>) -> Self {
+        self.with(MediaType::plain_text(), body)
+    }
+
+    /// Responds with the representation the client prefers, with `Vary: Accept` since the
+    /// response depends on it
+    /// Clients which accept none of the representations get 406 Not Acceptable
+    pub fn respond(self, request: &Request, status: StatusCode) -> Result<Response, RouterError> {
+        let available: Vec<_> = self
+            .variants
+            .iter()
+            .map(|(media, _)| media.clone())
+            .collect();
+        let Some(media) = request.negotiate(&available) else {
+            return Err(RouterError::Custom(
+                StatusCode::NOT_ACCEPTABLE,
+                "no acceptable representation".to_owned(),
+            ));
+        };
+        let (media, body) = self
+            .variants
+            .into_iter()
+            .find(|(variant, _)| *variant == media)
+            .expect("negotiated one of the variants");
+        let mut res = ResponseBuilder::from_req(request, status)
+            .set_header::<ContentType>(media.to_bytes())
+            .build();
+        res.headers.add_vary(&Accept::NAME);
+        res.body = Body::Full(body);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{header::Vary, method::Method};
+
+    fn request(accept: Option<&'static str>) -> Request {
+        let mut request = Request::new(Method::GET, "/");
+        if let Some(accept) = accept {
+            request
+                .headers
+                .entry(Accept::NAME)
+                .push(Bytes::from_static(accept.as_bytes()));
+        }
+        request
+    }
+
+    #[test]
+    fn picks_representation() {
+        let representations = Representations::new()
+            .json("{}")
+            .html("<p></p>")
+            .plain_text("text");
+        let respond = |accept| {
+            representations
+                .clone()
+                .respond(&request(accept), StatusCode::OK)
+        };
+
+        let res = respond(Some("text/html, application/json;q=0.9")).unwrap();
+        assert_eq!(
+            res.headers.get(&ContentType::NAME).unwrap()[0],
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(res.headers.get(&Vary::NAME).unwrap()[0], "Accept");
+        assert!(matches!(res.body, Body::Full(ref body) if body == "<p></p>"));
+
+        let res = respond(None).unwrap();
+        assert_eq!(
+            res.headers.get(&ContentType::NAME).unwrap()[0],
+            "application/json"
+        );
+        let res = respond(Some("text/plain")).unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "text"));
+        let err = respond(Some("image/*")).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::NOT_ACCEPTABLE);
+    }
+}
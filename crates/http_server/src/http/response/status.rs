@@ -0,0 +1,262 @@
+use std::fmt::{self, Display};
+
+/// HTTP Status Code
+/// SPEC: RFC 9110 - 15. Status Codes
+/// ABNF: status-code = 3DIGIT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StatusCode(u16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidStatusCode;
+
+impl Display for InvalidStatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid status code")
+    }
+}
+
+impl std::error::Error for InvalidStatusCode {}
+
+macro_rules! status_codes {
+    ($($(#[$doc: meta])* ($code: literal, $name: ident, $reason: literal);)+) => {
+        impl StatusCode {
+            $(
+                $(#[$doc])*
+                pub const $name: Self = Self($code);
+            )+
+
+            /// Returns the canonical reason phrase registered with IANA, if the code is registered
+            pub const fn canonical_reason(&self) -> Option<&'static str> {
+                Some(match self.0 {
+                    $($code => $reason,)+
+                    _ => return None,
+                })
+            }
+        }
+    };
+}
+
+// SPEC: https://www.iana.org/assignments/http-status-codes/http-status-codes.xhtml
+status_codes! {
+    /// SPEC: RFC 9110 - 15.2.1. 100 Continue
+    (100, CONTINUE, "Continue");
+    /// SPEC: RFC 9110 - 15.2.2. 101 Switching Protocols
+    (101, SWITCHING_PROTOCOLS, "Switching Protocols");
+    /// SPEC: RFC 2518 - 10.1. 102 Processing
+    (102, PROCESSING, "Processing");
+    /// SPEC: RFC 8297 - 2. 103 Early Hints
+    (103, EARLY_HINTS, "Early Hints");
+
+    /// SPEC: RFC 9110 - 15.3.1. 200 OK
+    (200, OK, "OK");
+    /// SPEC: RFC 9110 - 15.3.2. 201 Created
+    (201, CREATED, "Created");
+    /// SPEC: RFC 9110 - 15.3.3. 202 Accepted
+    (202, ACCEPTED, "Accepted");
+    /// SPEC: RFC 9110 - 15.3.4. 203 Non-Authoritative Information
+    (203, NON_AUTHORITATIVE_INFORMATION, "Non-Authoritative Information");
+    /// SPEC: RFC 9110 - 15.3.5. 204 No Content
+    (204, NO_CONTENT, "No Content");
+    /// SPEC: RFC 9110 - 15.3.6. 205 Reset Content
+    (205, RESET_CONTENT, "Reset Content");
+    /// SPEC: RFC 9110 - 15.3.7. 206 Partial Content
+    (206, PARTIAL_CONTENT, "Partial Content");
+    /// SPEC: RFC 4918 - 11.1. 207 Multi-Status
+    (207, MULTI_STATUS, "Multi-Status");
+    /// SPEC: RFC 5842 - 7.1. 208 Already Reported
+    (208, ALREADY_REPORTED, "Already Reported");
+    /// SPEC: RFC 3229 - 10.4.1. 226 IM Used
+    (226, IM_USED, "IM Used");
+
+    /// SPEC: RFC 9110 - 15.4.1. 300 Multiple Choices
+    (300, MULTIPLE_CHOICES, "Multiple Choices");
+    /// SPEC: RFC 9110 - 15.4.2. 301 Moved Permanently
+    (301, MOVED_PERMANENTLY, "Moved Permanently");
+    /// SPEC: RFC 9110 - 15.4.3. 302 Found
+    (302, FOUND, "Found");
+    /// SPEC: RFC 9110 - 15.4.4. 303 See Other
+    (303, SEE_OTHER, "See Other");
+    /// SPEC: RFC 9110 - 15.4.5. 304 Not Modified
+    (304, NOT_MODIFIED, "Not Modified");
+    /// SPEC: RFC 9110 - 15.4.6. 305 Use Proxy (deprecated)
+    (305, USE_PROXY, "Use Proxy");
+    /// SPEC: RFC 9110 - 15.4.8. 307 Temporary Redirect
+    (307, TEMPORARY_REDIRECT, "Temporary Redirect");
+    /// SPEC: RFC 9110 - 15.4.9. 308 Permanent Redirect
+    (308, PERMANENT_REDIRECT, "Permanent Redirect");
+
+    /// SPEC: RFC 9110 - 15.5.1. 400 Bad Request
+    (400, BAD_REQUEST, "Bad Request");
+    /// SPEC: RFC 9110 - 15.5.2. 401 Unauthorized
+    (401, UNAUTHORIZED, "Unauthorized");
+    /// SPEC: RFC 9110 - 15.5.3. 402 Payment Required
+    (402, PAYMENT_REQUIRED, "Payment Required");
+    /// SPEC: RFC 9110 - 15.5.4. 403 Forbidden
+    (403, FORBIDDEN, "Forbidden");
+    /// SPEC: RFC 9110 - 15.5.5. 404 Not Found
+    (404, NOT_FOUND, "Not Found");
+    /// SPEC: RFC 9110 - 15.5.6. 405 Method Not Allowed
+    (405, METHOD_NOT_ALLOWED, "Method Not Allowed");
+    /// SPEC: RFC 9110 - 15.5.7. 406 Not Acceptable
+    (406, NOT_ACCEPTABLE, "Not Acceptable");
+    /// SPEC: RFC 9110 - 15.5.8. 407 Proxy Authentication Required
+    (407, PROXY_AUTHENTICATION_REQUIRED, "Proxy Authentication Required");
+    /// SPEC: RFC 9110 - 15.5.9. 408 Request Timeout
+    (408, REQUEST_TIMEOUT, "Request Timeout");
+    /// SPEC: RFC 9110 - 15.5.10. 409 Conflict
+    (409, CONFLICT, "Conflict");
+    /// SPEC: RFC 9110 - 15.5.11. 410 Gone
+    (410, GONE, "Gone");
+    /// SPEC: RFC 9110 - 15.5.12. 411 Length Required
+    (411, LENGTH_REQUIRED, "Length Required");
+    /// SPEC: RFC 9110 - 15.5.13. 412 Precondition Failed
+    (412, PRECONDITION_FAILED, "Precondition Failed");
+    /// SPEC: RFC 9110 - 15.5.14. 413 Content Too Large
+    (413, CONTENT_TOO_LARGE, "Content Too Large");
+    /// SPEC: RFC 9110 - 15.5.15. 414 URI Too Long
+    (414, URI_TOO_LONG, "URI Too Long");
+    /// SPEC: RFC 9110 - 15.5.16. 415 Unsupported Media Type
+    (415, UNSUPPORTED_MEDIA_TYPE, "Unsupported Media Type");
+    /// SPEC: RFC 9110 - 15.5.17. 416 Range Not Satisfiable
+    (416, RANGE_NOT_SATISFIABLE, "Range Not Satisfiable");
+    /// SPEC: RFC 9110 - 15.5.18. 417 Expectation Failed
+    (417, EXPECTATION_FAILED, "Expectation Failed");
+    /// SPEC: RFC 9110 - 15.5.20. 421 Misdirected Request
+    (421, MISDIRECTED_REQUEST, "Misdirected Request");
+    /// SPEC: RFC 9110 - 15.5.21. 422 Unprocessable Content
+    (422, UNPROCESSABLE_CONTENT, "Unprocessable Content");
+    /// SPEC: RFC 4918 - 11.3. 423 Locked
+    (423, LOCKED, "Locked");
+    /// SPEC: RFC 4918 - 11.4. 424 Failed Dependency
+    (424, FAILED_DEPENDENCY, "Failed Dependency");
+    /// SPEC: RFC 8470 - 5.2. 425 Too Early
+    (425, TOO_EARLY, "Too Early");
+    /// SPEC: RFC 9110 - 15.5.22. 426 Upgrade Required
+    (426, UPGRADE_REQUIRED, "Upgrade Required");
+    /// SPEC: RFC 6585 - 3. 428 Precondition Required
+    (428, PRECONDITION_REQUIRED, "Precondition Required");
+    /// SPEC: RFC 6585 - 4. 429 Too Many Requests
+    (429, TOO_MANY_REQUESTS, "Too Many Requests");
+    /// SPEC: RFC 6585 - 5. 431 Request Header Fields Too Large
+    (431, REQUEST_HEADER_FIELDS_TOO_LARGE, "Request Header Fields Too Large");
+    /// SPEC: RFC 7725 - 3. 451 Unavailable For Legal Reasons
+    (451, UNAVAILABLE_FOR_LEGAL_REASONS, "Unavailable For Legal Reasons");
+
+    /// SPEC: RFC 9110 - 15.6.1. 500 Internal Server Error
+    (500, INTERNAL_SERVER_ERROR, "Internal Server Error");
+    /// SPEC: RFC 9110 - 15.6.2. 501 Not Implemented
+    (501, NOT_IMPLEMENTED, "Not Implemented");
+    /// SPEC: RFC 9110 - 15.6.3. 502 Bad Gateway
+    (502, BAD_GATEWAY, "Bad Gateway");
+    /// SPEC: RFC 9110 - 15.6.4. 503 Service Unavailable
+    (503, SERVICE_UNAVAILABLE, "Service Unavailable");
+    /// SPEC: RFC 9110 - 15.6.5. 504 Gateway Timeout
+    (504, GATEWAY_TIMEOUT, "Gateway Timeout");
+    /// SPEC: RFC 9110 - 15.6.6. 505 HTTP Version Not Supported
+    (505, HTTP_VERSION_NOT_SUPPORTED, "HTTP Version Not Supported");
+    /// SPEC: RFC 2295 - 8.1. 506 Variant Also Negotiates
+    (506, VARIANT_ALSO_NEGOTIATES, "Variant Also Negotiates");
+    /// SPEC: RFC 4918 - 11.5. 507 Insufficient Storage
+    (507, INSUFFICIENT_STORAGE, "Insufficient Storage");
+    /// SPEC: RFC 5842 - 7.2. 508 Loop Detected
+    (508, LOOP_DETECTED, "Loop Detected");
+    /// SPEC: RFC 2774 - 7. 510 Not Extended (obsoleted)
+    (510, NOT_EXTENDED, "Not Extended");
+    /// SPEC: RFC 6585 - 6. 511 Network Authentication Required
+    (511, NETWORK_AUTHENTICATION_REQUIRED, "Network Authentication Required");
+}
+
+impl StatusCode {
+    /// Creates a status code from a u16, the code must be in the range 100..=599
+    /// Unregistered codes in that range are allowed, as recipients must understand the class of
+    /// any status code
+    /// SPEC: RFC 9110 - 15. Status Codes
+    pub const fn from_u16(code: u16) -> Result<Self, InvalidStatusCode> {
+        if code < 100 || code > 599 {
+            return Err(InvalidStatusCode);
+        }
+        Ok(Self(code))
+    }
+
+    pub const fn as_u16(&self) -> u16 {
+        self.0
+    }
+
+    /// 1xx: The request was received, continuing process
+    pub const fn is_informational(&self) -> bool {
+        self.0 >= 100 && self.0 < 200
+    }
+
+    /// 2xx: The request was successfully received, understood, and accepted
+    pub const fn is_success(&self) -> bool {
+        self.0 >= 200 && self.0 < 300
+    }
+
+    /// 3xx: Further action needs to be taken in order to complete the request
+    pub const fn is_redirection(&self) -> bool {
+        self.0 >= 300 && self.0 < 400
+    }
+
+    /// 4xx: The request contains bad syntax or cannot be fulfilled
+    pub const fn is_client_error(&self) -> bool {
+        self.0 >= 400 && self.0 < 500
+    }
+
+    /// 5xx: The server failed to fulfill an apparently valid request
+    pub const fn is_server_error(&self) -> bool {
+        self.0 >= 500 && self.0 < 600
+    }
+}
+
+impl TryFrom<u16> for StatusCode {
+    type Error = InvalidStatusCode;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        Self::from_u16(code)
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> u16 {
+        status.0
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_u16_range() {
+        assert_eq!(StatusCode::from_u16(99), Err(InvalidStatusCode));
+        assert_eq!(StatusCode::from_u16(600), Err(InvalidStatusCode));
+        assert_eq!(StatusCode::from_u16(100), Ok(StatusCode::CONTINUE));
+        assert_eq!(StatusCode::from_u16(599).map(|s| s.as_u16()), Ok(599));
+    }
+
+    #[test]
+    fn canonical_reason() {
+        assert_eq!(StatusCode::OK.canonical_reason(), Some("OK"));
+        assert_eq!(
+            StatusCode::CONTENT_TOO_LARGE.canonical_reason(),
+            Some("Content Too Large")
+        );
+        assert_eq!(StatusCode::from_u16(299).unwrap().canonical_reason(), None);
+    }
+
+    #[test]
+    fn classification() {
+        assert!(StatusCode::EARLY_HINTS.is_informational());
+        assert!(StatusCode::NO_CONTENT.is_success());
+        assert!(StatusCode::PERMANENT_REDIRECT.is_redirection());
+        assert!(StatusCode::NOT_FOUND.is_client_error());
+        assert!(StatusCode::from_u16(599).unwrap().is_server_error());
+        assert!(!StatusCode::OK.is_server_error());
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+
+use bytes::{Bytes, BytesMut};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncWriteExt, ReadBuf},
+};
+
+use crate::{
+    RouterError,
+    http::{Body, BodyError},
+};
+
+/// How a body is collected by [`Body::collect_spilled`], bodies larger than the threshold are
+/// written to a temporary file rather than held in memory
+#[derive(Debug, Clone)]
+pub struct SpillConfig {
+    threshold: usize,
+    dir: PathBuf,
+    limit: Option<u64>,
+}
+
+impl SpillConfig {
+    /// Bodies up to `threshold` bytes are kept in memory
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            dir: std::env::temp_dir(),
+            limit: None,
+        }
+    }
+
+    /// The directory temporary files are created in, defaults to the system temporary directory
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Fails with [`BodyError::LimitExceeded`] once the body is larger than `limit`
+    pub fn with_limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// An error while collecting a body with [`Body::collect_spilled`]
+#[derive(Debug, thiserror::Error)]
+pub enum SpillError {
+    #[error(transparent)]
+    Body(#[from] BodyError),
+    #[error("failed to spill body to disk: {0}")]
+    Io(#[from] io::Error),
+}
+
+impl From<SpillError> for RouterError {
+    fn from(err: SpillError) -> Self {
+        match err {
+            SpillError::Body(err) => Self::Body(err),
+            SpillError::Io(err) => Self::Generic(Box::new(err)),
+        }
+    }
+}
+
+/// A body collected by [`Body::collect_spilled`]
+#[derive(Debug)]
+pub enum CollectedBody {
+    Memory(Bytes),
+    File(SpilledFile),
+}
+
+impl CollectedBody {
+    pub fn len(&self) -> u64 {
+        match self {
+            Self::Memory(bytes) => bytes.len() as u64,
+            Self::File(file) => file.len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the body from the start, each reader is independent
+    pub async fn reader(&self) -> io::Result<CollectedReader> {
+        Ok(match self {
+            Self::Memory(bytes) => CollectedReader::Memory(bytes.clone()),
+            Self::File(file) => CollectedReader::File(File::open(&file.path).await?),
+        })
+    }
+}
+
+/// A temporary file holding a body, the file is removed when this is dropped
+#[derive(Debug)]
+pub struct SpilledFile {
+    path: PathBuf,
+    len: u64,
+}
+
+impl SpilledFile {
+    /// Creates a new file in `dir`, the name is unique within this process, and an existing
+    /// file is never reused
+    async fn create(dir: &Path) -> io::Result<(Self, File)> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        loop {
+            let id = NEXT.fetch_add(1, Ordering::Relaxed);
+            let path = dir.join(format!("carbon-body-{}-{}", std::process::id(), id));
+            match File::options()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .await
+            {
+                Ok(file) => return Ok((Self { path, len: 0 }, file)),
+                // Left behind by an earlier process with the same id
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpilledFile {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log::warn!("failed to remove {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+/// Reads a [`CollectedBody`]
+pub enum CollectedReader {
+    Memory(Bytes),
+    File(File),
+}
+
+impl AsyncRead for CollectedReader {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Memory(bytes) => {
+                let len = bytes.len().min(buf.remaining());
+                buf.put_slice(&bytes.split_to(len));
+                Poll::Ready(Ok(()))
+            }
+            Self::File(file) => Pin::new(file).poll_read(cx, buf),
+        }
+    }
+}
+
+impl Body {
+    /// Reads the entire body, into memory if it is no larger than the threshold of `config`,
+    /// and otherwise into a temporary file
+    pub async fn collect_spilled(&self, config: &SpillConfig) -> Result<CollectedBody, SpillError> {
+        let stream = match self {
+            Self::None => return Ok(CollectedBody::Memory(Bytes::new())),
+            Self::Full(bytes) => {
+                if let Some(limit) = config.limit
+                    && bytes.len() as u64 > limit
+                {
+                    return Err(BodyError::LimitExceeded {
+                        limit: usize::try_from(limit).unwrap_or(usize::MAX),
+                    }
+                    .into());
+                }
+                return Ok(CollectedBody::Memory(bytes.clone()));
+            }
+            Self::Stream(stream) => stream,
+        };
+
+        let mut buf = BytesMut::new();
+        let mut spilled: Option<(SpilledFile, File)> = None;
+        while let Some(chunk) = stream.next_chunk().await {
+            let chunk = chunk?;
+            let len = match &spilled {
+                Some((spilled, _)) => spilled.len,
+                None => buf.len() as u64,
+            } + chunk.len() as u64;
+            if let Some(limit) = config.limit
+                && len > limit
+            {
+                return Err(BodyError::LimitExceeded {
+                    limit: usize::try_from(limit).unwrap_or(usize::MAX),
+                }
+                .into());
+            }
+            match &mut spilled {
+                Some((spilled, file)) => {
+                    file.write_all(&chunk).await?;
+                    spilled.len = len;
+                }
+                None if len > config.threshold as u64 => {
+                    let (mut created, mut file) = SpilledFile::create(&config.dir).await?;
+                    file.write_all(&buf).await?;
+                    file.write_all(&chunk).await?;
+                    created.len = len;
+                    buf = BytesMut::new();
+                    spilled = Some((created, file));
+                }
+                None => buf.extend_from_slice(&chunk),
+            }
+        }
+        Ok(match spilled {
+            Some((spilled, mut file)) => {
+                file.flush().await?;
+                CollectedBody::File(spilled)
+            }
+            None => CollectedBody::Memory(buf.freeze()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::http::BodyStream;
+
+    fn stream(chunks: &'static [&'static [u8]]) -> Body {
+        let (tx, stream) = BodyStream::channel(4);
+        tokio::spawn(async move {
+            for chunk in chunks {
+                tx.send(Bytes::from_static(chunk)).await.unwrap();
+            }
+        });
+        Body::Stream(stream)
+    }
+
+    async fn read(body: &CollectedBody) -> String {
+        let mut out = String::new();
+        body.reader()
+            .await
+            .unwrap()
+            .read_to_string(&mut out)
+            .await
+            .unwrap();
+        out
+    }
+
+    #[tokio::test]
+    async fn small_body_in_memory() {
+        let body = stream(&[b"hello ", b"world"])
+            .collect_spilled(&SpillConfig::new(16))
+            .await
+            .unwrap();
+        assert!(matches!(body, CollectedBody::Memory(_)));
+        assert_eq!(read(&body).await, "hello world");
+    }
+
+    #[tokio::test]
+    async fn large_body_spilled() {
+        let body = stream(&[b"hello ", b"world", b"!"])
+            .collect_spilled(&SpillConfig::new(8))
+            .await
+            .unwrap();
+        let CollectedBody::File(file) = &body else {
+            panic!("expected the body to be spilled");
+        };
+        let path = file.path().to_owned();
+        assert!(path.exists());
+        assert_eq!(body.len(), 12);
+        assert_eq!(read(&body).await, "hello world!");
+        // Every reader starts from the beginning
+        assert_eq!(read(&body).await, "hello world!");
+
+        drop(body);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn limit() {
+        let config = SpillConfig::new(4).with_limit(8);
+        let err = stream(&[b"hello ", b"world"])
+            .collect_spilled(&config)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SpillError::Body(BodyError::LimitExceeded { limit: 8 })
+        ));
+    }
+}
@@ -1,10 +1,10 @@
 use std::{
+    fmt,
     net::{AddrParseError, Ipv4Addr, Ipv6Addr},
-    str::{FromStr, Utf8Error},
+    str::FromStr,
     string::FromUtf8Error,
 };
 
-use bytes::Bytes;
 use uhsapi::ascii::{AsAsciiStr, AsciiString, InvalidAsciiError};
 
 #[derive(Debug, Clone, thiserror::Error)]
@@ -13,6 +13,8 @@ pub enum MalformedUriError {
     InvalidAddress(#[from] AddrParseError),
     #[error(transparent)]
     InvalidAscii(#[from] InvalidAsciiError),
+    #[error("invalid character in host name")]
+    InvalidRegName,
 }
 
 #[derive(Debug, Clone)]
@@ -31,7 +33,6 @@ impl IpLiteral {
             // IpvFuture, for now just hardcode
             // TODO: Actually implement
             Self::IpvFuture(IpvFuture {
-                version: 0,
                 content: s.as_ascii_str()?.to_ascii_string(),
             })
         } else {
@@ -40,12 +41,27 @@ impl IpLiteral {
     }
 }
 
+impl fmt::Display for IpLiteral {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ipv6(addr) => write!(f, "[{addr}]"),
+            Self::IpvFuture(future) => write!(f, "[{}]", future.content),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct IpvFuture {
-    version: u32,
     content: AsciiString,
 }
 
+impl IpvFuture {
+    /// The literal between the brackets, such as `v1.fe80::a+en1`
+    pub fn as_str(&self) -> &str {
+        self.content.as_str()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum UriHost {
     IpLiteral(IpLiteral),
@@ -63,13 +79,53 @@ impl FromStr for UriHost {
 
         // This is a bit of hack, not sure if this is spec compliant
         if let Ok(ipv4) = Ipv4Addr::from_str(s) {
-            return Ok(Self::Ipv4(ipv4));
+            Ok(Self::Ipv4(ipv4))
         } else {
-            return Ok(Self::RegName(s.as_ascii_str()?.to_ascii_string()));
+            let name = s.as_ascii_str()?;
+            if !is_reg_name(s.as_bytes()) {
+                return Err(MalformedUriError::InvalidRegName);
+            }
+            Ok(Self::RegName(name.to_ascii_string()))
         }
     }
 }
 
+impl fmt::Display for UriHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IpLiteral(literal) => fmt::Display::fmt(literal, f),
+            Self::Ipv4(addr) => fmt::Display::fmt(addr, f),
+            Self::RegName(name) => f.write_str(name.as_str()),
+        }
+    }
+}
+
+/// SPEC: RFC 3986 - 3.2.2. Host
+/// ABNF:
+///     reg-name    = *( unreserved / pct-encoded / sub-delims )
+///     pct-encoded = "%" HEXDIG HEXDIG
+///     sub-delims  = "!" / "$" / "&" / "'" / "(" / ")" / "*" / "+" / "," / ";" / "="
+fn is_reg_name(bytes: &[u8]) -> bool {
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3);
+                if !hex.is_some_and(|hex| hex.iter().all(u8::is_ascii_hexdigit)) {
+                    return false;
+                }
+                i += 3;
+                continue;
+            }
+            b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' => {}
+            b if is_unreserved(b) => {}
+            _ => return false,
+        }
+        i += 1;
+    }
+    true
+}
+
 pub type UriPort = u16;
 
 const HEX_CHARS_UPPER: &[u8] = b"0123456789ABCDEF";
@@ -133,6 +189,30 @@ pub fn url_decode(input: &[u8]) -> Result<String, UrlDecodeError> {
     Ok(String::from_utf8(decoded)?)
 }
 
+/// Decodes `application/x-www-form-urlencoded` name value pairs, as sent in query strings and
+/// HTML form bodies, where `+` is a space
+/// SPEC: WHATWG URL - 5.1. application/x-www-form-urlencoded parsing
+pub fn form_urlencoded_decode(input: &[u8]) -> Result<Vec<(String, String)>, UrlDecodeError> {
+    let decode = |bytes: &[u8]| {
+        let spaced: Vec<u8> = bytes
+            .iter()
+            .map(|b| if *b == b'+' { b' ' } else { *b })
+            .collect();
+        url_decode(&spaced)
+    };
+    input
+        .split(|b| *b == b'&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = match pair.iter().position(|b| *b == b'=') {
+                Some(eq) => (&pair[..eq], &pair[eq + 1..]),
+                None => (pair, &b""[..]),
+            };
+            Ok((decode(name)?, decode(value)?))
+        })
+        .collect()
+}
+
 fn parse_hex_byte(hex_slice: &[u8]) -> Result<u8, UrlDecodeError> {
     if hex_slice.len() != 2 {
         return Err(UrlDecodeError::MalformedEncoding);
@@ -167,7 +247,10 @@ mod tests {
     #[test]
     fn test_uri_host_valid_ipvfuture() {
         let host: UriHost = "[v5.123]".parse().unwrap();
-        assert!(matches!(host, UriHost::IpLiteral(IpLiteral::IpvFuture(_))))
+        let UriHost::IpLiteral(IpLiteral::IpvFuture(future)) = host else {
+            panic!("expected an IPvFuture literal");
+        };
+        assert_eq!(future.as_str(), "v5.123");
     }
 
     #[test]
@@ -176,6 +259,23 @@ mod tests {
         assert!(matches!(host, Err(MalformedUriError::InvalidAddress(_))))
     }
 
+    #[test]
+    fn test_uri_host_reg_name() {
+        let host: UriHost = "a-b.example%2D~_!$&'()*+,;=".parse().unwrap();
+        assert!(matches!(host, UriHost::RegName(_)));
+        for invalid in ["a b", "a/b", "a@b", "a:b", "a%2", "a%zz", "::1"] {
+            let host: Result<UriHost, _> = invalid.parse();
+            assert!(host.is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_uri_host_display() {
+        for host in ["[::1]", "[v5.123]", "127.0.0.1", "example.com"] {
+            assert_eq!(host.parse::<UriHost>().unwrap().to_string(), host);
+        }
+    }
+
     #[test]
     fn test_urlencode_basic() {
         assert_eq!(url_encode(b"hello world"), "hello%20world");
@@ -226,6 +326,20 @@ mod tests {
         assert!(url_decode(b"foo%G1").is_err());
     }
 
+    #[test]
+    fn test_form_urlencoded_decode() {
+        assert_eq!(
+            form_urlencoded_decode(b"q=a+b%2Bc&&empty=&flag&%C3%A9=1").unwrap(),
+            [
+                ("q".to_owned(), "a b+c".to_owned()),
+                ("empty".to_owned(), String::new()),
+                ("flag".to_owned(), String::new()),
+                ("é".to_owned(), "1".to_owned()),
+            ]
+        );
+        assert!(form_urlencoded_decode(b"a=%G1").is_err());
+    }
+
     #[test]
     fn test_urldecode_with_pluses_not_spaces() {
         // Standard RFC 3986 decoding doesn't convert + to space.
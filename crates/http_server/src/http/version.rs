@@ -2,13 +2,14 @@ use std::str::FromStr;
 
 /// HTTP Version
 /// SPEC: RFC 9110 - 2.5. Protocol Version
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct HttpVersion {
     pub major: u8,
     pub minor: u8,
 }
 
 impl HttpVersion {
+    pub const HTTP_1_0: Self = Self { major: 1, minor: 0 };
     pub const HTTP_1_1: Self = Self { major: 1, minor: 1 };
 }
 
@@ -1,21 +1,73 @@
 //! An async HTTP server implementation in rust
 
-#![feature(async_fn_traits, slice_split_once, str_from_raw_parts)]
-
+pub mod client;
+pub mod clock;
+pub mod config;
+mod connection;
+pub mod error_handler;
+pub mod extract;
+pub mod files;
 pub mod http;
+mod listener;
+pub mod metrics;
+pub mod middleware;
+pub mod options;
+mod panic;
+mod pipeline;
+pub mod policy;
+pub mod proxy;
+pub mod routing;
 pub mod service;
+pub mod shutdown;
 pub mod sync;
+pub mod testing;
+#[cfg(feature = "tls")]
+pub mod tls;
+mod trace;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring;
 
-use std::{net::SocketAddr, num::NonZeroUsize, sync::Arc, time::Duration};
+use std::{
+    io,
+    net::SocketAddr,
+    num::{NonZeroU64, NonZeroUsize},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
+use crate::clock::{SharedClock, TokioClock};
+use crate::connection::{ConnectionHandle, ConnectionRegistry};
+use crate::error_handler::{ErrorHandler, SharedErrorHandler};
 use crate::http::{
-    HttpVersion,
-    header::{Connection, ConnectionType},
-    parser::{HttpParseError, Parser, Sender},
-    request::Request,
-    response::{Response, ResponseBuilder, StatusCode},
+    Body, BodyError, BodyStream, HttpVersion,
+    header::{
+        Challenge, Connection, ConnectionOptions, FieldLinePolicy, HeaderField, KeepAlive,
+        KeepAliveParams, UpgradeOffer,
+    },
+    method::Method,
+    parser::{
+        BodyFraming, BodyLimits, BufferPool, HeadLimits, HttpParseError, ParseErrorKind, Parser,
+        Sender, WriteLimits, frame_response,
+    },
+    request::{Request, Scheme},
+    response::{InterimSender, Response, ResponseBuilder, StatusCode},
+};
+use crate::listener::{AcceptBackoff, Listen, Listener, Peer, Stream};
+use crate::metrics::ServerMetrics;
+use crate::options::{ServerOptions, SharedServerOptions};
+use crate::panic::CatchUnwind;
+use crate::pipeline::Ordered;
+use crate::policy::{ConnectionCounts, ConnectionPolicy, Rejection};
+use crate::proxy::{Prefixed, ProxyProtocol, TrustedProxies};
+use crate::shutdown::{ShutdownHandle, ShutdownReason, ShutdownSignal};
+pub use connection::{ConnectionId, ConnectionInfo, ConnectionStats, ServerHandle, TlsInfo};
+#[cfg(unix)]
+pub use listener::UnixSocket;
+pub use listener::{AcceptErrorHandler, AcceptErrorKind, TcpKeepalive, TcpOptions};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError},
 };
-use tokio::net::{TcpSocket, TcpStream};
 
 #[derive(Debug, Clone)]
 pub struct HttpServerConfig {
@@ -33,11 +85,86 @@ pub struct HttpServerConfig {
     pub max_body_bytes: Option<NonZeroUsize>, // None = unlimited (let app decide)
     pub max_chunk_size_bytes: NonZeroUsize,   // for chunked encoding
     pub max_trailer_bytes_total: NonZeroUsize, // trailers after chunked body
+    pub max_body_drain_bytes: usize, // unread body discarded to keep the connection alive, or before closing it
+
+    // Memory
+    /// Where connections take their read and write buffers from, and give them back to
+    pub buffer_pool: BufferPool,
+
+    // Concurrency (load shedding under a flood)
+    /// Connections accepted beyond this many are sent 503 Service Unavailable and closed,
+    /// None allows any number
+    pub max_connections: Option<NonZeroUsize>,
+    /// Requests beyond this many being routed at once fail with [`RouterError::Overloaded`],
+    /// None allows any number
+    pub max_in_flight_requests: Option<NonZeroUsize>,
+    /// The connection is closed after this many requests, None allows any number
+    pub max_requests_per_connection: Option<NonZeroU64>,
+    /// How many pipelined requests without a body are routed at once on a connection, 1 routes
+    /// them one after another
+    /// Requests routed together can't send interim responses
+    pub max_pipelined_requests: NonZeroUsize,
 
     // Timeouts (doS/smurf protection)
     pub header_read_timeout: Duration,
     pub request_body_timeout: Duration,
     pub keep_alive_timeout: Duration,
+    /// How long writing a response can wait on a client which takes none of it
+    pub response_write_timeout: Duration,
+    /// The minimum rate in bytes per second at which clients must take responses, once writing
+    /// one has taken longer than [`Self::response_write_timeout`], None allows any rate
+    pub min_response_write_rate: Option<NonZeroU64>,
+    /// How long the server keeps reading and discarding what a client sends after closing the
+    /// write side of a connection, so the client receives the whole response
+    pub linger_timeout: Duration,
+    /// How often idle connections are swept, None disables the reaper
+    pub idle_sweep_interval: Option<Duration>,
+    #[cfg(feature = "tls")]
+    pub tls_handshake_timeout: Duration,
+    /// How long in-flight requests are given to finish once shutdown starts
+    pub shutdown_grace_period: Duration,
+    /// The source of time for every timeout, a [`clock::MockClock`] makes them deterministic
+    pub clock: SharedClock,
+
+    // Headers
+    /// Remove hop-by-hop headers from requests before they are routed
+    pub strip_hop_by_hop_headers: bool,
+    /// Merge obsolete line folded header values instead of rejecting the request with 400
+    pub allow_obs_fold: bool,
+    /// Send a Keep-Alive field with responses on persistent connections, telling clients the
+    /// [`Self::keep_alive_timeout`] and how many more requests they can send
+    pub advertise_keep_alive: bool,
+    /// Answer TRACE requests with the request echoed back as `message/http`, without the
+    /// fields carrying credentials, instead of rejecting them with 405 Method Not Allowed
+    /// TRACE requests are never routed
+    pub enable_trace: bool,
+    /// Which response fields with multiple values are sent as repeated field lines
+    pub field_lines: FieldLinePolicy,
+    /// The reverse proxies whose forwarding fields name the client, none by default
+    pub trusted_proxies: TrustedProxies,
+    /// Whether connections start with a PROXY protocol header naming the client, which is read
+    /// within [`Self::header_read_timeout`]
+    pub proxy_protocol: ProxyProtocol,
+
+    // Sockets
+    /// Socket options of the TCP listener and accepted connections
+    pub tcp: TcpOptions,
+    /// The clients connections are accepted from, checked before anything is read
+    pub connection_policy: ConnectionPolicy,
+    /// Called with every failed accept, which is also logged
+    pub accept_error_handler: Option<AcceptErrorHandler>,
+
+    /// Renders the responses of requests which the router failed
+    pub error_handler: SharedErrorHandler,
+    /// Responds to `OPTIONS *`, with the common methods in Allow by default
+    pub server_options: SharedServerOptions,
+    /// Where the server counts connections, bytes and requests, share it with a
+    /// [`metrics::MetricsEndpoint`] to serve it
+    pub metrics: Arc<ServerMetrics>,
+
+    /// Terminates TLS on every accepted connection when set
+    #[cfg(feature = "tls")]
+    pub tls: Option<Arc<tls::ServerConfig>>,
 }
 
 impl Default for HttpServerConfig {
@@ -57,11 +184,48 @@ impl Default for HttpServerConfig {
             max_body_bytes: None,
             max_chunk_size_bytes: NonZeroUsize::new(8 * 1024 * 1024).unwrap(), // 8 MiB
             max_trailer_bytes_total: NonZeroUsize::new(8 * 1024).unwrap(),     // 8 KiB
+            max_body_drain_bytes: 256 * 1024,                                  // 256 KiB
+
+            // memory
+            buffer_pool: BufferPool::default(),
+
+            // concurrency
+            max_connections: None,
+            max_in_flight_requests: None,
+            max_requests_per_connection: None,
+            max_pipelined_requests: NonZeroUsize::MIN,
 
             // timeouts
             header_read_timeout: Duration::from_secs(10),
             request_body_timeout: Duration::from_secs(60),
             keep_alive_timeout: Duration::from_secs(75),
+            response_write_timeout: Duration::from_secs(60),
+            min_response_write_rate: None,
+            linger_timeout: Duration::from_secs(2),
+            idle_sweep_interval: Some(Duration::from_secs(1)),
+            #[cfg(feature = "tls")]
+            tls_handshake_timeout: Duration::from_secs(10),
+            shutdown_grace_period: Duration::from_secs(30),
+            clock: TokioClock::shared(),
+
+            // headers
+            strip_hop_by_hop_headers: true,
+            allow_obs_fold: false,
+            advertise_keep_alive: false,
+            enable_trace: false,
+            field_lines: FieldLinePolicy::default(),
+            trusted_proxies: TrustedProxies::default(),
+            proxy_protocol: ProxyProtocol::Disabled,
+            tcp: TcpOptions::default(),
+            connection_policy: ConnectionPolicy::default(),
+            accept_error_handler: None,
+
+            error_handler: SharedErrorHandler::default(),
+            server_options: SharedServerOptions::default(),
+            metrics: Arc::default(),
+
+            #[cfg(feature = "tls")]
+            tls: None,
         }
     }
 }
@@ -74,24 +238,245 @@ pub enum HttpServerError {
     HttpParseError(#[from] HttpParseError),
 }
 
+impl HttpServerConfig {
+    pub(crate) fn head_limits(&self) -> HeadLimits {
+        HeadLimits {
+            max_start_line_bytes: self.max_request_line_bytes,
+            max_header_bytes_total: self.max_header_bytes_total,
+            max_header_line_bytes: self.max_header_line_bytes,
+            max_header_count: self.max_header_count,
+            allow_obs_fold: self.allow_obs_fold,
+            read_timeout: self.header_read_timeout,
+            clock: self.clock.clone(),
+        }
+    }
+
+    pub(crate) fn write_limits(&self) -> WriteLimits {
+        WriteLimits {
+            write_timeout: self.response_write_timeout,
+            min_write_rate: self.min_response_write_rate,
+            clock: self.clock.clone(),
+        }
+    }
+
+    pub(crate) fn body_limits(&self) -> BodyLimits {
+        BodyLimits {
+            max_body_bytes: self.max_body_bytes,
+            max_chunk_size_bytes: self.max_chunk_size_bytes,
+            max_trailer_bytes_total: self.max_trailer_bytes_total,
+            read_timeout: self.request_body_timeout,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
 pub type HttpServerResult<T> = Result<T, HttpServerError>;
 
 pub struct HttpServer<R: Router>(Arc<HttpServerInternal<R>>);
 
 impl<R: Router> HttpServer<R> {
     pub fn new<A: Into<SocketAddr>>(addr: A, router: R) -> Self {
-        Self(Arc::new(HttpServerInternal::new(addr, router)))
+        Self::with_config(addr, router, HttpServerConfig::default())
     }
 
+    pub fn with_config<A: Into<SocketAddr>>(addr: A, router: R, config: HttpServerConfig) -> Self {
+        let listen = Listen::Tcp(addr.into());
+        Self(Arc::new(HttpServerInternal::new(listen, router, config)))
+    }
+
+    /// A server listening on a Unix domain socket, requests have no remote address unless a
+    /// PROXY protocol header names one
+    #[cfg(unix)]
+    pub fn unix(socket: UnixSocket, router: R) -> Self {
+        Self::unix_with_config(socket, router, HttpServerConfig::default())
+    }
+
+    #[cfg(unix)]
+    pub fn unix_with_config(socket: UnixSocket, router: R, config: HttpServerConfig) -> Self {
+        Self(Arc::new(HttpServerInternal::new(
+            Listen::Unix(socket),
+            router,
+            config,
+        )))
+    }
+
+    /// A server accepting connections on a listener bound by the caller, such as one inherited
+    /// from a supervisor
+    pub fn from_listener(listener: std::net::TcpListener, router: R) -> Self {
+        Self::from_listener_with_config(listener, router, HttpServerConfig::default())
+    }
+
+    pub fn from_listener_with_config(
+        listener: std::net::TcpListener,
+        router: R,
+        config: HttpServerConfig,
+    ) -> Self {
+        let listen = Listen::BoundTcp(Arc::new(listener));
+        Self(Arc::new(HttpServerInternal::new(listen, router, config)))
+    }
+
+    /// A server accepting connections on the TCP or Unix domain socket passed by systemd socket
+    /// activation, so the socket stays open while the server restarts
+    /// Fails if no socket was passed to this process
+    #[cfg(unix)]
+    pub fn from_socket_activation(router: R, config: HttpServerConfig) -> HttpServerResult<Self> {
+        let listen = listener::socket_activation()?;
+        Ok(Self(Arc::new(HttpServerInternal::new(
+            listen, router, config,
+        ))))
+    }
+
+    /// Serves connections until shutdown has completed
     pub async fn serve(&self) -> Result<(), HttpServerError> {
         HttpServerInternal::serve(self.0.clone()).await
     }
+
+    /// Serves connections on `workers` threads until shutdown has completed, blocking the
+    /// calling thread
+    /// Each worker runs a current-thread runtime with its own listener, bound with SO_REUSEPORT
+    /// where the platform has it, and serves the connections it accepted, so no connection is
+    /// moved between cores. Unix domain sockets and listeners bound by the caller are shared by
+    /// the workers
+    /// Must not be called from within a runtime
+    pub fn serve_multithreaded(&self, workers: NonZeroUsize) -> Result<(), HttpServerError> {
+        HttpServerInternal::serve_multithreaded(self.0.clone(), workers)
+    }
+
+    /// Serves a single connection on `stream`, such as an in-memory stream in tests, without
+    /// the listener
+    /// The future completes once the connection has been closed, and can be spawned
+    pub fn serve_connection<S>(
+        &self,
+        stream: S,
+        remote: Option<SocketAddr>,
+    ) -> impl Future<Output = ()> + Send + 'static
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        HttpServerInternal::handle_connection(self.0.clone(), stream, Peer(remote), None, None)
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.0.shutdown.clone()
+    }
+
+    /// Live stats of the connections of the server, which can also close idle connections
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle::new(&self.0.connections, self.0.shutdown_signal.clone())
+    }
+
+    pub fn metrics(&self) -> &Arc<ServerMetrics> {
+        &self.0.metrics
+    }
+
+    #[cfg(feature = "tls")]
+    pub fn tls_metrics(&self) -> &tls::HandshakeMetrics {
+        &self.0.tls_metrics
+    }
+
+    /// Replaces the config of the running server, connections accepted from then on use it,
+    /// and open connections keep the config they were accepted with
+    /// Settings which are fixed once the server starts keep their value: the clock, metrics,
+    /// TCP options, connection and in-flight request limits, idle sweeping, keep-alive timeout
+    /// and shutdown grace period. A server started without TLS stays plaintext, and the
+    /// certificates are kept when the new config has none
+    pub fn reload_config(&self, config: HttpServerConfig) {
+        self.0.reload_config(config);
+    }
+
+    /// Uses `tls` for handshakes from then on, such as renewed certificates, established
+    /// connections are kept
+    /// Returns false if the server doesn't terminate TLS
+    #[cfg(feature = "tls")]
+    pub fn reload_tls(&self, tls: Arc<tls::ServerConfig>) -> bool {
+        self.0.reload_tls(tls)
+    }
+
+    /// Reads `files` again whenever the process receives SIGHUP, until the server shuts down
+    /// A reload which fails is logged, and the current certificates are kept
+    #[cfg(all(feature = "tls", unix))]
+    pub fn reload_tls_on_sighup(
+        &self,
+        files: tls::CertificateFiles,
+    ) -> io::Result<tokio::task::JoinHandle<()>> {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut hangup = signal(SignalKind::hangup())?;
+        let sel = self.0.clone();
+        Ok(tokio::spawn(async move {
+            let shutdown = sel.shutdown_signal.clone();
+            loop {
+                tokio::select! {
+                    Some(()) = hangup.recv() => {}
+                    _ = shutdown.triggered() => return,
+                }
+                match files.load() {
+                    Ok(tls) => {
+                        if sel.reload_tls(tls) {
+                            log::info!(
+                                "reloaded TLS certificates from {}",
+                                files.cert_chain.display()
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        log::error!(
+                            "failed to reload TLS certificates, keeping the current ones: {err}"
+                        )
+                    }
+                }
+            }
+        }))
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum RouterError {
     #[error(transparent)]
     Generic(#[from] Box<dyn std::error::Error + Send + Sync>),
+    #[error(transparent)]
+    Body(#[from] BodyError),
+    /// The router panicked, with the panic message
+    #[error("router panicked: {0}")]
+    Panicked(String),
+    #[error("not found")]
+    NotFound,
+    /// The request was malformed, with a message describing why
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    /// The request lacks valid credentials, with the challenge sent in the WWW-Authenticate
+    /// field of the response
+    /// SPEC: RFC 9110 - 15.5.2. 401 Unauthorized
+    #[error("unauthorized")]
+    Unauthorized(Challenge),
+    /// The router did not produce a response in time
+    #[error("timed out")]
+    Timeout,
+    /// The server is routing as many requests as it allows, so the request was not routed
+    #[error("server overloaded")]
+    Overloaded,
+    /// An error with an application defined status
+    #[error("{1}")]
+    Custom(StatusCode, String),
+}
+
+impl RouterError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Generic(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Body(err) => err.status_code(),
+            Self::Panicked(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Timeout | Self::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Custom(status, _) => *status,
+        }
+    }
+
+    /// Whether the state of the connection is unknown after the error, so it can't be reused
+    fn closes_connection(&self) -> bool {
+        matches!(self, Self::Generic(_) | Self::Body(_) | Self::Panicked(_))
+    }
 }
 
 pub trait Router: Send + Sync + 'static {
@@ -102,101 +487,1427 @@ pub trait Router: Send + Sync + 'static {
 }
 
 pub(crate) struct HttpServerInternal<R: Router> {
-    addr: SocketAddr,
+    listen: Listen,
     router: R,
+    /// Replaced by [`HttpServer::reload_config`], connections take the config they are
+    /// accepted with
+    config: RwLock<Arc<HttpServerConfig>>,
+    metrics: Arc<ServerMetrics>,
+    connections: Arc<ConnectionRegistry>,
+    shutdown: ShutdownHandle,
+    shutdown_signal: ShutdownSignal,
+    connection_limit: Option<Arc<Semaphore>>,
+    connection_counts: ConnectionCounts,
+    request_limit: Option<Semaphore>,
+    #[cfg(feature = "tls")]
+    tls: Option<tls::Acceptor>,
+    #[cfg(feature = "tls")]
+    tls_metrics: Arc<tls::HandshakeMetrics>,
 }
 
 impl<R: Router> HttpServerInternal<R> {
-    pub fn new<A: Into<SocketAddr>>(addr: A, router: R) -> Self {
+    /// Number of body chunks buffered ahead of the router
+    const BODY_CHANNEL_CAPACITY: usize = 4;
+    /// Written to connections over the limit without reading their request
+    const OVERLOADED: &[u8] = b"HTTP/1.1 503 Service Unavailable\r\n\
+        Connection: close\r\nContent-Length: 0\r\nRetry-After: 1\r\n\r\n";
+
+    pub(crate) fn new(listen: Listen, router: R, config: HttpServerConfig) -> Self {
+        let shutdown = ShutdownHandle::new(config.shutdown_grace_period, config.clock.clone());
+        #[cfg(feature = "tls")]
+        let tls_metrics = Arc::new(tls::HandshakeMetrics::default());
         Self {
-            addr: addr.into(),
+            #[cfg(feature = "tls")]
+            tls: config.tls.clone().map(|tls_config| {
+                tls::Acceptor::new(
+                    tls_config,
+                    config.tls_handshake_timeout,
+                    config.clock.clone(),
+                    tls_metrics.clone(),
+                )
+            }),
+            #[cfg(feature = "tls")]
+            tls_metrics,
+            listen,
             router,
+            connections: Arc::new(ConnectionRegistry::new(
+                config.clock.clone(),
+                config.metrics.clone(),
+            )),
+            connection_limit: config
+                .max_connections
+                .map(|max| Arc::new(Semaphore::new(max.get()))),
+            connection_counts: ConnectionCounts::default(),
+            request_limit: config
+                .max_in_flight_requests
+                .map(|max| Semaphore::new(max.get())),
+            metrics: config.metrics.clone(),
+            config: RwLock::new(Arc::new(config)),
+            shutdown_signal: shutdown.signal(),
+            shutdown,
+        }
+    }
+
+    fn config(&self) -> Arc<HttpServerConfig> {
+        self.config.read().unwrap().clone()
+    }
+
+    fn reload_config(&self, mut config: HttpServerConfig) {
+        let mut current = self.config.write().unwrap();
+        config.clock = current.clock.clone();
+        config.metrics = current.metrics.clone();
+        config.tcp = current.tcp.clone();
+        config.max_connections = current.max_connections;
+        config.max_in_flight_requests = current.max_in_flight_requests;
+        config.idle_sweep_interval = current.idle_sweep_interval;
+        // The reaper closes idle connections after the timeout it was started with
+        config.keep_alive_timeout = current.keep_alive_timeout;
+        config.shutdown_grace_period = current.shutdown_grace_period;
+        #[cfg(feature = "tls")]
+        {
+            config.tls_handshake_timeout = current.tls_handshake_timeout;
+            match (&self.tls, &config.tls) {
+                (Some(acceptor), Some(tls)) => acceptor.set_config(tls.clone()),
+                _ => config.tls = current.tls.clone(),
+            }
+        }
+        *current = Arc::new(config);
+    }
+
+    #[cfg(feature = "tls")]
+    fn reload_tls(&self, tls: Arc<tls::ServerConfig>) -> bool {
+        let Some(acceptor) = &self.tls else {
+            return false;
+        };
+        acceptor.set_config(tls.clone());
+        Arc::make_mut(&mut self.config.write().unwrap()).tls = Some(tls);
+        true
+    }
+
+    /// Decides how long to wait before accepting again, fatal errors stop the server
+    fn accept_failed(
+        &self,
+        config: &HttpServerConfig,
+        err: io::Error,
+        backoff: &mut AcceptBackoff,
+    ) -> io::Result<Option<Duration>> {
+        let kind = AcceptErrorKind::of(&err);
+        if let Some(handler) = &config.accept_error_handler {
+            handler.call(&err, kind);
+        }
+        match kind {
+            AcceptErrorKind::Connection => {
+                log::debug!("failed to accept a connection: {err}");
+                Ok(None)
+            }
+            AcceptErrorKind::Transient => {
+                let delay = backoff.next();
+                log::warn!("failed to accept connections, retrying in {delay:?}: {err}");
+                Ok(Some(delay))
+            }
+            AcceptErrorKind::Fatal => {
+                log::error!("failed to accept connections, stopping: {err}");
+                Err(err)
+            }
         }
     }
 
     pub async fn serve(sel: Arc<Self>) -> Result<(), HttpServerError> {
-        let sock = match sel.addr {
-            SocketAddr::V4(_) => TcpSocket::new_v4()?,
-            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        let config = sel.config();
+        let listener = Listener::bind(&sel.listen, &config.tcp)?;
+        sel.spawn_reaper(&config);
+        Self::run(sel, listener).await
+    }
+
+    /// Runs `workers` threads, each with a current-thread runtime which accepts on a listener of
+    /// its own and serves the connections it accepted, until shutdown has completed
+    /// A worker which fails shuts the others down, and its error is returned
+    fn serve_multithreaded(sel: Arc<Self>, workers: NonZeroUsize) -> Result<(), HttpServerError> {
+        let config = sel.config();
+        let listens = sel.listen.bind_workers(&config.tcp, workers.get())?;
+        let mut threads = Vec::with_capacity(workers.get());
+        for (worker, listen) in listens.listens.iter().cloned().enumerate() {
+            let thread_sel = sel.clone();
+            let spawned = std::thread::Builder::new()
+                .name(format!("carbon-worker-{worker}"))
+                .spawn(move || {
+                    let sel = thread_sel;
+                    let result = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .map_err(HttpServerError::from)
+                        .and_then(|runtime| {
+                            runtime.block_on(async {
+                                let config = sel.config();
+                                let listener = Listener::bind(&listen, &config.tcp)?;
+                                // The connections are shared by the workers, so one reaper
+                                // sweeps all of them
+                                if worker == 0 {
+                                    sel.spawn_reaper(&config);
+                                }
+                                Self::run(sel.clone(), listener).await
+                            })
+                        });
+                    if let Err(err) = &result {
+                        log::error!("worker {worker} failed: {err}");
+                        sel.shutdown.shutdown(ShutdownReason::Custom(
+                            format!("worker {worker} failed").into(),
+                        ));
+                    }
+                    result
+                });
+            match spawned {
+                Ok(thread) => threads.push(thread),
+                Err(err) => {
+                    sel.shutdown
+                        .shutdown(ShutdownReason::Custom("failed to start workers".into()));
+                    threads.into_iter().for_each(|thread| _ = thread.join());
+                    return Err(err.into());
+                }
+            }
+        }
+        let mut result = Ok(());
+        for thread in threads {
+            match thread.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => result = result.and(Err(err)),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        }
+        result
+    }
+
+    fn spawn_reaper(&self, config: &HttpServerConfig) {
+        if let Some(sweep_interval) = config.idle_sweep_interval {
+            tokio::spawn(
+                self.connections
+                    .clone()
+                    .run_reaper(sweep_interval, config.keep_alive_timeout),
+            );
+        }
+    }
+
+    /// Accepts connections on `listener` until shutdown starts, then waits for the connections
+    /// to close
+    async fn run(sel: Arc<Self>, listener: Listener) -> Result<(), HttpServerError> {
+        let config = sel.config();
+        let mut backoff = AcceptBackoff::default();
+        let shutdown = loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let config = sel.config();
+                    let (stream, addr) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(err) => {
+                            if let Some(delay) = sel.accept_failed(&config, err, &mut backoff)? {
+                                tokio::select! {
+                                    _ = config.clock.sleep(delay) => {}
+                                    shutdown = sel.shutdown_signal.triggered() => break shutdown,
+                                }
+                            }
+                            continue;
+                        }
+                    };
+                    backoff.reset();
+                    let ip_permit = match sel
+                        .connection_counts
+                        .admit(&config.connection_policy, addr.0.map(|addr| addr.ip()))
+                    {
+                        Ok(permit) => permit,
+                        Err(Rejection::Denied) => {
+                            log::debug!("refusing connection from {}: denied", addr);
+                            sel.metrics.connection_rejected();
+                            continue;
+                        }
+                        Err(Rejection::TooManyConnections) => {
+                            sel.shed_connection(stream, addr);
+                            continue;
+                        }
+                    };
+                    let Ok(permit) = sel.try_connection_permit() else {
+                        sel.shed_connection(stream, addr);
+                        continue;
+                    };
+                    let sel = sel.clone();
+                    tokio::spawn(async move {
+                        let _permits = (permit, ip_permit);
+                        HttpServerInternal::accept_connection(sel, stream, addr).await
+                    });
+                }
+                shutdown = sel.shutdown_signal.triggered() => break shutdown,
+            }
         };
+        drop(listener);
+        log::info!("shutting down: {}", shutdown.reason);
 
-        sock.set_reuseaddr(true)?;
-        sock.bind(sel.addr)?;
+        // Busy connections close after their current response, the rest can close right away
+        sel.connections.close_all(false);
+        if clock::timeout_at(
+            &*config.clock,
+            shutdown.deadline,
+            sel.connections.wait_empty(),
+        )
+        .await
+        .is_err()
+        {
+            let closed = sel.connections.close_all(true);
+            log::warn!(
+                "closing {} connections after the shutdown grace period",
+                closed
+            );
+            sel.connections.wait_empty().await;
+        }
+        Ok(())
+    }
 
-        let listener = sock.listen(1024)?;
-        loop {
-            let (stream, addr) = listener.accept().await?;
-            tokio::spawn(HttpServerInternal::handle_connection(
-                sel.clone(),
-                stream,
-                addr,
-            ));
+    /// Takes one of the connections the server allows, which is given back once dropped
+    fn try_connection_permit(&self) -> Result<Option<OwnedSemaphorePermit>, TryAcquireError> {
+        self.connection_limit
+            .clone()
+            .map(Semaphore::try_acquire_owned)
+            .transpose()
+    }
+
+    /// Closes a connection over the limit, telling a plaintext client to retry later
+    /// The response is only written if the socket can take it right away, so a flood of
+    /// connections can't tie up tasks
+    fn shed_connection(&self, stream: Stream, addr: Peer) {
+        log::debug!("shedding connection from {}: too many connections", addr);
+        self.metrics.connection_rejected();
+        #[cfg(feature = "tls")]
+        if self.tls.is_some() {
+            return;
+        }
+        let _ = stream.try_write(Self::OVERLOADED);
+    }
+
+    /// Reads the PROXY protocol header if the server expects one, the client it names is the
+    /// remote address of the requests
+    async fn accept_connection(sel: Arc<Self>, mut stream: Stream, addr: Peer) {
+        let local = stream.local_addr();
+        let config = sel.config();
+        let (stream, addr, local) = match config.proxy_protocol {
+            ProxyProtocol::Disabled => (Prefixed::new(bytes::Bytes::new(), stream), addr, local),
+            mode => {
+                let read = proxy::read_header(&mut stream, mode);
+                let timeout = config.header_read_timeout;
+                let (header, rest) = match clock::timeout(&*config.clock, timeout, read).await {
+                    Ok(Ok(read)) => read,
+                    Ok(Err(err)) => {
+                        log::debug!("closing connection from {}: {}", addr, err);
+                        return;
+                    }
+                    Err(_) => {
+                        log::debug!("closing connection from {}: no PROXY header in time", addr);
+                        return;
+                    }
+                };
+                let (client, local) = match header {
+                    Some(header) => (
+                        header.source.map_or(addr, Peer::from),
+                        header.destination.or(local),
+                    ),
+                    None => (addr, local),
+                };
+                (Prefixed::new(rest, stream), client, local)
+            }
+        };
+        #[cfg(feature = "tls")]
+        if sel.tls.is_some() {
+            return Self::handle_tls_connection(sel, stream, addr, local).await;
         }
+        Self::handle_connection(sel, stream, addr, local, None).await
     }
 
-    async fn handle_connection(sel: Arc<Self>, stream: TcpStream, addr: SocketAddr) {
-        if let Err(err) = sel.handle_connection_internal(stream, addr).await {
-            log::error!("server error: {}", err);
+    #[cfg(feature = "tls")]
+    async fn handle_tls_connection<S>(
+        sel: Arc<Self>,
+        stream: S,
+        addr: Peer,
+        local: Option<SocketAddr>,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let acceptor = sel.tls.as_ref().expect("tls is configured");
+        match acceptor.accept(stream).await {
+            Ok(stream) => {
+                let (_, session) = stream.get_ref();
+                let tls = TlsInfo {
+                    server_name: session.server_name().map(str::to_owned),
+                    alpn_protocol: session.alpn_protocol().map(bytes::Bytes::copy_from_slice),
+                    version: session
+                        .protocol_version()
+                        .and_then(|version| version.as_str()),
+                };
+                Self::handle_connection(sel, stream, addr, local, Some(tls)).await
+            }
+            Err(cause) => log::debug!("TLS handshake with {} failed: {:?}", addr, cause),
         }
     }
 
-    async fn handle_connection_internal(
-        &self,
-        mut stream: TcpStream,
-        addr: SocketAddr,
-    ) -> HttpServerResult<()> {
-        let (mut read_stream, mut write_stream) = stream.split();
-        let mut parser = Parser::new(&mut read_stream);
-        let mut sender = Sender::new(&mut write_stream);
-
-        loop {
-            let req = match parser.parse_request().await {
-                Ok(mut req) => {
-                    req.remote = Some(addr);
-                    req
+    async fn handle_connection<S>(
+        sel: Arc<Self>,
+        stream: S,
+        addr: Peer,
+        local: Option<SocketAddr>,
+        tls: Option<TlsInfo>,
+    ) where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let conn = sel.connections.register(addr.0);
+        let info = ConnectionInfo {
+            id: conn.id(),
+            local_addr: local,
+            remote_addr: addr.0,
+            tls,
+            requests: 0,
+        };
+        tokio::select! {
+            res = sel.handle_connection_internal(stream, addr, info, &conn) => {
+                if let Err(err) = res {
+                    log::error!("server error: {}", err);
                 }
+            }
+            _ = conn.closed() => {
+                log::debug!("closing connection {} from {}", conn.id(), addr);
+            }
+        }
+    }
+
+    async fn handle_connection_internal<S>(
+        &self,
+        stream: S,
+        addr: Peer,
+        mut info: ConnectionInfo,
+        conn: &ConnectionHandle,
+    ) -> HttpServerResult<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let config = self.config();
+        let (read_stream, write_stream) = tokio::io::split(stream);
+        let mut parser = Parser::with_limits(conn.track(read_stream), config.head_limits())
+            .with_buffer_pool(config.buffer_pool.clone());
+        let mut sender =
+            Sender::with_field_lines(conn.track(write_stream), config.field_lines.clone())
+                .with_write_limits(config.write_limits())
+                .with_buffer_pool(config.buffer_pool.clone());
+
+        let body_limits = config.body_limits();
+        // A head parsed ahead while collecting pipelined requests, handled next
+        let mut pending = None;
+
+        'requests: loop {
+            let head = match pending.take() {
+                Some(head) => head,
+                None => parser.parse_request_head().await,
+            };
+            let (mut req, framing) = match head {
+                Ok(head) => head,
+                // The client closed the connection between requests
+                Err(_) if parser.is_idle() => return Ok(()),
+                Err(HttpParseError {
+                    kind: ParseErrorKind::Io(_),
+                    ..
+                }) => return Ok(()),
                 Err(err) => {
                     log::error!("failed to parse request: {}", err);
+                    // The rest of the request can't be found, so the connection is closed
                     let res = ResponseBuilder::new(HttpVersion::HTTP_1_1, err.status_code())
-                        .set_header::<Connection>(ConnectionType::Close)
+                        .set_header::<Connection>(ConnectionOptions::close())
                         .build();
-                    sender.send_response(res).await?;
-                    return Ok(());
+                    // No request was parsed, the response has no body either way
+                    sender
+                        .send_response(res, &Method::GET, HttpVersion::HTTP_1_1)
+                        .await?;
+                    break;
                 }
             };
-            let close_connection = matches!(
-                req.headers.get_header::<Connection>().unwrap(),
-                Some(ConnectionType::Close)
-            );
-            let res = self.router.route(&req).await;
-            match res {
-                Ok(res) => {
-                    let close_connection = matches!(
-                        res.headers.get_header::<Connection>().unwrap(),
-                        Some(ConnectionType::Close)
-                    );
-                    log::debug!("sending response = {:#?}", res);
-                    sender.send_response(res).await?;
-                    if close_connection {
+
+            if framing == BodyFraming::None
+                && config.max_pipelined_requests.get() > 1
+                && parser.has_buffered_head()
+            {
+                // SPEC: RFC 9112 - 9.3.2. Pipelining
+                // Requests without a body which the client sent before receiving a response are
+                // routed at once, and their responses are sent in the order of the requests
+                parser.reset();
+                let mut batch = vec![req];
+                while batch.len() < config.max_pipelined_requests.get()
+                    && parser.has_buffered_head()
+                {
+                    match parser.parse_request_head().await {
+                        Ok((next, BodyFraming::None)) => {
+                            batch.push(next);
+                            parser.reset();
+                        }
+                        // Requests with a body and errors are handled on their own, in order
+                        head => {
+                            pending = Some(head);
+                            break;
+                        }
+                    }
+                }
+                let closes: Vec<_> = batch
+                    .iter_mut()
+                    .map(|req| self.prepare_request(&config, req, addr, &mut info, conn))
+                    .collect();
+                // The client must not send more after asking to close the connection
+                if let Some(last) = closes.iter().position(|close| *close) {
+                    batch.truncate(last + 1);
+                    pending = None;
+                }
+                conn.set_busy(true);
+                let started = config.clock.now();
+                let mut routes =
+                    Ordered::new(batch.iter().map(|req| self.route_request(&config, req)));
+                for (i, (req, close)) in batch.iter().zip(closes).enumerate() {
+                    let mut close_connection = close;
+                    let res = match routes.take(i).await {
+                        Ok(res) => res,
+                        Err(err) => self.render_error(&config, req, err, &mut close_connection),
+                    };
+                    if self
+                        .send_response(&config, &mut sender, req, res, close_connection, started)
+                        .await?
+                    {
+                        break 'requests;
+                    }
+                }
+                conn.set_busy(false);
+                if self.shutdown_signal.is_shutting_down() {
+                    break;
+                }
+                continue;
+            }
+
+            let started = config.clock.now();
+            let mut close_connection =
+                self.prepare_request(&config, &mut req, addr, &mut info, conn);
+            // SPEC: RFC 9110 - 15.2. Informational 1xx
+            // A server must not send a 1xx response to an HTTP/1.0 client
+            let mut interim_rx = if req.version >= HttpVersion::HTTP_1_1 {
+                let (tx, rx) = InterimSender::channel();
+                req.interim = Some(tx);
+                Some(rx)
+            } else {
+                None
+            };
+            let body_tx = if framing == BodyFraming::None {
+                None
+            } else {
+                let (tx, stream) = BodyStream::channel(Self::BODY_CHANNEL_CAPACITY);
+                req.body = Body::Stream(stream);
+                Some(tx)
+            };
+            conn.set_busy(true);
+            // The body is read while the router runs, so the router can stream it
+            let mut body_complete = body_tx.is_none();
+            let res = {
+                let route = self.route_request(&config, &req);
+                let pump = async {
+                    match body_tx {
+                        Some(tx) => parser.pump_body(framing, tx, &body_limits).await,
+                        None => Ok(()),
+                    }
+                };
+                tokio::pin!(route, pump);
+                let mut pumping = !body_complete;
+                let res = loop {
+                    tokio::select! {
+                        res = &mut route => break res,
+                        Some(interim) = async { interim_rx.as_mut()?.recv().await } => {
+                            sender.send_interim(interim).await?;
+                        }
+                        body = &mut pump, if pumping => {
+                            pumping = false;
+                            match body {
+                                Ok(()) => body_complete = true,
+                                Err(err) => log::debug!("failed to read request body: {}", err),
+                            }
+                        }
+                    }
+                };
+                let res = match res {
+                    Ok(res) => res,
+                    Err(RouterError::Body(BodyError::ClientDisconnected)) => {
+                        log::debug!("client {} disconnected while sending body", addr);
                         return Ok(());
                     }
+                    Err(err) => self.render_error(&config, &req, err, &mut close_connection),
+                };
+                // The router did not read the whole body, so read the rest ourselves, otherwise
+                // the body would be parsed as the next request
+                if pumping
+                    && !close_connection
+                    && let Body::Stream(stream) = &req.body
+                {
+                    let max_drain = config.max_body_drain_bytes;
+                    let discard = async {
+                        let mut drained = 0;
+                        while let Some(Ok(chunk)) = stream.next_chunk().await {
+                            drained += chunk.len();
+                            if drained > max_drain {
+                                log::debug!("unread request body exceeds the drain limit");
+                                break;
+                            }
+                        }
+                    };
+                    // The stream only ends once the pump has completed, so the pump is polled first
+                    tokio::select! {
+                        biased;
+                        body = &mut pump => body_complete = body.is_ok(),
+                        _ = discard => {}
+                    }
                 }
-                Err(err) => {
-                    let res = ResponseBuilder::from_req(&req, StatusCode::INTERNAL_SERVER_ERROR)
-                        .set_header::<Connection>(ConnectionType::Close)
-                        .build();
-                    sender.send_response(res).await?;
-                    log::error!("router error: {}", err)
+                res
+            };
+            // If the router did not consume the whole body, we can't find the next request
+            close_connection |= !body_complete;
+            // Interim responses sent just before the router returned must precede the final one
+            if let Some(mut rx) = interim_rx {
+                rx.close();
+                while let Ok(interim) = rx.try_recv() {
+                    sender.send_interim(interim).await?;
                 }
             }
 
-            if close_connection {
-                return Ok(());
+            let close_connection = self
+                .send_response(&config, &mut sender, &req, res, close_connection, started)
+                .await?;
+            conn.set_busy(false);
+            parser.recycle_headers(std::mem::take(&mut req.headers));
+            if close_connection || self.shutdown_signal.is_shutting_down() {
+                break;
             }
+            // The body has been read, anything left in the buffer belongs to the next request
+            parser.reset();
         }
+
+        // SPEC: RFC 9112 - 9.6. Tear-down
+        // Closing the connection while the client is still sending can reset it, losing the
+        // response before the client has read it, so the write side is closed first and what
+        // the client still sends is discarded for a while
+        sender.shutdown().await?;
+        let linger = parser.discard(config.max_body_drain_bytes);
+        let _ = clock::timeout(&*config.clock, config.linger_timeout, linger).await;
+        Ok(())
+    }
+
+    /// Sets what the server knows about a request before it is routed, returns whether the
+    /// client asked to close the connection after it
+    fn prepare_request(
+        &self,
+        config: &HttpServerConfig,
+        req: &mut Request,
+        addr: Peer,
+        info: &mut ConnectionInfo,
+        conn: &ConnectionHandle,
+    ) -> bool {
+        req.remote = addr.0;
+        req.extensions.insert(self.shutdown_signal.clone());
+        req.extensions.insert(match info.tls {
+            Some(_) => Scheme::Https,
+            None => Scheme::Http,
+        });
+        info.requests += 1;
+        conn.request_received();
+        req.extensions.insert(info.clone());
+        if let Some(client) = addr
+            .0
+            .and_then(|addr| config.trusted_proxies.resolve(addr.ip(), &req.headers))
+        {
+            req.extensions.insert(client);
+        }
+        // SPEC: RFC 9112 - 9.3. Persistence
+        // HTTP/1.1 connections persist unless closed, HTTP/1.0 connections only persist if
+        // the client asks for keep-alive
+        let options = ConnectionOptions::of(&req.headers);
+        let mut close = if req.version >= HttpVersion::HTTP_1_1 {
+            options.close
+        } else {
+            !options.keep_alive || options.close
+        };
+        close |= config
+            .max_requests_per_connection
+            .is_some_and(|max| info.requests >= max.get());
+        // SPEC: RFC 9110 - 7.8. Upgrade
+        // The offer is kept for the router, as the fields it is made in are hop-by-hop
+        if let Some(offer) = UpgradeOffer::of(&req.headers) {
+            req.extensions.insert(offer);
+        }
+        // The connection options have been handled, they are not for the router
+        if config.strip_hop_by_hop_headers {
+            req.headers.remove_hop_by_hop();
+        }
+        close
+    }
+
+    /// Routes a request, a panicking router only fails its own request
+    async fn route_request(
+        &self,
+        config: &HttpServerConfig,
+        req: &Request,
+    ) -> Result<Response, RouterError> {
+        // SPEC: RFC 9112 - 3.2.4. asterisk-form
+        // A `*` target names the server rather than a resource, so it is never routed
+        if &req.target[..] == b"*" {
+            return match req.method == Method::OPTIONS {
+                true => Ok(config.server_options.respond(req)),
+                false => Err(RouterError::BadRequest(
+                    "the asterisk-form target is only used with OPTIONS".into(),
+                )),
+            };
+        }
+        // SPEC: RFC 9110 - 9.3.8. TRACE
+        if req.method == Method::TRACE {
+            return Ok(match config.enable_trace {
+                true => trace::echo(req),
+                false => trace::reject(req),
+            });
+        }
+        // Requests over the limit are rejected rather than queued, so a flood can't grow memory
+        // without bound
+        let Ok(_permit) = self
+            .request_limit
+            .as_ref()
+            .map(Semaphore::try_acquire)
+            .transpose()
+        else {
+            return Err(RouterError::Overloaded);
+        };
+        let _in_flight = self.connections.route_started();
+        let res = match panic::catch(|| self.router.route(req)) {
+            Ok(route) => CatchUnwind::new(std::pin::pin!(route)).await,
+            Err(panic) => Err(panic),
+        };
+        res.unwrap_or_else(|panic| {
+            if let Some(backtrace) = &panic.backtrace {
+                log::error!("router panicked: {}\n{}", panic.message, backtrace);
+            }
+            Err(RouterError::Panicked(panic.message))
+        })
+    }
+
+    fn render_error(
+        &self,
+        config: &HttpServerConfig,
+        req: &Request,
+        err: RouterError,
+        close_connection: &mut bool,
+    ) -> Response {
+        if err.status_code().is_server_error() && !matches!(err, RouterError::Overloaded) {
+            log::error!("router error: {}", err);
+        } else {
+            log::debug!("router error: {}", err);
+        }
+        *close_connection |= err.closes_connection();
+        config.error_handler.render(req, &err)
+    }
+
+    /// The reuse policy of the connection `req` was received on, after responding to it
+    fn keep_alive_params(config: &HttpServerConfig, req: &Request) -> KeepAliveParams {
+        let requests = req.connection().map_or(0, |info| info.requests);
+        KeepAliveParams {
+            timeout: Some(config.keep_alive_timeout.as_secs()),
+            max: config
+                .max_requests_per_connection
+                .map(|max| max.get().saturating_sub(requests)),
+        }
+    }
+
+    /// Sends the response to a request, returns whether the connection is closed after it
+    async fn send_response<W>(
+        &self,
+        config: &HttpServerConfig,
+        sender: &mut Sender<W>,
+        req: &Request,
+        mut res: Response,
+        mut close_connection: bool,
+        started: tokio::time::Instant,
+    ) -> io::Result<bool>
+    where
+        W: tokio::io::AsyncWriteExt + Unpin,
+    {
+        // SPEC: RFC 9110 - 6.2. Control Data
+        // The response has the highest version the server conforms to, but is framed the way
+        // the client understands, so an HTTP/1.0 client never gets a chunked body
+        res.version = HttpVersion::HTTP_1_1;
+        frame_response(&mut res, &req.method, req.version);
+        let mut options = ConnectionOptions::of(&res.headers);
+        close_connection |= options.close;
+        // Tell the client not to reuse the connection, rather than it finding out
+        // when its next request fails
+        close_connection |= self.shutdown_signal.is_shutting_down();
+        if close_connection {
+            if !options.close {
+                // Other options the handler set still apply to the response
+                options.close = true;
+                options.keep_alive = false;
+                res.headers.set_header::<Connection>(options);
+            }
+            res.headers.remove(&KeepAlive::NAME);
+        } else {
+            if req.version < HttpVersion::HTTP_1_1 {
+                // HTTP/1.0 clients assume the connection is closed unless told otherwise
+                options.keep_alive = true;
+                res.headers.set_header::<Connection>(options);
+            }
+            if config.advertise_keep_alive {
+                res.headers
+                    .set_header::<KeepAlive>(Self::keep_alive_params(config, req));
+            }
+        }
+        log::debug!("sending response = {:#?}", res);
+        let status = res.status;
+        sender.send_response(res, &req.method, req.version).await?;
+        let latency = config.clock.now().duration_since(started);
+        self.metrics.record_request(&req.method, status, latency);
+        Ok(close_connection)
     }
 }
 
 pub fn init_logger() {
     env_logger::init();
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{http::header::Protocol, shutdown::ShutdownReason};
+
+    const ADDR: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 0);
+
+    pub(crate) fn server<R: Router>(
+        router: R,
+        config: HttpServerConfig,
+    ) -> Arc<HttpServerInternal<R>> {
+        Arc::new(HttpServerInternal::new(Listen::Tcp(ADDR), router, config))
+    }
+
+    /// Sends `input` on a new connection to the server, returning everything the server sent
+    /// before closing the connection
+    pub(crate) async fn exchange<R: Router>(
+        server: &Arc<HttpServerInternal<R>>,
+        input: &[u8],
+    ) -> String {
+        let (mut client, stream) = tokio::io::duplex(64 * 1024);
+        let conn = tokio::spawn(HttpServerInternal::handle_connection(
+            server.clone(),
+            stream,
+            ADDR.into(),
+            None,
+            None,
+        ));
+        client.write_all(input).await.unwrap();
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await.unwrap();
+        // The server lingers until the client closes its side too
+        drop(client);
+        conn.await.unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Starts shutting down the server while the request is being routed
+    struct ShutdownDuring(Arc<OnceLock<ShutdownHandle>>);
+
+    impl Router for ShutdownDuring {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            self.0.get().unwrap().shutdown(ShutdownReason::Requested);
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    #[tokio::test]
+    async fn close_on_shutdown() {
+        let handle = Arc::new(OnceLock::new());
+        let server = server(ShutdownDuring(handle.clone()), HttpServerConfig::default());
+        handle.set(server.shutdown.clone()).unwrap();
+        let output = exchange(
+            &server,
+            b"GET / HTTP/1.1\r\nHost: a\r\n\r\nGET / HTTP/1.1\r\nHost: a\r\n\r\n",
+        )
+        .await;
+        // Only the in-flight request is answered, and the client is told the connection closes
+        assert_eq!(output.matches("HTTP/1.1 200").count(), 1, "{output}");
+        assert!(output.contains("Connection: Close\r\n"), "{output}");
+    }
+
+    struct Connection;
+
+    impl Router for Connection {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let info = request.connection().expect("received by the server");
+            let body = format!(
+                "{} {:?} {:?} {}",
+                info.id,
+                info.remote_addr,
+                info.tls.is_some(),
+                info.requests
+            );
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(bytes::Bytes::from(body))
+                .build())
+        }
+    }
+
+    #[tokio::test]
+    async fn connection_info() {
+        let server = server(Connection, HttpServerConfig::default());
+        let request = "GET / HTTP/1.1\r\nHost: a\r\n\r\n";
+        let output = exchange(
+            &server,
+            format!("{request}{request}GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+                .as_bytes(),
+        )
+        .await;
+        for count in 1..=3 {
+            assert!(
+                output.contains(&format!("\r\n\r\n0 Some({ADDR}) false {count}")),
+                "{output}"
+            );
+        }
+        // Every connection gets its own id
+        let output = exchange(
+            &server,
+            b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(
+            output.ends_with(&format!("1 Some({ADDR}) false 1")),
+            "{output}"
+        );
+    }
+
+    #[tokio::test]
+    async fn lingering_close() {
+        let clock = Arc::new(clock::MockClock::new());
+        let config = HttpServerConfig {
+            clock: clock.clone(),
+            ..HttpServerConfig::default()
+        };
+        let server = server(Connection, config);
+        let (mut client, stream) = tokio::io::duplex(64 * 1024);
+        let conn = tokio::spawn(HttpServerInternal::handle_connection(
+            server,
+            stream,
+            ADDR.into(),
+            None,
+            None,
+        ));
+        // The client sends more after a request which closes the connection
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\nGET /next")
+            .await
+            .unwrap();
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        assert!(output.starts_with("HTTP/1.1 200 OK"), "{output}");
+
+        // The server has closed its side, but keeps reading until the linger timeout
+        client.write_all(b" HTTP/1.1\r\n").await.unwrap();
+        tokio::task::yield_now().await;
+        assert!(!conn.is_finished());
+        clock.advance(HttpServerConfig::default().linger_timeout);
+        conn.await.unwrap();
+    }
+
+    /// `/slow` waits until `/fast` has been routed, `/echo` answers with the body
+    #[derive(Default)]
+    struct Pipelined(tokio::sync::Notify);
+
+    impl Router for Pipelined {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let body = match request.target.as_ref() {
+                b"/slow" => {
+                    self.0.notified().await;
+                    bytes::Bytes::from_static(b"slow")
+                }
+                b"/fast" => {
+                    self.0.notify_one();
+                    bytes::Bytes::from_static(b"fast")
+                }
+                _ => request.body.collect(None).await?,
+            };
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(body)
+                .build())
+        }
+    }
+
+    fn bodies(output: &str) -> Vec<&str> {
+        output
+            .split("HTTP/1.1 200 OK")
+            .skip(1)
+            .map(|res| res.rsplit("\r\n").next().unwrap())
+            .collect()
+    }
+
+    const PIPELINE: &str = "GET /fast HTTP/1.1\r\nHost: a\r\n\r\n\
+        POST /echo HTTP/1.1\r\nHost: a\r\nContent-Length: 4\r\n\r\necho\
+        GET /fast HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n";
+
+    #[tokio::test]
+    async fn pipelining() {
+        let server = server(Pipelined::default(), HttpServerConfig::default());
+        let output = exchange(&server, PIPELINE.as_bytes()).await;
+        assert_eq!(bodies(&output), ["fast", "echo", "fast"], "{output}");
+    }
+
+    /// Answers with a body, which `/empty` also sets on its 204 No Content
+    struct WithBody;
+
+    impl Router for WithBody {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let status = match request.target.as_ref() {
+                b"/empty" => StatusCode::NO_CONTENT,
+                _ => StatusCode::OK,
+            };
+            Ok(ResponseBuilder::from_req(request, status)
+                .body(bytes::Bytes::from_static(b"hello"))
+                .build())
+        }
+    }
+
+    #[tokio::test]
+    async fn responses_without_body() {
+        let server = server(WithBody, HttpServerConfig::default());
+        // The connection stays usable, so the next response isn't read as a body
+        let output = exchange(
+            &server,
+            b"HEAD / HTTP/1.1\r\nHost: a\r\n\r\n\
+              GET /empty HTTP/1.1\r\nHost: a\r\n\r\n\
+              GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert_eq!(
+            output,
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n\
+             HTTP/1.1 204 No Content\r\n\r\n\
+             HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 5\r\n\r\nhello"
+        );
+    }
+
+    /// Answers with the protocol it would switch to
+    struct Upgrading;
+
+    impl Router for Upgrading {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let supported = [Protocol::new("websocket")];
+            let selected = Protocol::select(request, &supported)
+                .map_or("none".to_owned(), ToString::to_string);
+            Ok(ResponseBuilder::text(selected).build())
+        }
+    }
+
+    #[tokio::test]
+    async fn upgrade_offer_reaches_router() {
+        let server = HttpServer::with_config(ADDR, Upgrading, HttpServerConfig::default());
+        let (mut client, stream) = tokio::io::duplex(64 * 1024);
+        let conn = tokio::spawn(server.serve_connection(stream, None));
+        client
+            .write_all(
+                b"GET / HTTP/1.1\r\nHost: a\r\nConnection: Upgrade\r\nUpgrade: WebSocket\r\n\r\n\
+                  GET / HTTP/1.1\r\nHost: a\r\nUpgrade: WebSocket\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await.unwrap();
+        drop(client);
+        conn.await.unwrap();
+        // The hop-by-hop fields are stripped, and an Upgrade field without the connection
+        // option is no offer
+        assert_eq!(
+            bodies(&String::from_utf8(output).unwrap()),
+            ["websocket", "none"]
+        );
+    }
+
+    #[tokio::test]
+    async fn http_1_0_keep_alive_option() {
+        let server = server(Pipelined::default(), HttpServerConfig::default());
+        let input = "GET /fast HTTP/1.0\r\nConnection: Upgrade, keep-alive\r\n\r\n\
+            GET /fast HTTP/1.0\r\n\r\n";
+        let output = exchange(&server, input.as_bytes()).await;
+        // The responses are still HTTP/1.1, only their framing changes
+        let responses: Vec<_> = output.split("HTTP/1.1 200 OK").skip(1).collect();
+        assert_eq!(responses.len(), 2, "{output}");
+        assert!(
+            responses[0].contains("Connection: Keep-Alive\r\n"),
+            "{output}"
+        );
+        assert!(responses[1].contains("Connection: Close\r\n"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn keep_alive_advertised() {
+        let config = HttpServerConfig {
+            advertise_keep_alive: true,
+            max_requests_per_connection: NonZeroU64::new(2),
+            keep_alive_timeout: Duration::from_secs(5),
+            ..HttpServerConfig::default()
+        };
+        let server = server(Pipelined::default(), config);
+        let request = "GET /fast HTTP/1.1\r\nHost: a\r\n\r\n";
+        let output = exchange(&server, request.repeat(3).as_bytes()).await;
+        let responses: Vec<_> = output.split("HTTP/1.1 200 OK").skip(1).collect();
+        // The connection is closed after the second request, the third is never answered
+        assert_eq!(responses.len(), 2, "{output}");
+        assert!(
+            responses[0].contains("Keep-Alive: timeout=5, max=1\r\n"),
+            "{output}"
+        );
+        assert!(responses[1].contains("Connection: Close\r\n"), "{output}");
+        assert!(!responses[1].contains("Keep-Alive"), "{output}");
+    }
+
+    #[tokio::test]
+    async fn concurrent_pipelining() {
+        let config = HttpServerConfig {
+            max_pipelined_requests: NonZeroUsize::new(4).unwrap(),
+            ..HttpServerConfig::default()
+        };
+        let server = server(Pipelined::default(), config);
+        // Routed one after another, the first request would never complete
+        let input = format!("GET /slow HTTP/1.1\r\nHost: a\r\n\r\n{PIPELINE}");
+        let exchange = exchange(&server, input.as_bytes());
+        let output = tokio::time::timeout(Duration::from_secs(5), exchange)
+            .await
+            .expect("pipelined requests are routed at once");
+        assert_eq!(
+            bodies(&output),
+            ["slow", "fast", "echo", "fast"],
+            "{output}"
+        );
+    }
+
+    #[tokio::test]
+    async fn pooled_buffers() {
+        let pool = BufferPool::default();
+        let config = HttpServerConfig {
+            buffer_pool: pool.clone(),
+            ..HttpServerConfig::default()
+        };
+        let server = server(Pipelined::default(), config);
+        let output = exchange(&server, PIPELINE.as_bytes()).await;
+        assert_eq!(bodies(&output), ["fast", "echo", "fast"], "{output}");
+        // The read and write buffers of the closed connection can be reused
+        assert_eq!(pool.retained_bytes(), 2 * pool.buffer_size());
+    }
+
+    struct Failing;
+
+    impl Router for Failing {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            match request.target.as_ref() {
+                b"/missing" => Err(RouterError::NotFound),
+                b"/conflict" => Err(RouterError::Custom(
+                    StatusCode::CONFLICT,
+                    "already exists".to_string(),
+                )),
+                _ => Err(RouterError::Generic("failed".into())),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn error_handler() {
+        let config = HttpServerConfig {
+            error_handler: SharedErrorHandler::new(|req: &Request, err: &RouterError| {
+                ResponseBuilder::from_req(req, err.status_code())
+                    .body(bytes::Bytes::from(format!("{{\"error\":\"{err}\"}}")))
+                    .build()
+            }),
+            ..HttpServerConfig::default()
+        };
+        let server = server(Failing, config);
+        let output = exchange(
+            &server,
+            b"GET /missing HTTP/1.1\r\nHost: a\r\n\r\n\
+              GET /conflict HTTP/1.1\r\nHost: a\r\n\r\n\
+              GET /broken HTTP/1.1\r\nHost: a\r\n\r\n\
+              GET /missing HTTP/1.1\r\nHost: a\r\n\r\n",
+        )
+        .await;
+        // Client errors keep the connection open, the server error closes it
+        assert!(output.starts_with("HTTP/1.1 404"), "{output}");
+        assert!(output.contains("{\"error\":\"not found\"}"), "{output}");
+        assert!(output.contains("HTTP/1.1 409"), "{output}");
+        assert!(
+            output.contains("{\"error\":\"already exists\"}"),
+            "{output}"
+        );
+        assert!(output.contains("HTTP/1.1 500"), "{output}");
+        assert_eq!(output.matches("HTTP/1.1 ").count(), 3, "{output}");
+    }
+
+    #[tokio::test]
+    async fn trace() {
+        const TRACE: &[u8] = b"TRACE /a?b HTTP/1.1\r\nHost: a\r\nAuthorization: Basic YTpi\r\n\
+            Cookie: session=1\r\nX-Custom: 1\r\nConnection: close\r\n\r\n";
+        let output = exchange(&server(Failing, HttpServerConfig::default()), TRACE).await;
+        assert!(output.starts_with("HTTP/1.1 405"), "{output}");
+        assert!(output.contains("Allow: GET, HEAD, "), "{output}");
+
+        let config = HttpServerConfig {
+            enable_trace: true,
+            ..HttpServerConfig::default()
+        };
+        let output = exchange(&server(Failing, config), TRACE).await;
+        assert!(output.starts_with("HTTP/1.1 200"), "{output}");
+        assert!(
+            output.contains("Content-Type: message/http\r\n"),
+            "{output}"
+        );
+        assert!(
+            output.ends_with("\r\n\r\nTRACE /a?b HTTP/1.1\r\nHost: a\r\nX-Custom: 1\r\n\r\n"),
+            "{output}"
+        );
+    }
+
+    #[tokio::test]
+    async fn server_wide_options() {
+        // The router would fail every request, so it must not see `*`
+        let output = exchange(
+            &server(Failing, HttpServerConfig::default()),
+            b"OPTIONS * HTTP/1.1\r\nHost: a\r\n\r\n\
+              GET * HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(output.starts_with("HTTP/1.1 204"), "{output}");
+        assert!(
+            output.contains("Allow: GET, HEAD, POST, PUT, DELETE, PATCH, OPTIONS\r\n"),
+            "{output}"
+        );
+        assert!(output.contains("HTTP/1.1 400"), "{output}");
+
+        let config = HttpServerConfig {
+            server_options: SharedServerOptions::new(|req: &Request| {
+                ResponseBuilder::from_req(req, StatusCode::OK)
+                    .body(bytes::Bytes::from_static(b"server"))
+                    .build()
+            }),
+            ..HttpServerConfig::default()
+        };
+        let output = exchange(
+            &server(Failing, config),
+            b"OPTIONS * HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(output.starts_with("HTTP/1.1 200"), "{output}");
+        assert!(output.ends_with("\r\n\r\nserver"), "{output}");
+    }
+
+    /// Holds requests to `/block` until released
+    struct Blocking {
+        entered: Arc<tokio::sync::Notify>,
+        release: Arc<tokio::sync::Notify>,
+    }
+
+    impl Router for Blocking {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            if request.target.as_ref() == b"/block" {
+                self.entered.notify_one();
+                self.release.notified().await;
+            }
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    #[tokio::test]
+    async fn shed_load() {
+        let entered = Arc::new(tokio::sync::Notify::new());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let config = HttpServerConfig {
+            max_connections: NonZeroUsize::new(1),
+            max_in_flight_requests: NonZeroUsize::new(1),
+            ..HttpServerConfig::default()
+        };
+        let server = server(
+            Blocking {
+                entered: entered.clone(),
+                release: release.clone(),
+            },
+            config,
+        );
+        let blocked = tokio::spawn({
+            let server = server.clone();
+            async move {
+                exchange(
+                    &server,
+                    b"GET /block HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n",
+                )
+                .await
+            }
+        });
+        entered.notified().await;
+        const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n";
+        let output = exchange(&server, REQUEST).await;
+        assert!(output.starts_with("HTTP/1.1 503"), "{output}");
+
+        release.notify_one();
+        let output = blocked.await.unwrap();
+        assert!(output.starts_with("HTTP/1.1 200"), "{output}");
+        let output = exchange(&server, REQUEST).await;
+        assert!(output.starts_with("HTTP/1.1 200"), "{output}");
+
+        let permit = server.try_connection_permit().unwrap();
+        assert!(permit.is_some());
+        assert!(server.try_connection_permit().is_err());
+        drop(permit);
+        assert!(server.try_connection_permit().is_ok());
+    }
+
+    #[tokio::test]
+    async fn records_metrics() {
+        let server = server(Failing, HttpServerConfig::default());
+        const INPUT: &[u8] = b"GET /missing HTTP/1.1\r\nHost: a\r\n\r\n\
+              GET /missing HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n";
+        let output = exchange(&server, INPUT).await;
+        let metrics = &server.metrics;
+        assert_eq!(metrics.requests(&Method::GET, StatusCode::NOT_FOUND), 2);
+        assert_eq!(metrics.latency().count(), 2);
+        assert_eq!(metrics.bytes_received(), INPUT.len() as u64);
+        assert_eq!(metrics.bytes_sent(), output.len() as u64);
+        assert_eq!(metrics.connections_accepted(), 1);
+        assert_eq!(metrics.connections_active(), 0);
+    }
+
+    #[tokio::test]
+    async fn reload_config() {
+        let server = server(Connection, HttpServerConfig::default());
+        const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: a\r\nX-Trace: 1\r\n\r\n";
+        let (mut client, stream) = tokio::io::duplex(64 * 1024);
+        let conn = tokio::spawn(HttpServerInternal::handle_connection(
+            server.clone(),
+            stream,
+            ADDR.into(),
+            None,
+            None,
+        ));
+        client.write_all(REQUEST).await.unwrap();
+        let mut buf = [0; 1024];
+        assert!(client.read(&mut buf).await.unwrap() > 0);
+
+        server.reload_config(HttpServerConfig {
+            max_header_count: NonZeroUsize::MIN,
+            keep_alive_timeout: Duration::from_secs(1),
+            ..HttpServerConfig::default()
+        });
+        let output = exchange(&server, REQUEST).await;
+        assert!(output.starts_with("HTTP/1.1 431"), "{output}");
+        // Settings fixed once the server starts are kept
+        assert_eq!(server.config().keep_alive_timeout, Duration::from_secs(75));
+
+        // The open connection keeps the config it was accepted with
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await.unwrap();
+        assert!(output.starts_with(b"HTTP/1.1 200"));
+        drop(client);
+        conn.await.unwrap();
+    }
+
+    /// Answers once notified
+    struct Gated(Arc<tokio::sync::Notify>);
+
+    impl Router for Gated {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            self.0.notified().await;
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    #[tokio::test]
+    async fn server_handle() {
+        let clock = Arc::new(clock::MockClock::new());
+        let gate = Arc::new(tokio::sync::Notify::new());
+        let config = HttpServerConfig {
+            clock: clock.clone(),
+            ..HttpServerConfig::default()
+        };
+        let server = server(Gated(gate.clone()), config);
+        let handle = ServerHandle::new(&server.connections, server.shutdown_signal.clone());
+        let connect = || {
+            let (client, stream) = tokio::io::duplex(1024);
+            let conn = HttpServerInternal::handle_connection(
+                server.clone(),
+                stream,
+                ADDR.into(),
+                None,
+                None,
+            );
+            (client, tokio::spawn(conn))
+        };
+
+        let (mut busy, busy_conn) = connect();
+        busy.write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+            .await
+            .unwrap();
+        while handle.in_flight_requests() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let (_idle, idle_conn) = connect();
+        while handle.active_connections() < 2 {
+            tokio::task::yield_now().await;
+        }
+        clock.advance(Duration::from_secs(5));
+        let stats = handle.connections();
+        assert_eq!(stats.len(), 2);
+        assert!(stats[0].busy);
+        assert_eq!(stats[0].requests, 1);
+        assert_eq!(stats[0].remote_addr, Some(ADDR));
+        assert!(!stats[1].busy);
+        assert_eq!(stats[1].requests, 0);
+        assert_eq!(stats[1].age, Duration::from_secs(5));
+
+        // Only the connection without a request in progress is closed
+        assert_eq!(handle.close_idle(Duration::from_secs(1)), 1);
+        idle_conn.await.unwrap();
+        assert_eq!(handle.active_connections(), 1);
+
+        gate.notify_one();
+        let mut buf = [0; 1024];
+        let n = busy.read(&mut buf).await.unwrap();
+        assert!(buf[..n].starts_with(b"HTTP/1.1 200"));
+        assert_eq!(handle.in_flight_requests(), 0);
+        assert!(handle.close_connection(stats[0].id));
+        busy_conn.await.unwrap();
+        assert_eq!(handle.active_connections(), 0);
+        assert!(!handle.close_connection(stats[0].id));
+
+        assert!(!handle.is_draining());
+        server.shutdown.shutdown(ShutdownReason::Requested);
+        assert!(handle.is_draining());
+    }
+
+    #[tokio::test]
+    async fn limit_responses() {
+        let config = HttpServerConfig {
+            max_header_line_bytes: NonZeroUsize::new(64).unwrap(),
+            ..HttpServerConfig::default()
+        };
+        let server = server(Failing, config);
+        let long = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(64));
+        let output = exchange(&server, long.as_bytes()).await;
+        assert!(output.starts_with("HTTP/1.1 431"), "{output}");
+        assert!(output.contains("Connection: Close\r\n"), "{output}");
+
+        // A client which closes the connection between requests is not sent an error
+        let (mut client, stream) = tokio::io::duplex(1024);
+        client.shutdown().await.unwrap();
+        HttpServerInternal::handle_connection(server, stream, ADDR.into(), None, None).await;
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await.unwrap();
+        assert!(output.is_empty());
+    }
+}
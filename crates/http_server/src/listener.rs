@@ -0,0 +1,877 @@
+//! The sockets the server accepts connections on
+
+use std::{
+    fmt, io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+#[cfg(unix)]
+use std::{os::unix::fs::FileTypeExt, os::unix::fs::PermissionsExt, path::PathBuf};
+
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpSocket, TcpStream},
+};
+
+/// A Unix domain socket to listen on, for servers behind a reverse proxy or sidecar on the same
+/// host
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixSocket {
+    path: PathBuf,
+    mode: Option<u32>,
+    remove_existing: bool,
+    remove_on_shutdown: bool,
+}
+
+#[cfg(unix)]
+impl UnixSocket {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            mode: None,
+            remove_existing: true,
+            remove_on_shutdown: true,
+        }
+    }
+
+    /// The permissions of the socket file, such as `0o660` to only let the owner and group
+    /// connect, the umask applies otherwise
+    pub fn with_mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Whether a socket left at the path by a previous server is removed before binding, on by
+    /// default, other kinds of files are never removed
+    pub fn with_remove_existing(mut self, remove: bool) -> Self {
+        self.remove_existing = remove;
+        self
+    }
+
+    /// Whether the socket file is removed once the server stops listening, on by default
+    pub fn with_remove_on_shutdown(mut self, remove: bool) -> Self {
+        self.remove_on_shutdown = remove;
+        self
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    fn bind(&self) -> io::Result<std::os::unix::net::UnixListener> {
+        if self.remove_existing
+            && std::fs::symlink_metadata(&self.path).is_ok_and(|meta| meta.file_type().is_socket())
+        {
+            std::fs::remove_file(&self.path)?;
+        }
+        let listener = std::os::unix::net::UnixListener::bind(&self.path)?;
+        if let Some(mode) = self.mode {
+            std::fs::set_permissions(&self.path, std::fs::Permissions::from_mode(mode))?;
+        }
+        Ok(listener)
+    }
+}
+
+/// Where the server listens
+#[derive(Debug, Clone)]
+pub(crate) enum Listen {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix(UnixSocket),
+    /// A socket bound by someone else, such as a supervisor which keeps it open across restarts
+    BoundTcp(Arc<std::net::TcpListener>),
+    #[cfg(unix)]
+    BoundUnix(Arc<std::os::unix::net::UnixListener>),
+}
+
+/// Whether several TCP sockets can listen on one address, each with its own accept queue
+const REUSE_PORT: bool = cfg!(all(
+    unix,
+    not(any(target_os = "solaris", target_os = "illumos"))
+));
+
+/// The listeners of the workers of [`crate::HttpServer::serve_multithreaded`], bound before any
+/// worker starts so a failed bind is returned to the caller
+pub(crate) struct WorkerListens {
+    pub(crate) listens: Vec<Listen>,
+    /// The socket file, removed once every worker has stopped
+    #[cfg(unix)]
+    cleanup: Option<PathBuf>,
+}
+
+impl Listen {
+    /// Binds a listener for each of `workers`
+    /// A TCP address is bound once per worker with SO_REUSEPORT, so the kernel spreads
+    /// connections over their accept queues, other listeners are shared by every worker
+    pub(crate) fn bind_workers(
+        &self,
+        options: &TcpOptions,
+        workers: usize,
+    ) -> io::Result<WorkerListens> {
+        #[cfg(unix)]
+        let mut cleanup = None;
+        let shared = match self {
+            Self::Tcp(addr) => {
+                let mut addr = *addr;
+                let mut listens = Vec::with_capacity(workers);
+                for _ in 0..if REUSE_PORT { workers } else { 1 } {
+                    let socket = socket2::Socket::new(
+                        socket2::Domain::for_address(addr),
+                        socket2::Type::STREAM,
+                        Some(socket2::Protocol::TCP),
+                    )?;
+                    socket.set_reuse_address(true)?;
+                    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+                    socket.set_reuse_port(true)?;
+                    if let Some(size) = options.recv_buffer_size {
+                        socket.set_recv_buffer_size(size)?;
+                    }
+                    socket.bind(&addr.into())?;
+                    socket.listen(options.backlog.try_into().unwrap_or(i32::MAX))?;
+                    let listener = std::net::TcpListener::from(socket);
+                    // The first bind picks the port when it is 0, the others take the same one
+                    addr = listener.local_addr()?;
+                    listens.push(Self::BoundTcp(Arc::new(listener)));
+                }
+                if listens.len() == workers {
+                    return Ok(WorkerListens {
+                        listens,
+                        #[cfg(unix)]
+                        cleanup,
+                    });
+                }
+                listens.swap_remove(0)
+            }
+            #[cfg(unix)]
+            Self::Unix(socket) => {
+                cleanup = socket.remove_on_shutdown.then(|| socket.path.clone());
+                Self::BoundUnix(Arc::new(socket.bind()?))
+            }
+            bound => bound.clone(),
+        };
+        Ok(WorkerListens {
+            listens: vec![shared; workers],
+            #[cfg(unix)]
+            cleanup,
+        })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for WorkerListens {
+    fn drop(&mut self) {
+        if let Some(path) = &self.cleanup {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// SPEC: sd_listen_fds(3), passed sockets start at file descriptor 3
+#[cfg(unix)]
+const LISTEN_FDS_START: std::os::fd::RawFd = 3;
+
+/// Takes the socket passed by systemd socket activation, or another supervisor following the
+/// same protocol, through the LISTEN_PID and LISTEN_FDS environment variables
+/// Only the first passed socket is used, and it can only be taken once
+#[cfg(unix)]
+pub(crate) fn socket_activation() -> io::Result<Listen> {
+    use std::os::fd::{FromRawFd, IntoRawFd};
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+
+    let var = |name| {
+        std::env::var(name)
+            .ok()
+            .and_then(|var| var.parse::<u32>().ok())
+    };
+    // The variables are inherited by children, which must not take the sockets
+    if var("LISTEN_PID") != Some(std::process::id()) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no sockets were passed to this process",
+        ));
+    }
+    let count = var("LISTEN_FDS").unwrap_or(0);
+    if count == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no sockets were passed to this process",
+        ));
+    }
+    if count > 1 {
+        log::warn!("{count} sockets were passed, only the first is used");
+    }
+    if TAKEN.swap(true, Ordering::SeqCst) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "the passed socket was already taken",
+        ));
+    }
+    // SAFETY: the supervisor passed the descriptor to this process to own, and it is only taken
+    // once
+    let tcp = unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FDS_START) };
+    // The address of a socket of another family can't be read as an IP address
+    if tcp.local_addr().is_ok() {
+        return Ok(Listen::BoundTcp(Arc::new(tcp)));
+    }
+    // SAFETY: ownership moves from the TCP listener
+    let unix = unsafe { std::os::unix::net::UnixListener::from_raw_fd(tcp.into_raw_fd()) };
+    unix.local_addr()?;
+    Ok(Listen::BoundUnix(Arc::new(unix)))
+}
+
+/// Socket options for the TCP listener and every connection it accepts, the defaults leave the
+/// operating system defaults in place
+#[derive(Debug, Clone)]
+pub struct TcpOptions {
+    /// Sets TCP_NODELAY on connections, so small writes are sent without waiting to be coalesced
+    pub nodelay: bool,
+    /// Sends keepalive probes on idle connections to find peers which went away, None disables
+    /// them
+    pub keepalive: Option<TcpKeepalive>,
+    /// How many connections the kernel queues before they are accepted, not applied to listeners
+    /// bound by the caller
+    pub backlog: u32,
+    /// SO_SNDBUF of connections
+    pub send_buffer_size: Option<usize>,
+    /// SO_RCVBUF of connections, also set on the listener so the window scale is negotiated
+    /// accordingly
+    pub recv_buffer_size: Option<usize>,
+    /// Accepts, reads and writes connections through io_uring, binding fails if the kernel
+    /// doesn't offer it
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub io_uring: bool,
+}
+
+impl Default for TcpOptions {
+    fn default() -> Self {
+        Self {
+            nodelay: false,
+            keepalive: None,
+            backlog: 1024,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            io_uring: false,
+        }
+    }
+}
+
+/// SO_KEEPALIVE settings, the interval and retries are ignored where the platform can't set them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    /// How long a connection is idle before the first probe
+    pub time: Duration,
+    /// How long to wait between unanswered probes, None keeps the system default
+    pub interval: Option<Duration>,
+    /// How many unanswered probes close the connection, None keeps the system default
+    pub retries: Option<u32>,
+}
+
+impl TcpKeepalive {
+    pub fn new(time: Duration) -> Self {
+        Self {
+            time,
+            interval: None,
+            retries: None,
+        }
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    fn to_socket2(self) -> socket2::TcpKeepalive {
+        #[allow(unused_mut)]
+        let mut keepalive = socket2::TcpKeepalive::new().with_time(self.time);
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            windows
+        ))]
+        if let Some(interval) = self.interval {
+            keepalive = keepalive.with_interval(interval);
+        }
+        #[cfg(any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "macos",
+            target_os = "ios",
+            target_os = "freebsd",
+            target_os = "netbsd"
+        ))]
+        if let Some(retries) = self.retries {
+            keepalive = keepalive.with_retries(retries);
+        }
+        keepalive
+    }
+}
+
+impl TcpOptions {
+    fn apply(&self, socket: socket2::SockRef<'_>) -> io::Result<()> {
+        if self.nodelay {
+            socket.set_nodelay(true)?;
+        }
+        if let Some(keepalive) = self.keepalive {
+            socket.set_tcp_keepalive(&keepalive.to_socket2())?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) enum Listener {
+    Tcp {
+        listener: TcpListener,
+        options: TcpOptions,
+    },
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring {
+        listener: crate::uring::UringListener,
+        options: TcpOptions,
+    },
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        /// The socket file, removed when the listener is dropped
+        cleanup: Option<PathBuf>,
+    },
+}
+
+impl Listener {
+    pub(crate) fn bind(listen: &Listen, options: &TcpOptions) -> io::Result<Self> {
+        match listen {
+            Listen::Tcp(addr) => {
+                let sock = match addr {
+                    SocketAddr::V4(_) => TcpSocket::new_v4()?,
+                    SocketAddr::V6(_) => TcpSocket::new_v6()?,
+                };
+                sock.set_reuseaddr(true)?;
+                if let Some(size) = options.recv_buffer_size {
+                    sock.set_recv_buffer_size(size.try_into().unwrap_or(u32::MAX))?;
+                }
+                sock.bind(*addr)?;
+                Self::tcp(sock.listen(options.backlog)?, options)
+            }
+            #[cfg(unix)]
+            Listen::Unix(socket) => {
+                let listener = socket.bind()?;
+                listener.set_nonblocking(true)?;
+                let cleanup = socket.remove_on_shutdown.then(|| socket.path.clone());
+                Ok(Self::Unix {
+                    listener: UnixListener::from_std(listener)?,
+                    cleanup,
+                })
+            }
+            Listen::BoundTcp(listener) => {
+                let listener = listener.try_clone()?;
+                listener.set_nonblocking(true)?;
+                Self::tcp(TcpListener::from_std(listener)?, options)
+            }
+            #[cfg(unix)]
+            Listen::BoundUnix(listener) => {
+                let listener = listener.try_clone()?;
+                listener.set_nonblocking(true)?;
+                Ok(Self::Unix {
+                    listener: UnixListener::from_std(listener)?,
+                    // The socket file belongs to whoever bound it
+                    cleanup: None,
+                })
+            }
+        }
+    }
+
+    fn tcp(listener: TcpListener, options: &TcpOptions) -> io::Result<Self> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if options.io_uring {
+            return Ok(Self::Uring {
+                listener: crate::uring::UringListener::new(listener.into_std()?)?,
+                options: options.clone(),
+            });
+        }
+        Ok(Self::Tcp {
+            listener,
+            options: options.clone(),
+        })
+    }
+
+    pub(crate) async fn accept(&self) -> io::Result<(Stream, Peer)> {
+        match self {
+            Self::Tcp { listener, options } => {
+                let (stream, addr) = listener.accept().await?;
+                // The connection is still usable without the options
+                if let Err(err) = options.apply(socket2::SockRef::from(&stream)) {
+                    log::warn!("failed to set socket options for {addr}: {err}");
+                }
+                Ok((Stream::Tcp(stream), Peer(Some(addr))))
+            }
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring { listener, options } => {
+                let (stream, addr) = listener.accept().await?;
+                if let Err(err) = options.apply(socket2::SockRef::from(&stream)) {
+                    log::warn!("failed to set socket options for {addr}: {err}");
+                }
+                Ok((Stream::Uring(stream), Peer(Some(addr))))
+            }
+            #[cfg(unix)]
+            Self::Unix { listener, .. } => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Stream::Unix(stream), Peer(None)))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Self::Unix {
+            cleanup: Some(path),
+            ..
+        } = self
+        {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// How the server reacts to a failed accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptErrorKind {
+    /// Only the connection being accepted failed, such as when the client reset it while it was
+    /// queued, the next one is accepted right away
+    Connection,
+    /// The process or system is out of a resource, such as file descriptors (EMFILE), the server
+    /// backs off before accepting again
+    Transient,
+    /// The listener can't accept any more connections, so the server stops
+    Fatal,
+}
+
+impl AcceptErrorKind {
+    pub fn of(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut => Self::Connection,
+            io::ErrorKind::InvalidInput
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::Unsupported => Self::Fatal,
+            _ => Self::Transient,
+        }
+    }
+}
+
+/// Told about every failed accept, for example to count them or to alert when the server runs
+/// out of file descriptors
+#[derive(Clone)]
+pub struct AcceptErrorHandler(Arc<AcceptErrorFn>);
+
+type AcceptErrorFn = dyn Fn(&io::Error, AcceptErrorKind) + Send + Sync;
+
+impl AcceptErrorHandler {
+    pub fn new(handler: impl Fn(&io::Error, AcceptErrorKind) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(handler))
+    }
+
+    pub(crate) fn call(&self, err: &io::Error, kind: AcceptErrorKind) {
+        (self.0)(err, kind)
+    }
+}
+
+impl fmt::Debug for AcceptErrorHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AcceptErrorHandler")
+    }
+}
+
+/// The delay before accepting again after transient errors, doubling with every consecutive
+/// error and randomized by up to half so many servers on a host don't retry in lockstep
+#[derive(Debug, Default)]
+pub(crate) struct AcceptBackoff {
+    delay: Option<Duration>,
+}
+
+impl AcceptBackoff {
+    const MIN: Duration = Duration::from_millis(5);
+    const MAX: Duration = Duration::from_secs(1);
+
+    pub(crate) fn next(&mut self) -> Duration {
+        let delay = self
+            .delay
+            .map_or(Self::MIN, |delay| (delay * 2).min(Self::MAX));
+        self.delay = Some(delay);
+        let jitter = delay.mul_f64(random_fraction() / 2.0);
+        delay - jitter
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.delay = None;
+    }
+}
+
+/// A number in [0, 1), the keys of a new [`RandomState`] are random enough for jitter
+///
+/// [`RandomState`]: std::hash::RandomState
+fn random_fraction() -> f64 {
+    use std::hash::{BuildHasher, RandomState};
+
+    (RandomState::new().hash_one(0u8) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// The address of the peer of a connection, Unix domain socket peers have none
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Peer(pub(crate) Option<SocketAddr>);
+
+impl From<SocketAddr> for Peer {
+    fn from(addr: SocketAddr) -> Self {
+        Self(Some(addr))
+    }
+}
+
+impl fmt::Display for Peer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(addr) => write!(f, "{addr}"),
+            None => f.write_str("unix socket peer"),
+        }
+    }
+}
+
+pub(crate) enum Stream {
+    Tcp(TcpStream),
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    Uring(crate::uring::UringStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    /// The address the client connected to, Unix domain sockets have none
+    pub(crate) fn local_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(stream) => stream.local_addr().ok(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(stream) => stream.local_addr().ok(),
+            #[cfg(unix)]
+            Self::Unix(_) => None,
+        }
+    }
+
+    pub(crate) fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.try_write(buf),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(stream) => stream.try_write(buf),
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.try_write(buf),
+        }
+    }
+}
+
+macro_rules! delegate {
+    ($self:ident, $stream:ident => $call:expr) => {
+        match $self.get_mut() {
+            Stream::Tcp($stream) => $call,
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Stream::Uring($stream) => $call,
+            #[cfg(unix)]
+            Stream::Unix($stream) => $call,
+        }
+    };
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_read(cx, buf))
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        delegate!(self, stream => Pin::new(stream).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_flush(cx))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        delegate!(self, stream => Pin::new(stream).poll_shutdown(cx))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        delegate!(self, stream => Pin::new(stream).poll_write_vectored(cx, bufs))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Self::Tcp(stream) => stream.is_write_vectored(),
+            #[cfg(all(target_os = "linux", feature = "io-uring"))]
+            Self::Uring(_) => false,
+            #[cfg(unix)]
+            Self::Unix(stream) => stream.is_write_vectored(),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{os::fd::AsRawFd, time::Duration};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{
+        HttpServer, Router, RouterError,
+        http::{
+            request::Request,
+            response::{Response, ResponseBuilder},
+        },
+        shutdown::ShutdownReason,
+    };
+
+    struct Remote;
+
+    impl Router for Remote {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::text(format!("{:?}", request.remote)).build())
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_unix_socket() {
+        let path = std::env::temp_dir().join(format!("carbon-unix-{}.sock", std::process::id()));
+        // A socket left behind by a previous server is replaced
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        let server = HttpServer::unix(UnixSocket::new(&path).with_mode(0o600), Remote);
+        let shutdown = server.shutdown_handle();
+        let serve = tokio::spawn(async move { server.serve().await });
+
+        let mut client = loop {
+            match UnixStream::connect(&path).await {
+                Ok(client) => break client,
+                Err(_) => tokio::time::sleep(Duration::from_millis(5)).await,
+            }
+        };
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"), "{output}");
+        assert!(output.ends_with("\r\n\r\nNone"), "{output}");
+
+        shutdown.shutdown(ShutdownReason::Requested);
+        serve.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn bind_workers() {
+        let listen = Listen::Tcp("127.0.0.1:0".parse().unwrap());
+        let workers = listen.bind_workers(&TcpOptions::default(), 3).unwrap();
+        let addrs: Vec<_> = workers
+            .listens
+            .iter()
+            .map(|listen| match listen {
+                Listen::BoundTcp(listener) => {
+                    (listener.local_addr().unwrap(), listener.as_raw_fd())
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(addrs.len(), 3);
+        assert!(addrs.iter().all(|(addr, _)| *addr == addrs[0].0));
+        assert!(addrs[0].0.port() != 0);
+        // Each worker has a socket of its own, with its own accept queue
+        assert!(addrs[1..].iter().all(|(_, fd)| *fd != addrs[0].1));
+
+        let path = std::env::temp_dir().join(format!("carbon-workers-{}.sock", std::process::id()));
+        let workers = Listen::Unix(UnixSocket::new(&path))
+            .bind_workers(&TcpOptions::default(), 2)
+            .unwrap();
+        assert!(path.exists());
+        drop(workers);
+        assert!(!path.exists());
+    }
+
+    struct Worker;
+
+    impl Router for Worker {
+        async fn route(&self, _: &Request) -> Result<Response, RouterError> {
+            let thread = std::thread::current();
+            Ok(ResponseBuilder::text(thread.name().unwrap_or_default().to_owned()).build())
+        }
+    }
+
+    #[test]
+    fn serve_multithreaded() {
+        use std::io::{Read, Write};
+
+        // Each worker binds the address itself, so it has to be known up front
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = HttpServer::new(addr, Worker);
+        let shutdown = server.shutdown_handle();
+        let serve = std::thread::spawn(move || {
+            server.serve_multithreaded(std::num::NonZeroUsize::new(2).unwrap())
+        });
+
+        for _ in 0..4 {
+            let mut client = loop {
+                match std::net::TcpStream::connect(addr) {
+                    Ok(client) => break client,
+                    Err(_) => std::thread::sleep(Duration::from_millis(5)),
+                }
+            };
+            client
+                .write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut output = String::new();
+            client.read_to_string(&mut output).unwrap();
+            assert!(output.starts_with("HTTP/1.1 200 OK\r\n"), "{output}");
+            assert!(output.contains("\r\n\r\ncarbon-worker-"), "{output}");
+        }
+
+        shutdown.shutdown(ShutdownReason::Requested);
+        serve.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn classify_accept_errors() {
+        for (kind, expected) in [
+            (
+                io::ErrorKind::ConnectionAborted,
+                AcceptErrorKind::Connection,
+            ),
+            (io::ErrorKind::Interrupted, AcceptErrorKind::Connection),
+            (io::ErrorKind::OutOfMemory, AcceptErrorKind::Transient),
+            (io::ErrorKind::InvalidInput, AcceptErrorKind::Fatal),
+        ] {
+            assert_eq!(AcceptErrorKind::of(&kind.into()), expected, "{kind:?}");
+        }
+        // EMFILE has no error kind of its own
+        assert_eq!(
+            AcceptErrorKind::of(&io::Error::from_raw_os_error(24)),
+            AcceptErrorKind::Transient
+        );
+    }
+
+    #[test]
+    fn accept_backoff() {
+        let mut backoff = AcceptBackoff::default();
+        let mut ceiling = AcceptBackoff::MIN;
+        for _ in 0..12 {
+            let delay = backoff.next();
+            assert!(
+                delay > ceiling / 2 && delay <= ceiling,
+                "{delay:?} {ceiling:?}"
+            );
+            ceiling = (ceiling * 2).min(AcceptBackoff::MAX);
+        }
+        backoff.reset();
+        assert!(backoff.next() <= AcceptBackoff::MIN);
+    }
+
+    #[tokio::test]
+    async fn apply_tcp_options() {
+        let options = TcpOptions {
+            nodelay: true,
+            keepalive: Some(
+                TcpKeepalive::new(Duration::from_secs(60))
+                    .with_interval(Duration::from_secs(5))
+                    .with_retries(3),
+            ),
+            backlog: 16,
+            ..TcpOptions::default()
+        };
+        let listener =
+            Listener::bind(&Listen::Tcp("127.0.0.1:0".parse().unwrap()), &options).unwrap();
+        let Listener::Tcp {
+            listener: inner, ..
+        } = &listener
+        else {
+            unreachable!()
+        };
+        let addr = inner.local_addr().unwrap();
+        let _client = TcpStream::connect(addr).await.unwrap();
+        let (Stream::Tcp(stream), _) = listener.accept().await.unwrap() else {
+            unreachable!()
+        };
+        assert!(stream.nodelay().unwrap());
+        let socket = socket2::SockRef::from(&stream);
+        assert!(socket.keepalive().unwrap());
+        #[cfg(target_os = "linux")]
+        {
+            assert_eq!(socket.keepalive_time().unwrap(), Duration::from_secs(60));
+            assert_eq!(socket.keepalive_interval().unwrap(), Duration::from_secs(5));
+            assert_eq!(socket.keepalive_retries().unwrap(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn serve_bound_listener() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = HttpServer::from_listener(listener, Remote);
+        let shutdown = server.shutdown_handle();
+        let serve = tokio::spawn(async move { server.serve().await });
+
+        // The listener is already listening, so the connection is queued until it is accepted
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: a\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut output = String::new();
+        client.read_to_string(&mut output).await.unwrap();
+        let local = client.local_addr().unwrap();
+        assert!(output.ends_with(&format!("Some({local})")), "{output}");
+
+        shutdown.shutdown(ShutdownReason::Requested);
+        serve.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,391 @@
+//! Server counters, rendered in the Prometheus text exposition format
+//!
+//! The server records into the [`ServerMetrics`] of its [`HttpServerConfig`], so the same
+//! metrics can be given to a [`MetricsEndpoint`] which serves them at `/metrics`
+//!
+//! [`HttpServerConfig`]: crate::HttpServerConfig
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        header::{ContentType, HeaderField},
+        method::Method,
+        request::{Request, RequestTarget},
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+};
+
+/// Counts of observations at or below each bound, with their sum
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Not cumulative, the observations above the last bound are counted in the last bucket
+    buckets: Box<[AtomicU64]>,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    /// The default buckets of the Prometheus client libraries, in seconds
+    pub const DEFAULT_BOUNDS: &[f64] = &[
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ];
+
+    /// `bounds` are the upper bounds of the buckets in seconds, in increasing order
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let bucket = self
+            .bounds
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn sum(&self) -> Duration {
+        Duration::from_micros(self.sum_micros.load(Ordering::Relaxed))
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let mut cumulative = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            match self.bounds.get(i) {
+                Some(bound) => writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}"),
+                None => writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {cumulative}"),
+            }
+            .unwrap();
+        }
+        writeln!(out, "{name}_sum {}", self.sum().as_secs_f64()).unwrap();
+        writeln!(out, "{name}_count {cumulative}").unwrap();
+    }
+}
+
+/// Counters of a server, shared between its connections
+#[derive(Debug)]
+pub struct ServerMetrics {
+    connections_accepted: AtomicU64,
+    connections_closed: AtomicU64,
+    connections_rejected: AtomicU64,
+    bytes_received: AtomicU64,
+    bytes_sent: AtomicU64,
+    /// Keyed by method and status, custom methods are counted as `OTHER` so clients can't
+    /// create any number of series
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    latency: Histogram,
+}
+
+impl Default for ServerMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerMetrics {
+    pub fn new() -> Self {
+        Self::with_latency_buckets(Histogram::DEFAULT_BOUNDS)
+    }
+
+    /// `bounds` are the upper bounds of the latency buckets in seconds
+    pub fn with_latency_buckets(bounds: &'static [f64]) -> Self {
+        Self {
+            connections_accepted: AtomicU64::new(0),
+            connections_closed: AtomicU64::new(0),
+            connections_rejected: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            requests: Mutex::default(),
+            latency: Histogram::new(bounds),
+        }
+    }
+
+    pub fn connections_accepted(&self) -> u64 {
+        self.connections_accepted.load(Ordering::Relaxed)
+    }
+
+    /// Connections which are currently open
+    pub fn connections_active(&self) -> u64 {
+        // Closed is read first, so a connection closing in between can't make this underflow
+        let closed = self.connections_closed.load(Ordering::Relaxed);
+        self.connections_accepted().saturating_sub(closed)
+    }
+
+    /// Connections which were closed right away because the server was at its limit
+    pub fn connections_rejected(&self) -> u64 {
+        self.connections_rejected.load(Ordering::Relaxed)
+    }
+
+    /// Bytes read from connections, including request heads
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written to connections, including response heads
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// The number of responses sent with `status` to requests with `method`
+    pub fn requests(&self, method: &Method, status: StatusCode) -> u64 {
+        let key = (Self::method_label(method), status.as_u16());
+        self.requests
+            .lock()
+            .unwrap()
+            .get(&key)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// How long requests took, from their head being parsed to their response being sent
+    pub fn latency(&self) -> &Histogram {
+        &self.latency
+    }
+
+    fn method_label(method: &Method) -> String {
+        match method.is_custom() {
+            true => "OTHER".to_owned(),
+            false => method.to_string(),
+        }
+    }
+
+    pub(crate) fn connection_opened(&self) {
+        self.connections_accepted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn connection_closed(&self) {
+        self.connections_closed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn connection_rejected(&self) {
+        self.connections_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn received(&self, bytes: usize) {
+        self.bytes_received
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_request(&self, method: &Method, status: StatusCode, latency: Duration) {
+        let key = (Self::method_label(method), status.as_u16());
+        *self.requests.lock().unwrap().entry(key).or_insert(0) += 1;
+        self.latency.observe(latency);
+    }
+
+    /// Renders every metric in the Prometheus text exposition format
+    /// SPEC: Prometheus - Exposition formats, Text-based format 0.0.4
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity(2048);
+        fn metric<'a>(out: &mut String, name: &'a str, kind: &str, help: &str) -> &'a str {
+            writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}").unwrap();
+            name
+        }
+        let counters = [
+            (
+                "http_connections_accepted_total",
+                "counter",
+                "Connections accepted by the server",
+                self.connections_accepted(),
+            ),
+            (
+                "http_connections_active",
+                "gauge",
+                "Connections which are currently open",
+                self.connections_active(),
+            ),
+            (
+                "http_connections_rejected_total",
+                "counter",
+                "Connections closed because the server was at its connection limit",
+                self.connections_rejected(),
+            ),
+            (
+                "http_received_bytes_total",
+                "counter",
+                "Bytes read from connections, including request heads",
+                self.bytes_received(),
+            ),
+            (
+                "http_sent_bytes_total",
+                "counter",
+                "Bytes written to connections, including response heads",
+                self.bytes_sent(),
+            ),
+        ];
+        for (name, kind, help, value) in counters {
+            let name = metric(&mut out, name, kind, help);
+            writeln!(out, "{name} {value}").unwrap();
+        }
+
+        let name = metric(
+            &mut out,
+            "http_requests_total",
+            "counter",
+            "Responses sent, by request method and response status",
+        );
+        for ((method, status), count) in self.requests.lock().unwrap().iter() {
+            writeln!(
+                out,
+                "{name}{{method=\"{method}\",status=\"{status}\"}} {count}"
+            )
+            .unwrap();
+        }
+
+        let name = metric(
+            &mut out,
+            "http_request_duration_seconds",
+            "histogram",
+            "Time from a request head being parsed to its response being sent",
+        );
+        self.latency.render(&mut out, name);
+        out
+    }
+}
+
+/// Serves the metrics of a server to GET requests for one path, and routes every other
+/// request to the inner router
+pub struct MetricsEndpoint<R: Router> {
+    inner: R,
+    metrics: Arc<ServerMetrics>,
+    path: Bytes,
+}
+
+impl<R: Router> MetricsEndpoint<R> {
+    /// Serves `metrics` at `/metrics`
+    pub fn new(inner: R, metrics: Arc<ServerMetrics>) -> Self {
+        Self {
+            inner,
+            metrics,
+            path: Bytes::from_static(b"/metrics"),
+        }
+    }
+
+    pub fn with_path(mut self, path: impl Into<Bytes>) -> Self {
+        self.path = path.into();
+        self
+    }
+}
+
+impl<R: Router> Router for MetricsEndpoint<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if request.method == Method::GET
+            && let Ok(RequestTarget::Origin(origin)) = request.target()
+            && let Ok(path) = origin.path()
+            && self.path == path.as_bytes()
+        {
+            let mut res = ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(Bytes::from(self.metrics.render()))
+                .build();
+            res.headers
+                .entry(ContentType::NAME)
+                .push(Bytes::from_static(
+                    b"text/plain; version=0.0.4; charset=utf-8",
+                ));
+            return Ok(res);
+        }
+        self.inner.route(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Body;
+
+    #[test]
+    fn render() {
+        let metrics = ServerMetrics::new();
+        metrics.connection_opened();
+        metrics.connection_opened();
+        metrics.connection_closed();
+        metrics.received(100);
+        metrics.sent(250);
+        metrics.record_request(&Method::GET, StatusCode::OK, Duration::from_millis(3));
+        metrics.record_request(&Method::GET, StatusCode::OK, Duration::from_millis(30));
+        metrics.record_request(
+            &Method::custom(Bytes::from_static(b"BREW")),
+            StatusCode::NOT_IMPLEMENTED,
+            Duration::from_secs(60),
+        );
+        assert_eq!(metrics.connections_active(), 1);
+        assert_eq!(metrics.requests(&Method::GET, StatusCode::OK), 2);
+        assert_eq!(metrics.latency().count(), 3);
+        assert_eq!(metrics.latency().sum(), Duration::from_millis(60_033));
+
+        let text = metrics.render();
+        for line in [
+            "# TYPE http_connections_accepted_total counter",
+            "http_connections_accepted_total 2",
+            "http_connections_active 1",
+            "http_received_bytes_total 100",
+            "http_sent_bytes_total 250",
+            "http_requests_total{method=\"GET\",status=\"200\"} 2",
+            "http_requests_total{method=\"OTHER\",status=\"501\"} 1",
+            "# TYPE http_request_duration_seconds histogram",
+            "http_request_duration_seconds_bucket{le=\"0.005\"} 1",
+            "http_request_duration_seconds_bucket{le=\"0.025\"} 1",
+            "http_request_duration_seconds_bucket{le=\"0.05\"} 2",
+            "http_request_duration_seconds_bucket{le=\"10\"} 2",
+            "http_request_duration_seconds_bucket{le=\"+Inf\"} 3",
+            "http_request_duration_seconds_count 3",
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line}\n{text}");
+        }
+    }
+
+    struct NotFound;
+
+    impl Router for NotFound {
+        async fn route(&self, _: &Request) -> Result<Response, RouterError> {
+            Err(RouterError::NotFound)
+        }
+    }
+
+    #[tokio::test]
+    async fn endpoint() {
+        let metrics = Arc::new(ServerMetrics::new());
+        metrics.connection_opened();
+        let router = MetricsEndpoint::new(NotFound, metrics);
+        let request = |target: &'static [u8]| Request::new(Method::GET, Bytes::from_static(target));
+        let res = router.route(&request(b"/metrics")).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(
+            matches!(res.body, Body::Full(ref body) if body.windows(33).any(|w| w == b"http_connections_accepted_total 1"))
+        );
+        assert!(matches!(
+            router.route(&request(b"/other")).await,
+            Err(RouterError::NotFound)
+        ));
+    }
+}
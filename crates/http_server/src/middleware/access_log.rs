@@ -0,0 +1,393 @@
+use std::{
+    fmt::{self, Write as _},
+    io,
+    net::SocketAddr,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    clock::{SharedClock, TokioClock},
+    http::{
+        Body, HttpDate, HttpVersion,
+        header::{Builtin, ContentLength, HeaderName},
+        json::write_json_string,
+        method::Method,
+        request::Request,
+        response::{Response, StatusCode},
+    },
+};
+
+/// How an [`AccessLogEntry`] is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// `host ident user [time] "request" status bytes`
+    Common,
+    /// The common format followed by `"referer" "user-agent"`
+    #[default]
+    Combined,
+    /// One JSON object per line
+    Json,
+}
+
+/// What is recorded about one request
+#[derive(Debug, Clone)]
+pub struct AccessLogEntry {
+    pub remote: Option<SocketAddr>,
+    /// When the request was received
+    pub time: SystemTime,
+    pub method: Method,
+    pub target: Bytes,
+    pub version: HttpVersion,
+    pub status: StatusCode,
+    /// The size of the response body, `None` if it is streamed without a Content-Length
+    pub bytes_sent: Option<u64>,
+    /// How long the router took to produce the response, not including sending the body
+    pub latency: Duration,
+    pub user_agent: Option<Bytes>,
+    pub referer: Option<Bytes>,
+}
+
+/// Writes `bytes` between double quotes, escaping quotes, backslashes and anything which
+/// is not printable ASCII, so a client can't forge log lines
+fn write_quoted(out: &mut String, bytes: &[u8]) {
+    out.push('"');
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7e => out.push(b as char),
+            _ => write!(out, "\\x{b:02x}").unwrap(),
+        }
+    }
+    out.push('"');
+}
+
+impl AccessLogEntry {
+    /// Formats the entry as one line, without a line ending
+    pub fn format(&self, format: AccessLogFormat) -> String {
+        let mut out = String::with_capacity(128);
+        match format {
+            AccessLogFormat::Common | AccessLogFormat::Combined => {
+                self.write_common(&mut out);
+                if format == AccessLogFormat::Combined {
+                    for field in [&self.referer, &self.user_agent] {
+                        out.push(' ');
+                        match field {
+                            Some(value) => write_quoted(&mut out, value),
+                            None => out.push_str("\"-\""),
+                        }
+                    }
+                }
+            }
+            AccessLogFormat::Json => self.write_json(&mut out),
+        }
+        out
+    }
+
+    fn write_common(&self, out: &mut String) {
+        match self.remote {
+            Some(remote) => write!(out, "{}", remote.ip()).unwrap(),
+            None => out.push('-'),
+        }
+        let (year, month, day, hour, minute, second) = HttpDate::from(self.time).civil();
+        write!(
+            out,
+            " - - [{day:02}/{}/{year:04}:{hour:02}:{minute:02}:{second:02} +0000] ",
+            HttpDate::month_name(month)
+        )
+        .unwrap();
+        let request_line = [
+            self.method.to_string().as_bytes(),
+            b" ",
+            &self.target,
+            b" ",
+            self.version.to_string().as_bytes(),
+        ]
+        .concat();
+        write_quoted(out, &request_line);
+        write!(out, " {}", self.status.as_u16()).unwrap();
+        // An empty body is written as "-"
+        match self.bytes_sent {
+            Some(bytes) if bytes > 0 => write!(out, " {bytes}").unwrap(),
+            _ => out.push_str(" -"),
+        }
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let (year, month, day, hour, minute, second) = HttpDate::from(self.time).civil();
+        out.push_str("{\"remote\":");
+        match self.remote {
+            Some(remote) => write!(out, "\"{}\"", remote.ip()).unwrap(),
+            None => out.push_str("null"),
+        }
+        // SPEC: RFC 3339 - 5.6. Internet Date/Time Format
+        write!(
+            out,
+            ",\"time\":\"{year:04}-{month:02}-{day:02}T{hour:02}:{minute:02}:{second:02}Z\""
+        )
+        .unwrap();
+        out.push_str(",\"method\":");
+        write_json_string(out, self.method.to_string().as_bytes());
+        out.push_str(",\"target\":");
+        write_json_string(out, &self.target);
+        write!(
+            out,
+            ",\"version\":\"{}\",\"status\":{}",
+            self.version,
+            self.status.as_u16()
+        )
+        .unwrap();
+        match self.bytes_sent {
+            Some(bytes) => write!(out, ",\"bytes\":{bytes}").unwrap(),
+            None => out.push_str(",\"bytes\":null"),
+        }
+        write!(out, ",\"latency_us\":{}", self.latency.as_micros()).unwrap();
+        for (name, field) in [("referer", &self.referer), ("user_agent", &self.user_agent)] {
+            write!(out, ",\"{name}\":").unwrap();
+            match field {
+                Some(value) => write_json_string(out, value),
+                None => out.push_str("null"),
+            }
+        }
+        out.push('}');
+    }
+}
+
+/// Where access log lines are written
+pub trait AccessLogSink: Send + Sync + 'static {
+    /// `line` is the entry in the format of the [`AccessLog`], without a line ending
+    fn write(&self, entry: &AccessLogEntry, line: &str);
+}
+
+impl<F> AccessLogSink for F
+where
+    F: Fn(&AccessLogEntry, &str) + Send + Sync + 'static,
+{
+    fn write(&self, entry: &AccessLogEntry, line: &str) {
+        self(entry, line)
+    }
+}
+
+/// Writes lines to the `log` crate at the info level, with the target `access`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl AccessLogSink for LogSink {
+    fn write(&self, _: &AccessLogEntry, line: &str) {
+        log::info!(target: "access", "{}", line);
+    }
+}
+
+/// Writes lines to a writer, such as a file or stdout
+/// Failed writes are dropped, so logging can't fail a request
+pub struct WriterSink<W: io::Write + Send + 'static>(Mutex<W>);
+
+impl<W: io::Write + Send + 'static> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self(Mutex::new(writer))
+    }
+}
+
+impl<W: io::Write + Send + 'static> fmt::Debug for WriterSink<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WriterSink")
+    }
+}
+
+impl<W: io::Write + Send + 'static> AccessLogSink for WriterSink<W> {
+    fn write(&self, _: &AccessLogEntry, line: &str) {
+        let mut writer = self.0.lock().unwrap();
+        let _ = writer
+            .write_all(line.as_bytes())
+            .and_then(|()| writer.write_all(b"\n"));
+    }
+}
+
+/// Records an [`AccessLogEntry`] for every request to a sink
+/// Requests the router failed are recorded with the status of the error, the response
+/// rendered by the error handler is not seen by the log
+pub struct AccessLog<R: Router, S: AccessLogSink = LogSink> {
+    inner: R,
+    sink: S,
+    format: AccessLogFormat,
+    clock: SharedClock,
+}
+
+impl<R: Router> AccessLog<R> {
+    /// Logs in the combined format to [`LogSink`]
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            sink: LogSink,
+            format: AccessLogFormat::default(),
+            clock: TokioClock::shared(),
+        }
+    }
+}
+
+impl<R: Router, S: AccessLogSink> AccessLog<R, S> {
+    pub fn with_sink<T: AccessLogSink>(self, sink: T) -> AccessLog<R, T> {
+        AccessLog {
+            inner: self.inner,
+            sink,
+            format: self.format,
+            clock: self.clock,
+        }
+    }
+
+    pub fn with_format(mut self, format: AccessLogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Times the latency, the wall clock time of entries always comes from the system
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+/// The size of a response body, if it is known before it is sent
+fn body_len(res: &Response) -> Option<u64> {
+    match &res.body {
+        Body::None => Some(0),
+        Body::Full(bytes) => Some(bytes.len() as u64),
+        Body::Stream(_) => res.headers.get_header::<ContentLength>().ok().flatten(),
+    }
+}
+
+impl<R: Router, S: AccessLogSink> Router for AccessLog<R, S> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let time = SystemTime::now();
+        let start = self.clock.now();
+        let res = self.inner.route(request).await;
+        let latency = self.clock.now().duration_since(start);
+
+        let header = |builtin| {
+            request
+                .headers
+                .get(&HeaderName::builtin(builtin))
+                .map(|value| value.collect())
+        };
+        let (status, bytes_sent) = match &res {
+            Ok(res) => (res.status, body_len(res)),
+            Err(err) => (err.status_code(), None),
+        };
+        let entry = AccessLogEntry {
+            remote: request.remote,
+            time,
+            method: request.method.clone(),
+            target: request.target.clone(),
+            version: request.version,
+            status,
+            bytes_sent,
+            latency,
+            user_agent: header(Builtin::UserAgent),
+            referer: header(Builtin::Referer),
+        };
+        self.sink.write(&entry, &entry.format(self.format));
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::UNIX_EPOCH};
+
+    use super::*;
+    use crate::http::{header::UserAgent, request::RequestBuilder, response::ResponseBuilder};
+
+    fn entry() -> AccessLogEntry {
+        AccessLogEntry {
+            remote: Some("192.0.2.1:4000".parse().unwrap()),
+            // SPEC: RFC 9110 - 5.6.7. Date/Time Formats
+            time: UNIX_EPOCH + Duration::from_secs(784_111_777),
+            method: Method::GET,
+            target: Bytes::from_static(b"/a\"b"),
+            version: HttpVersion::HTTP_1_1,
+            status: StatusCode::OK,
+            bytes_sent: Some(1234),
+            latency: Duration::from_micros(1500),
+            user_agent: Some(Bytes::from_static(b"curl/8.0")),
+            referer: None,
+        }
+    }
+
+    #[test]
+    fn formats() {
+        let entry = entry();
+        let common = "192.0.2.1 - - [06/Nov/1994:08:49:37 +0000] \"GET /a\\\"b HTTP/1.1\" 200 1234";
+        assert_eq!(entry.format(AccessLogFormat::Common), common);
+        assert_eq!(
+            entry.format(AccessLogFormat::Combined),
+            format!("{common} \"-\" \"curl/8.0\"")
+        );
+        assert_eq!(
+            entry.format(AccessLogFormat::Json),
+            "{\"remote\":\"192.0.2.1\",\"time\":\"1994-11-06T08:49:37Z\",\"method\":\"GET\",\
+             \"target\":\"/a\\\"b\",\"version\":\"HTTP/1.1\",\"status\":200,\"bytes\":1234,\
+             \"latency_us\":1500,\"referer\":null,\"user_agent\":\"curl/8.0\"}"
+        );
+
+        // A forged line break is escaped
+        let entry = AccessLogEntry {
+            target: Bytes::from_static(b"/\n127.0.0.1"),
+            bytes_sent: None,
+            remote: None,
+            ..entry
+        };
+        let common = entry.format(AccessLogFormat::Common);
+        assert!(common.starts_with("- - - ["), "{common}");
+        assert!(
+            common.ends_with("\"GET /\\x0a127.0.0.1 HTTP/1.1\" 200 -"),
+            "{common}"
+        );
+    }
+
+    struct Routes;
+
+    impl Router for Routes {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            match request.target.as_ref() {
+                b"/" => Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                    .body(Bytes::from_static(b"hello"))
+                    .build()),
+                _ => Err(RouterError::NotFound),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_requests() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let router = AccessLog::new(Routes)
+            .with_format(AccessLogFormat::Common)
+            .with_sink({
+                let lines = lines.clone();
+                move |entry: &AccessLogEntry, line: &str| {
+                    lines.lock().unwrap().push((entry.status, line.to_owned()));
+                }
+            });
+        for target in [&b"/"[..], b"/missing"] {
+            let request = RequestBuilder::get(Bytes::from_static(target))
+                .set_header::<UserAgent>(Bytes::from_static(b"test"))
+                .build();
+            let _ = router.route(&request).await;
+        }
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines[0].0, StatusCode::OK);
+        assert!(
+            lines[0].1.ends_with("\"GET / HTTP/1.1\" 200 5"),
+            "{}",
+            lines[0].1
+        );
+        assert_eq!(lines[1].0, StatusCode::NOT_FOUND);
+        assert!(lines[1].1.ends_with(" 404 -"), "{}", lines[1].1);
+    }
+}
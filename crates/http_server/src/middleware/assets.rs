@@ -0,0 +1,287 @@
+use std::{collections::HashMap, fmt::Write, io, path::Path, sync::Arc};
+
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        Body,
+        header::{CacheControl, ContentType, ETag, EntityTag, HeaderField, HeaderValueTrait},
+        method::Method,
+        mime::content_type_for,
+        request::{Request, RequestTarget},
+        response::{Response, ResponseBuilder, StatusCode, conditional},
+    },
+};
+
+/// A static asset, served under a path containing a hash of its content
+#[derive(Debug)]
+pub struct Asset {
+    url: String,
+    content_type: Bytes,
+    body: Bytes,
+    etag: EntityTag,
+}
+
+impl Asset {
+    /// The fingerprinted path the asset is served under, such as `/static/app.1f0e3dad99908345.css`
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn content_type(&self) -> &Bytes {
+        &self.content_type
+    }
+
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+}
+
+/// Static assets by name, hashed once when they are added
+/// Handlers use [`Self::url`] to reference an asset when rendering, so a new version of an
+/// asset gets a new URL, and the old one can be cached forever
+#[derive(Debug)]
+pub struct AssetManifest {
+    prefix: String,
+    by_name: HashMap<String, Arc<Asset>>,
+    by_url: HashMap<String, Arc<Asset>>,
+}
+
+impl AssetManifest {
+    /// Assets are served under `prefix`, such as `/static`
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: format!("/{}", prefix.trim_matches('/')),
+            by_name: HashMap::new(),
+            by_url: HashMap::new(),
+        }
+    }
+
+    /// Loads every file below `dir`, named by their path relative to it with `/` separators
+    pub fn from_dir(prefix: &str, dir: impl AsRef<Path>) -> io::Result<Self> {
+        let mut manifest = Self::new(prefix);
+        let mut pending = vec![dir.as_ref().to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for entry in std::fs::read_dir(&current)? {
+                let entry = entry?;
+                let path = entry.path();
+                if entry.file_type()?.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+                let name = path
+                    .strip_prefix(dir.as_ref())
+                    .expect("walked below dir")
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                manifest.insert(&name, Bytes::from(std::fs::read(&path)?));
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Adds an asset, guessing its Content-Type from the extension of `name`
+    pub fn insert(&mut self, name: &str, body: Bytes) -> &Asset {
+        self.insert_with_type(
+            name,
+            Bytes::from_static(content_type_for(name).as_bytes()),
+            body,
+        )
+    }
+
+    /// Adds an asset, replacing any asset with the same name, and returns it
+    pub fn insert_with_type(&mut self, name: &str, content_type: Bytes, body: Bytes) -> &Asset {
+        let name = name.trim_start_matches('/');
+        let etag = EntityTag::from_content(&body);
+        let hash = std::str::from_utf8(&etag.tag).expect("hex digits");
+        // The hash goes before the extension, so the extension still identifies the type
+        let file_start = name.rfind('/').map_or(0, |slash| slash + 1);
+        let fingerprinted = match name[file_start..].rfind('.') {
+            Some(dot) if dot > 0 => {
+                let dot = file_start + dot;
+                format!("{}.{}{}", &name[..dot], hash, &name[dot..])
+            }
+            _ => format!("{name}.{hash}"),
+        };
+        let mut url = self.prefix.clone();
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+        url.write_str(&fingerprinted).unwrap();
+
+        let asset = Arc::new(Asset {
+            url: url.clone(),
+            content_type,
+            body,
+            etag,
+        });
+        if let Some(previous) = self.by_name.insert(name.to_owned(), asset.clone()) {
+            self.by_url.remove(&previous.url);
+        }
+        self.by_url.insert(url, asset.clone());
+        &self.by_name[name]
+    }
+
+    /// The fingerprinted URL of the asset called `name`, such as `css/app.css`
+    pub fn url(&self, name: &str) -> Option<&str> {
+        self.get(name).map(Asset::url)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Asset> {
+        self.by_name
+            .get(name.trim_start_matches('/'))
+            .map(|asset| &**asset)
+    }
+
+    /// Every asset with its name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Asset)> {
+        self.by_name
+            .iter()
+            .map(|(name, asset)| (name.as_str(), &**asset))
+    }
+}
+
+/// Serves the assets of an [`AssetManifest`] at their fingerprinted URLs, with a Cache-Control
+/// that lets them be cached forever, and routes every other request to the inner router
+/// The manifest is added to the extensions of routed requests, so handlers can look up URLs
+/// SPEC: RFC 8246 - 2. The immutable Cache-Control Extension
+pub struct StaticAssets<R: Router> {
+    inner: R,
+    manifest: Arc<AssetManifest>,
+}
+
+impl<R: Router> StaticAssets<R> {
+    pub fn new(inner: R, manifest: impl Into<Arc<AssetManifest>>) -> Self {
+        Self {
+            inner,
+            manifest: manifest.into(),
+        }
+    }
+
+    pub fn manifest(&self) -> &Arc<AssetManifest> {
+        &self.manifest
+    }
+
+    fn serve(request: &Request, asset: &Asset) -> Response {
+        let cache_control = vec![
+            Bytes::from_static(b"public"),
+            Bytes::from_static(b"max-age=31536000"),
+            Bytes::from_static(b"immutable"),
+        ];
+        if let Some(mut res) = conditional(request, Some(&asset.etag), None) {
+            cache_control.to_header_value(res.headers.entry(CacheControl::NAME));
+            return res;
+        }
+        let mut res = ResponseBuilder::from_req(request, StatusCode::OK)
+            .set_header::<CacheControl>(cache_control)
+            .set_header::<ETag>(asset.etag.clone())
+            .build();
+        res.headers
+            .entry(ContentType::NAME)
+            .push(asset.content_type.clone());
+        res.body = Body::Full(asset.body.clone());
+        res
+    }
+}
+
+impl<R: Router> Router for StaticAssets<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if request.method == Method::GET
+            && let Ok(RequestTarget::Origin(origin)) = request.target()
+            && let Ok(path) = origin.path()
+            && let Some(asset) = self.manifest.by_url.get(&path)
+        {
+            return Ok(Self::serve(request, asset));
+        }
+        let mut request = request.clone();
+        request.extensions.insert(self.manifest.clone());
+        self.inner.route(&request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header::{Builtin, HeaderName};
+
+    struct Page;
+
+    impl Router for Page {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let manifest = request.extensions.get::<Arc<AssetManifest>>().unwrap();
+            let html = format!(
+                "<link rel=stylesheet href={}>",
+                manifest.url("css/app.css").unwrap()
+            );
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(Bytes::from(html))
+                .build())
+        }
+    }
+
+    fn request(target: String, if_none_match: Option<Bytes>) -> Request {
+        let mut request = Request::new(Method::GET, target);
+        if let Some(tag) = if_none_match {
+            request
+                .headers
+                .entry(HeaderName::builtin(Builtin::IfNoneMatch))
+                .push(tag);
+        }
+        request
+    }
+
+    #[test]
+    fn fingerprinted_urls() {
+        let mut manifest = AssetManifest::new("static/");
+        let url = manifest
+            .insert("css/app.css", Bytes::from_static(b"body{}"))
+            .url()
+            .to_owned();
+        assert!(url.starts_with("/static/css/app."));
+        assert!(url.ends_with(".css"));
+        assert_eq!(url.len(), "/static/css/app..css".len() + 16);
+        assert_eq!(manifest.url("/css/app.css"), Some(url.as_str()));
+
+        // New content gets a new URL, and the old one is no longer served
+        manifest.insert("css/app.css", Bytes::from_static(b"body{margin:0}"));
+        assert_ne!(manifest.url("css/app.css"), Some(url.as_str()));
+        assert!(!manifest.by_url.contains_key(&url));
+        assert_eq!(manifest.insert("LICENSE", Bytes::new()).url().len(), 32);
+    }
+
+    #[tokio::test]
+    async fn serves_assets() {
+        let mut manifest = AssetManifest::new("/static");
+        manifest.insert("css/app.css", Bytes::from_static(b"body{}"));
+        let url = manifest.url("css/app.css").unwrap().to_owned();
+        let router = StaticAssets::new(Page, manifest);
+
+        let res = router.route(&request(url.clone(), None)).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(
+            res.headers.get(&CacheControl::NAME).unwrap().collect(),
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(
+            res.headers.get(&ContentType::NAME).unwrap()[0],
+            "text/css; charset=utf-8"
+        );
+        assert!(matches!(res.body, Body::Full(ref body) if body == "body{}"));
+
+        let etag = res.headers.get_header::<ETag>().unwrap().unwrap();
+        let res = router
+            .route(&request(url.clone(), Some(Bytes::from(etag.to_string()))))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+
+        let res = router.route(&request("/".into(), None)).await.unwrap();
+        let Body::Full(body) = res.body else {
+            panic!("expected a full body");
+        };
+        assert_eq!(body, format!("<link rel=stylesheet href={url}>"));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{
+    Router, RouterError,
+    http::{
+        header::{Authorization, Challenge, Credentials, HeaderField, WWWAuthenticate},
+        request::Request,
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+};
+
+/// Checks the credentials of a request, returning the identity they belong to
+pub trait Authenticator: Send + Sync + 'static {
+    /// Added to the extensions of authenticated requests
+    type Identity: Send + Sync + 'static;
+
+    fn authenticate(
+        &self,
+        credentials: &Credentials,
+        request: &Request,
+    ) -> impl Future<Output = Option<Self::Identity>> + Send;
+}
+
+impl<F, I> Authenticator for F
+where
+    F: Fn(&Credentials, &Request) -> Option<I> + Send + Sync + 'static,
+    I: Send + Sync + 'static,
+{
+    type Identity = I;
+
+    async fn authenticate(&self, credentials: &Credentials, request: &Request) -> Option<I> {
+        self(credentials, request)
+    }
+}
+
+/// Authenticates requests with their Authorization field before they reach the router
+/// Requests without credentials, or with credentials which are rejected, get 401 Unauthorized
+/// with a WWW-Authenticate challenge, and malformed credentials get 400 Bad Request
+/// The identity of authenticated requests is added to their extensions
+/// SPEC: RFC 9110 - 11.6. Authenticating Users to Origin Servers
+pub struct Authenticate<R: Router, A: Authenticator> {
+    inner: R,
+    authenticator: A,
+    challenge: Challenge,
+}
+
+impl<R: Router, A: Authenticator> Authenticate<R, A> {
+    /// `challenge` is sent with every 401 response, such as [`Challenge::basic`]
+    pub fn new(inner: R, authenticator: A, challenge: Challenge) -> Self {
+        Self {
+            inner,
+            authenticator,
+            challenge,
+        }
+    }
+
+    fn unauthorized(&self, request: &Request, error: Option<&'static str>) -> Response {
+        let mut challenge = self.challenge.clone();
+        // SPEC: RFC 6750 - 3.1. Error Codes
+        if let Some(error) = error
+            && challenge.scheme.eq_ignore_ascii_case(b"Bearer")
+        {
+            challenge = challenge.param("error", error);
+        }
+        ResponseBuilder::from_req(request, StatusCode::UNAUTHORIZED)
+            .set_header::<WWWAuthenticate>(vec![challenge])
+            .build()
+    }
+}
+
+impl<R: Router, A: Authenticator> Router for Authenticate<R, A> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let credentials = match request.headers.get(&Authorization::NAME) {
+            None => return Ok(self.unauthorized(request, None)),
+            Some(_) => request.headers.get_header::<Authorization>(),
+        };
+        let credentials = match credentials {
+            Ok(Some(credentials)) => credentials,
+            _ => return Err(RouterError::BadRequest("malformed Authorization".into())),
+        };
+        let Some(identity) = self.authenticator.authenticate(&credentials, request).await else {
+            return Ok(self.unauthorized(request, Some("invalid_token")));
+        };
+        let mut request = request.clone();
+        request.extensions.insert(identity);
+        self.inner.route(&request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::http::{
+        Body,
+        header::{Builtin, HeaderName},
+        method::Method,
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct User(String);
+
+    struct Whoami;
+
+    impl Router for Whoami {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let user = request.extensions.get::<User>().unwrap();
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(Bytes::from(user.0.clone()))
+                .build())
+        }
+    }
+
+    fn request(authorization: Option<&'static [u8]>) -> Request {
+        let mut request = Request::new(Method::GET, "/");
+        if let Some(authorization) = authorization {
+            request
+                .headers
+                .entry(HeaderName::builtin(Builtin::Authorization))
+                .push(Bytes::from_static(authorization));
+        }
+        request
+    }
+
+    fn challenge(res: &Response) -> Vec<Challenge> {
+        res.headers
+            .get_header::<WWWAuthenticate>()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn basic() {
+        let router = Authenticate::new(
+            Whoami,
+            |credentials: &Credentials, _: &Request| match credentials {
+                Credentials::Basic { user_id, password } if password == "open sesame" => {
+                    Some(User(user_id.clone()))
+                }
+                _ => None,
+            },
+            Challenge::basic("admin"),
+        );
+
+        let res = router
+            .route(&request(Some(b"Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==")))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(matches!(res.body, Body::Full(ref body) if body == "Aladdin"));
+
+        let res = router.route(&request(None)).await.unwrap();
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(challenge(&res), vec![Challenge::basic("admin")]);
+
+        // Zm9vOmJhcg== is foo:bar
+        let res = router
+            .route(&request(Some(b"Basic Zm9vOmJhcg==")))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+
+        assert!(matches!(
+            router.route(&request(Some(b"Basic !"))).await,
+            Err(RouterError::BadRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn bearer() {
+        let router = Authenticate::new(
+            Whoami,
+            |credentials: &Credentials, _: &Request| match credentials {
+                Credentials::Bearer(token) if token == "secret" => Some(User("bot".into())),
+                _ => None,
+            },
+            Challenge::bearer("api"),
+        );
+        let res = router
+            .route(&request(Some(b"Bearer secret")))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+
+        let res = router.route(&request(Some(b"Bearer guess"))).await.unwrap();
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(challenge(&res)[0].get(b"error").unwrap(), "invalid_token");
+    }
+}
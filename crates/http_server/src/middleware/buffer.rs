@@ -0,0 +1,140 @@
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    Router, RouterError,
+    http::{
+        Body, BodyError, BodyStream,
+        header::{ContentLength, HeaderField},
+        request::Request,
+        response::Response,
+    },
+};
+
+/// Buffers streamed responses smaller than a threshold, so they can be sent with a
+/// Content-Length instead of being streamed
+/// Streams larger than the threshold are passed through, with the buffered prefix sent first
+pub struct BufferResponse<R: Router> {
+    inner: R,
+    threshold: usize,
+}
+
+impl<R: Router> BufferResponse<R> {
+    pub const DEFAULT_THRESHOLD: usize = 64 * 1024;
+
+    pub fn new(inner: R) -> Self {
+        Self::with_threshold(inner, Self::DEFAULT_THRESHOLD)
+    }
+
+    pub fn with_threshold(inner: R, threshold: usize) -> Self {
+        Self { inner, threshold }
+    }
+}
+
+impl<R: Router> Router for BufferResponse<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let mut res = self.inner.route(request).await?;
+        if !matches!(res.body, Body::Stream(_)) || res.headers.contains(&ContentLength::NAME) {
+            return Ok(res);
+        }
+        if let Some(buf) = buffer_body(&mut res.body, self.threshold).await? {
+            res.headers.set_header::<ContentLength>(buf.len() as u64);
+        }
+        Ok(res)
+    }
+}
+
+/// Reads a streamed body into memory if it is no larger than `threshold`, returning its bytes
+/// Larger streams are left as a stream, with the buffered prefix sent first, and `None` is
+/// returned
+pub(super) async fn buffer_body(
+    body: &mut Body,
+    threshold: usize,
+) -> Result<Option<Bytes>, BodyError> {
+    let stream = match body {
+        Body::None => return Ok(Some(Bytes::new())),
+        Body::Full(bytes) => return Ok(Some(bytes.clone())),
+        Body::Stream(stream) => stream.clone(),
+    };
+
+    let mut buf = BytesMut::new();
+    loop {
+        match stream.next_chunk().await {
+            Some(chunk) => {
+                buf.extend_from_slice(&chunk?);
+                if buf.len() > threshold {
+                    break;
+                }
+            }
+            None => {
+                let buf = buf.freeze();
+                *body = Body::Full(buf.clone());
+                return Ok(Some(buf));
+            }
+        }
+    }
+
+    // Too large, send what we have buffered, followed by the rest of the stream
+    let (tx, rest) = BodyStream::channel(1);
+    *body = Body::Stream(rest);
+    let buffered: Bytes = buf.freeze();
+    tokio::spawn(async move {
+        if tx.send(buffered).await.is_err() {
+            return;
+        }
+        while let Some(chunk) = stream.next_chunk().await {
+            match chunk {
+                Ok(chunk) => {
+                    if tx.send(chunk).await.is_err() {
+                        return;
+                    }
+                }
+                Err(err) => return tx.abort(err).await,
+            }
+        }
+    });
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        HttpVersion,
+        method::Method,
+        response::{ResponseBuilder, StatusCode},
+    };
+
+    struct Streaming(&'static [&'static [u8]]);
+
+    impl Router for Streaming {
+        async fn route(&self, _request: &Request) -> Result<Response, RouterError> {
+            let (tx, stream) = BodyStream::channel(self.0.len().max(1));
+            for chunk in self.0 {
+                tx.send(Bytes::from_static(chunk)).await?;
+            }
+            let mut res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+            res.body = Body::Stream(stream);
+            Ok(res)
+        }
+    }
+
+    fn request() -> Request {
+        Request::new(Method::GET, "/")
+    }
+
+    #[tokio::test]
+    async fn buffers_small_stream() {
+        let router = BufferResponse::with_threshold(Streaming(&[b"hello ", b"world"]), 16);
+        let res = router.route(&request()).await.unwrap();
+        assert_eq!(res.headers.get_header::<ContentLength>().unwrap(), Some(11));
+        assert!(matches!(res.body, Body::Full(ref bytes) if bytes == "hello world"));
+    }
+
+    #[tokio::test]
+    async fn streams_large_body() {
+        let router = BufferResponse::with_threshold(Streaming(&[b"hello ", b"world"]), 4);
+        let res = router.route(&request()).await.unwrap();
+        assert_eq!(res.headers.get_header::<ContentLength>().unwrap(), None);
+        assert_eq!(res.body.collect(None).await.unwrap(), "hello world");
+    }
+}
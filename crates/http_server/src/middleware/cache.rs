@@ -0,0 +1,500 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::{
+    Router, RouterError,
+    clock::{SharedClock, TokioClock},
+    http::{
+        Body, HttpDate,
+        header::{Builtin, HeaderMap, HeaderName, HeaderValueTrait},
+        method::Method,
+        request::Request,
+        response::{Response, StatusCode},
+    },
+    middleware::buffer::buffer_body,
+};
+
+/// The directives of a Cache-Control field, with lowercase names and unquoted values
+/// SPEC: RFC 9111 - 5.2. Cache-Control
+/// ABNF: cache-directive = token [ "=" ( token / quoted-string ) ]
+fn directives(headers: &HeaderMap) -> Vec<(Bytes, Option<Bytes>)> {
+    let Some(elements) = headers
+        .get(&HeaderName::builtin(Builtin::CacheControl))
+        .and_then(|value| Vec::<Bytes>::from_header_value(value).ok())
+    else {
+        return Vec::new();
+    };
+    elements
+        .iter()
+        .map(|element| match element.iter().position(|b| *b == b'=') {
+            Some(idx) => {
+                let value = element[idx + 1..].trim_ascii();
+                let value = value
+                    .strip_prefix(b"\"")
+                    .and_then(|value| value.strip_suffix(b"\""))
+                    .unwrap_or(value);
+                (
+                    Bytes::from(element[..idx].trim_ascii().to_ascii_lowercase()),
+                    Some(Bytes::copy_from_slice(value)),
+                )
+            }
+            None => (Bytes::from(element.trim_ascii().to_ascii_lowercase()), None),
+        })
+        .collect()
+}
+
+fn has(directives: &[(Bytes, Option<Bytes>)], name: &str) -> bool {
+    directives.iter().any(|(directive, _)| directive == name)
+}
+
+/// The value of a delta-seconds directive, such as max-age
+/// ABNF: delta-seconds = 1*DIGIT
+fn seconds(directives: &[(Bytes, Option<Bytes>)], name: &str) -> Option<Duration> {
+    let (_, value) = directives.iter().find(|(directive, _)| directive == name)?;
+    let secs = std::str::from_utf8(value.as_ref()?).ok()?.parse().ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+fn date(headers: &HeaderMap, builtin: Builtin) -> Option<Result<SystemTime, ()>> {
+    let value = headers.get(&HeaderName::builtin(builtin))?.collect();
+    Some(
+        std::str::from_utf8(&value)
+            .ok()
+            .and_then(|value| HttpDate::from_str(value).ok())
+            .map(SystemTime::from)
+            .ok_or(()),
+    )
+}
+
+/// A stored response, with the values of the request fields it varies on
+#[derive(Debug)]
+struct Variant {
+    vary: Vec<(HeaderName, Option<Bytes>)>,
+    status: StatusCode,
+    message: Bytes,
+    headers: HeaderMap,
+    body: Bytes,
+    /// The age of the response when it was stored
+    age: Duration,
+    stored: Instant,
+    expires: Instant,
+}
+
+impl Variant {
+    fn matches(&self, request: &Request) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| request.headers.get(name).map(|v| v.collect()) == *value)
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    variants: HashMap<Bytes, Vec<Variant>>,
+    /// The total size of the stored bodies
+    size: usize,
+}
+
+impl CacheState {
+    fn evict(&mut self, now: Instant, max_size: usize) {
+        let size = &mut self.size;
+        self.variants.retain(|_, variants| {
+            variants.retain(|variant| {
+                let fresh = variant.expires > now;
+                if !fresh {
+                    *size -= variant.body.len();
+                }
+                fresh
+            });
+            !variants.is_empty()
+        });
+        // Responses which would expire soonest are the least valuable
+        while self.size > max_size {
+            let Some((key, idx)) = self
+                .variants
+                .iter()
+                .flat_map(|(key, variants)| {
+                    variants
+                        .iter()
+                        .enumerate()
+                        .map(move |(idx, variant)| (key, idx, variant))
+                })
+                .min_by_key(|(_, _, variant)| variant.expires)
+                .map(|(key, idx, _)| (key.clone(), idx))
+            else {
+                break;
+            };
+            let variants = self.variants.get_mut(&key).unwrap();
+            self.size -= variants.remove(idx).body.len();
+            if variants.is_empty() {
+                self.variants.remove(&key);
+            }
+        }
+    }
+}
+
+/// Caches responses to GET requests in memory, serving them without routing the request while
+/// they are fresh
+/// Only responses with explicit freshness, from `s-maxage`, `max-age` or Expires, are stored,
+/// and responses marked `no-store`, `private` or `no-cache` never are, nor are ones setting
+/// cookies
+/// Responses are stored per Host and target, with a variant for each combination of the
+/// request fields named by Vary
+/// SPEC: RFC 9111 - HTTP Caching
+pub struct ResponseCache<R: Router> {
+    inner: R,
+    state: Mutex<CacheState>,
+    max_size: usize,
+    max_entry_size: usize,
+    max_ttl: Duration,
+    clock: SharedClock,
+}
+
+impl<R: Router> ResponseCache<R> {
+    pub const DEFAULT_MAX_SIZE: usize = 16 * 1024 * 1024;
+    pub const DEFAULT_MAX_ENTRY_SIZE: usize = 1024 * 1024;
+    pub const DEFAULT_MAX_TTL: Duration = Duration::from_secs(60 * 60);
+
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: Mutex::default(),
+            max_size: Self::DEFAULT_MAX_SIZE,
+            max_entry_size: Self::DEFAULT_MAX_ENTRY_SIZE,
+            max_ttl: Self::DEFAULT_MAX_TTL,
+            clock: TokioClock::shared(),
+        }
+    }
+
+    /// The total size of the stored bodies, past which the responses closest to expiring are
+    /// evicted
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// The largest response body which is stored
+    pub fn with_max_entry_size(mut self, max_entry_size: usize) -> Self {
+        self.max_entry_size = max_entry_size;
+        self
+    }
+
+    /// The longest a response is stored for, whatever its freshness lifetime
+    pub fn with_max_ttl(mut self, max_ttl: Duration) -> Self {
+        self.max_ttl = max_ttl;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The total size of the stored bodies
+    pub fn size(&self) -> usize {
+        self.state.lock().unwrap().size
+    }
+
+    /// Removes every stored response
+    pub fn clear(&self) {
+        *self.state.lock().unwrap() = CacheState::default();
+    }
+
+    fn key(request: &Request) -> Bytes {
+        let host = request
+            .headers
+            .get(&HeaderName::builtin(Builtin::Host))
+            .map(|value| value.collect())
+            .unwrap_or_default();
+        [&host[..], b" ", &request.target].concat().into()
+    }
+
+    fn lookup(&self, request: &Request, key: &Bytes) -> Option<Response> {
+        let now = self.clock.now();
+        let state = self.state.lock().unwrap();
+        let variant = state
+            .variants
+            .get(key)?
+            .iter()
+            .find(|variant| variant.expires > now && variant.matches(request))?;
+        let mut headers = variant.headers.clone();
+        // SPEC: RFC 9111 - 5.1. Age
+        let age = variant.age + now.duration_since(variant.stored);
+        let age_name = HeaderName::builtin(Builtin::Age);
+        headers.insert(age_name, Bytes::from(age.as_secs().to_string()));
+        Some(Response {
+            version: request.version,
+            status: variant.status,
+            message: variant.message.clone(),
+            headers,
+            body: Body::Full(variant.body.clone()),
+        })
+    }
+
+    /// How long the response can be served from the cache, if it can be stored at all
+    /// SPEC: RFC 9111 - 3. Storing Responses in Caches
+    /// SPEC: RFC 9111 - 4.2.1. Calculating Freshness Lifetime
+    fn ttl(&self, request: &Request, response: &Response) -> Option<(Duration, Duration)> {
+        // The statuses which are cacheable by default, the rest need extensions to be stored
+        // SPEC: RFC 9110 - 15.1. Overview of Status Codes
+        if !matches!(
+            response.status.as_u16(),
+            200 | 203 | 204 | 300 | 301 | 308 | 404 | 405 | 410 | 414 | 501
+        ) {
+            return None;
+        }
+        let directives = directives(&response.headers);
+        if ["no-store", "private", "no-cache"]
+            .iter()
+            .any(|directive| has(&directives, directive))
+            || response
+                .headers
+                .contains(&HeaderName::builtin(Builtin::SetCookie))
+        {
+            return None;
+        }
+        // SPEC: RFC 9111 - 3.5. Storing Responses to Authenticated Requests
+        if request
+            .headers
+            .contains(&HeaderName::builtin(Builtin::Authorization))
+            && !["public", "s-maxage", "must-revalidate"]
+                .iter()
+                .any(|directive| has(&directives, directive))
+        {
+            return None;
+        }
+
+        let lifetime = match seconds(&directives, "s-maxage").or(seconds(&directives, "max-age")) {
+            Some(lifetime) => lifetime,
+            None => {
+                // An invalid Expires is a time in the past
+                let expires = date(&response.headers, Builtin::Expires)?.ok()?;
+                let date = match date(&response.headers, Builtin::Date) {
+                    Some(date) => date.ok()?,
+                    None => SystemTime::now(),
+                };
+                expires.duration_since(date).ok()?
+            }
+        };
+        // SPEC: RFC 9111 - 4.2.3. Calculating Age
+        let age = response
+            .headers
+            .get(&HeaderName::builtin(Builtin::Age))
+            .and_then(|value| std::str::from_utf8(&value.collect()).ok()?.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+        let ttl = lifetime.checked_sub(age)?.min(self.max_ttl);
+        (!ttl.is_zero()).then_some((ttl, age))
+    }
+
+    /// The request fields the response varies on, or `None` if it varies on something other
+    /// than the request fields
+    /// SPEC: RFC 9111 - 4.1. Calculating Cache Keys with the Vary Header Field
+    fn vary(request: &Request, response: &Response) -> Option<Vec<(HeaderName, Option<Bytes>)>> {
+        let Some(value) = response.headers.get(&HeaderName::builtin(Builtin::Vary)) else {
+            return Some(Vec::new());
+        };
+        let mut vary = Vec::new();
+        for name in Vec::<Bytes>::from_header_value(value).ok()? {
+            if &name[..] == b"*" {
+                return None;
+            }
+            let name = HeaderName::try_from(&Bytes::from(name.to_ascii_lowercase())).ok()?;
+            let value = request.headers.get(&name).map(|value| value.collect());
+            vary.push((name, value));
+        }
+        Some(vary)
+    }
+}
+
+impl<R: Router> Router for ResponseCache<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if request.method != Method::GET {
+            return self.inner.route(request).await;
+        }
+        let key = Self::key(request);
+        let directives = directives(&request.headers);
+        // SPEC: RFC 9111 - 5.2.1.4. no-cache
+        if !has(&directives, "no-cache")
+            && let Some(res) = self.lookup(request, &key)
+        {
+            return Ok(res);
+        }
+
+        let mut res = self.inner.route(request).await?;
+        // SPEC: RFC 9111 - 5.2.1.5. no-store
+        if has(&directives, "no-store") {
+            return Ok(res);
+        }
+        let (Some((ttl, age)), Some(vary)) = (self.ttl(request, &res), Self::vary(request, &res))
+        else {
+            return Ok(res);
+        };
+        let Some(body) = buffer_body(&mut res.body, self.max_entry_size).await? else {
+            return Ok(res);
+        };
+        if body.len() > self.max_entry_size {
+            return Ok(res);
+        }
+
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        let variants = state.variants.entry(key).or_default();
+        let replaced = variants
+            .iter()
+            .position(|variant| variant.vary == vary)
+            .map(|idx| variants.remove(idx).body.len());
+        variants.push(Variant {
+            vary,
+            status: res.status,
+            message: res.message.clone(),
+            headers: res.headers.clone(),
+            body,
+            age,
+            stored: now,
+            expires: now + ttl,
+        });
+        state.size += variants.last().unwrap().body.len();
+        state.size -= replaced.unwrap_or_default();
+        state.evict(now, self.max_size);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use super::*;
+    use crate::{clock::MockClock, http::response::ResponseBuilder};
+
+    /// Responds with the number of calls, and the Cache-Control and Vary in the query
+    #[derive(Default)]
+    struct Counter {
+        calls: AtomicU32,
+    }
+
+    impl Router for Arc<Counter> {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            let mut res = ResponseBuilder::from_req(request, StatusCode::OK).build();
+            let target = std::str::from_utf8(&request.target).unwrap();
+            let query = target.split_once('?').map_or("", |(_, query)| query);
+            for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+                let (name, value) = pair.split_once('=').unwrap();
+                let name = match name {
+                    "cc" => Builtin::CacheControl,
+                    "vary" => Builtin::Vary,
+                    "age" => Builtin::Age,
+                    _ => panic!("unexpected field {name}"),
+                };
+                res.headers
+                    .entry(HeaderName::builtin(name))
+                    .push(Bytes::from(value.replace('+', " ")));
+            }
+            res.body = Body::Full(Bytes::from(calls.to_string()));
+            Ok(res)
+        }
+    }
+
+    fn request(target: &str, headers: &[(Builtin, &'static str)]) -> Request {
+        let mut request = Request::new(Method::GET, target.to_owned());
+        for (name, value) in headers {
+            request
+                .headers
+                .entry(HeaderName::builtin(*name))
+                .push(Bytes::from_static(value.as_bytes()));
+        }
+        request
+    }
+
+    async fn get<R: Router>(router: &R, request: &Request) -> (String, Option<Bytes>) {
+        let res = router.route(request).await.unwrap();
+        let Body::Full(body) = res.body else {
+            panic!("expected a full body");
+        };
+        let age = res
+            .headers
+            .get(&HeaderName::builtin(Builtin::Age))
+            .map(|value| value.collect());
+        (String::from_utf8(body.to_vec()).unwrap(), age)
+    }
+
+    #[tokio::test]
+    async fn freshness() {
+        let clock = Arc::new(MockClock::new());
+        let cache = ResponseCache::new(Arc::new(Counter::default())).with_clock(clock.clone());
+
+        let req = request("/a?cc=max-age=60", &[]);
+        assert_eq!(get(&cache, &req).await, ("1".into(), None));
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(get(&cache, &req).await, ("1".into(), Some("30".into())));
+        // A new request can skip the cache, and replaces the stored response
+        let no_cache = request("/a?cc=max-age=60", &[(Builtin::CacheControl, "no-cache")]);
+        assert_eq!(get(&cache, &no_cache).await.0, "2");
+        assert_eq!(get(&cache, &req).await, ("2".into(), Some("0".into())));
+        assert_eq!(cache.size(), 1);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(get(&cache, &req).await.0, "3");
+
+        // s-maxage takes precedence, and the age of the response counts against it
+        let req = request("/b?cc=max-age=600,+s-maxage=20&age=10", &[]);
+        assert_eq!(get(&cache, &req).await.0, "4");
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(get(&cache, &req).await, ("4".into(), Some("15".into())));
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(get(&cache, &req).await.0, "5");
+
+        for target in [
+            "/c",
+            "/c?cc=no-store,+max-age=60",
+            "/c?cc=private,+max-age=60",
+        ] {
+            let req = request(target, &[]);
+            let first = get(&cache, &req).await.0;
+            assert_ne!(get(&cache, &req).await.0, first);
+        }
+        let req = request("/d?cc=max-age=60", &[(Builtin::Authorization, "Bearer x")]);
+        assert_ne!(get(&cache, &req).await.0, get(&cache, &req).await.0);
+    }
+
+    #[tokio::test]
+    async fn vary_and_eviction() {
+        let clock = Arc::new(MockClock::new());
+        let cache = ResponseCache::new(Arc::new(Counter::default()))
+            .with_max_size(2)
+            .with_clock(clock.clone());
+
+        let target = "/?cc=max-age=60&vary=accept-language";
+        let en = request(target, &[(Builtin::AcceptLanguage, "en")]);
+        let fr = request(target, &[(Builtin::AcceptLanguage, "fr")]);
+        assert_eq!(get(&cache, &en).await.0, "1");
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(get(&cache, &fr).await.0, "2");
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(get(&cache, &en).await.0, "1");
+        assert_eq!(get(&cache, &fr).await.0, "2");
+        assert_eq!(get(&cache, &request(target, &[])).await.0, "3");
+        // The third variant pushed out the one closest to expiring
+        assert_eq!(cache.size(), 2);
+        assert_eq!(get(&cache, &en).await.0, "4");
+
+        let star = request("/?cc=max-age=60&vary=*", &[]);
+        assert_eq!(get(&cache, &star).await.0, "5");
+        assert_eq!(get(&cache, &star).await.0, "6");
+
+        cache.clear();
+        assert_eq!(cache.size(), 0);
+        assert_eq!(get(&cache, &fr).await.0, "7");
+    }
+}
@@ -0,0 +1,558 @@
+use std::io::Write;
+
+#[cfg(feature = "br")]
+use brotli::CompressorWriter as BrotliEncoder;
+use bytes::Bytes;
+#[cfg(feature = "deflate")]
+use flate2::write::DeflateEncoder;
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        Body, BodyStream,
+        header::{
+            AcceptEncoding, CacheControl, ContentEncoding, ContentLength, ContentType, ETag,
+            EntityTag, HeaderField, HeaderMap, parse_qvalue,
+        },
+        request::Request,
+        response::{Response, StatusCode},
+    },
+};
+
+/// A content coding the response can be compressed with
+/// SPEC: RFC 9110 - 8.4.1. Content Codings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coding {
+    /// SPEC: RFC 7932 - Brotli Compressed Data Format
+    #[cfg(feature = "br")]
+    Br,
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "deflate")]
+    Deflate,
+}
+
+impl Coding {
+    /// The supported codings, in order of preference when the client has none
+    const SUPPORTED: &[Self] = &[
+        #[cfg(feature = "br")]
+        Self::Br,
+        #[cfg(feature = "gzip")]
+        Self::Gzip,
+        #[cfg(feature = "deflate")]
+        Self::Deflate,
+    ];
+
+    fn token(self) -> &'static [u8] {
+        match self {
+            #[cfg(feature = "br")]
+            Self::Br => b"br",
+            #[cfg(feature = "gzip")]
+            Self::Gzip => b"gzip",
+            #[cfg(feature = "deflate")]
+            Self::Deflate => b"deflate",
+        }
+    }
+
+    fn from_token(token: &[u8]) -> Option<Self> {
+        // SPEC: RFC 9110 - 8.4.1.3. Gzip Coding
+        // x-gzip is an alias of gzip
+        #[cfg(feature = "gzip")]
+        if token.eq_ignore_ascii_case(b"x-gzip") {
+            return Some(Self::Gzip);
+        }
+        Self::SUPPORTED
+            .iter()
+            .copied()
+            .find(|coding| token.eq_ignore_ascii_case(coding.token()))
+    }
+
+    fn encoder(self, level: u32) -> Encoder {
+        match self {
+            // The default window of 4 MiB
+            #[cfg(feature = "br")]
+            Self::Br => Encoder::Br(Box::new(BrotliEncoder::new(Vec::new(), 4096, level, 22))),
+            #[cfg(feature = "gzip")]
+            Self::Gzip => {
+                Encoder::Gzip(GzEncoder::new(Vec::new(), flate2::Compression::new(level)))
+            }
+            #[cfg(feature = "deflate")]
+            Self::Deflate => Encoder::Deflate(DeflateEncoder::new(
+                Vec::new(),
+                flate2::Compression::new(level),
+            )),
+        }
+    }
+}
+
+/// An incremental encoder, the output is written into a Vec so writes never fail
+enum Encoder {
+    #[cfg(feature = "br")]
+    Br(Box<BrotliEncoder<Vec<u8>>>),
+    #[cfg(feature = "gzip")]
+    Gzip(GzEncoder<Vec<u8>>),
+    #[cfg(feature = "deflate")]
+    Deflate(DeflateEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    /// Compresses a chunk, and returns the output which is ready to be sent
+    fn write(&mut self, chunk: &[u8]) -> Bytes {
+        let out = match self {
+            #[cfg(feature = "br")]
+            Self::Br(enc) => {
+                enc.write_all(chunk).unwrap();
+                enc.flush().unwrap();
+                enc.get_mut()
+            }
+            #[cfg(feature = "gzip")]
+            Self::Gzip(enc) => {
+                enc.write_all(chunk).unwrap();
+                enc.flush().unwrap();
+                enc.get_mut()
+            }
+            #[cfg(feature = "deflate")]
+            Self::Deflate(enc) => {
+                enc.write_all(chunk).unwrap();
+                enc.flush().unwrap();
+                enc.get_mut()
+            }
+        };
+        Bytes::from(std::mem::take(out))
+    }
+
+    fn finish(self) -> Bytes {
+        Bytes::from(match self {
+            #[cfg(feature = "br")]
+            Self::Br(enc) => (*enc).into_inner(),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(enc) => enc.finish().unwrap(),
+            #[cfg(feature = "deflate")]
+            Self::Deflate(enc) => enc.finish().unwrap(),
+        })
+    }
+}
+
+/// Picks the coding the client prefers, ties go to the order of [`Coding::SUPPORTED`]
+/// A coding which is listed takes its own weight, `*` only applies to codings which are not
+/// SPEC: RFC 9110 - 12.5.3. Accept-Encoding
+fn negotiate(headers: &HeaderMap) -> Option<Coding> {
+    let accept = headers.get_header::<AcceptEncoding>().ok()??;
+    let mut wildcard = None;
+    let mut listed: Vec<(Coding, u16)> = Vec::new();
+    for elem in accept {
+        let mut params = elem.split(|&b| b == b';');
+        let token = params.next().unwrap_or_default().trim_ascii();
+        let qvalue = params.find_map(|param| {
+            let (name, value) = param.split_at(param.iter().position(|&b| b == b'=')?);
+            name.trim_ascii()
+                .eq_ignore_ascii_case(b"q")
+                .then(|| parse_qvalue(value[1..].trim_ascii()))
+        });
+        // An invalid weight makes the whole element invalid
+        let Some(qvalue) = qvalue.unwrap_or(Some(1000)) else {
+            continue;
+        };
+        if token == b"*" {
+            wildcard = Some(qvalue);
+        } else if let Some(coding) = Coding::from_token(token) {
+            listed.push((coding, qvalue));
+        }
+    }
+    Coding::SUPPORTED
+        .iter()
+        .filter_map(|&coding| {
+            let qvalue = listed
+                .iter()
+                .find(|(listed, _)| *listed == coding)
+                .map(|&(_, qvalue)| qvalue)
+                .or(wildcard)?;
+            (qvalue > 0).then_some((coding, qvalue))
+        })
+        .reduce(|best, next| if next.1 > best.1 { next } else { best })
+        .map(|(coding, _)| coding)
+}
+
+/// SPEC: RFC 9111 - 5.2.1.6. no-transform, 5.2.2.6. no-transform
+fn no_transform(headers: &HeaderMap) -> bool {
+    headers
+        .get_header::<CacheControl>()
+        .ok()
+        .flatten()
+        .is_some_and(|directives| {
+            directives
+                .iter()
+                .any(|directive| directive.eq_ignore_ascii_case(b"no-transform"))
+        })
+}
+
+/// Compresses response bodies with a content coding the client accepts
+/// Responses are left untouched when they already have a Content-Encoding, when either message
+/// has `Cache-Control: no-transform`, when the media type is excluded, or when the body is
+/// known to be smaller than the size floor
+pub struct Compression<R: Router> {
+    inner: R,
+    level: u32,
+    min_size: usize,
+    excluded_types: Vec<Bytes>,
+}
+
+impl<R: Router> Compression<R> {
+    /// Below this, the coding overhead is usually larger than the savings
+    pub const DEFAULT_MIN_SIZE: usize = 1024;
+    /// Balances the time spent compressing against the savings, for bodies made per request
+    pub const DEFAULT_LEVEL: u32 = 6;
+    /// Media types which are already compressed, a trailing `/*` matches the whole type
+    pub const DEFAULT_EXCLUDED_TYPES: &[&str] = &[
+        "image/*",
+        "video/*",
+        "audio/*",
+        "font/woff",
+        "font/woff2",
+        "application/zip",
+        "application/gzip",
+        "application/x-gzip",
+        "application/x-bzip2",
+        "application/x-xz",
+        "application/x-7z-compressed",
+        "application/x-rar-compressed",
+        "application/zstd",
+        "application/pdf",
+    ];
+
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            level: Self::DEFAULT_LEVEL,
+            min_size: Self::DEFAULT_MIN_SIZE,
+            excluded_types: Self::DEFAULT_EXCLUDED_TYPES
+                .iter()
+                .map(|ty| Bytes::from_static(ty.as_bytes()))
+                .collect(),
+        }
+    }
+
+    /// Sets the compression level, from 0 (fastest) to 9 (best), brotli uses it as its quality
+    pub fn with_level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    /// Responses with a known length below `min_size` are not compressed
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Replaces the excluded media types, a trailing `/*` matches the whole type
+    pub fn with_excluded_types<I, T>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Bytes>,
+    {
+        self.excluded_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    fn is_excluded(&self, headers: &HeaderMap) -> bool {
+        let Some(content_type) = headers.get_header::<ContentType>().ok().flatten() else {
+            return false;
+        };
+        // SPEC: RFC 9110 - 8.3.1. Media Type
+        // ABNF: media-type = type "/" subtype parameters
+        let essence = match content_type.iter().position(|&b| b == b';') {
+            Some(end) => &content_type[..end],
+            None => &content_type[..],
+        }
+        .trim_ascii();
+        self.excluded_types
+            .iter()
+            .any(|excluded| match excluded.strip_suffix(b"/*") {
+                Some(ty) => essence
+                    .split(|&b| b == b'/')
+                    .next()
+                    .is_some_and(|essence_ty| essence_ty.eq_ignore_ascii_case(ty)),
+                None => essence.eq_ignore_ascii_case(excluded),
+            })
+    }
+
+    fn should_compress(&self, request: &Request, res: &Response) -> bool {
+        let len = match &res.body {
+            Body::None => return false,
+            Body::Full(bytes) => Some(bytes.len() as u64),
+            Body::Stream(_) => res.headers.get_header::<ContentLength>().ok().flatten(),
+        };
+        if len.is_some_and(|len| len < self.min_size as u64) {
+            return false;
+        }
+        // SPEC: RFC 9110 - 6.4.1. Content
+        if res.status.is_informational()
+            || res.status == StatusCode::NO_CONTENT
+            || res.status == StatusCode::NOT_MODIFIED
+        {
+            return false;
+        }
+        !(res.headers.contains(&ContentEncoding::NAME)
+            || no_transform(&request.headers)
+            || no_transform(&res.headers)
+            || self.is_excluded(&res.headers))
+    }
+}
+
+impl<R: Router> Router for Compression<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let mut res = self.inner.route(request).await?;
+        if !self.should_compress(request, &res) {
+            return Ok(res);
+        }
+        // The response depends on Accept-Encoding even if we end up not compressing it
+        res.headers.add_vary(&AcceptEncoding::NAME);
+        let Some(coding) = negotiate(&request.headers) else {
+            return Ok(res);
+        };
+
+        let mut encoder = coding.encoder(self.level);
+        res.body = match std::mem::replace(&mut res.body, Body::None) {
+            Body::Full(bytes) => {
+                let mut out = encoder.write(&bytes).to_vec();
+                out.extend_from_slice(&encoder.finish());
+                Body::Full(Bytes::from(out))
+            }
+            Body::Stream(stream) => {
+                let (tx, compressed) = BodyStream::channel(1);
+                tokio::spawn(async move {
+                    while let Some(chunk) = stream.next_chunk().await {
+                        match chunk {
+                            Ok(chunk) => {
+                                if tx.send(encoder.write(&chunk)).await.is_err() {
+                                    return;
+                                }
+                            }
+                            Err(err) => return tx.abort(err).await,
+                        }
+                    }
+                    _ = tx.send(encoder.finish()).await;
+                });
+                Body::Stream(compressed)
+            }
+            Body::None => unreachable!("checked by should_compress"),
+        };
+        res.headers.remove(&ContentLength::NAME);
+        res.headers
+            .append(ContentEncoding::NAME, Bytes::from_static(coding.token()));
+        // SPEC: RFC 9110 - 8.8.3. ETag
+        // A strong tag promises the exact bytes of the representation, which the encoding changed
+        // Weakening it keeps it matching the tag of the inner router with the weak comparison
+        // of If-None-Match, so conditional requests still get 304
+        if let Ok(Some(etag)) = res.headers.get_header::<ETag>()
+            && !etag.weak
+        {
+            res.headers.set_header::<ETag>(EntityTag::weak(etag.tag));
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(all(test, feature = "gzip", feature = "deflate"))]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+    use crate::{
+        files::StaticFiles,
+        http::{
+            HttpVersion,
+            header::{IfNoneMatch, Vary},
+            method::Method,
+            response::{ResponseBuilder, StatusCode},
+        },
+        middleware::{AssetManifest, StaticAssets},
+    };
+
+    struct Fixed {
+        content_type: &'static str,
+        cache_control: Option<&'static str>,
+        body: Bytes,
+    }
+
+    impl Router for Fixed {
+        async fn route(&self, _request: &Request) -> Result<Response, RouterError> {
+            let mut res = ResponseBuilder::new(HttpVersion::HTTP_1_1, StatusCode::OK).build();
+            res.headers
+                .entry(ContentType::NAME)
+                .push(Bytes::from_static(self.content_type.as_bytes()));
+            if let Some(cache_control) = self.cache_control {
+                res.headers
+                    .entry(CacheControl::NAME)
+                    .push(Bytes::from_static(cache_control.as_bytes()));
+            }
+            res.body = Body::Full(self.body.clone());
+            Ok(res)
+        }
+    }
+
+    fn fixed(content_type: &'static str, len: usize) -> Fixed {
+        Fixed {
+            content_type,
+            cache_control: None,
+            body: Bytes::from("a".repeat(len)),
+        }
+    }
+
+    fn request(accept_encoding: &'static str) -> Request {
+        let mut request = Request::new(Method::GET, "/");
+        request
+            .headers
+            .entry(AcceptEncoding::NAME)
+            .push(Bytes::from_static(accept_encoding.as_bytes()));
+        request
+    }
+
+    fn content_encoding(res: &Response) -> Option<Vec<Bytes>> {
+        res.headers.get_header::<ContentEncoding>().unwrap()
+    }
+
+    #[tokio::test]
+    async fn compresses_gzip() {
+        let router = Compression::new(fixed("text/html; charset=utf-8", 4096));
+        let res = router.route(&request("deflate;q=0.5, gzip")).await.unwrap();
+        assert_eq!(
+            content_encoding(&res),
+            Some(vec![Bytes::from_static(b"gzip")])
+        );
+        let body = res.body.collect(None).await.unwrap();
+        let mut decoded = String::new();
+        GzDecoder::new(&body[..])
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "a".repeat(4096));
+    }
+
+    #[cfg(feature = "br")]
+    #[tokio::test]
+    async fn compresses_br() {
+        let router = Compression::new(fixed("application/json", 4096));
+        let res = router.route(&request("gzip, br")).await.unwrap();
+        assert_eq!(
+            content_encoding(&res),
+            Some(vec![Bytes::from_static(b"br")])
+        );
+        let body = res.body.collect(None).await.unwrap();
+        let mut decoded = String::new();
+        brotli::Decompressor::new(&body[..], 4096)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "a".repeat(4096));
+    }
+
+    #[tokio::test]
+    async fn size_floor() {
+        let router = Compression::new(fixed("text/plain", 100));
+        let res = router.route(&request("gzip")).await.unwrap();
+        assert_eq!(content_encoding(&res), None);
+        assert_eq!(res.headers.get_header::<Vary>().unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn excluded_types() {
+        let router = Compression::new(fixed("Image/PNG", 4096));
+        let res = router.route(&request("gzip")).await.unwrap();
+        assert_eq!(content_encoding(&res), None);
+
+        let router = Compression::new(fixed("text/css", 4096)).with_excluded_types(["text/css"]);
+        let res = router.route(&request("gzip")).await.unwrap();
+        assert_eq!(content_encoding(&res), None);
+
+        let router = Compression::new(fixed("application/zip", 4096))
+            .with_excluded_types(Vec::<Bytes>::new());
+        let res = router.route(&request("gzip")).await.unwrap();
+        assert!(content_encoding(&res).is_some());
+    }
+
+    #[tokio::test]
+    async fn no_transform() {
+        let mut inner = fixed("text/plain", 4096);
+        inner.cache_control = Some("public, No-Transform");
+        let res = Compression::new(inner)
+            .route(&request("gzip"))
+            .await
+            .unwrap();
+        assert_eq!(content_encoding(&res), None);
+    }
+
+    #[tokio::test]
+    async fn not_acceptable() {
+        let router = Compression::new(fixed("text/plain", 4096));
+        let res = router.route(&request("gzip;q=0, compress")).await.unwrap();
+        assert_eq!(content_encoding(&res), None);
+        assert_eq!(
+            res.headers.get_header::<Vary>().unwrap(),
+            Some(vec![Bytes::from_static(b"Accept-Encoding")])
+        );
+    }
+
+    #[tokio::test]
+    async fn weakens_etag() {
+        let dir = std::env::temp_dir().join(format!("carbon-compression-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "let a;".repeat(1024)).unwrap();
+        let files = Compression::new(StaticFiles::new(&dir));
+        let mut req = request("gzip");
+        req.target = Bytes::from_static(b"/app.js");
+        let res = files.route(&req).await.unwrap();
+        assert!(content_encoding(&res).is_some());
+        let etag = res.headers.get_header::<ETag>().unwrap().unwrap();
+        assert!(etag.weak);
+        // The tag of the encoded response is still matched by the files router
+        req.headers
+            .entry(IfNoneMatch::NAME)
+            .push(Bytes::from(etag.to_string()));
+        let res = files.route(&req).await.unwrap();
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let mut manifest = AssetManifest::new("/static");
+        let url = manifest
+            .insert("app.js", Bytes::from("let a;".repeat(1024)))
+            .url()
+            .to_owned();
+        let assets = Compression::new(StaticAssets::new(fixed("text/plain", 0), manifest));
+        let mut req = request("gzip");
+        req.target = Bytes::from(url.clone());
+        let res = assets.route(&req).await.unwrap();
+        assert!(content_encoding(&res).is_some());
+        let etag = res.headers.get_header::<ETag>().unwrap().unwrap();
+        assert!(etag.weak);
+        req.headers
+            .entry(IfNoneMatch::NAME)
+            .push(Bytes::from(etag.to_string()));
+        let res = assets.route(&req).await.unwrap();
+        assert_eq!(res.status, StatusCode::NOT_MODIFIED);
+        // Without an encoding the strong tag is kept
+        let mut req = request("identity");
+        req.target = Bytes::from(url);
+        let res = assets.route(&req).await.unwrap();
+        assert!(!res.headers.get_header::<ETag>().unwrap().unwrap().weak);
+    }
+
+    #[test]
+    fn negotiation() {
+        let negotiate = |accept: &'static str| negotiate(&request(accept).headers);
+        assert_eq!(negotiate("deflate, gzip"), Some(Coding::Gzip));
+        assert_eq!(negotiate("gzip;q=0, br;q=0, *"), Some(Coding::Deflate));
+        assert_eq!(negotiate("*;q=0.5, deflate;q=0.6"), Some(Coding::Deflate));
+        assert_eq!(negotiate("X-GZIP;Q=0.001"), Some(Coding::Gzip));
+        assert_eq!(negotiate("identity, *;q=0"), None);
+        #[cfg(feature = "br")]
+        assert_eq!(negotiate("gzip, deflate, br"), Some(Coding::Br));
+        // Invalid weights make the element invalid
+        assert_eq!(negotiate("gzip;q=1.5, deflate;q=0.1234"), None);
+        assert_eq!(parse_qvalue(b"0.5"), Some(500));
+        assert_eq!(parse_qvalue(b"1.000"), Some(1000));
+        assert_eq!(parse_qvalue(b"1.001"), None);
+    }
+}
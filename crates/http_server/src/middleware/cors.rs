@@ -0,0 +1,256 @@
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        header::{Builtin, HeaderMap, HeaderName, HeaderValueTrait},
+        method::Method,
+        request::Request,
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+};
+
+/// Which origins may make cross-origin requests
+#[derive(Debug, Clone)]
+pub enum AllowOrigin {
+    Any,
+    /// Origins are compared exactly, for example `https://example.com`
+    List(Vec<Bytes>),
+}
+
+/// Answers CORS preflight requests, and adds CORS headers to the responses of allowed origins
+/// Requests from other origins are routed without CORS headers, so the browser will not let
+/// the page read the response
+/// SPEC: Fetch Standard - 3.2. CORS protocol
+pub struct Cors<R: Router> {
+    inner: R,
+    origins: AllowOrigin,
+    methods: Vec<Bytes>,
+    allowed_headers: Vec<Bytes>,
+    exposed_headers: Vec<Bytes>,
+    max_age: Option<Duration>,
+}
+
+impl<R: Router> Cors<R> {
+    pub fn new(inner: R, origins: AllowOrigin) -> Self {
+        Self {
+            inner,
+            origins,
+            methods: vec![Bytes::from_static(b"GET"), Bytes::from_static(b"POST")],
+            allowed_headers: Vec::new(),
+            exposed_headers: Vec::new(),
+            max_age: None,
+        }
+    }
+
+    /// For gRPC-Web style endpoints, see [`crate::http::grpc_web`], which are called with POST
+    /// and custom request headers, and report their status in response headers
+    pub fn grpc_web(inner: R, origins: AllowOrigin) -> Self {
+        Self::new(inner, origins)
+            .with_methods([Bytes::from_static(b"POST")])
+            .with_allowed_headers(
+                [
+                    &b"content-type"[..],
+                    b"x-grpc-web",
+                    b"x-user-agent",
+                    b"grpc-timeout",
+                ]
+                .map(Bytes::from_static),
+            )
+            .with_exposed_headers([&b"grpc-status"[..], b"grpc-message"].map(Bytes::from_static))
+    }
+
+    pub fn with_methods(mut self, methods: impl IntoIterator<Item = Bytes>) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    pub fn with_allowed_headers(mut self, headers: impl IntoIterator<Item = Bytes>) -> Self {
+        self.allowed_headers = headers.into_iter().collect();
+        self
+    }
+
+    pub fn with_exposed_headers(mut self, headers: impl IntoIterator<Item = Bytes>) -> Self {
+        self.exposed_headers = headers.into_iter().collect();
+        self
+    }
+
+    /// How long the browser may cache the result of a preflight request
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// The value of Access-Control-Allow-Origin for the request, if its origin is allowed
+    fn allow_origin(&self, request: &Request) -> Option<Bytes> {
+        let origin = request.headers.get(&HeaderName::builtin(Builtin::Origin))?;
+        if origin.len() != 1 {
+            return None;
+        }
+        match &self.origins {
+            AllowOrigin::Any => Some(Bytes::from_static(b"*")),
+            AllowOrigin::List(origins) => origins.contains(&origin[0]).then(|| origin[0].clone()),
+        }
+    }
+
+    fn set_list(headers: &mut HeaderMap, builtin: Builtin, values: &[Bytes]) {
+        if !values.is_empty() {
+            values
+                .to_vec()
+                .to_header_value(headers.entry(HeaderName::builtin(builtin)));
+        }
+    }
+
+    fn set_origin(&self, headers: &mut HeaderMap, origin: Bytes) {
+        headers.remove(&HeaderName::builtin(Builtin::AccessControlAllowOrigin));
+        headers
+            .entry(HeaderName::builtin(Builtin::AccessControlAllowOrigin))
+            .push(origin);
+        // The response depends on the origin unless every origin is allowed
+        if matches!(self.origins, AllowOrigin::List(_)) {
+            headers.add_vary(&HeaderName::builtin(Builtin::Origin));
+        }
+    }
+}
+
+impl<R: Router> Router for Cors<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let Some(origin) = self.allow_origin(request) else {
+            return self.inner.route(request).await;
+        };
+
+        // SPEC: Fetch Standard - 3.2.2. HTTP requests
+        let preflight = request.method == Method::OPTIONS
+            && request
+                .headers
+                .contains(&HeaderName::builtin(Builtin::AccessControlRequestMethod));
+        if preflight {
+            let mut res = ResponseBuilder::from_req(request, StatusCode::NO_CONTENT).build();
+            self.set_origin(&mut res.headers, origin);
+            Self::set_list(
+                &mut res.headers,
+                Builtin::AccessControlAllowMethods,
+                &self.methods,
+            );
+            Self::set_list(
+                &mut res.headers,
+                Builtin::AccessControlAllowHeaders,
+                &self.allowed_headers,
+            );
+            if let Some(max_age) = self.max_age {
+                res.headers
+                    .entry(HeaderName::builtin(Builtin::AccessControlMaxAge))
+                    .push(Bytes::from(max_age.as_secs().to_string()));
+            }
+            return Ok(res);
+        }
+
+        let mut res = self.inner.route(request).await?;
+        self.set_origin(&mut res.headers, origin);
+        Self::set_list(
+            &mut res.headers,
+            Builtin::AccessControlExposeHeaders,
+            &self.exposed_headers,
+        );
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ok;
+
+    impl Router for Ok {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            Result::Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    fn request(method: Method, headers: &[(Builtin, &'static [u8])]) -> Request {
+        let mut request = Request::new(method, "/svc/Call");
+        for (name, value) in headers {
+            request
+                .headers
+                .entry(HeaderName::builtin(*name))
+                .push(Bytes::from_static(value));
+        }
+        request
+    }
+
+    fn header(res: &Response, builtin: Builtin) -> Option<Bytes> {
+        res.headers
+            .get(&HeaderName::builtin(builtin))
+            .map(|value| value.collect())
+    }
+
+    #[tokio::test]
+    async fn preflight() {
+        let cors = Cors::grpc_web(
+            Ok,
+            AllowOrigin::List(vec![Bytes::from_static(b"https://a.example")]),
+        )
+        .with_max_age(Duration::from_secs(600));
+        let res = cors
+            .route(&request(
+                Method::OPTIONS,
+                &[
+                    (Builtin::Origin, b"https://a.example"),
+                    (Builtin::AccessControlRequestMethod, b"POST"),
+                    (Builtin::AccessControlRequestHeaders, b"x-grpc-web"),
+                ],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        assert_eq!(
+            header(&res, Builtin::AccessControlAllowOrigin).unwrap(),
+            "https://a.example"
+        );
+        assert_eq!(
+            header(&res, Builtin::AccessControlAllowMethods).unwrap(),
+            "POST"
+        );
+        assert!(
+            header(&res, Builtin::AccessControlAllowHeaders)
+                .unwrap()
+                .windows(10)
+                .any(|w| w == b"x-grpc-web")
+        );
+        assert_eq!(header(&res, Builtin::AccessControlMaxAge).unwrap(), "600");
+        assert_eq!(header(&res, Builtin::Vary).unwrap(), "Origin");
+    }
+
+    #[tokio::test]
+    async fn actual_request() {
+        let cors = Cors::grpc_web(
+            Ok,
+            AllowOrigin::List(vec![Bytes::from_static(b"https://a.example")]),
+        );
+        let res = cors
+            .route(&request(
+                Method::POST,
+                &[(Builtin::Origin, b"https://a.example")],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(
+            header(&res, Builtin::AccessControlExposeHeaders).unwrap(),
+            "grpc-status, grpc-message"
+        );
+
+        // Other origins are not given CORS headers
+        let res = cors
+            .route(&request(
+                Method::POST,
+                &[(Builtin::Origin, b"https://b.example")],
+            ))
+            .await
+            .unwrap();
+        assert!(header(&res, Builtin::AccessControlAllowOrigin).is_none());
+    }
+}
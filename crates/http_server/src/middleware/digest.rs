@@ -0,0 +1,401 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use ring::digest::{Context, SHA256, SHA512};
+use tokio::sync::oneshot;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        Body, BodyError, BodyStream,
+        base64::{self, Alphabet},
+        header::{Builtin, HeaderMap, HeaderName},
+        request::Request,
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+};
+
+/// Why the digest of a request body could not be verified
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DigestError {
+    #[error("malformed {0} field")]
+    Malformed(&'static str),
+    #[error("{0} does not match the body")]
+    Mismatch(&'static str),
+}
+
+/// Verifies the Content-Digest and Repr-Digest fields of request bodies, sent either as
+/// headers or as trailers of a chunked body, requests with a digest which does not match get
+/// 400 Bad Request, even if the router has already produced a response
+///
+/// The body is hashed while the router reads it, and once the router is done the rest of the
+/// body is read, so the digest can be checked before the response is sent. Trailers are only
+/// verified when they are announced with the Trailer header, so that bodies without digests
+/// are not hashed. Algorithms other than sha-256 and sha-512 are ignored
+/// SPEC: RFC 9530 - Digest Fields
+pub struct VerifyDigest<R: Router> {
+    inner: R,
+}
+
+impl<R: Router> VerifyDigest<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    fn reject(request: &Request, err: &DigestError) -> Response {
+        log::debug!("rejecting request: {}", err);
+        ResponseBuilder::from_req(request, StatusCode::BAD_REQUEST).build()
+    }
+}
+
+impl<R: Router> Router for VerifyDigest<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        // The representation is only the content if there is no content coding
+        // SPEC: RFC 9530 - 3. The Repr-Digest Field
+        let repr = !request
+            .headers
+            .contains(&HeaderName::builtin(Builtin::ContentEncoding));
+        let mut expected = Vec::new();
+        if let Err(err) = Expected::parse(&request.headers, repr, &mut expected) {
+            return Ok(Self::reject(request, &err));
+        }
+        let trailer = HeaderName::builtin(Builtin::Trailer);
+        let announced = request.headers.contains_token(&trailer, b"content-digest")
+            || repr && request.headers.contains_token(&trailer, b"repr-digest");
+        if expected.is_empty() && !announced {
+            return self.inner.route(request).await;
+        }
+
+        let stream = match &request.body {
+            Body::None | Body::Full(_) => {
+                let mut hashers = Hashers::new(expected.iter().map(|e| e.algorithm));
+                if let Body::Full(bytes) = &request.body {
+                    hashers.update(bytes);
+                }
+                if let Err(err) = hashers.verify(&expected) {
+                    return Ok(Self::reject(request, &err));
+                }
+                return self.inner.route(request).await;
+            }
+            Body::Stream(stream) => stream.clone(),
+        };
+
+        // The algorithms of digests in the trailers are not known until the body has been read
+        let hashers = if announced {
+            Hashers::new(Algorithm::ALL)
+        } else {
+            Hashers::new(expected.iter().map(|e| e.algorithm))
+        };
+        let (tx, verified) = BodyStream::channel(1);
+        let (result_tx, result_rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let mut hashers = hashers;
+            let mut forwarding = true;
+            while let Some(chunk) = stream.next_chunk().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(err) => return tx.abort(err).await,
+                };
+                hashers.update(&chunk);
+                // Once the router has dropped the body, the rest is only hashed
+                forwarding = forwarding && tx.send(chunk).await.is_ok();
+            }
+            let res = match stream.trailers() {
+                Some(trailers) => Expected::parse(trailers, repr, &mut expected),
+                None => Ok(()),
+            }
+            .and_then(|()| hashers.verify(&expected));
+            match &res {
+                Ok(()) => {
+                    if let Some(trailers) = stream.trailers() {
+                        tx.set_trailers(trailers.clone());
+                    }
+                }
+                Err(err) => tx.abort(BodyError::Decode(Arc::new(err.clone()))).await,
+            }
+            _ = result_tx.send(res);
+        });
+
+        let mut routed = request.clone();
+        routed.body = Body::Stream(verified);
+        let res = self.inner.route(&routed).await;
+        drop(routed);
+        match result_rx.await {
+            Ok(Err(err)) => Ok(Self::reject(request, &err)),
+            // The body could not be read, so the router has already seen the error
+            Err(_) | Ok(Ok(())) => res,
+        }
+    }
+}
+
+/// A hash algorithm from the Hash Algorithms for HTTP Digest Fields registry
+/// SPEC: RFC 9530 - 7.2. Hash Algorithms for HTTP Digest Fields Registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    const ALL: [Self; 2] = [Self::Sha256, Self::Sha512];
+
+    fn from_key(key: &[u8]) -> Option<Self> {
+        match key {
+            b"sha-256" => Some(Self::Sha256),
+            b"sha-512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    fn context(self) -> Context {
+        Context::new(match self {
+            Self::Sha256 => &SHA256,
+            Self::Sha512 => &SHA512,
+        })
+    }
+}
+
+/// A digest sent by the client
+struct Expected {
+    field: &'static str,
+    algorithm: Algorithm,
+    /// Base64 without padding
+    digest: Bytes,
+}
+
+impl Expected {
+    /// Adds the digests of supported algorithms from the digest fields of `headers`
+    fn parse(headers: &HeaderMap, repr: bool, out: &mut Vec<Self>) -> Result<(), DigestError> {
+        Self::parse_field(headers, Builtin::ContentDigest, out)?;
+        if repr {
+            Self::parse_field(headers, Builtin::ReprDigest, out)?;
+        }
+        Ok(())
+    }
+
+    /// SPEC: RFC 9530 - 2. The Content-Digest Field
+    /// ABNF: Content-Digest = sf-dictionary
+    /// Each member is an algorithm key with a byte sequence, which is base64 between colons
+    fn parse_field(
+        headers: &HeaderMap,
+        builtin: Builtin,
+        out: &mut Vec<Self>,
+    ) -> Result<(), DigestError> {
+        let Some(values) = headers.get(&HeaderName::builtin(builtin)) else {
+            return Ok(());
+        };
+        let field = builtin.as_str();
+        let values = values.collect();
+        for member in values.split(|b| *b == b',') {
+            let member = member.trim_ascii();
+            let (key, value) = member
+                .iter()
+                .position(|b| *b == b'=')
+                .map(|eq| (&member[..eq], &member[eq + 1..]))
+                .ok_or(DigestError::Malformed(field))?;
+            // Parameters are allowed, but none are defined
+            let value = match value.iter().position(|b| *b == b';') {
+                Some(params) => &value[..params],
+                None => value,
+            };
+            let digest = value
+                .strip_prefix(b":")
+                .and_then(|value| value.strip_suffix(b":"))
+                .filter(|digest| {
+                    digest
+                        .iter()
+                        .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'='))
+                })
+                .ok_or(DigestError::Malformed(field))?;
+            if let Some(algorithm) = Algorithm::from_key(key) {
+                out.push(Self {
+                    field,
+                    algorithm,
+                    digest: Bytes::copy_from_slice(trim_padding(digest)),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Padding is optional in byte sequences, so digests are compared without it
+fn trim_padding(digest: &[u8]) -> &[u8] {
+    let end = digest.iter().rposition(|b| *b != b'=').map_or(0, |i| i + 1);
+    &digest[..end]
+}
+
+/// Hashes the body with every algorithm which might be needed
+struct Hashers(Vec<(Algorithm, Context)>);
+
+impl Hashers {
+    fn new(algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        let mut hashers: Vec<(Algorithm, Context)> = Vec::new();
+        for algorithm in algorithms {
+            if !hashers.iter().any(|(a, _)| *a == algorithm) {
+                hashers.push((algorithm, algorithm.context()));
+            }
+        }
+        Self(hashers)
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        for (_, context) in &mut self.0 {
+            context.update(data);
+        }
+    }
+
+    fn verify(self, expected: &[Expected]) -> Result<(), DigestError> {
+        let digests: Vec<(Algorithm, String)> = self
+            .0
+            .into_iter()
+            .map(|(algorithm, context)| {
+                (
+                    algorithm,
+                    base64::encode(context.finish().as_ref(), Alphabet::Standard),
+                )
+            })
+            .collect();
+        for expected in expected {
+            let digest = digests
+                .iter()
+                .find(|(algorithm, _)| *algorithm == expected.algorithm)
+                .map(|(_, digest)| trim_padding(digest.as_bytes()));
+            // Digests in trailers which were not announced were not computed, and are ignored
+            if digest.is_some_and(|digest| expected.digest != digest) {
+                return Err(DigestError::Mismatch(expected.field));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::method::Method;
+
+    const HELLO_SHA256: &[u8] = b"sha-256=:uU0nuZNNPgilLlLX2n2r+sSE7+N6U4DukIj3rOLvzek=:";
+
+    struct Echo;
+
+    impl Router for Echo {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let body = request.body.collect(None).await?;
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(body)
+                .build())
+        }
+    }
+
+    struct Ignore;
+
+    impl Router for Ignore {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    fn request(headers: &[(Builtin, &'static [u8])], body: Body) -> Request {
+        let mut request = Request::new(Method::POST, "/");
+        for (name, value) in headers {
+            request
+                .headers
+                .entry(HeaderName::builtin(*name))
+                .push(Bytes::from_static(value));
+        }
+        request.body = body;
+        request
+    }
+
+    /// A chunked body of `hello world`, with the given trailers
+    fn chunked(trailers: &[(Builtin, &'static [u8])]) -> Body {
+        let (tx, stream) = BodyStream::channel(4);
+        let mut map = HeaderMap::new();
+        for (name, value) in trailers {
+            map.entry(HeaderName::builtin(*name))
+                .push(Bytes::from_static(value));
+        }
+        tokio::spawn(async move {
+            tx.send(Bytes::from_static(b"hello ")).await.unwrap();
+            tx.send(Bytes::from_static(b"world")).await.unwrap();
+            tx.set_trailers(map);
+        });
+        Body::Stream(stream)
+    }
+
+    #[tokio::test]
+    async fn header_digest() {
+        let router = VerifyDigest::new(Echo);
+        let body = Body::Full(Bytes::from_static(b"hello world"));
+        let res = router
+            .route(&request(&[(Builtin::ContentDigest, HELLO_SHA256)], body))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+
+        let body = Body::Full(Bytes::from_static(b"hello there"));
+        let res = router
+            .route(&request(&[(Builtin::ReprDigest, HELLO_SHA256)], body))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+
+        let res = router
+            .route(&request(
+                &[(Builtin::ContentDigest, b"sha-256=uU0n")],
+                Body::None,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn trailer_digest() {
+        let router = VerifyDigest::new(Echo);
+        let announce = (Builtin::Trailer, &b"Content-Digest"[..]);
+        let res = router
+            .route(&request(
+                &[announce],
+                chunked(&[(Builtin::ContentDigest, HELLO_SHA256)]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(res.body.collect(None).await.unwrap(), "hello world");
+
+        // Unsupported algorithms are ignored
+        let res = router
+            .route(&request(
+                &[announce],
+                chunked(&[(Builtin::ContentDigest, b"md5=:XrY7u+Ae7tCTyyK7j1rNww==:")]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+
+        let wrong = b"sha-512=:z4PhNX7vuL3xVChQ1m2AB9Yg5AULVxXcg/SpIdNs6c5H0NE8XYXysP+DGNKHfuwvY7kxvUdBeoGlODJ6+SfaPg==:";
+        let res = router
+            .route(&request(
+                &[announce],
+                chunked(&[(Builtin::ContentDigest, wrong)]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn unread_body_is_verified() {
+        let router = VerifyDigest::new(Ignore);
+        let res = router
+            .route(&request(
+                &[(Builtin::ContentDigest, b"sha-256=:AAAA:")],
+                chunked(&[]),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::BAD_REQUEST);
+    }
+}
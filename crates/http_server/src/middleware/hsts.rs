@@ -0,0 +1,202 @@
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        header::{Host, HostWithPort, StrictTransportSecurity, StrictTransportSecurityValue},
+        method::Method,
+        request::{Request, Scheme},
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+    proxy::ForwardedClient,
+};
+
+/// Adds a Strict-Transport-Security field to responses sent over HTTPS, and optionally
+/// redirects requests received over plain HTTP to the same target over HTTPS
+/// The scheme is the one of the connection, or the one sent by a trusted proxy, see
+/// [`Request::scheme`]
+/// SPEC: RFC 6797 - 7. Server Processing Model
+pub struct Hsts<R: Router> {
+    inner: R,
+    policy: StrictTransportSecurityValue,
+    redirect: Option<StatusCode>,
+    https_port: Option<u16>,
+}
+
+impl<R: Router> Hsts<R> {
+    /// A policy kept for a year, without redirects
+    pub fn new(inner: R) -> Self {
+        Self::with_policy(inner, StrictTransportSecurityValue::new(365 * 24 * 60 * 60))
+    }
+
+    pub fn with_policy(inner: R, policy: StrictTransportSecurityValue) -> Self {
+        Self {
+            inner,
+            policy,
+            redirect: None,
+            https_port: None,
+        }
+    }
+
+    /// Answers plain HTTP requests with a redirect to HTTPS, with 301 Moved Permanently or 308
+    /// Permanent Redirect, which keeps the method and body of the request
+    /// Panics if the status is not 301 or 308
+    /// SPEC: RFC 6797 - 7.2. HTTP Request Type
+    pub fn with_redirect(mut self, status: StatusCode) -> Self {
+        assert!(
+            status == StatusCode::MOVED_PERMANENTLY || status == StatusCode::PERMANENT_REDIRECT,
+            "{status:?} is not a permanent redirect"
+        );
+        self.redirect = Some(status);
+        self
+    }
+
+    /// The port HTTPS is served on for redirects, 443 by default
+    pub fn with_https_port(mut self, port: u16) -> Self {
+        self.https_port = (port != 443).then_some(port);
+        self
+    }
+
+    /// The HTTPS URI of the request, None when the request names no valid host
+    fn https_location(&self, request: &Request) -> Option<Bytes> {
+        let host = match request
+            .extensions
+            .get::<ForwardedClient>()
+            .and_then(|client| client.host.as_ref())
+        {
+            Some(host) => std::str::from_utf8(host)
+                .ok()?
+                .parse::<HostWithPort>()
+                .ok()?,
+            None => request.headers.get_header::<Host>().ok()??,
+        };
+        let authority = HostWithPort {
+            host: host.host,
+            port: self.https_port,
+        };
+        let path = path_and_query(&request.target);
+        let mut location = format!("https://{authority}").into_bytes();
+        location.extend_from_slice(&path);
+        Some(location.into())
+    }
+}
+
+/// The path and query of a request target in origin-form or absolute-form
+fn path_and_query(target: &Bytes) -> Bytes {
+    if target.starts_with(b"/") {
+        return target.clone();
+    }
+    let Some(start) = target.windows(3).position(|w| w == b"://") else {
+        return Bytes::from_static(b"/");
+    };
+    let authority = start + 3;
+    match target[authority..]
+        .iter()
+        .position(|b| matches!(b, b'/' | b'?'))
+    {
+        Some(end) if target[authority + end] == b'/' => target.slice(authority + end..),
+        Some(end) => [&b"/"[..], &target[authority + end..]].concat().into(),
+        None => Bytes::from_static(b"/"),
+    }
+}
+
+impl<R: Router> Router for Hsts<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if request.scheme() == Scheme::Https {
+            let mut res = self.inner.route(request).await?;
+            res.headers
+                .set_header::<StrictTransportSecurity>(self.policy);
+            return Ok(res);
+        }
+        // SPEC: RFC 6797 - 7.2. HTTP Request Type
+        // The field is not sent over plain HTTP, where an attacker could have added it
+        match self.redirect {
+            Some(status) if request.method != Method::CONNECT => {
+                let location = self
+                    .https_location(request)
+                    .ok_or_else(|| RouterError::BadRequest("invalid host".into()))?;
+                Ok(ResponseBuilder::redirect(status, location).build())
+            }
+            _ => self.inner.route(request).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        header::{HeaderField, Location},
+        request::RequestBuilder,
+    };
+
+    struct Hello;
+
+    impl Router for Hello {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    fn request(target: &'static [u8], scheme: Scheme) -> Request {
+        RequestBuilder::get(Bytes::from_static(target))
+            .add_header(
+                &Bytes::from_static(b"Host"),
+                Bytes::from_static(b"example.com:8080"),
+            )
+            .extension(scheme)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn policy_over_https() {
+        let hsts = Hsts::with_policy(
+            Hello,
+            StrictTransportSecurityValue::new(600).with_include_subdomains(true),
+        )
+        .with_redirect(StatusCode::PERMANENT_REDIRECT);
+        let res = hsts.route(&request(b"/", Scheme::Https)).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert_eq!(
+            res.headers.get(&StrictTransportSecurity::NAME).unwrap()[0],
+            "max-age=600; includeSubDomains"
+        );
+
+        // Plain HTTP is served without the field unless redirected
+        let res = Hsts::new(Hello)
+            .route(&request(b"/", Scheme::Http))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(!res.headers.contains(&StrictTransportSecurity::NAME));
+    }
+
+    #[tokio::test]
+    async fn redirect_to_https() {
+        let hsts = Hsts::new(Hello).with_redirect(StatusCode::MOVED_PERMANENTLY);
+        for (target, location) in [
+            (&b"/a/b?c=d"[..], "https://example.com/a/b?c=d"),
+            (b"http://example.com:8080/a?b", "https://example.com/a?b"),
+            (b"http://example.com?b", "https://example.com/?b"),
+        ] {
+            let res = hsts.route(&request(target, Scheme::Http)).await.unwrap();
+            assert_eq!(res.status, StatusCode::MOVED_PERMANENTLY);
+            assert_eq!(res.headers.get(&Location::NAME).unwrap()[0], location);
+            assert!(!res.headers.contains(&StrictTransportSecurity::NAME));
+        }
+
+        let hsts = hsts.with_https_port(8443);
+        let res = hsts.route(&request(b"/", Scheme::Http)).await.unwrap();
+        assert_eq!(
+            res.headers.get(&Location::NAME).unwrap()[0],
+            "https://example.com:8443/"
+        );
+
+        let mut req = request(b"/", Scheme::Http);
+        req.headers.remove(&Host::NAME);
+        assert!(matches!(
+            hsts.route(&req).await,
+            Err(RouterError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,625 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::{BuildHasher, Hasher, RandomState},
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::{
+    Router, RouterError,
+    clock::{SharedClock, TokioClock},
+    http::{
+        Body,
+        header::{Authorization, Builtin, EntityTag, HeaderField, HeaderMap, HeaderName},
+        request::Request,
+        response::{Response, StatusCode},
+    },
+    middleware::buffer::buffer_body,
+};
+
+/// A response kept by an [`IdempotencyStore`] to be replayed
+#[derive(Debug, Clone)]
+pub struct StoredResponse {
+    pub status: StatusCode,
+    pub message: Bytes,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+/// The state of a key when a request with it arrives
+#[derive(Debug, Clone)]
+pub enum Reservation {
+    /// The key was unused, and is now reserved for this request
+    Reserved,
+    /// A request with the key is still being handled
+    InProgress,
+    /// The key was used for a different request
+    Mismatch,
+    /// The response to the first request with the key
+    Completed(StoredResponse),
+}
+
+/// Where [`Idempotency`] keeps the state of each key
+/// A key is reserved until it is either completed, or released so the request can be retried
+pub trait IdempotencyStore: Send + Sync + 'static {
+    /// Reserves `key` for a request identified by `fingerprint`, unless it is already in use
+    fn reserve(&self, key: &Bytes, fingerprint: &Bytes, now: Instant) -> Reservation;
+
+    /// Stores the response for a reserved key, to be replayed for `ttl` from `now`
+    fn complete(&self, key: &Bytes, response: StoredResponse, now: Instant, ttl: Duration);
+
+    /// Releases a reserved key without storing a response
+    fn release(&self, key: &Bytes);
+}
+
+impl<S: IdempotencyStore> IdempotencyStore for Arc<S> {
+    fn reserve(&self, key: &Bytes, fingerprint: &Bytes, now: Instant) -> Reservation {
+        (**self).reserve(key, fingerprint, now)
+    }
+
+    fn complete(&self, key: &Bytes, response: StoredResponse, now: Instant, ttl: Duration) {
+        (**self).complete(key, response, now, ttl)
+    }
+
+    fn release(&self, key: &Bytes) {
+        (**self).release(key)
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    fingerprint: Bytes,
+    response: Option<(StoredResponse, Instant)>,
+    /// Tells the entry apart from earlier entries with the same key in [`Entries::order`]
+    reservation: u64,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    map: HashMap<Bytes, Entry>,
+    /// The keys in the order they were reserved, the oldest is dropped first
+    order: VecDeque<(u64, Bytes)>,
+    reservations: u64,
+}
+
+/// Keeps keys in memory, so they are not shared between servers
+/// Keys are chosen by clients, so at most [`Self::DEFAULT_MAX_ENTRIES`] are kept, see
+/// [`Self::with_max_entries`], and the oldest key is dropped to make room for a new one
+#[derive(Debug)]
+pub struct MemoryStore {
+    entries: Mutex<Entries>,
+    max_entries: usize,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            entries: Mutex::default(),
+            max_entries: Self::DEFAULT_MAX_ENTRIES,
+        }
+    }
+}
+
+impl MemoryStore {
+    pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most keys kept at once, at least one
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+}
+
+impl IdempotencyStore for MemoryStore {
+    fn reserve(&self, key: &Bytes, fingerprint: &Bytes, now: Instant) -> Reservation {
+        let mut entries = self.entries.lock().unwrap();
+        let Entries {
+            map,
+            order,
+            reservations,
+        } = &mut *entries;
+        if let Some(entry) = map.get(key) {
+            match &entry.response {
+                Some((_, expires)) if *expires <= now => {}
+                _ if entry.fingerprint != fingerprint => return Reservation::Mismatch,
+                Some((response, _)) => return Reservation::Completed(response.clone()),
+                None => return Reservation::InProgress,
+            }
+        }
+        *reservations += 1;
+        map.insert(
+            key.clone(),
+            Entry {
+                fingerprint: fingerprint.clone(),
+                response: None,
+                reservation: *reservations,
+            },
+        );
+        order.push_back((*reservations, key.clone()));
+        // Expired and dropped keys are removed from the front, along with the oldest keys while
+        // there are too many, each key is only visited once
+        while let Some((reservation, oldest)) = order.front() {
+            let current = map
+                .get(oldest)
+                .filter(|entry| entry.reservation == *reservation);
+            let expired = current.is_some_and(|entry| {
+                entry
+                    .response
+                    .as_ref()
+                    .is_some_and(|(_, expires)| *expires <= now)
+            });
+            let full = map.len() > self.max_entries || order.len() > 2 * self.max_entries;
+            if current.is_some() && !expired && !full {
+                break;
+            }
+            if current.is_some() {
+                map.remove(oldest);
+            }
+            order.pop_front();
+        }
+        Reservation::Reserved
+    }
+
+    fn complete(&self, key: &Bytes, response: StoredResponse, now: Instant, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.map.get_mut(key) {
+            entry.response = Some((response, now + ttl));
+        }
+    }
+
+    fn release(&self, key: &Bytes) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries
+            .map
+            .get(key)
+            .is_some_and(|entry| entry.response.is_none())
+        {
+            entries.map.remove(key);
+        }
+    }
+}
+
+/// Identifies the caller of a request, keys are only shared between requests of the same caller
+pub type IdempotencyScope = Arc<dyn Fn(&Request) -> Bytes + Send + Sync>;
+
+/// The keys credentials are hashed with, chosen once per process
+static CALLER_KEYS: LazyLock<[RandomState; 2]> =
+    LazyLock::new(|| [RandomState::new(), RandomState::new()]);
+
+/// A digest of the credentials of the request, so they aren't kept by the store, or the
+/// address of the client for requests without any
+fn caller(request: &Request) -> Bytes {
+    match request.headers.get(&Authorization::NAME) {
+        Some(value) => CALLER_KEYS
+            .iter()
+            .flat_map(|keys| {
+                let mut hasher = keys.build_hasher();
+                for line in value.iter() {
+                    hasher.write(line);
+                    hasher.write_u8(b'\n');
+                }
+                hasher.finish().to_be_bytes()
+            })
+            .collect(),
+        None => match request.client_addr() {
+            Some(addr) => Bytes::from(addr.to_string()),
+            None => Bytes::new(),
+        },
+    }
+}
+
+/// Releases the reservation of a request which did not complete, such as when the handler
+/// fails or the connection is dropped
+struct Reserved<'a, S: IdempotencyStore> {
+    store: &'a S,
+    key: &'a Bytes,
+    completed: bool,
+}
+
+impl<S: IdempotencyStore> Drop for Reserved<'_, S> {
+    fn drop(&mut self) {
+        if !self.completed {
+            self.store.release(self.key);
+        }
+    }
+}
+
+/// Makes non-idempotent requests, such as POST and PATCH, with an Idempotency-Key safe to retry
+/// The response to the first request with a key is stored, and replayed to later requests
+/// with the same key, while the first is still being handled they get 409 Conflict
+/// Reusing a key for a different method or target gets 422 Unprocessable Content
+/// Keys are scoped to the caller, see [`Self::with_scope`], so one client can't replay the
+/// response stored for another
+/// Server errors, failed routes and responses larger than the body limit are not stored, so
+/// the request can be retried
+/// SPEC: draft-ietf-httpapi-idempotency-key-header - 2. The Idempotency-Key HTTP Request Header Field
+pub struct Idempotency<R: Router, S: IdempotencyStore = MemoryStore> {
+    inner: R,
+    store: S,
+    ttl: Duration,
+    max_body: usize,
+    scope: IdempotencyScope,
+    body_limit: Option<usize>,
+    clock: SharedClock,
+}
+
+impl<R: Router> Idempotency<R> {
+    pub fn new(inner: R) -> Self {
+        Self::with_store(inner, MemoryStore::new())
+    }
+}
+
+impl<R: Router, S: IdempotencyStore> Idempotency<R, S> {
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+    pub const DEFAULT_MAX_BODY: usize = 64 * 1024;
+
+    pub fn with_store(inner: R, store: S) -> Self {
+        Self {
+            inner,
+            store,
+            ttl: Self::DEFAULT_TTL,
+            max_body: Self::DEFAULT_MAX_BODY,
+            scope: Arc::new(caller),
+            body_limit: None,
+            clock: TokioClock::shared(),
+        }
+    }
+
+    /// How long responses are replayed for
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The largest response body which is stored
+    pub fn with_max_body(mut self, max_body: usize) -> Self {
+        self.max_body = max_body;
+        self
+    }
+
+    /// Identifies the caller of a request, such as by the identity an authentication middleware
+    /// added to its extensions
+    /// By default a digest of the Authorization field of the request is used, or the address of
+    /// the client for requests without one
+    pub fn with_scope(mut self, scope: impl Fn(&Request) -> Bytes + Send + Sync + 'static) -> Self {
+        self.scope = Arc::new(scope);
+        self
+    }
+
+    /// Also fingerprints request bodies of up to `limit` bytes, so reusing a key with a different
+    /// body gets 422 Unprocessable Content, larger bodies are rejected
+    /// The body is kept in memory for the inner router
+    pub fn with_body_fingerprint(mut self, limit: Option<usize>) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// The key of the request, without the quotes of the sf-string
+    /// ABNF: Idempotency-Key = sf-string
+    fn key(request: &Request) -> Option<Bytes> {
+        let value = request
+            .headers
+            .get(&HeaderName::builtin(Builtin::IdempotencyKey))?;
+        if value.len() != 1 {
+            return None;
+        }
+        let key = value[0].trim_ascii();
+        let key = key
+            .strip_prefix(b"\"")
+            .and_then(|key| key.strip_suffix(b"\""))
+            .unwrap_or(key);
+        (!key.is_empty()).then(|| Bytes::copy_from_slice(key))
+    }
+}
+
+impl<R: Router, S: IdempotencyStore> Router for Idempotency<R, S> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let key = match Self::key(request) {
+            Some(key) if !request.method.is_idempotent() => key,
+            _ => return self.inner.route(request).await,
+        };
+        // The scope is prefixed with its length, so it can't run into the key
+        let scope = (self.scope)(request);
+        let key: Bytes = [scope.len().to_string().as_bytes(), b":", &scope, &key]
+            .concat()
+            .into();
+        let mut request = request.clone();
+        let mut fingerprint =
+            [request.method.to_string().as_bytes(), b" ", &request.target].concat();
+        if let Some(limit) = self.body_limit {
+            let body = request.body.collect(Some(limit)).await?;
+            fingerprint.push(b' ');
+            fingerprint.extend_from_slice(&EntityTag::from_content(&body).tag);
+            request.body = Body::Full(body);
+        }
+        let fingerprint = Bytes::from(fingerprint);
+
+        match self.store.reserve(&key, &fingerprint, self.clock.now()) {
+            Reservation::Reserved => {}
+            Reservation::InProgress => {
+                return Err(RouterError::Custom(
+                    StatusCode::CONFLICT,
+                    "a request with this idempotency key is in progress".into(),
+                ));
+            }
+            Reservation::Mismatch => {
+                return Err(RouterError::Custom(
+                    StatusCode::UNPROCESSABLE_CONTENT,
+                    "the idempotency key was used for a different request".into(),
+                ));
+            }
+            Reservation::Completed(stored) => {
+                return Ok(Response {
+                    version: request.version,
+                    status: stored.status,
+                    message: stored.message,
+                    headers: stored.headers,
+                    body: Body::Full(stored.body),
+                });
+            }
+        }
+
+        let mut reserved = Reserved {
+            store: &self.store,
+            key: &key,
+            completed: false,
+        };
+        let mut res = self.inner.route(&request).await?;
+        if res.status.is_server_error() {
+            return Ok(res);
+        }
+        if let Some(body) = buffer_body(&mut res.body, self.max_body).await? {
+            self.store.complete(
+                &key,
+                StoredResponse {
+                    status: res.status,
+                    message: res.message.clone(),
+                    headers: res.headers.clone(),
+                    body,
+                },
+                self.clock.now(),
+                self.ttl,
+            );
+            reserved.completed = true;
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        http::{method::Method, response::ResponseBuilder},
+    };
+
+    #[derive(Default)]
+    struct Counter {
+        calls: AtomicU32,
+        gate: Option<Arc<Notify>>,
+    }
+
+    impl Router for Arc<Counter> {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if let Some(gate) = &self.gate {
+                gate.notified().await;
+            }
+            let mut res = ResponseBuilder::from_req(request, StatusCode::CREATED).build();
+            res.body = Body::Full(Bytes::from(calls.to_string()));
+            Ok(res)
+        }
+    }
+
+    fn request(target: &'static [u8], key: Option<&'static [u8]>) -> Request {
+        let mut request = Request::new(Method::POST, Bytes::from_static(target));
+        if let Some(key) = key {
+            request
+                .headers
+                .entry(HeaderName::builtin(Builtin::IdempotencyKey))
+                .push(Bytes::from_static(key));
+        }
+        request
+    }
+
+    async fn body(res: Response) -> Bytes {
+        res.body.collect(None).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let clock = Arc::new(MockClock::new());
+        let router = Idempotency::new(Arc::new(Counter::default()))
+            .with_ttl(Duration::from_secs(60))
+            .with_clock(clock.clone());
+
+        let res = router
+            .route(&request(b"/pay", Some(b"\"a\"")))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(body(res).await, "1");
+        let res = router
+            .route(&request(b"/pay", Some(b"\"a\"")))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::CREATED);
+        assert_eq!(body(res).await, "1");
+
+        // Requests without a key, or with another key, are handled
+        let res = router.route(&request(b"/pay", None)).await.unwrap();
+        assert_eq!(body(res).await, "2");
+        let res = router
+            .route(&request(b"/pay", Some(b"\"b\"")))
+            .await
+            .unwrap();
+        assert_eq!(body(res).await, "3");
+
+        assert!(matches!(
+            router.route(&request(b"/refund", Some(b"\"a\""))).await,
+            Err(RouterError::Custom(StatusCode::UNPROCESSABLE_CONTENT, _))
+        ));
+
+        clock.advance(Duration::from_secs(61));
+        let res = router
+            .route(&request(b"/pay", Some(b"\"a\"")))
+            .await
+            .unwrap();
+        assert_eq!(body(res).await, "4");
+    }
+
+    #[tokio::test]
+    async fn scoped_to_caller() {
+        let router = Idempotency::new(Arc::new(Counter::default()));
+        let authorized = |credentials: &'static [u8]| {
+            let mut req = request(b"/pay", Some(b"\"a\""));
+            req.headers
+                .entry(Authorization::NAME)
+                .push(Bytes::from_static(credentials));
+            req
+        };
+        let res = router.route(&authorized(b"Bearer one")).await.unwrap();
+        assert_eq!(body(res).await, "1");
+        // Another caller reusing the key doesn't get the stored response
+        let res = router.route(&authorized(b"Bearer two")).await.unwrap();
+        assert_eq!(body(res).await, "2");
+        let res = router.route(&authorized(b"Bearer one")).await.unwrap();
+        assert_eq!(body(res).await, "1");
+
+        let router = Idempotency::new(Arc::new(Counter::default()))
+            .with_scope(|req| Bytes::copy_from_slice(&req.target));
+        let res = router.route(&authorized(b"Bearer one")).await.unwrap();
+        assert_eq!(body(res).await, "1");
+        let res = router.route(&authorized(b"Bearer two")).await.unwrap();
+        assert_eq!(body(res).await, "1");
+    }
+
+    #[test]
+    fn caller_digest() {
+        let authorized = |credentials: &'static [u8]| {
+            let mut req = request(b"/pay", None);
+            req.headers
+                .entry(Authorization::NAME)
+                .push(Bytes::from_static(credentials));
+            req
+        };
+        let one = caller(&authorized(b"Bearer one"));
+        assert_eq!(one.len(), 16);
+        assert!(!one.windows(3).any(|window| window == b"one"));
+        assert_eq!(one, caller(&authorized(b"Bearer one")));
+        assert_ne!(one, caller(&authorized(b"Bearer two")));
+    }
+
+    #[test]
+    fn store_is_capped() {
+        let store = MemoryStore::new().with_max_entries(2);
+        let now = Instant::now();
+        let ttl = Duration::from_secs(60);
+        let fingerprint = Bytes::from_static(b"POST /pay");
+        let response = StoredResponse {
+            status: StatusCode::CREATED,
+            message: Bytes::new(),
+            headers: HeaderMap::new(),
+            body: Bytes::new(),
+        };
+        let reserve =
+            |key: &'static [u8], now| store.reserve(&Bytes::from_static(key), &fingerprint, now);
+        for key in [b"a", b"b"] {
+            assert!(matches!(reserve(key, now), Reservation::Reserved));
+            store.complete(&Bytes::from_static(key), response.clone(), now, ttl);
+        }
+        assert!(matches!(reserve(b"a", now), Reservation::Completed(_)));
+
+        // The oldest key makes room for a new one
+        assert!(matches!(reserve(b"c", now), Reservation::Reserved));
+        assert!(matches!(reserve(b"a", now), Reservation::Reserved));
+        assert!(matches!(reserve(b"c", now), Reservation::InProgress));
+        assert_eq!(store.entries.lock().unwrap().map.len(), 2);
+
+        // Released and reserved again, a key doesn't grow the order without bound
+        for _ in 0..100 {
+            store.release(&Bytes::from_static(b"c"));
+            assert!(matches!(reserve(b"c", now), Reservation::Reserved));
+        }
+        let entries = store.entries.lock().unwrap();
+        assert!(entries.map.contains_key(b"c".as_slice()));
+        assert!(entries.order.len() <= 4);
+    }
+
+    #[tokio::test]
+    async fn body_fingerprint() {
+        let router =
+            Idempotency::new(Arc::new(Counter::default())).with_body_fingerprint(Some(1024));
+        let with_body = |body: &'static [u8]| {
+            let mut req = request(b"/pay", Some(b"\"a\""));
+            req.body = Body::Full(Bytes::from_static(body));
+            req
+        };
+        let res = router.route(&with_body(b"amount=1")).await.unwrap();
+        assert_eq!(body(res).await, "1");
+        let res = router.route(&with_body(b"amount=1")).await.unwrap();
+        assert_eq!(body(res).await, "1");
+        assert!(matches!(
+            router.route(&with_body(b"amount=2")).await,
+            Err(RouterError::Custom(StatusCode::UNPROCESSABLE_CONTENT, _))
+        ));
+    }
+
+    #[tokio::test]
+    async fn concurrent() {
+        let gate = Arc::new(Notify::new());
+        let router = Arc::new(Idempotency::new(Arc::new(Counter {
+            calls: AtomicU32::new(0),
+            gate: Some(gate.clone()),
+        })));
+
+        let first = tokio::spawn({
+            let router = router.clone();
+            async move { router.route(&request(b"/pay", Some(b"\"a\""))).await }
+        });
+        while router.inner.calls.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(matches!(
+            router.route(&request(b"/pay", Some(b"\"a\""))).await,
+            Err(RouterError::Custom(StatusCode::CONFLICT, _))
+        ));
+
+        gate.notify_one();
+        assert_eq!(body(first.await.unwrap().unwrap()).await, "1");
+    }
+
+    #[tokio::test]
+    async fn cancelled_request_is_released() {
+        let gate = Arc::new(Notify::new());
+        let router = Idempotency::new(Arc::new(Counter {
+            calls: AtomicU32::new(0),
+            gate: Some(gate.clone()),
+        }));
+
+        let req = request(b"/pay", Some(b"\"a\""));
+        let cancelled = tokio::time::timeout(Duration::from_millis(10), router.route(&req)).await;
+        assert!(cancelled.is_err());
+
+        gate.notify_one();
+        let res = router.route(&req).await.unwrap();
+        assert_eq!(body(res).await, "2");
+    }
+}
@@ -0,0 +1,237 @@
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    http::{
+        Body,
+        header::{ContentType, MediaType},
+        method::Method,
+        request::Request,
+        response::Response,
+        uri::form_urlencoded_decode,
+    },
+};
+
+/// The method a request was sent with, inserted into the request extensions by
+/// [`MethodOverride`] when it rewrote the method
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalMethod(pub Method);
+
+/// Rewrites the method of POST requests to the one named by a header or form field, for
+/// clients such as HTML forms which can only send GET and POST
+/// Only the methods in [`Self::with_methods`] are taken, PUT, PATCH and DELETE by default, any
+/// other value is ignored and the request is routed as POST
+/// Other methods are never overridden, so a GET link can't be turned into a DELETE
+pub struct MethodOverride<R: Router> {
+    inner: R,
+    header: Option<Bytes>,
+    form_field: Option<String>,
+    methods: Vec<Method>,
+}
+
+impl<R: Router> MethodOverride<R> {
+    /// The largest form body which is read for the field
+    pub const FORM_LIMIT: usize = 1024 * 1024;
+
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            header: Some(Bytes::from_static(b"X-HTTP-Method-Override")),
+            form_field: Some("_method".to_owned()),
+            methods: vec![Method::PUT, Method::PATCH, Method::DELETE],
+        }
+    }
+
+    /// The header the method is read from, `X-HTTP-Method-Override` by default, or `None` to
+    /// ignore headers
+    pub fn with_header(mut self, header: Option<Bytes>) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// The field of `application/x-www-form-urlencoded` bodies the method is read from,
+    /// `_method` by default, or `None` to leave bodies unread
+    /// The header takes precedence over the field
+    pub fn with_form_field(mut self, field: Option<&str>) -> Self {
+        self.form_field = field.map(str::to_owned);
+        self
+    }
+
+    /// The methods a request can be rewritten to
+    pub fn with_methods(mut self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.methods = methods.into_iter().collect();
+        self
+    }
+
+    /// The allowed method named by `value`, compared without case
+    fn allowed(&self, value: &[u8]) -> Option<Method> {
+        let method = Method::try_from(Bytes::from(value.trim_ascii().to_ascii_uppercase())).ok()?;
+        self.methods.contains(&method).then_some(method)
+    }
+
+    fn header_method(&self, request: &Request) -> Option<Method> {
+        let header = self.header.as_ref()?;
+        // Custom field names keep the case they were received with
+        request
+            .headers
+            .iter()
+            .find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(header))
+            .and_then(|(_, value)| value.iter().next())
+            .and_then(|value| self.allowed(value))
+    }
+
+    /// Reads the form field from the body, which is kept in memory for the inner router
+    async fn form_method(&self, request: &mut Request) -> Result<Option<Method>, RouterError> {
+        let Some(field) = &self.form_field else {
+            return Ok(None);
+        };
+        let is_form = request
+            .headers
+            .get_header::<ContentType>()
+            .ok()
+            .flatten()
+            .and_then(|value| MediaType::parse(&value))
+            .is_some_and(|media| {
+                media.ty() == b"application" && media.subtype() == b"x-www-form-urlencoded"
+            });
+        if !is_form {
+            return Ok(None);
+        }
+        let body = request.body.collect(Some(Self::FORM_LIMIT)).await?;
+        request.body = Body::Full(body.clone());
+        // A malformed form is left for the inner router to reject
+        Ok(form_urlencoded_decode(&body).ok().and_then(|pairs| {
+            pairs
+                .into_iter()
+                .find(|(name, _)| name == field)
+                .and_then(|(_, value)| self.allowed(value.as_bytes()))
+        }))
+    }
+}
+
+impl<R: Router> Router for MethodOverride<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if request.method != Method::POST {
+            return self.inner.route(request).await;
+        }
+        let mut request = request.clone();
+        let method = match self.header_method(&request) {
+            Some(method) => Some(method),
+            None => self.form_method(&mut request).await?,
+        };
+        if let Some(method) = method {
+            request.extensions.insert(OriginalMethod(request.method));
+            request.method = method;
+        }
+        self.inner.route(&request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        header::{HeaderField, HeaderName},
+        response::{ResponseBuilder, StatusCode},
+    };
+
+    /// Responds with the method it was routed, and the body it read
+    struct Echo;
+
+    impl Router for Echo {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let body = request.body.collect(None).await?;
+            let original = request
+                .extensions
+                .get::<OriginalMethod>()
+                .map(|original| original.0.to_string());
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(Bytes::from(format!(
+                    "{} {:?} {}",
+                    request.method,
+                    original,
+                    String::from_utf8_lossy(&body)
+                )))
+                .build())
+        }
+    }
+
+    async fn route(router: &MethodOverride<Echo>, request: Request) -> String {
+        let res = router.route(&request).await.unwrap();
+        let Body::Full(body) = res.body else {
+            panic!("expected a full body");
+        };
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    fn request(
+        method: Method,
+        header: Option<&'static str>,
+        form: Option<&'static str>,
+    ) -> Request {
+        let mut request = Request::new(method, "/posts/1");
+        if let Some(header) = header {
+            request
+                .headers
+                .entry(
+                    HeaderName::try_from(&Bytes::from_static(b"x-http-method-override")).unwrap(),
+                )
+                .push(Bytes::from_static(header.as_bytes()));
+        }
+        if let Some(form) = form {
+            request
+                .headers
+                .entry(ContentType::NAME)
+                .push(Bytes::from_static(b"application/x-www-form-urlencoded"));
+            request.body = Body::Full(Bytes::from_static(form.as_bytes()));
+        }
+        request
+    }
+
+    #[tokio::test]
+    async fn overrides_post() {
+        let router = MethodOverride::new(Echo);
+        assert_eq!(
+            route(&router, request(Method::POST, Some("delete"), None)).await,
+            "DELETE Some(\"POST\") "
+        );
+        // The body is still there for the inner router
+        assert_eq!(
+            route(
+                &router,
+                request(Method::POST, None, Some("_method=PUT&a=1"))
+            )
+            .await,
+            "PUT Some(\"POST\") _method=PUT&a=1"
+        );
+        assert_eq!(
+            route(
+                &router,
+                request(Method::POST, Some("PATCH"), Some("_method=PUT"))
+            )
+            .await,
+            "PATCH Some(\"POST\") _method=PUT"
+        );
+
+        // Only POST is overridden, and only to the allowed methods
+        assert_eq!(
+            route(&router, request(Method::GET, Some("DELETE"), None)).await,
+            "GET None "
+        );
+        assert_eq!(
+            route(&router, request(Method::POST, Some("CONNECT"), None)).await,
+            "POST None "
+        );
+        let router = MethodOverride::new(Echo)
+            .with_methods([Method::DELETE])
+            .with_form_field(None);
+        assert_eq!(
+            route(
+                &router,
+                request(Method::POST, Some("PUT"), Some("_method=DELETE"))
+            )
+            .await,
+            "POST None _method=DELETE"
+        );
+    }
+}
@@ -0,0 +1,45 @@
+//! Middleware are [`Router`]s which wrap another router, adding behaviour before or after it
+//!
+//! [`Router`]: crate::Router
+
+mod access_log;
+mod assets;
+mod auth;
+mod buffer;
+mod cache;
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "br"))]
+mod compression;
+mod cors;
+#[cfg(feature = "digest")]
+mod digest;
+mod hsts;
+mod idempotency;
+mod method_override;
+mod policy;
+mod rate_limit;
+mod state;
+mod timeout;
+mod versioning;
+
+pub use access_log::{
+    AccessLog, AccessLogEntry, AccessLogFormat, AccessLogSink, LogSink, WriterSink,
+};
+pub use assets::{Asset, AssetManifest, StaticAssets};
+pub use auth::{Authenticate, Authenticator};
+pub use buffer::BufferResponse;
+pub use cache::ResponseCache;
+#[cfg(any(feature = "gzip", feature = "deflate", feature = "br"))]
+pub use compression::Compression;
+pub use cors::{AllowOrigin, Cors};
+#[cfg(feature = "digest")]
+pub use digest::{DigestError, VerifyDigest};
+pub use hsts::Hsts;
+pub use idempotency::{
+    Idempotency, IdempotencyScope, IdempotencyStore, MemoryStore, Reservation, StoredResponse,
+};
+pub use method_override::{MethodOverride, OriginalMethod};
+pub use policy::{Authorize, Policy, RoutePolicy};
+pub use rate_limit::{RateLimit, RateLimitAlgorithm, RateLimitKey, RateLimited, RateLimiter};
+pub use state::AddState;
+pub use timeout::Timeout;
+pub use versioning::{ApiVersion, Versioned};
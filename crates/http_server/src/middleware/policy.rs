@@ -0,0 +1,371 @@
+use std::{sync::Arc, time::Duration};
+
+use super::{
+    rate_limit::{RateLimit, RateLimiter, too_many_requests},
+    timeout::Timeout,
+};
+use crate::{
+    Router, RouterError,
+    clock::{SharedClock, TokioClock},
+    http::{
+        Body, BodyError, BodyStream,
+        header::{Challenge, ContentLength, WWWAuthenticate},
+        request::Request,
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+};
+
+/// Decides whether a request is authorized
+pub type Authorize = Arc<dyn Fn(&Request) -> bool + Send + Sync>;
+
+/// The operational policy of a route, kept as data so it can be declared in one place, and
+/// shared between the routes of a group
+/// A policy does nothing until it is compiled into a middleware with [`Self::layer`]
+#[derive(Clone, Default)]
+pub struct RoutePolicy {
+    /// Requests which are not answered in time get [`Self::timeout_status`]
+    pub timeout: Option<Duration>,
+    /// The status of timed out requests, defaults to 503 Service Unavailable
+    pub timeout_status: Option<StatusCode>,
+    /// Requests with larger bodies get 413 Content Too Large
+    pub max_body_bytes: Option<u64>,
+    /// Requests over the limit get 429 Too Many Requests
+    pub rate_limit: Option<RateLimit>,
+    /// Requests which are not authorized get 401 Unauthorized with the challenge
+    pub auth: Option<(Authorize, Challenge)>,
+    /// Times the timeout and rate limit windows, defaults to [`TokioClock`]
+    pub clock: Option<SharedClock>,
+}
+
+impl std::fmt::Debug for RoutePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RoutePolicy")
+            .field("timeout", &self.timeout)
+            .field("timeout_status", &self.timeout_status)
+            .field("max_body_bytes", &self.max_body_bytes)
+            .field("rate_limit", &self.rate_limit)
+            .field("auth", &self.auth.is_some())
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+impl RoutePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Such as 504 Gateway Timeout for routes which are waiting on an upstream server
+    pub fn timeout_status(mut self, status: StatusCode) -> Self {
+        self.timeout_status = Some(status);
+        self
+    }
+
+    pub fn max_body_bytes(mut self, max: u64) -> Self {
+        self.max_body_bytes = Some(max);
+        self
+    }
+
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// `challenge` is sent in the WWW-Authenticate field of 401 responses, such as
+    /// [`Challenge::bearer`]
+    pub fn require_auth(
+        mut self,
+        challenge: Challenge,
+        authorize: impl Fn(&Request) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.auth = Some((Arc::new(authorize), challenge));
+        self
+    }
+
+    pub fn clock(mut self, clock: SharedClock) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// The policy of a route within a group, anything the route does not set is taken from
+    /// the group
+    pub fn inherit(self, group: &RoutePolicy) -> Self {
+        Self {
+            timeout: self.timeout.or(group.timeout),
+            timeout_status: self.timeout_status.or(group.timeout_status),
+            max_body_bytes: self.max_body_bytes.or(group.max_body_bytes),
+            rate_limit: self.rate_limit.or_else(|| group.rate_limit.clone()),
+            auth: self.auth.or_else(|| group.auth.clone()),
+            clock: self.clock.or_else(|| group.clock.clone()),
+        }
+    }
+
+    /// Compiles the policy into a middleware around `inner`
+    pub fn layer<R: Router>(self, inner: R) -> Policy<R> {
+        let clock = self.clock.clone().unwrap_or_else(TokioClock::shared);
+        let inner = match self.timeout {
+            Some(timeout) => Inner::Timeout(
+                Timeout::new(inner, timeout)
+                    .with_status(
+                        self.timeout_status
+                            .unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+                    )
+                    .with_clock(clock.clone()),
+            ),
+            None => Inner::Plain(inner),
+        };
+        Policy {
+            inner,
+            limiter: self
+                .rate_limit
+                .clone()
+                .map(|config| RateLimiter::with_clock(config, clock)),
+            policy: self,
+        }
+    }
+}
+
+/// Applies a [`RoutePolicy`] to a router
+/// Checks are applied in order: rate limit, authorization, body size, then the timeout
+/// Timed out requests fail with an error from [`Timeout`], so they reach the
+/// [`ErrorHandler`](crate::error_handler::ErrorHandler) like any other failed route
+pub struct Policy<R: Router> {
+    inner: Inner<R>,
+    policy: RoutePolicy,
+    limiter: Option<RateLimiter>,
+}
+
+enum Inner<R: Router> {
+    Plain(R),
+    Timeout(Timeout<R>),
+}
+
+impl<R: Router> Policy<R> {
+    fn reject(request: &Request, status: StatusCode) -> Response {
+        ResponseBuilder::from_req(request, status).build()
+    }
+}
+
+impl<R: Router> Router for Policy<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if let Some(limiter) = &self.limiter
+            && let Err(retry_after) = limiter.check(request)
+        {
+            return Ok(too_many_requests(request, retry_after));
+        }
+        // SPEC: RFC 9110 - 15.5.2. 401 Unauthorized
+        if let Some((authorize, challenge)) = &self.policy.auth
+            && !authorize(request)
+        {
+            return Ok(ResponseBuilder::from_req(request, StatusCode::UNAUTHORIZED)
+                .set_header::<WWWAuthenticate>(vec![challenge.clone()])
+                .build());
+        }
+
+        let limited;
+        let request = match self.policy.max_body_bytes {
+            Some(max) => {
+                if request
+                    .headers
+                    .get_header::<ContentLength>()
+                    .ok()
+                    .flatten()
+                    .is_some_and(|len| len > max)
+                {
+                    return Ok(Self::reject(request, StatusCode::CONTENT_TOO_LARGE));
+                }
+                limited = limit_body(request, max);
+                limited.as_ref().unwrap_or(request)
+            }
+            None => request,
+        };
+
+        match &self.inner {
+            Inner::Plain(inner) => inner.route(request).await,
+            Inner::Timeout(inner) => inner.route(request).await,
+        }
+    }
+}
+
+/// Bodies without a Content-Length can only be checked as they are read, so the stream is
+/// replaced by one which fails once the limit is exceeded
+fn limit_body(request: &Request, max: u64) -> Option<Request> {
+    let Body::Stream(stream) = &request.body else {
+        return None;
+    };
+    let stream = stream.clone();
+    let (tx, limited) = BodyStream::channel(1);
+    tokio::spawn(async move {
+        let mut read = 0;
+        while let Some(chunk) = stream.next_chunk().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => return tx.abort(err).await,
+            };
+            read += chunk.len() as u64;
+            if read > max {
+                let limit = usize::try_from(max).unwrap_or(usize::MAX);
+                return tx.abort(BodyError::LimitExceeded { limit }).await;
+            }
+            // The router has finished with the body
+            if tx.send(chunk).await.is_err() {
+                return;
+            }
+        }
+        if let Some(trailers) = stream.trailers() {
+            tx.set_trailers(trailers.clone());
+        }
+    });
+    let mut request = request.clone();
+    request.body = Body::Stream(limited);
+    Some(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        http::{
+            header::{Builtin, HeaderName},
+            request::RequestBuilder,
+        },
+        middleware::RateLimitKey,
+    };
+
+    struct Echo;
+
+    impl Router for Echo {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let body = request.body.collect(None).await?;
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .body(body)
+                .build())
+        }
+    }
+
+    struct Sleep;
+
+    impl Router for Sleep {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    fn request(headers: &[(&'static [u8], &'static [u8])], body: Body) -> Request {
+        let mut builder = RequestBuilder::post("/");
+        for (name, value) in headers {
+            builder = builder.add_header(&Bytes::from_static(name), Bytes::from_static(value));
+        }
+        let mut request = builder.build();
+        request.body = body;
+        request
+    }
+
+    #[tokio::test]
+    async fn rate_limit() {
+        let clock = Arc::new(MockClock::new());
+        let policy = RoutePolicy::new()
+            .clock(clock.clone())
+            .rate_limit(RateLimit::new(
+                RateLimitKey::Header(HeaderName::try_from(&Bytes::from_static(b"X-Key")).unwrap()),
+                NonZeroU32::new(2).unwrap(),
+                Duration::from_secs(60),
+            ))
+            .layer(Echo);
+        let a = request(&[(b"X-Key", b"a")], Body::None);
+        let b = request(&[(b"X-Key", b"b")], Body::None);
+        assert_eq!(policy.route(&a).await.unwrap().status, StatusCode::OK);
+        assert_eq!(policy.route(&a).await.unwrap().status, StatusCode::OK);
+        let res = policy.route(&a).await.unwrap();
+        assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+        assert!(
+            res.headers
+                .contains(&HeaderName::builtin(Builtin::RetryAfter))
+        );
+        assert_eq!(policy.route(&b).await.unwrap().status, StatusCode::OK);
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(policy.route(&a).await.unwrap().status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn auth_inherited_from_group() {
+        let group = RoutePolicy::new()
+            .require_auth(Challenge::bearer("api"), |req| {
+                req.headers
+                    .contains(&HeaderName::builtin(Builtin::Authorization))
+            })
+            .timeout(Duration::from_secs(5));
+        let policy = RoutePolicy::new().max_body_bytes(16).inherit(&group);
+        assert_eq!(policy.timeout, Some(Duration::from_secs(5)));
+        let policy = policy.layer(Echo);
+
+        let res = policy.route(&request(&[], Body::None)).await.unwrap();
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            res.headers.get_header::<WWWAuthenticate>().unwrap(),
+            Some(vec![Challenge::bearer("api")])
+        );
+        let res = policy
+            .route(&request(&[(b"Authorization", b"Bearer a")], Body::None))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn max_body() {
+        let policy = RoutePolicy::new().max_body_bytes(4).layer(Echo);
+        let res = policy
+            .route(&request(&[(b"Content-Length", b"5")], Body::None))
+            .await
+            .unwrap();
+        assert_eq!(res.status, StatusCode::CONTENT_TOO_LARGE);
+
+        let (tx, stream) = BodyStream::channel(4);
+        tokio::spawn(async move {
+            tx.send(Bytes::from_static(b"abc")).await.unwrap();
+            tx.send(Bytes::from_static(b"def")).await.unwrap();
+        });
+        let err = policy
+            .route(&request(&[], Body::Stream(stream)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::CONTENT_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        let clock = Arc::new(MockClock::new());
+        let policy = RoutePolicy::new()
+            .clock(clock.clone())
+            .timeout(Duration::from_secs(10))
+            .layer(Sleep);
+        let req = request(&[], Body::None);
+        let (res, ()) = tokio::join!(policy.route(&req), async {
+            clock.advance(Duration::from_secs(10))
+        });
+        assert!(matches!(res, Err(RouterError::Timeout)));
+
+        let policy = RoutePolicy::new()
+            .clock(clock.clone())
+            .timeout(Duration::from_secs(10))
+            .timeout_status(StatusCode::GATEWAY_TIMEOUT)
+            .layer(Sleep);
+        let (res, ()) = tokio::join!(policy.route(&req), async {
+            clock.advance(Duration::from_secs(10))
+        });
+        assert_eq!(res.unwrap_err().status_code(), StatusCode::GATEWAY_TIMEOUT);
+    }
+}
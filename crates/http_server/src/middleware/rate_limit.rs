@@ -0,0 +1,371 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::{
+    Router, RouterError,
+    clock::{SharedClock, TokioClock},
+    http::{
+        header::{Builtin, HeaderName},
+        request::Request,
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+};
+
+/// What requests are counted together by a [`RateLimit`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// All requests share one limit
+    Global,
+    /// Requests are limited per client address
+    ClientIp,
+    /// Requests are limited per value of a header, such as an API key
+    Header(HeaderName),
+}
+
+/// How requests are counted against a [`RateLimit`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RateLimitAlgorithm {
+    /// Counts requests in consecutive windows of length `per`, which allows a burst of up to
+    /// twice the limit around the end of a window
+    #[default]
+    FixedWindow,
+    /// A bucket holding up to `requests` tokens, refilled evenly over `per`, so requests are
+    /// spread out once the bucket is empty
+    TokenBucket,
+}
+
+/// Allows `requests` requests per key in each window of length `per`
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    pub key: RateLimitKey,
+    pub requests: NonZeroU32,
+    pub per: Duration,
+    pub algorithm: RateLimitAlgorithm,
+}
+
+impl RateLimit {
+    pub fn new(key: RateLimitKey, requests: NonZeroU32, per: Duration) -> Self {
+        Self {
+            key,
+            requests,
+            per,
+            algorithm: RateLimitAlgorithm::default(),
+        }
+    }
+
+    pub fn with_algorithm(mut self, algorithm: RateLimitAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Global,
+    Ip(Option<IpAddr>),
+    Header(Option<Bytes>),
+}
+
+struct Limiter {
+    config: RateLimit,
+    state: Mutex<State>,
+    clock: SharedClock,
+}
+
+#[derive(Default)]
+struct State {
+    /// The start of the window and the requests counted in it, or the time the bucket was last
+    /// refilled and the tokens left in it
+    keys: HashMap<Key, (Instant, f64)>,
+    /// When expired keys were last removed
+    swept: Option<Instant>,
+}
+
+/// Counts requests against a [`RateLimit`]
+/// Clones share their counts, so one limiter can cover several routes, or the whole server
+#[derive(Clone)]
+pub struct RateLimiter {
+    inner: Arc<Limiter>,
+}
+
+impl std::fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("config", &self.inner.config)
+            .finish()
+    }
+}
+
+impl RateLimiter {
+    /// The most keys counted at once, so clients sending a new key with each request can't
+    /// grow the table without bound
+    /// Expired keys are removed once there are this many, at most once per window, and until
+    /// then requests with a new key are rejected
+    const MAX_KEYS: usize = 4096;
+
+    pub fn new(config: RateLimit) -> Self {
+        Self::with_clock(config, TokioClock::shared())
+    }
+
+    pub fn with_clock(config: RateLimit, clock: SharedClock) -> Self {
+        Self {
+            inner: Arc::new(Limiter {
+                config,
+                state: Mutex::default(),
+                clock,
+            }),
+        }
+    }
+
+    pub fn config(&self) -> &RateLimit {
+        &self.inner.config
+    }
+
+    fn key(&self, request: &Request) -> Key {
+        match &self.inner.config.key {
+            RateLimitKey::Global => Key::Global,
+            RateLimitKey::ClientIp => Key::Ip(request.remote.map(|addr| addr.ip())),
+            RateLimitKey::Header(name) => {
+                Key::Header(request.headers.get(name).map(|v| v.collect()))
+            }
+        }
+    }
+
+    /// Counts a request, returning how long until it would be allowed if it is over the limit
+    pub fn check(&self, request: &Request) -> Result<(), Duration> {
+        let key = self.key(request);
+        let RateLimit {
+            requests,
+            per,
+            algorithm,
+            ..
+        } = self.inner.config;
+        let limit = f64::from(requests.get());
+        let now = self.inner.clock.now();
+        let mut state = self.inner.state.lock().unwrap();
+        let State { keys, swept } = &mut *state;
+        if keys.len() >= Self::MAX_KEYS && !keys.contains_key(&key) {
+            let next_sweep = swept.map_or(Duration::ZERO, |swept| {
+                per.saturating_sub(now.duration_since(swept))
+            });
+            if !next_sweep.is_zero() {
+                return Err(next_sweep);
+            }
+            // A window which has ended, or a bucket which has refilled, is the same as a new one
+            keys.retain(|_, (since, _)| now.duration_since(*since) < per);
+            *swept = Some(now);
+            if keys.len() >= Self::MAX_KEYS {
+                return Err(per);
+            }
+        }
+        match algorithm {
+            RateLimitAlgorithm::FixedWindow => {
+                let (start, count) = keys.entry(key).or_insert((now, 0.0));
+                if now.duration_since(*start) >= per {
+                    *start = now;
+                    *count = 0.0;
+                }
+                if *count >= limit {
+                    return Err(per - now.duration_since(*start));
+                }
+                *count += 1.0;
+            }
+            RateLimitAlgorithm::TokenBucket => {
+                let (updated, tokens) = keys.entry(key).or_insert((now, limit));
+                let refilled = now.duration_since(*updated).as_secs_f64() / per.as_secs_f64();
+                *tokens = (*tokens + refilled * limit).min(limit);
+                *updated = now;
+                if *tokens < 1.0 {
+                    return Err(per.mul_f64((1.0 - *tokens) / limit));
+                }
+                *tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 429 Too Many Requests, telling the client when to retry
+pub(super) fn too_many_requests(request: &Request, retry_after: Duration) -> Response {
+    let mut res = ResponseBuilder::from_req(request, StatusCode::TOO_MANY_REQUESTS).build();
+    // SPEC: RFC 9110 - 10.2.3. Retry-After
+    // Rounded up, so a client which waits exactly that long is allowed
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    res.headers.insert(
+        HeaderName::builtin(Builtin::RetryAfter),
+        Bytes::from(secs.max(1).to_string()),
+    );
+    res
+}
+
+/// Rejects requests over the limit of a [`RateLimiter`] with 429 Too Many Requests
+/// Wrap the whole router for a global limit and single routes for per-route limits, the
+/// limits of nested layers all apply
+/// SPEC: RFC 6585 - 4. 429 Too Many Requests
+pub struct RateLimited<R: Router> {
+    inner: R,
+    limiter: RateLimiter,
+}
+
+impl<R: Router> RateLimited<R> {
+    pub fn new(inner: R, limiter: RateLimiter) -> Self {
+        Self { inner, limiter }
+    }
+
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+}
+
+impl<R: Router> Router for RateLimited<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        match self.limiter.check(request) {
+            Ok(()) => self.inner.route(request).await,
+            Err(retry_after) => Ok(too_many_requests(request, retry_after)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        http::{method::Method, request::RequestBuilder},
+    };
+
+    struct Ok200;
+
+    impl Router for Ok200 {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    fn request(remote: &str) -> Request {
+        RequestBuilder::get("/")
+            .remote(remote.parse::<SocketAddr>().unwrap())
+            .build()
+    }
+
+    fn retry_after(res: &Response) -> Bytes {
+        res.headers
+            .get(&HeaderName::builtin(Builtin::RetryAfter))
+            .unwrap()
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn token_bucket_per_ip() {
+        let clock = Arc::new(MockClock::new());
+        let limit = RateLimit::new(
+            RateLimitKey::ClientIp,
+            NonZeroU32::new(2).unwrap(),
+            Duration::from_secs(10),
+        )
+        .with_algorithm(RateLimitAlgorithm::TokenBucket);
+        let router = RateLimited::new(Ok200, RateLimiter::with_clock(limit, clock.clone()));
+        let a = request("192.0.2.1:1000");
+
+        assert_eq!(router.route(&a).await.unwrap().status, StatusCode::OK);
+        // Another port of the same address shares the bucket
+        let res = router.route(&request("192.0.2.1:2000")).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        let res = router.route(&a).await.unwrap();
+        assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(retry_after(&res), "5");
+        let res = router.route(&request("192.0.2.2:1000")).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+
+        // One token is refilled every 5 seconds
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(router.route(&a).await.unwrap().status, StatusCode::OK);
+        let res = router.route(&a).await.unwrap();
+        assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn shared_global_limit() {
+        let clock = Arc::new(MockClock::new());
+        let global = RateLimiter::with_clock(
+            RateLimit::new(
+                RateLimitKey::Global,
+                NonZeroU32::new(3).unwrap(),
+                Duration::from_secs(60),
+            ),
+            clock.clone(),
+        );
+        let per_route = RateLimiter::with_clock(
+            RateLimit::new(
+                RateLimitKey::ClientIp,
+                NonZeroU32::new(1).unwrap(),
+                Duration::from_secs(60),
+            ),
+            clock.clone(),
+        );
+        let search = RateLimited::new(RateLimited::new(Ok200, per_route), global.clone());
+        let home = RateLimited::new(Ok200, global);
+
+        let a = request("192.0.2.1:1000");
+        assert_eq!(search.route(&a).await.unwrap().status, StatusCode::OK);
+        let res = search.route(&a).await.unwrap();
+        assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(retry_after(&res), "60");
+        // The rejected request still counted against the global limit
+        assert_eq!(home.route(&a).await.unwrap().status, StatusCode::OK);
+        let res = home.route(&a).await.unwrap();
+        assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS);
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(home.route(&a).await.unwrap().status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn distinct_keys_are_capped() {
+        let clock = Arc::new(MockClock::new());
+        let name = HeaderName::try_from(&Bytes::from_static(b"x-api-key")).unwrap();
+        let limiter = RateLimiter::with_clock(
+            RateLimit::new(
+                RateLimitKey::Header(name.clone()),
+                NonZeroU32::new(5).unwrap(),
+                Duration::from_secs(60),
+            ),
+            clock.clone(),
+        );
+        let request = |key: usize| {
+            let mut request = Request::new(Method::GET, "/");
+            request
+                .headers
+                .insert(name.clone(), Bytes::from(key.to_string()));
+            request
+        };
+        for key in 0..RateLimiter::MAX_KEYS {
+            assert_eq!(limiter.check(&request(key)), Ok(()));
+        }
+        // Once full, new keys are turned away, while known keys are still counted
+        let flood = RateLimiter::MAX_KEYS..RateLimiter::MAX_KEYS * 2;
+        for key in flood.clone() {
+            assert!(limiter.check(&request(key)).is_err());
+        }
+        assert_eq!(
+            limiter.inner.state.lock().unwrap().keys.len(),
+            RateLimiter::MAX_KEYS
+        );
+        assert_eq!(limiter.check(&request(0)), Ok(()));
+
+        // Once their windows end the keys are removed, making room again
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(limiter.check(&request(flood.start)), Ok(()));
+        assert_eq!(limiter.inner.state.lock().unwrap().keys.len(), 1);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{
+    Router, RouterError,
+    http::{request::Request, response::Response},
+};
+
+/// Adds a clone of some shared state to the extensions of every request, where handlers take
+/// it with the [`State`] extractor
+/// Wrap the state in an `Arc` when it is expensive to clone
+///
+/// [`State`]: crate::extract::State
+pub struct AddState<R: Router, T> {
+    inner: R,
+    state: T,
+}
+
+impl<R: Router, T: Clone + Send + Sync + 'static> AddState<R, T> {
+    pub fn new(inner: R, state: T) -> Self {
+        Self { inner, state }
+    }
+
+    pub fn state(&self) -> &T {
+        &self.state
+    }
+}
+
+impl<R: Router, T: Clone + Send + Sync + 'static> Router for AddState<R, T> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let mut request = request.clone();
+        request.extensions.insert(self.state.clone());
+        self.inner.route(&request).await
+    }
+}
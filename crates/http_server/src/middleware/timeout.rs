@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use crate::{
+    Router, RouterError,
+    clock::{self, SharedClock, TokioClock},
+    http::{
+        request::Request,
+        response::{Response, StatusCode},
+    },
+};
+
+/// Bounds how long the inner router has to produce a response, including any time spent
+/// reading the request body, which is separate from the read timeouts of the connection
+/// Wrap the whole router for a global limit and single routes for per-route limits, the
+/// shortest enclosing timeout applies
+/// A router which times out is dropped, so it must not leave shared state half updated
+pub struct Timeout<R: Router> {
+    inner: R,
+    timeout: Duration,
+    status: StatusCode,
+    clock: SharedClock,
+}
+
+impl<R: Router> Timeout<R> {
+    pub fn new(inner: R, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            clock: TokioClock::shared(),
+        }
+    }
+
+    /// The status of timed out requests, 503 Service Unavailable by default
+    /// 504 Gateway Timeout suits routes which are waiting on an upstream server
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl<R: Router> Router for Timeout<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        match clock::timeout(&*self.clock, self.timeout, self.inner.route(request)).await {
+            Ok(res) => res,
+            Err(_) if self.status == RouterError::Timeout.status_code() => {
+                Err(RouterError::Timeout)
+            }
+            Err(_) => Err(RouterError::Custom(self.status, "timed out".to_owned())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        http::{method::Method, response::ResponseBuilder},
+    };
+
+    /// Responds after the number of seconds in the target
+    struct Slow;
+
+    impl Router for Slow {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let secs = std::str::from_utf8(&request.target[1..]).unwrap();
+            tokio::time::sleep(Duration::from_secs(secs.parse().unwrap())).await;
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK).build())
+        }
+    }
+
+    fn request(target: &'static [u8]) -> Request {
+        Request::new(Method::GET, Bytes::from_static(target))
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        let clock = Arc::new(MockClock::new());
+        let route = Timeout::new(Slow, Duration::from_secs(5))
+            .with_status(StatusCode::GATEWAY_TIMEOUT)
+            .with_clock(clock.clone());
+        let global = Timeout::new(route, Duration::from_secs(10)).with_clock(clock.clone());
+
+        let req = request(b"/0");
+        assert_eq!(global.route(&req).await.unwrap().status, StatusCode::OK);
+
+        let req = request(b"/60");
+        let (res, ()) = tokio::join!(global.route(&req), async {
+            clock.advance(Duration::from_secs(5))
+        });
+        assert_eq!(res.unwrap_err().status_code(), StatusCode::GATEWAY_TIMEOUT);
+
+        let global = Timeout::new(Slow, Duration::from_secs(10)).with_clock(clock.clone());
+        let (res, ()) = tokio::join!(global.route(&req), async {
+            clock.advance(Duration::from_secs(10))
+        });
+        assert!(matches!(res, Err(RouterError::Timeout)));
+    }
+}
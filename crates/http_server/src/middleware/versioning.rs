@@ -0,0 +1,223 @@
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    http::{header::HeaderName, request::Request, response::Response},
+};
+
+/// The API version a request was dispatched to by [`Versioned`], inserted into the request
+/// extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiVersion(pub Bytes);
+
+/// Dispatches requests to a router per API version
+/// The version is taken from a path prefix, such as `/v2/users`, which is stripped before the
+/// request is routed, and otherwise from a request header, `Accept-Version` by default
+/// Responses to versions chosen by the header name it in Vary, so caches keep them apart
+pub struct Versioned<R: Router> {
+    versions: Vec<(Bytes, R)>,
+    header: Option<Bytes>,
+    path_prefix: bool,
+    default: Option<Bytes>,
+}
+
+impl<R: Router> Default for Versioned<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: Router> Versioned<R> {
+    pub fn new() -> Self {
+        Self {
+            versions: Vec::new(),
+            header: Some(Bytes::from_static(b"Accept-Version")),
+            path_prefix: true,
+            default: None,
+        }
+    }
+
+    /// Routes requests for `version` to `router`, versions are compared exactly
+    pub fn version(mut self, version: impl Into<Bytes>, router: R) -> Self {
+        self.versions.push((version.into(), router));
+        self
+    }
+
+    /// The header the version is read from, or `None` to ignore headers
+    pub fn with_header(mut self, header: Option<Bytes>) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Whether the first path segment may name the version
+    pub fn with_path_prefix(mut self, enabled: bool) -> Self {
+        self.path_prefix = enabled;
+        self
+    }
+
+    /// The version used for requests which do not name one, without a default they are
+    /// answered with 404
+    pub fn with_default(mut self, version: impl Into<Bytes>) -> Self {
+        self.default = Some(version.into());
+        self
+    }
+
+    fn find(&self, version: &[u8]) -> Option<(&Bytes, &R)> {
+        self.versions
+            .iter()
+            .find(|(name, _)| name == version)
+            .map(|(name, router)| (name, router))
+    }
+
+    /// The version named by the first path segment, and the target with that segment removed
+    fn path_version(&self, target: &Bytes) -> Option<(&Bytes, &R, Bytes)> {
+        let rest = target.strip_prefix(b"/")?;
+        let end = rest
+            .iter()
+            .position(|b| matches!(b, b'/' | b'?'))
+            .unwrap_or(rest.len());
+        let (name, router) = self.find(&rest[..end])?;
+        let stripped = match rest.get(end) {
+            Some(b'/') => target.slice(end + 1..),
+            Some(_) => [&b"/"[..], &rest[end..]].concat().into(),
+            None => Bytes::from_static(b"/"),
+        };
+        Some((name, router, stripped))
+    }
+
+    fn header_version<'a>(&self, request: &'a Request, header: &[u8]) -> Option<&'a Bytes> {
+        // Custom field names keep the case they were received with
+        request
+            .headers
+            .iter()
+            .find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(header))
+            .and_then(|(_, value)| value.iter().next())
+    }
+}
+
+impl<R: Router> Router for Versioned<R> {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        if self.path_prefix
+            && let Some((name, router, target)) = self.path_version(&request.target)
+        {
+            let mut request = request.clone();
+            request.target = target;
+            request.extensions.insert(ApiVersion(name.clone()));
+            return router.route(&request).await;
+        }
+
+        let requested = self
+            .header
+            .as_ref()
+            .and_then(|header| self.header_version(request, header));
+        let (name, router) = match requested {
+            Some(version) => self
+                .find(version.trim_ascii())
+                .ok_or_else(|| RouterError::BadRequest("unsupported API version".into()))?,
+            None => self
+                .default
+                .as_ref()
+                .and_then(|default| self.find(default))
+                .ok_or(RouterError::NotFound)?,
+        };
+        let mut request = request.clone();
+        request.extensions.insert(ApiVersion(name.clone()));
+        let mut res = router.route(&request).await?;
+        // SPEC: RFC 9110 - 12.5.5. Vary
+        if let Some(name) = self
+            .header
+            .as_ref()
+            .and_then(|header| HeaderName::try_from(header).ok())
+        {
+            res.headers.add_vary(&name);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        request::RequestBuilder,
+        response::{ResponseBuilder, StatusCode},
+    };
+
+    struct Echo(&'static str);
+
+    impl Router for Echo {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let version = request.extensions.get::<ApiVersion>().unwrap();
+            assert_eq!(version.0, self.0);
+            let mut res = ResponseBuilder::from_req(request, StatusCode::OK).build();
+            res.headers
+                .entry(HeaderName::try_from(&Bytes::from_static(b"X-Target")).unwrap())
+                .push(request.target.clone());
+            Ok(res)
+        }
+    }
+
+    fn request(target: &'static [u8], version: Option<&'static [u8]>) -> Request {
+        let mut builder = RequestBuilder::get(Bytes::from_static(target));
+        if let Some(version) = version {
+            builder = builder.add_header(
+                &Bytes::from_static(b"accept-version"),
+                Bytes::from_static(version),
+            );
+        }
+        builder.build()
+    }
+
+    fn header(res: &Response, name: &'static [u8]) -> Option<Bytes> {
+        res.headers
+            .iter()
+            .find(|(key, _)| key.as_bytes() == name)
+            .map(|(_, value)| value.collect())
+    }
+
+    fn versioned() -> Versioned<Echo> {
+        Versioned::new()
+            .version("v1", Echo("v1"))
+            .version("v2", Echo("v2"))
+    }
+
+    #[tokio::test]
+    async fn path_prefix() {
+        let versioned = versioned();
+        for (target, stripped) in [
+            (&b"/v2/users?page=2"[..], &b"/users?page=2"[..]),
+            (b"/v2", b"/"),
+            (b"/v2?page=2", b"/?page=2"),
+        ] {
+            let mut req = request(b"/", None);
+            req.target = Bytes::from_static(target);
+            let res = versioned.route(&req).await.unwrap();
+            assert_eq!(header(&res, b"X-Target").unwrap(), stripped);
+            assert!(header(&res, b"Vary").is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn header_version() {
+        let versioned = versioned();
+        let res = versioned
+            .route(&request(b"/users", Some(b"v1")))
+            .await
+            .unwrap();
+        assert_eq!(header(&res, b"X-Target").unwrap(), "/users");
+        assert_eq!(header(&res, b"Vary").unwrap(), "Accept-Version");
+
+        assert!(matches!(
+            versioned.route(&request(b"/users", Some(b"v9"))).await,
+            Err(RouterError::BadRequest(_))
+        ));
+        assert!(matches!(
+            versioned.route(&request(b"/users", None)).await,
+            Err(RouterError::NotFound)
+        ));
+
+        let versioned = versioned.with_default("v2");
+        let res = versioned.route(&request(b"/users", None)).await.unwrap();
+        assert_eq!(header(&res, b"Vary").unwrap(), "Accept-Version");
+    }
+}
@@ -0,0 +1,96 @@
+//! Answering `OPTIONS *`, which asks about the server rather than one of its resources
+//!
+//! A request with the asterisk-form target is never routed, since routers expect a path. The
+//! [`ServerOptions`] of the server responds to it instead, and a `*` target with any other
+//! method is rejected with 400 Bad Request
+
+use std::{fmt, sync::Arc};
+
+use bytes::Bytes;
+
+use crate::http::{
+    header::Allow,
+    method::Method,
+    request::Request,
+    response::{Response, ResponseBuilder, StatusCode},
+};
+
+/// Responds to `OPTIONS *`
+/// SPEC: RFC 9110 - 9.3.7. OPTIONS
+pub trait ServerOptions: Send + Sync + 'static {
+    fn respond(&self, request: &Request) -> Response;
+}
+
+impl<F> ServerOptions for F
+where
+    F: Fn(&Request) -> Response + Send + Sync + 'static,
+{
+    fn respond(&self, request: &Request) -> Response {
+        self(request)
+    }
+}
+
+/// Responds with the methods the server allows in the Allow field, and no content
+#[derive(Debug, Clone)]
+pub struct AllowedMethods(pub Vec<Method>);
+
+impl Default for AllowedMethods {
+    fn default() -> Self {
+        Self(vec![
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::PATCH,
+            Method::OPTIONS,
+        ])
+    }
+}
+
+impl AllowedMethods {
+    /// SPEC: RFC 9110 - 10.2.1. Allow
+    /// ABNF: Allow = #method
+    pub(crate) fn allow(&self) -> Vec<Bytes> {
+        self.0
+            .iter()
+            .map(|method| Bytes::from(method.to_string()))
+            .collect()
+    }
+}
+
+impl ServerOptions for AllowedMethods {
+    fn respond(&self, request: &Request) -> Response {
+        ResponseBuilder::from_req(request, StatusCode::NO_CONTENT)
+            .set_header::<Allow>(self.allow())
+            .build()
+    }
+}
+
+/// A [`ServerOptions`] shared between the connections of a server
+#[derive(Clone)]
+pub struct SharedServerOptions(Arc<dyn ServerOptions>);
+
+impl SharedServerOptions {
+    pub fn new(options: impl ServerOptions) -> Self {
+        Self(Arc::new(options))
+    }
+}
+
+impl Default for SharedServerOptions {
+    fn default() -> Self {
+        Self::new(AllowedMethods::default())
+    }
+}
+
+impl fmt::Debug for SharedServerOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedServerOptions")
+    }
+}
+
+impl ServerOptions for SharedServerOptions {
+    fn respond(&self, request: &Request) -> Response {
+        self.0.respond(request)
+    }
+}
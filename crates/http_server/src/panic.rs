@@ -0,0 +1,108 @@
+//! Catching panics in routers, so they become 500 responses instead of silently ending the
+//! connection task
+
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    cell::RefCell,
+    panic::{AssertUnwindSafe, catch_unwind},
+    pin::Pin,
+    sync::Once,
+    task::{Context, Poll},
+};
+
+thread_local! {
+    /// The backtrace of the last panic on this thread, as it is no longer available once the
+    /// panic has been caught
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+/// A panic caught while polling a future
+#[derive(Debug)]
+pub(crate) struct Panic {
+    pub message: String,
+    /// Only captured if enabled with `RUST_BACKTRACE`
+    pub backtrace: Option<Backtrace>,
+}
+
+impl Panic {
+    fn new(payload: Box<dyn Any + Send>) -> Self {
+        let message = match payload.downcast::<String>() {
+            Ok(message) => *message,
+            Err(payload) => match payload.downcast::<&'static str>() {
+                Ok(message) => message.to_string(),
+                Err(_) => "Box<dyn Any>".to_string(),
+            },
+        };
+        Self {
+            message,
+            backtrace: BACKTRACE
+                .with_borrow_mut(Option::take)
+                .filter(|backtrace| backtrace.status() == BacktraceStatus::Captured),
+        }
+    }
+}
+
+/// Records the backtrace of every panic, the previous hook still runs
+fn install_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            BACKTRACE.set(Some(Backtrace::capture()));
+            previous(info);
+        }));
+    });
+}
+
+/// Calls `f`, catching any panic
+pub(crate) fn catch<T>(f: impl FnOnce() -> T) -> Result<T, Panic> {
+    install_hook();
+    catch_unwind(AssertUnwindSafe(f)).map_err(Panic::new)
+}
+
+/// Resolves to the output of the future, or the panic if polling it panicked
+/// The future must not be polled again after it panics
+pub(crate) struct CatchUnwind<F> {
+    inner: F,
+}
+
+impl<F: Future + Unpin> CatchUnwind<F> {
+    pub fn new(inner: F) -> Self {
+        install_hook();
+        Self { inner }
+    }
+}
+
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, Panic>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match catch_unwind(AssertUnwindSafe(|| Pin::new(&mut self.inner).poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(Panic::new(payload))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn catch_panic() {
+        let res = CatchUnwind::new(Box::pin(async { 1 })).await;
+        assert_eq!(res.unwrap(), 1);
+
+        let res = CatchUnwind::new(Box::pin(async {
+            tokio::task::yield_now().await;
+            panic!("handler {}", "failed")
+        }))
+        .await;
+        assert_eq!(res.unwrap_err().message, "handler failed");
+
+        let res = catch(|| -> () { panic!("static") });
+        assert_eq!(res.unwrap_err().message, "static");
+    }
+}
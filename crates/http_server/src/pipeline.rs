@@ -0,0 +1,91 @@
+//! Routing pipelined requests at once while answering them in order
+
+use std::{
+    future::poll_fn,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+enum Slot<F: Future> {
+    Running(Pin<Box<F>>),
+    Done(F::Output),
+    Taken,
+}
+
+/// Futures which run concurrently while they are waited on, whose outputs are taken in order
+pub(crate) struct Ordered<F: Future> {
+    slots: Vec<Slot<F>>,
+}
+
+impl<F: Future> Ordered<F> {
+    pub(crate) fn new(futures: impl IntoIterator<Item = F>) -> Self {
+        Self {
+            slots: futures
+                .into_iter()
+                .map(|future| Slot::Running(Box::pin(future)))
+                .collect(),
+        }
+    }
+
+    /// Runs every future until the one at `index` completes, and returns its output
+    ///
+    /// # Panics
+    /// If the output at `index` was already taken
+    pub(crate) async fn take(&mut self, index: usize) -> F::Output {
+        poll_fn(|cx| self.poll_take(cx, index)).await
+    }
+
+    fn poll_take(&mut self, cx: &mut Context<'_>, index: usize) -> Poll<F::Output> {
+        for slot in &mut self.slots {
+            if let Slot::Running(future) = slot
+                && let Poll::Ready(output) = future.as_mut().poll(cx)
+            {
+                *slot = Slot::Done(output);
+            }
+        }
+        match std::mem::replace(&mut self.slots[index], Slot::Taken) {
+            Slot::Done(output) => Poll::Ready(output),
+            Slot::Taken => panic!("output {index} was already taken"),
+            running => {
+                self.slots[index] = running;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn concurrent_in_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let first = {
+            let order = order.clone();
+            async move {
+                // Only completes once the second future has run
+                rx.await.unwrap();
+                order.lock().unwrap().push(1);
+                1
+            }
+        };
+        let second = {
+            let order = order.clone();
+            async move {
+                order.lock().unwrap().push(2);
+                tx.send(()).unwrap();
+                2
+            }
+        };
+        let futures: Vec<Pin<Box<dyn Future<Output = i32> + Send>>> =
+            vec![Box::pin(first), Box::pin(second)];
+        let mut ordered = Ordered::new(futures);
+        assert_eq!(ordered.take(0).await, 1);
+        assert_eq!(ordered.take(1).await, 2);
+        assert_eq!(*order.lock().unwrap(), [2, 1]);
+    }
+}
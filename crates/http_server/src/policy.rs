@@ -0,0 +1,165 @@
+//! Which clients may connect, decided when a connection is accepted
+//!
+//! The [`ConnectionPolicy`] of a server is checked against the peer address before anything is
+//! read from the connection, so denied clients and clients over their connection limit cost
+//! nothing but the accept. Addresses named by a PROXY header or forwarding fields are not known
+//! yet, so behind a proxy the policy applies to the proxy
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
+
+use crate::proxy::IpNet;
+
+/// The clients a server accepts connections from, every client by default
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionPolicy {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    max_per_ip: Option<NonZeroUsize>,
+}
+
+/// Why a connection was not accepted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The address is denied, or not allowed
+    Denied,
+    /// The address already has as many connections as allowed
+    TooManyConnections,
+}
+
+impl ConnectionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accepts clients in the allowed networks, once any network is allowed
+    pub fn with_allow(mut self, net: IpNet) -> Self {
+        self.allow.push(net);
+        self
+    }
+
+    /// Rejects clients in the network, even if they are also allowed
+    pub fn with_deny(mut self, net: IpNet) -> Self {
+        self.deny.push(net);
+        self
+    }
+
+    /// Limits how many connections one client address can have open at once
+    pub fn with_max_connections_per_ip(mut self, max: NonZeroUsize) -> Self {
+        self.max_per_ip = Some(max);
+        self
+    }
+
+    /// Whether the lists let the address connect, ignoring the connection limit
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(addr))
+            && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(addr)))
+    }
+}
+
+/// The open connections of every client address, shared by the connections of a server
+#[derive(Debug, Default)]
+pub(crate) struct ConnectionCounts(Arc<Mutex<HashMap<IpAddr, usize>>>);
+
+/// Counts a connection of a client until it is dropped
+#[derive(Debug)]
+pub(crate) struct IpPermit {
+    addr: IpAddr,
+    counts: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl ConnectionCounts {
+    /// Checks a new connection from `addr` against the policy, counting it if it is accepted
+    /// Unix domain socket peers have no address and are always accepted
+    pub(crate) fn admit(
+        &self,
+        policy: &ConnectionPolicy,
+        addr: Option<IpAddr>,
+    ) -> Result<Option<IpPermit>, Rejection> {
+        let Some(addr) = addr else {
+            return Ok(None);
+        };
+        if !policy.is_allowed(addr) {
+            return Err(Rejection::Denied);
+        }
+        let Some(max) = policy.max_per_ip else {
+            return Ok(None);
+        };
+        // IPv4 clients of dual stack sockets have mapped addresses
+        let addr = addr.to_canonical();
+        let mut counts = self.0.lock().unwrap();
+        let count = counts.entry(addr).or_default();
+        if *count >= max.get() {
+            return Err(Rejection::TooManyConnections);
+        }
+        *count += 1;
+        Ok(Some(IpPermit {
+            addr,
+            counts: self.0.clone(),
+        }))
+    }
+}
+
+impl Drop for IpPermit {
+    fn drop(&mut self) {
+        let mut counts = self.counts.lock().unwrap();
+        if let Some(count) = counts.get_mut(&self.addr) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.addr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(addr: &str) -> Option<IpAddr> {
+        Some(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn allow_and_deny() {
+        let policy = ConnectionPolicy::new()
+            .with_allow("10.0.0.0/8".parse().unwrap())
+            .with_deny("10.1.0.0/16".parse().unwrap());
+        assert!(policy.is_allowed("10.2.3.4".parse().unwrap()));
+        assert!(policy.is_allowed("::ffff:10.2.3.4".parse().unwrap()));
+        assert!(!policy.is_allowed("10.1.3.4".parse().unwrap()));
+        assert!(!policy.is_allowed("192.0.2.1".parse().unwrap()));
+        assert!(ConnectionPolicy::new().is_allowed("192.0.2.1".parse().unwrap()));
+
+        let counts = ConnectionCounts::default();
+        assert_eq!(
+            counts.admit(&policy, ip("10.1.3.4")).unwrap_err(),
+            Rejection::Denied
+        );
+        assert!(counts.admit(&policy, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn connections_per_ip() {
+        let policy =
+            ConnectionPolicy::new().with_max_connections_per_ip(NonZeroUsize::new(2).unwrap());
+        let counts = ConnectionCounts::default();
+        let first = counts.admit(&policy, ip("192.0.2.1")).unwrap();
+        let second = counts.admit(&policy, ip("::ffff:192.0.2.1")).unwrap();
+        assert_eq!(
+            counts.admit(&policy, ip("192.0.2.1")).unwrap_err(),
+            Rejection::TooManyConnections
+        );
+        // Other clients have their own limit
+        assert!(counts.admit(&policy, ip("192.0.2.2")).is_ok());
+
+        drop(first);
+        assert!(counts.admit(&policy, ip("192.0.2.1")).unwrap().is_some());
+        drop(second);
+        assert!(counts.0.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+//! Resolving the client of requests which were sent through reverse proxies and load
+//! balancers
+
+mod protocol;
+
+use std::{fmt, net::IpAddr, str::FromStr};
+
+use bytes::Bytes;
+
+use crate::http::{
+    header::{
+        Forwarded, ForwardedElement, ForwardedNode, HeaderField, HeaderMap, XForwardedFor,
+        XForwardedHost, XForwardedProto,
+    },
+    request::Scheme,
+};
+
+pub(crate) use protocol::{Prefixed, read_header};
+pub use protocol::{ProxyHeader, ProxyProtocol, ProxyProtocolError};
+
+/// A range of IP addresses, such as `10.0.0.0/8` or `fd00::/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid IP network")]
+pub struct InvalidIpNet;
+
+impl IpNet {
+    /// # Panics
+    /// If the prefix is longer than the address
+    pub fn new(addr: IpAddr, prefix: u8) -> Self {
+        assert!(prefix <= max_prefix(addr), "prefix /{prefix} is too long");
+        Self { addr, prefix }
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        // IPv4 clients of dual stack sockets have mapped addresses
+        match (self.addr, addr.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn max_prefix(addr: IpAddr) -> u8 {
+    match addr {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    }
+}
+
+/// Parses `addr/prefix`, or an address on its own, which is a network of just that address
+impl FromStr for IpNet {
+    type Err = InvalidIpNet;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| InvalidIpNet)?;
+        let prefix = match prefix {
+            Some(prefix) if prefix.bytes().all(|b| b.is_ascii_digit()) => {
+                prefix.parse().map_err(|_| InvalidIpNet)?
+            }
+            Some(_) => return Err(InvalidIpNet),
+            None => max_prefix(addr),
+        };
+        if prefix > max_prefix(addr) {
+            return Err(InvalidIpNet);
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// The client of a request sent through trusted proxies, inserted into the request extensions
+/// by the server
+/// See [`crate::http::request::Request::client_addr`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForwardedClient {
+    pub addr: IpAddr,
+    /// The scheme the client used, if the proxies sent it
+    pub scheme: Option<Scheme>,
+    /// The Host field the client sent, if the proxies sent it
+    pub host: Option<Bytes>,
+}
+
+/// The proxies whose Forwarded, or X-Forwarded-For, X-Forwarded-Proto and X-Forwarded-Host
+/// fields are believed
+/// The fields are read from the right, skipping the addresses of trusted proxies, so a client
+/// can't claim to be someone else by sending the fields itself
+/// Forwarded is used when present, and a malformed Forwarded field is not believed at all
+/// SPEC: RFC 7239 - 8.1. Header Validity and Integrity
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    pub fn new(nets: impl IntoIterator<Item = IpNet>) -> Self {
+        Self {
+            nets: nets.into_iter().collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        self.nets.iter().any(|net| net.contains(addr))
+    }
+
+    /// The client of a request received from `peer`, if `peer` is a trusted proxy which named
+    /// the client
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> Option<ForwardedClient> {
+        if !self.is_trusted(peer) {
+            return None;
+        }
+        if headers.contains(&Forwarded::NAME) {
+            let elements = headers.get_header::<Forwarded>().ok()??;
+            return self.resolve_forwarded(&elements);
+        }
+        let nodes = headers.get_header::<XForwardedFor>().ok()??;
+        let nodes: Vec<_> = nodes.iter().map(ForwardedNode::ip).collect();
+        let addr = nodes[self.rightmost_untrusted(&nodes)?]?;
+        // Each proxy sets these fields, rather than appending to them, so the last value is the
+        // one set by the proxy closest to the server
+        let last = |values: Option<Vec<Bytes>>| values?.pop();
+        let proto = last(headers.get_header::<XForwardedProto>().ok().flatten());
+        Some(ForwardedClient {
+            addr,
+            scheme: proto.and_then(|proto| scheme(&proto)),
+            host: last(headers.get_header::<XForwardedHost>().ok().flatten()),
+        })
+    }
+
+    fn resolve_forwarded(&self, elements: &[ForwardedElement]) -> Option<ForwardedClient> {
+        let nodes: Vec<_> = elements
+            .iter()
+            .map(|element| element.r#for.as_ref().and_then(ForwardedNode::ip))
+            .collect();
+        let client = self.rightmost_untrusted(&nodes)?;
+        // The element naming the client was added by the proxy the client connected to
+        let element = &elements[client];
+        Some(ForwardedClient {
+            addr: nodes[client]?,
+            scheme: element.proto.as_deref().and_then(scheme),
+            host: element.host.clone(),
+        })
+    }
+
+    /// The index of the address the chain of proxies was entered from, the rightmost address
+    /// which is not a trusted proxy, or the leftmost address when every proxy is trusted
+    /// Nodes which are not addresses end the chain, since what is left of them can't be
+    /// checked
+    fn rightmost_untrusted(&self, nodes: &[Option<IpAddr>]) -> Option<usize> {
+        let mut client = None;
+        for (i, node) in nodes.iter().enumerate().rev() {
+            let Some(addr) = node else { break };
+            client = Some(i);
+            if !self.is_trusted(*addr) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+fn scheme(proto: &[u8]) -> Option<Scheme> {
+    match proto {
+        _ if proto.eq_ignore_ascii_case(b"http") => Some(Scheme::Http),
+        _ if proto.eq_ignore_ascii_case(b"https") => Some(Scheme::Https),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::header::{Builtin, HeaderName};
+
+    fn headers(fields: &[(Builtin, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in fields {
+            headers
+                .entry(HeaderName::builtin(*name))
+                .push(Bytes::from_static(value.as_bytes()));
+        }
+        headers
+    }
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn ip_networks() {
+        let net: IpNet = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains(ip("10.1.255.3")));
+        assert!(net.contains(ip("::ffff:10.1.0.1")));
+        assert!(!net.contains(ip("10.2.0.1")));
+        assert!(!net.contains(ip("::1")));
+        assert_eq!(net.to_string(), "10.1.0.0/16");
+
+        let net: IpNet = "fd00::/8".parse().unwrap();
+        assert!(net.contains(ip("fd12::1")));
+        assert!(!net.contains(ip("fe80::1")));
+        assert!(
+            "0.0.0.0/0"
+                .parse::<IpNet>()
+                .unwrap()
+                .contains(ip("1.2.3.4"))
+        );
+        assert!("::1".parse::<IpNet>().unwrap().contains(ip("::1")));
+        for invalid in [
+            "10.0.0.0/33",
+            "10.0.0.0/",
+            "10.0.0/8",
+            "::/+8",
+            "fd00::/129",
+        ] {
+            assert_eq!(invalid.parse::<IpNet>(), Err(InvalidIpNet), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn resolve_clients() {
+        let proxies = TrustedProxies::new(["10.0.0.0/8".parse().unwrap()]);
+        let peer = ip("10.0.0.2");
+
+        // Untrusted peers are the client, whatever they send
+        let spoofed = headers(&[(Builtin::XForwardedFor, "1.1.1.1")]);
+        assert_eq!(proxies.resolve(ip("203.0.113.9"), &spoofed), None);
+
+        // A client can prepend addresses, but not skip past the proxies
+        let fields = headers(&[
+            (Builtin::XForwardedFor, "1.1.1.1, 203.0.113.9, 10.0.0.7"),
+            (Builtin::XForwardedProto, "https"),
+            (Builtin::XForwardedHost, "example.com"),
+        ]);
+        assert_eq!(
+            proxies.resolve(peer, &fields),
+            Some(ForwardedClient {
+                addr: ip("203.0.113.9"),
+                scheme: Some(Scheme::Https),
+                host: Some(Bytes::from_static(b"example.com")),
+            })
+        );
+        let fields = headers(&[(Builtin::XForwardedFor, "10.0.0.9, 10.0.0.7")]);
+        assert_eq!(proxies.resolve(peer, &fields).unwrap().addr, ip("10.0.0.9"));
+        let fields = headers(&[(Builtin::XForwardedFor, "203.0.113.9, unknown, 10.0.0.7")]);
+        assert_eq!(proxies.resolve(peer, &fields).unwrap().addr, ip("10.0.0.7"));
+        assert_eq!(proxies.resolve(peer, &headers(&[])), None);
+
+        // Forwarded is preferred, and the client's element gives the scheme and host
+        let fields = headers(&[
+            (
+                Builtin::Forwarded,
+                "for=1.1.1.1, for=\"[2001:db8::1]:4711\";proto=https;host=example.com, \
+                 for=10.0.0.7;proto=http",
+            ),
+            (Builtin::XForwardedFor, "198.51.100.1"),
+        ]);
+        assert_eq!(
+            proxies.resolve(peer, &fields),
+            Some(ForwardedClient {
+                addr: ip("2001:db8::1"),
+                scheme: Some(Scheme::Https),
+                host: Some(Bytes::from_static(b"example.com")),
+            })
+        );
+        let malformed = headers(&[
+            (Builtin::Forwarded, "for=1.1.1.1;for=2.2.2.2"),
+            (Builtin::XForwardedFor, "198.51.100.1"),
+        ]);
+        assert_eq!(proxies.resolve(peer, &malformed), None);
+    }
+}
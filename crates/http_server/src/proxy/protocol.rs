@@ -0,0 +1,400 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+/// Whether accepted connections start with a PROXY protocol header, as sent by load balancers
+/// which forward TCP connections, such as HAProxy or AWS NLB
+/// SPEC: The PROXY protocol Versions 1 & 2
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProxyProtocol {
+    /// Connections are HTTP from the first byte
+    #[default]
+    Disabled,
+    /// Connections may start with a header, which is only safe when every peer which can reach
+    /// the listener is a load balancer, since any client can send a header
+    Optional,
+    /// Connections without a header are closed
+    Required,
+}
+
+/// The addresses of the connection the load balancer received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// The client, or `None` for connections the load balancer made itself, such as health
+    /// checks, and for address families other than TCP over IPv4 and IPv6
+    pub source: Option<SocketAddr>,
+    /// The address the client connected to
+    pub destination: Option<SocketAddr>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProxyProtocolError {
+    #[error("missing PROXY protocol header")]
+    Missing,
+    #[error("malformed PROXY protocol header")]
+    Malformed,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// SPEC: The PROXY protocol - 2.2. Binary header format (version 2)
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+const V1_PREFIX: &[u8; 6] = b"PROXY ";
+/// SPEC: The PROXY protocol - 2.1. Human-readable header format (version 1)
+/// A header is at most 107 bytes, including the CRLF
+const V1_MAX: usize = 107;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Parsed {
+    /// The header may continue past the buffered bytes
+    Incomplete,
+    /// The connection does not start with a header
+    Absent,
+    /// A header of the given length
+    Header(usize, ProxyHeader),
+}
+
+fn parse(buf: &[u8]) -> Result<Parsed, ProxyProtocolError> {
+    let starts = |prefix: &[u8]| {
+        let len = buf.len().min(prefix.len());
+        buf[..len] == prefix[..len]
+    };
+    if starts(V1_PREFIX) {
+        return parse_v1(buf);
+    }
+    if starts(V2_SIGNATURE) {
+        return parse_v2(buf);
+    }
+    Ok(Parsed::Absent)
+}
+
+/// ABNF:
+///     header = "PROXY" SP ( tcp / "UNKNOWN" *VCHAR ) CRLF
+///     tcp    = ( "TCP4" / "TCP6" ) SP src SP dst SP sport SP dport
+fn parse_v1(buf: &[u8]) -> Result<Parsed, ProxyProtocolError> {
+    let Some(end) = buf.windows(2).position(|w| w == b"\r\n") else {
+        return match buf.len() < V1_MAX {
+            true => Ok(Parsed::Incomplete),
+            false => Err(ProxyProtocolError::Malformed),
+        };
+    };
+    if end + 2 > V1_MAX {
+        return Err(ProxyProtocolError::Malformed);
+    }
+    let line = std::str::from_utf8(&buf[V1_PREFIX.len()..end])
+        .map_err(|_| ProxyProtocolError::Malformed)?;
+    let mut fields = line.split(' ');
+    let v6 = match fields.next() {
+        Some("TCP4") => false,
+        Some("TCP6") => true,
+        // The rest of the line is ignored, and the connection is used as it is
+        Some("UNKNOWN") => {
+            let header = ProxyHeader {
+                source: None,
+                destination: None,
+            };
+            return Ok(Parsed::Header(end + 2, header));
+        }
+        _ => return Err(ProxyProtocolError::Malformed),
+    };
+    let mut next = || fields.next().ok_or(ProxyProtocolError::Malformed);
+    let addr = |field: &str| -> Result<IpAddr, ProxyProtocolError> {
+        let addr = match v6 {
+            false => field.parse::<Ipv4Addr>().map(IpAddr::V4),
+            true => field.parse::<Ipv6Addr>().map(IpAddr::V6),
+        };
+        addr.map_err(|_| ProxyProtocolError::Malformed)
+    };
+    // Ports are decimal without leading zeroes
+    let port = |field: &str| match field.starts_with('0') && field != "0" {
+        true => Err(ProxyProtocolError::Malformed),
+        false => field
+            .parse::<u16>()
+            .map_err(|_| ProxyProtocolError::Malformed),
+    };
+    let (src, dst) = (addr(next()?)?, addr(next()?)?);
+    let (sport, dport) = (port(next()?)?, port(next()?)?);
+    if fields.next().is_some() {
+        return Err(ProxyProtocolError::Malformed);
+    }
+    let header = ProxyHeader {
+        source: Some(SocketAddr::new(src, sport)),
+        destination: Some(SocketAddr::new(dst, dport)),
+    };
+    Ok(Parsed::Header(end + 2, header))
+}
+
+/// The 12 byte signature, the version and command, the address family and protocol, and the
+/// length of the addresses and any TLVs, which are ignored
+fn parse_v2(buf: &[u8]) -> Result<Parsed, ProxyProtocolError> {
+    if buf.len() < 16 {
+        return Ok(Parsed::Incomplete);
+    }
+    let len = 16 + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if buf.len() < len {
+        return Ok(Parsed::Incomplete);
+    }
+    let body = &buf[16..len];
+    let (version, command) = (buf[12] >> 4, buf[12] & 0xf);
+    if version != 2 {
+        return Err(ProxyProtocolError::Malformed);
+    }
+    let unknown = ProxyHeader {
+        source: None,
+        destination: None,
+    };
+    match command {
+        // LOCAL, the connection was made by the load balancer itself
+        0x0 => return Ok(Parsed::Header(len, unknown)),
+        // PROXY
+        0x1 => {}
+        _ => return Err(ProxyProtocolError::Malformed),
+    }
+    let header = match buf[13] {
+        // TCP over IPv4
+        0x11 if body.len() >= 12 => {
+            let ip = |at: usize| IpAddr::from(<[u8; 4]>::try_from(&body[at..at + 4]).unwrap());
+            let port = |at: usize| u16::from_be_bytes([body[at], body[at + 1]]);
+            ProxyHeader {
+                source: Some(SocketAddr::new(ip(0), port(8))),
+                destination: Some(SocketAddr::new(ip(4), port(10))),
+            }
+        }
+        // TCP over IPv6
+        0x21 if body.len() >= 36 => {
+            let ip = |at: usize| IpAddr::from(<[u8; 16]>::try_from(&body[at..at + 16]).unwrap());
+            let port = |at: usize| u16::from_be_bytes([body[at], body[at + 1]]);
+            ProxyHeader {
+                source: Some(SocketAddr::new(ip(0), port(32))),
+                destination: Some(SocketAddr::new(ip(16), port(34))),
+            }
+        }
+        0x11 | 0x21 => return Err(ProxyProtocolError::Malformed),
+        // Other families, such as UDP and unix sockets, have no usable address
+        _ => unknown,
+    };
+    Ok(Parsed::Header(len, header))
+}
+
+/// Reads the header a connection starts with, returning it and the bytes read past it
+pub(crate) async fn read_header<S: AsyncRead + Unpin>(
+    stream: &mut S,
+    mode: ProxyProtocol,
+) -> Result<(Option<ProxyHeader>, Bytes), ProxyProtocolError> {
+    let mut buf = BytesMut::with_capacity(V1_MAX);
+    loop {
+        if stream.read_buf(&mut buf).await? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        match parse(&buf)? {
+            Parsed::Incomplete => continue,
+            Parsed::Absent if mode == ProxyProtocol::Required => {
+                return Err(ProxyProtocolError::Missing);
+            }
+            Parsed::Absent => return Ok((None, buf.freeze())),
+            Parsed::Header(len, header) => {
+                buf.advance(len);
+                return Ok((Some(header), buf.freeze()));
+            }
+        }
+    }
+}
+
+/// A stream which reads `prefix` before reading from the stream, so bytes read while looking
+/// for a header are not lost
+pub(crate) struct Prefixed<S> {
+    prefix: Bytes,
+    inner: S,
+}
+
+impl<S> Prefixed<S> {
+    pub(crate) fn new(prefix: Bytes, inner: S) -> Self {
+        Self { prefix, inner }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Prefixed<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.prefix.is_empty() {
+            return Pin::new(&mut self.inner).poll_read(cx, buf);
+        }
+        let len = self.prefix.len().min(buf.remaining());
+        buf.put_slice(&self.prefix.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Prefixed<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    fn addr(addr: &str) -> Option<SocketAddr> {
+        Some(addr.parse().unwrap())
+    }
+
+    #[test]
+    fn parse_v1_headers() {
+        let header = b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET /";
+        assert_eq!(
+            parse(header).unwrap(),
+            Parsed::Header(
+                45,
+                ProxyHeader {
+                    source: addr("192.0.2.1:56324"),
+                    destination: addr("198.51.100.1:443"),
+                }
+            )
+        );
+        let header = b"PROXY TCP6 2001:db8::1 2001:db8::2 1 65535\r\n";
+        let Parsed::Header(_, parsed) = parse(header).unwrap() else {
+            panic!()
+        };
+        assert_eq!(parsed.source, addr("[2001:db8::1]:1"));
+        let Parsed::Header(len, parsed) = parse(b"PROXY UNKNOWN ffff::1 x\r\n").unwrap() else {
+            panic!()
+        };
+        assert_eq!((len, parsed.source), (25, None));
+
+        assert_eq!(parse(b"PRO").unwrap(), Parsed::Incomplete);
+        assert_eq!(parse(b"PROXY TCP4 192.0.2.1").unwrap(), Parsed::Incomplete);
+        assert_eq!(parse(b"PRI * HTTP/2.0").unwrap(), Parsed::Absent);
+        assert_eq!(parse(b"GET / HTTP/1.1").unwrap(), Parsed::Absent);
+        for malformed in [
+            &b"PROXY TCP4 192.0.2.1 198.51.100.1 56324\r\n"[..],
+            b"PROXY TCP4 2001:db8::1 198.51.100.1 1 2\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 01 2\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 1 2 3\r\n",
+            b"PROXY UDP4 192.0.2.1 198.51.100.1 1 2\r\n",
+            &[&b"PROXY U"[..], &[b'x'; 120]].concat(),
+        ] {
+            assert!(parse(malformed).is_err(), "{malformed:?}");
+        }
+    }
+
+    fn v2(command: u8, family: u8, addrs: &[u8]) -> Vec<u8> {
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x20 | command, family]);
+        header.extend((addrs.len() as u16).to_be_bytes());
+        header.extend(addrs);
+        header
+    }
+
+    #[test]
+    fn parse_v2_headers() {
+        // With a TLV after the addresses
+        let mut addrs = vec![192, 0, 2, 1, 198, 51, 100, 1, 0xdc, 0x04, 0x01, 0xbb];
+        addrs.extend([0x04, 0x00, 0x01, 0x00]);
+        let header = v2(1, 0x11, &addrs);
+        assert_eq!(
+            parse(&header).unwrap(),
+            Parsed::Header(
+                32,
+                ProxyHeader {
+                    source: addr("192.0.2.1:56324"),
+                    destination: addr("198.51.100.1:443"),
+                }
+            )
+        );
+        assert_eq!(parse(&header[..20]).unwrap(), Parsed::Incomplete);
+
+        let mut addrs = [0; 36];
+        addrs[15] = 1;
+        addrs[31] = 2;
+        addrs[33] = 80;
+        let Parsed::Header(_, parsed) = parse(&v2(1, 0x21, &addrs)).unwrap() else {
+            panic!()
+        };
+        assert_eq!(parsed.source, addr("[::1]:80"));
+        assert_eq!(parsed.destination, addr("[::2]:0"));
+
+        let Parsed::Header(16, parsed) = parse(&v2(0, 0x00, &[])).unwrap() else {
+            panic!()
+        };
+        assert_eq!(parsed.source, None);
+        // Unix sockets have no address the server can use
+        let Parsed::Header(_, parsed) = parse(&v2(1, 0x31, &[0; 216])).unwrap() else {
+            panic!()
+        };
+        assert_eq!(parsed.source, None);
+
+        assert!(parse(&v2(1, 0x11, &[0; 8])).is_err());
+        assert!(parse(&v2(2, 0x11, &[0; 12])).is_err());
+        let mut version_1 = v2(1, 0x11, &[0; 12]);
+        version_1[12] = 0x11;
+        assert!(parse(&version_1).is_err());
+    }
+
+    #[tokio::test]
+    async fn read_headers() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let read = tokio::spawn(async move {
+            let read = read_header(&mut server, ProxyProtocol::Required).await;
+            (read.unwrap(), server)
+        });
+        // The header may arrive in pieces
+        client.write_all(b"PROXY TCP4 192.0.2.1 ").await.unwrap();
+        tokio::task::yield_now().await;
+        client
+            .write_all(b"198.51.100.1 56324 443\r\nGET / HTTP/1.1\r\n")
+            .await
+            .unwrap();
+        let ((header, rest), server) = read.await.unwrap();
+        assert_eq!(header.unwrap().source, addr("192.0.2.1:56324"));
+        let mut prefixed = Prefixed::new(rest, server);
+        let mut line = [0; 16];
+        prefixed.read_exact(&mut line).await.unwrap();
+        assert_eq!(&line, b"GET / HTTP/1.1\r\n");
+
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let (header, rest) = read_header(&mut server, ProxyProtocol::Optional)
+            .await
+            .unwrap();
+        assert_eq!((header, &rest[..]), (None, &b"GET / HTTP/1.1\r\n"[..]));
+        client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        let err = read_header(&mut server, ProxyProtocol::Required).await;
+        assert!(matches!(err, Err(ProxyProtocolError::Missing)));
+    }
+}
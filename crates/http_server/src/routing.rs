@@ -0,0 +1,782 @@
+use std::{fmt, future::Future, pin::Pin, sync::Arc};
+
+use bytes::Bytes;
+
+use crate::{
+    Router, RouterError,
+    extract::PathParams,
+    http::{
+        header::{Allow, ContentType, MediaRange, MediaType},
+        method::Method,
+        request::{Request, Scheme},
+        response::{Response, ResponseBuilder, StatusCode},
+        uri::url_decode,
+    },
+};
+
+type RouteFuture<'a> = Pin<Box<dyn Future<Output = Result<Response, RouterError>> + Send + 'a>>;
+
+/// The object safe form of [`Router`]
+trait DynRouter: Send + Sync + 'static {
+    fn route_dyn<'a>(&'a self, request: &'a Request) -> RouteFuture<'a>;
+}
+
+impl<R: Router> DynRouter for R {
+    fn route_dyn<'a>(&'a self, request: &'a Request) -> RouteFuture<'a> {
+        Box::pin(self.route(request))
+    }
+}
+
+/// A type erased [`Router`], so routers of different types can be kept together
+pub struct BoxRouter(Box<dyn DynRouter>);
+
+impl BoxRouter {
+    pub fn new(router: impl Router) -> Self {
+        Self(Box::new(router))
+    }
+}
+
+impl Router for BoxRouter {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        self.0.route_dyn(request).await
+    }
+}
+
+/// Routes the requests to one path by their method
+/// Requests with a method without a router get 405 Method Not Allowed, and OPTIONS requests get
+/// the allowed methods unless OPTIONS has a router
+/// HEAD requests are routed to the GET router unless HEAD has a router, the server leaves out
+/// the body
+/// SPEC: RFC 9110 - 15.5.6. 405 Method Not Allowed
+/// ```ignore
+/// let user = MethodRouter::new()
+///     .get(handler(show_user))
+///     .put(handler(update_user))
+///     .delete(handler(delete_user));
+/// ```
+#[derive(Default)]
+pub struct MethodRouter {
+    routes: Vec<(Method, BoxRouter)>,
+}
+
+impl MethodRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Panics
+    /// If the method already has a router
+    pub fn on(mut self, method: Method, router: impl Router) -> Self {
+        assert!(
+            self.routes.iter().all(|(other, _)| *other != method),
+            "{method} is already routed"
+        );
+        self.routes.push((method, BoxRouter::new(router)));
+        self
+    }
+
+    pub fn get(self, router: impl Router) -> Self {
+        self.on(Method::GET, router)
+    }
+
+    pub fn head(self, router: impl Router) -> Self {
+        self.on(Method::HEAD, router)
+    }
+
+    pub fn post(self, router: impl Router) -> Self {
+        self.on(Method::POST, router)
+    }
+
+    pub fn put(self, router: impl Router) -> Self {
+        self.on(Method::PUT, router)
+    }
+
+    pub fn patch(self, router: impl Router) -> Self {
+        self.on(Method::PATCH, router)
+    }
+
+    pub fn delete(self, router: impl Router) -> Self {
+        self.on(Method::DELETE, router)
+    }
+
+    pub fn options(self, router: impl Router) -> Self {
+        self.on(Method::OPTIONS, router)
+    }
+
+    /// The methods with a router in the order they were added, HEAD after GET, and OPTIONS,
+    /// which is always answered
+    pub fn allowed(&self) -> Vec<Method> {
+        let mut methods: Vec<_> = self
+            .routes
+            .iter()
+            .map(|(method, _)| method.clone())
+            .collect();
+        if let Some(get) = methods.iter().position(|method| *method == Method::GET)
+            && !methods.contains(&Method::HEAD)
+        {
+            methods.insert(get + 1, Method::HEAD);
+        }
+        if !methods.contains(&Method::OPTIONS) {
+            methods.push(Method::OPTIONS);
+        }
+        methods
+    }
+
+    /// SPEC: RFC 9110 - 10.2.1. Allow
+    /// ABNF: Allow = #method
+    fn allow(&self) -> Vec<Bytes> {
+        self.allowed()
+            .iter()
+            .map(|method| Bytes::from(method.to_string()))
+            .collect()
+    }
+}
+
+impl MethodRouter {
+    fn find(&self, method: &Method) -> Option<&BoxRouter> {
+        self.routes
+            .iter()
+            .find(|(other, _)| other == method)
+            .map(|(_, router)| router)
+    }
+}
+
+impl Router for MethodRouter {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        // SPEC: RFC 9110 - 9.3.2. HEAD
+        // The response to HEAD is the response to GET, without its content
+        let router = match self.find(&request.method) {
+            None if request.method == Method::HEAD => self.find(&Method::GET),
+            router => router,
+        };
+        if let Some(router) = router {
+            return router.route(request).await;
+        }
+        // SPEC: RFC 9110 - 9.3.7. OPTIONS
+        // A server generating a successful response to OPTIONS should send any header that
+        // might indicate optional features, such as Allow
+        let status = match request.method == Method::OPTIONS {
+            true => StatusCode::NO_CONTENT,
+            false => StatusCode::METHOD_NOT_ALLOWED,
+        };
+        Ok(ResponseBuilder::from_req(request, status)
+            .set_header::<Allow>(self.allow())
+            .build())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    /// `{name}`, matches any non empty segment
+    Param(String),
+}
+
+/// A route pattern such as `/users/{id}/posts`, matched a segment at a time against the
+/// percent decoded request path
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern(Vec<Segment>);
+
+impl Pattern {
+    /// # Panics
+    /// If the pattern does not start with `/`, or a segment has a malformed parameter
+    fn parse(pattern: &str) -> Self {
+        let Some(rest) = pattern.strip_prefix('/') else {
+            panic!("route {pattern} does not start with /");
+        };
+        let segments = rest.split('/').map(|segment| {
+            match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                Some(name) => {
+                    assert!(
+                        !name.is_empty() && !name.contains(['{', '}']),
+                        "invalid parameter in route {pattern}"
+                    );
+                    Segment::Param(name.to_owned())
+                }
+                None => {
+                    assert!(
+                        !segment.contains(['{', '}']),
+                        "invalid parameter in route {pattern}"
+                    );
+                    Segment::Literal(segment.to_owned())
+                }
+            }
+        });
+        Self(segments.collect())
+    }
+
+    /// Matches the first segments of `path`, and adds the parameters to `params`
+    fn matches(&self, path: &[&[u8]], params: &mut PathParams) -> bool {
+        if path.len() < self.0.len() {
+            return false;
+        }
+        for (segment, raw) in self.0.iter().zip(path) {
+            let Ok(decoded) = url_decode(raw) else {
+                return false;
+            };
+            match segment {
+                Segment::Literal(literal) if *literal == decoded => {}
+                Segment::Param(name) if !decoded.is_empty() => params.insert(name, decoded),
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// A condition a request has to meet for a guarded route to match, requests which do not meet
+/// it fall through to the other routes
+#[derive(Clone)]
+pub struct Guard(Arc<dyn Fn(&Request) -> bool + Send + Sync>);
+
+impl fmt::Debug for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Guard").finish_non_exhaustive()
+    }
+}
+
+impl Guard {
+    pub fn new(check: impl Fn(&Request) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(check))
+    }
+
+    /// The request has a field named `name`, names are case insensitive
+    pub fn header(name: &str) -> Self {
+        let name = name.to_owned();
+        Self::new(move |request| {
+            request
+                .headers
+                .iter()
+                .any(|(field, _)| field.as_bytes().eq_ignore_ascii_case(name.as_bytes()))
+        })
+    }
+
+    /// A field line of the field named `name`, or an element of its list, is `value`
+    pub fn header_value(name: &str, value: &str) -> Self {
+        let (name, value) = (name.to_owned(), value.to_owned());
+        Self::new(move |request| {
+            request
+                .headers
+                .iter()
+                .filter(|(field, _)| field.as_bytes().eq_ignore_ascii_case(name.as_bytes()))
+                .flat_map(|(_, lines)| lines.iter())
+                .any(|line| {
+                    line.trim_ascii() == value.as_bytes()
+                        || line
+                            .split(|b| *b == b',')
+                            .any(|element| element.trim_ascii() == value.as_bytes())
+                })
+        })
+    }
+
+    /// The Content-Type of the request is in `range`, such as `application/json` or `text/*`,
+    /// any parameters of the range have to match
+    pub fn content_type(range: MediaType) -> Self {
+        let range = MediaRange {
+            media: range,
+            weight: 1000,
+        };
+        Self::new(move |request| {
+            request
+                .headers
+                .get_header::<ContentType>()
+                .ok()
+                .flatten()
+                .and_then(|value| MediaType::parse(&value))
+                .is_some_and(|media| range.contains(&media))
+        })
+    }
+
+    /// The request was received over `scheme`
+    pub fn scheme(scheme: Scheme) -> Self {
+        Self::new(move |request| request.scheme() == scheme)
+    }
+
+    /// Both guards pass
+    pub fn and(self, other: Guard) -> Self {
+        Self::new(move |request| self.check(request) && other.check(request))
+    }
+
+    /// Either guard passes
+    pub fn or(self, other: Guard) -> Self {
+        Self::new(move |request| self.check(request) || other.check(request))
+    }
+
+    pub fn check(&self, request: &Request) -> bool {
+        (self.0)(request)
+    }
+}
+
+type Route = (Pattern, Option<Guard>, BoxRouter);
+
+/// What [`Routes`] does with a path which is not in its normal form
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SlashPolicy {
+    /// The path is matched as it is
+    #[default]
+    Keep,
+    /// The request is redirected with 308 Permanent Redirect to the normal path, which keeps the
+    /// method and body
+    Redirect,
+    /// The request is routed as if it had the normal path
+    Merge,
+}
+
+/// Routes requests by their path
+/// Routes are tried in the order they were added, before the nested routers, and requests
+/// matching none of them get 404 Not Found
+/// A guarded route only matches requests which pass its [`Guard`]
+/// Trailing and duplicate slashes are normalized before matching by the configured
+/// [`SlashPolicy`], which is best set on the outermost router, since the redirects of nested
+/// routers can't see the prefix they are nested under
+/// The parameters of a matched pattern are added to the [`PathParams`] of the request, after
+/// the parameters matched by any enclosing router, which they replace when they have the same
+/// name
+/// ```ignore
+/// let api = Routes::new()
+///     .route("/users", MethodRouter::new().get(handler(list)).post(handler(create)))
+///     .route("/users/{id}", MethodRouter::new().get(handler(show)));
+/// let app = Routes::new()
+///     .route("/", handler(home))
+///     .nest("/api/{version}", api);
+/// ```
+#[derive(Default)]
+pub struct Routes {
+    routes: Vec<Route>,
+    nested: Vec<Route>,
+    trailing_slash: SlashPolicy,
+    duplicate_slashes: SlashPolicy,
+}
+
+impl Routes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Routes requests whose path matches `pattern` exactly
+    /// # Panics
+    /// If the pattern is malformed or already routed without a guard
+    pub fn route(self, pattern: &str, router: impl Router) -> Self {
+        self.add_route(pattern, None, router)
+    }
+
+    /// Routes requests whose path matches `pattern` exactly and which pass `guard`
+    /// # Panics
+    /// If the pattern is malformed or already routed without a guard
+    pub fn route_guarded(self, pattern: &str, guard: Guard, router: impl Router) -> Self {
+        self.add_route(pattern, Some(guard), router)
+    }
+
+    fn add_route(mut self, pattern: &str, guard: Option<Guard>, router: impl Router) -> Self {
+        let parsed = Pattern::parse(pattern);
+        // A route without a guard matches every request a later route with the same pattern
+        // could match
+        assert!(
+            self.routes
+                .iter()
+                .all(|(other, guard, _)| *other != parsed || guard.is_some()),
+            "{pattern} is already routed"
+        );
+        self.routes.push((parsed, guard, BoxRouter::new(router)));
+        self
+    }
+
+    /// Routes requests whose path starts with the segments of `prefix` to `router`, which sees
+    /// the path with the prefix removed, so `/api/v1/users` nested under `/api/v1` is routed as
+    /// `/users`
+    /// # Panics
+    /// If the prefix is malformed, is `/` or ends with `/`
+    pub fn nest(self, prefix: &str, router: impl Router) -> Self {
+        self.add_nested(prefix, None, router)
+    }
+
+    /// Nests `router` under `prefix` for requests which pass `guard`, see [`Self::nest`]
+    /// # Panics
+    /// If the prefix is malformed, is `/` or ends with `/`
+    pub fn nest_guarded(self, prefix: &str, guard: Guard, router: impl Router) -> Self {
+        self.add_nested(prefix, Some(guard), router)
+    }
+
+    /// How paths ending with a slash, such as `/users/`, are treated, the root path is never
+    /// changed
+    pub fn with_trailing_slash(mut self, policy: SlashPolicy) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// How paths with empty segments, such as `/users//7`, are treated
+    pub fn with_duplicate_slashes(mut self, policy: SlashPolicy) -> Self {
+        self.duplicate_slashes = policy;
+        self
+    }
+
+    /// The normal form of `path`, if it is not already normal, and whether the request should
+    /// be redirected to it
+    fn normalize(&self, path: &[u8]) -> Option<(Vec<u8>, bool)> {
+        let mut normal = path.to_vec();
+        let mut redirect = false;
+        if self.duplicate_slashes != SlashPolicy::Keep && path.windows(2).any(|w| w == b"//") {
+            normal.dedup_by(|a, b| *a == b'/' && *b == b'/');
+            redirect |= self.duplicate_slashes == SlashPolicy::Redirect;
+        }
+        if self.trailing_slash != SlashPolicy::Keep && normal.len() > 1 && normal.ends_with(b"/") {
+            while normal.len() > 1 && normal.ends_with(b"/") {
+                normal.pop();
+            }
+            redirect |= self.trailing_slash == SlashPolicy::Redirect;
+        }
+        (normal != path).then_some((normal, redirect))
+    }
+
+    fn add_nested(mut self, prefix: &str, guard: Option<Guard>, router: impl Router) -> Self {
+        assert!(
+            prefix.len() > 1 && !prefix.ends_with('/'),
+            "invalid prefix {prefix}"
+        );
+        self.nested
+            .push((Pattern::parse(prefix), guard, BoxRouter::new(router)));
+        self
+    }
+}
+
+impl Router for Routes {
+    async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+        let target = &request.target;
+        if !target.starts_with(b"/") {
+            return Err(RouterError::BadRequest(
+                "expected an origin-form target".into(),
+            ));
+        }
+        let end = target
+            .iter()
+            .position(|b| *b == b'?')
+            .unwrap_or(target.len());
+        let (path, query) = target.split_at(end);
+        let normal = self.normalize(path);
+        let path = match &normal {
+            Some((normal, true)) => {
+                let location = [&normal[..], query].concat();
+                return Ok(
+                    ResponseBuilder::redirect(StatusCode::PERMANENT_REDIRECT, location).build(),
+                );
+            }
+            Some((normal, false)) => &normal[..],
+            None => path,
+        };
+        // Routers see the path they were matched by
+        let merged = normal
+            .as_ref()
+            .map(|(normal, _)| Bytes::from([&normal[..], query].concat()));
+        let path: Vec<_> = path[1..].split(|b| *b == b'/').collect();
+        let enclosing = request
+            .extensions
+            .get::<PathParams>()
+            .cloned()
+            .unwrap_or_default();
+
+        let passes = |guard: &Option<Guard>, request: &Request| {
+            guard.as_ref().is_none_or(|guard| guard.check(request))
+        };
+        for (pattern, guard, router) in &self.routes {
+            let mut params = enclosing.clone();
+            if pattern.0.len() == path.len() && pattern.matches(&path, &mut params) {
+                let mut request = request.clone();
+                if let Some(merged) = &merged {
+                    request.target = merged.clone();
+                }
+                request.extensions.insert(params);
+                if passes(guard, &request) {
+                    return router.route(&request).await;
+                }
+            }
+        }
+        for (prefix, guard, router) in &self.nested {
+            let mut params = enclosing.clone();
+            if prefix.matches(&path, &mut params) {
+                let mut request = request.clone();
+                let rest = path[prefix.0.len()..].join(&b'/');
+                request.target = [&b"/"[..], &rest, query].concat().into();
+                request.extensions.insert(params);
+                if passes(guard, &request) {
+                    return router.route(&request).await;
+                }
+            }
+        }
+        Err(RouterError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{
+        Body,
+        header::{HeaderField, HeaderName, Location},
+    };
+
+    fn request(method: Method) -> Request {
+        Request::new(method, "/users/1")
+    }
+
+    struct Text(&'static str);
+
+    impl Router for Text {
+        async fn route(&self, _request: &Request) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::text(self.0).build())
+        }
+    }
+
+    fn allow(res: &Response) -> Bytes {
+        res.headers.get(&Allow::NAME).unwrap().collect()
+    }
+
+    #[tokio::test]
+    async fn routes_by_method() {
+        let router = MethodRouter::new().get(Text("show")).delete(Text("delete"));
+
+        let res = router.route(&request(Method::GET)).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "show"));
+        let res = router.route(&request(Method::DELETE)).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "delete"));
+
+        let res = router.route(&request(Method::POST)).await.unwrap();
+        assert_eq!(res.status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(allow(&res), "GET, HEAD, DELETE, OPTIONS");
+
+        let res = router.route(&request(Method::OPTIONS)).await.unwrap();
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+        assert_eq!(allow(&res), "GET, HEAD, DELETE, OPTIONS");
+    }
+
+    #[tokio::test]
+    async fn head_falls_back_to_get() {
+        let router = MethodRouter::new().get(Text("show"));
+        let res = router.route(&request(Method::HEAD)).await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(matches!(res.body, Body::Full(ref body) if body == "show"));
+
+        // An explicit HEAD router is preferred, and listed where it was added
+        let router = MethodRouter::new().head(Text("head")).get(Text("show"));
+        let res = router.route(&request(Method::HEAD)).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "head"));
+        assert_eq!(
+            router.allowed(),
+            [Method::HEAD, Method::GET, Method::OPTIONS]
+        );
+
+        let router = MethodRouter::new().post(Text("create"));
+        let res = router.route(&request(Method::HEAD)).await.unwrap();
+        assert_eq!(res.status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(allow(&res), "POST, OPTIONS");
+    }
+
+    #[tokio::test]
+    async fn explicit_options() {
+        let router = MethodRouter::new()
+            .options(Text("options"))
+            .post(Text("create"));
+        let res = router.route(&request(Method::OPTIONS)).await.unwrap();
+        assert!(matches!(res.body, Body::Full(ref body) if body == "options"));
+
+        let res = router.route(&request(Method::GET)).await.unwrap();
+        assert_eq!(res.status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(allow(&res), "OPTIONS, POST");
+    }
+
+    #[test]
+    #[should_panic(expected = "GET is already routed")]
+    fn duplicate_method() {
+        let _ = MethodRouter::new().get(Text("a")).get(Text("b"));
+    }
+
+    struct Echo;
+
+    impl Router for Echo {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            let params = request.extensions.get::<PathParams>().unwrap();
+            let params: Vec<_> = params
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            let target = String::from_utf8_lossy(&request.target);
+            Ok(ResponseBuilder::text(format!("{target} {}", params.join(","))).build())
+        }
+    }
+
+    async fn body(router: &Routes, target: &'static str) -> Result<Bytes, StatusCode> {
+        let mut req = request(Method::GET);
+        req.target = Bytes::from_static(target.as_bytes());
+        match router.route(&req).await {
+            Ok(Response {
+                body: Body::Full(body),
+                ..
+            }) => Ok(body),
+            Ok(res) => Err(res.status),
+            Err(err) => Err(err.status_code()),
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_by_path() {
+        let router = Routes::new()
+            .route("/", Text("home"))
+            .route("/users/{id}", Echo)
+            .route("/users/{id}/posts/{post}", Echo);
+        assert_eq!(body(&router, "/?x=1").await.unwrap(), "home");
+        assert_eq!(
+            body(&router, "/users/a%20b").await.unwrap(),
+            "/users/a%20b id=a b"
+        );
+        assert_eq!(
+            body(&router, "/users/7/posts/2?page=1").await.unwrap(),
+            "/users/7/posts/2?page=1 id=7,post=2"
+        );
+        for target in [
+            "/users",
+            "/users/",
+            "/users/7/posts",
+            "/other",
+            "/users/%ZZ",
+        ] {
+            assert_eq!(body(&router, target).await, Err(StatusCode::NOT_FOUND));
+        }
+    }
+
+    #[tokio::test]
+    async fn nested_routers() {
+        let users = Routes::new()
+            .route("/", Echo)
+            .route("/{id}", Echo)
+            .route("/{id}/{version}", Echo);
+        let api = Routes::new()
+            .route("/health", Text("ok"))
+            .nest("/users", users);
+        let router = Routes::new()
+            .route("/api/{version}/health", Text("outer"))
+            .nest("/api/{version}", api);
+
+        // Routes are tried before nested routers
+        assert_eq!(body(&router, "/api/v1/health").await.unwrap(), "outer");
+        assert_eq!(
+            body(&router, "/api/v1/users?all").await.unwrap(),
+            "/?all version=v1"
+        );
+        assert_eq!(
+            body(&router, "/api/v1/users/7?x=1").await.unwrap(),
+            "/7?x=1 version=v1,id=7"
+        );
+        // Inner parameters replace the enclosing parameters with the same name
+        assert_eq!(
+            body(&router, "/api/v1/users/7/v2").await.unwrap(),
+            "/7/v2 id=7,version=v2"
+        );
+        // Prefixes match whole segments
+        assert_eq!(
+            body(&router, "/api/v1/usersx").await,
+            Err(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(body(&router, "/api").await, Err(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn guarded_routes() {
+        let json = Guard::content_type(MediaType::json());
+        let router = &Routes::new()
+            .route_guarded(
+                "/items",
+                json.clone().and(Guard::header("x-api-key")),
+                Text("json"),
+            )
+            .route_guarded(
+                "/items",
+                Guard::header_value("Prefer", "minimal").or(Guard::scheme(Scheme::Https)),
+                Text("preferred"),
+            )
+            .route_guarded(
+                "/items",
+                Guard::new(|request| request.method == Method::DELETE),
+                Text("custom"),
+            )
+            .route("/items", Text("fallback"))
+            .nest_guarded("/admin", Guard::scheme(Scheme::Https), Text("admin"));
+
+        let route = async |headers: &[(&'static str, &'static str)], scheme, method| {
+            let mut req = request(method);
+            req.target = Bytes::from_static(b"/items");
+            for (name, value) in headers {
+                let name = HeaderName::try_from(&Bytes::from_static(name.as_bytes())).unwrap();
+                req.headers
+                    .entry(name)
+                    .push(Bytes::from_static(value.as_bytes()));
+            }
+            req.extensions.insert::<Scheme>(scheme);
+            match router.route(&req).await.unwrap().body {
+                Body::Full(body) => body,
+                _ => unreachable!(),
+            }
+        };
+        let (http, https) = (Scheme::Http, Scheme::Https);
+        let json_key = [
+            ("Content-Type", "application/json; charset=utf-8"),
+            ("X-Api-Key", "1"),
+        ];
+        assert_eq!(route(&json_key, http, Method::POST).await, "json");
+        // Guards which fail fall through to the next route
+        let text_key = [("Content-Type", "text/plain"), ("x-api-key", "1")];
+        assert_eq!(route(&text_key, http, Method::POST).await, "fallback");
+        let prefer = [("prefer", "respond-async, minimal")];
+        assert_eq!(route(&prefer, http, Method::GET).await, "preferred");
+        assert_eq!(route(&[], https, Method::GET).await, "preferred");
+        assert_eq!(route(&[], http, Method::DELETE).await, "custom");
+        assert_eq!(route(&[], http, Method::GET).await, "fallback");
+
+        assert_eq!(body(router, "/admin").await, Err(StatusCode::NOT_FOUND));
+        let mut req = request(Method::GET);
+        req.target = Bytes::from_static(b"/admin/users");
+        req.extensions.insert(Scheme::Https);
+        assert_eq!(router.route(&req).await.unwrap().status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn normalizes_slashes() {
+        let routes = || Routes::new().route("/", Echo).route("/users/{id}", Echo);
+        let redirect = &routes()
+            .with_trailing_slash(SlashPolicy::Redirect)
+            .with_duplicate_slashes(SlashPolicy::Merge);
+        let location = async |target: &'static str| {
+            let mut req = request(Method::POST);
+            req.target = Bytes::from_static(target.as_bytes());
+            let res = redirect.route(&req).await.unwrap();
+            assert_eq!(res.status, StatusCode::PERMANENT_REDIRECT);
+            res.headers.get(&Location::NAME).unwrap().collect()
+        };
+        assert_eq!(location("/users/7/?a=1").await, "/users/7?a=1");
+        // Merged slashes are left out of the redirect
+        assert_eq!(location("//users//7//").await, "/users/7");
+        assert_eq!(body(redirect, "/").await.unwrap(), "/ ");
+        assert_eq!(
+            body(redirect, "/users//7?a=//").await.unwrap(),
+            "/users/7?a=// id=7"
+        );
+
+        let merge = &routes()
+            .with_trailing_slash(SlashPolicy::Merge)
+            .with_duplicate_slashes(SlashPolicy::Redirect);
+        assert_eq!(body(merge, "/users/7/").await.unwrap(), "/users/7 id=7");
+        let mut req = request(Method::GET);
+        req.target = Bytes::from_static(b"/users//7/");
+        let res = merge.route(&req).await.unwrap();
+        assert_eq!(res.status, StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(
+            res.headers.get(&Location::NAME).unwrap().collect(),
+            "/users/7"
+        );
+
+        // Paths are matched as they are by default
+        assert_eq!(
+            body(&routes(), "/users/7/").await,
+            Err(StatusCode::NOT_FOUND)
+        );
+        assert_eq!(body(&routes(), "//").await, Err(StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,144 @@
+//! Graceful shutdown
+//!
+//! Once shutdown starts the server stops accepting connections, and in-flight requests are
+//! given a grace period to finish. Handlers which produce long running responses (event
+//! streams, long polls) can find a [`ShutdownSignal`] in the request extensions, and use it to
+//! end their response before the grace period expires and the connection is closed
+
+use std::{fmt, future::IntoFuture, pin::Pin, sync::Arc, time::Duration};
+
+use tokio::{sync::watch, time::Instant};
+
+use crate::clock::SharedClock;
+
+/// Why the server is shutting down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// Shutdown was requested through a [`ShutdownHandle`]
+    Requested,
+    /// The server is being restarted, and should be available again shortly
+    Restart,
+    /// An application defined reason
+    Custom(Arc<str>),
+}
+
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Requested => f.write_str("shutdown requested"),
+            Self::Restart => f.write_str("restarting"),
+            Self::Custom(reason) => f.write_str(reason),
+        }
+    }
+}
+
+/// A shutdown which has started
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shutdown {
+    pub reason: ShutdownReason,
+    /// Connections which are still open at the deadline are closed
+    pub deadline: Instant,
+}
+
+/// Starts a graceful shutdown of the server it was obtained from
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    tx: Arc<watch::Sender<Option<Shutdown>>>,
+    grace_period: Duration,
+    clock: SharedClock,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new(grace_period: Duration, clock: SharedClock) -> Self {
+        Self {
+            tx: Arc::new(watch::Sender::new(None)),
+            grace_period,
+            clock,
+        }
+    }
+
+    /// Starts shutting down with the configured grace period
+    /// Only the first shutdown takes effect, returns false if shutdown had already started
+    pub fn shutdown(&self, reason: ShutdownReason) -> bool {
+        self.shutdown_with_grace(reason, self.grace_period)
+    }
+
+    pub fn shutdown_with_grace(&self, reason: ShutdownReason, grace_period: Duration) -> bool {
+        let deadline = self.clock.now() + grace_period;
+        self.tx.send_if_modified(|state| {
+            if state.is_some() {
+                return false;
+            }
+            *state = Some(Shutdown { reason, deadline });
+            true
+        })
+    }
+
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            rx: self.tx.subscribe(),
+        }
+    }
+}
+
+/// Observes the server shutting down, awaiting the signal resolves once shutdown starts
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal {
+    rx: watch::Receiver<Option<Shutdown>>,
+}
+
+impl ShutdownSignal {
+    /// The shutdown, if it has started
+    pub fn get(&self) -> Option<Shutdown> {
+        self.rx.borrow().clone()
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.rx.borrow().is_some()
+    }
+
+    /// Resolves once shutdown starts, never resolves if the server is dropped without
+    /// shutting down
+    pub async fn triggered(&self) -> Shutdown {
+        let mut rx = self.rx.clone();
+        let shutdown = rx
+            .wait_for(Option::is_some)
+            .await
+            .map(|state| state.clone().unwrap());
+        match shutdown {
+            Ok(shutdown) => shutdown,
+            Err(_) => std::future::pending().await,
+        }
+    }
+}
+
+impl IntoFuture for ShutdownSignal {
+    type Output = Shutdown;
+    type IntoFuture = Pin<Box<dyn Future<Output = Shutdown> + Send>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move { self.triggered().await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TokioClock;
+
+    #[tokio::test]
+    async fn signal() {
+        let handle = ShutdownHandle::new(Duration::from_secs(5), TokioClock::shared());
+        let signal = handle.signal();
+        assert!(!signal.is_shutting_down());
+
+        let waiter = tokio::spawn(signal.clone().into_future());
+        assert!(handle.shutdown(ShutdownReason::Restart));
+        assert!(!handle.shutdown(ShutdownReason::Requested));
+
+        let shutdown = waiter.await.unwrap();
+        assert_eq!(shutdown.reason, ShutdownReason::Restart);
+        assert!(shutdown.deadline > Instant::now());
+        assert_eq!(signal.get(), Some(shutdown));
+    }
+}
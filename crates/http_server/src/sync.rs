@@ -1,5 +1,116 @@
-use tokio::{io::AsyncRead, sync::mpsc};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
 
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::mpsc::{self, OwnedPermit, error::SendError},
+};
+
+/// Creates an in-memory pipe, what is written to the [`ChannelWriter`] is read from the
+/// [`ChunkReader`]
+/// Each write is sent as one chunk, and writes wait once `capacity` chunks are waiting to be
+/// read
+pub fn channel(capacity: usize) -> (ChannelWriter, ChunkReader) {
+    let (tx, rx) = mpsc::channel(capacity);
+    (ChannelWriter::new(tx), ChunkReader::new(rx))
+}
+
+/// Reads the bytes of the chunks received from a channel, the stream ends once every sender
+/// has been dropped
+pub struct ChunkReader {
+    rx: mpsc::Receiver<Bytes>,
+    /// The rest of a chunk which did not fit in the last read
+    pending: Bytes,
+}
+
+impl ChunkReader {
+    pub fn new(rx: mpsc::Receiver<Bytes>) -> Self {
+        Self {
+            rx,
+            pending: Bytes::new(),
+        }
+    }
+}
+
+impl AsyncRead for ChunkReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        while self.pending.is_empty() {
+            match ready!(self.rx.poll_recv(cx)) {
+                Some(chunk) => self.pending = chunk,
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+        let len = self.pending.len().min(buf.remaining());
+        buf.put_slice(&self.pending.split_to(len));
+        Poll::Ready(Ok(()))
+    }
+}
+
+type Reserve = Pin<Box<dyn Future<Output = Result<OwnedPermit<Bytes>, SendError<()>>> + Send>>;
+
+/// Sends what is written to a channel, one chunk per write
+/// Writes wait while the channel is full, and fail with [`io::ErrorKind::BrokenPipe`] once the
+/// receiver has been dropped, shutting the writer down ends the stream for the reader
+pub struct ChannelWriter {
+    tx: Option<mpsc::Sender<Bytes>>,
+    /// Waiting for room in the channel
+    reserve: Option<Reserve>,
+}
+
+impl ChannelWriter {
+    pub fn new(tx: mpsc::Sender<Bytes>) -> Self {
+        Self {
+            tx: Some(tx),
+            reserve: None,
+        }
+    }
+}
+
+impl AsyncWrite for ChannelWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.reserve.is_none() {
+            let Some(tx) = self.tx.clone() else {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            };
+            self.reserve = Some(Box::pin(tx.reserve_owned()));
+        }
+        let reserve = self.reserve.as_mut().expect("a reservation was made");
+        let permit = ready!(reserve.as_mut().poll(cx));
+        self.reserve = None;
+        match permit {
+            Ok(permit) => {
+                permit.send(Bytes::copy_from_slice(buf));
+                Poll::Ready(Ok(buf.len()))
+            }
+            Err(_) => Poll::Ready(Err(io::ErrorKind::BrokenPipe.into())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.tx = None;
+        self.reserve = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads the bytes received from a channel one at a time, for tests which pace a message a
+/// byte at a time, [`ChunkReader`] is faster otherwise
 pub struct ChannelReader {
     rx: mpsc::Receiver<u8>,
 }
@@ -12,11 +123,10 @@ impl ChannelReader {
 
 impl AsyncRead for ChannelReader {
     fn poll_read(
-        mut self: std::pin::Pin<&mut Self>,
-        cx: &mut std::task::Context<'_>,
-        buf: &mut tokio::io::ReadBuf<'_>,
-    ) -> std::task::Poll<std::io::Result<()>> {
-        use std::task::Poll;
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
         let mut read_bytes = 0;
         while buf.remaining() > 0 {
             match self.rx.poll_recv(cx) {
@@ -42,13 +152,16 @@ impl AsyncRead for ChannelReader {
 mod tests {
     use std::time::Duration;
 
-    use tokio::{io::AsyncReadExt, time::sleep};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        time::sleep,
+    };
 
     use super::*;
 
     #[tokio::test]
     async fn test_channel_reader() {
-        const LINE: &'static [u8] = b"GET / HTTP/1.1\r\nHost: test\r\n\r\n";
+        const LINE: &[u8] = b"GET / HTTP/1.1\r\nHost: test\r\n\r\n";
         fn setup_reader() -> ChannelReader {
             let (tx, rx) = mpsc::channel::<u8>(LINE.len());
 
@@ -86,4 +199,39 @@ mod tests {
             assert_eq!(&buf[..], LINE);
         }
     }
+
+    #[tokio::test]
+    async fn chunks() {
+        let (mut writer, mut reader) = channel(4);
+        writer.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+        writer.write_all(b"Host: test\r\n\r\n").await.unwrap();
+        writer.shutdown().await.unwrap();
+
+        // A chunk which doesn't fit in the buffer is read over several reads
+        let mut buf = [0; 4];
+        assert_eq!(reader.read(&mut buf).await.unwrap(), 4);
+        assert_eq!(&buf, b"GET ");
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"/ HTTP/1.1\r\nHost: test\r\n\r\n");
+    }
+
+    #[tokio::test]
+    async fn backpressure() {
+        let (mut writer, mut reader) = channel(1);
+        writer.write_all(b"first").await.unwrap();
+        // The channel is full until the first chunk is read
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), writer.write_all(b"second"))
+                .await
+                .is_err()
+        );
+        let mut buf = [0; 5];
+        reader.read_exact(&mut buf).await.unwrap();
+        writer.write_all(b"second").await.unwrap();
+
+        drop(reader);
+        let err = writer.write_all(b"third").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
 }
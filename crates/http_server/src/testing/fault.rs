@@ -0,0 +1,217 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use crate::clock::{SharedClock, Sleep, TokioClock};
+
+/// Something which goes wrong while reading from a [`FaultyStream`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Waits this long on the clock of the stream before reading on
+    Delay(Duration),
+    /// Returns at most this many bytes per read from then on
+    ShortReads(usize),
+    /// Ends the stream, every later read returns 0 bytes
+    Eof,
+    /// Fails one read with an error of this kind, reads after it carry on
+    Error(io::ErrorKind),
+}
+
+/// Wraps a transport, and injects [`Fault`]s once given numbers of bytes have been read from it
+/// A read never crosses the offset of a fault, so the parser sees the fault at exactly that
+/// byte. Writes go straight to the transport
+/// Delays sleep on the clock of the stream, so with a [`crate::clock::MockClock`] they only end
+/// once the clock is advanced
+pub struct FaultyStream<S> {
+    inner: S,
+    /// Sorted by offset, faults at the same offset apply in the order they were added
+    faults: Vec<(u64, Fault)>,
+    read: u64,
+    max_read: Option<usize>,
+    clock: SharedClock,
+    sleep: Option<Sleep>,
+}
+
+impl<S> FaultyStream<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            faults: Vec::new(),
+            read: 0,
+            max_read: None,
+            clock: TokioClock::shared(),
+            sleep: None,
+        }
+    }
+
+    /// The clock delays sleep on
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Injects `fault` once `offset` bytes have been read
+    pub fn with_fault(mut self, offset: u64, fault: Fault) -> Self {
+        let idx = self.faults.partition_point(|(at, _)| *at <= offset);
+        self.faults.insert(idx, (offset, fault));
+        self
+    }
+
+    /// How many bytes have been read from the transport
+    pub fn bytes_read(&self) -> u64 {
+        self.read
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for FaultyStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        while let Some(&(offset, fault)) = this.faults.first()
+            && offset <= this.read
+        {
+            match fault {
+                Fault::Delay(duration) => {
+                    let clock = &this.clock;
+                    let sleep = this.sleep.get_or_insert_with(|| clock.sleep(duration));
+                    ready!(sleep.as_mut().poll(cx));
+                    this.sleep = None;
+                }
+                Fault::ShortReads(max) => this.max_read = Some(max),
+                Fault::Eof => return Poll::Ready(Ok(())),
+                Fault::Error(kind) => {
+                    this.faults.remove(0);
+                    return Poll::Ready(Err(kind.into()));
+                }
+            }
+            this.faults.remove(0);
+        }
+
+        let mut limit = buf.remaining();
+        if let Some(&(offset, _)) = this.faults.first() {
+            limit = limit.min((offset - this.read).try_into().unwrap_or(usize::MAX));
+        }
+        if let Some(max) = this.max_read {
+            limit = limit.min(max);
+        }
+        let mut scratch = vec![0; limit];
+        let mut limited = ReadBuf::new(&mut scratch);
+        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut limited))?;
+        buf.put_slice(limited.filled());
+        this.read += limited.filled().len() as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for FaultyStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        clock::MockClock,
+        http::parser::{HeadLimits, Location, ParseErrorKind, Parser},
+    };
+
+    const HEAD: &[u8] = b"GET /path HTTP/1.1\r\nHost: example.com\r\nX-Trace: 1\r\n\r\n";
+
+    fn head_parser(
+        stream: FaultyStream<&'static [u8]>,
+        clock: SharedClock,
+    ) -> Parser<FaultyStream<&'static [u8]>> {
+        let limits = HeadLimits {
+            read_timeout: Duration::from_secs(1),
+            clock: clock.clone(),
+            ..HeadLimits::default()
+        };
+        Parser::with_limits(stream.with_clock(clock), limits)
+    }
+
+    #[tokio::test]
+    async fn short_reads() {
+        let stream = FaultyStream::new(HEAD).with_fault(0, Fault::ShortReads(1));
+        let (req, _) = head_parser(stream, TokioClock::shared())
+            .parse_request_head()
+            .await
+            .unwrap();
+        assert_eq!(req.target, "/path");
+        assert_eq!(req.headers.keys().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn eof_and_errors() {
+        // The request line is complete, the headers are not
+        let stream = FaultyStream::new(HEAD).with_fault(25, Fault::Eof);
+        let err = head_parser(stream, TokioClock::shared())
+            .parse_request_head()
+            .await
+            .unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::IncompleteMessage));
+        assert!(matches!(err.location, Location::Headers));
+
+        let stream = FaultyStream::new(HEAD)
+            .with_fault(4, Fault::ShortReads(2))
+            .with_fault(10, Fault::Error(io::ErrorKind::ConnectionReset));
+        let mut parser = head_parser(stream, TokioClock::shared());
+        let err = parser.parse_request_head().await.unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ParseErrorKind::Io(io::ErrorKind::ConnectionReset)
+        ));
+        assert_eq!(parser.buffered(), &HEAD[..10]);
+    }
+
+    #[tokio::test]
+    async fn delays() {
+        let clock = Arc::new(MockClock::new());
+        // A pause shorter than the read timeout is waited out
+        let stream =
+            FaultyStream::new(HEAD).with_fault(8, Fault::Delay(Duration::from_millis(500)));
+        let mut parser = head_parser(stream, clock.clone());
+        let (res, ()) = tokio::join!(parser.parse_request_head(), async {
+            tokio::task::yield_now().await;
+            clock.advance(Duration::from_millis(500))
+        });
+        assert!(res.is_ok());
+
+        let stream = FaultyStream::new(HEAD).with_fault(8, Fault::Delay(Duration::from_secs(2)));
+        let mut parser = head_parser(stream, clock.clone());
+        let (res, ()) = tokio::join!(parser.parse_request_head(), async {
+            tokio::task::yield_now().await;
+            clock.advance(Duration::from_secs(1))
+        });
+        let err = res.unwrap_err();
+        assert!(matches!(err.kind, ParseErrorKind::Timeout));
+        assert!(matches!(err.location, Location::StartLine));
+    }
+}
@@ -0,0 +1,197 @@
+//! Driving a [`Router`] end to end in tests, without binding sockets
+//!
+//! A [`TestClient`] serves each connection over an in-memory stream with the real parser,
+//! server and [`Client`], so requests go through the same framing, limits and middleware as on
+//! the network
+//!
+//! ```ignore
+//! let client = TestClient::new(router);
+//! let res = client.get("/path").send().await?;
+//! assert_eq!(res.status, StatusCode::OK);
+//! ```
+//!
+//! A [`FaultyStream`] wraps a transport to inject delays, short reads, early EOFs and errors at
+//! given offsets, for testing how the parser handles a misbehaving peer
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use bytes::Bytes;
+use tokio::io::DuplexStream;
+
+use crate::{
+    HttpServer, HttpServerConfig, Router,
+    client::{Client, ClientConnection, ClientError},
+    http::{
+        HttpVersion,
+        method::Method,
+        request::{Request, RequestBuilder},
+        response::Response,
+    },
+};
+
+mod fault;
+pub use fault::{Fault, FaultyStream};
+
+/// How many bytes each direction of an in-memory connection buffers
+const DUPLEX_CAPACITY: usize = 64 * 1024;
+
+/// The address connections from a [`TestClient`] appear to come from
+pub const TEST_PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 49152);
+
+/// Sends requests to a router through an in-memory server
+pub struct TestClient<R: Router> {
+    server: HttpServer<R>,
+    client: Client,
+}
+
+impl<R: Router> TestClient<R> {
+    pub fn new(router: R) -> Self {
+        Self::with_config(router, HttpServerConfig::default())
+    }
+
+    pub fn with_config(router: R, config: HttpServerConfig) -> Self {
+        let client = Client::new().with_clock(config.clock.clone());
+        Self {
+            server: HttpServer::with_config(TEST_PEER, router, config),
+            client,
+        }
+    }
+
+    /// Replaces the client which sends the requests, for its limits or timeout
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Opens a connection to the server, for tests which send several requests on one
+    /// connection
+    pub fn connect(&self) -> ClientConnection<DuplexStream> {
+        let (client, server) = tokio::io::duplex(DUPLEX_CAPACITY);
+        tokio::spawn(self.server.serve_connection(server, Some(TEST_PEER)));
+        self.client.handshake(client, "localhost")
+    }
+
+    pub fn request(&self, method: Method, target: impl Into<Bytes>) -> TestRequest<'_, R> {
+        TestRequest {
+            client: self,
+            builder: RequestBuilder::new(method, target),
+        }
+    }
+
+    pub fn get(&self, target: impl Into<Bytes>) -> TestRequest<'_, R> {
+        self.request(Method::GET, target)
+    }
+
+    pub fn post(&self, target: impl Into<Bytes>) -> TestRequest<'_, R> {
+        self.request(Method::POST, target)
+    }
+
+    pub fn put(&self, target: impl Into<Bytes>) -> TestRequest<'_, R> {
+        self.request(Method::PUT, target)
+    }
+
+    pub fn delete(&self, target: impl Into<Bytes>) -> TestRequest<'_, R> {
+        self.request(Method::DELETE, target)
+    }
+
+    /// Sends `request` on a new connection
+    pub async fn send(&self, request: Request) -> Result<Response, ClientError> {
+        self.connect().send(request).await
+    }
+}
+
+/// A request being built by a [`TestClient`]
+pub struct TestRequest<'a, R: Router> {
+    client: &'a TestClient<R>,
+    builder: RequestBuilder,
+}
+
+impl<R: Router> TestRequest<'_, R> {
+    /// Adds a header
+    /// Panics if the name is not a token, or the value contains CR, LF or NUL
+    pub fn header(mut self, name: &'static str, value: impl Into<Bytes>) -> Self {
+        self.builder = self
+            .builder
+            .add_header(&Bytes::from_static(name.as_bytes()), value.into());
+        self
+    }
+
+    pub fn version(mut self, version: HttpVersion) -> Self {
+        self.builder = self.builder.version(version);
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Bytes>) -> Self {
+        self.builder = self.builder.body(body.into());
+        self
+    }
+
+    /// Sets the body to `value` serialized as JSON
+    /// Panics if the value can't be serialized
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize + ?Sized>(mut self, value: &T) -> Self {
+        self.builder = self.builder.json(value).expect("invalid JSON body");
+        self
+    }
+
+    /// Sends the request on a new connection, and reads the whole response
+    pub async fn send(self) -> Result<Response, ClientError> {
+        self.client.send(self.builder.build()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        RouterError,
+        http::{
+            Body,
+            response::{ResponseBuilder, StatusCode},
+        },
+    };
+
+    struct Echo;
+
+    impl Router for Echo {
+        async fn route(&self, request: &Request) -> Result<Response, RouterError> {
+            if request.target.as_ref() == b"/missing" {
+                return Err(RouterError::NotFound);
+            }
+            let body = request.body.collect(None).await?;
+            let peer = request.client_addr().unwrap().to_string();
+            Ok(ResponseBuilder::from_req(request, StatusCode::OK)
+                .add_header(&Bytes::from_static(b"X-Peer"), Bytes::from(peer))
+                .body(body)
+                .build())
+        }
+    }
+
+    #[tokio::test]
+    async fn requests() {
+        let client = TestClient::new(Echo);
+        let res = client.post("/echo").body("hello").send().await.unwrap();
+        assert_eq!(res.status, StatusCode::OK);
+        assert!(matches!(res.body, Body::Full(ref body) if body == "hello"));
+        let peer = res
+            .headers
+            .iter()
+            .find(|(name, _)| name.as_bytes() == b"X-Peer");
+        assert_eq!(peer.unwrap().1[0], "127.0.0.1");
+
+        let res = client.get("/missing").send().await.unwrap();
+        assert_eq!(res.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn keep_alive() {
+        let client = TestClient::new(Echo);
+        let mut conn = client.connect();
+        for body in ["a", "b", "c"] {
+            let req = RequestBuilder::post("/").body(Bytes::from(body)).build();
+            let res = conn.send(req).await.unwrap();
+            assert!(matches!(res.body, Body::Full(ref received) if received == body));
+        }
+        assert!(!conn.is_closed());
+    }
+}
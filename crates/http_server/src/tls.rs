@@ -0,0 +1,301 @@
+//! TLS termination on the listener
+//!
+//! Handshakes have their own timeout, as a client which stalls the handshake holds a connection
+//! open before any HTTP limits apply
+//!
+//! The certificates can be replaced while the server runs, see [`crate::HttpServer::reload_tls`],
+//! established connections keep the certificates they were accepted with
+
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::clock::{self, SharedClock};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{
+    TlsAcceptor,
+    rustls::{
+        self, AlertDescription,
+        crypto::ring::default_provider,
+        pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+    },
+    server::TlsStream,
+};
+
+pub use tokio_rustls::rustls::ServerConfig;
+
+/// Why a TLS handshake failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HandshakeFailure {
+    /// The client and server don't share a protocol version or cipher suite
+    ProtocolVersion,
+    /// A certificate was rejected by either side
+    BadCertificate,
+    /// The handshake did not complete within the handshake timeout
+    TimedOut,
+    /// The connection failed, or the client sent something which is not TLS
+    Other,
+}
+
+impl HandshakeFailure {
+    pub(crate) fn from_io(err: &std::io::Error) -> Self {
+        let Some(err) = err
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        else {
+            return Self::Other;
+        };
+        match err {
+            rustls::Error::PeerIncompatible(_)
+            | rustls::Error::AlertReceived(
+                AlertDescription::ProtocolVersion | AlertDescription::HandshakeFailure,
+            ) => Self::ProtocolVersion,
+            rustls::Error::InvalidCertificate(_)
+            | rustls::Error::NoCertificatesPresented
+            | rustls::Error::AlertReceived(
+                AlertDescription::BadCertificate
+                | AlertDescription::UnsupportedCertificate
+                | AlertDescription::CertificateRevoked
+                | AlertDescription::CertificateExpired
+                | AlertDescription::CertificateUnknown
+                | AlertDescription::UnknownCA,
+            ) => Self::BadCertificate,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// A certificate chain and its private key could not be loaded
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateError {
+    #[error("failed to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: rustls::pki_types::pem::Error,
+    },
+    #[error("no certificate in {0}")]
+    NoCertificate(PathBuf),
+    /// The key does not match the certificate, or is not supported
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+}
+
+/// The PEM files of a server certificate chain and its private key, which can be read again
+/// once they have been renewed
+#[derive(Debug, Clone)]
+pub struct CertificateFiles {
+    pub cert_chain: PathBuf,
+    pub private_key: PathBuf,
+}
+
+impl CertificateFiles {
+    pub fn new(cert_chain: impl Into<PathBuf>, private_key: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_chain: cert_chain.into(),
+            private_key: private_key.into(),
+        }
+    }
+
+    /// Reads the files into a config serving the chain, which negotiates HTTP/1.1 with ALPN
+    pub fn load(&self) -> Result<Arc<ServerConfig>, CertificateError> {
+        let read = |path: &PathBuf, source| CertificateError::Read {
+            path: path.clone(),
+            source,
+        };
+        let chain = CertificateDer::pem_file_iter(&self.cert_chain)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|err| read(&self.cert_chain, err))?;
+        if chain.is_empty() {
+            return Err(CertificateError::NoCertificate(self.cert_chain.clone()));
+        }
+        let key = PrivateKeyDer::from_pem_file(&self.private_key)
+            .map_err(|err| read(&self.private_key, err))?;
+        let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(chain, key)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(Arc::new(config))
+    }
+}
+
+/// Counters for TLS handshakes on the listener
+#[derive(Debug, Default)]
+pub struct HandshakeMetrics {
+    completed: AtomicU64,
+    protocol_version: AtomicU64,
+    bad_certificate: AtomicU64,
+    timed_out: AtomicU64,
+    other: AtomicU64,
+}
+
+impl HandshakeMetrics {
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub fn failures(&self, cause: HandshakeFailure) -> u64 {
+        self.counter(cause).load(Ordering::Relaxed)
+    }
+
+    fn counter(&self, cause: HandshakeFailure) -> &AtomicU64 {
+        match cause {
+            HandshakeFailure::ProtocolVersion => &self.protocol_version,
+            HandshakeFailure::BadCertificate => &self.bad_certificate,
+            HandshakeFailure::TimedOut => &self.timed_out,
+            HandshakeFailure::Other => &self.other,
+        }
+    }
+}
+
+/// Accepts TLS connections, recording the outcome of every handshake
+pub(crate) struct Acceptor {
+    acceptor: RwLock<TlsAcceptor>,
+    timeout: Duration,
+    clock: SharedClock,
+    metrics: Arc<HandshakeMetrics>,
+}
+
+impl Acceptor {
+    pub fn new(
+        config: Arc<ServerConfig>,
+        timeout: Duration,
+        clock: SharedClock,
+        metrics: Arc<HandshakeMetrics>,
+    ) -> Self {
+        Self {
+            acceptor: RwLock::new(TlsAcceptor::from(config)),
+            timeout,
+            clock,
+            metrics,
+        }
+    }
+
+    /// Handshakes from then on use `config`
+    pub fn set_config(&self, config: Arc<ServerConfig>) {
+        *self.acceptor.write().unwrap() = TlsAcceptor::from(config);
+    }
+
+    pub async fn accept<S>(&self, stream: S) -> Result<TlsStream<S>, HandshakeFailure>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let acceptor = self.acceptor.read().unwrap().clone();
+        let accept = acceptor.accept(stream);
+        let cause = match clock::timeout(&*self.clock, self.timeout, accept).await {
+            Ok(Ok(stream)) => {
+                self.metrics.completed.fetch_add(1, Ordering::Relaxed);
+                return Ok(stream);
+            }
+            Ok(Err(err)) => HandshakeFailure::from_io(&err),
+            Err(_) => HandshakeFailure::TimedOut,
+        };
+        self.metrics.counter(cause).fetch_add(1, Ordering::Relaxed);
+        Err(cause)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio::io::AsyncWriteExt;
+    use tokio_rustls::{
+        TlsConnector,
+        rustls::{
+            ClientConfig, RootCertStore,
+            crypto::ring,
+            pki_types::ServerName,
+            server::{ClientHello, ResolvesServerCert},
+            sign::CertifiedKey,
+        },
+    };
+
+    use super::*;
+    use crate::clock::{MockClock, TokioClock};
+
+    #[derive(Debug)]
+    struct NoCertificate;
+
+    impl ResolvesServerCert for NoCertificate {
+        fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+            None
+        }
+    }
+
+    fn acceptor(timeout: Duration, clock: SharedClock) -> Acceptor {
+        let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(NoCertificate));
+        Acceptor::new(Arc::new(config), timeout, clock, Arc::default())
+    }
+
+    #[tokio::test]
+    async fn handshake_timeout() {
+        let clock = Arc::new(MockClock::new());
+        let acceptor = acceptor(Duration::from_secs(10), clock.clone());
+        let (_client, server) = tokio::io::duplex(1024);
+        let (res, ()) = tokio::join!(acceptor.accept(server), async {
+            clock.advance(Duration::from_secs(10))
+        });
+        assert_eq!(res.err(), Some(HandshakeFailure::TimedOut));
+        assert_eq!(acceptor.metrics.failures(HandshakeFailure::TimedOut), 1);
+    }
+
+    #[tokio::test]
+    async fn not_tls() {
+        let acceptor = acceptor(Duration::from_secs(5), TokioClock::shared());
+        let (mut client, server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            acceptor.accept(server).await.err(),
+            Some(HandshakeFailure::Other)
+        );
+        assert_eq!(acceptor.metrics.failures(HandshakeFailure::Other), 1);
+        assert_eq!(acceptor.metrics.completed(), 0);
+    }
+
+    #[tokio::test]
+    async fn reload_certificates() {
+        let testdata = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/client/testdata");
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(testdata.join("ca.pem")).unwrap())
+            .unwrap();
+        let client_config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let handshake = async |acceptor: &Acceptor| {
+            let (client, server) = tokio::io::duplex(16 * 1024);
+            let name = ServerName::try_from("localhost").unwrap();
+            let (accepted, _) =
+                tokio::join!(acceptor.accept(server), connector.connect(name, client));
+            accepted.is_ok()
+        };
+
+        let acceptor = acceptor(Duration::from_secs(5), TokioClock::shared());
+        assert!(!handshake(&acceptor).await);
+        let files = CertificateFiles::new(
+            testdata.join("localhost.pem"),
+            testdata.join("localhost.key"),
+        );
+        acceptor.set_config(files.load().unwrap());
+        assert!(handshake(&acceptor).await);
+
+        let missing = CertificateFiles::new(testdata.join("missing.pem"), files.private_key);
+        assert!(matches!(missing.load(), Err(CertificateError::Read { .. })));
+    }
+}
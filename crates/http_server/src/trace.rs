@@ -0,0 +1,57 @@
+//! Answering TRACE, which echoes the request back so a client can see what reached the server
+//!
+//! TRACE can reveal fields added on the way to the server, so it is only answered when
+//! [`crate::HttpServerConfig::enable_trace`] is set, and is otherwise rejected before routing
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::{
+    http::{
+        header::{Allow, Builtin, ContentType, HeaderName},
+        request::Request,
+        response::{Response, ResponseBuilder, StatusCode},
+    },
+    options::AllowedMethods,
+};
+
+/// Fields which carry credentials, which are left out of the echo
+/// SPEC: RFC 9110 - 9.3.8. TRACE
+const SENSITIVE: [Builtin; 3] = [
+    Builtin::Authorization,
+    Builtin::ProxyAuthorization,
+    Builtin::Cookie,
+];
+
+/// Responds with the request line and fields of `request` as a `message/http` body
+pub(crate) fn echo(request: &Request) -> Response {
+    let mut message = BytesMut::new();
+    message.put_slice(b"TRACE ");
+    message.put_slice(&request.target);
+    message.put_slice(format!(" {}\r\n", request.version).as_bytes());
+    for (name, value) in request.headers.iter() {
+        if SENSITIVE
+            .iter()
+            .any(|sensitive| *name == HeaderName::builtin(*sensitive))
+        {
+            continue;
+        }
+        for line in value.iter() {
+            message.put_slice(name.as_bytes());
+            message.put_slice(b": ");
+            message.put_slice(line);
+            message.put_slice(b"\r\n");
+        }
+    }
+    message.put_slice(b"\r\n");
+    ResponseBuilder::from_req(request, StatusCode::OK)
+        .set_header::<ContentType>(Bytes::from_static(b"message/http"))
+        .body(message.freeze())
+        .build()
+}
+
+/// Responds with 405 Method Not Allowed, and the methods the server allows otherwise
+pub(crate) fn reject(request: &Request) -> Response {
+    ResponseBuilder::from_req(request, StatusCode::METHOD_NOT_ALLOWED)
+        .set_header::<Allow>(AllowedMethods::default().allow())
+        .build()
+}
@@ -0,0 +1,795 @@
+//! Accepting, reading and writing TCP connections through io_uring on Linux
+//!
+//! One ring is shared by the process. Tasks submit their operations to it directly, and a
+//! thread waits on the ring and wakes the tasks whose operations completed, so a read on an idle
+//! connection costs one system call instead of a failed read, a readiness wait and another read
+//! Buffers belong to their operation until the kernel is done with them, an operation which is
+//! no longer waited on is cancelled and its buffer freed once the cancellation completes
+
+use std::{
+    cell::UnsafeCell,
+    io,
+    mem::{self, size_of},
+    net::SocketAddr,
+    os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    pin::Pin,
+    ptr,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU32, Ordering},
+    },
+    task::{Context, Poll, Waker, ready},
+};
+
+use bytes::{Buf, Bytes};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// SPEC: io_uring_setup(2), io_uring_enter(2), the layouts are those of linux/io_uring.h
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+const IORING_ENTER_GETEVENTS: u32 = 1 << 0;
+const IORING_OP_ACCEPT: u8 = 13;
+const IORING_OP_ASYNC_CANCEL: u8 = 14;
+const IORING_OP_SEND: u8 = 26;
+const IORING_OP_RECV: u8 = 27;
+
+/// Entries in the submission queue, the kernel makes the completion queue twice as large and
+/// keeps completions which don't fit until there is room
+const ENTRIES: u32 = 1024;
+/// The most bytes a read asks for
+const READ_SIZE: usize = 16 * 1024;
+/// The most bytes a write sends
+const WRITE_SIZE: usize = 64 * 1024;
+/// An address written by accept, followed by its length
+const ADDR_SIZE: usize = size_of::<libc::sockaddr_storage>();
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+/// A submission queue entry
+#[repr(C)]
+#[derive(Default)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    op_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+/// A completion queue entry
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+static_assertions::assert_eq_size!(Params, [u8; 120]);
+static_assertions::assert_eq_size!(Sqe, [u8; 64]);
+static_assertions::assert_eq_size!(Cqe, [u8; 16]);
+
+/// Part of the ring mapped into the process, unmapped on drop
+struct Mmap {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(ring: &OwnedFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        // SAFETY: a new shared mapping, which nothing else aliases
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                ring.as_raw_fd(),
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+
+    /// SAFETY: `offset` is one the kernel gave for a `T` in this mapping
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: mapped in new, and no pointer into it outlives the queue which owns it
+        unsafe { libc::munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+struct Submissions {
+    _ring: Mmap,
+    sqes: Mmap,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    entries: u32,
+    array: *mut u32,
+}
+
+struct Completions {
+    _ring: Mmap,
+    head: *const AtomicU32,
+    tail: *const AtomicU32,
+    mask: u32,
+    cqes: *const Cqe,
+}
+
+// SAFETY: the pointers are into mappings owned by the queues, the submission queue is behind a
+// lock and the completion queue is only used by the thread which waits on it
+unsafe impl Send for Submissions {}
+unsafe impl Send for Completions {}
+
+impl Completions {
+    /// Completes every operation the kernel finished
+    fn drain(&self) {
+        // SAFETY: the head is only written here, and the kernel fills entries up to the tail
+        // before publishing it
+        unsafe {
+            let mut head = (*self.head).load(Ordering::Relaxed);
+            let tail = (*self.tail).load(Ordering::Acquire);
+            while head != tail {
+                let cqe = &*self.cqes.add((head & self.mask) as usize);
+                let (user_data, res) = (cqe.user_data, cqe.res);
+                head = head.wrapping_add(1);
+                (*self.head).store(head, Ordering::Release);
+                Op::complete(user_data, res);
+            }
+        }
+    }
+}
+
+/// The ring shared by the process
+pub(crate) struct Driver {
+    ring: OwnedFd,
+    sq: Mutex<Submissions>,
+}
+
+impl Driver {
+    /// The ring, which is set up on first use, fails if the kernel doesn't offer io_uring
+    pub(crate) fn get() -> io::Result<&'static Self> {
+        static DRIVER: OnceLock<Result<Driver, (io::ErrorKind, String)>> = OnceLock::new();
+        match DRIVER.get_or_init(|| Self::start().map_err(|err| (err.kind(), err.to_string()))) {
+            Ok(driver) => Ok(driver),
+            Err((kind, msg)) => Err(io::Error::new(*kind, format!("io_uring: {msg}"))),
+        }
+    }
+
+    fn start() -> io::Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: params is a valid io_uring_params for the kernel to fill
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, ENTRIES, &mut params) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the descriptor was just created, and is owned here
+        let ring = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        let (sq_off, cq_off) = (&params.sq_off, &params.cq_off);
+        let sq_len = sq_off.array as usize + params.sq_entries as usize * size_of::<u32>();
+        let cq_len = cq_off.cqes as usize + params.cq_entries as usize * size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * size_of::<Sqe>();
+        let sq_ring = Mmap::new(&ring, sq_len, IORING_OFF_SQ_RING)?;
+        let cq_ring = Mmap::new(&ring, cq_len, IORING_OFF_CQ_RING)?;
+        let sqes = Mmap::new(&ring, sqes_len, IORING_OFF_SQES)?;
+        // SAFETY: the offsets are the ones the kernel gave for these fields
+        let (sq, cq) = unsafe {
+            let sq = Submissions {
+                head: sq_ring.at(sq_off.head),
+                tail: sq_ring.at(sq_off.tail),
+                mask: *sq_ring.at::<u32>(sq_off.ring_mask),
+                entries: *sq_ring.at::<u32>(sq_off.ring_entries),
+                array: sq_ring.at(sq_off.array),
+                _ring: sq_ring,
+                sqes,
+            };
+            let cq = Completions {
+                head: cq_ring.at(cq_off.head),
+                tail: cq_ring.at(cq_off.tail),
+                mask: *cq_ring.at::<u32>(cq_off.ring_mask),
+                cqes: cq_ring.at(cq_off.cqes),
+                _ring: cq_ring,
+            };
+            (sq, cq)
+        };
+
+        // The ring lives as long as the process, so the thread can keep its descriptor
+        let fd = ring.as_raw_fd();
+        std::thread::Builder::new()
+            .name("carbon-io-uring".into())
+            .spawn(move || Self::run(fd, cq))?;
+        Ok(Self {
+            ring,
+            sq: Mutex::new(sq),
+        })
+    }
+
+    /// Waits for completions, and wakes the tasks waiting on them
+    fn run(ring: RawFd, cq: Completions) {
+        loop {
+            // SAFETY: only waits, nothing is submitted
+            let res = unsafe {
+                libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    ring,
+                    0,
+                    1,
+                    IORING_ENTER_GETEVENTS,
+                    ptr::null::<libc::sigset_t>(),
+                    0,
+                )
+            };
+            if res < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    log::error!("failed to wait for io_uring completions: {err}");
+                    return;
+                }
+            }
+            cq.drain();
+        }
+    }
+
+    /// Submits `op`, with the entry filled in by `fill`
+    fn submit(&self, op: &Arc<Op>, fill: impl FnOnce(&mut Sqe)) -> io::Result<()> {
+        let sq = self.sq.lock().unwrap();
+        let user_data = Arc::into_raw(op.clone());
+        // SAFETY: the queue is locked, and the kernel consumed every earlier entry when they were
+        // submitted, so the slot at the tail is free
+        unsafe {
+            let tail = (*sq.tail).load(Ordering::Relaxed);
+            debug_assert!(tail.wrapping_sub((*sq.head).load(Ordering::Acquire)) < sq.entries);
+            let idx = tail & sq.mask;
+            let sqe = &mut *sq.sqes.at::<Sqe>(0).add(idx as usize);
+            *sqe = Sqe {
+                user_data: user_data as u64,
+                ..Sqe::default()
+            };
+            fill(sqe);
+            *sq.array.add(idx as usize) = idx;
+            (*sq.tail).store(tail.wrapping_add(1), Ordering::Release);
+            loop {
+                let res = libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    self.ring.as_raw_fd(),
+                    1,
+                    0,
+                    0,
+                    ptr::null::<libc::sigset_t>(),
+                    0,
+                );
+                if res >= 0 {
+                    return Ok(());
+                }
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EINTR) => {}
+                    // Completions which didn't fit are waiting for the completion thread
+                    Some(libc::EBUSY | libc::EAGAIN) => std::thread::yield_now(),
+                    _ => {
+                        // Nothing was submitted, so the entry is taken back
+                        (*sq.tail).store(tail, Ordering::Release);
+                        drop(Arc::from_raw(user_data));
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Asks the kernel to cancel `op`, which completes it with ECANCELED if it was still waiting
+    fn cancel(&self, op: &Arc<Op>) {
+        let cancel = Op::new(Vec::new(), false);
+        cancel.state.lock().unwrap().abandoned = true;
+        let target = Arc::as_ptr(op) as u64;
+        let submitted = self.submit(&cancel, |sqe| {
+            sqe.opcode = IORING_OP_ASYNC_CANCEL;
+            sqe.fd = -1;
+            sqe.addr = target;
+        });
+        if let Err(err) = submitted {
+            log::warn!("failed to cancel an io_uring operation: {err}");
+        }
+    }
+}
+
+/// An operation submitted to the ring, which owns its buffer until the kernel completes it
+struct Op {
+    state: Mutex<OpState>,
+    /// Only used before the operation is submitted, and after it completed
+    buf: UnsafeCell<Vec<u8>>,
+    /// The result is a new descriptor, which is closed if nobody takes it
+    returns_fd: bool,
+}
+
+#[derive(Default)]
+struct OpState {
+    result: Option<i32>,
+    waker: Option<Waker>,
+    /// Nobody waits for the result anymore
+    abandoned: bool,
+}
+
+// SAFETY: the buffer is only touched by whoever submitted the operation, while the kernel isn't
+// using it
+unsafe impl Send for Op {}
+unsafe impl Sync for Op {}
+
+impl Op {
+    fn new(buf: Vec<u8>, returns_fd: bool) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::default(),
+            buf: UnsafeCell::new(buf),
+            returns_fd,
+        })
+    }
+
+    /// The address of the buffer the kernel reads from or writes to
+    fn buf_addr(&self) -> u64 {
+        // SAFETY: the buffer isn't borrowed elsewhere before the operation is submitted
+        unsafe { (*self.buf.get()).as_mut_ptr() as u64 }
+    }
+
+    /// SAFETY: the operation completed
+    unsafe fn take_buf(&self) -> Vec<u8> {
+        unsafe { mem::take(&mut *self.buf.get()) }
+    }
+
+    fn complete(user_data: u64, res: i32) {
+        // SAFETY: the entry was submitted with a reference from Arc::into_raw, and every
+        // operation completes once
+        let op = unsafe { Arc::from_raw(user_data as *const Op) };
+        let mut state = op.state.lock().unwrap();
+        if state.abandoned {
+            op.close_result(res);
+            return;
+        }
+        state.result = Some(res);
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn poll(&self, cx: &mut Context<'_>) -> Poll<i32> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(res) => Poll::Ready(res),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// Stops waiting for the operation, which is cancelled unless it already completed
+    fn abandon(self: &Arc<Self>, driver: &Driver) {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(res) => self.close_result(res),
+            None => {
+                state.abandoned = true;
+                drop(state);
+                driver.cancel(self);
+            }
+        }
+    }
+
+    fn close_result(&self, res: i32) {
+        if self.returns_fd && res >= 0 {
+            // SAFETY: the kernel gave this descriptor to the operation, which nobody took
+            drop(unsafe { OwnedFd::from_raw_fd(res) });
+        }
+    }
+}
+
+/// The number of bytes, or the error, of a result
+fn check(res: i32) -> io::Result<usize> {
+    if res < 0 {
+        return Err(io::Error::from_raw_os_error(-res));
+    }
+    Ok(res as usize)
+}
+
+/// Abandons an operation if the future waiting on it is dropped
+struct Pending<'a> {
+    op: Option<Arc<Op>>,
+    driver: &'a Driver,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if let Some(op) = self.op.take() {
+            op.abandon(self.driver);
+        }
+    }
+}
+
+/// A TCP listener which accepts through the ring
+pub(crate) struct UringListener {
+    listener: std::net::TcpListener,
+    driver: &'static Driver,
+}
+
+impl UringListener {
+    pub(crate) fn new(listener: std::net::TcpListener) -> io::Result<Self> {
+        let driver = Driver::get()?;
+        // The kernel waits for connections itself, on non-blocking sockets it would fail instead
+        listener.set_nonblocking(false)?;
+        Ok(Self { listener, driver })
+    }
+
+    pub(crate) async fn accept(&self) -> io::Result<(UringStream, SocketAddr)> {
+        let mut buf = vec![0; ADDR_SIZE + size_of::<libc::socklen_t>()];
+        buf[ADDR_SIZE..].copy_from_slice(&(ADDR_SIZE as libc::socklen_t).to_ne_bytes());
+        let op = Op::new(buf, true);
+        let addr = op.buf_addr();
+        let fd = self.listener.as_raw_fd();
+        self.driver.submit(&op, |sqe| {
+            sqe.opcode = IORING_OP_ACCEPT;
+            sqe.fd = fd;
+            sqe.addr = addr;
+            // The length follows the address in the buffer
+            sqe.off = addr + ADDR_SIZE as u64;
+            sqe.op_flags = libc::SOCK_CLOEXEC as u32;
+        })?;
+        let mut pending = Pending {
+            op: Some(op),
+            driver: self.driver,
+        };
+        let res = std::future::poll_fn(|cx| pending.op.as_ref().unwrap().poll(cx)).await;
+        let op = pending.op.take().unwrap();
+        // SAFETY: the kernel returned a new descriptor
+        let fd = unsafe { OwnedFd::from_raw_fd(check(res)? as RawFd) };
+        // SAFETY: the operation completed, and the kernel wrote the address and its length
+        let addr = unsafe {
+            let buf = op.take_buf();
+            let storage = ptr::read_unaligned(buf.as_ptr().cast::<libc::sockaddr_storage>());
+            let len = ptr::read_unaligned(buf[ADDR_SIZE..].as_ptr().cast::<libc::socklen_t>());
+            socket2::SockAddr::new(storage, len)
+        };
+        let addr = addr
+            .as_socket()
+            .ok_or_else(|| io::Error::other("accepted a connection without an IP address"))?;
+        Ok((UringStream::new(fd, self.driver), addr))
+    }
+}
+
+/// A TCP connection read and written through the ring
+/// Like other ring based streams, a write which returned Pending is sent as it was first given,
+/// so it must be retried with the same bytes
+pub(crate) struct UringStream {
+    fd: OwnedFd,
+    driver: &'static Driver,
+    read: Option<Arc<Op>>,
+    /// Bytes read past the end of the buffer of the caller
+    unread: Bytes,
+    write: Option<Arc<Op>>,
+}
+
+impl UringStream {
+    fn new(fd: OwnedFd, driver: &'static Driver) -> Self {
+        Self {
+            fd,
+            driver,
+            read: None,
+            unread: Bytes::new(),
+            write: None,
+        }
+    }
+
+    pub(crate) fn local_addr(&self) -> io::Result<SocketAddr> {
+        socket2::SockRef::from(self)
+            .local_addr()?
+            .as_socket()
+            .ok_or_else(|| io::Error::other("the socket has no IP address"))
+    }
+
+    /// Writes what the socket can take without waiting
+    pub(crate) fn try_write(&self, buf: &[u8]) -> io::Result<usize> {
+        // SAFETY: buf is valid for its length
+        let res = unsafe {
+            libc::send(
+                self.fd.as_raw_fd(),
+                buf.as_ptr().cast(),
+                buf.len(),
+                libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(res as usize)
+    }
+
+    /// Waits for the write in flight, if any
+    fn poll_written(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+        let Some(op) = &self.write else {
+            return Poll::Ready(Ok(0));
+        };
+        let res = ready!(op.poll(cx));
+        self.write = None;
+        Poll::Ready(check(res))
+    }
+}
+
+impl AsFd for UringStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsyncRead for UringStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        if this.unread.is_empty() {
+            let op = match &this.read {
+                Some(op) => op,
+                None => {
+                    let op = Op::new(Vec::with_capacity(READ_SIZE), false);
+                    let (fd, addr) = (this.fd.as_raw_fd(), op.buf_addr());
+                    this.driver.submit(&op, |sqe| {
+                        sqe.opcode = IORING_OP_RECV;
+                        sqe.fd = fd;
+                        sqe.addr = addr;
+                        sqe.len = READ_SIZE as u32;
+                    })?;
+                    this.read.insert(op)
+                }
+            };
+            let res = ready!(op.poll(cx));
+            let op = this.read.take().unwrap();
+            let n = check(res)?;
+            // SAFETY: the read completed, and the kernel wrote n bytes to the buffer
+            let mut data = unsafe { op.take_buf() };
+            unsafe { data.set_len(n) };
+            // An empty read is the end of the stream
+            this.unread = data.into();
+        }
+        let n = this.unread.len().min(buf.remaining());
+        buf.put_slice(&this.unread[..n]);
+        this.unread.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for UringStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.write.is_none() {
+            if buf.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            let len = buf.len().min(WRITE_SIZE);
+            let op = Op::new(buf[..len].to_vec(), false);
+            let (fd, addr) = (this.fd.as_raw_fd(), op.buf_addr());
+            this.driver.submit(&op, |sqe| {
+                sqe.opcode = IORING_OP_SEND;
+                sqe.fd = fd;
+                sqe.addr = addr;
+                sqe.len = len as u32;
+                sqe.op_flags = libc::MSG_NOSIGNAL as u32;
+            })?;
+            this.write = Some(op);
+        }
+        this.poll_written(cx)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Sent bytes are with the kernel, only a write still in flight is waited for
+        ready!(self.get_mut().poll_written(cx))?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        // SAFETY: shuts down the socket owned by the stream
+        if unsafe { libc::shutdown(self.fd.as_raw_fd(), libc::SHUT_WR) } < 0 {
+            return Poll::Ready(Err(io::Error::last_os_error()));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for UringStream {
+    fn drop(&mut self) {
+        // An operation in flight keeps the socket open, so it is cancelled for the socket to
+        // close along with the descriptor
+        for op in [self.read.take(), self.write.take()].into_iter().flatten() {
+            op.abandon(self.driver);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::{
+        HttpServer, HttpServerConfig, Router, RouterError,
+        http::{
+            request::Request,
+            response::{Response, ResponseBuilder},
+        },
+        listener::TcpOptions,
+        shutdown::ShutdownReason,
+    };
+
+    fn listener() -> (UringListener, SocketAddr) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        (UringListener::new(listener).unwrap(), addr)
+    }
+
+    #[tokio::test]
+    async fn echo() {
+        let (listener, local) = listener();
+        let mut client = tokio::net::TcpStream::connect(local).await.unwrap();
+        let (mut stream, addr) = listener.accept().await.unwrap();
+        assert_eq!(addr, client.local_addr().unwrap());
+        assert_eq!(stream.local_addr().unwrap(), local);
+
+        // Larger than a single read or write
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let (_, received) = tokio::join!(async { client.write_all(&data).await.unwrap() }, async {
+            let mut received = vec![0; data.len()];
+            stream.read_exact(&mut received).await.unwrap();
+            received
+        });
+        assert_eq!(received, data);
+
+        stream.write_all(b"pong").await.unwrap();
+        stream.shutdown().await.unwrap();
+        let mut output = Vec::new();
+        client.read_to_end(&mut output).await.unwrap();
+        assert_eq!(output, b"pong");
+
+        drop(client);
+        let mut buf = [0; 8];
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn abandoned_operations() {
+        let (listener, addr) = listener();
+        // An accept which is no longer waited on doesn't take the next connection
+        let accept = listener.accept();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), accept)
+                .await
+                .is_err()
+        );
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        // Neither does a read keep the connection open
+        let mut buf = [0; 8];
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), stream.read(&mut buf))
+                .await
+                .is_err()
+        );
+        drop(stream);
+        let read = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf)).await;
+        assert_eq!(read.unwrap().unwrap(), 0);
+    }
+
+    struct Hello;
+
+    impl Router for Hello {
+        async fn route(&self, _request: &Request) -> Result<Response, RouterError> {
+            Ok(ResponseBuilder::text("hello").build())
+        }
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = HttpServerConfig {
+            tcp: TcpOptions {
+                io_uring: true,
+                nodelay: true,
+                ..TcpOptions::default()
+            },
+            ..HttpServerConfig::default()
+        };
+        let server = HttpServer::from_listener_with_config(listener, Hello, config);
+        let shutdown = server.shutdown_handle();
+        let serve = tokio::spawn(async move { server.serve().await });
+
+        let mut client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        for _ in 0..2 {
+            client
+                .write_all(b"GET / HTTP/1.1\r\nHost: a\r\n\r\n")
+                .await
+                .unwrap();
+            let mut buf = [0; 1024];
+            let n = client.read(&mut buf).await.unwrap();
+            let output = std::str::from_utf8(&buf[..n]).unwrap();
+            assert!(output.starts_with("HTTP/1.1 200 OK\r\n"), "{output}");
+            assert!(output.ends_with("\r\n\r\nhello"), "{output}");
+        }
+
+        shutdown.shutdown(ShutdownReason::Requested);
+        serve.await.unwrap().unwrap();
+    }
+}
@@ -35,6 +35,7 @@ pub fn bytes_are_ascii(bytes: &[u8]) -> Result<(), InvalidAsciiError> {
 }
 
 impl AsciiString {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<AsciiString, InvalidAsciiError> {
         Self::from_ascii(s.as_bytes())
     }
@@ -51,10 +52,14 @@ impl AsciiString {
         Ok(unsafe { Self::from_bytes_unchecked(bytes) })
     }
 
+    /// # Safety
+    /// All bytes must be valid ascii
     pub unsafe fn from_bytes_unchecked(bytes: Vec<u8>) -> AsciiString {
         Self { bytes }
     }
 
+    /// # Safety
+    /// All bytes must be valid ascii
     pub unsafe fn from_ascii_unchecked(bytes: &[u8]) -> AsciiString {
         Self {
             bytes: Vec::from(bytes),
@@ -85,10 +90,13 @@ impl fmt::Display for AsciiStr {
 }
 
 impl AsciiStr {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<&AsciiStr, InvalidAsciiError> {
         Self::from_ascii(s.as_bytes())
     }
 
+    /// # Safety
+    /// The string must be valid ascii
     pub const unsafe fn from_str_unchecked(s: &str) -> &AsciiStr {
         unsafe { Self::from_ascii_unchecked(s.as_bytes()) }
     }
@@ -98,6 +106,8 @@ impl AsciiStr {
         Ok(unsafe { Self::from_ascii_unchecked(bytes) })
     }
 
+    /// # Safety
+    /// All bytes must be valid ascii
     pub const unsafe fn from_ascii_unchecked(bytes: &[u8]) -> &AsciiStr {
         unsafe { std::mem::transmute(bytes) }
     }
@@ -0,0 +1,258 @@
+use std::{
+    collections::{HashMap, hash_map},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+use bytes::Bytes;
+
+use crate::ascii::{InvalidAsciiError, bytes_are_ascii};
+
+/// The name of a header field, names are compared case insensitively
+/// SPEC: RFC 9110 - 5.1 Field Names
+/// ABNF: field-name = token
+#[derive(Clone)]
+pub struct HeaderType {
+    name: Bytes,
+}
+
+impl HeaderType {
+    /// Panics if the name is not valid ascii
+    pub const fn from_static(name: &'static str) -> Self {
+        let bytes = name.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            assert!(bytes[i] < 0x80, "header name must be ascii");
+            i += 1;
+        }
+        Self {
+            name: Bytes::from_static(bytes),
+        }
+    }
+
+    pub fn from_bytes(name: Bytes) -> Result<Self, InvalidAsciiError> {
+        bytes_are_ascii(&name)?;
+        // SAFETY: We checked that all bytes are valid
+        Ok(unsafe { Self::from_bytes_unchecked(name) })
+    }
+
+    /// # Safety
+    /// All bytes must be valid ascii
+    pub unsafe fn from_bytes_unchecked(name: Bytes) -> Self {
+        Self { name }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // SAFETY: valid ascii is valid UTF-8
+        unsafe { std::str::from_utf8_unchecked(&self.name) }
+    }
+
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.name
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.name
+    }
+}
+
+impl PartialEq for HeaderType {
+    fn eq(&self, other: &Self) -> bool {
+        self.name.eq_ignore_ascii_case(&other.name)
+    }
+}
+
+impl Eq for HeaderType {}
+
+impl Hash for HeaderType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.name.len());
+        for b in self.name.iter() {
+            state.write_u8(b.to_ascii_lowercase());
+        }
+    }
+}
+
+impl fmt::Debug for HeaderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "\"{}\"", self.as_str())
+    }
+}
+
+impl fmt::Display for HeaderType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The header only allows a single field line
+    Duplicate,
+    /// The field value is not valid for the header
+    Invalid,
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Duplicate => "duplicate header",
+            Self::Invalid => "invalid header value",
+        })
+    }
+}
+
+impl std::error::Error for HeaderError {}
+
+/// A typed header field, the facade equivalent of a backend's header field trait
+pub trait Header {
+    const NAME: HeaderType;
+    type Value: HeaderValue;
+}
+
+/// A value which can be read from, and written to the field lines of a header
+pub trait HeaderValue: Sized {
+    fn decode(values: &[Bytes]) -> Result<Self, HeaderError>;
+    fn encode(self, values: &mut Vec<Bytes>);
+}
+
+fn single(values: &[Bytes]) -> Result<&Bytes, HeaderError> {
+    match values {
+        [value] => Ok(value),
+        _ => Err(HeaderError::Duplicate),
+    }
+}
+
+impl HeaderValue for Bytes {
+    fn decode(values: &[Bytes]) -> Result<Self, HeaderError> {
+        single(values).cloned()
+    }
+
+    fn encode(self, values: &mut Vec<Bytes>) {
+        values.push(self);
+    }
+}
+
+/// A comma separated list, which may be split across multiple field lines
+/// SPEC: RFC 9110 - 5.6.1. Lists (#rule ABNF Extension)
+impl HeaderValue for Vec<Bytes> {
+    fn decode(values: &[Bytes]) -> Result<Self, HeaderError> {
+        Ok(values
+            .iter()
+            .flat_map(|value| {
+                value
+                    .split(|&b| b == b',')
+                    .map(|elem| value.slice_ref(elem.trim_ascii()))
+            })
+            .filter(|elem| !elem.is_empty())
+            .collect())
+    }
+
+    fn encode(self, values: &mut Vec<Bytes>) {
+        values.extend(self);
+    }
+}
+
+impl HeaderValue for u64 {
+    fn decode(values: &[Bytes]) -> Result<Self, HeaderError> {
+        std::str::from_utf8(single(values)?)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(HeaderError::Invalid)
+    }
+
+    fn encode(self, values: &mut Vec<Bytes>) {
+        values.push(Bytes::from(self.to_string()));
+    }
+}
+
+macro_rules! header_struct {
+    ($name: ident, $str: literal, $ty: ty) => {
+        pub struct $name;
+
+        impl Header for $name {
+            const NAME: HeaderType = HeaderType::from_static($str);
+            type Value = $ty;
+        }
+    };
+}
+
+header_struct!(Host, "Host", Bytes);
+header_struct!(ContentLength, "Content-Length", u64);
+header_struct!(ContentType, "Content-Type", Bytes);
+header_struct!(ContentEncoding, "Content-Encoding", Vec<Bytes>);
+header_struct!(AcceptEncoding, "Accept-Encoding", Vec<Bytes>);
+header_struct!(Location, "Location", Bytes);
+header_struct!(Server, "Server", Bytes);
+header_struct!(UserAgent, "User-Agent", Bytes);
+header_struct!(Vary, "Vary", Vec<Bytes>);
+
+/// A backend independent header map, each header keeps its field lines in order
+/// Backends convert their own header maps to and from this without copying the values
+#[derive(Debug, Clone, Default)]
+pub struct HeaderMap {
+    map: HashMap<HeaderType, Vec<Bytes>>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(size: usize) -> Self {
+        Self {
+            map: HashMap::with_capacity(size),
+        }
+    }
+
+    pub fn entry(&mut self, name: HeaderType) -> &mut Vec<Bytes> {
+        self.map.entry(name).or_default()
+    }
+
+    pub fn contains(&self, name: &HeaderType) -> bool {
+        self.map.contains_key(name)
+    }
+
+    pub fn get(&self, name: &HeaderType) -> Option<&[Bytes]> {
+        self.map.get(name).map(Vec::as_slice)
+    }
+
+    pub fn remove(&mut self, name: &HeaderType) -> Option<Vec<Bytes>> {
+        self.map.remove(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn get_header<T: Header>(&self) -> Result<Option<T::Value>, HeaderError> {
+        self.map
+            .get(&T::NAME)
+            .map(|values| T::Value::decode(values))
+            .transpose()
+    }
+
+    /// Replaces every field line of the header with `value`
+    pub fn set_header<T: Header>(&mut self, value: T::Value) {
+        let values = self.entry(T::NAME);
+        values.clear();
+        value.encode(values);
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, HeaderType, Vec<Bytes>> {
+        self.map.iter()
+    }
+}
+
+impl IntoIterator for HeaderMap {
+    type Item = (HeaderType, Vec<Bytes>);
+    type IntoIter = hash_map::IntoIter<HeaderType, Vec<Bytes>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
@@ -0,0 +1,5 @@
+//! Version agnostic HTTP types, which backends convert to and from their own representation
+
+pub mod header;
+
+pub use header::{Header, HeaderError, HeaderMap, HeaderType};
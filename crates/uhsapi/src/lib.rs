@@ -6,3 +6,4 @@
 #![feature(bool_to_result)]
 
 pub mod ascii;
+pub mod http;